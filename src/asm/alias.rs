@@ -0,0 +1,124 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Operand aliases.
+//!
+//! The operand `name@expr` in an instruction stands for `expr` and binds
+//! `name` to it.  Until the end of the current scope, `name` in a later
+//! operand stands for the same expression.  Aliases usually give readable
+//! names to registers:
+//!
+//! ```text
+//! loop:   moveq   10, count@d0
+//!         subq    1, count
+//! ```
+//!
+//! A scope ends at the next non-local label or at the end of a `.block`.
+
+use crate::lang::ast::*;
+use crate::name::Name;
+
+use super::Assembler;
+
+// ----------------------------------------------------------------------------
+
+/// Operand alias.
+#[derive(Clone, Debug)]
+pub(super) struct Alias {
+    /// Name of the alias.
+    name: Name,
+
+    /// Expression for which the alias stands.
+    expr: Expr<Span>,
+
+    /// Register for which the alias stands, as identified by the target, if
+    /// the expression names one.
+    reg: Option<u32>,
+}
+
+impl Assembler<'_> {
+    /// Returns the given instruction statement with aliases replaced by the
+    /// expressions for which they stand, binding any new aliases.
+    pub(super) fn expand_aliases(&mut self, dir: &Dir<Span>) -> Dir<Span> {
+        let args = dir.args
+            .iter()
+            .map(|arg| match *arg {
+                Arg::Expr(ref e) => Arg::Expr(self.expand(e)),
+                Arg::Unknown(s)  => Arg::Unknown(s),
+            })
+            .collect();
+
+        Dir { name: dir.name, args, data: dir.data }
+    }
+
+    fn expand(&mut self, expr: &Expr<Span>) -> Expr<Span> {
+        use Expr::*;
+        match *expr {
+            Ident(_, name) => match self.aliases.iter().rfind(|a| a.name == name) {
+                Some(alias) => alias.expr.clone(),
+                None        => expr.clone(),
+            },
+            Alias(span, name, ref inner) => {
+                let inner = self.expand(inner);
+                self.bind(span, name, &inner);
+                inner
+            },
+            Deref(span, ref inner, post) => {
+                Deref(span, Box::new(self.expand(inner)), post)
+            },
+            Unary(span, op, ref inner) => {
+                Unary(span, op, Box::new(self.expand(inner)))
+            },
+            Binary(span, op, ref lhs, ref rhs) => {
+                let lhs = self.expand(lhs);
+                let rhs = self.expand(rhs);
+                Binary(span, op, Box::new(lhs), Box::new(rhs))
+            },
+            Call(span, ref func, ref args) => {
+                Call(span, func.clone(), args.iter().map(|a| self.expand(a)).collect())
+            },
+            _ => expr.clone(),
+        }
+    }
+
+    /// Binds the alias `name`, at the given `span`, to the given expression.
+    /// Warns if another alias stands for the same register, by any of its
+    /// names.
+    fn bind(&mut self, span: Span, name: Name, expr: &Expr<Span>) {
+        self.aliases.retain(|a| a.name != name);
+
+        let reg = match (&self.target, expr) {
+            (Some(target), &Expr::Ident(_, n)) => target.register(&self.session.names()[n]),
+            _                                  => None,
+        };
+
+        if let (true, Some(_)) = (self.last, reg) {
+            if let Some(other) = self.aliases.iter().find(|a| a.reg == reg) {
+                let names = self.session.names();
+                let msg   = format!(
+                    "aliases '{}' and '{}' both stand for the same register",
+                    &names[other.name], &names[name]
+                );
+                let loc = self.loc(span);
+                self.session.warning(loc, msg);
+            }
+        }
+
+        self.aliases.push(Alias { name, expr: expr.clone(), reg });
+    }
+}
@@ -0,0 +1,232 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Expression evaluation.
+
+use crate::lang::ast::*;
+use crate::name::Name;
+
+use super::Value;
+
+/// Message for an operator other than `+` or `-` on a relocatable value.
+const RELOC_OPERAND: &str = "operator is not valid on relocatable values";
+
+/// Message for a sum or difference that the linker cannot compute.
+const NOT_RELOC: &str = "expression cannot be expressed as a relocation";
+
+// ----------------------------------------------------------------------------
+
+/// Trait for contexts in which expressions are evaluated.
+pub trait Context {
+    /// Returns the value of the symbol with the given `name`, referenced at
+    /// the given `span`, or `None` if the symbol has no value.
+    fn symbol(&mut self, span: Span, name: Name) -> Option<Value>;
+
+    /// Returns the value in memory at the given `addr`, referenced at the
+    /// given `span`, or `None` if the context has no memory.
+    fn deref(&mut self, span: Span, addr: i64) -> Option<i64> {
+        let _ = addr;
+        self.error(span, "expected: constant expression");
+        None
+    }
+
+    /// Looks up the builtin function with the given `name`.  Returns `None`
+    /// if the context has no such function.
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        let _ = name;
+        None
+    }
+
+    /// Reports an error at the given `span`.
+    fn error(&mut self, span: Span, msg: &str);
+}
+
+/// Evaluates the given expression in the given context.
+///
+/// Returns `None` if the expression has no value.  The context reports an
+/// error in that case.  The value is constant unless it depends on a symbol
+/// for which the context returns a relocatable value.
+pub fn eval<C: Context + ?Sized>(cx: &mut C, expr: &Expr<Span>) -> Option<Value> {
+    use Expr::*;
+    match *expr {
+        Ident  (span, name)                 => cx.symbol(span, name),
+        Int    (_,    value)                => Some(Value::Const(value as i64)),
+        Char   (_,    value)                => Some(Value::Const(value as i64)),
+        Unary  (span, op, ref expr)         => eval_unary(cx, span, op, expr),
+        Binary (span, op, ref lhs, ref rhs) => eval_binary(cx, span, op, lhs, rhs),
+        Call   (span, ref func, ref args)   => eval_call(cx, span, func, args),
+        Deref  (span, ref addr, false)      => {
+            let addr = eval(cx, addr)?.provisional();
+            cx.deref(span, addr).map(Value::Const)
+        },
+        Alias  (span, ..)                   => {
+            cx.error(span, "alias is valid only as an instruction operand");
+            None
+        },
+        Block  (ref block)                  => {
+            cx.error(block.data, "block is valid only as the argument of .block");
+            None
+        },
+        _ => {
+            cx.error(*expr.data(), "expected: constant expression");
+            None
+        },
+    }
+}
+
+fn eval_unary<C: Context + ?Sized>(cx: &mut C, span: Span, op: UnOp, expr: &Expr<Span>)
+    -> Option<Value>
+{
+    use UnOp::*;
+
+    // A target interprets relocation operators like %hi(x) in its operands
+    if let (UnsignedH, Expr::Call(..)) = (op, expr) {
+        cx.error(span, "relocation operator is valid only as an instruction operand");
+        return None;
+    }
+
+    let value = eval(cx, expr)?;
+
+    let v = match (op, value) {
+        (SignedH | UnsignedH | SignedL | UnsignedL, _) => return Some(value),
+        (PostInc | PostDec | PreInc | PreDec,       _) => {
+            cx.error(span, "expected: constant expression");
+            return None;
+        },
+        (Literal, _) => {
+            cx.error(span, "literal is valid only as an instruction operand");
+            return None;
+        },
+        (Neg, _) => {
+            return value.neg().or_else(|| { cx.error(span, NOT_RELOC); None });
+        },
+        (_, Value::Const(v)) => v,
+        (_, Value::Reloc(_)) => {
+            cx.error(span, RELOC_OPERAND);
+            return None;
+        },
+    };
+
+    Some(Value::Const(match op {
+        BitNot => !v,
+        _      => (v == 0) as i64,
+    }))
+}
+
+fn eval_binary<C: Context + ?Sized>(
+    cx:   &mut C,
+    span: Span,
+    op:   BinOp,
+    lhs:  &Expr<Span>,
+    rhs:  &Expr<Span>,
+) -> Option<Value> {
+    use BinOp::*;
+
+    let unsigned = is_unsigned(lhs) || is_unsigned(rhs);
+    let lhs      = eval(cx, lhs)?;
+    let rhs      = eval(cx, rhs)?;
+
+    let (lhs, rhs) = match (op, lhs, rhs) {
+        (Add, _, _) => return lhs.add(rhs).or_else(|| { cx.error(span, NOT_RELOC); None }),
+        (Sub, _, _) => return lhs.sub(rhs).or_else(|| { cx.error(span, NOT_RELOC); None }),
+        (_, Value::Const(l), Value::Const(r)) => (l, r),
+        _ => {
+            cx.error(span, RELOC_OPERAND);
+            return None;
+        },
+    };
+
+    let (ul, ur) = (lhs as u64, rhs as u64);
+
+    Some(Value::Const(match op {
+        Mul                   => lhs.wrapping_mul(rhs),
+        Div | Mod if rhs == 0 => {
+            cx.error(span, "division by zero");
+            return None;
+        },
+        Div if unsigned       => (ul / ur) as i64,
+        Div                   => lhs.wrapping_div(rhs),
+        Mod if unsigned       => (ul % ur) as i64,
+        Mod                   => lhs.wrapping_rem(rhs),
+        Shl                   => ul.checked_shl(ur as u32).unwrap_or(0) as i64,
+        Shr if unsigned       => ul.checked_shr(ur as u32).unwrap_or(0) as i64,
+        Shr                   => lhs >> ur.min(63),
+        BitAnd                => lhs & rhs,
+        BitXor                => lhs ^ rhs,
+        BitOr                 => lhs | rhs,
+        Eq                    => (lhs == rhs) as i64,
+        NotEq                 => (lhs != rhs) as i64,
+        Less   if unsigned    => (ul <  ur) as i64,
+        More   if unsigned    => (ul >  ur) as i64,
+        LessEq if unsigned    => (ul <= ur) as i64,
+        MoreEq if unsigned    => (ul >= ur) as i64,
+        Less                  => (lhs <  rhs) as i64,
+        More                  => (lhs >  rhs) as i64,
+        LessEq                => (lhs <= rhs) as i64,
+        MoreEq                => (lhs >= rhs) as i64,
+        LogAnd                => (lhs != 0 && rhs != 0) as i64,
+        LogXor                => ((lhs != 0) ^ (rhs != 0)) as i64,
+        LogOr                 => (lhs != 0 || rhs != 0) as i64,
+        _                     => {
+            cx.error(span, "expected: constant expression");
+            return None;
+        },
+    }))
+}
+
+fn eval_call<C: Context + ?Sized>(
+    cx:   &mut C,
+    span: Span,
+    func: &Expr<Span>,
+    args: &[Expr<Span>],
+) -> Option<Value> {
+    let builtin = match *func {
+        Expr::Ident(_, name) => cx.builtin(name),
+        _                    => None,
+    };
+
+    let builtin = match builtin {
+        Some(builtin) => builtin,
+        None          => {
+            cx.error(*func.data(), "expected: builtin function");
+            return None;
+        },
+    };
+
+    let value = match args {
+        [arg] => eval(cx, arg)?,
+        _     => {
+            cx.error(span, "builtin function requires one argument");
+            return None;
+        },
+    };
+
+    // The linker computes the function of a relocatable value; the result
+    // here is a placeholder for layout.
+    let v = builtin(value.provisional());
+
+    Some(match value {
+        Value::Const(_) => Value::Const(v),
+        Value::Reloc(_) => Value::opaque(v),
+    })
+}
+
+/// Returns whether the given expression is explicitly unsigned.
+fn is_unsigned(expr: &Expr<Span>) -> bool {
+    matches!(expr, Expr::Unary(_, UnOp::UnsignedH | UnOp::UnsignedL, _))
+}
@@ -0,0 +1,150 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! File inclusion.
+
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::lang::ast::*;
+use crate::lang::lexer::Lexer;
+use crate::lang::parser::Parser;
+use crate::name::Name;
+
+use super::Assembler;
+
+/// Maximum nesting depth of `.include` directives.
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+impl Assembler<'_> {
+    pub(super) fn dir_include(&mut self, dir: &Dir<Span>) {
+        let path = match self.path_arg(dir) {
+            Some(path) => path,
+            None       => return,
+        };
+
+        if self.depth == MAX_INCLUDE_DEPTH {
+            return self.error(dir.data, "includes nested too deeply");
+        }
+
+        let ast = match self.sources.get(&path) {
+            Some(ast) => ast.clone(),
+            None => {
+                let ast = self.read_source(path);
+                self.sources.insert(path, ast.clone());
+                ast
+            },
+        };
+
+        let ast = match ast {
+            Some(ast) => ast,
+            None      => return self.error(dir.data, format!(
+                "cannot read '{}'", &self.session.names()[path]
+            )),
+        };
+
+        let file = std::mem::replace(&mut self.file, path);
+        self.depth += 1;
+        self.block(&ast);
+        self.depth -= 1;
+        self.file = file;
+    }
+
+    pub(super) fn dir_incbin(&mut self, dir: &Dir<Span>) {
+        self.check_data(dir);
+
+        let path = match self.path_arg(dir) {
+            Some(path) => path,
+            None       => return,
+        };
+
+        let bytes = match self.binaries.get(&path) {
+            Some(bytes) => bytes.clone(),
+            None => {
+                let bytes = fs::read(&self.session.names()[path]).ok().map(Rc::new);
+                self.add_dep(path);
+                self.binaries.insert(path, bytes.clone());
+                bytes
+            },
+        };
+
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None        => return self.error(dir.data, format!(
+                "cannot read '{}'", &self.session.names()[path]
+            )),
+        };
+
+        // Optional offset and length
+        let mut range = 0..bytes.len() as i64;
+        for (i, arg) in dir.args.iter().enumerate().skip(1).take(2) {
+            let value = match *arg {
+                Arg::Expr(ref e) => match self.eval_const(e) {
+                    Some(v) => v,
+                    None    => return,
+                },
+                Arg::Unknown(_) => continue,
+            };
+            match i {
+                1 => range.start = value,
+                _ => range.end   = range.start.saturating_add(value),
+            }
+        }
+
+        if range.start < 0 || range.start > range.end || range.end > bytes.len() as i64 {
+            return self.error(dir.data, "range exceeds file size");
+        }
+
+        self.emit(&bytes[range.start as usize..range.end as usize]);
+    }
+
+    /// Returns the path given as the first argument of the directive,
+    /// resolved relative to the directory of the current source file.
+    fn path_arg(&mut self, dir: &Dir<Span>) -> Option<Name> {
+        let path = match dir.args.first() {
+            Some(Arg::Expr(Expr::Str(_, ref path))) => path,
+            _ => {
+                self.error(dir.data, "expected: file path");
+                return None;
+            },
+        };
+
+        let path = match Path::new(&self.session.names()[self.file]).parent() {
+            Some(parent) if Path::new(path).is_relative() => parent.join(path),
+            _                                             => path.into(),
+        };
+
+        Some(self.session.names_mut().add(&path.to_string_lossy()))
+    }
+
+    /// Reads and parses the source file with the given `path`.
+    fn read_source(&mut self, path: Name) -> Option<Rc<Block<Span>>> {
+        let content = fs::read_to_string(&self.session.names()[path]).ok()?;
+        let lexer   = Lexer::new(content.bytes());
+        let ast     = Parser::new(lexer, path, self.session).parse();
+        self.add_dep(path);
+        Some(Rc::new(ast))
+    }
+
+    fn add_dep(&mut self, path: Name) {
+        if !self.deps.contains(&path) {
+            self.deps.push(path);
+        }
+    }
+}
@@ -0,0 +1,637 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Macros.
+//!
+//! `.define` binds a name to an expression.  The assembler replaces each
+//! later use of the name in a statement argument with the expression, which
+//! is an *inline macro*.  An inline macro with parameters expands where it
+//! appears as a function call.  Inline macros, and their removal by `.undef`,
+//! end with the scope in which they appear.
+//!
+//! `.macro` binds a name to the statements up to the matching `.end`.  The
+//! assembler replaces each later statement with that name by the statements,
+//! which is a *statement macro*.  Each expansion has a scope of its own, as
+//! for `.block`.  Statement macros remain until `.unmac` removes them.
+//!
+//! An argument may be a block of statements.  In the body of a statement
+//! macro, a statement that consists only of a parameter bound to a block
+//! renders the block there.
+//!
+//! Macros exist from their definition onward in each pass, and those that an
+//! included file defines remain after the file ends.
+
+use std::borrow::Cow;
+use std::mem;
+use std::rc::Rc;
+
+use crate::lang::ast::*;
+use crate::name::Name;
+
+use super::Assembler;
+
+// ----------------------------------------------------------------------------
+
+/// Macro parameter.
+#[derive(Clone, Debug)]
+pub(super) struct Param {
+    /// Name of the parameter.
+    name: Name,
+
+    /// Whether the argument is evaluated where the macro expands.
+    eager: bool,
+
+    /// Whether the parameter takes all remaining arguments.
+    rest: bool,
+
+    /// Value of the parameter if the argument is omitted, or `None` if the
+    /// parameter is required.
+    default: Option<Expr<Span>>,
+}
+
+/// Inline macro, defined by `.define`.
+#[derive(Clone, Debug)]
+pub(super) struct Define {
+    /// Name of the macro.
+    name: Name,
+
+    /// Parameters of the macro, or `None` if it expands without a call.
+    params: Option<Vec<Param>>,
+
+    /// Expression to which the macro expands, or `None` if `.undef` hides
+    /// the macro until the end of the scope.
+    value: Option<Expr<Span>>,
+
+    /// Count of blocks that enclose the definition.
+    pub(super) depth: usize,
+}
+
+/// Statement macro, defined by `.macro`.
+#[derive(Debug)]
+pub(super) struct Macro {
+    /// Name of the macro.
+    name: Name,
+
+    /// Parameters of the macro.
+    params: Vec<Param>,
+
+    /// Statements to which the macro expands.
+    body: Block<Span>,
+
+    /// Name of the source file that defines the macro.
+    file: Name,
+}
+
+/// Statement macro being defined.
+#[derive(Debug)]
+pub(super) struct OpenMacro {
+    /// Name of the macro.
+    name: Name,
+
+    /// Parameters of the macro.
+    params: Vec<Param>,
+
+    /// Statements collected so far.
+    stmts: Vec<Stmt<Span>>,
+
+    /// Count of directives within the body that await their own `.end`.
+    nest: usize,
+
+    /// Location of the `.macro` directive.
+    span: Span,
+}
+
+/// Arguments bound to macro parameters.
+type Bindings = Vec<(Name, Vec<Arg<Span>>)>;
+
+impl Assembler<'_> {
+    // === Inline Macros ===
+
+    pub(super) fn dir_define(&mut self, dir: &Dir<Span>) {
+        let (lhs, value) = match dir.args[..] {
+            [Arg::Expr(Expr::Binary(_, BinOp::Assign, ref lhs, ref value))] => (lhs, value),
+            _ => {
+                return self.error(dir.data, "expected: <name> = <value>");
+            },
+        };
+
+        let (name, params) = match **lhs {
+            Expr::Ident(_, name) => (name, None),
+            Expr::Call(_, ref func, ref args) => match **func {
+                Expr::Ident(_, name) => match self.params(args.iter()) {
+                    Some(params) => (name, Some(params)),
+                    None         => return,
+                },
+                _ => return self.error(*func.data(), "expected: macro name"),
+            },
+            _ => {
+                return self.error(*lhs.data(), "expected: macro name");
+            },
+        };
+
+        let value = Some((**value).clone());
+        self.hide(name);
+        self.defines.push(Define { name, params, value, depth: self.scopes.len() });
+    }
+
+    pub(super) fn dir_undef(&mut self, dir: &Dir<Span>) {
+        let (span, name) = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(span, name))] => (span, name),
+            _ => {
+                return self.error(dir.data, "expected: macro name");
+            },
+        };
+
+        if self.find_define(name).is_none() {
+            return self.warning(span, format!(
+                "inline macro '{}' is not defined", &self.session.names()[name]
+            ));
+        }
+
+        // Hide any definition of an enclosing scope until this one ends
+        self.hide(name);
+        if self.find_define(name).is_some() {
+            let depth = self.scopes.len();
+            self.defines.push(Define { name, params: None, value: None, depth });
+        }
+    }
+
+    /// Removes the definitions of the inline macro with the given `name`
+    /// that the current scope made.
+    fn hide(&mut self, name: Name) {
+        let depth = self.scopes.len();
+        self.defines.retain(|d| d.name != name || d.depth < depth);
+    }
+
+    /// Returns the inline macro with the given `name`, if one is defined.
+    fn find_define(&self, name: Name) -> Option<&Define> {
+        self.defines
+            .iter()
+            .rfind(|d| d.name == name)
+            .filter(|d| d.value.is_some())
+    }
+
+    /// Returns the given statement with inline macros in its arguments
+    /// expanded.
+    pub(super) fn expand_defines<'s>(&mut self, stmt: &'s Stmt<Span>) -> Cow<'s, Stmt<Span>> {
+        let dir = match *stmt {
+            Stmt::Dir(ref dir) if !self.defines.is_empty() => dir,
+            _ => return Cow::Borrowed(stmt),
+        };
+
+        // Leave macro names and parameters as written
+        match dir.name {
+            Name::DOT_DEFINE | Name::DOT_UNDEF | Name::DOT_MACRO | Name::DOT_UNMAC => {
+                return Cow::Borrowed(stmt);
+            },
+            _ => (),
+        }
+
+        let args = dir.args
+            .iter()
+            .map(|arg| match *arg {
+                Arg::Expr(ref e) => Arg::Expr(self.expand_define(e, &mut vec![])),
+                Arg::Unknown(s)  => Arg::Unknown(s),
+            })
+            .collect();
+
+        Cow::Owned(Stmt::Dir(Dir { name: dir.name, args, data: dir.data }))
+    }
+
+    /// Returns the given expression with inline macros expanded, except for
+    /// those in `active`, which are already expanding.
+    fn expand_define(&mut self, expr: &Expr<Span>, active: &mut Vec<Name>) -> Expr<Span> {
+        use Expr::*;
+        match *expr {
+            Ident(span, name) if !active.contains(&name) => {
+                let value = match self.find_define(name) {
+                    Some(&Define { params: None, value: Some(ref v), .. }) => respan(v, span),
+                    _ => return expr.clone(),
+                };
+                active.push(name);
+                let value = self.expand_define(&value, active);
+                active.pop();
+                value
+            },
+            Call(span, ref func, ref args) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.expand_define(a, active))
+                    .collect::<Vec<_>>();
+
+                let name = match **func {
+                    Ident(_, name) if !active.contains(&name) => name,
+                    _ => return Call(span, Box::new(self.expand_define(func, active)), args),
+                };
+
+                let (params, value) = match self.find_define(name) {
+                    Some(&Define { params: Some(ref p), value: Some(ref v), .. }) => {
+                        (p.clone(), respan(v, span))
+                    },
+                    _ => return Call(span, Box::new(self.expand_define(func, active)), args),
+                };
+
+                let args  = args.into_iter().map(Arg::Expr).collect();
+                let value = match self.bind_args(span, name, &params, args) {
+                    Some(bindings) => self.subst_expr(&value, &bindings),
+                    None           => return expr.clone(),
+                };
+
+                active.push(name);
+                let value = self.expand_define(&value, active);
+                active.pop();
+                value
+            },
+            Alias(span, name, ref inner) => {
+                Alias(span, name, Box::new(self.expand_define(inner, active)))
+            },
+            Deref(span, ref inner, post) => {
+                Deref(span, Box::new(self.expand_define(inner, active)), post)
+            },
+            Unary(span, op, ref inner) => {
+                Unary(span, op, Box::new(self.expand_define(inner, active)))
+            },
+            Binary(span, op, ref lhs, ref rhs) => {
+                let lhs = self.expand_define(lhs, active);
+                let rhs = self.expand_define(rhs, active);
+                Binary(span, op, Box::new(lhs), Box::new(rhs))
+            },
+            _ => expr.clone(),
+        }
+    }
+
+    // === Statement Macros ===
+
+    pub(super) fn dir_macro(&mut self, dir: &Dir<Span>) {
+        let name = match dir.args.first() {
+            Some(Arg::Expr(Expr::Ident(_, name))) => *name,
+            _ => {
+                return self.error(dir.data, "expected: macro name");
+            },
+        };
+
+        let params = dir.args[1..].iter().map(|arg| match *arg {
+            Arg::Expr(ref e) => Ok(e),
+            Arg::Unknown(s)  => Err(s),
+        });
+
+        let params = match params.collect::<Result<Vec<_>, _>>() {
+            Ok(params) => self.params(params.into_iter()),
+            Err(span)  => return self.error(span, "expected: parameter name"),
+        };
+
+        let params = match params {
+            Some(params) => params,
+            None         => return,
+        };
+
+        self.defining = Some(OpenMacro {
+            name,
+            params,
+            stmts: Vec::new(),
+            nest:  0,
+            span:  dir.data,
+        });
+    }
+
+    /// Collects the given statement into the body of the statement macro
+    /// being defined.
+    pub(super) fn macro_stmt(&mut self, stmt: &Stmt<Span>) {
+        let open = self.defining.as_mut().unwrap();
+
+        if let Stmt::Dir(ref dir) = *stmt {
+            match dir.name {
+                Name::DOT_MACRO | Name::DOT_STRUCT | Name::DOT_UNION | Name::DOT_TEST => {
+                    open.nest += 1;
+                },
+                Name::DOT_END if open.nest == 0 => {
+                    return self.macro_end(dir);
+                },
+                Name::DOT_END => {
+                    open.nest -= 1;
+                },
+                _ => (),
+            }
+        }
+
+        open.stmts.push(stmt.clone());
+    }
+
+    fn macro_end(&mut self, dir: &Dir<Span>) {
+        let open = self.defining.take().unwrap();
+
+        match dir.args[..] {
+            [] => (),
+            [Arg::Expr(Expr::Ident(_, name))] if name == open.name => (),
+            _ => self.error(dir.data, format!(
+                "expected: .end {}", &self.session.names()[open.name]
+            )),
+        }
+
+        let body = Block { stmts: open.stmts, data: open.span };
+        let file = self.file;
+
+        self.macros.insert(open.name, Rc::new(Macro {
+            name:   open.name,
+            params: open.params,
+            body,
+            file,
+        }));
+    }
+
+    /// Reports an error if a statement macro is still being defined at the
+    /// end of the block that began it.
+    pub(super) fn end_macro_def(&mut self) {
+        if let Some(open) = self.defining.take() {
+            self.error(open.span, ".macro without .end");
+        }
+    }
+
+    pub(super) fn dir_unmac(&mut self, dir: &Dir<Span>) {
+        let (span, name) = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(span, name))] => (span, name),
+            _ => {
+                return self.error(dir.data, "expected: macro name");
+            },
+        };
+
+        if self.macros.remove(&name).is_none() {
+            self.warning(span, format!(
+                "macro '{}' is not defined", &self.session.names()[name]
+            ));
+        }
+    }
+
+    /// Returns the statement macro with the given `name`, if one is defined.
+    pub(super) fn find_macro(&self, name: Name) -> Option<Rc<Macro>> {
+        self.macros.get(&name).cloned()
+    }
+
+    /// Assembles the expansion of the given statement macro for the given
+    /// statement.
+    pub(super) fn invoke(&mut self, mac: &Macro, dir: &Dir<Span>) {
+        if self.active.contains(&mac.name) {
+            return self.error(dir.data, format!(
+                "macro '{}' expands within itself", &self.session.names()[mac.name]
+            ));
+        }
+
+        // Resolve local label names where the macro is used, not within it
+        let args = dir.args
+            .iter()
+            .map(|arg| match *arg {
+                Arg::Expr(ref e) => Arg::Expr(self.resolve_names(e)),
+                Arg::Unknown(s)  => Arg::Unknown(s),
+            })
+            .collect();
+
+        let body = match self.bind_args(dir.data, mac.name, &mac.params, args) {
+            Some(bindings) => self.subst_block(&mac.body, &bindings),
+            None           => return,
+        };
+
+        let file = mem::replace(&mut self.file, mac.file);
+        self.active.push(mac.name);
+        self.scoped(&body);
+        self.active.pop();
+        self.file = file;
+    }
+
+    // === Parameters ===
+
+    /// Parses the given macro parameters.  Returns `None` if any is invalid.
+    fn params<'e>(&mut self, exprs: impl Iterator<Item = &'e Expr<Span>>) -> Option<Vec<Param>> {
+        let mut params = Vec::<Param>::new();
+
+        for expr in exprs {
+            if params.last().is_some_and(|p| p.rest) {
+                self.error(*expr.data(), "parameter follows one that takes remaining arguments");
+                return None;
+            }
+
+            let (mut expr, default) = match *expr {
+                Expr::Binary(_, BinOp::Assign, ref lhs, ref rhs) => (&**lhs, Some(&**rhs)),
+                ref expr                                         => (expr, None),
+            };
+
+            let mut eager = false;
+            let mut rest  = false;
+
+            let name = loop {
+                match *expr {
+                    Expr::Unary(_, UnOp::LogNot,  ref e) => { eager = true; expr = e; },
+                    Expr::Unary(_, UnOp::SignedH, ref e) => { rest  = true; expr = e; },
+                    Expr::Ident(_, name)                 => break name,
+                    _ => {
+                        self.error(*expr.data(), "expected: parameter name");
+                        return None;
+                    },
+                }
+            };
+
+            params.push(Param { name, eager, rest, default: default.cloned() });
+        }
+
+        Some(params)
+    }
+
+    /// Binds the given arguments to the parameters of the macro with the
+    /// given `name`, used at the given `span`.  Returns `None` if the
+    /// arguments do not fit the parameters.
+    fn bind_args(&mut self, span: Span, name: Name, params: &[Param], args: Vec<Arg<Span>>)
+        -> Option<Bindings>
+    {
+        let mut args     = args.into_iter();
+        let mut bindings = Bindings::new();
+
+        for param in params {
+            let values = if param.rest {
+                args.by_ref().collect()
+            } else {
+                match (args.next(), &param.default) {
+                    (Some(arg), _)        => vec![arg],
+                    (None, Some(default)) => vec![Arg::Expr(respan(default, span))],
+                    (None, None)          => {
+                        let names = self.session.names();
+                        let msg   = format!(
+                            "macro '{}' requires argument '{}'", &names[name], &names[param.name]
+                        );
+                        self.error(span, msg);
+                        return None;
+                    },
+                }
+            };
+
+            let values = match param.eager {
+                true  => values.into_iter().map(|v| self.eval_arg(v)).collect(),
+                false => values,
+            };
+
+            bindings.push((param.name, values));
+        }
+
+        if args.next().is_some() {
+            self.error(span, format!(
+                "too many arguments to macro '{}'", &self.session.names()[name]
+            ));
+            return None;
+        }
+
+        Some(bindings)
+    }
+
+    /// Returns the given argument of an eager parameter, replaced by its
+    /// value if it has one.
+    fn eval_arg(&mut self, arg: Arg<Span>) -> Arg<Span> {
+        match arg {
+            Arg::Expr(e) => match self.eval_const(&e) {
+                Some(value) => Arg::Expr(Expr::Int(*e.data(), value as u64)),
+                None        => Arg::Expr(e),
+            },
+            arg => arg,
+        }
+    }
+
+    // === Substitution ===
+
+    /// Returns the given block with parameters replaced by their arguments.
+    /// A statement that consists of a parameter bound to a block renders the
+    /// block.
+    fn subst_block(&mut self, block: &Block<Span>, bindings: &Bindings) -> Block<Span> {
+        let stmts = block.stmts
+            .iter()
+            .map(|stmt| match *stmt {
+                Stmt::Dir(ref dir) => Stmt::Dir(self.subst_dir(dir, bindings)),
+                ref label          => label.clone(),
+            })
+            .collect();
+
+        Block { stmts, data: block.data }
+    }
+
+    fn subst_dir(&mut self, dir: &Dir<Span>, bindings: &Bindings) -> Dir<Span> {
+        if dir.args.is_empty() {
+            if let Some(block @ [Arg::Expr(Expr::Block(_))]) = lookup(bindings, dir.name) {
+                return Dir { name: Name::DOT_BLOCK, args: block.to_vec(), data: dir.data };
+            }
+        }
+
+        let mut args = Vec::with_capacity(dir.args.len());
+
+        for arg in &dir.args {
+            match *arg {
+                Arg::Expr(Expr::Ident(_, name)) if lookup(bindings, name).is_some() => {
+                    args.extend_from_slice(lookup(bindings, name).unwrap());
+                },
+                Arg::Expr(ref e) => {
+                    args.push(Arg::Expr(self.subst_expr(e, bindings)));
+                },
+                Arg::Unknown(s) => {
+                    args.push(Arg::Unknown(s));
+                },
+            }
+        }
+
+        Dir { name: dir.name, args, data: dir.data }
+    }
+
+    /// Returns the given expression with parameters replaced by their
+    /// arguments.
+    fn subst_expr(&mut self, expr: &Expr<Span>, bindings: &Bindings) -> Expr<Span> {
+        use Expr::*;
+        match *expr {
+            Ident(span, name) => match lookup(bindings, name) {
+                Some([Arg::Expr(e)]) => e.clone(),
+                Some(_) => {
+                    self.error(span, format!(
+                        "parameter '{}' does not stand for one value here",
+                        &self.session.names()[name]
+                    ));
+                    expr.clone()
+                },
+                None => expr.clone(),
+            },
+            Call(span, ref func, ref args) => {
+                let func = self.subst_expr(func, bindings);
+                let mut list = Vec::with_capacity(args.len());
+                for arg in args {
+                    match *arg {
+                        Ident(s, name) if lookup(bindings, name).is_some() => {
+                            for value in lookup(bindings, name).unwrap() {
+                                match *value {
+                                    Arg::Expr(ref e) => list.push(e.clone()),
+                                    Arg::Unknown(_)  => self.error(s, "expected: value"),
+                                }
+                            }
+                        },
+                        ref arg => list.push(self.subst_expr(arg, bindings)),
+                    }
+                }
+                Call(span, Box::new(func), list)
+            },
+            Alias(span, name, ref inner) => {
+                Alias(span, name, Box::new(self.subst_expr(inner, bindings)))
+            },
+            Deref(span, ref inner, post) => {
+                Deref(span, Box::new(self.subst_expr(inner, bindings)), post)
+            },
+            Unary(span, op, ref inner) => {
+                Unary(span, op, Box::new(self.subst_expr(inner, bindings)))
+            },
+            Binary(span, op, ref lhs, ref rhs) => {
+                let lhs = self.subst_expr(lhs, bindings);
+                let rhs = self.subst_expr(rhs, bindings);
+                Binary(span, op, Box::new(lhs), Box::new(rhs))
+            },
+            Block(ref block) => {
+                Block(self.subst_block(block, bindings))
+            },
+            _ => expr.clone(),
+        }
+    }
+}
+
+/// Returns the arguments bound to the parameter with the given `name`, if
+/// there is such a parameter.
+fn lookup(bindings: &Bindings, name: Name) -> Option<&[Arg<Span>]> {
+    bindings.iter().find(|b| b.0 == name).map(|b| &b.1[..])
+}
+
+/// Returns the given expression with each span replaced by `span`, so that
+/// diagnostics about an expanded inline macro refer to its use.
+fn respan(expr: &Expr<Span>, span: Span) -> Expr<Span> {
+    use Expr::*;
+    match *expr {
+        Ident  (_, name)         => Ident(span, name),
+        Int    (_, value)        => Int(span, value),
+        Float  (_, ref value)    => Float(span, value.clone()),
+        Str    (_, ref value)    => Str(span, value.clone()),
+        Char   (_, value)        => Char(span, value),
+        Alias  (_, name, ref e)  => Alias(span, name, Box::new(respan(e, span))),
+        Deref  (_, ref e, post)  => Deref(span, Box::new(respan(e, span)), post),
+        Unary  (_, op, ref e)    => Unary(span, op, Box::new(respan(e, span))),
+        Binary (_, op, ref l, ref r) => {
+            Binary(span, op, Box::new(respan(l, span)), Box::new(respan(r, span)))
+        },
+        Call   (_, ref f, ref args) => {
+            Call(span, Box::new(respan(f, span)), args.iter().map(|a| respan(a, span)).collect())
+        },
+        Block  (ref block)       => Block(block.clone()),
+    }
+}
@@ -0,0 +1,126 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! User diagnostics.
+//!
+//! The `.print`, `.warning`, and `.error` directives report a message at
+//! their own location, as a note, a warning, or an error.  The message is a
+//! format string in which each placeholder `{}` takes the value of the next
+//! argument.  A placeholder may select the base of the value: `{:d}` for
+//! decimal, `{:x}` for hexadecimal, `{:o}` for octal, or `{:b}` for binary.
+//! `{{` and `}}` stand for literal braces.
+
+use std::fmt::Write;
+
+use crate::lang::ast::*;
+use crate::num;
+use crate::session::Level;
+
+use super::Assembler;
+
+// ----------------------------------------------------------------------------
+
+impl Assembler<'_> {
+    pub(super) fn dir_message(&mut self, dir: &Dir<Span>, level: Level) {
+        // Report the message once, not in every pass
+        if !self.last {
+            return;
+        }
+
+        let (format, values) = match dir.args.split_first() {
+            Some((Arg::Expr(Expr::Str(_, s)), values)) => (s, values),
+            _ => {
+                return self.error(dir.data, "expected: message string");
+            },
+        };
+
+        if let Some(msg) = self.format(dir.data, format, values) {
+            let loc = self.loc(dir.data);
+            self.session.report(level, Some(loc), msg);
+        }
+    }
+
+    /// Formats a message, replacing the placeholders in `format` with the
+    /// given values.  Returns `None` if the message is invalid.
+    fn format(&mut self, span: Span, format: &str, values: &[Arg<Span>]) -> Option<String> {
+        let mut msg    = String::new();
+        let mut rest   = format;
+        let mut values = values.iter();
+
+        while let Some(i) = rest.find(['{', '}']) {
+            msg.push_str(&rest[..i]);
+
+            let brace = &rest[i..i + 1];
+            let after = &rest[i + 1..];
+
+            if let Some(after) = after.strip_prefix(brace) {
+                msg.push_str(brace);
+                rest = after;
+                continue;
+            }
+
+            if brace == "}" {
+                self.error(span, "unmatched '}' in message");
+                return None;
+            }
+
+            let end = match after.find('}') {
+                Some(end) => end,
+                None      => {
+                    self.error(span, "unterminated placeholder in message");
+                    return None;
+                },
+            };
+
+            let base = match &after[..end] {
+                "" | ":d" => num::Base::Dec,
+                ":x"      => num::Base::Hex,
+                ":o"      => num::Base::Oct,
+                ":b"      => num::Base::Bin,
+                spec      => {
+                    self.error(span, format!("invalid placeholder '{{{}}}' in message", spec));
+                    return None;
+                },
+            };
+
+            let value = match values.next() {
+                Some(Arg::Expr(e))    => self.eval_const(e)?,
+                Some(Arg::Unknown(s)) => {
+                    self.error(*s, "expected: value");
+                    return None;
+                },
+                None                  => {
+                    self.error(span, "message has more placeholders than values");
+                    return None;
+                },
+            };
+
+            let _ = write!(msg, "{}", base.display(&value));
+            rest = &after[end + 1..];
+        }
+
+        msg.push_str(rest);
+
+        if values.next().is_some() {
+            self.error(span, "message has more values than placeholders");
+            return None;
+        }
+
+        Some(msg)
+    }
+}
@@ -0,0 +1,1644 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Assembler.
+//!
+//! The assembler translates an abstract syntax tree into sections of bytes
+//! and a table of symbols.  Because a statement can refer to a symbol defined
+//! later in the source, the assembler makes repeated layout passes over the
+//! tree until symbol values stop changing, then a final pass in which it
+//! reports diagnostics and records symbol references.
+//!
+//! A section is *relocatable* unless `.org` fixes its address.  The assembler
+//! cannot compute a value that depends on the address of a relocatable
+//! section or on a symbol defined in another unit.  Instead, it records a
+//! [`Reloc`] for the linker to compute.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::lang::ast::*;
+use crate::name::{Name, NameTable};
+use crate::session::{Level, Loc, Session};
+use crate::target::{Emitter, Target};
+
+mod alias;
+mod eval;
+mod include;
+mod macros;
+mod message;
+mod record;
+mod relax;
+mod scope;
+mod section;
+mod symbol;
+mod test;
+mod value;
+
+pub use self::eval::*;
+pub use self::section::*;
+pub use self::symbol::*;
+pub use self::test::*;
+pub use self::value::*;
+
+use self::alias::Alias;
+use self::macros::{Define, Macro, OpenMacro};
+use self::record::{OpenRecord, Record};
+use self::test::OpenTest;
+
+/// Maximum count of layout passes in which no span-dependent instruction
+/// grows before the assembler gives up.  Passes in which an instruction grows
+/// do not count: forms only grow, so there are finitely many such passes.
+const MAX_PASSES: u32 = 16;
+
+/// Maximum count of bytes that `.skip` or `.org` may reserve at once in a
+/// section that stores content.
+const MAX_FILL: u64 = 1 << 28;
+
+// ----------------------------------------------------------------------------
+
+/// Byte orders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+
+    /// Most significant byte first.
+    Big,
+}
+
+// ----------------------------------------------------------------------------
+
+/// Assembled translation unit.
+#[derive(Clone, Debug)]
+pub struct Unit {
+    /// Name of the source file.
+    pub file: Name,
+
+    /// Sections, in order of first appearance.
+    pub sections: Vec<Section>,
+
+    /// Symbols, in order of first appearance.
+    pub symbols: SymbolTable,
+
+    /// Values for the linker to compute.
+    pub relocs: Vec<Reloc>,
+
+    /// Test blocks, in order of appearance.  Empty unless the session
+    /// includes tests.
+    pub tests: Vec<Test>,
+
+    /// Byte order of values.
+    pub endian: Endian,
+
+    /// Names of the files that contributed to the unit, in order of first
+    /// use, beginning with the source file itself.
+    pub deps: Vec<Name>,
+}
+
+/// Value for the linker to compute and store into a section.
+#[derive(Clone, Debug)]
+pub struct Reloc {
+    /// Index of the section to receive the value.
+    pub section: usize,
+
+    /// Offset within the section at which to store the value.
+    pub offset: u64,
+
+    /// How to store the value.
+    pub kind: RelocKind,
+
+    /// Expression that computes the value.
+    pub expr: Expr<Span>,
+
+    /// Name of the innermost non-local label at the expression, which
+    /// qualifies local labels in the expression.
+    pub scope: Name,
+
+    /// Offset within the section of the statement containing the expression.
+    pub here: u64,
+
+    /// Location of the expression.
+    pub loc: Loc,
+}
+
+/// Method by which the linker stores a computed value into a section.
+#[derive(Clone, Copy, Debug)]
+pub struct RelocKind {
+    /// Name, for diagnostics.
+    pub name: &'static str,
+
+    /// Size in bytes of the field that receives the value.
+    pub size: usize,
+
+    /// Merges a value into the existing content of the field, read in the
+    /// byte order of the unit.  Returns the new content of the field, or an
+    /// error message if the field cannot represent the value.
+    pub apply: fn(value: i64, field: u64) -> Result<u64, String>,
+}
+
+impl RelocKind {
+    /// 8-bit integer, signed or unsigned.
+    pub const INT8:  Self = Self { name: "int8",  size: 1, apply: apply_int::<8>  };
+
+    /// 16-bit integer, signed or unsigned.
+    pub const INT16: Self = Self { name: "int16", size: 2, apply: apply_int::<16> };
+
+    /// 32-bit integer, signed or unsigned.
+    pub const INT32: Self = Self { name: "int32", size: 4, apply: apply_int::<32> };
+
+    /// 64-bit integer, signed or unsigned.
+    pub const INT64: Self = Self { name: "int64", size: 8, apply: apply_int::<64> };
+
+    /// Returns the kind for an integer of the given `size` in bytes.
+    pub fn int(size: usize) -> Self {
+        match size {
+            1 => Self::INT8,
+            2 => Self::INT16,
+            4 => Self::INT32,
+            _ => Self::INT64,
+        }
+    }
+}
+
+fn apply_int<const BITS: usize>(value: i64, _: u64) -> Result<u64, String> {
+    match fits(value, BITS) {
+        true  => Ok(value as u64),
+        false => Err(format!("value {} does not fit in {} bits", value, BITS)),
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Assembler for a single translation unit.
+#[derive(Debug)]
+pub struct Assembler<'a> {
+    session:  &'a mut Session,
+    target:   Option<Rc<dyn Target>>,
+    root:     Name,
+    endian:   Endian,
+    sections: Vec<Section>,
+    symbols:  SymbolTable,
+    relocs:   Vec<Reloc>,
+    tests:    Vec<Test>,
+    deps:     Vec<Name>,
+
+    /// Parsed source files included by `.include`, or `None` if unreadable.
+    sources: HashMap<Name, Option<Rc<Block<Span>>>>,
+
+    /// Binary files included by `.incbin`, or `None` if unreadable.
+    binaries: HashMap<Name, Option<Rc<Vec<u8>>>>,
+
+    /// Name of the current source file.
+    file: Name,
+
+    /// Count of `.include` directives being processed.
+    depth: usize,
+
+    /// Index of the current section.
+    section: usize,
+
+    /// Test block being assembled, if any.
+    test: Option<OpenTest>,
+
+    /// Record types, with the number of the pass that last defined each.
+    records: HashMap<Name, (u32, Rc<Record>)>,
+
+    /// Record type being defined, if any.
+    record: Option<OpenRecord>,
+
+    /// Name of the innermost non-local label, which qualifies local labels.
+    /// Within a `.block`, the name of the block instead.
+    scope: Name,
+
+    /// Scope names that enclosing blocks replaced, from outermost to
+    /// innermost.
+    scopes: Vec<Name>,
+
+    /// Count of blocks so far in the current pass.
+    blocks: u32,
+
+    /// Operand aliases of the current scope, in order of binding.
+    aliases: Vec<Alias>,
+
+    /// Inline macros of the current scope and enclosing scopes, in order of
+    /// definition.
+    defines: Vec<Define>,
+
+    /// Statement macros defined so far in the current pass.
+    macros: HashMap<Name, Rc<Macro>>,
+
+    /// Statement macro being defined, if any.
+    defining: Option<OpenMacro>,
+
+    /// Names of the statement macros being expanded, from outermost to
+    /// innermost.
+    active: Vec<Name>,
+
+    /// Address of the current statement.
+    here: u64,
+
+    /// Number of the current pass.
+    pass: u32,
+
+    /// Whether the current pass is the final pass.
+    last: bool,
+
+    /// Whether any symbol value changed during the current pass.
+    changed: bool,
+
+    /// Whether any span-dependent instruction grew during the current pass.
+    grew: bool,
+
+    /// Location of the first symbol whose value changed during the current
+    /// pass.
+    unstable: Option<Span>,
+
+    /// Whether the most recent evaluation requires relocation.
+    reloc: bool,
+
+    /// Forms chosen for span-dependent instructions, in order of occurrence.
+    forms: Vec<usize>,
+
+    /// Count of span-dependent instructions so far in the current pass.
+    relaxed: usize,
+}
+
+impl<'a> Assembler<'a> {
+    /// Creates a new [`Assembler`] for the given source `file`.
+    pub fn new(session: &'a mut Session, file: Name) -> Self {
+        let target = session.target().cloned();
+        let endian = target.as_ref().map_or(Endian::Big, |t| t.endian());
+
+        Self {
+            session,
+            target,
+            root:     file,
+            endian,
+            sections: vec![Section::new(Name::DOT_CODE, SectionKind::Code)],
+            symbols:  SymbolTable::new(),
+            relocs:   Vec::new(),
+            tests:    Vec::new(),
+            deps:     vec![file],
+            sources:  HashMap::new(),
+            binaries: HashMap::new(),
+            file,
+            depth:    0,
+            section:  0,
+            test:     None,
+            records:  HashMap::new(),
+            record:   None,
+            scope:    Name::EMPTY,
+            scopes:   Vec::new(),
+            blocks:   0,
+            aliases:  Vec::new(),
+            defines:  Vec::new(),
+            macros:   HashMap::new(),
+            defining: None,
+            active:   Vec::new(),
+            here:     0,
+            pass:     0,
+            last:     false,
+            changed:  false,
+            grew:     false,
+            unstable: None,
+            reloc:    false,
+            forms:    Vec::new(),
+            relaxed:  0,
+        }
+    }
+
+    /// Assembles the given abstract syntax tree.
+    pub fn assemble(mut self, ast: &Block<Span>) -> Unit {
+        // Count of passes in which symbols moved but no instruction grew
+        let mut stalled = 0;
+
+        loop {
+            self.run_pass(ast);
+
+            if !self.changed {
+                break;
+            }
+            if !self.grew {
+                stalled += 1;
+            }
+            if stalled == MAX_PASSES {
+                // Blame the first symbol that moved, else the top of the file
+                let span = self.unstable.unwrap_or(Span { line: 1, ..ast.data });
+                let loc  = self.loc(span);
+                self.session.error(loc, "layout did not converge");
+                break;
+            }
+        }
+
+        self.last = true;
+        self.run_pass(ast);
+
+        let sizes = self.sections.iter().map(|s| s.size).collect::<Vec<_>>();
+        self.symbols.compute_sizes(&sizes);
+
+        Unit {
+            file:     self.root,
+            sections: self.sections,
+            symbols:  self.symbols,
+            relocs:   self.relocs,
+            tests:    self.tests,
+            endian:   self.endian,
+            deps:     self.deps,
+        }
+    }
+
+    fn run_pass(&mut self, ast: &Block<Span>) {
+        self.pass    += 1;
+        self.changed  = false;
+        self.grew     = false;
+        self.unstable = None;
+        self.section  = 0;
+        self.scope    = Name::EMPTY;
+        self.relaxed  = 0;
+        self.blocks   = 0;
+        self.aliases.clear();
+        self.defines.clear();
+        self.macros.clear();
+
+        for section in &mut self.sections {
+            section.reset();
+        }
+
+        if let Some(target) = &self.target {
+            target.begin_pass();
+        }
+
+        self.block(ast);
+        self.end_records();
+        self.end_tests();
+
+        if let Some(target) = self.target.clone() {
+            for index in 0..self.sections.len() {
+                self.section = index;
+                self.here    = self.sections[index].end();
+                target.end_section(self);
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block<Span>) {
+        for stmt in &block.stmts {
+            self.here = self.sections[self.section].end();
+
+            if self.skips(stmt) {
+                continue;
+            }
+
+            if self.defining.is_some() {
+                self.macro_stmt(stmt);
+                continue;
+            }
+
+            let stmt = self.expand_defines(stmt);
+
+            if self.record.is_some() {
+                self.record_stmt(&stmt);
+                continue;
+            }
+
+            match *stmt {
+                Stmt::Label (ref l) => self.label(l),
+                Stmt::Dir   (ref d) => self.dir(d),
+            }
+        }
+
+        self.end_macro_def();
+    }
+
+    fn label(&mut self, label: &Label<Span>) {
+        let name = if label.scope == Scope::Local {
+            self.qualify(label.name)
+        } else {
+            self.scope = label.name;
+            self.aliases.clear();
+            label.name
+        };
+
+        let section = self.section;
+        let value   = self.sections[section].size;
+
+        self.define(name, label.scope, Some(section), value, label.data);
+    }
+
+    /// Defines the symbol with the given `name` at the given `span`.  If
+    /// `section` is `None`, the symbol has the absolute value `value`.
+    /// Otherwise, `value` is an offset within the section.
+    fn define(&mut self, name: Name, scope: Scope, section: Option<usize>, value: u64, span: Span) {
+        let loc    = self.loc(span);
+        let pass   = self.pass;
+        let symbol = self.symbols.entry(name);
+
+        if symbol.pass == pass {
+            let prev = symbol.def;
+            self.error(span, format!(
+                "symbol '{}' is already defined", &self.session.names()[name]
+            ));
+            if let (true, Some(prev)) = (self.last, prev) {
+                self.session.report(Level::Note, Some(prev), "previous definition is here");
+            }
+            return;
+        }
+
+        if symbol.def.is_none() || symbol.section != section || symbol.value != value {
+            self.changed = true;
+            self.unstable.get_or_insert(span);
+        }
+
+        symbol.scope   = scope;
+        symbol.section = section;
+        symbol.value   = value;
+        symbol.def     = Some(loc);
+        symbol.pass    = pass;
+    }
+
+    fn dir(&mut self, dir: &Dir<Span>) {
+        match dir.name {
+            Name::DOT_NOP     => (),
+            Name::DOT_DEFINE  => self.dir_define(dir),
+            Name::DOT_UNDEF   => self.dir_undef(dir),
+            Name::DOT_MACRO   => self.dir_macro(dir),
+            Name::DOT_UNMAC   => self.dir_unmac(dir),
+            Name::DOT_BLOCK   => self.dir_block(dir),
+            Name::DOT_PRINT   => self.dir_message(dir, Level::Note),
+            Name::DOT_WARNING => self.dir_message(dir, Level::Warning),
+            Name::DOT_ERROR   => self.dir_message(dir, Level::Error),
+            Name::DOT_INCLUDE => self.dir_include(dir),
+            Name::DOT_INCBIN  => self.dir_incbin(dir),
+            Name::DOT_SECTION => self.dir_section(dir),
+            Name::DOT_CODE    => self.switch_section(dir.name, SectionKind::Code),
+            Name::DOT_DATA    => self.switch_section(dir.name, SectionKind::Data),
+            Name::DOT_BSS     => self.switch_section(dir.name, SectionKind::Bss),
+            Name::DOT_ORG     => self.dir_org(dir),
+            Name::DOT_SKIP    => self.dir_skip(dir),
+            Name::DOT_ALIGN   => self.dir_align(dir),
+            Name::DOT_INT8    => self.dir_int(dir, 1),
+            Name::DOT_INT16   => self.dir_int(dir, 2),
+            Name::DOT_INT32   => self.dir_int(dir, 4),
+            Name::DOT_INT64   => self.dir_int(dir, 8),
+            Name::DOT_FLOAT32 => self.dir_float(dir, 4),
+            Name::DOT_FLOAT64 => self.dir_float(dir, 8),
+            Name::DOT_ASCII   => self.dir_str(dir, Encoding::Ascii, false),
+            Name::DOT_ASCIIZ  => self.dir_str(dir, Encoding::Ascii, true),
+            Name::DOT_UTF8    => self.dir_str(dir, Encoding::Utf8,  false),
+            Name::DOT_UTF8Z   => self.dir_str(dir, Encoding::Utf8,  true),
+            Name::DOT_UTF16   => self.dir_str(dir, Encoding::Utf16, false),
+            Name::DOT_UTF16Z  => self.dir_str(dir, Encoding::Utf16, true),
+            Name::DOT_STRUCT  => self.dir_struct(dir, false),
+            Name::DOT_UNION   => self.dir_struct(dir, true),
+            Name::DOT_NEW     => self.dir_object(dir),
+            Name::DOT_TEST    => self.dir_test(dir),
+            Name::DOT_EXPECT  => self.dir_expect(dir),
+            Name::DOT_MAP     => self.dir_map(dir),
+            Name::DOT_END     => self.dir_end(dir),
+            name => {
+                if let Some(mac) = self.find_macro(name) {
+                    return self.invoke(&mac, dir);
+                }
+
+                let target = self.target.clone();
+                if let Some((target, insn)) = target
+                    .as_ref()
+                    .and_then(|t| Some((t, t.instruction(name)?)))
+                {
+                    return self.instruction(&**target, insn, dir);
+                }
+
+                let kind = if self.session.names()[name].starts_with('.') {
+                    "directive"
+                } else {
+                    "instruction"
+                };
+                self.error(dir.data, format!(
+                    "unknown {} '{}'", kind, &self.session.names()[name]
+                ));
+            },
+        }
+    }
+
+    // === Instructions ===
+
+    fn instruction(&mut self, target: &dyn Target, insn: usize, dir: &Dir<Span>) {
+        if !self.sections[self.section].has_data() {
+            return self.error(dir.data, "instruction in uninitialized section");
+        }
+
+        let align   = target.align();
+        let section = &mut self.sections[self.section];
+        section.align = section.align.max(align);
+
+        if !section.size.is_multiple_of(align) {
+            self.error(dir.data, format!("instruction is not aligned to {} bytes", align));
+        }
+
+        let dir = self.expand_aliases(dir);
+        target.encode(insn, &dir, self);
+    }
+
+    // === Sections ===
+
+    fn dir_section(&mut self, dir: &Dir<Span>) {
+        let name = match dir.args.first() {
+            Some(Arg::Expr(Expr::Ident(_, name))) => *name,
+            Some(Arg::Expr(Expr::Str(_, ref s)))  => self.session.names_mut().add(s),
+            _ => {
+                return self.error(dir.data, "expected: section name");
+            },
+        };
+
+        let kind = match dir.args.get(1) {
+            None => SectionKind::for_name(&self.session.names()[name]),
+            Some(Arg::Expr(Expr::Ident(span, kind))) => {
+                match &self.session.names()[*kind] {
+                    "code" => SectionKind::Code,
+                    "data" => SectionKind::Data,
+                    "bss"  => SectionKind::Bss,
+                    _      => return self.error(*span, "expected: code, data, or bss"),
+                }
+            },
+            Some(arg) => {
+                return self.error(*arg.data(), "expected: code, data, or bss");
+            },
+        };
+
+        self.switch_section(name, kind)
+    }
+
+    fn switch_section(&mut self, name: Name, kind: SectionKind) {
+        self.section = match self.sections.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(name, kind));
+                self.sections.len() - 1
+            },
+        };
+    }
+
+    // === Location Counter ===
+
+    fn dir_org(&mut self, dir: &Dir<Span>) {
+        let addr = match self.eval_one_arg(dir) {
+            Some(addr) => addr as u64,
+            None       => return,
+        };
+
+        let section = &mut self.sections[self.section];
+
+        if section.size == 0 {
+            section.base  = addr;
+            section.fixed = true;
+        } else if !section.fixed {
+            self.error(dir.data, ".org in a relocatable section must precede its content");
+        } else if addr >= section.end() {
+            let len = addr - section.end();
+            self.reserve(dir.data, len);
+        } else {
+            self.error(dir.data, "cannot move location counter backwards");
+        }
+    }
+
+    fn dir_skip(&mut self, dir: &Dir<Span>) {
+        match self.eval_one_arg(dir) {
+            Some(len) if len >= 0 => self.reserve(dir.data, len as u64),
+            Some(_)               => self.error(dir.data, "skip length must not be negative"),
+            None                  => (),
+        }
+    }
+
+    fn reserve(&mut self, span: Span, len: u64) {
+        let section = &mut self.sections[self.section];
+
+        if section.has_data() && len > MAX_FILL {
+            return self.error(span, format!(
+                "cannot reserve more than {} bytes at once in a section with content", MAX_FILL
+            ));
+        }
+
+        section.reserve(len);
+    }
+
+    fn dir_align(&mut self, dir: &Dir<Span>) {
+        match self.eval_one_arg(dir) {
+            Some(align) if align > 0 => self.align(align as u64),
+            Some(_)                  => self.error(dir.data, "alignment must be positive"),
+            None                     => (),
+        }
+    }
+
+    fn align(&mut self, align: u64) {
+        let section = &mut self.sections[self.section];
+        section.align = section.align.max(align);
+        let excess  = section.end() % align;
+        if excess != 0 {
+            section.reserve(align - excess);
+        }
+    }
+
+    // === Data ===
+
+    fn dir_int(&mut self, dir: &Dir<Span>, size: usize) {
+        self.check_data(dir);
+
+        for arg in &dir.args {
+            self.reloc = false;
+
+            let value = match *arg {
+                Arg::Expr(ref e) => self.eval(e).unwrap_or(0),
+                Arg::Unknown(_)  => 0,
+            };
+
+            if self.reloc {
+                if let Arg::Expr(ref e) = *arg {
+                    self.add_reloc(e, RelocKind::int(size));
+                }
+                self.emit_int(0, size);
+                continue;
+            }
+
+            if !fits(value, size * 8) {
+                self.error(*arg.data(), format!(
+                    "value {} does not fit in {} bits", value, size * 8
+                ));
+            }
+
+            self.emit_int(value as u64, size);
+        }
+    }
+
+    fn dir_float(&mut self, dir: &Dir<Span>, size: usize) {
+        self.check_data(dir);
+
+        for arg in &dir.args {
+            let bits = self.float_bits(arg, size);
+            self.emit_int(bits, size);
+        }
+    }
+
+    fn dir_str(&mut self, dir: &Dir<Span>, encoding: Encoding, terminate: bool) {
+        self.check_data(dir);
+
+        for arg in &dir.args {
+            let bytes = self.encode_str(arg, encoding, terminate);
+            self.emit(&bytes);
+        }
+    }
+
+    /// Returns the bits of the value of the given argument as a
+    /// floating-point number of `size` bytes.
+    fn float_bits(&mut self, arg: &Arg<Span>, size: usize) -> u64 {
+        let value = match *arg {
+            Arg::Expr(Expr::Float(_, ref n)) => n.to_f64(),
+            Arg::Expr(ref e)                 => self.eval_const(e).unwrap_or(0) as f64,
+            Arg::Unknown(_)                  => 0.0,
+        };
+
+        match size {
+            4 => (value as f32).to_bits() as u64,
+            _ => value.to_bits(),
+        }
+    }
+
+    /// Returns the bytes of the given string argument in the given encoding.
+    fn encode_str(&mut self, arg: &Arg<Span>, encoding: Encoding, terminate: bool) -> Vec<u8> {
+        let s = match *arg {
+            Arg::Expr(Expr::Str(_, ref s)) => s,
+            _ => {
+                self.error(*arg.data(), "expected: string");
+                return vec![];
+            },
+        };
+
+        let mut bytes = match encoding {
+            Encoding::Ascii => {
+                if !s.is_ascii() {
+                    self.error(*arg.data(), "string contains non-ASCII characters");
+                }
+                s.as_bytes().to_vec()
+            },
+            Encoding::Utf8 => {
+                s.as_bytes().to_vec()
+            },
+            Encoding::Utf16 => {
+                s.encode_utf16().flat_map(|unit| self.int_bytes(unit as u64, 2)).collect()
+            },
+        };
+
+        if terminate {
+            match encoding {
+                Encoding::Utf16 => bytes.extend([0, 0]),
+                _               => bytes.push(0),
+            }
+        }
+
+        bytes
+    }
+
+    /// Reports an error if the current section cannot store the data that
+    /// the given directive emits.
+    fn check_data(&mut self, dir: &Dir<Span>) {
+        if !self.sections[self.section].has_data() {
+            self.error(dir.data, "initialized data in uninitialized section");
+        }
+    }
+
+    /// Appends the given `bytes` to the current section.
+    fn emit(&mut self, bytes: &[u8]) {
+        self.sections[self.section].emit(bytes);
+    }
+
+    /// Appends the low `size` bytes of `value` to the current section in the
+    /// current byte order.
+    fn emit_int(&mut self, value: u64, size: usize) {
+        let bytes = self.int_bytes(value, size);
+        self.emit(&bytes);
+    }
+
+    /// Returns the low `size` bytes of `value` in the current byte order.
+    fn int_bytes(&self, value: u64, size: usize) -> Vec<u8> {
+        match self.endian {
+            Endian::Little => value.to_le_bytes()[..size].to_vec(),
+            Endian::Big    => value.to_be_bytes()[8 - size..].to_vec(),
+        }
+    }
+
+    // === Helpers ===
+
+    /// Records a relocation for the value of the given expression, which the
+    /// current statement is about to emit.
+    fn add_reloc(&mut self, expr: &Expr<Span>, kind: RelocKind) {
+        if self.last {
+            let loc     = self.loc(*expr.data());
+            let expr    = self.resolve_locals(expr);
+            let section = &self.sections[self.section];
+            self.relocs.push(Reloc {
+                section: self.section,
+                offset:  section.size,
+                kind,
+                expr,
+                scope:   self.scope,
+                here:    self.here.wrapping_sub(section.base),
+                loc,
+            });
+        }
+    }
+
+    /// Evaluates the given expression.
+    ///
+    /// Returns `None` if the expression is not constant.  In the final pass,
+    /// this method reports an error in that case.  If the value depends on a
+    /// relocatable or external symbol, this method sets the `reloc` flag and
+    /// returns the provisional value.
+    pub(super) fn eval(&mut self, expr: &Expr<Span>) -> Option<i64> {
+        self.eval_value(expr).map(Value::provisional)
+    }
+
+    /// Evaluates the given expression to a value that is either constant or
+    /// that the linker can compute as a relocation.  If the value is
+    /// relocatable, this method sets the `reloc` flag.
+    fn eval_value(&mut self, expr: &Expr<Span>) -> Option<Value> {
+        let value = eval(self, expr)?;
+        if !value.is_relocatable() {
+            self.error(*expr.data(), "expression cannot be expressed as a relocation");
+            return None;
+        }
+        self.reloc |= matches!(value, Value::Reloc(_));
+        Some(value)
+    }
+
+    /// Evaluates the given expression, which must not require relocation.
+    pub(super) fn eval_const(&mut self, expr: &Expr<Span>) -> Option<i64> {
+        self.reloc = false;
+        let value  = self.eval(expr)?;
+        if self.reloc {
+            self.error(*expr.data(), "expected: constant expression, not relocatable");
+            return None;
+        }
+        Some(value)
+    }
+
+    /// Evaluates the single argument of the given directive, which must not
+    /// require relocation.
+    fn eval_one_arg(&mut self, dir: &Dir<Span>) -> Option<i64> {
+        match dir.args[..] {
+            [Arg::Expr(ref e)] => self.eval_const(e),
+            _ => {
+                self.error(dir.data, format!(
+                    "{} requires one argument", &self.session.names()[dir.name]
+                ));
+                None
+            },
+        }
+    }
+
+    /// Qualifies a local label name with the innermost non-local label name.
+    fn qualify(&mut self, name: Name) -> Name {
+        self.qualify_in(self.scope, name)
+    }
+
+    /// Qualifies a local label name with the given scope name.
+    fn qualify_in(&mut self, scope: Name, name: Name) -> Name {
+        let names = self.session.names_mut();
+        let qualified = format!("{}{}", &names[scope], &names[name]);
+        names.add(&qualified)
+    }
+
+    /// Returns the location of the given span in the current file.
+    fn loc(&self, span: Span) -> Loc {
+        Loc { file: self.file, span }
+    }
+
+    /// Reports an error at the given `span` if the current pass is the final
+    /// pass.
+    fn error(&mut self, span: Span, msg: impl Display) {
+        if self.last {
+            let loc = self.loc(span);
+            self.session.error(loc, msg);
+        }
+    }
+
+    /// Reports a warning at the given `span` if the current pass is the final
+    /// pass.
+    fn warning(&mut self, span: Span, msg: impl Display) {
+        if self.last {
+            let loc = self.loc(span);
+            self.session.warning(loc, msg);
+        }
+    }
+}
+
+impl Context for Assembler<'_> {
+    fn symbol(&mut self, span: Span, name: Name) -> Option<Value> {
+        if name == Name::DOT {
+            return Some(self.address(self.section, self.here));
+        }
+
+        let name   = self.resolve(name);
+        let loc    = self.loc(span);
+        let last   = self.last;
+        let symbol = self.symbols.entry(name);
+
+        if last {
+            symbol.refs.push(loc);
+        }
+
+        match (symbol.def, symbol.section) {
+            (Some(_), _) if symbol.scope == Scope::Weak => {
+                // Another unit may override
+                Some(Value::at(Base::Symbol(name), 0))
+            },
+            (Some(_), Some(s)) => {
+                let value = self.sections[s].base.wrapping_add(symbol.value);
+                Some(self.address(s, value))
+            },
+            (Some(_), None) => {
+                Some(Value::Const(symbol.value as i64))
+            },
+            (None, _) if !self.session.names()[name].starts_with('.') => {
+                // Assume defined in another unit
+                Some(Value::at(Base::Symbol(name), 0))
+            },
+            (None, _) => {
+                // Assume forward reference until final pass
+                self.error(span, format!(
+                    "undefined symbol '{}'", &self.session.names()[name]
+                ));
+                if last { None } else { Some(Value::Const(0)) }
+            },
+        }
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        self.target.as_ref()?.builtin(name)
+    }
+
+    fn error(&mut self, span: Span, msg: &str) {
+        Assembler::error(self, span, msg)
+    }
+}
+
+impl Assembler<'_> {
+    /// Returns the value of the given address in the section with index `s`:
+    /// constant if the section is fixed, relative to the section otherwise.
+    fn address(&self, s: usize, addr: u64) -> Value {
+        match self.sections[s].fixed {
+            true  => Value::Const(addr as i64),
+            false => Value::at(Base::Section(s), addr as i64),
+        }
+    }
+
+    /// Returns the name of the symbol to which the given name in an
+    /// expression refers.
+    fn resolve(&mut self, name: Name) -> Name {
+        if !self.session.names()[name].starts_with('.') {
+            return name;
+        }
+
+        // Prefer the local label in the current scope, then in each
+        // enclosing scope, innermost first
+        let local = self.qualify(name);
+
+        for scope in [self.scope].into_iter().chain(self.scopes.clone().into_iter().rev()) {
+            let qualified = self.qualify_in(scope, name);
+            if self.symbols.get(qualified).is_some_and(|s| s.is_defined()) {
+                return qualified;
+            }
+        }
+
+        match self.symbols.get(name) {
+            Some(s) if s.is_defined() => name,
+            _                         => local,
+        }
+    }
+}
+
+impl Emitter for Assembler<'_> {
+    fn names(&self) -> &NameTable {
+        self.session.names()
+    }
+
+    fn here(&self) -> u64 {
+        self.here
+    }
+
+    fn section(&self) -> usize {
+        self.section
+    }
+
+    fn is_final(&self) -> bool {
+        self.last
+    }
+
+    fn eval(&mut self, expr: &Expr<Span>) -> Option<Value> {
+        self.eval_value(expr)
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        Assembler::emit(self, bytes)
+    }
+
+    fn align(&mut self, align: u64) {
+        Assembler::align(self, align)
+    }
+
+    fn relayout(&mut self) {
+        self.changed = true;
+    }
+
+    fn relax(&mut self, count: usize, fits: &dyn Fn(usize) -> bool) -> usize {
+        Assembler::relax(self, count, fits)
+    }
+
+    fn reloc(&mut self, expr: &Expr<Span>, kind: RelocKind) {
+        self.add_reloc(expr, kind)
+    }
+
+    fn error(&mut self, span: Span, msg: &str) {
+        Assembler::error(self, span, msg)
+    }
+
+    fn warning(&mut self, span: Span, msg: &str) {
+        if self.last {
+            let loc = self.loc(span);
+            self.session.warning(loc, msg);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// String encodings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16,
+}
+
+/// Returns whether `value` is representable in `bits` bits as either a signed
+/// or an unsigned integer.
+pub fn fits(value: i64, bits: usize) -> bool {
+    bits >= 64 || {
+        let min = -1i64 << (bits - 1);
+        let max = (1i64 << bits) - 1;
+        (min..=max).contains(&value)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::session::Session;
+    use super::*;
+
+    fn assemble(session: &mut Session, content: &str) -> Unit {
+        session.set_quiet(true);
+        session.assemble("test.s", content)
+    }
+
+    #[test]
+    fn data() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+            .int8 1, x'FF
+            .int16 x'1234
+            .int32 -1
+            .asciiz \"hi\"
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [
+            0x01, 0xFF, 0x12, 0x34, 0xFF, 0xFF, 0xFF, 0xFF, b'h', b'i', 0x00
+        ]);
+    }
+
+    #[test]
+    fn associativity() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, ".int8 10 - 2 - 1, 64 / 4 / 2, 1 << 2 << 1");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [7, 8, 8]);
+    }
+
+    #[test]
+    fn builtin_functions() {
+        let mut session = Session::new();
+        session.set_target(crate::target::find("6502").unwrap());
+
+        let unit = assemble(&mut session, ".int8 lo(x'1234), hi(x'1234), hi(-1), lo(x'100) + 1");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [0x34, 0x12, 0xFF, 0x01]);
+
+        assemble(&mut session, ".int8 foo(1), lo(1, 2), lo8(1)");
+
+        assert_eq!(session.error_count(), 3);
+        assert_eq!(session.diagnostics()[0].msg, "expected: builtin function");
+        assert_eq!(session.diagnostics()[1].msg, "builtin function requires one argument");
+        assert_eq!(session.diagnostics()[2].msg, "expected: builtin function");
+
+        let mut session = Session::new();
+        session.set_target(crate::target::find("avr2").unwrap());
+
+        let unit = assemble(&mut session, ".int8 lo8(x'1234), hi8(x'1234), pm(x'34), lo8(pm(x'246))");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [0x34, 0x12, 0x1A, 0x23]);
+
+        // Without a target, or on a target without them, there are none
+        let mut session = Session::new();
+        session.set_target(crate::target::find("rv32i").unwrap());
+
+        assemble(&mut session, ".int32 hi(x'12345FFC), %hi(x'12345FFC), %lo(x'12345FFC)");
+
+        assert_eq!(session.error_count(), 3);
+        assert_eq!(session.diagnostics()[0].msg, "expected: builtin function");
+        assert_eq!(session.diagnostics()[1].msg, "relocation operator is valid only as an instruction operand");
+        assert_eq!(session.diagnostics()[2].msg, "relocation operator is valid only as an instruction operand");
+
+        let mut session = Session::new();
+
+        assemble(&mut session, ".int8 lo(1)");
+
+        assert_eq!(session.error_count(), 1);
+    }
+
+    #[test]
+    fn forward_reference() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+            .org 0
+            .int16 end - start
+        start:
+            .skip end - start
+        end:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [0x00, 0x00]);
+
+        let unit = assemble(&mut session, "
+            .skip len
+            .int8 len
+            .bss
+            .org 0
+            .skip 3
+        len:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [0x00, 0x00, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn relocatable_values() {
+        let mut session = Session::new();
+        session.set_target(crate::target::find("avr2").unwrap());
+
+        let unit = assemble(&mut session, "
+        start:
+            .int8 end - start
+            .int16 start + 2, lo8(start)
+            .int32 data - start
+        end:
+            .skip end - start
+            .data
+        data:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data.len(), 18);
+        assert_eq!(unit.sections[0].data[0], 9);
+        assert_eq!(unit.relocs.len(), 3);
+
+        assemble(&mut session, "
+        start:
+            .int8 start * 2, ~start, start + data, -start, lo8(start) + 1
+            .skip start
+            .data
+        data:
+        ");
+
+        let msgs = session.diagnostics().iter().map(|d| &d.msg[..]).collect::<Vec<_>>();
+        assert_eq!(msgs, [
+            "operator is not valid on relocatable values",
+            "operator is not valid on relocatable values",
+            "expression cannot be expressed as a relocation",
+            "expression cannot be expressed as a relocation",
+            "expression cannot be expressed as a relocation",
+            "expected: constant expression, not relocatable",
+        ]);
+    }
+
+    #[test]
+    fn structs() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+            .struct Point
+            x:  .int16 1
+            y:  .int16 2
+            .end
+
+            .struct Shape
+            kind:   .int8 7
+                    .align 4
+            pos:    .object Point
+                    .union data
+            radius:     .int32
+            size:       .struct
+            w:              .int16
+            h:              .int16
+                        .end
+                    .end
+            name:   .ascii \"abc\"
+            .end
+
+            .int8 Point.x, Point.y, Point.size
+            .int8 Shape.kind, Shape.pos, Shape.pos.x, Shape.pos.y, Shape.data
+            .int8 Shape.data.radius, Shape.data.size.w, Shape.data.size.h
+            .int8 Shape.name, Shape.size
+            .object Point, y = 9
+            .object Shape, pos.x = -1, data.size.h = x'1234, kind = label
+        label:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [
+            0, 2, 4,
+            0, 4, 4, 6, 8,
+            8, 8, 10,
+            12, 16,
+            0x00, 0x01, 0x00, 0x09,
+            0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x02,
+            0x00, 0x00, 0x12, 0x34, b'a', b'b', b'c', 0x00,
+        ]);
+        assert_eq!(unit.relocs.len(), 1);
+
+        assemble(&mut session, "
+            .struct P
+            x:  .int8 1, 2
+            y:  .int8
+                nop
+            .end
+            .object P, x = 1, z = 2, y
+            .object Q
+            .struct P
+            .end
+            .struct R
+        ");
+
+        let msgs = session.diagnostics().iter().map(|d| &d.msg[..]).collect::<Vec<_>>();
+        assert_eq!(msgs, [
+            "'nop' is not valid in a struct",
+            "field 'x' cannot be initialized",
+            "struct has no field 'z'",
+            "expected: field = value",
+            "undefined struct 'Q'",
+            "struct 'P' is already defined",
+            ".struct block without .end",
+        ]);
+    }
+
+    #[test]
+    fn aliases() {
+        let mut session = Session::new();
+        session.set_target(crate::target::find("m68000").unwrap());
+
+        let unit = assemble(&mut session, "
+        loop:
+            moveq   10, count@d0
+            subq.l  1, count
+            move.l  [ptr@a1 + 4], total@d2
+            add.l   count, total
+            move.l  total, [ptr]
+        next:
+            moveq   0, count
+        ");
+
+        assert_eq!(unit.sections[0].data, [
+            0x70, 0x0A, 0x53, 0x80, 0x24, 0x29, 0x00, 0x04,
+            0xD4, 0x80, 0x22, 0x82,
+        ]);
+        // The alias ends with its scope
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "invalid operands");
+
+        // An alias bound in a block ends with the block
+        let unit = assemble(&mut session, "
+            moveq   1, n@d0
+            .block {
+                moveq   2, n@d1
+            }
+            moveq   3, n
+        ");
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(unit.sections[0].data, [0x70, 0x01, 0x72, 0x02, 0x70, 0x03]);
+    }
+
+    #[test]
+    fn alias_errors() {
+        let mut session = Session::new();
+        session.set_target(crate::target::find("m68000").unwrap());
+
+        assemble(&mut session, "
+            moveq   1, a@d0
+            moveq   2, b@D0
+            move.l  c@sp, d@a7
+            .int8   e@1
+        ");
+
+        assert_eq!(session.error_count(), 1);
+        let msgs = session.diagnostics().iter().map(|d| &d.msg[..]).collect::<Vec<_>>();
+        assert_eq!(msgs, [
+            "aliases 'a' and 'b' both stand for the same register",
+            "aliases 'c' and 'd' both stand for the same register",
+            "alias is valid only as an instruction operand",
+        ]);
+    }
+
+    #[test]
+    fn blocks() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+            .org 0
+        start:
+            .int8 .a
+            .block {
+            .a: .int8 .a, .b
+            }
+            .block {
+            .a: .int8 .a
+                .block {
+                    .int8 .a, .c
+                }
+            .c:
+            }
+        .b: .int8 .a
+        .a:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [7, 1, 6, 3, 3, 6, 7]);
+
+        // The linker resolves references from a block to enclosing scopes
+        let mut unit = assemble(&mut session, "
+        start:
+            .block {
+                .int8 .a
+            .a: .int8 .a
+            }
+        .a: .int8 .a
+        ");
+
+        let program = crate::link::link(
+            std::slice::from_mut(&mut unit), &crate::link::Layout::new(), &mut session
+        );
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(program.sections[0].data, [1, 1, 2]);
+
+        assemble(&mut session, ".block 1");
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "expected: block");
+
+        // A block is not a value
+        session.set_target(crate::target::find("m68000").unwrap());
+        assemble(&mut session, ".int16 {\n.int8 1\n}\nmoveq {\nnop\n}, d0");
+
+        let diags = session.diagnostics().iter().skip(1).map(|d| (d.loc.as_ref().unwrap().span.line, &d.msg[..]));
+        assert_eq!(diags.collect::<Vec<_>>(), [
+            (1, "block is valid only as the argument of .block"),
+            (4, "block is valid only as the argument of .block"),
+        ]);
+    }
+
+    #[test]
+    fn macros() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+            .org 0
+            .define N = 3
+            .define sum(a, b = 1) = a + b
+            .int8 N, sum(N), sum(N, 4)
+
+            .macro pair x, y = 0
+                .int8 x, y
+            .end pair
+            pair 5
+            pair 6, N
+
+            .macro bytes +rest
+                .int8 rest
+            .end
+            bytes 1, 2, 3
+
+            .macro later !at, lazy
+                .int8 0
+                .int8 at, lazy
+            .end
+            later ., .
+
+            .macro label
+            .x: .int8 .x
+            .end
+        start:
+            label
+            label
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [3, 4, 7, 5, 0, 6, 3, 1, 2, 3, 0, 10, 11, 13, 14]);
+
+        // A block argument renders where its parameter is a statement
+        let unit = assemble(&mut session, "
+            .org 0
+            .define ONE = {
+                .int8 1
+            }
+            .macro twice body
+                body
+                .block body
+            .end
+            twice {
+            .a: .int8 .a
+            }
+            .block ONE
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [0, 1, 1]);
+
+        // Inline macros end with the block that defines or removes them
+        let unit = assemble(&mut session, "
+            .org 0
+            .define N = 7
+            .block {
+                .define N = 8
+                .int8 N
+                .block {
+                    .undef N
+                    .int8 N
+                }
+                .int8 N
+            }
+            .int8 N
+        N:
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [8, 4, 8, 7]);
+
+        let mut session = Session::new();
+
+        assemble(&mut session, "
+            .define N = 1
+            .undef N
+            .undef N
+            .macro m
+            .end
+            .unmac m
+            .unmac m
+            m
+            .macro m a, b = 1
+                m a
+            .end x
+            m
+            m 1, 2, 3
+            m 1
+            .define f(+a, b) = a
+            .macro n
+        ");
+
+        let diags = session.diagnostics()
+            .iter()
+            .map(|d| (d.level, d.loc.unwrap().span.line, &d.msg[..]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(diags, [
+            (Level::Warning, 4,  "inline macro 'N' is not defined"),
+            (Level::Warning, 8,  "macro 'm' is not defined"),
+            (Level::Error,   9,  "unknown instruction 'm'"),
+            (Level::Error,   12, "expected: .end m"),
+            (Level::Error,   13, "macro 'm' requires argument 'a'"),
+            (Level::Error,   14, "too many arguments to macro 'm'"),
+            (Level::Error,   11, "macro 'm' expands within itself"),
+            (Level::Error,   16, "parameter follows one that takes remaining arguments"),
+            (Level::Error,   17, ".macro without .end"),
+        ]);
+    }
+
+    #[test]
+    fn messages() {
+        let mut session = Session::new();
+
+        assemble(&mut session, "
+            .org x'100
+        start:
+            .print \"start is {} = {:x} = {:o} = {:b}\", start, start, start, start
+            .warning \"{{literal}} {:d}\", -1
+            .error \"size {} too large\", end - start
+            .int8 0, 0
+        end:
+            .print 42
+            .print \"{}\"
+            .print \"{}\", 1, 2
+            .print \"{:q}\", 1
+            .print \"{\", 1
+            .print \"}\"
+            .error \"bad {}\", ?
+        ");
+
+        let diags = session.diagnostics()
+            .iter()
+            .map(|d| (d.level, d.loc.unwrap().span.line, &d.msg[..]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(diags, [
+            (Level::Note,    4,  "start is 256 = x'100 = o'400 = b'100000000"),
+            (Level::Warning, 5,  "{literal} -1"),
+            (Level::Error,   6,  "size 2 too large"),
+            (Level::Error,   9,  "expected: message string"),
+            (Level::Error,   10, "message has more placeholders than values"),
+            (Level::Error,   11, "message has more values than placeholders"),
+            (Level::Error,   12, "invalid placeholder '{:q}' in message"),
+            (Level::Error,   13, "unterminated placeholder in message"),
+            (Level::Error,   14, "unmatched '}' in message"),
+            (Level::Error,   15, "expected: value"),
+        ]);
+    }
+
+    #[test]
+    fn symbols() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, "
+        foo::
+            .int32 .loop
+        .loop:
+            .int8 0
+            .section .bss
+        bar:?
+            .skip 8
+        ");
+
+        let names  = session.names_mut();
+        let foo    = names.add("foo");
+        let loop_  = names.add("foo.loop");
+        let bar    = names.add("bar");
+        let foo    = unit.symbols.get(foo)  .unwrap();
+        let loop_  = unit.symbols.get(loop_).unwrap();
+        let bar    = unit.symbols.get(bar)  .unwrap();
+
+        assert_eq!((foo  .scope, foo  .section, foo  .value, foo  .size), (Scope::Public, Some(0), 0, 4));
+        assert_eq!((loop_.scope, loop_.section, loop_.value, loop_.size), (Scope::Local,  Some(0), 4, 1));
+        assert_eq!((bar  .scope, bar  .section, bar  .value, bar  .size), (Scope::Weak,   Some(1), 0, 8));
+        assert_eq!(loop_.refs.len(), 1);
+        assert_eq!(loop_.refs[0].span.line, 3);
+    }
+
+    #[test]
+    fn include() {
+        let dir = std::env::temp_dir().join(format!("ras-include-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.s"),   ".int8 1\n.incbin \"c.bin\", 1\n").unwrap();
+        std::fs::write(dir.join("c.bin"), [7, 8, 9]).unwrap();
+
+        let mut session = Session::new();
+        session.set_quiet(true);
+        let path = dir.join("a.s");
+        let unit = session.assemble(&path.to_string_lossy(), ".include \"b.s\"\n.int8 2");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [1, 8, 9, 2]);
+        assert_eq!(unit.deps.len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn undefined() {
+        let mut session = Session::new();
+
+        assemble(&mut session, ".int8 .nope");
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "undefined symbol '.nope'");
+    }
+
+    #[test]
+    fn external() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, ".int8 0\n.int16 nope + 1");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.relocs.len(),        1);
+        assert_eq!(unit.relocs[0].offset,    1);
+        assert_eq!(unit.relocs[0].kind.size, 2);
+    }
+
+    #[test]
+    fn not_converged() {
+        let mut session = Session::new();
+
+        assemble(&mut session, "start:\n.skip end - start + 1\nend:");
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "layout did not converge");
+        assert_eq!(session.diagnostics()[0].loc.unwrap().span.line, 3);
+    }
+
+    #[test]
+    fn huge_skip() {
+        let mut session = Session::new();
+
+        let unit = assemble(&mut session, ".int8 1\n.skip x'7FFFFFFFFFFF\n.org x'7FFFFFFFFFFF");
+
+        assert_eq!(session.error_count(), 2);
+        assert_eq!(unit.sections[0].data, [1]);
+
+        let unit = assemble(&mut session, ".bss\n.skip x'7FFFFFFFFFFF");
+
+        assert_eq!(session.error_count(), 2);
+        assert_eq!(unit.sections[1].size, 0x7FFF_FFFF_FFFF);
+    }
+
+    #[test]
+    fn redefined() {
+        let mut session = Session::new();
+
+        assemble(&mut session, "a: a:");
+
+        assert_eq!(session.error_count(), 1);
+    }
+}
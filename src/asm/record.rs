@@ -0,0 +1,511 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Record layouts.
+//!
+//! A `.struct` or `.union` block defines a record type.  Within the block,
+//! labels name fields, and data directives reserve space for them without
+//! emitting bytes.  The arguments of a data directive, if any, become the
+//! default value of its field.  At the end of the block, the assembler
+//! defines an absolute symbol for the offset of each field and for the size
+//! of the record.  The `.object` directive emits an instance of a record,
+//! optionally overriding the defaults of some fields.
+
+use std::rc::Rc;
+
+use crate::lang::ast::*;
+use crate::name::Name;
+use crate::session::{Level, Loc};
+
+use super::{Assembler, Encoding, RelocKind, fits};
+
+// ----------------------------------------------------------------------------
+
+/// Record type.
+#[derive(Clone, Default, Debug)]
+pub(super) struct Record {
+    /// Size in bytes, a multiple of the alignment.
+    size: u64,
+
+    /// Required alignment of an instance.
+    align: u64,
+
+    /// Fields, in order of appearance.  Nested fields follow the field that
+    /// contains them.
+    fields: Vec<Field>,
+
+    /// Content of an instance with default values.
+    image: Vec<u8>,
+}
+
+/// Field of a record type.
+#[derive(Clone, Debug)]
+struct Field {
+    /// Name relative to the record, with nested names joined by `.`.
+    path: String,
+
+    /// Offset from the start of the record.
+    offset: u64,
+
+    /// Size in bytes.
+    size: u64,
+
+    /// Size of the integer that the field holds, if it holds exactly one.
+    /// Only such a field accepts an initializer.
+    int: Option<usize>,
+
+    /// Location of the definition.
+    span: Span,
+}
+
+/// Record type being defined.
+#[derive(Clone, Debug)]
+pub(super) struct OpenRecord {
+    /// Name of the record type.
+    name: Name,
+
+    /// Location of the `.struct` or `.union` directive.
+    loc: Loc,
+
+    /// Record laid out so far.
+    record: Record,
+
+    /// Open blocks, from outermost to innermost.
+    frames: Vec<Frame>,
+
+    /// Index of the field that a label named and the next statement sizes,
+    /// if any.
+    label: Option<usize>,
+}
+
+/// Open `.struct` or `.union` block within a record type.
+#[derive(Clone, Debug)]
+struct Frame {
+    /// Whether members overlap.
+    union: bool,
+
+    /// Prefix of the names of fields in the block.
+    prefix: String,
+
+    /// Offset of the block from the start of the record.
+    start: u64,
+
+    /// Offset of the end of the content of the block so far.
+    end: u64,
+
+    /// Index of the field that names the block, if any.
+    field: Option<usize>,
+}
+
+impl OpenRecord {
+    /// Returns the offset at which the next member begins.
+    fn here(&self) -> u64 {
+        let frame = self.frames.last().unwrap();
+        if frame.union { frame.start } else { frame.end }
+    }
+
+    /// Reserves `size` bytes for the next member, aligned to `align`, and
+    /// returns its offset.  The field that a preceding label named, if any,
+    /// becomes the member.
+    fn place(&mut self, size: u64, align: u64, int: Option<usize>) -> u64 {
+        let at    = self.here().next_multiple_of(align);
+        let frame = self.frames.last_mut().unwrap();
+        frame.end = frame.end.max(at + size);
+
+        self.record.align = self.record.align.max(align);
+
+        if let Some(i) = self.label.take() {
+            let field  = &mut self.record.fields[i];
+            field.offset = at;
+            field.size   = size;
+            field.int    = int;
+        }
+
+        at
+    }
+
+    /// Stores `bytes` into the default content at offset `at`.
+    fn write(&mut self, at: u64, bytes: &[u8]) {
+        let at    = at as usize;
+        let image = &mut self.record.image;
+        if image.len() < at + bytes.len() {
+            image.resize(at + bytes.len(), 0);
+        }
+        image[at..][..bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Adds a field with the given name, relative to the innermost block, at
+    /// the next offset.  Returns its index.
+    fn add_field(&mut self, name: &str, span: Span) -> usize {
+        let path = format!("{}{}", self.frames.last().unwrap().prefix, name);
+        let here = self.here();
+        self.record.fields.push(Field { path, offset: here, size: 0, int: None, span });
+        self.record.fields.len() - 1
+    }
+}
+
+impl Assembler<'_> {
+    pub(super) fn dir_struct(&mut self, dir: &Dir<Span>, union: bool) {
+        let name = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(_, name))] => name,
+            _ => {
+                return self.error(dir.data, "expected: struct name");
+            },
+        };
+
+        self.record = Some(OpenRecord {
+            name,
+            loc:    self.loc(dir.data),
+            record: Record { align: 1, ..Record::default() },
+            frames: vec![Frame { union, prefix: String::new(), start: 0, end: 0, field: None }],
+            label:  None,
+        });
+    }
+
+    /// Processes a statement within a record type definition.
+    pub(super) fn record_stmt(&mut self, stmt: &Stmt<Span>) {
+        let dir = match *stmt {
+            Stmt::Label(ref label) => return self.record_label(label),
+            Stmt::Dir(ref dir)     => dir,
+        };
+
+        match dir.name {
+            Name::DOT_NOP     => (),
+            Name::DOT_PRINT   => self.dir_message(dir, Level::Note),
+            Name::DOT_WARNING => self.dir_message(dir, Level::Warning),
+            Name::DOT_ERROR   => self.dir_message(dir, Level::Error),
+            Name::DOT_STRUCT  => self.record_nested(dir, false),
+            Name::DOT_UNION   => self.record_nested(dir, true),
+            Name::DOT_END     => self.record_end(),
+            Name::DOT_SKIP    => self.record_skip(dir),
+            Name::DOT_ALIGN   => self.record_align(dir),
+            Name::DOT_INT8    => self.record_int(dir, 1),
+            Name::DOT_INT16   => self.record_int(dir, 2),
+            Name::DOT_INT32   => self.record_int(dir, 4),
+            Name::DOT_INT64   => self.record_int(dir, 8),
+            Name::DOT_FLOAT32 => self.record_float(dir, 4),
+            Name::DOT_FLOAT64 => self.record_float(dir, 8),
+            Name::DOT_ASCII   => self.record_str(dir, Encoding::Ascii, false),
+            Name::DOT_ASCIIZ  => self.record_str(dir, Encoding::Ascii, true),
+            Name::DOT_UTF8    => self.record_str(dir, Encoding::Utf8,  false),
+            Name::DOT_UTF8Z   => self.record_str(dir, Encoding::Utf8,  true),
+            Name::DOT_UTF16   => self.record_str(dir, Encoding::Utf16, false),
+            Name::DOT_UTF16Z  => self.record_str(dir, Encoding::Utf16, true),
+            Name::DOT_NEW     => self.record_object(dir),
+            name => {
+                self.error(dir.data, format!(
+                    "'{}' is not valid in a struct", &self.session.names()[name]
+                ));
+            },
+        }
+    }
+
+    fn record_label(&mut self, label: &Label<Span>) {
+        let names = self.session.names();
+        let name  = names[label.name].trim_start_matches('.').to_string();
+        let open  = self.record.as_mut().unwrap();
+
+        open.label = Some(open.add_field(&name, label.data));
+    }
+
+    fn record_nested(&mut self, dir: &Dir<Span>, union: bool) {
+        let open = self.record.as_mut().unwrap();
+
+        let field = match dir.args[..] {
+            [] => open.label.take(),
+            [Arg::Expr(Expr::Ident(span, name))] => {
+                let name = self.session.names()[name].to_string();
+                Some(open.add_field(&name, span))
+            },
+            _ => {
+                return self.error(dir.data, "expected: field name");
+            },
+        };
+
+        let start  = open.here();
+        let prefix = match field {
+            Some(i) => {
+                open.record.fields[i].offset = start;
+                format!("{}.", open.record.fields[i].path)
+            },
+            None => open.frames.last().unwrap().prefix.clone(),
+        };
+
+        open.frames.push(Frame { union, prefix, start, end: start, field });
+    }
+
+    fn record_end(&mut self) {
+        let open = self.record.as_mut().unwrap();
+
+        if open.frames.len() == 1 {
+            return self.end_record();
+        }
+
+        let frame = open.frames.pop().unwrap();
+
+        if let Some(i) = frame.field {
+            open.record.fields[i].size = frame.end - frame.start;
+        }
+
+        let parent = open.frames.last_mut().unwrap();
+        parent.end = parent.end.max(frame.end);
+    }
+
+    fn record_skip(&mut self, dir: &Dir<Span>) {
+        match self.eval_one_arg(dir) {
+            Some(len) if len >= 0 => { self.record.as_mut().unwrap().place(len as u64, 1, None); },
+            Some(_)               => self.error(dir.data, "skip length must not be negative"),
+            None                  => (),
+        }
+    }
+
+    fn record_align(&mut self, dir: &Dir<Span>) {
+        match self.eval_one_arg(dir) {
+            Some(align) if align > 0 => {
+                let open  = self.record.as_mut().unwrap();
+                let frame = open.frames.last_mut().unwrap();
+                frame.end = frame.end.next_multiple_of(align as u64);
+                open.record.align = open.record.align.max(align as u64);
+            },
+            Some(_) => self.error(dir.data, "alignment must be positive"),
+            None    => (),
+        }
+    }
+
+    fn record_int(&mut self, dir: &Dir<Span>, size: usize) {
+        let mut bytes = vec![];
+
+        for arg in &dir.args {
+            let value = match *arg {
+                Arg::Expr(ref e) => self.eval_const(e).unwrap_or(0),
+                Arg::Unknown(_)  => 0,
+            };
+
+            if !fits(value, size * 8) {
+                self.error(*arg.data(), format!(
+                    "value {} does not fit in {} bits", value, size * 8
+                ));
+            }
+
+            bytes.extend(self.int_bytes(value as u64, size));
+        }
+
+        let int = (dir.args.len() <= 1).then_some(size);
+        bytes.resize(bytes.len().max(size), 0);
+        self.record_bytes(&bytes, int);
+    }
+
+    fn record_float(&mut self, dir: &Dir<Span>, size: usize) {
+        let mut bytes = vec![];
+
+        for arg in &dir.args {
+            let bits = self.float_bits(arg, size);
+            bytes.extend(self.int_bytes(bits, size));
+        }
+
+        bytes.resize(bytes.len().max(size), 0);
+        self.record_bytes(&bytes, None);
+    }
+
+    fn record_str(&mut self, dir: &Dir<Span>, encoding: Encoding, terminate: bool) {
+        let mut bytes = vec![];
+
+        for arg in &dir.args {
+            bytes.extend(self.encode_str(arg, encoding, terminate));
+        }
+
+        self.record_bytes(&bytes, None);
+    }
+
+    /// Adds a member with the given default content.
+    fn record_bytes(&mut self, bytes: &[u8], int: Option<usize>) {
+        let open = self.record.as_mut().unwrap();
+        let at   = open.place(bytes.len() as u64, 1, int);
+        open.write(at, bytes);
+    }
+
+    /// Embeds an instance of another record type as a member.
+    fn record_object(&mut self, dir: &Dir<Span>) {
+        let inner = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(span, name))] => match self.find_record(span, name) {
+                Some(inner) => inner,
+                None        => return,
+            },
+            _ => {
+                return self.error(dir.data, "expected: struct name");
+            },
+        };
+
+        let open   = self.record.as_mut().unwrap();
+        let prefix = match open.label {
+            Some(i) => format!("{}.", open.record.fields[i].path),
+            None    => open.frames.last().unwrap().prefix.clone(),
+        };
+
+        let at = open.place(inner.size, inner.align, None);
+        open.write(at, &inner.image);
+
+        for field in &inner.fields {
+            open.record.fields.push(Field {
+                path:   format!("{}{}", prefix, field.path),
+                offset: at + field.offset,
+                span:   dir.data,
+                ..*field
+            });
+        }
+    }
+
+    /// Completes the definition of the open record type.
+    fn end_record(&mut self) {
+        let OpenRecord { name, loc, mut record, frames, .. } = self.record.take().unwrap();
+
+        // Pad to the alignment, so that consecutive instances stay aligned
+        record.size = frames[0].end.next_multiple_of(record.align);
+        record.image.resize(record.size as usize, 0);
+
+        let prefix = self.session.names()[name].to_string();
+
+        if self.records.get(&name).is_some_and(|&(pass, _)| pass == self.pass) {
+            return self.error(loc.span, format!("struct '{}' is already defined", prefix));
+        }
+
+        for field in &record.fields {
+            let symbol = self.session.names_mut().add(&format!("{}.{}", prefix, field.path));
+            self.define(symbol, Scope::Private, None, field.offset, field.span);
+        }
+
+        let symbol = self.session.names_mut().add(&format!("{}.size", prefix));
+        self.define(symbol, Scope::Private, None, record.size, loc.span);
+
+        self.records.insert(name, (self.pass, Rc::new(record)));
+    }
+
+    pub(super) fn dir_object(&mut self, dir: &Dir<Span>) {
+        let record = match dir.args.first() {
+            Some(&Arg::Expr(Expr::Ident(span, name))) => match self.find_record(span, name) {
+                Some(record) => record,
+                None         => return,
+            },
+            _ => {
+                return self.error(dir.data, "expected: struct name");
+            },
+        };
+
+        let mut inits = vec![];
+
+        for arg in &dir.args[1..] {
+            let (span, name, expr) = match *arg {
+                Arg::Expr(Expr::Binary(_, BinOp::Assign, ref lhs, ref rhs)) => match **lhs {
+                    Expr::Ident(span, name) => (span, name, &**rhs),
+                    _ => {
+                        self.error(*lhs.data(), "expected: field name");
+                        continue;
+                    },
+                },
+                _ => {
+                    self.error(*arg.data(), "expected: field = value");
+                    continue;
+                },
+            };
+
+            let path  = &self.session.names()[name];
+            let field = match record.fields.iter().find(|f| f.path == path) {
+                Some(field) => field,
+                None => {
+                    self.error(span, format!("struct has no field '{}'", path));
+                    continue;
+                },
+            };
+
+            match field.int {
+                Some(size) => inits.push((field.offset, size, field.path.as_str(), expr)),
+                None       => self.error(span, format!("field '{}' cannot be initialized", path)),
+            }
+        }
+
+        inits.sort_by_key(|&(offset, ..)| offset);
+
+        for pair in inits.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if a.0 + a.1 as u64 > b.0 {
+                self.error(*b.3.data(), format!(
+                    "initializer of field '{}' overlaps field '{}'", b.2, a.2
+                ));
+            }
+        }
+
+        // Reserve space only in an uninitialized section
+        if !self.sections[self.section].has_data() {
+            if let Some(&(.., expr)) = inits.first() {
+                self.error(*expr.data(), "initialized data in uninitialized section");
+            }
+            return self.sections[self.section].reserve(record.size);
+        }
+
+        let mut at = 0;
+
+        for (offset, size, _, expr) in inits {
+            if offset < at {
+                continue;
+            }
+
+            self.emit(&record.image[at as usize..offset as usize]);
+
+            self.reloc = false;
+            let value  = self.eval(expr).unwrap_or(0);
+
+            if self.reloc {
+                self.add_reloc(expr, RelocKind::int(size));
+                self.emit_int(0, size);
+            } else {
+                if !fits(value, size * 8) {
+                    self.error(*expr.data(), format!(
+                        "value {} does not fit in {} bits", value, size * 8
+                    ));
+                }
+                self.emit_int(value as u64, size);
+            }
+
+            at = offset + size as u64;
+        }
+
+        self.emit(&record.image[at as usize..]);
+    }
+
+    /// Returns the record type with the given `name`, referenced at the given
+    /// `span`.  Reports an error if there is none.
+    fn find_record(&mut self, span: Span, name: Name) -> Option<Rc<Record>> {
+        match self.records.get(&name) {
+            Some((_, record)) => Some(record.clone()),
+            None => {
+                self.error(span, format!("undefined struct '{}'", &self.session.names()[name]));
+                None
+            },
+        }
+    }
+
+    /// Reports an error if a record type definition is open at the end of a
+    /// pass.
+    pub(super) fn end_records(&mut self) {
+        if let Some(open) = self.record.take() {
+            if self.last {
+                self.session.error(open.loc, ".struct block without .end");
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Relaxation of span-dependent instructions.
+//!
+//! An instruction is *span-dependent* if the target has several encodings of
+//! it that differ in size, the shorter ones reaching a smaller range of
+//! operand values.  A typical example is a branch with short and long forms.
+//! Because the size of such an instruction affects the addresses after it,
+//! which in turn affect the operands of other such instructions, the choice
+//! of forms is part of layout.
+//!
+//! The assembler identifies each span-dependent instruction by the order in
+//! which it occurs in a pass.  Relaxation starts each instruction in its
+//! shortest form and, in each layout pass after the first, grows any
+//! instruction whose operand does not fit its current form.  Forms never
+//! shrink, so the passes reach a fixpoint.  The final pass reuses the forms
+//! of the pass before it.
+
+use super::Assembler;
+
+impl Assembler<'_> {
+    /// Chooses the form of the current span-dependent instruction.  See
+    /// [`Emitter::relax`](crate::target::Emitter::relax).
+    pub(super) fn relax(&mut self, count: usize, fits: &dyn Fn(usize) -> bool) -> usize {
+        let number = self.relaxed;
+        self.relaxed += 1;
+
+        if self.forms.len() <= number {
+            self.forms.resize(number + 1, 0);
+        }
+
+        let prev = self.forms[number];
+
+        if self.last {
+            return prev;
+        }
+
+        // Forward references are unknown in the first pass
+        if self.pass == 1 {
+            if !fits(prev) {
+                self.changed = true;
+            }
+            return prev;
+        }
+
+        // Grow to the shortest form that fits, or the longest if none does
+        let form = (prev..count).find(|&f| fits(f)).unwrap_or(count - 1).max(prev);
+
+        if form != prev {
+            self.forms[number] = form;
+            self.changed       = true;
+            self.grew          = true;
+        }
+
+        form
+    }
+}
@@ -0,0 +1,109 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Lexical scopes.
+//!
+//! The `.block` directive renders a block of statements in place, within a
+//! scope of its own.  Local labels that the block defines do not clash with
+//! those outside it, and a local label reference inside the block prefers a
+//! label of the block, then one of each enclosing scope.  Aliases bound
+//! within the block end with it, and those of the enclosing scope resume.
+//! Likewise for inline macros that the block defines or removes.
+
+use std::mem;
+
+use crate::lang::ast::*;
+use crate::name::Name;
+
+use super::Assembler;
+
+// ----------------------------------------------------------------------------
+
+impl Assembler<'_> {
+    pub(super) fn dir_block(&mut self, dir: &Dir<Span>) {
+        let block = match dir.args[..] {
+            [Arg::Expr(Expr::Block(ref block))] => block,
+            _ => {
+                return self.error(dir.data, "expected: block");
+            },
+        };
+
+        self.scoped(block);
+    }
+
+    /// Assembles the given block in place, within a scope of its own.
+    pub(super) fn scoped(&mut self, block: &Block<Span>) {
+        // Name the scope after the enclosing one and the count of blocks
+        self.blocks += 1;
+        let names = self.session.names_mut();
+        let name  = format!("{}{{{}}}", &names[self.scope], self.blocks);
+        let name  = names.add(&name);
+
+        let outer   = mem::replace(&mut self.scope, name);
+        let aliases = self.aliases.clone();
+        self.scopes.push(outer);
+
+        self.block(block);
+
+        self.scope   = self.scopes.pop().unwrap();
+        self.aliases = aliases;
+
+        let depth = self.scopes.len();
+        self.defines.retain(|d| d.depth <= depth);
+    }
+
+    /// Returns the given expression with each local label name replaced by
+    /// the qualified name of the label to which it refers.  Outside of any
+    /// block, the linker qualifies names in the same way, so the expression
+    /// is unchanged.
+    pub(super) fn resolve_locals(&mut self, expr: &Expr<Span>) -> Expr<Span> {
+        if self.scopes.is_empty() {
+            return expr.clone();
+        }
+
+        self.resolve_names(expr)
+    }
+
+    /// Returns the given expression with each local label name replaced by
+    /// the qualified name of the label to which it refers, even outside of
+    /// any block.
+    pub(super) fn resolve_names(&mut self, expr: &Expr<Span>) -> Expr<Span> {
+        use Expr::*;
+
+        match *expr {
+            Ident(span, name) if name != Name::DOT => {
+                Ident(span, self.resolve(name))
+            },
+            Deref(span, ref inner, post) => {
+                Deref(span, Box::new(self.resolve_names(inner)), post)
+            },
+            Unary(span, op, ref inner) => {
+                Unary(span, op, Box::new(self.resolve_names(inner)))
+            },
+            Binary(span, op, ref lhs, ref rhs) => {
+                let lhs = self.resolve_names(lhs);
+                let rhs = self.resolve_names(rhs);
+                Binary(span, op, Box::new(lhs), Box::new(rhs))
+            },
+            Call(span, ref func, ref args) => {
+                Call(span, func.clone(), args.iter().map(|a| self.resolve_names(a)).collect())
+            },
+            _ => expr.clone(),
+        }
+    }
+}
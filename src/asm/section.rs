@@ -0,0 +1,129 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sections.
+
+use crate::name::Name;
+
+// ----------------------------------------------------------------------------
+
+/// Section kinds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SectionKind {
+    /// Executable code.
+    Code,
+
+    /// Initialized data.
+    Data,
+
+    /// Uninitialized data.  Occupies space but has no content.
+    Bss,
+}
+
+impl SectionKind {
+    /// Returns the kind implied by the given section name.
+    pub fn for_name(name: &str) -> Self {
+        if name.starts_with(".code") || name.starts_with(".text") {
+            Self::Code
+        } else if name.starts_with(".bss") {
+            Self::Bss
+        } else {
+            Self::Data
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Section of assembled output.
+#[derive(Clone, Debug)]
+pub struct Section {
+    /// Name.
+    pub name: Name,
+
+    /// Kind.
+    pub kind: SectionKind,
+
+    /// Address of the first byte of the section.
+    pub base: u64,
+
+    /// Address at which the section is loaded, if different from the address
+    /// at which it runs.  Set by the linker.
+    pub load: Option<u64>,
+
+    /// Whether `.org` fixes the address of the section.  If not, the section
+    /// is relocatable.
+    pub fixed: bool,
+
+    /// Required alignment of the address of the section.
+    pub align: u64,
+
+    /// Bytes emitted into the section.  Empty for [`SectionKind::Bss`].
+    pub data: Vec<u8>,
+
+    /// Size of the section in bytes.
+    pub size: u64,
+}
+
+impl Section {
+    /// Creates a new, empty [`Section`] with the given `name` and `kind`.
+    pub fn new(name: Name, kind: SectionKind) -> Self {
+        Self { name, kind, base: 0, load: None, fixed: false, align: 1, data: Vec::new(), size: 0 }
+    }
+
+    /// Returns the address after the last byte of the section.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.base.wrapping_add(self.size)
+    }
+
+    /// Returns the address at which the section is loaded.
+    #[inline]
+    pub fn load_base(&self) -> u64 {
+        self.load.unwrap_or(self.base)
+    }
+
+    /// Returns whether the section stores content.
+    #[inline]
+    pub fn has_data(&self) -> bool {
+        self.kind != SectionKind::Bss
+    }
+
+    /// Appends the given `bytes` to the section.
+    pub fn emit(&mut self, bytes: &[u8]) {
+        if self.has_data() {
+            self.data.extend_from_slice(bytes);
+        }
+        self.size += bytes.len() as u64;
+    }
+
+    /// Appends `len` zero bytes to the section.
+    pub fn reserve(&mut self, len: u64) {
+        if self.has_data() {
+            self.data.resize(self.data.len() + len as usize, 0);
+        }
+        self.size += len;
+    }
+
+    /// Discards the content of the section in preparation for another pass.
+    pub fn reset(&mut self) {
+        self.data.clear();
+        self.size  = 0;
+        self.align = 1;
+    }
+}
@@ -0,0 +1,158 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Symbols.
+
+use std::collections::HashMap;
+
+use crate::lang::ast::Scope;
+use crate::name::Name;
+use crate::session::Loc;
+
+// ----------------------------------------------------------------------------
+
+/// Symbol.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// Name.  For a [`Scope::Local`] symbol, this is the name qualified by the
+    /// preceding non-local label.
+    pub name: Name,
+
+    /// Scope.
+    pub scope: Scope,
+
+    /// Index of the section containing the symbol, or `None` if the symbol
+    /// has an absolute value.
+    pub section: Option<usize>,
+
+    /// Value.  If the symbol is in a section, this is the offset from the
+    /// start of the section.
+    pub value: u64,
+
+    /// Size in bytes: the distance to the next symbol in the same section or
+    /// to the end of the section.
+    pub size: u64,
+
+    /// Location of the definition, or `None` if the symbol is referenced but
+    /// not defined.
+    pub def: Option<Loc>,
+
+    /// Locations of references to the symbol.
+    pub refs: Vec<Loc>,
+
+    /// Number of the assembly pass that last defined the symbol.
+    pub(super) pass: u32,
+}
+
+impl Symbol {
+    /// Returns whether the symbol is defined.
+    #[inline]
+    pub fn is_defined(&self) -> bool {
+        self.def.is_some()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Table of symbols, in order of first appearance.
+#[derive(Clone, Default, Debug)]
+pub struct SymbolTable {
+    vec: Vec<Symbol>,
+    map: HashMap<Name, usize>,
+}
+
+impl SymbolTable {
+    /// Creates a new, empty [`SymbolTable`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of symbols in the table.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Returns whether the table is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Returns the symbol with the given `name`, if any.
+    pub fn get(&self, name: Name) -> Option<&Symbol> {
+        self.map.get(&name).map(|&i| &self.vec[i])
+    }
+
+    /// Returns the symbol with the given `name`, if any.
+    pub fn get_mut(&mut self, name: Name) -> Option<&mut Symbol> {
+        self.map.get(&name).map(|&i| &mut self.vec[i])
+    }
+
+    /// Returns an iterator over the symbols in the table.
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.vec.iter()
+    }
+
+    /// Returns a mutable iterator over the symbols in the table.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Symbol> {
+        self.vec.iter_mut()
+    }
+
+    /// Returns the symbol with the given `name`, adding an undefined symbol
+    /// if not already present.
+    pub fn entry(&mut self, name: Name) -> &mut Symbol {
+        let index = *self.map.entry(name).or_insert_with(|| {
+            self.vec.push(Symbol {
+                name,
+                scope:   Scope::Private,
+                section: None,
+                value:   0,
+                size:    0,
+                def:     None,
+                refs:    Vec::new(),
+                pass:    0,
+            });
+            self.vec.len() - 1
+        });
+        &mut self.vec[index]
+    }
+
+    /// Computes the size of each symbol in a section, given the sizes of the
+    /// sections.
+    pub fn compute_sizes(&mut self, section_sizes: &[u64]) {
+        let mut order = self.vec
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((s.section?, s.value, i)))
+            .collect::<Vec<_>>();
+
+        order.sort_unstable();
+
+        for (n, &(section, value, index)) in order.iter().enumerate() {
+            let end = order[n + 1..]
+                .iter()
+                .take_while(|&&(s, _, _)| s == section)
+                .map(|&(_, v, _)| v)
+                .find(|&v| v > value)
+                .unwrap_or(section_sizes[section]);
+
+            self.vec[index].size = end.saturating_sub(value);
+        }
+    }
+}
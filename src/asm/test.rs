@@ -0,0 +1,259 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test blocks.
+//!
+//! A `.test` block holds code for the `test` command to run in a simulator.
+//! When the session includes tests, the assembler places the code of each
+//! block in the `.test` section and records the block as a [`Test`].
+//! Otherwise, the assembler skips the content of the block.
+
+use crate::lang::ast::*;
+use crate::name::Name;
+use crate::session::Loc;
+
+use super::{Assembler, SectionKind};
+
+// ----------------------------------------------------------------------------
+
+/// Test block.
+#[derive(Clone, Debug)]
+pub struct Test {
+    /// Name of the test.
+    pub name: String,
+
+    /// Location of the `.test` directive.
+    pub loc: Loc,
+
+    /// Index of the section containing the code of the test.
+    pub section: usize,
+
+    /// Offset within the section at which the code of the test begins.
+    pub start: u64,
+
+    /// Offset within the section at which the code of the test ends.
+    pub end: u64,
+
+    /// Assertions, in order of appearance.
+    pub expects: Vec<Expect>,
+
+    /// Memory to map for the test, in order of appearance.
+    pub maps: Vec<Mapping>,
+}
+
+/// Assertion within a test block.
+#[derive(Clone, Debug)]
+pub struct Expect {
+    /// Offset within the section of the test at which to check the
+    /// assertion.
+    pub offset: u64,
+
+    /// Expression that must evaluate to a nonzero value.
+    pub expr: Expr<Span>,
+
+    /// Name of the innermost non-local label at the assertion, which
+    /// qualifies local labels in the expression.
+    pub scope: Name,
+
+    /// Location of the expression.
+    pub loc: Loc,
+}
+
+/// Memory that the runner maps for a test.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mapping {
+    /// Kind of memory.
+    pub kind: MapKind,
+
+    /// Address of the first byte.
+    pub base: u64,
+
+    /// Size in bytes.
+    pub size: u64,
+}
+
+/// Kinds of memory that a test can map.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapKind {
+    /// Zeroed RAM.
+    Ram,
+
+    /// Loopback device: each read yields the oldest value written and not
+    /// yet read.
+    Echo,
+}
+
+/// Test block being assembled.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct OpenTest {
+    /// Location of the `.test` directive.
+    loc: Loc,
+
+    /// Index of the section that was current before the block.
+    prev: usize,
+
+    /// Whether the assembler skips the content of the block.
+    skip: bool,
+}
+
+impl Assembler<'_> {
+    pub(super) fn dir_test(&mut self, dir: &Dir<Span>) {
+        if self.test.is_some() {
+            return self.error(dir.data, ".test block cannot be nested");
+        }
+
+        let name = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(_, name))] => self.session.names()[name].to_string(),
+            [Arg::Expr(Expr::Str(_, ref s))]  => s.clone(),
+            _ => {
+                return self.error(dir.data, "expected: test name");
+            },
+        };
+
+        let loc  = self.loc(dir.data);
+        let skip = !self.session.tests();
+
+        self.test = Some(OpenTest { loc, prev: self.section, skip });
+
+        if skip {
+            return;
+        }
+
+        // The section has the same name as the directive
+        self.switch_section(Name::DOT_TEST, SectionKind::Code);
+
+        if let Some(align) = self.target.as_ref().map(|t| t.align()) {
+            self.align(align);
+        }
+
+        if self.last {
+            let section = &self.sections[self.section];
+            self.tests.push(Test {
+                name,
+                loc,
+                section: self.section,
+                start:   section.size,
+                end:     section.size,
+                expects: vec![],
+                maps:    vec![],
+            });
+        }
+    }
+
+    pub(super) fn dir_expect(&mut self, dir: &Dir<Span>) {
+        if self.test.is_none() {
+            return self.error(dir.data, ".expect outside of .test block");
+        }
+
+        let expr = match dir.args[..] {
+            [Arg::Expr(ref e)] => e,
+            _ => {
+                return self.error(dir.data, ".expect requires one argument");
+            },
+        };
+
+        if self.last {
+            let offset = self.sections[self.section].size;
+            let loc    = self.loc(*expr.data());
+            let expect = Expect {
+                offset,
+                expr:  self.resolve_locals(expr),
+                scope: self.scope,
+                loc,
+            };
+            if let Some(test) = self.tests.last_mut() {
+                test.expects.push(expect);
+            }
+        }
+    }
+
+    pub(super) fn dir_map(&mut self, dir: &Dir<Span>) {
+        if self.test.is_none() {
+            return self.error(dir.data, ".map outside of .test block");
+        }
+
+        let (kind, base, size) = match dir.args[..] {
+            [Arg::Expr(Expr::Ident(_, kind)), Arg::Expr(ref base), Arg::Expr(ref size)] => {
+                (kind, base, size)
+            },
+            _ => {
+                return self.error(dir.data, "expected: ram or echo, address, and size");
+            },
+        };
+
+        let kind = match kind {
+            Name::RAM  => MapKind::Ram,
+            Name::ECHO => MapKind::Echo,
+            _          => return self.error(dir.data, "expected: ram or echo"),
+        };
+
+        let (base, size) = match (self.eval_const(base), self.eval_const(size)) {
+            (Some(base), Some(size)) => (base as u64, size),
+            _                        => return,
+        };
+
+        if size <= 0 {
+            return self.error(dir.data, "map size must be positive");
+        }
+
+        if self.last {
+            if let Some(test) = self.tests.last_mut() {
+                test.maps.push(Mapping { kind, base, size: size as u64 });
+            }
+        }
+    }
+
+    pub(super) fn dir_end(&mut self, dir: &Dir<Span>) {
+        let open = match self.test.take() {
+            Some(open) => open,
+            None       => return self.error(dir.data, "unexpected .end"),
+        };
+
+        if open.skip {
+            return;
+        }
+
+        if self.last {
+            if let Some(test) = self.tests.last_mut() {
+                test.end = self.sections[self.section].size;
+            }
+        }
+
+        self.section = open.prev;
+    }
+
+    /// Returns whether the assembler skips the given statement because it is
+    /// in a `.test` block that the session excludes.
+    pub(super) fn skips(&self, stmt: &Stmt<Span>) -> bool {
+        match (self.test, stmt) {
+            (Some(open), Stmt::Dir(dir)) => open.skip && dir.name != Name::DOT_END,
+            (Some(open), _)              => open.skip,
+            (None, _)                    => false,
+        }
+    }
+
+    /// Reports an error if a `.test` block is open at the end of a pass.
+    pub(super) fn end_tests(&mut self) {
+        if let Some(open) = self.test.take() {
+            self.section = open.prev;
+            if self.last {
+                self.session.error(open.loc, ".test block without .end");
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Symbolic values.
+//!
+//! An expression that refers to an address the linker has yet to assign
+//! cannot fold to a number at assembly time.  Instead, it folds to a
+//! [`Value`] in terms of such addresses: a constant, an offset from the
+//! start of a relocatable section, the address of an external symbol plus an
+//! addend, or the difference of two addresses plus an addend.  Only addition
+//! and subtraction preserve these forms; any other operator requires
+//! constant operands.  Subtracting addresses in the same section cancels
+//! them, so the distance between two labels is constant even when their
+//! section is relocatable.
+
+use crate::name::Name;
+
+// ----------------------------------------------------------------------------
+
+/// Result of evaluating an expression.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Value {
+    /// Value known at assembly time.
+    Const(i64),
+
+    /// Value that the linker must compute.
+    Reloc(Symbolic),
+}
+
+/// Value that depends on addresses that the linker assigns.
+///
+/// The value is `plus - minus + addend`.  A section-relative value or an
+/// external symbol plus an addend has only `plus`; a difference of addresses
+/// has both.  A builtin function of such a value has neither: the linker
+/// computes it from the whole expression, and no further arithmetic on it is
+/// valid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Symbolic {
+    /// Address that adds to the value, if any.
+    pub plus: Option<Base>,
+
+    /// Address that subtracts from the value, if any.
+    pub minus: Option<Base>,
+
+    /// Provisional value, computed as if relocatable sections began at
+    /// address zero and external symbols were zero.
+    pub addend: i64,
+}
+
+/// Address that the linker assigns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Base {
+    /// Start of the section of the current unit with the given index.
+    Section(usize),
+
+    /// External symbol with the given name.
+    Symbol(Name),
+}
+
+impl Value {
+    /// Returns the offset `addend` from the given `base`.
+    pub fn at(base: Base, addend: i64) -> Self {
+        Self::Reloc(Symbolic { plus: Some(base), minus: None, addend })
+    }
+
+    /// Returns a value that the linker computes by applying a builtin
+    /// function, with the given provisional value.
+    pub fn opaque(addend: i64) -> Self {
+        Self::Reloc(Symbolic { plus: None, minus: None, addend })
+    }
+
+    /// Returns the value, or the provisional value if the linker must
+    /// compute it.
+    pub fn provisional(self) -> i64 {
+        match self {
+            Self::Const(v) => v,
+            Self::Reloc(s) => s.addend,
+        }
+    }
+
+    /// Returns whether the linker can compute the value as a relocation: a
+    /// constant, or a value in terms of addresses with at least one address
+    /// that adds to it.
+    pub fn is_relocatable(self) -> bool {
+        match self {
+            Self::Const(_) => true,
+            Self::Reloc(s) => s.plus.is_some() || s.minus.is_none(),
+        }
+    }
+
+    /// Returns the sum of two values, or `None` if the sum adds more than one
+    /// address or subtracts more than one address.
+    pub fn add(self, other: Self) -> Option<Self> {
+        if let (Self::Const(a), Self::Const(b)) = (self, other) {
+            return Some(Self::Const(a.wrapping_add(b)));
+        }
+
+        if self.is_opaque() || other.is_opaque() {
+            return None;
+        }
+
+        let (a, b) = (self.symbolic(), other.symbolic());
+
+        let mut plus  = [a.plus,  b.plus ].into_iter().flatten().collect::<Vec<_>>();
+        let mut minus = [a.minus, b.minus].into_iter().flatten().collect::<Vec<_>>();
+
+        // Cancel an address that both adds and subtracts
+        plus.retain(|p| match minus.iter().position(|m| m == p) {
+            Some(i) => { minus.remove(i); false },
+            None    => true,
+        });
+
+        if plus.len() > 1 || minus.len() > 1 {
+            return None;
+        }
+
+        let addend = a.addend.wrapping_add(b.addend);
+
+        Some(match (plus.first(), minus.first()) {
+            (None, None) => Self::Const(addend),
+            (p,    m)    => Self::Reloc(Symbolic { plus: p.copied(), minus: m.copied(), addend }),
+        })
+    }
+
+    /// Returns the difference of two values, or `None` if the difference
+    /// adds more than one address or subtracts more than one address.
+    pub fn sub(self, other: Self) -> Option<Self> {
+        self.add(other.neg()?)
+    }
+
+    /// Returns the negation of the value, or `None` if it is the result of a
+    /// builtin function.
+    pub fn neg(self) -> Option<Self> {
+        match self {
+            _ if self.is_opaque() => None,
+            Self::Const(v)        => Some(Self::Const(v.wrapping_neg())),
+            Self::Reloc(s)        => Some(Self::Reloc(Symbolic {
+                plus:   s.minus,
+                minus:  s.plus,
+                addend: s.addend.wrapping_neg(),
+            })),
+        }
+    }
+
+    /// Returns whether the value is the result of a builtin function of an
+    /// address that the linker assigns.
+    fn is_opaque(self) -> bool {
+        matches!(self, Self::Reloc(s) if s.plus.is_none() && s.minus.is_none())
+    }
+
+    fn symbolic(self) -> Symbolic {
+        match self {
+            Self::Const(v) => Symbolic { plus: None, minus: None, addend: v },
+            Self::Reloc(s) => s,
+        }
+    }
+}
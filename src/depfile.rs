@@ -0,0 +1,76 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Makefile-compatible dependency files.
+
+use std::io::{self, Write};
+
+/// Writes a Makefile rule stating that `target` depends on each of `deps`.
+pub fn write_depfile<'a, W, I>(out: &mut W, target: &str, deps: I) -> io::Result<()>
+where
+    W: Write + ?Sized,
+    I: IntoIterator<Item = &'a str>,
+{
+    write!(out, "{}:", escape(target))?;
+
+    for dep in deps {
+        write!(out, " \\\n  {}", escape(dep))?;
+    }
+
+    writeln!(out)
+}
+
+/// Escapes characters in `path` that are special to Make.
+fn escape(path: &str) -> String {
+    let mut s = String::with_capacity(path.len());
+
+    for c in path.chars() {
+        match c {
+            ' ' | '\t' | '#' => s.push('\\'),
+            '$'              => s.push('$'),
+            _                => (),
+        }
+        s.push(c);
+    }
+
+    s
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_special() {
+        assert_eq!(escape("a b#c$d"), "a\\ b\\#c$$d");
+    }
+
+    #[test]
+    fn rule() {
+        let mut out = Vec::new();
+
+        write_depfile(&mut out, "out.bin", ["a.s", "inc/b.s"]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "out.bin: \\\n  a.s \\\n  inc/b.s\n"
+        );
+    }
+}
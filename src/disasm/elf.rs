@@ -0,0 +1,214 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ELF object file reader.
+//!
+//! The reader extracts only what the disassembler needs: the allocated
+//! sections with their addresses and contents, and the symbols that name
+//! addresses within them.
+
+use crate::asm::SectionKind;
+
+use super::{Image, Region};
+
+// ----------------------------------------------------------------------------
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB:   u32 = 2;
+const SHT_NOBITS:   u32 = 8;
+
+const SHF_ALLOC:     u64 = 1 << 1;
+const SHF_EXECINSTR: u64 = 1 << 2;
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC:   u8 = 2;
+
+/// Returns whether the given bytes begin with the ELF magic number.
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7FELF")
+}
+
+/// Reads an ELF file.
+pub fn read(bytes: &[u8]) -> Result<Image, String> {
+    Reader::new(bytes)?.read()
+}
+
+/// Section header.
+#[derive(Clone, Debug)]
+struct Header {
+    name:   u32,
+    kind:   u32,
+    flags:  u64,
+    addr:   u64,
+    offset: u64,
+    size:   u64,
+    link:   u32,
+    entry:  u64,
+}
+
+struct Reader<'a> {
+    bytes:  &'a [u8],
+    wide:   bool,
+    big:    bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        if !is_elf(bytes) || bytes.len() < 16 {
+            return Err("not an ELF file".into());
+        }
+
+        let wide = match bytes[4] {
+            1 => false,
+            2 => true,
+            _ => return Err("invalid ELF class".into()),
+        };
+
+        let big = match bytes[5] {
+            1 => false,
+            2 => true,
+            _ => return Err("invalid ELF data encoding".into()),
+        };
+
+        Ok(Self { bytes, wide, big })
+    }
+
+    fn read(&self) -> Result<Image, String> {
+        let (shoff, shentsize, shnum, shstrndx) = match self.wide {
+            false => (self.u32(32)? as u64, self.u16(46)?, self.u16(48)?, self.u16(50)?),
+            true  => (self.u64(40)?,        self.u16(58)?, self.u16(60)?, self.u16(62)?),
+        };
+
+        let headers = (0..shnum as u64)
+            .map(|i| self.header(shoff + i * shentsize as u64))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let strtab = headers.get(shstrndx as usize).ok_or("invalid section name table")?;
+
+        let mut elf = Image::default();
+
+        for header in &headers {
+            if header.flags & SHF_ALLOC == 0 || header.size == 0 {
+                continue;
+            }
+
+            let kind = match header.kind {
+                SHT_NOBITS                                        => SectionKind::Bss,
+                SHT_PROGBITS if header.flags & SHF_EXECINSTR != 0 => SectionKind::Code,
+                SHT_PROGBITS                                      => SectionKind::Data,
+                _                                                 => continue,
+            };
+
+            let data = match kind {
+                SectionKind::Bss => vec![],
+                _                => self.slice(header.offset, header.size)?.to_vec(),
+            };
+
+            elf.regions.push(Region {
+                name: Some(self.string(strtab, header.name)?),
+                kind,
+                addr: header.addr,
+                size: header.size,
+                data,
+            });
+        }
+
+        for header in headers.iter().filter(|h| h.kind == SHT_SYMTAB) {
+            let names = headers.get(header.link as usize).ok_or("invalid symbol name table")?;
+            let size  = if self.wide { 24 } else { 16 };
+            let count = header.size / header.entry.max(size);
+
+            // Skip the null symbol
+            for i in 1..count {
+                let at = header.offset + i * header.entry.max(size);
+
+                let (name, value, info, shndx) = match self.wide {
+                    false => (self.u32(at)?, self.u32(at + 4)? as u64, self.u8(at + 12)?, self.u16(at + 14)?),
+                    true  => (self.u32(at)?, self.u64(at + 8)?,         self.u8(at + 4)?,  self.u16(at + 6)?),
+                };
+
+                let allocated = headers
+                    .get(shndx as usize)
+                    .is_some_and(|h| h.flags & SHF_ALLOC != 0);
+
+                if allocated && matches!(info & 0xF, STT_NOTYPE | STT_OBJECT | STT_FUNC) {
+                    let name = self.string(names, name)?;
+                    if !name.is_empty() {
+                        elf.symbols.push((value, name));
+                    }
+                }
+            }
+        }
+
+        Ok(elf)
+    }
+
+    fn header(&self, at: u64) -> Result<Header, String> {
+        Ok(match self.wide {
+            false => Header {
+                name:   self.u32(at)?,
+                kind:   self.u32(at + 4)?,
+                flags:  self.u32(at + 8)?  as u64,
+                addr:   self.u32(at + 12)? as u64,
+                offset: self.u32(at + 16)? as u64,
+                size:   self.u32(at + 20)? as u64,
+                link:   self.u32(at + 24)?,
+                entry:  self.u32(at + 36)? as u64,
+            },
+            true => Header {
+                name:   self.u32(at)?,
+                kind:   self.u32(at + 4)?,
+                flags:  self.u64(at + 8)?,
+                addr:   self.u64(at + 16)?,
+                offset: self.u64(at + 24)?,
+                size:   self.u64(at + 32)?,
+                link:   self.u32(at + 40)?,
+                entry:  self.u64(at + 56)?,
+            },
+        })
+    }
+
+    /// Returns the null-terminated string at offset `at` in the string table
+    /// section `table`.
+    fn string(&self, table: &Header, at: u32) -> Result<String, String> {
+        let bytes = self.slice(table.offset, table.size)?;
+        let bytes = bytes.get(at as usize..).ok_or("invalid string offset")?;
+        let len   = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    fn slice(&self, at: u64, len: u64) -> Result<&'a [u8], String> {
+        at.checked_add(len)
+            .and_then(|end| self.bytes.get(at as usize..end as usize))
+            .ok_or_else(|| "unexpected end of file".into())
+    }
+
+    fn int<const N: usize>(&self, at: u64) -> Result<u64, String> {
+        let bytes = self.slice(at, N as u64)?;
+        Ok(match self.big {
+            false => bytes.iter().rev().fold(0, |v, &b| v << 8 | b as u64),
+            true  => bytes.iter()      .fold(0, |v, &b| v << 8 | b as u64),
+        })
+    }
+
+    fn u8 (&self, at: u64) -> Result<u8,  String> { Ok(self.int::<1>(at)? as u8)  }
+    fn u16(&self, at: u64) -> Result<u16, String> { Ok(self.int::<2>(at)? as u16) }
+    fn u32(&self, at: u64) -> Result<u32, String> { Ok(self.int::<4>(at)? as u32) }
+    fn u64(&self, at: u64) -> Result<u64, String> {    self.int::<8>(at)           }
+}
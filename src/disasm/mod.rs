@@ -0,0 +1,414 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Disassembler.
+//!
+//! The disassembler decodes the code of a raw binary or of the sections of
+//! an ELF file and prints ras source that reassembles to the same bytes.
+//! It checks each decoded instruction by reassembling it, and emits bytes
+//! that the target cannot decode, or that do not reassemble exactly, as
+//! `.int8` or `.int16` data.  It labels the targets of jumps, calls, and
+//! branches, and the addresses of ELF symbols.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::asm::{Endian, SectionKind};
+use crate::name::Name;
+use crate::session::{Level, Session};
+use crate::target::{Target, TargetInfo};
+
+pub mod elf;
+
+#[cfg(test)]
+mod tests;
+
+// ----------------------------------------------------------------------------
+
+/// Contiguous range of memory to disassemble.
+#[derive(Clone, Debug)]
+pub struct Region {
+    /// Name of the section, if any.
+    pub name: Option<String>,
+
+    /// Kind of the section.  The disassembler decodes only code.
+    pub kind: SectionKind,
+
+    /// Address of the first byte.
+    pub addr: u64,
+
+    /// Size in bytes.
+    pub size: u64,
+
+    /// Content.  Empty for a `.bss` section.
+    pub data: Vec<u8>,
+}
+
+/// Memory image to disassemble.
+#[derive(Clone, Default, Debug)]
+pub struct Image {
+    /// Regions, in order.  For an ELF file, the allocated sections.
+    pub regions: Vec<Region>,
+
+    /// Symbols that name addresses in the regions.
+    pub symbols: Vec<(u64, String)>,
+}
+
+/// Reads the given input, which is an ELF file or a raw binary that begins
+/// at address `base`.
+pub fn read(bytes: &[u8], base: u64) -> Result<Image, String> {
+    if elf::is_elf(bytes) {
+        return elf::read(bytes);
+    }
+
+    let region = Region {
+        name: None,
+        kind: SectionKind::Code,
+        addr: base,
+        size: bytes.len() as u64,
+        data: bytes.to_vec(),
+    };
+
+    Ok(Image { regions: vec![region], symbols: vec![] })
+}
+
+/// Disassembles the given regions for the given target and returns ras
+/// source that reassembles to the same bytes.  `symbols` name addresses in
+/// the regions.  Reports in `session` a warning if the code decodes to no
+/// instructions at all, and an error if the result does not reassemble
+/// exactly.
+pub fn disassemble(
+    info:    &TargetInfo,
+    regions: &[Region],
+    symbols: &[(u64, String)],
+    session: &mut Session,
+) -> String {
+    let mut disasm = Disassembler::new(info);
+
+    let items = regions
+        .iter()
+        .map(|r| disasm.sweep(r))
+        .collect::<Vec<_>>();
+
+    let code  = regions.iter().any(|r| r.kind == SectionKind::Code && !r.data.is_empty());
+    let insns = items.iter().flatten().any(|i| matches!(i, Item::Insn { .. }));
+
+    if code && !insns {
+        session.report(Level::Warning, None, format_args!(
+            "no instructions decode for target '{}'; the output is all data", info.name
+        ));
+    }
+
+    // Prefer symbol names, then synthesized labels, then no labels at all,
+    // whichever first reassembles exactly
+    let symbols = symbols
+        .iter()
+        .filter(|(_, name)| is_ident(name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let attempts: [&[(u64, String)]; 2] = [&symbols, &[]];
+
+    for symbols in attempts {
+        let labels = disasm.labels(regions, &items, symbols);
+        let text   = disasm.render(info, regions, &items, &labels);
+        if disasm.verify(&text, regions) {
+            return text;
+        }
+    }
+
+    let text = disasm.render(info, regions, &items, &BTreeMap::new());
+
+    if !disasm.verify(&text, regions) {
+        session.report(Level::Error, None, format_args!(
+            "disassembly for target '{}' does not reassemble to the input", info.name
+        ));
+    }
+
+    text
+}
+
+/// Item of disassembly.
+#[derive(Clone, Debug)]
+enum Item {
+    /// Instruction of `size` bytes at offset `at`, with the given targets.
+    Insn { at: usize, size: usize, targets: Vec<u64> },
+
+    /// Data of `size` bytes at offset `at`.
+    Data { at: usize, size: usize },
+}
+
+impl Item {
+    fn at(&self) -> usize {
+        match *self {
+            Item::Insn { at, .. } | Item::Data { at, .. } => at,
+        }
+    }
+}
+
+/// Disassembler state.
+struct Disassembler {
+    target:  Rc<dyn Target>,
+    session: Session,
+}
+
+impl Disassembler {
+    fn new(info: &TargetInfo) -> Self {
+        let mut session = Session::new();
+        session.set_quiet(true);
+        session.set_target(info);
+
+        let target = session.target().unwrap().clone();
+
+        Self { target, session }
+    }
+
+    /// Divides the given region into instructions and data.
+    fn sweep(&mut self, region: &Region) -> Vec<Item> {
+        let mut items = vec![];
+
+        if region.kind == SectionKind::Bss {
+            return items;
+        }
+
+        let data = &region.data;
+        let mut at = 0;
+
+        while at < data.len() {
+            let addr = region.addr.wrapping_add(at as u64);
+
+            // Instructions begin only at aligned addresses
+            if region.kind == SectionKind::Code && addr.is_multiple_of(self.target.align()) {
+                if let Some(insn) = self.target.decode(&data[at..], addr, &|_| None) {
+                    let size = insn.size;
+                    if size != 0 && self.check(&insn.text, addr, &data[at..at + size]) {
+                        items.push(Item::Insn { at, size, targets: insn.targets });
+                        at += size;
+                        continue;
+                    }
+                }
+            }
+
+            let size = self.unit(region).min(data.len() - at);
+            items.push(Item::Data { at, size });
+            at += size;
+        }
+
+        items
+    }
+
+    /// Returns the size of the data unit for the given region.
+    fn unit(&self, region: &Region) -> usize {
+        match region.kind == SectionKind::Code && self.target.align() >= 2 {
+            true  => 2,
+            false => 1,
+        }
+    }
+
+    /// Returns whether the given instruction text, assembled at `addr`,
+    /// yields exactly `bytes`.
+    fn check(&mut self, text: &str, addr: u64, bytes: &[u8]) -> bool {
+        let errors = self.session.error_count();
+        let source = format!(".org x'{:X}\n{}\n", addr, text);
+        let unit   = self.session.assemble("disasm", &source);
+
+        self.session.error_count() == errors
+            && unit.sections.first().is_some_and(|s| s.data == bytes)
+    }
+
+    /// Returns the labels to print, by address.
+    fn labels(
+        &self,
+        regions: &[Region],
+        items:   &[Vec<Item>],
+        symbols: &[(u64, String)],
+    ) -> BTreeMap<u64, Vec<String>> {
+        // Addresses at which a label may appear
+        let starts = regions
+            .iter()
+            .zip(items)
+            .flat_map(|(r, items)| items.iter().map(|i| r.addr.wrapping_add(i.at() as u64)))
+            .collect::<HashSet<_>>();
+
+        let mut labels = BTreeMap::<u64, Vec<String>>::new();
+        let mut names  = HashSet::new();
+
+        for (addr, name) in symbols {
+            if starts.contains(addr) && names.insert(name.clone()) {
+                labels.entry(*addr).or_default().push(name.clone());
+            }
+        }
+
+        let targets = items
+            .iter()
+            .flatten()
+            .flat_map(|i| match i {
+                Item::Insn { targets, .. } => targets.as_slice(),
+                Item::Data { .. }          => &[],
+            });
+
+        for &addr in targets {
+            if starts.contains(&addr) && !labels.contains_key(&addr) {
+                let mut name = format!("L{:04X}", addr);
+                while names.contains(&name) {
+                    name.push('_');
+                }
+                names.insert(name.clone());
+                labels.insert(addr, vec![name]);
+            }
+        }
+
+        labels
+    }
+
+    /// Returns the source text for the given items.
+    fn render(
+        &self,
+        info:    &TargetInfo,
+        regions: &[Region],
+        items:   &[Vec<Item>],
+        labels:  &BTreeMap<u64, Vec<String>>,
+    ) -> String {
+        let label = |addr| labels.get(&addr).map(|names: &Vec<String>| names[0].clone());
+        let mut text = format!("# Disassembly for {}\n", info.name);
+
+        for (region, items) in regions.iter().zip(items) {
+            text.push('\n');
+
+            if let Some(ref name) = region.name {
+                let kind = match region.kind {
+                    SectionKind::Code => "code",
+                    SectionKind::Data => "data",
+                    SectionKind::Bss  => "bss",
+                };
+                line(&mut text, ".section", &format!("\"{}\", {}", name, kind));
+            }
+
+            line(&mut text, ".org", &format!("x'{:X}", region.addr));
+
+            if region.kind == SectionKind::Bss {
+                line(&mut text, ".skip", &region.size.to_string());
+                continue;
+            }
+
+            let mut data = vec![];
+
+            for item in items {
+                let addr = region.addr.wrapping_add(item.at() as u64);
+
+                if let Some(names) = labels.get(&addr) {
+                    self.data(&mut text, region, &mut data);
+                    for name in names {
+                        let _ = writeln!(text, "{}:", name);
+                    }
+                }
+
+                match *item {
+                    Item::Insn { at, .. } => {
+                        self.data(&mut text, region, &mut data);
+                        let insn = self.target.decode(&region.data[at..], addr, &label).unwrap();
+                        for insn in insn.text.lines() {
+                            match insn.split_once(' ') {
+                                Some((mnemonic, operands)) => line(&mut text, mnemonic, operands),
+                                None                       => line(&mut text, insn, ""),
+                            }
+                        }
+                    },
+                    Item::Data { at, size } => {
+                        if data.len() == 8 || data.first().is_some_and(|&(_, s)| s != size) {
+                            self.data(&mut text, region, &mut data);
+                        }
+                        data.push((at, size));
+                    },
+                }
+            }
+
+            self.data(&mut text, region, &mut data);
+        }
+
+        text
+    }
+
+    /// Prints and clears the pending data units.
+    fn data(&self, text: &mut String, region: &Region, data: &mut Vec<(usize, usize)>) {
+        let size = match data.first() {
+            Some(&(_, size)) => size,
+            None             => return,
+        };
+
+        let values = data
+            .drain(..)
+            .map(|(at, size)| {
+                let bytes = &region.data[at..at + size];
+                let value = match self.target.endian() {
+                    Endian::Little => bytes.iter().rev().fold(0u64, |v, &b| v << 8 | b as u64),
+                    Endian::Big    => bytes.iter()      .fold(0u64, |v, &b| v << 8 | b as u64),
+                };
+                format!("x'{:0width$X}", value, width = size * 2)
+            })
+            .collect::<Vec<_>>();
+
+        let directive = match size {
+            1 => ".int8",
+            _ => ".int16",
+        };
+
+        line(text, directive, &values.join(", "));
+    }
+
+    /// Returns whether the given source text reassembles to the given
+    /// regions.
+    fn verify(&mut self, text: &str, regions: &[Region]) -> bool {
+        let errors = self.session.error_count();
+        let unit   = self.session.assemble("disasm", text);
+
+        if self.session.error_count() != errors {
+            return false;
+        }
+
+        regions.iter().all(|region| {
+            let name = match region.name {
+                Some(ref name) => self.session.names_mut().add(name),
+                None           => Name::DOT_CODE,
+            };
+
+            unit.sections.iter().any(|s| {
+                s.name == name
+                    && s.base == region.addr
+                    && s.size == region.size
+                    && (region.kind == SectionKind::Bss || s.data == region.data)
+            })
+        })
+    }
+}
+
+/// Appends a line with the given mnemonic or directive and operands.
+fn line(text: &mut String, op: &str, operands: &str) {
+    let _ = match operands.is_empty() {
+        true  => writeln!(text, "        {}", op),
+        false => writeln!(text, "        {:<7} {}", op, operands),
+    };
+}
+
+/// Returns whether the given string is a plain identifier.
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,451 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Disassembler tests.
+
+use crate::asm::SectionKind;
+use crate::session::{Level, Session};
+use crate::target::{find, TARGETS};
+
+use super::*;
+
+/// Assembles the given source for the given target and returns the bytes
+/// of its first section.
+fn assemble(target: &str, source: &str) -> Vec<u8> {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find(target).unwrap());
+
+    let unit = session.assemble("test.s", source);
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+
+    unit.sections[0].data.clone()
+}
+
+/// Disassembles the given bytes at address `base`, checks that the result
+/// reassembles to the same bytes, and returns the result.
+fn round_trip(target: &str, base: u64, bytes: &[u8]) -> String {
+    let image = read(bytes, base).unwrap();
+
+    let mut session = Session::new();
+    session.set_quiet(true);
+
+    let text = disassemble(find(target).unwrap(), &image.regions, &image.symbols, &mut session);
+    assert_eq!(session.error_count(), 0, "{}:\n{}", target, text);
+    assert_eq!(assemble(target, &text), bytes, "{}:\n{}", target, text);
+
+    text
+}
+
+#[test]
+fn avr() {
+    let bytes = assemble("atmega328p", "
+        .org x'100
+        start:  ldi r16, 10
+        loop:   dec r16
+                brne loop
+                rcall sub
+                rjmp start
+                .int16 x'FFFF
+        sub:    lds r0, [x'200]
+                ret
+    ");
+
+    let text = round_trip("atmega328p", 0x100, &bytes);
+
+    assert_eq!(text, "\
+# Disassembly for atmega328p
+
+        .org    x'100
+L0100:
+        ldi     r16, x'A
+L0102:
+        dec     r16
+        brne    L0102
+        rcall   L010C
+        rjmp    L0100
+        .int16  x'FFFF
+L010C:
+        lds     r0, [x'200]
+        ret
+");
+}
+
+#[test]
+fn raw_data() {
+    // None of these bytes begins an instruction on the 8086
+    let bytes = [0x0F, 0x60, 0x62, 0x63, 0xD6, 0xD8, 0xD9, 0xF1, 0xFF];
+    let text  = round_trip("8086", 0, &bytes);
+
+    let mut session = Session::new();
+    session.set_quiet(true);
+
+    let image = read(&bytes, 0).unwrap();
+    disassemble(find("8086").unwrap(), &image.regions, &image.symbols, &mut session);
+
+    let diags = session.diagnostics();
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].level, Level::Warning);
+    assert_eq!(diags[0].msg, "no instructions decode for target '8086'; the output is all data");
+
+    assert_eq!(text, "\
+# Disassembly for 8086
+
+        .org    x'0
+        .int8   x'0F, x'60, x'62, x'63, x'D6, x'D8, x'D9, x'F1
+        .int8   x'FF
+");
+}
+
+/// Code that the targets of each family must decode entirely.
+const STREAMS: &[(&[&str], &str)] = &[
+    (
+        &[
+            "avr2", "avr25", "avr35", "avr4", "avr5", "avr51", "avr6", "avrxmega",
+            "attiny85", "atmega8", "atmega328p", "atmega2560",
+        ],
+        "
+        .org x'1000
+        start:  ldi r16, 10
+        loop:   add r0, r1
+                dec r16
+                brne loop
+                ld r2, [x]
+                st [z], r2
+                sbic [x'1F], 0
+                rcall start
+                rjmp start
+                ret
+        ",
+    ),
+    (
+        &["rv32i", "rv32im", "rv32imac", "rv64i", "rv64im", "rv64imac"],
+        "
+        .org x'1000
+        start:  addi a0, zero, 10
+        loop:   addi a0, a0, -1
+                add a1, a1, a0
+                lw a2, [sp + 4]
+                sw a2, [sp + 8]
+                lui a3, x'12345
+                slli a1, a1, 1
+                bne a0, zero, loop
+                jal ra, start
+                jalr zero, ra, 0
+        ",
+    ),
+    (
+        &["6502", "65c02"],
+        "
+        .org x'1000
+        start:  ldx 10
+        loop:   lda [x'10 + x]
+                sta [x'0300 + y]
+                adc [[x'20] + y]
+                asl a
+                dex
+                bne loop
+                jsr start
+                jmp [x'FFFC]
+                rts
+        ",
+    ),
+    (
+        &["z80", "z80-undoc"],
+        "
+        .org x'1000
+        start:  ld hl, x'2000
+                ld b, 10
+        loop:   ld a, [ix + 3]
+                add a, [hl]
+                ld [hl], a
+                inc hl
+                djnz loop
+                bit 7, a
+                jr nz, start
+                call start
+                ldir
+                ret
+        ",
+    ),
+    (
+        &["msp430", "msp430x"],
+        "
+        .org x'1000
+        start:  mov x'2000, r4
+                mov 10, r5
+        loop:   add [r4]!, r6
+                mov.b r6, [r4 + 2]
+                dec r5
+                jne loop
+                push r6
+                call start
+                br start
+                ret
+        ",
+    ),
+    (
+        &["armv6m"],
+        "
+        .org x'1000
+        start:  push r4-r7/lr
+                movs r4, 10
+                ldr r5, [r0 + 4]
+        loop:   adds r0, r0, r5
+                subs r4, 1
+                bne loop
+                str r0, [sp + 8]
+                bl start
+                mov r8, r0
+                pop r4-r7/pc
+        ",
+    ),
+    (
+        &["armv7m"],
+        "
+        .org x'1000
+        start:  push r4-r7/lr
+                movs r4, 10
+                ldr r5, [r0 + 4]
+        loop:   adds r0, r0, r5
+                subs r4, 1
+                bne loop
+                cmp r0, 1
+                ite eq
+                moveq r1, 2
+                movne r1, 3
+                itt gt
+                addgt r2, r2, r1
+                ldrgt.w r3, [r2 + 4]
+                str r0, [sp + 8]
+                bl start
+                mov r8, r0
+                pop r4-r7/pc
+        ",
+    ),
+    (
+        &["m68000", "m68010", "m68020"],
+        "
+        .org x'1000
+        start:  movem.l d2-d3/a2, [--sp]
+                moveq 10, d2
+                lea [pc + start], a0
+        loop:   move.w [a0]!, d0
+                add.w d0, d3
+                dbra d2, loop
+                cmpi.l x'1234, d3
+                bne start
+                jsr [x'2000]
+                movem.l [sp]!, d2-d3/a2
+                rts
+        ",
+    ),
+    (
+        &["coldfire-a", "coldfire-b", "coldfire-c"],
+        "
+        .org x'1000
+        start:  lea [sp - 8], sp
+                movem.l d2/a2, [sp]
+                moveq 10, d2
+        loop:   move.l [a0]!, d0
+                add.l d0, d1
+                subq.l 1, d2
+                bne loop
+                bsr start
+                movem.l [sp], d2/a2
+                lea [sp + 8], sp
+                rts
+        ",
+    ),
+    (
+        &["8086", "80186", "80386"],
+        "
+        .org x'1000
+        start:  push si
+                mov si, x'2000
+                mov cx, 10
+                xor ax, ax
+        next:   add ax, [si]
+                add si, 2
+                loop next
+                cmp ax, x'1234
+                jne start
+                mov [x'3000], ax
+                call start
+                pop si
+                ret
+        ",
+    ),
+];
+
+/// Every target's decoder must agree with its encoder: the disassembly of
+/// arbitrary bytes reassembles to the same bytes, and known code decodes as
+/// instructions, not data.  Every target must have such code.
+#[test]
+fn oracle() {
+    let inputs = [0x1234_5678_u32, 0x9E37_79B9, 0x3C6E_F372, 0xDAA6_6D2B].map(|mut seed| {
+        (0..2048)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for target in TARGETS {
+        for bytes in &inputs {
+            round_trip(target.name, 0x1000, bytes);
+        }
+
+        let covered = STREAMS.iter().any(|(targets, _)| targets.contains(&target.name));
+        assert!(covered, "{}: no code to decode", target.name);
+    }
+
+    for &(targets, source) in STREAMS {
+        for &target in targets {
+            let text = round_trip(target, 0x1000, &assemble(target, source));
+            assert!(!text.contains(".int"), "{}:\n{}", target, text);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Builds a little-endian ELF32 file with the given allocated sections and
+/// symbols.
+fn elf32(sections: &[(&str, u32, u32, u32, &[u8])], symbols: &[(&str, u32, u16)]) -> Vec<u8> {
+    fn u16(v: &mut Vec<u8>, x: u16) { v.extend_from_slice(&x.to_le_bytes()) }
+    fn u32(v: &mut Vec<u8>, x: u32) { v.extend_from_slice(&x.to_le_bytes()) }
+
+    // Section name table
+    let mut shstrtab = vec![0];
+    let mut names    = vec![];
+    for name in sections.iter().map(|s| s.0).chain([".symtab", ".strtab", ".shstrtab"]) {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    // Symbol and string tables
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; 16];
+    for &(name, value, shndx) in symbols {
+        u32(&mut symtab, strtab.len() as u32);
+        u32(&mut symtab, value);
+        u32(&mut symtab, 0);
+        symtab.extend_from_slice(&[2, 0]);
+        u16(&mut symtab, shndx);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+
+    // Contents follow the file header
+    let mut file    = vec![0; 52];
+    let mut headers = vec![0; 40];
+
+    let mut header = |file: &mut Vec<u8>, name, kind, flags, addr, data: &[u8], size, link, entry| {
+        u32(&mut headers, name);
+        u32(&mut headers, kind);
+        u32(&mut headers, flags);
+        u32(&mut headers, addr);
+        u32(&mut headers, file.len() as u32);
+        u32(&mut headers, size);
+        u32(&mut headers, link);
+        u32(&mut headers, 0);
+        u32(&mut headers, 1);
+        u32(&mut headers, entry);
+        file.extend_from_slice(data);
+    };
+
+    let n = sections.len() as u32;
+
+    for (i, &(_, kind, flags, addr, data)) in sections.iter().enumerate() {
+        let size = if kind == 8 { 16 } else { data.len() as u32 };
+        header(&mut file, names[i], kind, flags, addr, data, size, 0, 0);
+    }
+    header(&mut file, names[n as usize],     2, 0, 0, &symtab,   symtab  .len() as u32, n + 2, 16);
+    header(&mut file, names[n as usize + 1], 3, 0, 0, &strtab,   strtab  .len() as u32, 0,     0);
+    header(&mut file, names[n as usize + 2], 3, 0, 0, &shstrtab, shstrtab.len() as u32, 0,     0);
+
+    let shoff = file.len() as u32;
+    file.extend_from_slice(&headers);
+
+    file[..8].copy_from_slice(b"\x7FELF\x01\x01\x01\x00");
+    file[32..36].copy_from_slice(&shoff.to_le_bytes());
+    file[46..48].copy_from_slice(&40u16.to_le_bytes());
+    file[48..50].copy_from_slice(&(n as u16 + 4).to_le_bytes());
+    file[50..52].copy_from_slice(&(n as u16 + 3).to_le_bytes());
+    file
+}
+
+#[test]
+fn elf() {
+    let code = assemble("avr5", ".org x'0\nmain: rcall func\nrjmp main\nfunc: ret\n");
+    let data = [1, 2, 3];
+
+    let file = elf32(
+        &[
+            (".text", 1, 6, 0x000,   &code),
+            (".data", 1, 3, 0x800100, &data),
+            (".bss",  8, 3, 0x800103, &[]),
+        ],
+        &[("main", 0, 1), ("func", 4, 1), ("table", 0x800100, 2), ("r0", 0x800101, 2)],
+    );
+
+    let Image { regions, symbols } = read(&file, 0).unwrap();
+
+    assert_eq!(regions.len(), 3);
+    assert_eq!(regions[0].name.as_deref(), Some(".text"));
+    assert_eq!(regions[0].kind, SectionKind::Code);
+    assert_eq!(regions[1].kind, SectionKind::Data);
+    assert_eq!(regions[1].data, data);
+    assert_eq!(regions[2].kind, SectionKind::Bss);
+    assert_eq!(regions[2].size, 16);
+    assert_eq!(symbols.len(), 4);
+
+    let mut session = Session::new();
+    session.set_quiet(true);
+
+    let text = disassemble(find("avr5").unwrap(), &regions, &symbols, &mut session);
+
+    assert_eq!(text, "\
+# Disassembly for avr5
+
+        .section \".text\", code
+        .org    x'0
+main:
+        rcall   func
+        rjmp    main
+func:
+        ret
+
+        .section \".data\", data
+        .org    x'800100
+table:
+        .int8   x'01
+r0:
+        .int8   x'02, x'03
+
+        .section \".bss\", bss
+        .org    x'800103
+        .skip   16
+");
+
+    assert!(read(b"\x7FELF\x03", 0).is_err());
+}
+
@@ -24,6 +24,19 @@ use colored::*;
 use crate::name::{Name, NameTable};
 use crate::num::Num;
 
+/// Source span of a node.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Span {
+    /// Line number at which the node begins.
+    pub line: usize,
+
+    /// Byte position at which the node begins.
+    pub start: usize,
+
+    /// Byte position after the node ends.
+    pub end: usize,
+}
+
 /// Block of statements.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Block<T = ()> {
@@ -144,8 +157,51 @@ pub enum Expr<T = ()> {
     /// Binary operation on subexpressions.
     Binary(T, BinOp, Box<Expr<T>>, Box<Expr<T>>),
 
-    // Statement block.
-    Block(Block),
+    /// Call expression: `f(args)`.
+    Call(T, Box<Expr<T>>, Vec<Expr<T>>),
+
+    /// Statement block.
+    Block(Block<T>),
+}
+
+impl<T> Stmt<T> {
+    /// Returns the additional data of the statement.
+    pub fn data(&self) -> &T {
+        match *self {
+            Stmt::Label (ref l) => &l.data,
+            Stmt::Dir   (ref d) => &d.data,
+        }
+    }
+}
+
+impl<T> Arg<T> {
+    /// Returns the additional data of the argument.
+    pub fn data(&self) -> &T {
+        match *self {
+            Arg::Unknown (ref d) => d,
+            Arg::Expr    (ref e) => e.data(),
+        }
+    }
+}
+
+impl<T> Expr<T> {
+    /// Returns the additional data of the expression.
+    pub fn data(&self) -> &T {
+        use Expr::*;
+        match *self {
+            Ident  (ref d, ..) => d,
+            Int    (ref d, ..) => d,
+            Float  (ref d, ..) => d,
+            Str    (ref d, ..) => d,
+            Char   (ref d, ..) => d,
+            Alias  (ref d, ..) => d,
+            Deref  (ref d, ..) => d,
+            Unary  (ref d, ..) => d,
+            Binary (ref d, ..) => d,
+            Call   (ref d, ..) => d,
+            Block  (ref b)     => &b.data,
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -184,13 +240,16 @@ pub enum UnOp {
     /// `--x` - pre-decrement operator.
     PreDec,
 
-// Signedness, precedence level 0
+// Signedness and literal, precedence level 0
 
     /// `+:` - implicit-signed operator.
     SignedL,
 
     /// `%:` - implicit-unsigned operator.
     UnsignedL,
+
+    /// `=x` - literal operator.
+    Literal,
 }
 
 // ----------------------------------------------------------------------------
@@ -361,7 +420,7 @@ enum Nesting<'a> {
 #[derive(Clone, Copy, Debug)]
 struct Indent<'a> (Nesting<'a>);
 
-impl Block {
+impl<T> Block<T> {
     /// Returns a wrapper over the node that implements [`Display`].
     pub fn for_display<'a>(&'a self, names: &'a NameTable) -> impl Display + 'a {
         ForDisplay { node: self, names, nesting: Nesting::Root }
@@ -380,19 +439,19 @@ impl<T: ?Sized> ForDisplay<'_, T> {
 
     fn node0<'a>(
         &'a self, kind: &'a str
-    ) -> DisplayNode0 {
+    ) -> DisplayNode0<'a> {
         DisplayNode0 { kind, nesting: self.nesting }
     }
 
     fn node1<'a, T0: Display>(
         &'a self, kind: &'a str, data: T0
-    ) -> DisplayNode1<T0> {
+    ) -> DisplayNode1<'a, T0> {
         DisplayNode1 { kind, nesting: self.nesting, data }
     }
 
     fn node2<'a, T0: Display, T1: Display>(
         &'a self, kind: &'a str, data0: T0, data1: T1
-    ) -> DisplayNode2<T0, T1> {
+    ) -> DisplayNode2<'a, T0, T1> {
         DisplayNode2 { kind, nesting: self.nesting, data: (data0, data1) }
     }
 }
@@ -525,6 +584,11 @@ impl<T> Display for ForDisplay<'_, Expr<T>> {
                 self.child(&**lhs, true ).fmt(f)?;
                 self.child(&**rhs, false).fmt(f)
             },
+            Call(_, ref func, ref args) => {
+                self.node0("Call").fmt(f)?;
+                self.child(&**func, !args.is_empty()).fmt(f)?;
+                self.drill(args).fmt(f)
+            },
             Block(ref block) => self.drill(block).fmt(f),
         }
     }
@@ -69,7 +69,7 @@ const fn char(n: u8) -> u8 {
 
 impl Char {
     /// Count of logical characters.
-    const COUNT: usize = Self::Eof as usize / State::COUNT as usize + 1;
+    const COUNT: usize = Self::Eof as usize / State::COUNT + 1;
 }
 
 impl LogicalChar for Char {
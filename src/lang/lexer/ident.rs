@@ -27,7 +27,7 @@ use super::*;
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u8)]
 enum Char {
-    Ident,  // A-Z a-z 0-9 . {U+0080..}
+    Ident,  // A-Z a-z 0-9 . _ {U+0080..}
     Lit,    // '
     Other,  // everything else
 }
@@ -55,7 +55,7 @@ static CHARS: [Char; 128] = {
     __,     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 4x │@ABCDEFG│
     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 4x │HIJKLMNO│
     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 5x │PQRSTUVW│
-    Ident,  Ident,  Ident,  __,     __,     __,     __,     __,     // 5x │XYZ[\]^_│
+    Ident,  Ident,  Ident,  __,     __,     __,     __,     Ident,  // 5x │XYZ[\]^_│
     __,     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 6x │`abcdefg│
     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 6x │hijklmno│
     Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  Ident,  // 7x │pqrstuvw│
@@ -161,7 +161,7 @@ impl Item {
         if (self.0 as i8) >= 0 {
             eprintln!("self.0 = {}", self.0);
             // SAFETY: Token validity enforced by `Item::yielder()`.
-            Some(unsafe { transmute(self.0) })
+            Some(unsafe { transmute::<u8, Token>(self.0) })
         } else {
             None
         }
@@ -255,6 +255,7 @@ impl Lex for Tokens<'_> {
 }
 
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod test {
     use super::*;
 
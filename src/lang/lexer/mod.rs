@@ -115,7 +115,7 @@ impl<I: Iterator<Item = u8>> Lexer<I> {
     }
 
     /// Returns the value of the most recent token.
-    pub fn value(&self, token: Token) -> Value<I> {
+    pub fn value(&self, token: Token) -> Value<'_, I> {
         Value { lexer: self, token }
     }
 }
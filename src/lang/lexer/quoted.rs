@@ -26,6 +26,7 @@ use super::*;
 /// Logical characters recognized by the quoted literal sublexer.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u8)]
+#[allow(clippy::unusual_byte_groupings)]
 enum Char {
     //          ╭───────── is_lf
     //          │ ╭─────── is_cr
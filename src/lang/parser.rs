@@ -19,7 +19,7 @@
 //! Parser.
 
 use crate::name::Name;
-use crate::session::Session;
+use crate::session::{Loc, Session};
 
 use super::ast::*;
 use super::lexer::{Lex, Token, Token::*};
@@ -27,17 +27,20 @@ use super::lexer::{Lex, Token, Token::*};
 #[derive(Debug)]
 pub struct Parser<'a, L: Lex> {
     lexer:   L,
+    file:    Name,
+    end:     usize,
     session: &'a mut Session,
 }
 
 impl<'a, L: Lex> Parser<'a, L> {
-    /// Creates a new [`Parser`] for the given `lexer` and `session`.
-    pub fn new(lexer: L, session: &'a mut Session) -> Self {
-        Self { lexer, session }
+    /// Creates a new [`Parser`] for the given `lexer` and `session`.  The
+    /// parser attributes diagnostics to the source `file`.
+    pub fn new(lexer: L, file: Name, session: &'a mut Session) -> Self {
+        Self { lexer, file, end: 0, session }
     }
 
     /// Parses input completely, returning an abstract syntax tree.
-    pub fn parse(&mut self) -> Block {
+    pub fn parse(&mut self) -> Block<Span> {
         self.parse_block(Eof).unwrap()
     }
 
@@ -61,7 +64,8 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// Lexer positions:
     /// - On entry: at BOF or on block opening delimiter.
     /// - On exit:  at EOF or on block closing delimiter.
-    fn parse_block(&mut self, end: Token) -> Result<Block, ()> {
+    fn parse_block(&mut self, end: Token) -> Result<Block<Span>, ()> {
+        let     start = self.start();
         let mut stmts = vec![];
 
         'block: loop {
@@ -76,20 +80,20 @@ impl<'a, L: Lex> Parser<'a, L> {
                 },
                 Err(RCurly) => {
                     // } not in {} block
-                    eprintln!("error: unexpected '}}'");
+                    self.error("unexpected '}'");
                 },
                 Err(Eof) => {
                     // EOF not in top-level block
-                    eprintln!("error: unexpected end of file");
+                    self.error("unexpected end of file");
                     return Err(());
                 }
                 Err(_) => {
                     // Other weirdness
-                    eprintln!("error: expected statement");
+                    self.error("expected statement");
 
                     // Recover
                     'recov: loop {
-                        match self.lexer.next() {
+                        match self.next() {
                             t if t == end => break 'block,
                             Eos | Eof     => break 'recov,
                             _             => (),
@@ -99,7 +103,7 @@ impl<'a, L: Lex> Parser<'a, L> {
             }
         }
 
-        Ok(Block { stmts, data: () })
+        Ok(Block { stmts, data: self.span_to_current(start) })
     }
 
     /// Attempts to parse a statement.
@@ -108,9 +112,9 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// - On entry:   before statement token.
     /// - On success: after statement, on EOS or EOF.
     /// - On failure: on unexpected token (returned).
-    fn parse_stmt(&mut self) -> Result<Stmt, Token> {
+    fn parse_stmt(&mut self) -> Result<Stmt<Span>, Token> {
         loop {
-            match self.lexer.next() {
+            match self.next() {
                 Eos => {
                     // Ignore empty statement
                 },
@@ -135,22 +139,23 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// Lexer positions:
     /// - On entry: on [`Ident`].
     /// - On exit:  on EOS or EOF.
-    fn parse_label_or_dir(&mut self) -> Result<Stmt, ()> {
+    fn parse_label_or_dir(&mut self) -> Result<Stmt<Span>, ()> {
         // Get label or directive name
+        let start  = self.start();
         let name   = self.lexer.str();
         let pseudo = name.starts_with('.');
         let name   = self.session.names_mut().add(name);
 
         // Expect label declarator as EOS; otherwise parse as directive
-        let scope = match self.lexer.next() {
+        let scope = match self.next() {
             Colon  if pseudo => Scope::Local,
             Colon            => Scope::Private,
             Weak             => Scope::Weak,
             Public           => Scope::Public,
-            token            => return self.parse_dir(name, token),
+            token            => return self.parse_dir(name, start, token),
         };
 
-        Ok(Stmt::Label(Label { name, scope, data: () }))
+        Ok(Stmt::Label(Label { name, scope, data: self.span_to_current(start) }))
     }
 
     /// Attempts to parse a directive with the given `name`.
@@ -158,9 +163,19 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// Lexer positions:
     /// - On entry: after `name`, on given `token`.
     /// - On exit:  on EOS or EOF.
-    fn parse_dir(&mut self, name: Name, mut token: Token) -> Result<Stmt, ()> {
+    fn parse_dir(&mut self, name: Name, start: Span, mut token: Token)
+        -> Result<Stmt<Span>, ()>
+    {
         let mut args = vec![];
 
+        // The name of a statement macro precedes its parameters without a comma
+        if name == Name::DOT_MACRO && token == Ident {
+            let start = self.start();
+            let name  = self.name();
+            args.push(Arg::Expr(Expr::Ident(self.span_to_current(start), name)));
+            token = self.next();
+        }
+
         // Parse arguments if present
         if !token.is_eos() {
             loop {
@@ -171,7 +186,7 @@ impl<'a, L: Lex> Parser<'a, L> {
                         token = t;
                     },
                     Err(t) => {
-                        eprintln!("expected: argument");
+                        self.error("expected: argument");
                         return self.parse_dir_fail(t);
                     },
                 }
@@ -179,26 +194,26 @@ impl<'a, L: Lex> Parser<'a, L> {
                 // Parse argument separator or end of statement
                 match token {
                     Comma => {
-                        token = self.lexer.next();
+                        token = self.next();
                     },
                     Eos | Eof => {
                         break;
                     },
                     t => {
-                        eprintln!("expected: comma, end of statement, or end of file");
+                        self.error("expected: comma, end of statement, or end of file");
                         return self.parse_dir_fail(t);
                     },
                 }
             }
         }
 
-        Ok(Stmt::Dir(Dir { name, args, data: () }))
+        Ok(Stmt::Dir(Dir { name, args, data: self.span(start) }))
     }
 
-    fn parse_dir_fail(&mut self, mut token: Token) -> Result<Stmt, ()> {
+    fn parse_dir_fail(&mut self, mut token: Token) -> Result<Stmt<Span>, ()> {
         // Recover
         while !token.is_eos() {
-            token = self.lexer.next();
+            token = self.next();
         }
 
         Err(())
@@ -210,11 +225,11 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// - On entry:   at `token`, the first token of the expression.
     /// - On success: at the returned token, the first token after the expression.
     /// - On failure: at the returned token, the token that was unexpected.
-    fn parse_arg(&mut self, token: Token) -> Result<(Arg, Token), Token> {
+    fn parse_arg(&mut self, token: Token) -> Result<(Arg<Span>, Token), Token> {
         match token {
             Unknown => Ok((
-                Arg::Unknown(()),
-                self.lexer.next()
+                Arg::Unknown(self.span_to_current(self.start())),
+                self.next()
             )),
             token => self
                 .parse_expr(token)
@@ -229,7 +244,7 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// - On success: at the returned token, the first token after the expression.
     /// - On failure: at the returned token, the token that was unexpected.
     #[inline]
-    fn parse_expr(&mut self, token: Token) -> Result<(Expr, Token), Token> {
+    fn parse_expr(&mut self, token: Token) -> Result<(Expr<Span>, Token), Token> {
         self.parse_expr_prec(token, 0)
     }
 
@@ -242,9 +257,12 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// - On entry:   at `token`, the first token of the expression.
     /// - On success: at the returned token, the first token after the expression.
     /// - On failure: at the returned token, the token that was unexpected.
-    fn parse_expr_prec(&mut self, token: Token, min_prec: u8) -> Result<(Expr, Token), Token> {
+    fn parse_expr_prec(&mut self, token: Token, min_prec: u8)
+        -> Result<(Expr<Span>, Token), Token>
+    {
         use PostfixParse as P;
 
+        let start = self.start();
         let (mut expr, mut token) = self.parse_expr_prefix(token)?;
 
         loop {
@@ -256,18 +274,27 @@ impl<'a, L: Lex> Parser<'a, L> {
                     let (prec, _assoc) = unary_prec(op);
                     if prec < min_prec { break; }
 
-                    token = self.lexer.next();
-                    expr  = Expr::Unary((), op, Box::new(expr));
+                    token = self.next();
+                    expr  = Expr::Unary(self.span(start), op, Box::new(expr));
                 },
                 P::Binary(op) => {
                     let (prec, assoc) = binary_prec(op);
                     if prec < min_prec { break; }
 
-                    token = self.lexer.next();
+                    token = self.next();
                     let (rhs, t) = self.parse_expr_prec(token, prec + assoc as u8)?;
 
                     token = t;
-                    expr  = Expr::Binary((), op, Box::new(expr), Box::new(rhs))
+                    expr  = Expr::Binary(self.span(start), op, Box::new(expr), Box::new(rhs))
+                },
+                P::Call => {
+                    let (prec, _assoc) = CALL_PREC;
+                    if prec < min_prec { break; }
+
+                    let (args, t) = self.parse_call_args()?;
+
+                    token = t;
+                    expr  = Expr::Call(self.span(start), Box::new(expr), args)
                 },
             }
         }
@@ -275,6 +302,32 @@ impl<'a, L: Lex> Parser<'a, L> {
         Ok((expr, token))
     }
 
+    /// Attempts to parse the parenthesized argument list of a call expression.
+    ///
+    /// Lexer positions:
+    /// - On entry:   on [`LParen`].
+    /// - On success: at the returned token, the first token after `)`.
+    /// - On failure: at the returned token, the token that was unexpected.
+    fn parse_call_args(&mut self) -> Result<(Vec<Expr<Span>>, Token), Token> {
+        let mut args  = vec![];
+        let mut token = self.next();
+
+        if token == RParen {
+            return Ok((args, self.next()));
+        }
+
+        loop {
+            let (arg, t) = self.parse_expr(token)?;
+            args.push(arg);
+
+            match t {
+                Comma  => token = self.next(),
+                RParen => return Ok((args, self.next())),
+                _      => return Err({ self.error("expected: ',' or ')'"); t }),
+            }
+        }
+    }
+
     /// Attempts to parse an atomic, prefix, or circumfix expression.
     ///
     /// This method is the prefix half of the precedence-climbing expression
@@ -284,77 +337,79 @@ impl<'a, L: Lex> Parser<'a, L> {
     /// - On entry:   at `token`, the first token of the expression.
     /// - On success: at the returned token, the first token after the expression.
     /// - On failure: at the returned token, the token that was unexpected.
-    fn parse_expr_prefix(&mut self, token: Token) -> Result<(Expr, Token), Token> {
+    fn parse_expr_prefix(&mut self, token: Token) -> Result<(Expr<Span>, Token), Token> {
         use PrefixParse as P;
 
+        let start = self.start();
+
         match prefix_parse_kind(token) {
             P::Ident => Ok({
                 let name = self.name();
-                match self.lexer.next() {
+                match self.next() {
                     Alias => {
                         let (prec, assoc) = ALIAS_PREC;
-                        let        token  = self.lexer.next();
+                        let        token  = self.next();
                         let (expr, token) = self.parse_expr_prec(token, prec + assoc as u8)?;
-                        (Expr::Alias((), name, Box::new(expr)), token)
+                        (Expr::Alias(self.span(start), name, Box::new(expr)), token)
                     },
-                    token => (Expr::Ident((), name), token)
+                    token => (Expr::Ident(self.span(start), name), token)
                 }
             }),
             P::Param => todo!(), // TODO: Process macro right here?
             P::Int => Ok((
-                Expr::Int((), self.lexer.int()),
-                self.lexer.next()
+                Expr::Int(self.span_to_current(start), self.lexer.int()),
+                self.next()
             )),
             P::Float => Ok((
-                Expr::Float((), Box::new(*self.lexer.num())),
-                self.lexer.next()
+                Expr::Float(self.span_to_current(start), Box::new(*self.lexer.num())),
+                self.next()
             )),
             P::Str => Ok((
-                Expr::Str((), self.lexer.str().to_string()),
-                self.lexer.next()
+                Expr::Str(self.span_to_current(start), self.lexer.str().to_string()),
+                self.next()
             )),
             P::Char => Ok((
-                Expr::Char((), self.lexer.char()),
-                self.lexer.next()
+                Expr::Char(self.span_to_current(start), self.lexer.char()),
+                self.next()
             )),
             P::Unary(op) => {
                 let (prec, assoc) = unary_prec(op);
-                let        token  = self.lexer.next();
+                let        token  = self.next();
                 let (expr, token) = self.parse_expr_prec(token, prec + assoc as u8)?;
-                Ok(( Expr::Unary((), op, Box::new(expr)), token ))
+                Ok(( Expr::Unary(self.span(start), op, Box::new(expr)), token ))
             },
             P::Group => {
-                let       token  = self.lexer.next();
+                let       token  = self.next();
                 let (lhs, token) = self.parse_expr(token)?;
                 match token {
-                    RParen => Ok((lhs, self.lexer.next())),
-                    _      => Err({ eprintln!("expected: ')'"); token }),
+                    RParen => Ok((lhs, self.next())),
+                    _      => Err({ self.error("expected: ')'"); token }),
                 }
             },
             P::Deref => {
-                let       token  = self.lexer.next();
+                let       token  = self.next();
                 let (lhs, token) = self.parse_expr(token)?;
                 match token {
                     RSquare => {
-                        let (effect, token) = match self.lexer.next() {
-                            LogNot => (true, self.lexer.next()),
+                        let (effect, token) = match self.next() {
+                            LogNot => (true, self.next()),
                             token  => (false, token),
                         };
-                        Ok(( Expr::Deref((), Box::new(lhs), effect), token ))
+                        Ok(( Expr::Deref(self.span(start), Box::new(lhs), effect), token ))
                     },
-                    _ => Err({ eprintln!("expected: ']'"); token }),
+                    _ => Err({ self.error("expected: ']'"); token }),
                 }
             },
             P::Block => {
                 let block = self.parse_block(RCurly);
-                let token = self.lexer.next();
+                let token = self.next();
                 match block {
                     Ok(block) => Ok(( Expr::Block(block), token )),
                     _         => Err(token),
                 }
             },
             P::None => Err({
-                eprintln!("expected: expression");
+                self.error("expected: expression");
                 token
             }),
         }
@@ -363,6 +418,34 @@ impl<'a, L: Lex> Parser<'a, L> {
     fn name(&mut self) -> Name {
         self.session.names_mut().add(self.lexer.str())
     }
+
+    /// Advances to the next token, remembering where the current one ends.
+    fn next(&mut self) -> Token {
+        self.end = self.lexer.range().end;
+        self.lexer.next()
+    }
+
+    /// Returns an empty span at the current token.
+    fn start(&self) -> Span {
+        let start = self.lexer.range().start;
+        Span { line: self.lexer.line(), start, end: start }
+    }
+
+    /// Returns a span from `start` through the end of the previous token.
+    fn span(&self, start: Span) -> Span {
+        Span { end: self.end.max(start.start), ..start }
+    }
+
+    /// Returns a span from `start` through the end of the current token.
+    fn span_to_current(&self, start: Span) -> Span {
+        Span { end: self.lexer.range().end, ..start }
+    }
+
+    /// Reports an error at the current token.
+    fn error(&mut self, msg: &str) {
+        let loc = Loc { file: self.file, span: self.span_to_current(self.start()) };
+        self.session.error(loc, msg);
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -424,6 +507,7 @@ const fn prefix_parse_kind(token: Token) -> PrefixParse {
         Mod      => P::Unary(UnOp::UnsignedH),
         Add      => P::Unary(UnOp::SignedH),
         Sub      => P::Unary(UnOp::Neg),
+        Assign   => P::Unary(UnOp::Literal),
 
         LParen   => P::Group,
         LSquare  => P::Deref,
@@ -451,6 +535,9 @@ enum PostfixParse {
 
     /// Parse as a binary operator expression.
     Binary(BinOp),
+
+    /// Parse as a call expression.
+    Call,
 }
 
 /// Returns the expression parsing strategy for the given trailing `token`.
@@ -462,7 +549,7 @@ const fn postfix_parse_kind(token: Token) -> PostfixParse {
         Inc           => Unary(UnOp::PostInc),
         Dec           => Unary(UnOp::PostDec),
 
-        //LParen      => Call,
+        LParen        => Call,
         //LSquare     => Index,
 
         Mul           => Binary(BinOp::Mul),
@@ -515,19 +602,25 @@ const fn postfix_parse_kind(token: Token) -> PostfixParse {
 // ----------------------------------------------------------------------------
 
 /// Operator associativity kinds.
+///
+/// The value of each kind is the amount by which the minimum precedence of an
+/// operand exceeds the precedence of the operator.
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
 enum Assoc {
     /// Left-associative.
-    Left,
+    Left = 1,
 
     /// Right-associative.
-    Right
+    Right = 0,
 }
 
 /// Precedence and associativity of alias operator.
 const ALIAS_PREC: (u8, Assoc) = (16, Assoc::Right);
 
+/// Precedence and associativity of call operator.
+const CALL_PREC: (u8, Assoc) = (15, Assoc::Left);
+
 /// Returns the precedence and associativity of the given unary operator.
 const fn unary_prec(op: UnOp) -> (u8, Assoc) {
     use UnOp::*;
@@ -539,7 +632,7 @@ const fn unary_prec(op: UnOp) -> (u8, Assoc) {
         BitNot  | LogNot | Neg                      |
         SignedH | UnsignedH                         => (14, Right),
 
-        SignedL | UnsignedL                         => ( 0, Right),
+        SignedL | UnsignedL | Literal               => ( 0, Right),
     }
 }
 
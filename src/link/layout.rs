@@ -0,0 +1,447 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Layout rules.
+
+use std::fs;
+
+use crate::asm::{Context, Value, eval};
+use crate::lang::ast::*;
+use crate::lang::lexer::Lexer;
+use crate::lang::parser::Parser;
+use crate::name::Name;
+use crate::session::{Level, Loc, Session};
+
+// ----------------------------------------------------------------------------
+
+/// Rules for placement of sections by the linker.
+#[derive(Clone, Default, Debug)]
+pub struct Layout {
+    /// Address of the first section without an explicit address or region.
+    pub base: u64,
+
+    /// Memory regions, in order of definition.
+    pub regions: Vec<Region>,
+
+    /// Placement rules, in the order in which to place sections.  Sections
+    /// without a rule follow in order of kind (code, data, bss) and then order
+    /// of first appearance.
+    pub rules: Vec<Rule>,
+
+    /// Explicit addresses of sections.  These override placement rules.
+    pub starts: Vec<(Name, u64)>,
+
+    /// Symbols defined by the layout.
+    pub symbols: Vec<LayoutSymbol>,
+}
+
+/// Range of memory into which the linker places sections.
+#[derive(Clone, Debug)]
+pub struct Region {
+    /// Name.
+    pub name: Name,
+
+    /// Address of the first byte of the region.
+    pub origin: u64,
+
+    /// Size of the region in bytes.
+    pub length: u64,
+}
+
+impl Region {
+    /// Returns the address after the last byte of the region.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.origin.saturating_add(self.length)
+    }
+}
+
+/// Rule for placement of a section.
+#[derive(Clone, Copy, Debug)]
+pub struct Rule {
+    /// Name of the section.
+    pub section: Name,
+
+    /// Where the section runs, or `None` to follow the previous section.
+    pub run: Option<Target>,
+
+    /// Where the section is loaded, or `None` if the same as where it runs.
+    pub load: Option<Target>,
+}
+
+/// Destination of a section.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    /// Next available address in the region with the given index.
+    Region(usize),
+
+    /// Explicit address.
+    Addr(u64),
+}
+
+/// Symbol whose value is an address computed by the linker.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutSymbol {
+    /// Name.
+    pub name: Name,
+
+    /// Name of the section whose address is the value of the symbol.
+    pub section: Name,
+
+    /// Which address of the section is the value of the symbol.
+    pub at: Edge,
+
+    /// Location of the definition.
+    pub def: Loc,
+}
+
+/// Address of a placed section.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    /// Address of the first byte where the section runs.
+    Start,
+
+    /// Address after the last byte where the section runs.
+    End,
+
+    /// Address of the first byte where the section is loaded.
+    Load,
+}
+
+impl Layout {
+    /// Creates a new, empty [`Layout`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the explicit address of the section with the given `name`, if
+    /// any.
+    pub fn start(&self, name: Name) -> Option<u64> {
+        self.starts.iter().rev().find(|&&(n, _)| n == name).map(|&(_, a)| a)
+    }
+
+    /// Sets the explicit address of the section with the given `name`.
+    pub fn set_start(&mut self, name: Name, addr: u64) {
+        self.starts.push((name, addr));
+    }
+
+    /// Returns the placement rule for the section with the given `name`, if
+    /// any.
+    pub fn rule(&self, name: Name) -> Option<&Rule> {
+        self.rules.iter().find(|r| r.section == name)
+    }
+
+    /// Appends a placement rule for the section with the given `name`,
+    /// replacing any previous rule for the section.
+    pub fn place(&mut self, name: Name, run: Option<Target>, load: Option<Target>) {
+        self.rules.retain(|r| r.section != name);
+        self.rules.push(Rule { section: name, run, load });
+    }
+
+    /// Returns the index of the region with the given `name`, if any.
+    pub fn region(&self, name: Name) -> Option<usize> {
+        self.regions.iter().position(|r| r.name == name)
+    }
+
+    /// Reads layout rules from the file with the given `path`.
+    ///
+    /// A layout file contains statements in ras syntax:
+    ///
+    /// ```text
+    /// .region <name> , <origin> , <length>
+    /// .place  <section> [ , <run> [ , <load> ] ]
+    /// .symbol <name> , start | end | load , <section>
+    /// <symbol>:
+    /// ```
+    ///
+    /// Each `.region` statement defines a memory region.  Each `.place`
+    /// statement appends a section to the placement order.  The section runs
+    /// at `<run>` and is loaded at `<load>`, each of which is either a region
+    /// name or an address.  Each `.symbol` statement defines a symbol at the
+    /// start, end, or load address of a section.  A label preceding a
+    /// `.place` statement defines a symbol at the address of the placed
+    /// section.  A label following the last `.place` statement defines a
+    /// symbol at the address after the last placed section.
+    pub fn read(&mut self, path: &str, session: &mut Session) {
+        let file    = session.names_mut().add(path);
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e)      => {
+                return session.report(Level::Error, None, format!("{}: {}", path, e));
+            },
+        };
+
+        self.read_content(file, &content, session);
+    }
+
+    fn read_content(&mut self, file: Name, content: &str, session: &mut Session) {
+        let ast = Parser::new(Lexer::new(content.bytes()), file, session).parse();
+        let mut cx = LayoutContext { file, session };
+
+        // Labels awaiting the next placed section
+        let mut labels = Vec::<(Name, Span)>::new();
+
+        for stmt in &ast.stmts {
+            match *stmt {
+                Stmt::Label(ref label) => {
+                    labels.push((label.name, label.data));
+                },
+                Stmt::Dir(ref dir) if dir.name == Name::DOT_REGION => {
+                    self.read_region(dir, &mut cx);
+                },
+                Stmt::Dir(ref dir) if dir.name == Name::DOT_PLACE => {
+                    if let Some(section) = self.read_place(dir, &mut cx) {
+                        self.define(&mut labels, section, Edge::Start, file);
+                    }
+                },
+                Stmt::Dir(ref dir) if dir.name == Name::DOT_SYMBOL => {
+                    self.read_symbol(dir, &mut cx);
+                },
+                _ => {
+                    cx.error(*stmt.data(), "expected: layout directive");
+                },
+            }
+        }
+
+        match self.rules.last() {
+            Some(rule) => {
+                let section = rule.section;
+                self.define(&mut labels, section, Edge::End, file);
+            },
+            None => for (_, span) in labels {
+                cx.error(span, "expected: .place after label");
+            },
+        }
+    }
+
+    fn define(&mut self, labels: &mut Vec<(Name, Span)>, section: Name, at: Edge, file: Name) {
+        for (name, span) in labels.drain(..) {
+            let def = Loc { file, span };
+            self.symbols.push(LayoutSymbol { name, section, at, def });
+        }
+    }
+
+    fn read_region(&mut self, dir: &Dir<Span>, cx: &mut LayoutContext) {
+        let name = match dir.args.first() {
+            Some(arg) => match read_name(arg, cx) {
+                Some(name) => name,
+                None       => return cx.error(*arg.data(), "expected: region name"),
+            },
+            None => return cx.error(dir.data, "expected: region name"),
+        };
+
+        if dir.args.len() != 3 {
+            return cx.error(dir.data, "expected: region name, origin, and length");
+        }
+
+        let origin = read_addr(&dir.args[1], cx);
+        let length = read_addr(&dir.args[2], cx);
+
+        if self.region(name).is_some() {
+            let msg = format!("region '{}' is already defined", &cx.session.names()[name]);
+            return cx.error(dir.data, &msg);
+        }
+
+        if let (Some(origin), Some(length)) = (origin, length) {
+            self.regions.push(Region { name, origin, length });
+        }
+    }
+
+    fn read_place(&mut self, dir: &Dir<Span>, cx: &mut LayoutContext) -> Option<Name> {
+        let name = match dir.args.first() {
+            Some(arg) => read_name(arg, cx),
+            None      => None,
+        };
+
+        let name = match name {
+            Some(name) => name,
+            None       => {
+                cx.error(dir.data, "expected: section name");
+                return None;
+            },
+        };
+
+        if dir.args.len() > 3 {
+            cx.error(*dir.args[3].data(), "unexpected: argument");
+        }
+
+        let run  = dir.args.get(1).and_then(|a| self.read_target(a, cx));
+        let load = dir.args.get(2).and_then(|a| self.read_target(a, cx));
+
+        self.place(name, run, load);
+        Some(name)
+    }
+
+    fn read_symbol(&mut self, dir: &Dir<Span>, cx: &mut LayoutContext) {
+        if dir.args.len() != 3 {
+            return cx.error(dir.data, "expected: symbol name, start|end|load, and section name");
+        }
+
+        let name = match read_name(&dir.args[0], cx) {
+            Some(name) => name,
+            None       => return cx.error(*dir.args[0].data(), "expected: symbol name"),
+        };
+
+        let at = match dir.args[1] {
+            Arg::Expr(Expr::Ident(_, Name::START)) => Edge::Start,
+            Arg::Expr(Expr::Ident(_, Name::END))   => Edge::End,
+            Arg::Expr(Expr::Ident(_, Name::LOAD))  => Edge::Load,
+            ref arg => return cx.error(*arg.data(), "expected: start, end, or load"),
+        };
+
+        let section = match read_name(&dir.args[2], cx) {
+            Some(section) => section,
+            None          => return cx.error(*dir.args[2].data(), "expected: section name"),
+        };
+
+        let def = Loc { file: cx.file, span: dir.data };
+        self.symbols.push(LayoutSymbol { name, section, at, def });
+    }
+
+    fn read_target(&self, arg: &Arg<Span>, cx: &mut LayoutContext) -> Option<Target> {
+        if let Arg::Expr(Expr::Ident(span, name)) = *arg {
+            return match self.region(name) {
+                Some(index) => Some(Target::Region(index)),
+                None        => {
+                    let msg = format!("undefined region '{}'", &cx.session.names()[name]);
+                    cx.error(span, &msg);
+                    None
+                },
+            };
+        }
+
+        read_addr(arg, cx).map(Target::Addr)
+    }
+}
+
+fn read_name(arg: &Arg<Span>, cx: &mut LayoutContext) -> Option<Name> {
+    match *arg {
+        Arg::Expr(Expr::Ident(_, name))  => Some(name),
+        Arg::Expr(Expr::Str(_, ref s))   => Some(cx.session.names_mut().add(s)),
+        _                                => None,
+    }
+}
+
+fn read_addr(arg: &Arg<Span>, cx: &mut LayoutContext) -> Option<u64> {
+    match *arg {
+        Arg::Expr(ref e) => eval(cx, e).map(|v| v.provisional() as u64),
+        _                => {
+            cx.error(*arg.data(), "expected: address");
+            None
+        },
+    }
+}
+
+/// Context for evaluation of expressions in layout files.
+struct LayoutContext<'a> {
+    file:    Name,
+    session: &'a mut Session,
+}
+
+impl Context for LayoutContext<'_> {
+    fn symbol(&mut self, span: Span, name: Name) -> Option<Value> {
+        let msg = format!("undefined symbol '{}'", &self.session.names()[name]);
+        self.error(span, &msg);
+        None
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        self.session.target()?.builtin(name)
+    }
+
+    fn error(&mut self, span: Span, msg: &str) {
+        let loc = Loc { file: self.file, span };
+        self.session.error(loc, msg);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(session: &mut Session, content: &str) -> Layout {
+        session.set_quiet(true);
+
+        let file       = session.names_mut().add("test.ras");
+        let mut layout = Layout::new();
+        layout.read_content(file, content, session);
+        layout
+    }
+
+    #[test]
+    fn rules() {
+        let mut session = Session::new();
+
+        let layout = read(&mut session, "
+            .region rom, x'1000, x'100
+            .region ram, x'8000, x'100
+            .place .code, rom
+        start:
+            .place .data, ram, rom
+            .place .extra, x'4000
+        end:
+            .symbol data_load, load, .data
+            .symbol \"data_end\", end, \".data\"
+        ");
+
+        let sections = [".code", ".data", ".extra"].map(|s| session.names_mut().add(s));
+        let place    = |i: usize| layout.rule(sections[i]).map(|r| (r.run, r.load));
+        let names    = session.names();
+        let label = |i: usize| {
+            let s = layout.symbols[i];
+            (&names[s.name], &names[s.section], s.at)
+        };
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(layout.regions.len(),     2);
+        assert_eq!(layout.regions[1].origin, 0x8000);
+        assert_eq!(layout.regions[1].end(),  0x8100);
+        assert_eq!(place(0), Some((Some(Target::Region(0)),    None)));
+        assert_eq!(place(1), Some((Some(Target::Region(1)),    Some(Target::Region(0)))));
+        assert_eq!(place(2), Some((Some(Target::Addr(0x4000)), None)));
+        assert_eq!(label(0), ("start",     ".data",  Edge::Start));
+        assert_eq!(label(1), ("data_load", ".data",  Edge::Load ));
+        assert_eq!(label(2), ("data_end",  ".data",  Edge::End  ));
+        assert_eq!(label(3), ("end",       ".extra", Edge::End  ));
+    }
+
+    #[test]
+    fn invalid_symbol() {
+        let mut session = Session::new();
+
+        read(&mut session, ".symbol a, middle, .data\n.symbol b, start");
+
+        let msgs = session.diagnostics().iter().map(|d| &d.msg[..]).collect::<Vec<_>>();
+        assert_eq!(msgs, [
+            "expected: start, end, or load",
+            "expected: symbol name, start|end|load, and section name",
+        ]);
+    }
+
+    #[test]
+    fn undefined_region() {
+        let mut session = Session::new();
+
+        read(&mut session, ".place .code, rom");
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "undefined region 'rom'");
+    }
+}
@@ -0,0 +1,699 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Linker.
+//!
+//! The linker combines assembled units into a single program.  It places the
+//! relocatable sections of the units according to layout rules, resolves
+//! symbols across units, and computes the values of relocations.
+//!
+//! Relocatable sections with the same name merge into a single output section,
+//! in order of their units.  A section whose address `.org` fixes becomes an
+//! output section by itself.
+//!
+//! An output section has two addresses: the address at which it runs, which
+//! determines the values of its symbols, and the address at which it is
+//! loaded, which determines its position in the output image.  The two are
+//! the same unless the layout says otherwise.
+
+use std::collections::HashMap;
+
+use crate::asm::*;
+use crate::lang::ast::{Scope, Span};
+use crate::name::Name;
+use crate::session::{Level, Loc, Session};
+
+mod layout;
+
+pub use self::layout::*;
+
+// ----------------------------------------------------------------------------
+
+/// Linked program.
+#[derive(Clone, Debug)]
+pub struct Program {
+    /// Output sections, in order of placement.
+    pub sections: Vec<Section>,
+
+    /// Symbols defined by the layout, with their values.
+    pub symbols: Vec<(LayoutSymbol, u64)>,
+}
+
+/// Links the given `units` into a program according to the given `layout`.
+///
+/// On return, the base address of each section of each unit is the address
+/// at which the linker placed it.
+pub fn link(units: &mut [Unit], layout: &Layout, session: &mut Session) -> Program {
+    let mut groups = group_sections(units);
+
+    add_empty_groups(&mut groups, layout, session);
+    order_groups(&mut groups, layout);
+    place_groups(&mut groups, units, layout, session);
+
+    let mut sections = merge_groups(&groups, units);
+
+    check_overlap(&sections, session);
+
+    let mut globals = resolve_globals(units, session);
+    let layout_syms = define_layout_symbols(&groups, units, layout, &mut globals, session);
+
+    let values  = layout_syms.iter().map(|&(s, v)| (s.name, v)).collect();
+    let symbols = Symbols { globals: &globals, layout: &values };
+
+    for (u, unit) in units.iter().enumerate() {
+        for reloc in &unit.relocs {
+            apply_reloc(units, u, reloc, &groups, &mut sections, &symbols, session);
+        }
+    }
+
+    Program { sections, symbols: layout_syms }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Group of unit sections that become a single output section.
+#[derive(Clone, Debug)]
+struct Group {
+    /// Name of the output section.
+    name: Name,
+
+    /// Kind of the output section.
+    kind: SectionKind,
+
+    /// Whether `.org` fixes the address of the output section.
+    fixed: bool,
+
+    /// Member sections, as (unit index, section index) pairs.
+    members: Vec<(usize, usize)>,
+
+    /// Address at which the output section runs.
+    base: u64,
+
+    /// Address at which the output section is loaded, if different.
+    load: Option<u64>,
+
+    /// Size of the output section in bytes.
+    size: u64,
+}
+
+fn group_sections(units: &[Unit]) -> Vec<Group> {
+    let mut groups = Vec::<Group>::new();
+
+    for (u, unit) in units.iter().enumerate() {
+        for (s, section) in unit.sections.iter().enumerate() {
+            if section.size == 0 && !section.fixed {
+                continue;
+            }
+
+            let group = groups
+                .iter_mut()
+                .find(|g| !g.fixed && !section.fixed && g.name == section.name);
+
+            match group {
+                Some(group) => group.members.push((u, s)),
+                None        => groups.push(Group {
+                    name:    section.name,
+                    kind:    section.kind,
+                    fixed:   section.fixed,
+                    members: vec![(u, s)],
+                    base:    section.base,
+                    load:    None,
+                    size:    section.size,
+                }),
+            }
+        }
+    }
+
+    groups
+}
+
+/// Adds an empty group for each section that the layout places but that no
+/// unit contains, so that layout symbols can refer to it.
+fn add_empty_groups(groups: &mut Vec<Group>, layout: &Layout, session: &Session) {
+    for rule in &layout.rules {
+        if groups.iter().any(|g| g.name == rule.section) {
+            continue;
+        }
+
+        groups.push(Group {
+            name:    rule.section,
+            kind:    SectionKind::for_name(&session.names()[rule.section]),
+            fixed:   false,
+            members: vec![],
+            base:    0,
+            load:    None,
+            size:    0,
+        });
+    }
+}
+
+fn order_groups(groups: &mut [Group], layout: &Layout) {
+    groups.sort_by_key(|g| {
+        match layout.rules.iter().position(|r| r.section == g.name) {
+            Some(i) => (0, i),
+            None    => (1, g.kind as usize),
+        }
+    });
+}
+
+fn place_groups(groups: &mut [Group], units: &mut [Unit], layout: &Layout, session: &mut Session) {
+    // Next available address in each region
+    let mut cursors = layout.regions.iter().map(|r| r.origin).collect::<Vec<_>>();
+
+    // Next available address after the previous group, and its region
+    let mut next   = layout.base;
+    let mut region = None;
+
+    for group in groups.iter_mut().filter(|g| !g.fixed) {
+        let rule  = layout.rule(group.name);
+        let align = group.members
+            .iter()
+            .map(|&(u, s)| units[u].sections[s].align)
+            .max()
+            .unwrap_or(1);
+
+        // Choose where the group runs
+        let run = match layout.start(group.name) {
+            Some(addr) => Some(Target::Addr(addr)),
+            None       => rule.and_then(|r| r.run),
+        };
+
+        let start = match run {
+            Some(Target::Region(r)) => { region = Some(r); cursors[r] },
+            Some(Target::Addr(a))   => { region = None;    a          },
+            None                    => region.map_or(next, |r| cursors[r]),
+        };
+
+        group.base = align_up(start, align);
+
+        let mut addr = group.base;
+
+        for &(u, s) in &group.members {
+            let section = &mut units[u].sections[s];
+            section.base = align_up(addr, section.align);
+            addr         = section.end();
+        }
+
+        group.size = addr - group.base;
+        next       = addr;
+
+        if let Some(r) = region {
+            cursors[r] = addr;
+            check_fit(group.name, group.base, addr, &layout.regions[r], session);
+        }
+
+        // Choose where the group is loaded
+        group.load = match rule.and_then(|r| r.load) {
+            Some(Target::Region(r)) => {
+                let load = align_up(cursors[r], align);
+                cursors[r] = load + group.size;
+                check_fit(group.name, load, cursors[r], &layout.regions[r], session);
+                Some(load)
+            },
+            Some(Target::Addr(a)) => Some(a),
+            None                  => None,
+        };
+
+        if let Some(load) = group.load {
+            for &(u, s) in &group.members {
+                let section = &mut units[u].sections[s];
+                section.load = Some(load + (section.base - group.base));
+            }
+        }
+    }
+}
+
+fn check_fit(name: Name, start: u64, end: u64, region: &Region, session: &mut Session) {
+    if start >= region.origin && end <= region.end() {
+        return;
+    }
+
+    let over = end.saturating_sub(region.end()).max(region.origin.saturating_sub(start));
+    let msg  = format!(
+        "section '{}' does not fit in region '{}' ({} byte{} over)",
+        &session.names()[name],
+        &session.names()[region.name],
+        over,
+        if over == 1 { "" } else { "s" },
+    );
+    session.report(Level::Error, None, msg);
+}
+
+fn merge_groups(groups: &[Group], units: &[Unit]) -> Vec<Section> {
+    groups.iter().map(|group| {
+        let mut section = Section::new(group.name, group.kind);
+
+        section.base  = group.base;
+        section.load  = group.load;
+        section.fixed = group.fixed;
+
+        for &(u, s) in &group.members {
+            let member = &units[u].sections[s];
+            section.align = section.align.max(member.align);
+            section.reserve(member.base - section.end());
+
+            match member.has_data() {
+                true  => section.emit(&member.data),
+                false => section.reserve(member.size),
+            }
+        }
+
+        section
+    }).collect()
+}
+
+fn check_overlap(sections: &[Section], session: &mut Session) {
+    // Where sections run
+    let mut order = (0..sections.len())
+        .filter(|&i| sections[i].size != 0)
+        .collect::<Vec<_>>();
+
+    order.sort_by_key(|&i| sections[i].base);
+
+    for pair in order.windows(2) {
+        let (a, b) = (&sections[pair[0]], &sections[pair[1]]);
+        if b.base < a.end() {
+            report_overlap(a, b, "", session);
+        }
+    }
+
+    // Where sections are loaded, if different
+    let mut order = (0..sections.len())
+        .filter(|&i| sections[i].size != 0 && sections[i].has_data())
+        .collect::<Vec<_>>();
+
+    order.sort_by_key(|&i| sections[i].load_base());
+
+    for pair in order.windows(2) {
+        let (a, b) = (&sections[pair[0]], &sections[pair[1]]);
+        if (a.load.is_some() || b.load.is_some())
+            && b.load_base() < a.load_base().wrapping_add(a.size)
+        {
+            report_overlap(a, b, " where loaded", session);
+        }
+    }
+}
+
+fn report_overlap(a: &Section, b: &Section, suffix: &str, session: &mut Session) {
+    let msg = format!(
+        "sections '{}' and '{}' overlap{}",
+        &session.names()[a.name], &session.names()[b.name], suffix,
+    );
+    session.report(Level::Error, None, msg);
+}
+
+// ----------------------------------------------------------------------------
+
+/// Table of symbols visible to all units, mapping each name to the index of
+/// the unit that defines it.
+type Globals = HashMap<Name, usize>;
+
+fn resolve_globals(units: &[Unit], session: &mut Session) -> Globals {
+    let mut globals = Globals::new();
+
+    for (u, unit) in units.iter().enumerate() {
+        for symbol in unit.symbols.iter() {
+            if !symbol.is_defined() || symbol.scope < Scope::Weak {
+                continue;
+            }
+
+            let prev = match globals.get(&symbol.name) {
+                Some(&prev) => units[prev].symbols.get(symbol.name).unwrap(),
+                None        => {
+                    globals.insert(symbol.name, u);
+                    continue;
+                },
+            };
+
+            match (prev.scope, symbol.scope) {
+                (Scope::Weak, Scope::Public) => {
+                    // Public overrides weak
+                    globals.insert(symbol.name, u);
+                },
+                (Scope::Public, Scope::Public) => {
+                    let msg = format!(
+                        "symbol '{}' is defined in multiple units",
+                        &session.names()[symbol.name]
+                    );
+                    session.report(Level::Error, symbol.def, msg);
+                    session.report(Level::Note, prev.def, "previous definition is here");
+                },
+                _ => {
+                    // Keep the first weak symbol or the public symbol
+                },
+            }
+        }
+    }
+
+    globals
+}
+
+/// Computes the values of symbols defined by the layout.  A layout symbol
+/// overrides a weak symbol of the same name.
+fn define_layout_symbols(
+    groups:  &[Group],
+    units:   &[Unit],
+    layout:  &Layout,
+    globals: &mut Globals,
+    session: &mut Session,
+) -> Vec<(LayoutSymbol, u64)> {
+    let mut symbols = Vec::<(LayoutSymbol, u64)>::new();
+
+    for symbol in &layout.symbols {
+        let name = &session.names()[symbol.name];
+
+        if symbols.iter().any(|(s, _)| s.name == symbol.name) {
+            let msg = format!("symbol '{}' is already defined", name);
+            session.error(symbol.def, msg);
+            continue;
+        }
+
+        if let Some(&u) = globals.get(&symbol.name) {
+            let other = units[u].symbols.get(symbol.name).unwrap();
+            if other.scope == Scope::Public {
+                let msg = format!("symbol '{}' is defined by both layout and unit", name);
+                session.error(symbol.def, msg);
+                session.report(Level::Note, other.def, "other definition is here");
+                continue;
+            }
+            globals.remove(&symbol.name);
+        }
+
+        let group = groups.iter().find(|g| !g.fixed && g.name == symbol.section);
+        let value = match (group, symbol.at) {
+            (Some(g), Edge::Start) => g.base,
+            (Some(g), Edge::End)   => g.base + g.size,
+            (Some(g), Edge::Load)  => g.load.unwrap_or(g.base),
+            (None,    _)           => continue,
+        };
+
+        symbols.push((*symbol, value));
+    }
+
+    symbols
+}
+
+/// Returns the address of the given symbol of the given unit.
+fn symbol_value(unit: &Unit, symbol: &Symbol) -> i64 {
+    let base = symbol.section.map_or(0, |s| unit.sections[s].base);
+    base.wrapping_add(symbol.value) as i64
+}
+
+fn apply_reloc(
+    units:    &[Unit],
+    unit:     usize,
+    reloc:    &Reloc,
+    groups:   &[Group],
+    sections: &mut [Section],
+    symbols:  &Symbols,
+    session:  &mut Session,
+) {
+    let mut cx = LinkContext { units, unit, reloc, symbols, session };
+
+    let value = match eval(&mut cx, &reloc.expr) {
+        Some(value) => value.provisional(),
+        None        => return,
+    };
+
+    // Find the output section
+    let g = groups
+        .iter()
+        .position(|g| g.members.contains(&(unit, reloc.section)))
+        .unwrap();
+
+    let size    = reloc.kind.size;
+    let section = &mut sections[g];
+    let offset  = units[unit].sections[reloc.section].base - section.base + reloc.offset;
+    let bytes   = &mut section.data[offset as usize..][..size];
+
+    // Merge the value into the field
+    let mut buf = [0u8; 8];
+    let field = match units[unit].endian {
+        Endian::Little => { buf[..size].copy_from_slice(bytes);     u64::from_le_bytes(buf) },
+        Endian::Big    => { buf[8 - size..].copy_from_slice(bytes); u64::from_be_bytes(buf) },
+    };
+
+    let field = match (reloc.kind.apply)(value, field) {
+        Ok(field) => field,
+        Err(msg)  => return session.error(reloc.loc, msg),
+    };
+
+    match units[unit].endian {
+        Endian::Little => bytes.copy_from_slice(&field.to_le_bytes()[..size]),
+        Endian::Big    => bytes.copy_from_slice(&field.to_be_bytes()[8 - size..]),
+    }
+}
+
+/// Symbols visible to all units.
+struct Symbols<'a> {
+    /// Symbols defined by units.
+    globals: &'a Globals,
+
+    /// Symbols defined by the layout.
+    layout: &'a HashMap<Name, u64>,
+}
+
+/// Context for evaluation of relocations.
+struct LinkContext<'a> {
+    units:   &'a [Unit],
+    unit:    usize,
+    reloc:   &'a Reloc,
+    symbols: &'a Symbols<'a>,
+    session: &'a mut Session,
+}
+
+impl Context for LinkContext<'_> {
+    fn symbol(&mut self, span: Span, name: Name) -> Option<Value> {
+        let unit = &self.units[self.unit];
+
+        if name == Name::DOT {
+            let base = unit.sections[self.reloc.section].base;
+            return Some(Value::Const(base.wrapping_add(self.reloc.here) as i64));
+        }
+
+        // Prefer a symbol of this unit, qualifying a local label
+        let names = self.session.names_mut();
+        let local = if names[name].starts_with('.') {
+            let qualified = format!("{}{}", &names[self.reloc.scope], &names[name]);
+            unit.symbols.get(names.add(&qualified)).filter(|s| s.is_defined())
+        } else {
+            None
+        };
+        let symbol = local.or_else(|| unit.symbols.get(name).filter(|s| s.is_defined()));
+
+        match symbol {
+            Some(symbol) if symbol.scope < Scope::Weak => {
+                return Some(Value::Const(symbol_value(unit, symbol)));
+            },
+            _ => (),
+        }
+
+        // Otherwise, use the symbol visible to all units
+        if let Some(&u) = self.symbols.globals.get(&name) {
+            let unit = &self.units[u];
+            return Some(Value::Const(symbol_value(unit, unit.symbols.get(name).unwrap())));
+        }
+
+        if let Some(&value) = self.symbols.layout.get(&name) {
+            return Some(Value::Const(value as i64));
+        }
+
+        let msg = format!("undefined symbol '{}'", &self.session.names()[name]);
+        self.error(span, &msg);
+        None
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        self.session.target()?.builtin(name)
+    }
+
+    fn error(&mut self, span: Span, msg: &str) {
+        let loc = Loc { file: self.reloc.loc.file, span };
+        self.session.error(loc, msg);
+    }
+}
+
+/// Rounds `addr` up to a multiple of `align`.
+fn align_up(addr: u64, align: u64) -> u64 {
+    match addr % align {
+        0      => addr,
+        excess => addr + (align - excess),
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link_sources(session: &mut Session, layout: &Layout, sources: &[&str]) -> Program {
+        session.set_quiet(true);
+
+        let mut units = sources
+            .iter()
+            .enumerate()
+            .map(|(i, s)| session.assemble(&format!("{}.s", i), s))
+            .collect::<Vec<_>>();
+
+        link(&mut units, layout, session)
+    }
+
+    #[test]
+    fn resolve_across_units() {
+        let mut session = Session::new();
+
+        let program = link_sources(&mut session, &Layout::new(), &[
+            "a:: .int16 b, c\nc: .int8 1",
+            "b:: .int16 a, c\nc: .int8 2",
+        ]);
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(program.sections.len(), 1);
+        assert_eq!(program.sections[0].data, [0, 5, 0, 4, 1, 0, 0, 0, 9, 2]);
+    }
+
+    #[test]
+    fn public_overrides_weak() {
+        let mut session = Session::new();
+
+        let program = link_sources(&mut session, &Layout::new(), &[
+            "x:? .int8 x",
+            "x:: .int8 x",
+        ]);
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(program.sections[0].data, [1, 1]);
+    }
+
+    #[test]
+    fn public_overrides_weak_within_unit() {
+        let mut session = Session::new();
+        session.set_target(crate::target::find("m68000").unwrap());
+
+        // The unit that defines the weak symbol refers to the public one too
+        let program = link_sources(&mut session, &Layout::new(), &[
+            ".org x'100\nstart: bsr handler\nrts\nhandler:? rts\n.int32 handler",
+            ".org x'200\nhandler:: nop\nrts",
+        ]);
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(program.sections[0].data, [0x61, 0, 0, 0xFE, 0x4E, 0x75, 0x4E, 0x75, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn multiple_public() {
+        let mut session = Session::new();
+
+        link_sources(&mut session, &Layout::new(), &["x:: .int8 0", "x:: .int8 0"]);
+
+        assert_eq!(session.error_count(), 1);
+    }
+
+    #[test]
+    fn undefined() {
+        let mut session = Session::new();
+
+        link_sources(&mut session, &Layout::new(), &["x: .int8 x", ".int8 x"]);
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(session.diagnostics()[0].msg, "undefined symbol 'x'");
+    }
+
+    fn layout(session: &mut Session) -> Layout {
+        let mut layout = Layout::new();
+        let names      = session.names_mut();
+
+        let [rom, ram, code, data, bss] = [
+            "rom", "ram", ".code", ".data", ".bss"
+        ].map(|s| names.add(s));
+
+        layout.regions.push(Region { name: rom, origin: 0x1000, length: 0x10 });
+        layout.regions.push(Region { name: ram, origin: 0x8000, length: 0x08 });
+        layout.place(code, Some(Target::Region(0)), None);
+        layout.place(data, Some(Target::Region(1)), Some(Target::Region(0)));
+        layout.place(bss,  None,                    None);
+
+        let def = Loc { file: Name::EMPTY, span: Span::default() };
+        let mut sym = |s: &str, section, at| LayoutSymbol { name: names.add(s), section, at, def };
+        layout.symbols.push(sym("bss_start", bss,  Edge::Start));
+        layout.symbols.push(sym("bss_end",   bss,  Edge::End  ));
+        layout.symbols.push(sym("data_load", data, Edge::Load ));
+        layout.symbols.push(sym("data_end",  data, Edge::End  ));
+        layout
+    }
+
+    #[test]
+    fn regions() {
+        let mut session = Session::new();
+        let layout      = layout(&mut session);
+
+        let program = link_sources(&mut session, &layout, &[
+            ".int16 bss_start, bss_end, data_load, data_end\n.data\n.int8 1\n.bss\n.skip 2",
+        ]);
+
+        let placed = program.sections
+            .iter()
+            .map(|s| (s.base, s.load_base(), s.size))
+            .collect::<Vec<_>>();
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(placed, [(0x1000, 0x1000, 8), (0x8000, 0x1008, 1), (0x8001, 0x8001, 2)]);
+        assert_eq!(program.sections[0].data, [0x80, 0x01, 0x80, 0x03, 0x10, 0x08, 0x80, 0x01]);
+        assert_eq!(program.symbols[2].1, 0x1008);
+        assert_eq!(crate::output::flat_binary(&program.sections)[8..], [1]);
+    }
+
+    #[test]
+    fn region_overflow() {
+        let mut session = Session::new();
+        let layout      = layout(&mut session);
+
+        link_sources(&mut session, &layout, &[".bss\n.skip 9"]);
+
+        assert_eq!(session.error_count(), 1);
+        assert_eq!(
+            session.diagnostics()[0].msg,
+            "section '.bss' does not fit in region 'ram' (1 byte over)"
+        );
+    }
+
+    #[test]
+    fn placement() {
+        let mut session = Session::new();
+        let mut layout  = Layout::new();
+
+        let data = session.names_mut().add(".data");
+        layout.base = 0x100;
+        layout.set_start(data, 0x200);
+
+        let program = link_sources(&mut session, &layout, &[
+            ".data\n.int8 1\n.code\n.int8 2",
+            ".bss\n.skip 4\n.code\n.align 4\n.int32 .",
+        ]);
+
+        let placed = program.sections
+            .iter()
+            .map(|s| (&session.names()[s.name], s.base, s.size))
+            .collect::<Vec<_>>();
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(placed, [(".code", 0x100, 8), (".data", 0x200, 1), (".bss", 0x201, 4)]);
+        assert_eq!(program.sections[0].data, [2, 0, 0, 0, 0, 0, 1, 4]);
+    }
+}
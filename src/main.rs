@@ -21,46 +21,213 @@
 #![allow(dead_code)]
 #![allow(unused_macros)]
 
+mod asm;
+mod depfile;
+mod disasm;
 mod lang;
+mod link;
+mod map;
 mod name;
 mod num;
+mod options;
+mod output;
 mod session;
+mod sim;
+mod target;
+mod test;
 
 use std::env::args;
 use std::fs::File;
-use std::io::{Read, stdin};
+use std::io::{self, BufWriter, Read, Write, stdin};
+use std::process::exit;
 
-use session::Session;
+use link::Layout;
+use options::{Command, Options};
+use session::{Level, Session};
 
 fn main() {
+    let opts = match Options::parse(args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e)   => {
+            eprintln!("ras: {}", e);
+            exit(2);
+        },
+    };
+
     let mut session = Session::new();
+    let mut units   = vec![];
+
+    if let Some(ref name) = opts.target {
+        match target::find(name) {
+            Some(info) => session.set_target(info),
+            None       => {
+                let names = target::TARGETS.iter().map(|t| t.name).collect::<Vec<_>>();
+                eprintln!("ras: unknown target '{}' (available: {})", name, names.join(", "));
+                exit(2);
+            },
+        }
+    }
+
+    if opts.command == Command::Disasm {
+        return disassemble(&opts, &mut session);
+    }
+
+    session.set_tests(opts.command == Command::Test);
 
-    for_each_input(|path, content| {
-        session.print_tokens(path, content);
-        session.print_ast(path, content);
+    for_each_input(&opts.inputs, &mut session, |session, path, content| {
+        if opts.tokens { session.print_tokens(path, content); }
+        if opts.ast    { session.print_ast   (path, content); }
+
+        units.push(session.assemble(path, content));
     });
+
+    let mut layout = Layout::new();
+
+    if let Some(ref path) = opts.layout {
+        layout.read(path, &mut session);
+    }
+
+    layout.base = opts.base;
+
+    for (name, addr) in &opts.section_starts {
+        let name = session.names_mut().add(name);
+        layout.set_start(name, *addr);
+    }
+
+    let program = link::link(&mut units, &layout, &mut session);
+
+    if opts.command == Command::Test {
+        return run_tests(&units, &program, &mut session);
+    }
+
+    if let Some(ref path) = opts.map {
+        write_file(&mut session, path, |f, session| {
+            map::write_map(f, &units, &program.symbols, session.names())
+        });
+    }
+
+    if let Some(ref path) = opts.output {
+        if session.error_count() == 0 {
+            let image = output::flat_binary(&program.sections);
+            write_file(&mut session, path, |f, _| f.write_all(&image));
+        }
+
+        if let Some(ref dep_path) = opts.depfile_path() {
+            write_file(&mut session, dep_path, |f, session| {
+                let mut deps = vec![];
+                for &dep in units.iter().flat_map(|u| &u.deps) {
+                    let dep = &session.names()[dep];
+                    if dep != "-" && !deps.contains(&dep) {
+                        deps.push(dep);
+                    }
+                }
+                if let Some(ref layout) = opts.layout {
+                    deps.push(layout.as_str());
+                }
+                depfile::write_depfile(f, path, deps)
+            });
+        }
+    }
+
+    if session.error_count() != 0 {
+        exit(1);
+    }
 }
 
-fn for_each_input<F>(mut f: F)
+fn disassemble(opts: &Options, session: &mut Session) {
+    let path = &opts.inputs[0];
+
+    let result = match path.as_str() {
+        "-" => {
+            let mut bytes = vec![];
+            stdin().read_to_end(&mut bytes).map(|_| bytes)
+        },
+        _ => std::fs::read(path),
+    };
+
+    let bytes = result.unwrap_or_else(|e| {
+        eprintln!("ras: {}: {}", path, e);
+        exit(1);
+    });
+
+    let image = disasm::read(&bytes, opts.base).unwrap_or_else(|e| {
+        eprintln!("ras: {}: {}", path, e);
+        exit(1);
+    });
+
+    // The target is known to exist
+    let info = target::find(opts.target.as_deref().unwrap()).unwrap();
+    let text = disasm::disassemble(info, &image.regions, &image.symbols, session);
+
+    match opts.output {
+        Some(ref path) => write_file(session, path, |f, _| f.write_all(text.as_bytes())),
+        None           => print!("{}", text),
+    }
+
+    if session.error_count() != 0 {
+        exit(1);
+    }
+}
+
+fn run_tests(units: &[asm::Unit], program: &link::Program, session: &mut Session) {
+    if session.error_count() != 0 {
+        exit(1);
+    }
+
+    let outcomes = test::run(units, program, session);
+    let passed   = outcomes.iter().filter(|o| o.passed).count();
+    let failed   = outcomes.len() - passed;
+
+    for outcome in &outcomes {
+        let result = match outcome.passed {
+            true  => "ok",
+            false => "FAILED",
+        };
+        println!("test {} ... {}", outcome.name, result);
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+
+    if failed != 0 || session.error_count() != 0 {
+        exit(1);
+    }
+}
+
+fn for_each_input<F>(paths: &[String], session: &mut Session, mut f: F)
 where
-    F: FnMut(&str, &str) -> ()
+    F: FnMut(&mut Session, &str, &str)
 {
     let mut content = String::with_capacity(4096);
 
-    for path in args().skip(1) {
+    for path in paths {
         content.clear();
 
         let result = if path == "-" {
             stdin().read_to_string(&mut content)
         } else {
-            File::open(&path).and_then(|mut f| f.read_to_string(&mut content))
+            File::open(path).and_then(|mut f| f.read_to_string(&mut content))
         };
 
         if let Err(e) = result {
-            eprintln!("{}: {}", path, e); // TODO: add error state
+            session.report(Level::Error, None, format_args!("{}: {}", path, e));
             continue;
         }
 
-        f(path.as_str(), content.as_str())
+        f(session, path.as_str(), content.as_str())
+    }
+}
+
+fn write_file<F>(session: &mut Session, path: &str, f: F)
+where
+    F: FnOnce(&mut dyn Write, &Session) -> io::Result<()>
+{
+    let result = File::create(path).and_then(|file| {
+        let mut file = BufWriter::new(file);
+        f(&mut file, session)?;
+        file.flush()
+    });
+
+    if let Err(e) = result {
+        session.report(Level::Error, None, format_args!("{}: {}", path, e));
     }
 }
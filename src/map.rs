@@ -0,0 +1,225 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Symbol map and cross-reference report.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::asm::Unit;
+use crate::lang::ast::Scope;
+use crate::link::LayoutSymbol;
+use crate::name::{Name, NameTable};
+use crate::session::Loc;
+
+/// Writes a symbol map and cross-reference report for the given linked
+/// `units` and the symbols defined by the layout.
+pub fn write_map<W: Write + ?Sized>(
+    out:    &mut W,
+    units:  &[Unit],
+    layout: &[(LayoutSymbol, u64)],
+    names:  &NameTable,
+) -> io::Result<()> {
+    // Gather references to symbols defined in other units
+    let mut globals  = HashMap::<Name, Vec<&Loc>>::new();
+    let mut external = HashMap::<Name, Vec<&Loc>>::new();
+
+    for symbol in units.iter().flat_map(|u| u.symbols.iter()) {
+        if symbol.is_defined() && symbol.scope >= Scope::Weak {
+            globals.entry(symbol.name).or_default();
+        }
+    }
+
+    for (symbol, _) in layout {
+        globals.entry(symbol.name).or_default();
+    }
+
+    for symbol in units.iter().flat_map(|u| u.symbols.iter()) {
+        if !symbol.is_defined() && globals.contains_key(&symbol.name) {
+            external.entry(symbol.name).or_default().extend(&symbol.refs);
+        }
+    }
+
+    writeln!(out, "SECTIONS")?;
+    writeln!(out)?;
+    writeln!(out, "Section                  Kind              Base             Load             Size  File")?;
+
+    for unit in units {
+        for section in &unit.sections {
+            writeln!(out, "{:<24} {:<5} {:016X} {:016X} {:016X}  {}",
+                &names[section.name],
+                format!("{:?}", section.kind),
+                section.base,
+                section.load_base(),
+                section.size,
+                &names[unit.file],
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "SYMBOLS")?;
+    writeln!(out)?;
+    writeln!(out, "Symbol                           Scope   Section                             Value             Size  Defined")?;
+
+    for unit in units {
+        for symbol in unit.symbols.iter() {
+            let section = symbol.section.map(|s| &unit.sections[s]);
+            let value   = section.map_or(0, |s| s.base).wrapping_add(symbol.value);
+
+            match symbol.def {
+                Some(ref def) => writeln!(out, "{:<32} {:<7} {:<24} {:016X} {:016X}  {}",
+                    &names[symbol.name],
+                    format!("{:?}", symbol.scope),
+                    section.map_or("(absolute)", |s| &names[s.name]),
+                    value,
+                    symbol.size,
+                    def.for_display(names),
+                )?,
+                None if external.contains_key(&symbol.name) => (),
+                None => writeln!(out, "{:<32} {:<7} (undefined)",
+                    &names[symbol.name], "",
+                )?,
+            }
+        }
+    }
+
+    for &(ref symbol, value) in layout {
+        writeln!(out, "{:<32} {:<7} {:<24} {:016X} {:016X}  {}",
+            &names[symbol.name],
+            "Public",
+            "(layout)",
+            value,
+            0,
+            symbol.def.for_display(names),
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "CROSS-REFERENCE")?;
+    writeln!(out)?;
+
+    for unit in units {
+        for symbol in unit.symbols.iter() {
+            let others = match symbol.def {
+                Some(_) if symbol.scope >= Scope::Weak => external.get(&symbol.name),
+                Some(_)                                => None,
+                None if external.contains_key(&symbol.name) => continue,
+                None                                   => None,
+            };
+
+            write!(out, "{:<32}", &names[symbol.name])?;
+
+            if let Some(ref def) = symbol.def {
+                write!(out, " {}*", def.for_display(names))?;
+            }
+
+            let refs = symbol.refs.iter().chain(others.into_iter().flatten().copied());
+            let mut any = false;
+
+            for r in refs {
+                write!(out, " {}", r.for_display(names))?;
+                any = true;
+            }
+
+            if !any {
+                write!(out, " (unreferenced)")?;
+            }
+
+            writeln!(out)?;
+        }
+    }
+
+    for (symbol, _) in layout {
+        write!(out, "{:<32} {}*", &names[symbol.name], symbol.def.for_display(names))?;
+
+        match external.get(&symbol.name) {
+            Some(refs) if !refs.is_empty() => for r in refs {
+                write!(out, " {}", r.for_display(names))?;
+            },
+            _ => write!(out, " (unreferenced)")?,
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::ast::Span;
+    use crate::link::{link, Edge, Layout, LayoutSymbol};
+    use crate::session::Session;
+
+    use super::*;
+
+    #[test]
+    fn map() {
+        let mut session = Session::new();
+        session.set_quiet(true);
+
+        let a = "start:: .int8 1\n.int16 ext\nhelper: .int8 2\n.data\nflag:? .int8 3";
+        let b = "ext:: .int8 start\n.int16 flag, data_end";
+
+        let mut units = vec![session.assemble("a.s", a), session.assemble("b.s", b)];
+
+        // Layout symbol at the end of .data
+        let names      = session.names_mut();
+        let name       = names.add("data_end");
+        let section    = names.add(".data");
+        let file       = names.add("layout.ras");
+        let def        = Loc { file, span: Span { line: 2, start: 0, end: 0 } };
+        let mut layout = Layout::new();
+        layout.symbols.push(LayoutSymbol { name, section, at: Edge::End, def });
+
+        let program = link(&mut units, &layout, &mut session);
+        assert_eq!(session.error_count(), 0);
+
+        let mut out = Vec::new();
+        write_map(&mut out, &units, &program.symbols, session.names()).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+SECTIONS
+
+Section                  Kind              Base             Load             Size  File
+.code                    Code  0000000000000000 0000000000000000 0000000000000004  a.s
+.data                    Data  0000000000000009 0000000000000009 0000000000000001  a.s
+.code                    Code  0000000000000004 0000000000000004 0000000000000005  b.s
+
+SYMBOLS
+
+Symbol                           Scope   Section                             Value             Size  Defined
+start                            Public  .code                    0000000000000000 0000000000000003  a.s:1
+helper                           Private .code                    0000000000000003 0000000000000001  a.s:3
+flag                             Weak    .data                    0000000000000009 0000000000000001  a.s:5
+ext                              Public  .code                    0000000000000004 0000000000000005  b.s:1
+data_end                         Public  (layout)                 000000000000000A 0000000000000000  layout.ras:2
+
+CROSS-REFERENCE
+
+start                            a.s:1* b.s:1
+helper                           a.s:3* (unreferenced)
+flag                             a.s:5* b.s:2
+ext                              b.s:1* a.s:2
+data_end                         layout.ras:2* b.s:2
+");
+    }
+}
@@ -168,7 +168,7 @@ impl StringArena {
 macro_rules! prepopulate {
     ($($(#[$attr:meta])* $ident:ident => $value:literal,)*) => {
         #[repr(u32)]
-        #[allow(non_camel_case_types)]
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
         enum _Names { $($ident),* }
 
         impl Name { $(
@@ -204,6 +204,14 @@ prepopulate! {
     // Misc
     DOT         => ".",
     DOT_NOP     => ".nop",
+    DOT_BLOCK   => ".block",
+
+    // Builtin functions
+    LO          => "lo",
+    HI          => "hi",
+    LO8         => "lo8",
+    HI8         => "hi8",
+    PM          => "pm",
 
     // Messages
     DOT_PRINT   => ".print",
@@ -233,6 +241,7 @@ prepopulate! {
     DOT_DATA    => ".data",
     DOT_BSS     => ".bss",
     DOT_STRUCT  => ".struct",
+    DOT_UNION   => ".union",
 
     // Address
     DOT_ORG     => ".org",
@@ -252,9 +261,24 @@ prepopulate! {
     DOT_ASCIIZ  => ".asciiz",
     DOT_UTF8    => ".utf8",
     DOT_UTF8Z   => ".utf8z",
-    DOT_UTF16   => ".utf8",
-    DOT_UTF16Z  => ".utf8z",
+    DOT_UTF16   => ".utf16",
+    DOT_UTF16Z  => ".utf16z",
     DOT_NEW     => ".object",
+
+    // Layout
+    DOT_PLACE   => ".place",
+    DOT_REGION  => ".region",
+    DOT_SYMBOL  => ".symbol",
+    START       => "start",
+    END         => "end",
+    LOAD        => "load",
+
+    // Testing
+    DOT_TEST    => ".test",
+    DOT_EXPECT  => ".expect",
+    DOT_MAP     => ".map",
+    RAM         => "ram",
+    ECHO        => "echo",
 }
 
 // ----------------------------------------------------------------------------
@@ -263,7 +287,7 @@ prepopulate! {
 mod tests {
     use super::{Name, NameTable};
 
-    const INITIAL_LEN: usize = 46; // Increment for each prepopulated name
+    const INITIAL_LEN: usize = 66; // Increment for each prepopulated name
 
     #[test]
     fn empty() {
@@ -150,6 +150,13 @@ pub struct Num {
     pub base: Base,
 }
 
+impl Num {
+    /// Returns the nearest [`f64`] to the number.
+    pub fn to_f64(self) -> f64 {
+        self.significand as f64 * (self.base.exp_lhs() as f64).powi(self.exponent)
+    }
+}
+
 impl Display for Num {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{} * p{}({})",
@@ -0,0 +1,281 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Command-line options.
+
+// ----------------------------------------------------------------------------
+
+/// Command-line options.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Options {
+    /// Command to perform.
+    pub command: Command,
+
+    /// Paths of input files.  The path `-` denotes standard input.
+    pub inputs: Vec<String>,
+
+    /// Path of the output file to write, if any.
+    pub output: Option<String>,
+
+    /// Name of the target architecture, if any.
+    pub target: Option<String>,
+
+    /// Path of the symbol map file to write, if any.
+    pub map: Option<String>,
+
+    /// Address at which the linker places the first section without an
+    /// explicit address.
+    pub base: u64,
+
+    /// Explicit addresses of sections, by section name.
+    pub section_starts: Vec<(String, u64)>,
+
+    /// Path of the layout file to read, if any.
+    pub layout: Option<String>,
+
+    /// Whether to write a Makefile-compatible dependency file.
+    pub depfile: bool,
+
+    /// Path of the dependency file to write.  If `None`, the path is that of
+    /// the output file with the extension replaced by `.d`.
+    pub depfile_path: Option<String>,
+
+    /// Whether to print the tokens of each input.
+    pub tokens: bool,
+
+    /// Whether to print the abstract syntax tree of each input.
+    pub ast: bool,
+}
+
+/// Commands.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Command {
+    /// Assemble and link the inputs.
+    #[default]
+    Assemble,
+
+    /// Disassemble the input.
+    Disasm,
+
+    /// Assemble and link the inputs, then run their `.test` blocks.
+    Test,
+}
+
+impl Options {
+    /// Parses options from the given command-line arguments, excluding the
+    /// program name.
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>
+    {
+        let mut opts = Self::default();
+        let mut args = args.into_iter().peekable();
+
+        if let Some(command) = args.next_if(|a| a == "disasm" || a == "test") {
+            opts.command = match command.as_str() {
+                "disasm" => Command::Disasm,
+                _        => Command::Test,
+            };
+        }
+
+        while let Some(arg) = args.next() {
+            // Split --name=value
+            let (name, value) = match arg.split_once('=') {
+                Some((n, v)) if n.starts_with("--") => (n, Some(v.to_string())),
+                _                                   => (arg.as_str(), None),
+            };
+
+            match name {
+                "-o"       => opts.output = Some(value_of(name, value, &mut args)?),
+                "--map"    => opts.map    = Some(value_of(name, value, &mut args)?),
+                "--target" => opts.target = Some(value_of(name, value, &mut args)?),
+                "--base"   => opts.base   = parse_addr(&value_of(name, value, &mut args)?)?,
+                "--layout" => opts.layout = Some(value_of(name, value, &mut args)?),
+                "--section-start" => {
+                    let value = value_of(name, value, &mut args)?;
+                    let (section, addr) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("option '{}' requires NAME=ADDRESS", name))?;
+                    opts.section_starts.push((section.into(), parse_addr(addr)?));
+                },
+                "-MD"      => opts.depfile = true,
+                "-MF"      => {
+                    opts.depfile      = true;
+                    opts.depfile_path = Some(value_of(name, value, &mut args)?);
+                },
+                "--tokens" => opts.tokens = true,
+                "--ast"    => opts.ast    = true,
+                "--" => {
+                    opts.inputs.extend(args.by_ref());
+                },
+                "-" => {
+                    opts.inputs.push(arg);
+                },
+                _ if name.starts_with('-') => {
+                    return Err(format!("unrecognized option '{}'", name));
+                },
+                _ => {
+                    opts.inputs.push(arg);
+                },
+            }
+        }
+
+        if opts.depfile && opts.output.is_none() {
+            return Err("option '-MD' or '-MF' requires '-o'".into());
+        }
+
+        if opts.command == Command::Disasm {
+            if opts.target.is_none() {
+                return Err("command 'disasm' requires '--target'".into());
+            }
+            if opts.inputs.len() != 1 {
+                return Err("command 'disasm' requires exactly one input".into());
+            }
+        }
+
+        if opts.command == Command::Test && opts.target.is_none() {
+            return Err("command 'test' requires '--target'".into());
+        }
+
+        Ok(opts)
+    }
+
+    /// Returns the path of the dependency file to write, if any.
+    pub fn depfile_path(&self) -> Option<String> {
+        if !self.depfile {
+            return None;
+        }
+
+        self.depfile_path.clone().or_else(|| {
+            let output = std::path::Path::new(self.output.as_ref()?);
+            Some(output.with_extension("d").to_string_lossy().into_owned())
+        })
+    }
+}
+
+/// Returns the value of the option `name`, either the given inline `value` or
+/// the next argument.
+fn value_of<I>(name: &str, value: Option<String>, args: &mut I) -> Result<String, String>
+where
+    I: Iterator<Item = String>
+{
+    value
+        .or_else(|| args.next())
+        .ok_or_else(|| format!("option '{}' requires a value", name))
+}
+
+/// Parses an address given in decimal, in hexadecimal with a `0x` prefix, or
+/// with a ras base prefix such as `x'`.
+fn parse_addr(s: &str) -> Result<u64, String> {
+    let digits = s.replace('_', "");
+
+    let (radix, digits) = match digits.as_bytes() {
+        [b'0', b'x' | b'X', ..] => (16, &digits[2..]),
+        [b'x' | b'X', b'\'', ..] => (16, &digits[2..]),
+        [b'o' | b'O', b'\'', ..] => ( 8, &digits[2..]),
+        [b'b' | b'B', b'\'', ..] => ( 2, &digits[2..]),
+        [b'd' | b'D', b'\'', ..] => (10, &digits[2..]),
+        _                        => (10, &digits[..]),
+    };
+
+    u64::from_str_radix(digits, radix).map_err(|_| format!("invalid address '{}'", s))
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Options};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn inputs() {
+        let opts = parse(&["a.s", "-", "--", "--b.s"]).unwrap();
+
+        assert_eq!(opts.inputs, ["a.s", "-", "--b.s"]);
+        assert_eq!(opts.map,    None);
+    }
+
+    #[test]
+    fn map() {
+        assert_eq!(parse(&["--map", "a.map"]).unwrap().map.as_deref(), Some("a.map"));
+        assert_eq!(parse(&["--map=a.map"   ]).unwrap().map.as_deref(), Some("a.map"));
+        assert!   (parse(&["--map"         ]).is_err());
+    }
+
+    #[test]
+    fn depfile() {
+        let opts = parse(&["-o", "out/a.bin", "-MD"]).unwrap();
+        assert_eq!(opts.depfile_path().as_deref(), Some("out/a.d"));
+
+        let opts = parse(&["-o", "out/a.bin", "-MF", "deps/a.dep"]).unwrap();
+        assert_eq!(opts.depfile_path().as_deref(), Some("deps/a.dep"));
+
+        let opts = parse(&["-o", "out/a.bin"]).unwrap();
+        assert_eq!(opts.depfile_path(), None);
+
+        assert!(parse(&["-MD"]).is_err());
+    }
+
+    #[test]
+    fn layout() {
+        let opts = parse(&[
+            "--base", "0x1000", "--section-start", ".data=x'2000", "--section-start=.bss=64"
+        ]).unwrap();
+
+        assert_eq!(opts.base, 0x1000);
+        assert_eq!(opts.section_starts, [(".data".into(), 0x2000), (".bss".into(), 64)]);
+
+        assert!(parse(&["--base", "zz"]).is_err());
+        assert!(parse(&["--section-start", ".data"]).is_err());
+    }
+
+    #[test]
+    fn disasm() {
+        let opts = parse(&["disasm", "--target", "avr5", "--base=x'100", "a.bin"]).unwrap();
+
+        assert_eq!(opts.command, Command::Disasm);
+        assert_eq!(opts.target.as_deref(), Some("avr5"));
+        assert_eq!(opts.base, 0x100);
+        assert_eq!(opts.inputs, ["a.bin"]);
+
+        assert_eq!(parse(&["a.s", "disasm"]).unwrap().command, Command::Assemble);
+
+        assert!(parse(&["disasm", "a.bin"]).is_err());
+        assert!(parse(&["disasm", "--target", "avr5"]).is_err());
+    }
+
+    #[test]
+    fn test() {
+        let opts = parse(&["test", "--target", "rv32i", "a.s", "b.s"]).unwrap();
+
+        assert_eq!(opts.command, Command::Test);
+        assert_eq!(opts.target.as_deref(), Some("rv32i"));
+        assert_eq!(opts.inputs, ["a.s", "b.s"]);
+
+        assert!(parse(&["test", "a.s"]).is_err());
+    }
+
+    #[test]
+    fn unrecognized() {
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Output file formats.
+
+use crate::asm::Section;
+
+/// Builds a flat binary image of the given sections, which must not overlap.
+///
+/// Each section appears at its load address.  The image begins at the lowest
+/// load address of any section with content and ends at the highest end load
+/// address of any such section.  Gaps between
+/// sections are filled with zero bytes.  Sections without content, such as
+/// `.bss` sections, do not contribute to the image.
+pub fn flat_binary<'a, I>(sections: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a Section>
+{
+    let mut sections = sections
+        .into_iter()
+        .filter(|s| s.has_data() && s.size != 0)
+        .collect::<Vec<_>>();
+
+    sections.sort_by_key(|s| s.load_base());
+
+    let start = match sections.first() {
+        Some(s) => s.load_base(),
+        None    => return vec![],
+    };
+
+    let mut image = Vec::new();
+
+    for section in sections {
+        image.resize((section.load_base() - start) as usize, 0);
+        image.extend_from_slice(&section.data);
+    }
+
+    image
+}
@@ -18,23 +18,38 @@
 
 //! Assembly session.
 
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use colored::*;
+
+use crate::asm::{Assembler, Unit};
+use crate::lang::ast::Span;
 use crate::lang::lexer::{Lex, Lexer, Token};
 use crate::lang::parser::Parser;
-use crate::name::NameTable;
+use crate::name::{Name, NameTable};
+use crate::target::{Target, TargetInfo};
 
 // ----------------------------------------------------------------------------
 
 /// Assembler session.
 #[derive(Debug)]
 pub struct Session {
-    names: NameTable,
+    names:  NameTable,
+    diags:  Vec<Diagnostic>,
+    quiet:  bool,
+    tests:  bool,
+    target: Option<Rc<dyn Target>>,
 }
 
 impl Session {
     /// Creates a new [`Session`].
     pub fn new() -> Self {
         Self {
-            names: NameTable::new(),
+            names:  NameTable::new(),
+            diags:  Vec::new(),
+            quiet:  false,
+            tests:  false,
+            target: None,
         }
     }
 
@@ -46,6 +61,68 @@ impl Session {
         &mut self.names
     }
 
+    /// Returns the target architecture, if any.
+    pub fn target(&self) -> Option<&Rc<dyn Target>> {
+        self.target.as_ref()
+    }
+
+    /// Sets the target architecture.
+    pub fn set_target(&mut self, info: &TargetInfo) {
+        self.target = Some((info.new)(&mut self.names));
+    }
+
+    /// Sets whether diagnostics are recorded without being printed.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Returns whether the assembler includes `.test` blocks.
+    pub fn tests(&self) -> bool {
+        self.tests
+    }
+
+    /// Sets whether the assembler includes `.test` blocks.
+    pub fn set_tests(&mut self, tests: bool) {
+        self.tests = tests;
+    }
+
+    /// Returns the diagnostics reported so far.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diags
+    }
+
+    /// Returns the count of errors reported so far.
+    pub fn error_count(&self) -> usize {
+        self.diags.iter().filter(|d| d.level == Level::Error).count()
+    }
+
+    /// Reports a diagnostic message of the given `level` at the given `loc`.
+    pub fn report(&mut self, level: Level, loc: Option<Loc>, msg: impl Display) {
+        let diag = Diagnostic { level, loc, msg: msg.to_string() };
+        if !self.quiet {
+            eprintln!("{}", diag.for_display(&self.names));
+        }
+        self.diags.push(diag);
+    }
+
+    /// Reports an error at the given `loc`.
+    pub fn error(&mut self, loc: Loc, msg: impl Display) {
+        self.report(Level::Error, Some(loc), msg)
+    }
+
+    /// Reports a warning at the given `loc`.
+    pub fn warning(&mut self, loc: Loc, msg: impl Display) {
+        self.report(Level::Warning, Some(loc), msg)
+    }
+
+    /// Parses and assembles the given source `content`, using `path` as the
+    /// name of the source file.
+    pub fn assemble(&mut self, path: &str, content: &str) -> Unit {
+        let file = self.names.add(path);
+        let ast  = Parser::new(Lexer::new(content.bytes()), file, self).parse();
+        Assembler::new(self, file).assemble(&ast)
+    }
+
     pub fn print_tokens(&mut self, path: &str, content: &str) {
         println!("[{}:tokens]", path);
 
@@ -75,11 +152,106 @@ impl Session {
     pub fn print_ast(&mut self, path: &str, content: &str) {
         println!("[{}:ast]", path);
 
-        let     lexer = Lexer::new(content.bytes());
-        let mut parser = Parser::new(lexer, self);
+        let     file   = self.names.add(path);
+        let     lexer  = Lexer::new(content.bytes());
+        let mut parser = Parser::new(lexer, file, self);
 
         let ast = parser.parse();
 
         println!("{}", ast.for_display(self.names()))
     }
 }
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Source location.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Loc {
+    /// Name of the source file.
+    pub file: Name,
+
+    /// Span within the source file.
+    pub span: Span,
+}
+
+impl Loc {
+    /// Returns a wrapper over the location that implements [`Display`].
+    pub fn for_display<'a>(&'a self, names: &'a NameTable) -> impl Display + 'a {
+        LocDisplay { loc: self, names }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LocDisplay<'a> {
+    loc:   &'a Loc,
+    names: &'a NameTable,
+}
+
+impl Display for LocDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", &self.names[self.loc.file], self.loc.span.line)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Diagnostic severity levels.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    /// Supplementary information.
+    Note,
+
+    /// Suspicious condition that does not prevent assembly.
+    Warning,
+
+    /// Condition that prevents assembly.
+    Error,
+}
+
+/// Diagnostic message.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    /// Severity.
+    pub level: Level,
+
+    /// Source location, if any.
+    pub loc: Option<Loc>,
+
+    /// Message text.
+    pub msg: String,
+}
+
+impl Diagnostic {
+    /// Returns a wrapper over the diagnostic that implements [`Display`].
+    pub fn for_display<'a>(&'a self, names: &'a NameTable) -> impl Display + 'a {
+        DiagnosticDisplay { diag: self, names }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DiagnosticDisplay<'a> {
+    diag:  &'a Diagnostic,
+    names: &'a NameTable,
+}
+
+impl Display for DiagnosticDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(ref loc) = self.diag.loc {
+            write!(f, "{}: ", loc.for_display(self.names))?;
+        }
+
+        let level = match self.diag.level {
+            Level::Note    => "note"   .cyan()  .bold(),
+            Level::Warning => "warning".yellow().bold(),
+            Level::Error   => "error"  .red()   .bold(),
+        };
+
+        write!(f, "{}: {}", level, self.diag.msg)
+    }
+}
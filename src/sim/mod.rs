@@ -0,0 +1,280 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction-level simulation.
+//!
+//! A target that supports simulation provides a [`Cpu`] through
+//! [`Target::simulator`](crate::target::Target::simulator).  The CPU
+//! executes instructions from a [`Memory`], which holds RAM loaded from
+//! assembled sections and memory-mapped [`Device`] stubs.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+use crate::asm::{Endian, Section};
+
+#[cfg(test)]
+mod tests;
+
+// ----------------------------------------------------------------------------
+
+/// Simulated processor.
+pub trait Cpu {
+    /// Returns the size in bytes of a general-purpose register.
+    fn width(&self) -> usize;
+
+    /// Returns the address of the next instruction.
+    fn pc(&self) -> u64;
+
+    /// Sets the address of the next instruction.
+    fn set_pc(&mut self, pc: u64);
+
+    /// Returns the value of the register with the given name, if the CPU
+    /// has such a register.
+    fn register(&self, name: &str) -> Option<u64>;
+
+    /// Sets the value of the register with the given name.  Returns `false`
+    /// if the CPU has no such register.
+    fn set_register(&mut self, name: &str, value: u64) -> bool;
+
+    /// Executes one instruction.  Returns `Err` if execution stops.
+    fn step(&mut self, mem: &mut Memory) -> Result<(), Stop>;
+}
+
+/// Reasons that execution stops.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Stop {
+    /// The program executed a breakpoint instruction.  The CPU is at the
+    /// following instruction.
+    Break,
+
+    /// The program executed an environment or system call instruction.  The
+    /// CPU is at the following instruction.
+    Call,
+
+    /// The program executed the given number of instructions without
+    /// stopping.
+    Limit(u64),
+
+    /// The program faulted.  The CPU is at the faulting instruction.
+    Fault(Fault),
+}
+
+/// Faults.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Fault {
+    /// Access to an address with no memory or device.
+    Unmapped(u64),
+
+    /// Instruction fetch from an address that is not suitably aligned.
+    Misaligned(u64),
+
+    /// Instruction that the CPU does not implement, with its bits.
+    Illegal(u64),
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Stop::Break    => f.write_str("breakpoint"),
+            Stop::Call     => f.write_str("system call"),
+            Stop::Limit(n) => write!(f, "no stop after {} instructions", n),
+            Stop::Fault(ref fault) => fault.fmt(f),
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Fault::Unmapped(a)   => write!(f, "access to unmapped address x'{:X}", a),
+            Fault::Misaligned(a) => write!(f, "misaligned instruction address x'{:X}", a),
+            Fault::Illegal(i)    => write!(f, "illegal instruction x'{:X}", i),
+        }
+    }
+}
+
+/// Executes instructions until execution stops or `limit` instructions have
+/// executed.
+pub fn run(cpu: &mut dyn Cpu, mem: &mut Memory, limit: u64) -> Stop {
+    for _ in 0..limit {
+        if let Err(stop) = cpu.step(mem) {
+            return stop;
+        }
+    }
+    Stop::Limit(limit)
+}
+
+// ----------------------------------------------------------------------------
+
+/// Memory-mapped device.
+pub trait Device {
+    /// Reads `size` bytes at `offset` bytes from the start of the device.
+    fn read(&mut self, offset: u64, size: usize) -> u64;
+
+    /// Writes the low `size` bytes of `value` at `offset` bytes from the
+    /// start of the device.
+    fn write(&mut self, offset: u64, size: usize, value: u64);
+}
+
+/// Device stub that records writes and replays queued values for reads.
+#[derive(Clone, Default, Debug)]
+pub struct Stub {
+    /// Values for subsequent reads, in order.  A read with no queued value
+    /// yields zero.
+    pub input: VecDeque<u64>,
+
+    /// Writes so far, as offset and value.
+    pub output: Vec<(u64, u64)>,
+}
+
+impl Stub {
+    /// Creates a new [`Stub`] that yields the given values for reads.
+    pub fn new(input: impl IntoIterator<Item = u64>) -> Self {
+        Self { input: input.into_iter().collect(), output: vec![] }
+    }
+
+    /// Returns the low bytes of the values written so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.output.iter().map(|&(_, v)| v as u8).collect()
+    }
+}
+
+impl Device for Stub {
+    fn read(&mut self, _offset: u64, _size: usize) -> u64 {
+        self.input.pop_front().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: u64, _size: usize, value: u64) {
+        self.output.push((offset, value));
+    }
+}
+
+/// Loopback device: each read yields the oldest value written and not yet
+/// read, or zero if none remains.
+#[derive(Clone, Default, Debug)]
+pub struct Echo {
+    queue: VecDeque<u64>,
+}
+
+impl Device for Echo {
+    fn read(&mut self, _offset: u64, _size: usize) -> u64 {
+        self.queue.pop_front().unwrap_or(0)
+    }
+
+    fn write(&mut self, _offset: u64, size: usize, value: u64) {
+        self.queue.push_back(value & u64::MAX >> (64 - size * 8));
+    }
+}
+
+/// Shared reference to a device.
+pub type DeviceRef = Rc<RefCell<dyn Device>>;
+
+/// Simulated memory: RAM and memory-mapped devices.
+pub struct Memory {
+    endian:  Endian,
+    ram:     Vec<(u64, Vec<u8>)>,
+    devices: Vec<(u64, u64, DeviceRef)>,
+}
+
+impl Memory {
+    /// Creates a new, empty [`Memory`] with the given byte order.
+    pub fn new(endian: Endian) -> Self {
+        Self { endian, ram: vec![], devices: vec![] }
+    }
+
+    /// Adds `size` bytes of zeroed RAM at address `base`.
+    pub fn add_ram(&mut self, base: u64, size: u64) {
+        self.ram.push((base, vec![0; size as usize]));
+    }
+
+    /// Adds RAM holding each of the given sections at its load address.  A
+    /// section that runs at another address also receives zeroed RAM at
+    /// that address.
+    pub fn load(&mut self, sections: &[Section]) {
+        for section in sections.iter().filter(|s| s.size != 0) {
+            let mut data = section.data.clone();
+            data.resize(section.size as usize, 0);
+
+            if section.load_base() != section.base {
+                self.add_ram(section.base, section.size);
+            }
+            self.ram.push((section.load_base(), data));
+        }
+    }
+
+    /// Maps the given device at `size` bytes from address `base`.  The
+    /// device takes precedence over any RAM there.
+    pub fn map(&mut self, base: u64, size: u64, device: DeviceRef) {
+        self.devices.push((base, size, device));
+    }
+
+    /// Reads `size` bytes at address `addr`.
+    pub fn read(&mut self, addr: u64, size: usize) -> Result<u64, Fault> {
+        if let Some((base, device)) = self.device(addr) {
+            return Ok(device.borrow_mut().read(addr - base, size));
+        }
+
+        let endian = self.endian;
+        let bytes  = self.ram(addr, size)?;
+        Ok(match endian {
+            Endian::Little => bytes.iter().rev().fold(0, |v, &b| v << 8 | b as u64),
+            Endian::Big    => bytes.iter()      .fold(0, |v, &b| v << 8 | b as u64),
+        })
+    }
+
+    /// Writes the low `size` bytes of `value` at address `addr`.
+    pub fn write(&mut self, addr: u64, size: usize, value: u64) -> Result<(), Fault> {
+        if let Some((base, device)) = self.device(addr) {
+            device.borrow_mut().write(addr - base, size, value);
+            return Ok(());
+        }
+
+        let endian = self.endian;
+        let bytes  = self.ram(addr, size)?;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let shift = match endian {
+                Endian::Little => i,
+                Endian::Big    => size - 1 - i,
+            };
+            *byte = (value >> (shift * 8)) as u8;
+        }
+        Ok(())
+    }
+
+    fn device(&self, addr: u64) -> Option<(u64, DeviceRef)> {
+        self.devices
+            .iter()
+            .rev()
+            .find(|&&(base, size, _)| addr.wrapping_sub(base) < size)
+            .map(|(base, _, device)| (*base, device.clone()))
+    }
+
+    fn ram(&mut self, addr: u64, size: usize) -> Result<&mut [u8], Fault> {
+        self.ram
+            .iter_mut()
+            .rev()
+            .find_map(|(base, data)| {
+                let at = addr.wrapping_sub(*base) as usize;
+                data.get_mut(at..at.checked_add(size)?)
+            })
+            .ok_or(Fault::Unmapped(addr))
+    }
+}
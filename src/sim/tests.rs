@@ -0,0 +1,292 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Simulation tests.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::link::{link, Layout};
+use crate::session::Session;
+use crate::target::find;
+
+use super::*;
+
+/// Assembles and links the given source for the given target, loads it with
+/// 4 KiB of RAM at `x'10000` and a device stub at `x'20000`, and runs it from
+/// its first address until it stops.
+fn run_source(target: &str, source: &str) -> (Box<dyn Cpu>, Memory, Rc<RefCell<Stub>>, Stop) {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find(target).unwrap());
+
+    let mut unit = session.assemble("test.s", source);
+    let program  = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+
+    let target = session.target().unwrap().clone();
+    let stub   = Rc::new(RefCell::new(Stub::new([b'o' as u64, b'k' as u64])));
+
+    let mut mem = Memory::new(target.endian());
+    mem.add_ram(0x10000, 0x1000);
+    mem.load(&program.sections);
+    mem.map(0x20000, 0x10, stub.clone());
+
+    let mut cpu = target.simulator().unwrap();
+    cpu.set_pc(program.sections[0].base);
+    cpu.set_register("sp", 0x11000);
+
+    let stop = run(&mut *cpu, &mut mem, 10_000);
+    (cpu, mem, stub, stop)
+}
+
+fn reg(cpu: &dyn Cpu, name: &str) -> u64 {
+    cpu.register(name).unwrap()
+}
+
+/// Program that exercises the base integer instructions.
+const BASE: &str = "
+    .org x'1000
+    start:  li      a0, 10
+            li      a1, 0
+    loop:   add     a1, a1, a0          # sum 10..1
+            addi    a0, a0, -1
+            bnez    a0, loop
+            li      t0, -7
+            srai    t1, t0, 1
+            srli    t2, t0, 28
+            slt     t3, t0, zero
+            sltu    t4, t0, zero
+            lui     t5, x'12345
+            addi    sp, sp, -16
+            sw      t0, [sp + 4]
+            lbu     s0, [sp + 4]
+            lh      s1, [sp + 4]
+            call    double
+            ebreak
+    double: add     a1, a1, a1
+            ret
+";
+
+fn check_base(cpu: &dyn Cpu, mask: u64) {
+    assert_eq!(reg(cpu, "a0"), 0);
+    assert_eq!(reg(cpu, "a1"), 110);
+    assert_eq!(reg(cpu, "t1"), (-4i64) as u64 & mask);
+    assert_eq!(reg(cpu, "t2"), (-7i64 as u64 & mask) >> 28);
+    assert_eq!(reg(cpu, "t3"), 1);
+    assert_eq!(reg(cpu, "t4"), 0);
+    assert_eq!(reg(cpu, "t5"), 0x1234_5000);
+    assert_eq!(reg(cpu, "s0"), 0xF9);
+    assert_eq!(reg(cpu, "s1"), (-7i64) as u64 & mask);
+    assert_eq!(reg(cpu, "sp"), 0x10FF0);
+}
+
+#[test]
+fn base() {
+    for target in ["rv32i", "rv32im"] {
+        let (cpu, _, _, stop) = run_source(target, BASE);
+        assert_eq!(stop, Stop::Break, "{}", target);
+        check_base(&*cpu, 0xFFFF_FFFF);
+        assert_eq!(reg(&*cpu, "pc"), 0x1048);
+    }
+}
+
+/// The compressed encodings behave as their full-size equivalents.
+#[test]
+fn compressed() {
+    let (full, _, _, _) = run_source("rv32im", BASE);
+    let (comp, _, _, stop) = run_source("rv32imac", BASE);
+
+    assert_eq!(stop, Stop::Break);
+    check_base(&*comp, 0xFFFF_FFFF);
+    assert!(reg(&*comp, "pc") < reg(&*full, "pc"));
+
+    let (cpu, _, _, stop) = run_source("rv64imac", BASE);
+    assert_eq!(stop, Stop::Break);
+    check_base(&*cpu, 0xFFFF_FFFF_FFFF_FFFF);
+}
+
+#[test]
+fn multiply() {
+    let (cpu, _, _, stop) = run_source("rv32im", "
+        .org x'1000
+        li      a0, -6
+        li      a1, 4
+        mul     t0, a0, a1
+        mulh    t1, a0, a1
+        mulhu   t2, a0, a1
+        div     t3, a0, a1
+        rem     t4, a0, a1
+        divu    t5, a1, zero
+        li      a2, x'80000000
+        li      a3, -1
+        div     t6, a2, a3
+        ebreak
+    ");
+
+    assert_eq!(stop, Stop::Break);
+    assert_eq!(reg(&*cpu, "t0"), 0xFFFF_FFE8);
+    assert_eq!(reg(&*cpu, "t1"), 0xFFFF_FFFF);
+    assert_eq!(reg(&*cpu, "t2"), 3);
+    assert_eq!(reg(&*cpu, "t3"), 0xFFFF_FFFF);
+    assert_eq!(reg(&*cpu, "t4"), 0xFFFF_FFFE);
+    assert_eq!(reg(&*cpu, "t5"), 0xFFFF_FFFF);
+    assert_eq!(reg(&*cpu, "t6"), 0x8000_0000);
+}
+
+#[test]
+fn rv64() {
+    let (cpu, mem, _, stop) = run_source("rv64im", "
+        .org x'1000
+        li      a0, x'123456789
+        addiw   a1, a0, 0
+        slli    a2, a0, 32
+        li      t0, x'10000
+        sd      a0, [t0]
+        lwu     a3, [t0 + 4]
+        mulhu   a4, a2, a2
+        subw    a5, zero, a1
+        ecall
+    ");
+
+    assert_eq!(stop, Stop::Call);
+    assert_eq!(reg(&*cpu, "a0"), 0x1_2345_6789);
+    assert_eq!(reg(&*cpu, "a1"), 0x2345_6789);
+    assert_eq!(reg(&*cpu, "a2"), 0x2345_6789_0000_0000);
+    assert_eq!(reg(&*cpu, "a3"), 1);
+    assert_eq!(reg(&*cpu, "a4"), 0x04DC_0D20_6FB9_8751);
+    assert_eq!(reg(&*cpu, "a5"), 0xFFFF_FFFF_DCBA_9877);
+
+    let mut mem = mem;
+    assert_eq!(mem.read(0x10000, 8), Ok(0x1_2345_6789));
+}
+
+#[test]
+fn atomic() {
+    let (cpu, mut mem, _, stop) = run_source("rv32imac", "
+        .org x'1000
+        li      a0, x'10000
+        li      a1, 5
+        sw      a1, [a0]
+        amoadd.w t0, a1, [a0]
+        lr.w    t1, [a0]
+        sc.w    t2, a1, [a0]
+        sc.w    t3, a1, [a0]
+        li      a2, -1
+        amomaxu.w t4, a2, [a0]
+        ebreak
+    ");
+
+    assert_eq!(stop, Stop::Break);
+    assert_eq!(reg(&*cpu, "t0"), 5);
+    assert_eq!(reg(&*cpu, "t1"), 10);
+    assert_eq!(reg(&*cpu, "t2"), 0);
+    assert_eq!(reg(&*cpu, "t3"), 1);
+    assert_eq!(reg(&*cpu, "t4"), 5);
+    assert_eq!(mem.read(0x10000, 4), Ok(0xFFFF_FFFF));
+}
+
+#[test]
+fn devices() {
+    let (cpu, _, stub, stop) = run_source("rv32i", "
+        .org x'1000
+        li      t0, x'20000
+        la      t1, msg
+    next:   lbu     t2, [t1]
+            beqz    t2, done
+            sb      t2, [t0 + 4]
+            addi    t1, t1, 1
+            j       next
+    done:   lbu     a0, [t0]
+            lbu     a1, [t0]
+            lbu     a2, [t0]
+            ebreak
+    msg:    .ascii  \"hello\"
+            .int8   0
+    ");
+
+    assert_eq!(stop, Stop::Break);
+    assert_eq!(stub.borrow().bytes(), b"hello");
+    assert_eq!(stub.borrow().output[0], (4, b'h' as u64));
+    assert_eq!(reg(&*cpu, "a0"), b'o' as u64);
+    assert_eq!(reg(&*cpu, "a1"), b'k' as u64);
+    assert_eq!(reg(&*cpu, "a2"), 0);
+}
+
+#[test]
+fn stops() {
+    // Instruction from an extension that the target lacks
+    let (cpu, _, _, stop) = run_source("rv32i", ".org x'1000\nnop\n.int32 x'02B50533");
+    assert_eq!(stop, Stop::Fault(Fault::Illegal(0x02B5_0533)));
+    assert_eq!(reg(&*cpu, "pc"), 0x1004);
+
+    // Reserved compressed encodings: c.addi16sp with zero, c.lwsp to zero
+    for (word, insn) in [("x'6101", 0x6101), ("x'4002", 0x4002)] {
+        let (_, _, _, stop) = run_source("rv32imac", &format!(".org x'1000\n.int16 {}", word));
+        assert_eq!(stop, Stop::Fault(Fault::Illegal(insn)));
+    }
+
+    let (_, _, _, stop) = run_source("rv32i", ".org x'1000\nli a0, x'30000\nlw a1, [a0]");
+    assert_eq!(stop, Stop::Fault(Fault::Unmapped(0x30000)));
+    assert_eq!(stop.to_string(), "access to unmapped address x'30000");
+
+    let (_, _, _, stop) = run_source("rv32i", ".org x'1000\nli a0, x'1002\njr a0");
+    assert_eq!(stop, Stop::Fault(Fault::Misaligned(0x1002)));
+
+    let (_, _, _, stop) = run_source("rv32i", ".org x'1000\nloop: j loop");
+    assert_eq!(stop, Stop::Limit(10_000));
+
+    // Running off the end of the program
+    let (_, _, _, stop) = run_source("rv32i", ".org x'1000\nnop");
+    assert_eq!(stop, Stop::Fault(Fault::Unmapped(0x1004)));
+}
+
+#[test]
+fn memory() {
+    let mut mem = Memory::new(Endian::Big);
+    mem.add_ram(0x100, 4);
+
+    assert_eq!(mem.write(0x100, 4, 0x1234_5678), Ok(()));
+    assert_eq!(mem.read(0x100, 2), Ok(0x1234));
+    assert_eq!(mem.read(0x103, 1), Ok(0x78));
+    assert_eq!(mem.read(0x103, 2), Err(Fault::Unmapped(0x103)));
+    assert_eq!(mem.read(0xFF, 1),  Err(Fault::Unmapped(0xFF)));
+
+    // Devices take precedence over RAM
+    let stub = Rc::new(RefCell::new(Stub::new([7])));
+    mem.map(0x102, 1, stub.clone());
+
+    assert_eq!(mem.read(0x102, 1), Ok(7));
+    assert_eq!(mem.write(0x102, 1, 9), Ok(()));
+    assert_eq!(mem.read(0x101, 1), Ok(0x34));
+    assert_eq!(stub.borrow().output, [(0, 9)]);
+
+    // A loopback device yields what was written, oldest first
+    mem.map(0x200, 4, Rc::new(RefCell::new(Echo::default())));
+
+    assert_eq!(mem.write(0x200, 1, 0x1FF), Ok(()));
+    assert_eq!(mem.write(0x202, 2, 0x1234), Ok(()));
+    assert_eq!(mem.read(0x201, 4), Ok(0xFF));
+    assert_eq!(mem.read(0x200, 1), Ok(0x1234));
+    assert_eq!(mem.read(0x200, 1), Ok(0));
+
+    // Unsupported targets have no simulator
+    let mut session = Session::new();
+    session.set_target(find("avr5").unwrap());
+    assert!(session.target().unwrap().simulator().is_none());
+}
@@ -0,0 +1,296 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Atmel AVR.
+//!
+//! Instructions use Atmel mnemonics.  Operands use ras syntax:
+//!
+//! | Operand                       | Atmel               | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | immediate                     | `ldi r16, 42`       | `ldi r16, 42`
+//! | indirect                      | `ld r0, X`          | `ld r0, [x]`
+//! | indirect, post-increment      | `ld r0, X+`         | `ld r0, [x]!`
+//! | indirect, pre-decrement       | `ld r0, -X`         | `ld r0, [--x]`
+//! | indirect with displacement    | `ldd r0, Y+5`       | `ldd r0, [y + 5]`
+//! | data address                  | `lds r0, 0x100`     | `lds r0, [x'100]`
+//! | I/O address                   | `in r0, 0x3F`       | `in r0, [x'3f]`
+//!
+//! Program memory is word-addressed, but ras addresses every section by
+//! byte, so that labels in code and data mean the same thing.  A jump, call,
+//! or branch takes the byte address of its target and encodes the word
+//! address or offset.  The builtin function `pm` converts a byte address in
+//! program memory to a word address, as for `ijmp` and `icall`:
+//! `ldi r30, lo8(pm(func))`.  `lpm` takes byte addresses, so its operand
+//! needs no conversion.
+//!
+//! Each target selects a device family or a device, and rejects the
+//! instructions and instruction forms that the family lacks.
+//!
+//! The instruction set is a [`Spec`](super::spec::Spec), from which the
+//! encoder and decoder derive.
+
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::spec::{self, Table};
+use super::{Decoded, Emitter, Target};
+
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: instructions common to all supported families.
+pub const CORE: u32 = 1 << 0;
+
+/// Instruction set feature: `movw`.
+pub const MOVW: u32 = 1 << 1;
+
+/// Instruction set feature: `lpm` with a destination register.
+pub const LPMX: u32 = 1 << 2;
+
+/// Instruction set feature: `spm`.
+pub const SPM: u32 = 1 << 3;
+
+/// Instruction set feature: `jmp` and `call`.
+pub const JMP: u32 = 1 << 4;
+
+/// Instruction set feature: hardware multiplier.
+pub const MUL: u32 = 1 << 5;
+
+/// Instruction set feature: `elpm`.
+pub const ELPM: u32 = 1 << 6;
+
+/// Instruction set feature: `eijmp` and `eicall`.
+pub const EIJMP: u32 = 1 << 7;
+
+/// Instruction set feature: XMEGA additions.
+pub const XMEGA: u32 = 1 << 8;
+
+/// Instruction set of the `avr2` family: classic cores with up to 8 KiB of
+/// program memory.
+pub const AVR2: u32 = CORE;
+
+/// Instruction set of the `avr25` family: `avr2` with the enhanced core.
+pub const AVR25: u32 = AVR2 | MOVW | LPMX | SPM;
+
+/// Instruction set of the `avr35` family: `avr25` with `jmp` and `call`.
+pub const AVR35: u32 = AVR25 | JMP;
+
+/// Instruction set of the `avr4` family: `avr25` with the multiplier.
+pub const AVR4: u32 = AVR25 | MUL;
+
+/// Instruction set of the `avr5` family: `avr4` with `jmp` and `call`.
+pub const AVR5: u32 = AVR4 | JMP;
+
+/// Instruction set of the `avr51` family: `avr5` with 128 KiB of program
+/// memory.
+pub const AVR51: u32 = AVR5 | ELPM;
+
+/// Instruction set of the `avr6` family: `avr51` with more than 128 KiB of
+/// program memory.
+pub const AVR6: u32 = AVR51 | EIJMP;
+
+/// Instruction set of the XMEGA family.
+pub const AVRXMEGA: u32 = AVR6 | XMEGA;
+
+/// Member of the AVR family.
+#[derive(Debug)]
+pub struct Avr {
+    name:  &'static str,
+    isa:   u32,
+    table: Table,
+}
+
+impl Avr {
+    /// Creates a new [`Avr`] target with the given `name` and instruction set
+    /// features `isa`, interning its mnemonics in `names`.
+    ///
+    /// The target knows every mnemonic, so that it can reject one that the
+    /// device lacks with a better message than an unknown instruction.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        Self { name, isa, table: Table::new(&SPEC, names) }
+    }
+
+    /// Returns whether the target has all of the given features.
+    pub fn has(&self, isa: u32) -> bool {
+        self.isa & isa == isa
+    }
+}
+
+impl Target for Avr {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        2
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[IMM8, BRANCH, REL12, JUMP, RelocKind::INT8, RelocKind::INT16, RelocKind::INT32]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.table.instruction(name)
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        self.table.register(name).map(|n| n as u32)
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        match name {
+            Name::LO8 => Some(|v| v      & 0xFF),
+            Name::HI8 => Some(|v| v >> 8 & 0xFF),
+            Name::PM  => Some(|v| v >> 1),
+            _         => None,
+        }
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        spec::encode(&self.table, self.isa, self.name, insn, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        self.table.decode(self.isa, bytes, addr, label)
+    }
+}
+
+/// Creates a target for the `avr2` family.
+pub fn new_avr2(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr2", AVR2))
+}
+
+/// Creates a target for the `avr25` family.
+pub fn new_avr25(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr25", AVR25))
+}
+
+/// Creates a target for the `avr35` family.
+pub fn new_avr35(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr35", AVR35))
+}
+
+/// Creates a target for the `avr4` family.
+pub fn new_avr4(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr4", AVR4))
+}
+
+/// Creates a target for the `avr5` family.
+pub fn new_avr5(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr5", AVR5))
+}
+
+/// Creates a target for the `avr51` family.
+pub fn new_avr51(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr51", AVR51))
+}
+
+/// Creates a target for the `avr6` family.
+pub fn new_avr6(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avr6", AVR6))
+}
+
+/// Creates a target for the XMEGA family.
+pub fn new_avrxmega(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "avrxmega", AVRXMEGA))
+}
+
+/// Creates a target for the ATtiny85.
+pub fn new_attiny85(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "attiny85", AVR25))
+}
+
+/// Creates a target for the ATmega8.
+pub fn new_atmega8(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "atmega8", AVR4))
+}
+
+/// Creates a target for the ATmega328P.
+pub fn new_atmega328p(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "atmega328p", AVR5))
+}
+
+/// Creates a target for the ATmega2560.
+pub fn new_atmega2560(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Avr::new(names, "atmega2560", AVR6))
+}
+
+// ----------------------------------------------------------------------------
+
+/// 8-bit immediate value in a register-immediate instruction.
+pub const IMM8: RelocKind = RelocKind { name: "avr-imm8", size: 2, apply: apply_imm8 };
+
+/// 7-bit conditional branch offset.
+pub const BRANCH: RelocKind = RelocKind { name: "avr-branch", size: 2, apply: apply_branch };
+
+/// 12-bit relative jump or call offset.
+pub const REL12: RelocKind = RelocKind { name: "avr-rel12", size: 2, apply: apply_rel12 };
+
+/// 22-bit absolute jump or call address.
+pub const JUMP: RelocKind = RelocKind { name: "avr-jump", size: 4, apply: apply_jump };
+
+fn apply_imm8(value: i64, field: u64) -> Result<u64, String> {
+    match value {
+        -128..=255 => Ok(field & 0xF0F0 | (value as u64 & 0xF0) << 4 | value as u64 & 0xF),
+        _          => Err(format!("immediate value {} out of range", value)),
+    }
+}
+
+fn branch_range(value: i64, bits: u32) -> Result<u64, String> {
+    let min = -1i64 << bits;
+    match value & 1 == 0 && (min..=!min).contains(&value) {
+        true  => Ok((value >> 1) as u64 & !(u64::MAX << bits)),
+        false => Err(format!("branch offset {} out of range", value)),
+    }
+}
+
+fn apply_branch(value: i64, field: u64) -> Result<u64, String> {
+    Ok(field & 0xFC07 | branch_range(value, 7)? << 3)
+}
+
+fn apply_rel12(value: i64, field: u64) -> Result<u64, String> {
+    Ok(field & 0xF000 | branch_range(value, 12)?)
+}
+
+fn apply_jump(value: i64, field: u64) -> Result<u64, String> {
+    match value & 1 == 0 && (0..1 << 23).contains(&value) {
+        true => {
+            let k = value as u64 >> 1;
+            Ok(field & 0xFE0E | (k >> 17) << 4 | (k >> 16 & 1) | (k & 0xFFFF) << 16)
+        },
+        false => Err(format!("jump address {:#X} out of range", value)),
+    }
+}
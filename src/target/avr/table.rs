@@ -0,0 +1,273 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction set specification.
+
+use crate::asm::{Endian, RelocKind};
+use crate::target::spec::{alias, row, Field, Kind, Row, Spec};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set specification.
+pub static SPEC: Spec = Spec {
+    word:       2,
+    endian:     Endian::Little,
+    word_order: Endian::Big,
+    registers:  REGISTERS,
+    fields:     FIELDS,
+    rows:       ROWS,
+};
+
+static REGISTERS: &[&str] = &[
+    "r0",  "r1",  "r2",  "r3",  "r4",  "r5",  "r6",  "r7",
+    "r8",  "r9",  "r10", "r11", "r12", "r13", "r14", "r15",
+    "r16", "r17", "r18", "r19", "r20", "r21", "r22", "r23",
+    "r24", "r25", "r26", "r27", "r28", "r29", "r30", "r31",
+];
+
+/// Returns a register field.
+const fn reg(
+    name:    &'static str,
+    letters: &'static str,
+    min:     u16,
+    max:     u16,
+    step:    u16,
+    what:    &'static str,
+) -> Field {
+    Field { name, letters, kind: Kind::Reg { min, max, step }, order: "", what, reloc: None }
+}
+
+/// Returns an integer field.
+const fn int(
+    name:    &'static str,
+    letters: &'static str,
+    min:     i64,
+    max:     i64,
+    what:    &'static str,
+    reloc:   Option<RelocKind>,
+) -> Field {
+    Field { name, letters, kind: Kind::Int { min, max, invert: 0 }, order: "", what, reloc }
+}
+
+static FIELDS: &[Field] = &[
+    reg("Rd",  "d",  0,  31, 1, ""),
+    reg("Rr",  "r",  0,  31, 1, ""),
+    reg("Rdd", "dr", 0,  31, 1, ""),
+    reg("Rh",  "d",  16, 31, 1, "register must be r16-r31"),
+    reg("Rrh", "r",  16, 31, 1, "register must be r16-r31"),
+    reg("Rm",  "d",  16, 23, 1, "register must be r16-r23"),
+    reg("Rrm", "r",  16, 23, 1, "register must be r16-r23"),
+    reg("Rw",  "d",  24, 30, 2, "register must be r24, r26, r28, or r30"),
+    reg("Re",  "d",  0,  30, 2, "register must be even"),
+    reg("Rre", "r",  0,  30, 2, "register must be even"),
+    int("K",   "K", -128,   255,   "immediate value", Some(IMM8)),
+    Field {
+        name:    "Kc",
+        letters: "K",
+        kind:    Kind::Int { min: -128, max: 255, invert: 0xFF },
+        order:   "",
+        what:    "immediate value",
+        reloc:   Some(IMM8),
+    },
+    int("K6",  "K", 0,      63,    "immediate value", None),
+    int("K4",  "K", 0,      15,    "round",           None),
+    int("s",   "s", 0,      7,     "status bit",      None),
+    int("b",   "b", 0,      7,     "bit number",      None),
+    int("A",   "A", 0,      63,    "I/O address",     None),
+    int("A5",  "A", 0,      31,    "I/O address",     None),
+    int("q",   "q", 0,      63,    "displacement",    None),
+    int("k16", "k", -32768, 65535, "address",         Some(RelocKind::INT16)),
+    Field {
+        name:    "k7",
+        letters: "k",
+        kind:    Kind::Rel { origin: 2, scale: 2 },
+        order:   "",
+        what:    "branch offset",
+        reloc:   Some(BRANCH),
+    },
+    Field {
+        name:    "k12",
+        letters: "k",
+        kind:    Kind::Rel { origin: 2, scale: 2 },
+        order:   "",
+        what:    "branch offset",
+        reloc:   Some(REL12),
+    },
+    Field {
+        name:    "k22",
+        letters: "k",
+        kind:    Kind::Abs { scale: 2 },
+        order:   "",
+        what:    "jump address",
+        reloc:   Some(JUMP),
+    },
+];
+
+static ROWS: &[Row] = &[
+    // Arithmetic and logic
+    row  ("add",            "Rd, Rr",       "0000_11rd_dddd_rrrr",                      CORE),
+    row  ("adc",            "Rd, Rr",       "0001_11rd_dddd_rrrr",                      CORE),
+    row  ("sub",            "Rd, Rr",       "0001_10rd_dddd_rrrr",                      CORE),
+    row  ("sbc",            "Rd, Rr",       "0000_10rd_dddd_rrrr",                      CORE),
+    row  ("and",            "Rd, Rr",       "0010_00rd_dddd_rrrr",                      CORE),
+    row  ("or",             "Rd, Rr",       "0010_10rd_dddd_rrrr",                      CORE),
+    row  ("eor",            "Rd, Rr",       "0010_01rd_dddd_rrrr",                      CORE),
+    row  ("cp",             "Rd, Rr",       "0001_01rd_dddd_rrrr",                      CORE),
+    row  ("cpc",            "Rd, Rr",       "0000_01rd_dddd_rrrr",                      CORE),
+    row  ("cpse",           "Rd, Rr",       "0001_00rd_dddd_rrrr",                      CORE),
+    row  ("mov",            "Rd, Rr",       "0010_11rd_dddd_rrrr",                      CORE),
+    alias("lsl",            "Rdd",          "0000_11rd_dddd_rrrr",                      CORE),
+    alias("rol",            "Rdd",          "0001_11rd_dddd_rrrr",                      CORE),
+    alias("tst",            "Rdd",          "0010_00rd_dddd_rrrr",                      CORE),
+    alias("clr",            "Rdd",          "0010_01rd_dddd_rrrr",                      CORE),
+    row  ("com",            "Rd",           "1001_010d_dddd_0000",                      CORE),
+    row  ("neg",            "Rd",           "1001_010d_dddd_0001",                      CORE),
+    row  ("swap",           "Rd",           "1001_010d_dddd_0010",                      CORE),
+    row  ("inc",            "Rd",           "1001_010d_dddd_0011",                      CORE),
+    row  ("asr",            "Rd",           "1001_010d_dddd_0101",                      CORE),
+    row  ("lsr",            "Rd",           "1001_010d_dddd_0110",                      CORE),
+    row  ("ror",            "Rd",           "1001_010d_dddd_0111",                      CORE),
+    row  ("dec",            "Rd",           "1001_010d_dddd_1010",                      CORE),
+    row  ("cpi",            "Rh, K",        "0011_KKKK_dddd_KKKK",                      CORE),
+    row  ("sbci",           "Rh, K",        "0100_KKKK_dddd_KKKK",                      CORE),
+    row  ("subi",           "Rh, K",        "0101_KKKK_dddd_KKKK",                      CORE),
+    row  ("ori",            "Rh, K",        "0110_KKKK_dddd_KKKK",                      CORE),
+    alias("sbr",            "Rh, K",        "0110_KKKK_dddd_KKKK",                      CORE),
+    row  ("andi",           "Rh, K",        "0111_KKKK_dddd_KKKK",                      CORE),
+    alias("cbr",            "Rh, Kc",       "0111_KKKK_dddd_KKKK",                      CORE),
+    alias("ser",            "Rh",           "1110_1111_dddd_1111",                      CORE),
+    row  ("ldi",            "Rh, K",        "1110_KKKK_dddd_KKKK",                      CORE),
+    row  ("adiw",           "Rw, K6",       "1001_0110_KKdd_KKKK",                      CORE),
+    row  ("sbiw",           "Rw, K6",       "1001_0111_KKdd_KKKK",                      CORE),
+    row  ("movw",           "Re, Rre",      "0000_0001_dddd_rrrr",                      MOVW),
+    row  ("mul",            "Rd, Rr",       "1001_11rd_dddd_rrrr",                      MUL),
+    row  ("muls",           "Rh, Rrh",      "0000_0010_dddd_rrrr",                      MUL),
+    row  ("mulsu",          "Rm, Rrm",      "0000_0011_0ddd_0rrr",                      MUL),
+    row  ("fmul",           "Rm, Rrm",      "0000_0011_0ddd_1rrr",                      MUL),
+    row  ("fmuls",          "Rm, Rrm",      "0000_0011_1ddd_0rrr",                      MUL),
+    row  ("fmulsu",         "Rm, Rrm",      "0000_0011_1ddd_1rrr",                      MUL),
+    row  ("des",            "K4",           "1001_0100_KKKK_1011",                      XMEGA),
+
+    // Branches
+    row  ("brcs brlo",      "k7",           "1111_00kk_kkkk_k000",                      CORE),
+    row  ("brcc brsh",      "k7",           "1111_01kk_kkkk_k000",                      CORE),
+    row  ("breq",           "k7",           "1111_00kk_kkkk_k001",                      CORE),
+    row  ("brne",           "k7",           "1111_01kk_kkkk_k001",                      CORE),
+    row  ("brmi",           "k7",           "1111_00kk_kkkk_k010",                      CORE),
+    row  ("brpl",           "k7",           "1111_01kk_kkkk_k010",                      CORE),
+    row  ("brvs",           "k7",           "1111_00kk_kkkk_k011",                      CORE),
+    row  ("brvc",           "k7",           "1111_01kk_kkkk_k011",                      CORE),
+    row  ("brlt",           "k7",           "1111_00kk_kkkk_k100",                      CORE),
+    row  ("brge",           "k7",           "1111_01kk_kkkk_k100",                      CORE),
+    row  ("brhs",           "k7",           "1111_00kk_kkkk_k101",                      CORE),
+    row  ("brhc",           "k7",           "1111_01kk_kkkk_k101",                      CORE),
+    row  ("brts",           "k7",           "1111_00kk_kkkk_k110",                      CORE),
+    row  ("brtc",           "k7",           "1111_01kk_kkkk_k110",                      CORE),
+    row  ("brie",           "k7",           "1111_00kk_kkkk_k111",                      CORE),
+    row  ("brid",           "k7",           "1111_01kk_kkkk_k111",                      CORE),
+    row  ("brbs",           "s, k7",        "1111_00kk_kkkk_ksss",                      CORE),
+    row  ("brbc",           "s, k7",        "1111_01kk_kkkk_ksss",                      CORE),
+    row  ("rjmp",           "k12",          "1100_kkkk_kkkk_kkkk",                      CORE),
+    row  ("rcall",          "k12",          "1101_kkkk_kkkk_kkkk",                      CORE),
+    row  ("jmp",            "k22",          "1001_010k_kkkk_110k kkkk_kkkk_kkkk_kkkk",  JMP),
+    row  ("call",           "k22",          "1001_010k_kkkk_111k kkkk_kkkk_kkkk_kkkk",  JMP),
+    row  ("ijmp",           "",             "1001_0100_0000_1001",                      CORE),
+    row  ("icall",          "",             "1001_0101_0000_1001",                      CORE),
+    row  ("eijmp",          "",             "1001_0100_0001_1001",                      EIJMP),
+    row  ("eicall",         "",             "1001_0101_0001_1001",                      EIJMP),
+    row  ("ret",            "",             "1001_0101_0000_1000",                      CORE),
+    row  ("reti",           "",             "1001_0101_0001_1000",                      CORE),
+    row  ("sbrc",           "Rd, b",        "1111_110d_dddd_0bbb",                      CORE),
+    row  ("sbrs",           "Rd, b",        "1111_111d_dddd_0bbb",                      CORE),
+    row  ("sbic",           "[A5], b",      "1001_1001_AAAA_Abbb",                      CORE),
+    row  ("sbis",           "[A5], b",      "1001_1011_AAAA_Abbb",                      CORE),
+
+    // Bits and status flags
+    row  ("sbi",            "[A5], b",      "1001_1010_AAAA_Abbb",                      CORE),
+    row  ("cbi",            "[A5], b",      "1001_1000_AAAA_Abbb",                      CORE),
+    row  ("bst",            "Rd, b",        "1111_101d_dddd_0bbb",                      CORE),
+    row  ("bld",            "Rd, b",        "1111_100d_dddd_0bbb",                      CORE),
+    row  ("sec",            "",             "1001_0100_0000_1000",                      CORE),
+    row  ("sez",            "",             "1001_0100_0001_1000",                      CORE),
+    row  ("sen",            "",             "1001_0100_0010_1000",                      CORE),
+    row  ("sev",            "",             "1001_0100_0011_1000",                      CORE),
+    row  ("ses",            "",             "1001_0100_0100_1000",                      CORE),
+    row  ("seh",            "",             "1001_0100_0101_1000",                      CORE),
+    row  ("set",            "",             "1001_0100_0110_1000",                      CORE),
+    row  ("sei",            "",             "1001_0100_0111_1000",                      CORE),
+    row  ("clc",            "",             "1001_0100_1000_1000",                      CORE),
+    row  ("clz",            "",             "1001_0100_1001_1000",                      CORE),
+    row  ("cln",            "",             "1001_0100_1010_1000",                      CORE),
+    row  ("clv",            "",             "1001_0100_1011_1000",                      CORE),
+    row  ("cls",            "",             "1001_0100_1100_1000",                      CORE),
+    row  ("clh",            "",             "1001_0100_1101_1000",                      CORE),
+    row  ("clt",            "",             "1001_0100_1110_1000",                      CORE),
+    row  ("cli",            "",             "1001_0100_1111_1000",                      CORE),
+    row  ("bset",           "s",            "1001_0100_0sss_1000",                      CORE),
+    row  ("bclr",           "s",            "1001_0100_1sss_1000",                      CORE),
+
+    // Data transfer
+    row  ("ld ldd",         "Rd, [x]",      "1001_000d_dddd_1100",                      CORE),
+    row  ("ld ldd",         "Rd, [x]!",     "1001_000d_dddd_1101",                      CORE),
+    row  ("ld ldd",         "Rd, [--x]",    "1001_000d_dddd_1110",                      CORE),
+    row  ("ld ldd",         "Rd, [y]",      "1000_000d_dddd_1000",                      CORE),
+    row  ("ld ldd",         "Rd, [y]!",     "1001_000d_dddd_1001",                      CORE),
+    row  ("ld ldd",         "Rd, [--y]",    "1001_000d_dddd_1010",                      CORE),
+    row  ("ld ldd",         "Rd, [z]",      "1000_000d_dddd_0000",                      CORE),
+    row  ("ld ldd",         "Rd, [z]!",     "1001_000d_dddd_0001",                      CORE),
+    row  ("ld ldd",         "Rd, [--z]",    "1001_000d_dddd_0010",                      CORE),
+    row  ("ldd ld",         "Rd, [y + q]",  "10q0_qq0d_dddd_1qqq",                      CORE),
+    row  ("ldd ld",         "Rd, [z + q]",  "10q0_qq0d_dddd_0qqq",                      CORE),
+    row  ("st std",         "[x], Rr",      "1001_001r_rrrr_1100",                      CORE),
+    row  ("st std",         "[x]!, Rr",     "1001_001r_rrrr_1101",                      CORE),
+    row  ("st std",         "[--x], Rr",    "1001_001r_rrrr_1110",                      CORE),
+    row  ("st std",         "[y], Rr",      "1000_001r_rrrr_1000",                      CORE),
+    row  ("st std",         "[y]!, Rr",     "1001_001r_rrrr_1001",                      CORE),
+    row  ("st std",         "[--y], Rr",    "1001_001r_rrrr_1010",                      CORE),
+    row  ("st std",         "[z], Rr",      "1000_001r_rrrr_0000",                      CORE),
+    row  ("st std",         "[z]!, Rr",     "1001_001r_rrrr_0001",                      CORE),
+    row  ("st std",         "[--z], Rr",    "1001_001r_rrrr_0010",                      CORE),
+    row  ("std st",         "[y + q], Rr",  "10q0_qq1r_rrrr_1qqq",                      CORE),
+    row  ("std st",         "[z + q], Rr",  "10q0_qq1r_rrrr_0qqq",                      CORE),
+    row  ("lds",            "Rd, [k16]",    "1001_000d_dddd_0000 kkkk_kkkk_kkkk_kkkk",  CORE),
+    row  ("sts",            "[k16], Rr",    "1001_001r_rrrr_0000 kkkk_kkkk_kkkk_kkkk",  CORE),
+    row  ("in",             "Rd, [A]",      "1011_0AAd_dddd_AAAA",                      CORE),
+    row  ("out",            "[A], Rr",      "1011_1AAr_rrrr_AAAA",                      CORE),
+    row  ("push",           "Rr",           "1001_001r_rrrr_1111",                      CORE),
+    row  ("pop",            "Rd",           "1001_000d_dddd_1111",                      CORE),
+    row  ("lpm",            "",             "1001_0101_1100_1000",                      CORE),
+    row  ("lpm",            "Rd, [z]",      "1001_000d_dddd_0100",                      LPMX),
+    row  ("lpm",            "Rd, [z]!",     "1001_000d_dddd_0101",                      LPMX),
+    row  ("elpm",           "",             "1001_0101_1101_1000",                      ELPM),
+    row  ("elpm",           "Rd, [z]",      "1001_000d_dddd_0110",                      ELPM),
+    row  ("elpm",           "Rd, [z]!",     "1001_000d_dddd_0111",                      ELPM),
+    row  ("spm",            "",             "1001_0101_1110_1000",                      SPM),
+    row  ("spm",            "[z]!",         "1001_0101_1111_1000",                      SPM | XMEGA),
+    row  ("xch",            "[z], Rr",      "1001_001r_rrrr_0100",                      XMEGA),
+    row  ("las",            "[z], Rr",      "1001_001r_rrrr_0101",                      XMEGA),
+    row  ("lac",            "[z], Rr",      "1001_001r_rrrr_0110",                      XMEGA),
+    row  ("lat",            "[z], Rr",      "1001_001r_rrrr_0111",                      XMEGA),
+
+    // Control
+    row  ("nop",            "",             "0000_0000_0000_0000",                      CORE),
+    row  ("sleep",          "",             "1001_0101_1000_1000",                      CORE),
+    row  ("wdr",            "",             "1001_0101_1010_1000",                      CORE),
+    row  ("break",          "",             "1001_0101_1001_1000",                      CORE),
+];
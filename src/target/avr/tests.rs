@@ -0,0 +1,206 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, le16};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u16> = Bench { origin: 0x200, split: le16 };
+
+#[test]
+fn arithmetic() {
+    BENCH.check("avr2", &[
+        ("nop",                                 &[0x0000]),
+        ("ADD r0, r1",                          &[0x0C01]),
+        ("adc r16, r17",                        &[0x1F01]),
+        ("mov r0, r31",                         &[0x2E0F]),
+        ("clr r1",                              &[0x2411]),
+        ("lsl r16",                             &[0x0F00]),
+        ("tst r31",                             &[0x23FF]),
+        ("inc r16",                             &[0x9503]),
+        ("swap r0",                             &[0x9402]),
+        ("ldi r16, 255",                        &[0xEF0F]),
+        ("ldi r31, -1",                         &[0xEFFF]),
+        ("subi r20, x'12",                      &[0x5142]),
+        ("cbr r16, 1",                          &[0x7F0E]),
+        ("ser r17",                             &[0xEF1F]),
+        ("adiw r24, 1",                         &[0x9601]),
+        ("sbiw r30, 63",                        &[0x97FF]),
+    ]);
+    BENCH.check("avr4", &[
+        ("movw r24, r30",                       &[0x01CF]),
+        ("mul r0, r31",                         &[0x9E0F]),
+        ("muls r16, r17",                       &[0x0201]),
+        ("mulsu r16, r23",                      &[0x0307]),
+        ("fmul r16, r17",                       &[0x0309]),
+        ("fmulsu r23, r16",                     &[0x03F8]),
+    ]);
+    BENCH.check("avrxmega", &[
+        ("des 15",                              &[0x94FB]),
+    ]);
+}
+
+#[test]
+fn control() {
+    BENCH.check("avr2", &[
+        ("rjmp x'200",                          &[0xCFFF]),
+        ("rcall x'1200",                        &[0xD7FF]),
+        ("breq x'200",                          &[0xF3F9]),
+        ("brne x'210",                          &[0xF439]),
+        ("brbs 1, x'200",                       &[0xF3F9]),
+        ("ijmp",                                &[0x9409]),
+        ("ret",                                 &[0x9508]),
+        ("sbrs r16, 7",                         &[0xFF07]),
+        ("sbic [x'1f], 0",                      &[0x99F8]),
+        ("cli",                                 &[0x94F8]),
+        ("bset 7",                              &[0x9478]),
+        ("bst r0, 7",                           &[0xFA07]),
+    ]);
+    BENCH.check("avr5", &[
+        ("jmp x'1234",                          &[0x940C, 0x091A]),
+        ("call x'20000",                        &[0x940F, 0x0000]),
+    ]);
+    BENCH.check("avr6", &[
+        ("eicall",                              &[0x9519]),
+    ]);
+}
+
+#[test]
+fn memory() {
+    BENCH.check("avr2", &[
+        ("in r16, [x'3f]",                      &[0xB70F]),
+        ("out [x'3f], r0",                      &[0xBE0F]),
+        ("sbi [x'18], 3",                       &[0x9AC3]),
+        ("lds r16, [x'100]",                    &[0x9100, 0x0100]),
+        ("sts [x'ffff], r1",                    &[0x9210, 0xFFFF]),
+        ("ld r16, [x]",                         &[0x910C]),
+        ("ld r16, [x]!",                        &[0x910D]),
+        ("ld r0, [--x]",                        &[0x900E]),
+        ("ld r0, [y]",                          &[0x8008]),
+        ("ldd r16, [y + 1]",                    &[0x8109]),
+        ("ldd r16, [z + 63]",                   &[0xAD07]),
+        ("std [y + 2], r1",                     &[0x821A]),
+        ("st [--y], r0",                        &[0x920A]),
+        ("st [z]!, r31",                        &[0x93F1]),
+        ("push r16",                            &[0x930F]),
+        ("pop r16",                             &[0x910F]),
+        ("lpm",                                 &[0x95C8]),
+    ]);
+    BENCH.check("avr25", &[
+        ("lpm r0, [z]!",                        &[0x9005]),
+        ("lpm r16, [z]",                        &[0x9104]),
+        ("spm",                                 &[0x95E8]),
+    ]);
+    BENCH.check("avr51", &[
+        ("elpm",                                &[0x95D8]),
+        ("elpm r16, [z]",                       &[0x9106]),
+    ]);
+    BENCH.check("avrxmega", &[
+        ("spm [z]!",                            &[0x95F8]),
+        ("xch [z], r16",                        &[0x9304]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("atmega328p").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  rcall sub
+                ldi r30, lo8(pm(sub))
+                ldi r31, hi8(pm(sub))
+                ijmp
+        sub:    ret
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(BENCH.words(&program.sections[0].data), [0xD003, 0xE0E4, 0xE0F0, 0x9409, 0x9508]);
+}
+
+#[test]
+fn decoding() {
+    let target = (find("avrxmega").unwrap().new)(&mut NameTable::new());
+    let label  = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for line in [
+        "add r0, r1",
+        "ldi r16, x'FF",
+        "andi r17, x'F0",
+        "adiw r24, 1",
+        "movw r24, r30",
+        "fmulsu r23, r16",
+        "breq start",
+        "brie start",
+        "rcall x'1200",
+        "call x'20000",
+        "sei",
+        "sbic [x'1F], 0",
+        "in r16, [x'3F]",
+        "lds r16, [x'100]",
+        "ld r0, [--x]",
+        "ld r0, [y]",
+        "ldd r16, [z + x'3F]",
+        "std [y + 2], r1",
+        "lpm r0, [z]!",
+        "spm [z]!",
+        "xch [z], r16",
+    ] {
+        let (bytes, _) = BENCH.assemble("avrxmega", &line.replace("start", "x'200"));
+        let decoded    = target.decode(&bytes, 0x200, &label).unwrap();
+        assert_eq!(decoded.text, line);
+        assert_eq!(decoded.size, bytes.len());
+    }
+
+    // Aliases decode as the instructions that they stand for
+    let (bytes, _) = BENCH.assemble("avr2", "lsl r16");
+    assert_eq!(target.decode(&bytes, 0x200, &label).unwrap().text, "add r16, r16");
+
+    // Instructions that the target lacks do not decode
+    let avr2 = (find("avr2").unwrap().new)(&mut NameTable::new());
+    assert_eq!(avr2.decode(&[0x01, 0x00], 0, &label), None);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("attiny85",   "mul r0, r1"),       "instruction 'mul' is not available on attiny85");
+    assert_eq!(BENCH.error("atmega8",    "jmp x'200"),        "instruction 'jmp' is not available on atmega8");
+    assert_eq!(BENCH.error("atmega328p", "elpm"),             "instruction 'elpm' is not available on atmega328p");
+    assert_eq!(BENCH.error("avr2",       "lpm r0, [z]"),      "instruction form is not available on avr2");
+    assert_eq!(BENCH.error("atmega2560", "spm [z]!"),         "instruction form is not available on atmega2560");
+    assert_eq!(BENCH.error("avr2",       "ldi r0, 1"),        "register must be r16-r31");
+    assert_eq!(BENCH.error("avr4",       "fmul r24, r16"),    "register must be r16-r23");
+    assert_eq!(BENCH.error("avr2",       "ldi r16, 256"),     "immediate value 256 out of range");
+    assert_eq!(BENCH.error("avr2",       "breq x'300"),       "branch offset 254 out of range");
+    assert_eq!(BENCH.error("avr2",       "adiw r25, 1"),      "register must be r24, r26, r28, or r30");
+    assert_eq!(BENCH.error("avr25",      "movw r1, r2"),      "register must be even");
+    assert_eq!(BENCH.error("avr2",       "in r0, [64]"),      "I/O address 64 out of range");
+    assert_eq!(BENCH.error("avr2",       "ldd r0, [x + 1]"),  "invalid memory operand");
+    assert_eq!(BENCH.error("avr2",       "ldd r0, [y + 64]"), "displacement 64 out of range");
+    assert_eq!(BENCH.error("avr2",       "add r0, 1"),        "invalid operands");
+}
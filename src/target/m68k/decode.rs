@@ -0,0 +1,816 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction decoding.
+//!
+//! The decoder reads the brief extension word format of indexed operands but
+//! not the 68020 full format, so a memory indirect operand, or an indexed
+//! operand with a displacement beyond 8 bits, decodes as data.
+
+use crate::target::{number, Decoded};
+
+use super::*;
+use super::operand::CONTROL;
+
+// ----------------------------------------------------------------------------
+
+/// General-purpose registers, by number, data registers first.
+const REGS: [&str; 16] = [
+    "d0", "d1", "d2", "d3", "d4", "d5", "d6", "d7",
+    "a0", "a1", "a2", "a3", "a4", "a5", "a6", "sp",
+];
+
+/// Immediate operation mnemonics, by bits 11-9 of the opcode word.
+const IMMEDIATE: [&str; 8] = ["ori", "andi", "subi", "addi", "", "eori", "cmpi", ""];
+
+/// Bit operation mnemonics, by their type.
+const BITS: [&str; 4] = ["btst", "bchg", "bclr", "bset"];
+
+/// Shift and rotate mnemonics, by their type and direction.
+const SHIFTS: [&str; 8] = ["asr", "asl", "lsr", "lsl", "roxr", "roxl", "ror", "rol"];
+
+/// Bit field mnemonics, by bits 10-8 of the opcode word.
+const BITFIELDS: [&str; 8] = [
+    "bftst", "bfextu", "bfchg", "bfexts", "bfclr", "bfffo", "bfset", "bfins",
+];
+
+/// Decodes the instruction at the beginning of `bytes`, which lies at
+/// address `addr`.  `label` returns the name by which to refer to an
+/// address, if any.  `isa` is the instruction set features of the target,
+/// and `table` its instruction table.
+pub fn decode(
+    isa:   u32,
+    table: &[Entry],
+    bytes: &[u8],
+    addr:  u64,
+    label: &dyn Fn(u64) -> Option<String>,
+) -> Option<Decoded> {
+    let mut d = Decoder { isa, table, bytes, addr, at: 0, label, jump: false, targets: vec![] };
+    let text  = d.insn()?;
+    Some(Decoded { text, size: d.at, targets: d.targets })
+}
+
+/// Decoder state for one instruction.
+struct Decoder<'a> {
+    /// Instruction set features of the target.
+    isa: u32,
+
+    /// Instruction table of the target.
+    table: &'a [Entry],
+
+    /// Bytes of the instruction and those after it.
+    bytes: &'a [u8],
+
+    /// Address of the instruction.
+    addr: u64,
+
+    /// Offset of the next word to read.
+    at: usize,
+
+    /// Returns the name by which to refer to an address, if any.
+    label: &'a dyn Fn(u64) -> Option<String>,
+
+    /// Whether the address of a memory operand is a jump or call target.
+    jump: bool,
+
+    /// Addresses to which the instruction jumps or calls.
+    targets: Vec<u64>,
+}
+
+impl Decoder<'_> {
+    fn insn(&mut self) -> Option<String> {
+        let op = self.word()?;
+
+        let inherent = self.table
+            .iter()
+            .find(|e| e.form == Form::Inherent && e.op == op && e.isa & self.isa != 0);
+
+        if let Some(entry) = inherent {
+            return Some(entry.name.clone());
+        }
+
+        match op >> 12 {
+            0x0                     => self.line0(op),
+            0x1..=0x3               => self.move_(op),
+            0x4                     => self.line4(op),
+            0x5                     => self.line5(op),
+            0x6                     => self.branch(op),
+            0x7                     => self.line7(op),
+            0x8 | 0x9 | 0xB..=0xD   => self.arith(op),
+            0xA if self.has(COLDFIRE) => self.mac(op),
+            0xE                     => self.line14(op),
+            _                       => None,
+        }
+    }
+
+    // === Opcode Lines ===
+
+    /// Decodes bit, immediate, and `movep` instructions, and their 68020 and
+    /// ColdFire neighbors.
+    fn line0(&mut self, op: u16) -> Option<String> {
+        let n    = op >> 9 & 7;
+        let kind = op >> 6 & 3;
+
+        // Immediate to condition code or status register
+        if matches!(op & 0xFFBF, 0x003C | 0x023C | 0x0A3C) {
+            let (size, reg) = match op & 0x40 {
+                0 => (Size::B, "ccr"),
+                _ => (Size::W, "sr"),
+            };
+            return Some(format!("{} {}, {}", IMMEDIATE[n as usize], self.imm(size)?, reg));
+        }
+
+        if op & 0x0100 != 0 {
+            if op & 0x38 == 0x08 {
+                // Data register to or from alternate bytes
+                let size = if op & 0x40 != 0 { Size::L } else { Size::W };
+                let a    = areg(op & 7);
+                let mem  = match self.word()? as i16 {
+                    0    => format!("[{}]", a),
+                    disp => format!("[{}{}]", a, offset(disp as i64)),
+                };
+                return match op & 0x80 {
+                    0 => Some(format!("movep{} {}, d{}", size.suffix(), mem, n)),
+                    _ => Some(format!("movep{} d{}, {}", size.suffix(), n, mem)),
+                };
+            }
+            let dst = self.ea(op, Size::W)?;
+            return Some(format!("{} d{}, {}", BITS[kind as usize], n, dst));
+        }
+
+        if op & 0xFF00 == 0x0800 {
+            let bit = self.imm(Size::B)?;
+            let dst = self.ea(op, Size::W)?;
+            return Some(format!("{} {}, {}", BITS[kind as usize], bit, dst));
+        }
+
+        if kind == 3 {
+            return match n {
+                0..=2 if self.has(COLDFIRE) && op & 0x38 == 0 => {
+                    let name = ["bitrev", "byterev", "ff1"][n as usize];
+                    Some(format!("{} d{}", name, op & 7))
+                },
+                0..=2 => {
+                    let size = [Size::B, Size::W, Size::L][n as usize];
+                    let ext  = self.word()?;
+                    let name = if ext & 0x0800 != 0 { "chk2" } else { "cmp2" };
+                    if ext & 0x07FF != 0 {
+                        return None;
+                    }
+                    let src = self.ea(op, size)?;
+                    let reg  = REGS[(ext >> 12) as usize];
+                    Some(format!("{}{} {}, {}", name, size.suffix(), src, reg))
+                },
+                6 | 7 if op & 0x3F == 0x3C => {
+                    let size = if n == 7 { Size::L } else { Size::W };
+                    let (dc1, du1, rn1) = cas2(self.word()?)?;
+                    let (dc2, du2, rn2) = cas2(self.word()?)?;
+                    Some(format!(
+                        "cas2{} d{}:d{}, d{}:d{}, [{}]:[{}]",
+                        size.suffix(), dc1, dc2, du1, du2, REGS[rn1], REGS[rn2]
+                    ))
+                },
+                5..=7 => {
+                    let size = [Size::B, Size::W, Size::L][n as usize - 5];
+                    let ext  = self.word()?;
+                    if ext & 0xFE38 != 0 {
+                        return None;
+                    }
+                    let dst = self.ea(op, size)?;
+                    Some(format!("cas{} d{}, d{}, {}", size.suffix(), ext & 7, ext >> 6 & 7, dst))
+                },
+                _ => None,
+            };
+        }
+
+        let size = [Size::B, Size::W, Size::L][kind as usize];
+
+        if n == 7 {
+            // Move to or from an address space
+            let ext = self.word()?;
+            if ext & 0x07FF != 0 {
+                return None;
+            }
+            let reg = REGS[(ext >> 12) as usize];
+            let ea  = self.ea(op, size)?;
+            return match ext & 0x0800 {
+                0 => Some(format!("moves{} {}, {}", size.suffix(), ea, reg)),
+                _ => Some(format!("moves{} {}, {}", size.suffix(), reg, ea)),
+            };
+        }
+
+        let name = IMMEDIATE[n as usize];
+        if name.is_empty() {
+            return None;
+        }
+        let src = self.imm(size)?;
+        let dst = self.ea(op, size)?;
+        Some(format!("{}{} {}, {}", name, size.suffix(), src, dst))
+    }
+
+    /// Decodes `move` and `movea`.
+    fn move_(&mut self, op: u16) -> Option<String> {
+        let size = match op >> 12 {
+            1 => Size::B,
+            3 => Size::W,
+            _ => Size::L,
+        };
+
+        let src = self.ea(op, size)?;
+
+        if op >> 6 & 7 == 1 {
+            if size == Size::B {
+                return None;
+            }
+            return Some(format!("movea{} {}, {}", size.suffix(), src, areg(op >> 9 & 7)));
+        }
+
+        let dst = self.ea(op >> 3 & 0x38 | op >> 9 & 7, size)?;
+        Some(format!("move{} {}, {}", size.suffix(), src, dst))
+    }
+
+    /// Decodes miscellaneous instructions.
+    fn line4(&mut self, op: u16) -> Option<String> {
+        let n = op & 7;
+        let a = areg(n);
+        let r = REGS[(op >> 9 & 7) as usize];
+
+        let text = match op {
+            0x4E72 => format!("stop {}", self.imm(Size::W)?),
+            0x4E74 => format!("rtd {}", self.imm(Size::W)?),
+            0x4E7A | 0x4E7B => {
+                let ext  = self.word()?;
+                let reg  = REGS[(ext >> 12) as usize];
+                let ctrl = match ext & 0xFFF {
+                    0x800 => "usp",
+                    code  => CONTROL.iter().find(|c| c.1 == code)?.0,
+                };
+                match op & 1 {
+                    0 => format!("movec {}, {}", ctrl, reg),
+                    _ => format!("movec {}, {}", reg, ctrl),
+                }
+            },
+            _ => match op & 0xFFF8 {
+                0x4E40 | 0x4E48 => format!("trap {}", number((op & 15) as i64)),
+                0x4E50 => format!("link {}, {}", a, number(self.word()? as i16 as i64)),
+                0x4E58 => format!("unlk {}", a),
+                0x4E60 => format!("move {}, usp", a),
+                0x4E68 => format!("move usp, {}", a),
+                0x4808 => format!("link.l {}, {}", a, number(self.long()? as i32 as i64)),
+                0x4848 => format!("bkpt {}", n),
+                0x4840 => format!("swap d{}", n),
+                0x4880 => format!("ext.w d{}", n),
+                0x48C0 => format!("ext.l d{}", n),
+                0x49C0 => format!("extb d{}", n),
+                0x4C80 if self.has(COLDFIRE) => format!("sats d{}", n),
+                _ => return self.line4_ea(op, r),
+            },
+        };
+
+        Some(text)
+    }
+
+    /// Decodes miscellaneous instructions with an effective address.  `r` is
+    /// the data register of bits 11-9.
+    fn line4_ea(&mut self, op: u16, r: &str) -> Option<String> {
+        let text = match op & 0xFFC0 {
+            0x4E80 | 0x4EC0 => {
+                self.jump = true;
+                let dst   = self.ea(op, Size::L)?;
+                let name  = if op & 0x40 != 0 { "jmp" } else { "jsr" };
+                format!("{} {}", name, dst)
+            },
+            0x4800 => format!("nbcd {}",        self.ea(op, Size::B)?),
+            0x4840 => format!("pea {}",         self.ea(op, Size::L)?),
+            0x4AC0 => format!("tas {}",         self.ea(op, Size::B)?),
+            0x40C0 => format!("move sr, {}",    self.ea(op, Size::W)?),
+            0x42C0 => format!("move ccr, {}",   self.ea(op, Size::W)?),
+            0x44C0 => format!("move {}, ccr",   self.ea(op, Size::W)?),
+            0x46C0 => format!("move {}, sr",    self.ea(op, Size::W)?),
+            0x4880 | 0x48C0 | 0x4C80 | 0x4CC0 => {
+                let size = if op & 0x40 != 0 { Size::L } else { Size::W };
+                let mask = self.word()?;
+                if mask == 0 {
+                    return None;
+                }
+                let ea   = self.ea(op, size)?;
+                let list = match op & 0x38 {
+                    0x20 => list(mask.reverse_bits()),
+                    _    => list(mask),
+                };
+                match op & 0x0400 {
+                    0 => format!("movem{} {}, {}", size.suffix(), list, ea),
+                    _ => format!("movem{} {}, {}", size.suffix(), ea, list),
+                }
+            },
+            0x4C00 => {
+                let ext  = self.word()?;
+                let name = if ext & 0x0800 != 0 { "muls" } else { "mulu" };
+                let (hi, lo) = (ext & 7, ext >> 12 & 7);
+                if ext & 0x83F8 != 0 || ext & 0x0400 == 0 && hi != 0 {
+                    return None;
+                }
+                let src = self.ea(op, Size::L)?;
+                match ext & 0x0400 {
+                    0 => format!("{}.l {}, d{}", name, src, lo),
+                    _ => format!("{}.l {}, d{}:d{}", name, src, hi, lo),
+                }
+            },
+            0x4C40 => {
+                let ext    = self.word()?;
+                let signed = ext & 0x0800 != 0;
+                let (r, q) = (ext & 7, ext >> 12 & 7);
+                if ext & 0x83F8 != 0 {
+                    return None;
+                }
+                let src  = self.ea(op, Size::L)?;
+                let name = match (ext & 0x0400 != 0, r == q, self.has(COLDFIRE)) {
+                    (false, true, _) => return Some(format!(
+                        "{}.l {}, d{}", if signed { "divs" } else { "divu" }, src, q
+                    )),
+                    (true,  _, _)     => if signed { "divs.l" } else { "divu.l" },
+                    (false, _, true)  => if signed { "rems" }   else { "remu" },
+                    (false, _, false) => if signed { "divsl" }  else { "divul" },
+                };
+                format!("{} {}, d{}:d{}", name, src, r, q)
+            },
+            _ => match op & 0xF1C0 {
+                0x41C0 => format!("lea {}, {}", self.ea(op, Size::L)?, areg(op >> 9 & 7)),
+                0x4180 => format!("chk.w {}, {}", self.ea(op, Size::W)?, r),
+                0x4100 => format!("chk.l {}, {}", self.ea(op, Size::L)?, r),
+                _ => {
+                    let name = match op & 0xFF00 {
+                        0x4000 => "negx",
+                        0x4200 => "clr",
+                        0x4400 => "neg",
+                        0x4600 => "not",
+                        0x4A00 => "tst",
+                        _      => return None,
+                    };
+                    let size = size(op >> 6)?;
+                    format!("{}{} {}", name, size.suffix(), self.ea(op, size)?)
+                },
+            },
+        };
+
+        Some(text)
+    }
+
+    /// Decodes quick arithmetic and conditional instructions other than
+    /// branches.
+    fn line5(&mut self, op: u16) -> Option<String> {
+        let cc = op >> 8 & 15;
+
+        if let Some(size) = size(op >> 6) {
+            let name = if op & 0x0100 != 0 { "subq" } else { "addq" };
+            let n    = match op >> 9 & 7 { 0 => 8, n => n };
+            return Some(format!("{}{} {}, {}", name, size.suffix(), n, self.ea(op, size)?));
+        }
+
+        match op >> 3 & 7 {
+            1 => {
+                let disp   = self.word()? as i16 as i64;
+                let target = self.target(self.addr.wrapping_add(2).wrapping_add(disp as u64));
+                match cc {
+                    1 => Some(format!("dbra d{}, {}", op & 7, target)),
+                    _ => Some(format!("db{} d{}, {}", condition(cc), op & 7, target)),
+                }
+            },
+            7 if matches!(op & 7, 2..=4) => {
+                let name = match self.has(COLDFIRE) {
+                    true if cc == 1 => "tpf".to_string(),
+                    true            => return None,
+                    false           => format!("trap{}", condition(cc)),
+                };
+                match op & 7 {
+                    2 => Some(format!("{}.w {}", name, self.imm(Size::W)?)),
+                    3 => Some(format!("{}.l {}", name, self.imm(Size::L)?)),
+                    _ => Some(name),
+                }
+            },
+            _ => Some(format!("s{} {}", condition(cc), self.ea(op, Size::B)?)),
+        }
+    }
+
+    /// Decodes branches.  A branch has a size suffix only if the assembler
+    /// would otherwise choose a shorter form.
+    fn branch(&mut self, op: u16) -> Option<String> {
+        let name = match op >> 8 & 15 {
+            0  => "bra".to_string(),
+            1  => "bsr".to_string(),
+            cc => format!("b{}", condition(cc)),
+        };
+
+        let (size, disp) = match op as u8 {
+            0x00 => (Size::W, self.word()? as i16 as i64),
+            0xFF if self.has(M68020 | CF_ISA_B) => (Size::L, self.long()? as i32 as i64),
+            0xFF => return None,
+            disp => (Size::S, disp as i8 as i64),
+        };
+
+        let suffix = match size {
+            Size::W if matches!(disp, -128..=-2 | 1..=127) => ".w",
+            Size::L if is_signed(disp, 16)                 => ".l",
+            _                                              => "",
+        };
+
+        let target = self.target(self.addr.wrapping_add(2).wrapping_add(disp as u64));
+        Some(format!("{}{} {}", name, suffix, target))
+    }
+
+    /// Decodes `moveq` and the ColdFire `mvs` and `mvz`.
+    fn line7(&mut self, op: u16) -> Option<String> {
+        let n = op >> 9 & 7;
+
+        if op & 0x0100 == 0 {
+            return Some(format!("moveq {}, d{}", number(op as u8 as i8 as i64), n));
+        }
+
+        if !self.has(CF_ISA_B) {
+            return None;
+        }
+
+        let size = if op & 0x40 != 0 { Size::W } else { Size::B };
+        let name = if op & 0x80 != 0 { "mvz" } else { "mvs" };
+        Some(format!("{}{} {}, d{}", name, size.suffix(), self.ea(op, size)?, n))
+    }
+
+    /// Decodes two-operand arithmetic and logic and their neighbors.
+    fn arith(&mut self, op: u16) -> Option<String> {
+        let line   = op >> 12;
+        let n      = op >> 9 & 7;
+        let y      = op & 7;
+        let opmode = op >> 6 & 7;
+        let memory = op & 0x08 != 0;
+
+        let name = match line {
+            0x8 => "or",
+            0x9 => "sub",
+            0xB => "cmp",
+            0xC => "and",
+            _   => "add",
+        };
+
+        // Address or word multiply and divide
+        if opmode & 3 == 3 {
+            let size = if opmode == 7 { Size::L } else { Size::W };
+            let name = match (line, opmode) {
+                (0x8, 3) => "divu",
+                (0x8, _) => "divs",
+                (0xC, 3) => "mulu",
+                (0xC, _) => "muls",
+                _        => return Some(format!(
+                    "{}a{} {}, {}", name, size.suffix(), self.ea(op, size)?, areg(n)
+                )),
+            };
+            return Some(format!("{}.w {}, d{}", name, self.ea(op, Size::W)?, n));
+        }
+
+        let size = [Size::B, Size::W, Size::L][(opmode & 3) as usize];
+
+        // <ea>, Dn
+        if opmode < 4 {
+            return Some(format!("{}{} {}, d{}", name, size.suffix(), self.ea(op, size)?, n));
+        }
+
+        // Register forms
+        let (ry, rx) = match memory {
+            false => (format!("d{}", y), format!("d{}", n)),
+            true  => (format!("[--{}]", areg(y)), format!("[--{}]", areg(n))),
+        };
+
+        let text = match (line, op & 0x38) {
+            (0xB, 0x08) => format!(
+                "cmpm{} [{}]!, [{}]!", size.suffix(), areg(y), areg(n)
+            ),
+            (0xB, _) => {
+                return Some(format!("eor{} d{}, {}", size.suffix(), n, self.ea(op, size)?));
+            },
+            (0x8 | 0xC, 0x00 | 0x08) => match (line, opmode) {
+                (0x8, 4) => format!("sbcd {}, {}", ry, rx),
+                (0xC, 4) => format!("abcd {}, {}", ry, rx),
+                (0x8, 5) => format!("pack {}, {}, {}", ry, rx, self.imm(Size::W)?),
+                (0x8, _) => format!("unpk {}, {}, {}", ry, rx, self.imm(Size::W)?),
+                (_,   5) if memory => format!("exg {}, {}", areg(n), areg(y)),
+                (_,   5) => format!("exg d{}, d{}", n, y),
+                (_,   _) if memory => format!("exg d{}, {}", n, areg(y)),
+                (_,   _) => return None,
+            },
+            (_, 0x00 | 0x08) => format!("{}x{} {}, {}", name, size.suffix(), ry, rx),
+            (_, _) => format!("{}{} d{}, {}", name, size.suffix(), n, self.ea(op, size)?),
+        };
+
+        Some(text)
+    }
+
+    /// Decodes the ColdFire multiply-accumulate instructions and `mov3q`.
+    fn mac(&mut self, op: u16) -> Option<String> {
+        let acc  = op >> 9 & 3;
+        let reg  = REGS[(op & 15) as usize];
+
+        let text = match op & 0xF9F0 {
+            0xA180                  => format!("move.l acc{}, {}", acc, reg),
+            0xA1C0                  => format!("movclr acc{}, {}", acc, reg),
+            0xA980 if acc == 0      => format!("move.l macsr, {}", reg),
+            0xA980 if acc == 2      => format!("move.l mask, {}", reg),
+            0xA9C0 if op == 0xA9C0  => "move.l macsr, ccr".to_string(),
+            _ => match op & 0xF9C0 {
+                0xA100 => format!("move.l {}, acc{}", self.ea(op, Size::L)?, acc),
+                0xA900 if acc == 0 => format!("move.l {}, macsr", self.ea(op, Size::L)?),
+                0xA900 if acc == 2 => format!("move.l {}, mask",  self.ea(op, Size::L)?),
+                _ if op & 0xF1C0 == 0xA140 => {
+                    let n = match op >> 9 & 7 { 0 => -1, n => n as i64 };
+                    format!("mov3q {}, {}", n, self.ea(op, Size::L)?)
+                },
+                _ if op & 0xF130 == 0xA000 => return self.mac_op(op),
+                _ => return None,
+            },
+        };
+
+        Some(text)
+    }
+
+    /// Decodes `mac` and `msac`.
+    fn mac_op(&mut self, op: u16) -> Option<String> {
+        let ext = self.word()?;
+        if ext & 0xF02F != 0 {
+            return None;
+        }
+
+        let rx   = (op >> 9 & 7 | op >> 3 & 8) as usize;
+        let ry   = (op & 7 | op & 8) as usize;
+        let acc  = op >> 7 & 1 | ext >> 3 & 2;
+        let long = ext & 0x0800 != 0;
+        let ux   = if ext & 0x80 != 0 { ".u" } else { "" };
+        let uy   = if ext & 0x40 != 0 { ".u" } else { "" };
+        let name = if ext & 0x0100 != 0 { "msac" } else { "mac" };
+
+        if long && (ux, uy) != ("", "") {
+            return None;
+        }
+
+        let shift = match ext >> 9 & 3 {
+            0 => "",
+            1 => " << 1",
+            3 => " >> 1",
+            _ => return None,
+        };
+
+        let size = if long { Size::L } else { Size::W };
+        let mut text = format!("{}{} {}{}, {}{}", name, size.suffix(), REGS[ry], uy, REGS[rx], ux);
+        text += shift;
+        if acc != 0 {
+            text += &format!(", acc{}", acc);
+        }
+        Some(text)
+    }
+
+    /// Decodes shifts, rotates, and bit field instructions.
+    fn line14(&mut self, op: u16) -> Option<String> {
+        let dir = (op >> 8 & 1) as usize;
+
+        let size = match size(op >> 6) {
+            Some(size) => size,
+            None if op & 0x0800 != 0 => return self.bitfield(op),
+            None => {
+                let name = SHIFTS[((op >> 9 & 3) as usize) << 1 | dir];
+                return Some(format!("{}.w {}", name, self.ea(op, Size::W)?));
+            },
+        };
+
+        let name  = SHIFTS[((op >> 3 & 3) as usize) << 1 | dir];
+        let count = match (op & 0x20 != 0, op >> 9 & 7) {
+            (true, n)  => format!("d{}", n),
+            (false, 0) => "8".to_string(),
+            (false, n) => n.to_string(),
+        };
+        Some(format!("{}{} {}, d{}", name, size.suffix(), count, op & 7))
+    }
+
+    /// Decodes a 68020 bit field instruction.
+    fn bitfield(&mut self, op: u16) -> Option<String> {
+        let ext = self.word()?;
+        if ext & 0x8000 != 0 {
+            return None;
+        }
+
+        let reg    = ext >> 12;
+        let offset = match ext & 0x0800 {
+            0 => (ext >> 6 & 31).to_string(),
+            _ if ext & 0x0600 != 0 => return None,
+            _ => format!("d{}", ext >> 6 & 7),
+        };
+        let width = match ext & 0x20 {
+            0 => match ext & 31 { 0 => 32, w => w }.to_string(),
+            _ if ext & 0x18 != 0 => return None,
+            _ => format!("d{}", ext & 7),
+        };
+
+        let kind = op >> 8 & 7;
+        let name = BITFIELDS[kind as usize];
+        let ea   = self.ea(op, Size::L)?;
+
+        match kind {
+            1 | 3 | 5 => Some(format!("{} {}, {}, {}, d{}", name, ea, offset, width, reg)),
+            7         => Some(format!("{} d{}, {}, {}, {}", name, reg, ea, offset, width)),
+            _ if reg != 0 => None,
+            _         => Some(format!("{} {}, {}, {}", name, ea, offset, width)),
+        }
+    }
+
+    // === Operands ===
+
+    /// Decodes the effective address in the low 6 bits of `field`, reading
+    /// its extension words.  `size` is the size of an immediate operand.
+    fn ea(&mut self, field: u16, size: Size) -> Option<String> {
+        let a = areg(field & 7);
+
+        let text = match field >> 3 & 7 {
+            0 => format!("d{}", field & 7),
+            1 => a.to_string(),
+            2 => format!("[{}]", a),
+            3 => format!("[{}]!", a),
+            4 => format!("[--{}]", a),
+            5 => format!("[{}{}]", a, offset(self.word()? as i16 as i64)),
+            6 => {
+                let (index, disp) = self.brief()?;
+                match disp {
+                    0    => format!("[{} + {}]", a, index),
+                    disp => format!("[{} + {}{}]", a, index, offset(disp)),
+                }
+            },
+            _ => match field & 7 {
+                0 => {
+                    let addr = self.word()? as i16 as i32 as u32;
+                    format!("[{}]", self.address(addr as u64))
+                },
+                1 => {
+                    let addr = self.long()?;
+                    if is_abs16(addr as i64) {
+                        return None;
+                    }
+                    format!("[{}]", self.address(addr as u64))
+                },
+                2 => {
+                    let at   = self.at as u64;
+                    let disp = self.word()? as i16 as i64;
+                    let addr = self.addr.wrapping_add(at).wrapping_add(disp as u64);
+                    format!("[pc + {}]", self.address(addr))
+                },
+                3 => {
+                    let at            = self.at as u64;
+                    let (index, disp) = self.brief()?;
+                    let addr          = self.addr.wrapping_add(at).wrapping_add(disp as u64);
+                    format!("[pc + {} + {}]", index, self.address(addr))
+                },
+                4 => self.imm(size)?,
+                _ => return None,
+            },
+        };
+
+        Some(text)
+    }
+
+    /// Reads a brief extension word.  Returns the index register and the
+    /// displacement.
+    fn brief(&mut self) -> Option<(String, i64)> {
+        let ext = self.word()?;
+        if ext & 0x0100 != 0 {
+            return None;
+        }
+
+        let size  = if ext & 0x0800 != 0 { "l" } else { "w" };
+        let index = match ext >> 9 & 3 {
+            0     => format!("{}.{}", REGS[(ext >> 12) as usize], size),
+            scale => format!("{}.{}*{}", REGS[(ext >> 12) as usize], size, 1 << scale),
+        };
+
+        Some((index, ext as u8 as i8 as i64))
+    }
+
+    /// Reads an immediate operand of the given size.
+    fn imm(&mut self, size: Size) -> Option<String> {
+        let value = match size {
+            Size::B => match self.word()? {
+                word @ 0..=0xFF => word as u32,
+                _               => return None,
+            },
+            Size::L => self.long()?,
+            _       => self.word()? as u32,
+        };
+        Some(number(value as i64))
+    }
+
+    /// Returns the text by which to refer to the address of a memory
+    /// operand.
+    fn address(&mut self, addr: u64) -> String {
+        let addr = addr & 0xFFFF_FFFF;
+        match self.jump {
+            true  => self.target(addr),
+            false => (self.label)(addr).unwrap_or_else(|| number(addr as i64)),
+        }
+    }
+
+    /// Returns the text by which to refer to the target of a jump or call.
+    fn target(&mut self, addr: u64) -> String {
+        let addr = addr & 0xFFFF_FFFF;
+        self.targets.push(addr);
+        (self.label)(addr).unwrap_or_else(|| number(addr as i64))
+    }
+
+    fn word(&mut self) -> Option<u16> {
+        let bytes = self.bytes.get(self.at..self.at + 2)?;
+        self.at += 2;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn long(&mut self) -> Option<u32> {
+        let hi = self.word()? as u32;
+        let lo = self.word()? as u32;
+        Some(hi << 16 | lo)
+    }
+
+    /// Returns whether the target has one of the given instruction set
+    /// features.
+    fn has(&self, isa: u32) -> bool {
+        self.isa & isa != 0
+    }
+}
+
+/// Returns the name of the given address register.
+fn areg(n: u16) -> &'static str {
+    REGS[8 + n as usize]
+}
+
+/// Returns the size that a standard size field selects, if any.
+fn size(bits: u16) -> Option<Size> {
+    match bits & 3 {
+        0 => Some(Size::B),
+        1 => Some(Size::W),
+        2 => Some(Size::L),
+        _ => None,
+    }
+}
+
+/// Returns the mnemonic suffix of the given condition code.
+fn condition(code: u16) -> &'static str {
+    CONDITIONS.iter().find(|c| c.1 == code).map_or("", |c| c.0)
+}
+
+/// Returns the text that adds a displacement to a register.
+fn offset(disp: i64) -> String {
+    match disp < 0 {
+        true  => format!(" - {}", number(-disp)),
+        false => format!(" + {}", number(disp)),
+    }
+}
+
+/// Returns the compare register, update register, and address register of
+/// a `cas2` extension word.
+fn cas2(ext: u16) -> Option<(u16, u16, usize)> {
+    match ext & 0x8E38 {
+        0x8000 => Some((ext & 7, ext >> 6 & 7, (ext >> 12) as usize)),
+        _      => None,
+    }
+}
+
+/// Returns the text of a register list, with bit 0 for `d0` and bit 15 for
+/// `a7`.
+fn list(mask: u16) -> String {
+    let name  = |r: usize| format!("{}{}", if r < 8 { 'd' } else { 'a' }, r & 7);
+    let mut items = vec![];
+    let mut r = 0;
+
+    while r < 16 {
+        if mask & 1 << r == 0 {
+            r += 1;
+            continue;
+        }
+        let first = r;
+        while r + 1 < 16 && r + 1 != 8 && mask & 1 << (r + 1) != 0 {
+            r += 1;
+        }
+        items.push(match r > first {
+            true  => format!("{}-{}", name(first), name(r)),
+            false => name(first),
+        });
+        r += 1;
+    }
+
+    items.join("/")
+}
@@ -0,0 +1,1121 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{pc_rel_expr, pc_rel_value, Emitter, Value};
+
+use super::*;
+use super::operand::*;
+
+// ----------------------------------------------------------------------------
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.
+pub fn encode(
+    isa:    u32,
+    entry:  &Entry,
+    suffix: Option<Size>,
+    stmt:   &Dir<Span>,
+    out:    &mut dyn Emitter,
+) {
+    let mut size = suffix.or(entry.dflt).unwrap_or(Size::W);
+
+    // ColdFire permits fewer sizes
+    if isa & COLDFIRE != 0 && entry.sizes != 0 {
+        let sizes = entry.cf | if isa & CF_ISA_B != 0 { entry.cf_b } else { 0 };
+
+        match suffix {
+            Some(s) if sizes & s.bit() == 0 => {
+                let msg = format!("size {} not supported on ColdFire", s.suffix());
+                return out.error(stmt.data, &msg);
+            },
+            None if sizes & size.bit() == 0 => {
+                size = Size::L;
+            },
+            _ => (),
+        }
+    }
+
+    let mut e = Encoder {
+        p:    Parser { out, isa },
+        entry,
+        suffix,
+        size,
+        span: stmt.data,
+    };
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| e.p.parse(arg))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    if entry.form == Form::Branch {
+        return e.branch(&ops);
+    }
+
+    if let Some(insn) = e.encode(&ops) {
+        e.emit(insn);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Encoded instruction: an opcode word and its extensions.
+struct Insn {
+    op:  u16,
+    ext: Vec<Ext>,
+}
+
+impl Insn {
+    fn new(op: u16) -> Self {
+        Self { op, ext: vec![] }
+    }
+
+    /// Appends the given extension word.
+    fn word(mut self, word: u16) -> Self {
+        self.ext.push(Ext::Word(word));
+        self
+    }
+
+    /// Merges the mode and register fields of the given effective address
+    /// into the opcode word and appends its extensions.
+    fn ea(mut self, ea: Ea) -> Self {
+        self.op |= ea.field;
+        self.ext.extend(ea.ext);
+        self
+    }
+
+    /// Appends the extensions of the given effective address without merging
+    /// its fields into the opcode word.
+    fn ext(mut self, ea: Ea) -> Self {
+        self.ext.extend(ea.ext);
+        self
+    }
+}
+
+struct Encoder<'a, 'e> {
+    p:      Parser<'a>,
+    entry:  &'e Entry,
+    suffix: Option<Size>,
+    size:   Size,
+    span:   Span,
+}
+
+impl Encoder<'_, '_> {
+    fn encode(&mut self, ops: &[Operand]) -> Option<Insn> {
+        use Form::*;
+
+        let op = self.entry.op;
+
+        match (self.entry.form, ops) {
+            (Inherent, []) => Some(Insn::new(op)),
+
+            (Ea(mask), [dst]) => {
+                let dst = self.ea(dst, mask)?;
+                if matches!(op & 0xFF00, 0x4000 | 0x4400 | 0x4600) || op & 0xF0C0 == 0x50C0 {
+                    // neg, negx, not, and scc
+                    self.cf_restrict(&dst, DN)?;
+                }
+                Some(Insn::new(op | self.sf()).ea(dst))
+            },
+
+            (Tst, [src]) => {
+                let mask = if self.has(M68020) { ALL } else { DATA_ALT };
+                let src  = self.ea(src, mask)?;
+                self.check_byte_areg(&src)?;
+                Some(Insn::new(op | self.sf()).ea(src))
+            },
+
+            (Arith(flags), [src, dst]) => self.arith(flags, src, dst),
+            (Addr,         [src, dst]) => self.addr(src, dst),
+            (Imm(flags),   [src, dst]) => self.imm_op(flags, src, dst),
+
+            (Quick, [src, dst]) => {
+                let n   = self.constant(src, 1, 8)? as u16;
+                let dst = self.ea(dst, ALT)?;
+                self.check_byte_areg(&dst)?;
+                Some(Insn::new(op | (n & 7) << 9 | self.sf()).ea(dst))
+            },
+
+            (Moveq, [src, Operand::Ea(dst)]) if dst.mode == DN => {
+                let v = self.constant(src, -128, 255)?;
+                Some(Insn::new(op | dst.reg() << 9 | v as u16 & 0xFF))
+            },
+
+            (Move,  [src, dst]) => self.move_(src, dst),
+            (Movea, [src, dst]) => self.movea(src, dst),
+            (Shift, ops)        => self.shift(ops),
+            (Bit,   [src, dst]) => self.bit(src, dst),
+
+            (Dbcc, [Operand::Ea(reg), Operand::Imm(target)]) if reg.mode == DN => {
+                let value = self.p.eval(target);
+                Some(Insn::new(op | reg.reg()).pc_rel(target, value, DISP16))
+            },
+
+            (Trapcc, []) if self.suffix.is_none() => {
+                Some(Insn::new(op | 4))
+            },
+            (Trapcc, [src]) if self.suffix.is_some() => {
+                let src = self.ea(src, IMM)?;
+                let opmode = if self.size == Size::L { 3 } else { 2 };
+                Some(Insn::new(op | opmode).ext(src))
+            },
+
+            (Lea, [src, Operand::Ea(dst)]) if dst.mode == AN => {
+                let src = self.ea(src, CTRL)?;
+                Some(Insn::new(op | dst.reg() << 9).ea(src))
+            },
+
+            (Link, [Operand::Ea(reg), Operand::Imm(disp)]) if reg.mode == AN => {
+                let value = self.p.eval(disp);
+                if self.size == Size::L {
+                    self.require(M68020)?;
+                    let insn = Insn::new(0x4808 | reg.reg());
+                    return Some(insn.value(disp, value, RelocKind::INT32));
+                }
+                Some(Insn::new(op | reg.reg()).value(disp, value, DISP16))
+            },
+
+            (AReg, [Operand::Ea(reg)]) if reg.mode == AN => {
+                Some(Insn::new(op | reg.reg()))
+            },
+
+            (DReg, [Operand::Ea(reg)]) if reg.mode == DN => {
+                Some(Insn::new(op | self.sf() | reg.reg()))
+            },
+
+            (Trap, [src]) => {
+                let n = self.constant(src, 0, 15)? as u16;
+                Some(Insn::new(op | n))
+            },
+
+            (ImmWord, [src]) => {
+                let src = self.ea(src, IMM)?;
+                Some(Insn::new(op).ext(src))
+            },
+
+            (Bkpt, [src]) => {
+                let n = self.constant(src, 0, 7)? as u16;
+                Some(Insn::new(op | n))
+            },
+
+            (Exg, [x, y]) => {
+                let (x, y) = match (x.general(), y.general()) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => return self.fail(self.span, "expected: data or address registers"),
+                };
+                let op = match (x < 8, y < 8) {
+                    (true,  true ) => 0xC140 | x << 9 | y,
+                    (false, false) => 0xC148 | (x & 7) << 9 | (y & 7),
+                    (true,  false) => 0xC188 | x << 9 | (y & 7),
+                    (false, true ) => 0xC188 | y << 9 | (x & 7),
+                };
+                Some(Insn::new(op))
+            },
+
+            (MulDiv, [src, dst]) => self.mul_div(src, dst),
+            (DivL,   [src, dst]) => self.div_long(src, dst),
+
+            (Chk, [src, Operand::Ea(dst)]) if dst.mode == DN => {
+                if self.size == Size::L {
+                    self.require(M68020)?;
+                }
+                let src = self.ea(src, DATA)?;
+                Some(Insn::new(op | dst.reg() << 9 | self.sf()).ea(src))
+            },
+
+            (Extended, [Operand::Ea(src), Operand::Ea(dst)]) => {
+                let rm = match (src.mode, dst.mode) {
+                    (DN,     DN    ) => 0,
+                    (PREDEC, PREDEC) => 8,
+                    _                => return self.fail(src.span, "invalid addressing mode"),
+                };
+                Some(Insn::new(op | dst.reg() << 9 | self.sf() | rm | src.reg()))
+            },
+
+            (Cmpm, [Operand::Ea(src), Operand::Ea(dst)])
+                if src.mode == POSTINC && dst.mode == POSTINC =>
+            {
+                Some(Insn::new(op | dst.reg() << 9 | self.sf() | src.reg()))
+            },
+
+            (Movem,  [src, dst]) => self.movem(src, dst),
+            (Movep,  [src, dst]) => self.movep(src, dst),
+            (Movec,  [src, dst]) => self.movec(src, dst),
+            (Moves,  [src, dst]) => self.moves(src, dst),
+
+            (Bitfield(mask), ops) => self.bitfield(mask, ops),
+
+            (Cas, [Operand::Ea(dc), Operand::Ea(du), dst])
+                if dc.mode == DN && du.mode == DN =>
+            {
+                let dst = self.ea(dst, MEM_ALT)?;
+                let ext = du.reg() << 6 | dc.reg();
+                Some(Insn::new(op | self.sf()).word(ext).ea(dst))
+            },
+
+            (Cas2, [Operand::Pair(dc1, dc2, _), Operand::Pair(du1, du2, _), Operand::Pair(rn1, rn2, _)]) => {
+                let ext1 = self.cas2_word(dc1, du1, rn1)?;
+                let ext2 = self.cas2_word(dc2, du2, rn2)?;
+                Some(Insn::new(op | self.sf()).word(ext1).word(ext2))
+            },
+
+            (Chk2, [src, dst]) => {
+                let reg = match dst.general() {
+                    Some(reg) => reg,
+                    None      => return self.fail(dst.span(), "expected: data or address register"),
+                };
+                let src = self.ea(src, CTRL)?;
+                Some(Insn::new(op | self.sf()).word(reg << 12 | self.entry.op2).ea(src))
+            },
+
+            (Mov3q, [src, dst]) => {
+                let n = match self.constant(src, -1, 7)? {
+                    0  => return self.fail(src.span(), "value 0 out of range -1, 1..7"),
+                    -1 => 0,
+                    n  => n as u16,
+                };
+                let dst = self.ea(dst, ALT)?;
+                Some(Insn::new(op | n << 9).ea(dst))
+            },
+
+            (Mvx, [src, Operand::Ea(dst)]) if dst.mode == DN => {
+                if self.suffix.is_none() {
+                    return self.fail(self.span, "size suffix required");
+                }
+                let src = self.ea(src, ALL)?;
+                self.check_byte_areg(&src)?;
+                let w = ((self.size == Size::W) as u16) << 6;
+                Some(Insn::new(op | dst.reg() << 9 | w).ea(src))
+            },
+
+            (Mac, ops) => self.mac(ops),
+
+            (Movclr, [Operand::Special(Reg::Acc(n), _), dst]) => {
+                let reg = match dst.general() {
+                    Some(reg) => reg,
+                    None      => return self.fail(dst.span(), "expected: data or address register"),
+                };
+                Some(Insn::new(op | (*n as u16) << 9 | reg))
+            },
+
+            (Pack, [Operand::Ea(src), Operand::Ea(dst), adj]) => {
+                let rm = match (src.mode, dst.mode) {
+                    (DN,     DN    ) => 0,
+                    (PREDEC, PREDEC) => 8,
+                    _                => return self.fail(src.span, "invalid addressing mode"),
+                };
+                let adj = self.imm(adj, Size::W)?;
+                Some(Insn::new(op | dst.reg() << 9 | rm | src.reg()).ext(adj))
+            },
+
+            _ => self.fail(self.span, "invalid operands"),
+        }
+    }
+
+    // === Arithmetic and Logic ===
+
+    fn arith(&mut self, flags: u8, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let op = self.entry.op;
+
+        // Immediate forms
+        if let Operand::Special(reg @ (Reg::Ccr | Reg::Sr), _) = *dst {
+            if flags & ARITH_CCR != 0 {
+                return self.ccr_sr(src, reg);
+            }
+        }
+
+        if matches!(*dst, Operand::Ea(ref ea) if ea.mode == AN) && flags & ARITH_ADDR != 0 {
+            return self.addr(src, dst);
+        }
+
+        if let Operand::Imm(_) = *src {
+            return self.imm_op(flags, src, dst);
+        }
+
+        // <ea>, Dn
+        if let Some(n) = dst.dreg() {
+            if flags & ARITH_FROM_EA != 0 {
+                let mask = if flags & ARITH_ADDR != 0 { ALL } else { DATA };
+                let src  = self.ea(src, mask)?;
+                self.check_byte_areg(&src)?;
+                return Some(Insn::new(op | n << 9 | self.sf()).ea(src));
+            }
+        }
+
+        // Dn, <ea>
+        if let Some(n) = src.dreg() {
+            if flags & ARITH_TO_EA != 0 {
+                let mask = if flags & ARITH_FROM_EA != 0 { MEM_ALT } else { DATA_ALT };
+                let dst  = self.ea(dst, mask)?;
+                return Some(Insn::new(op | n << 9 | 0x0100 | self.sf()).ea(dst));
+            }
+        }
+
+        self.fail(self.span, "invalid addressing mode")
+    }
+
+    fn addr(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let n = match dst.areg() {
+            Some(n) => n,
+            None    => return self.fail(dst.span(), "expected: address register"),
+        };
+
+        let size = match self.size {
+            Size::L => Size::L,
+            Size::W => Size::W,
+            _       => return self.fail(self.span, "operation on address register must be word or long size"),
+        };
+
+        let src = self.ea(src, ALL)?;
+        Some(Insn::new(self.entry.op | n << 9 | Sf::Addr.bits(size)).ea(src))
+    }
+
+    fn imm_op(&mut self, flags: u8, src: &Operand, dst: &Operand) -> Option<Insn> {
+        if let Operand::Special(reg @ (Reg::Ccr | Reg::Sr), _) = *dst {
+            if flags & ARITH_CCR != 0 {
+                return self.ccr_sr(src, reg);
+            }
+        }
+
+        let op = match self.entry.form {
+            Form::Arith(_) => self.entry.op2,
+            _              => self.entry.op,
+        };
+
+        let mask = match flags & ARITH_CMP != 0 && self.has(M68020) {
+            true  => DATA & !IMM,
+            false => DATA_ALT,
+        };
+
+        let src = self.ea(src, IMM)?;
+        let dst = self.ea(dst, mask)?;
+        self.cf_restrict(&dst, DN)?;
+        Some(Insn::new(op | self.sf()).ext(src).ea(dst))
+    }
+
+    /// Encodes an immediate operation on the condition code or status
+    /// register.
+    fn ccr_sr(&mut self, src: &Operand, reg: Reg) -> Option<Insn> {
+        let op = match self.entry.form {
+            Form::Arith(_) => self.entry.op2,
+            _              => self.entry.op,
+        };
+
+        let (size, field) = match reg {
+            Reg::Ccr => (Size::B, 0x3C),
+            _        => (Size::W, 0x7C),
+        };
+
+        if self.suffix.is_some_and(|s| s != size) {
+            return self.fail(self.span, "invalid size for this register");
+        }
+
+        let src = self.imm(src, size)?;
+        Some(Insn::new(op | field).ext(src))
+    }
+
+    fn mul_div(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let divide = self.entry.op2 & 1 != 0;
+        let signed = self.entry.op2 & 2 != 0;
+
+        if self.size == Size::W {
+            let n = match dst.dreg() {
+                Some(n) => n,
+                None    => return self.fail(dst.span(), "expected: data register"),
+            };
+            let src = self.ea(src, DATA)?;
+            return Some(Insn::new(self.entry.op | n << 9).ea(src));
+        }
+
+        self.require(M68020 | COLDFIRE)?;
+
+        if self.p.coldfire() && matches!(*dst, Operand::Pair(..)) {
+            return self.fail(dst.span(), "64-bit multiply and divide not supported on ColdFire");
+        }
+
+        // Dn for 32-bit result; Dh:Dl or Dr:Dq for 64-bit
+        let (hi, lo, wide) = match *dst {
+            Operand::Pair(ref hi, ref lo, _) => match (hi.dreg(), lo.dreg()) {
+                (Some(hi), Some(lo)) => (hi, lo, 0x0400),
+                _ => return self.fail(dst.span(), "expected: data register pair"),
+            },
+            _ => match dst.dreg() {
+                Some(n) if divide => (n, n, 0),
+                Some(n)           => (0, n, 0),
+                None              => return self.fail(dst.span(), "expected: data register"),
+            },
+        };
+
+        let op  = if divide { 0x4C40 } else { 0x4C00 };
+        let ext = lo << 12 | (signed as u16) << 11 | wide | hi;
+        let src = self.ea(src, DATA)?;
+        self.cf_restrict(&src, DN | IND | POSTINC | PREDEC | DISP)?;
+        Some(Insn::new(op).word(ext).ea(src))
+    }
+
+    fn div_long(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let signed = self.entry.op2 != 0;
+
+        let (r, q) = match *dst {
+            Operand::Pair(ref r, ref q, _) => match (r.dreg(), q.dreg()) {
+                (Some(r), Some(q)) => (r, q),
+                _ => return self.fail(dst.span(), "expected: data register pair"),
+            },
+            _ => match dst.dreg() {
+                Some(n) => (n, n),
+                None    => return self.fail(dst.span(), "expected: data register"),
+            },
+        };
+
+        let ext = q << 12 | (signed as u16) << 11 | r;
+        let src = self.ea(src, DATA)?;
+        self.cf_restrict(&src, DN | IND | POSTINC | PREDEC | DISP)?;
+        Some(Insn::new(self.entry.op).word(ext).ea(src))
+    }
+
+    // === Data Movement ===
+
+    fn move_(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        match (src, dst) {
+            (&Operand::Special(Reg::Macsr, _), &Operand::Special(Reg::Ccr, _)) => {
+                Some(Insn::new(0xA9C0))
+            },
+            (src, &Operand::Special(reg @ (Reg::Acc(_) | Reg::Macsr | Reg::Mask), _)) => {
+                self.check_size(Size::L)?;
+                let src = self.with_size(Size::L, |e| e.ea(src, DN | AN | IMM))?;
+                Some(Insn::new(0xA100 | mac_reg(reg)).ea(src))
+            },
+            (&Operand::Special(reg @ (Reg::Acc(_) | Reg::Macsr | Reg::Mask), _), dst) => {
+                self.check_size(Size::L)?;
+                let reg2 = match dst.general() {
+                    Some(reg) => reg,
+                    None      => return self.fail(dst.span(), "expected: data or address register"),
+                };
+                Some(Insn::new(0xA180 | mac_reg(reg) | reg2))
+            },
+            (&Operand::Special(Reg::Sr, _), dst) => {
+                self.check_size(Size::W)?;
+                let dst = self.ea(dst, DATA_ALT)?;
+                Some(Insn::new(0x40C0).ea(dst))
+            },
+            (&Operand::Special(Reg::Ccr, _), dst) => {
+                self.require(M68010)?;
+                self.check_size(Size::W)?;
+                let dst = self.ea(dst, DATA_ALT)?;
+                Some(Insn::new(0x42C0).ea(dst))
+            },
+            (src, &Operand::Special(Reg::Ccr, _)) => {
+                self.check_size(Size::W)?;
+                let src = self.with_size(Size::W, |e| e.ea(src, DATA))?;
+                Some(Insn::new(0x44C0).ea(src))
+            },
+            (src, &Operand::Special(Reg::Sr, _)) => {
+                self.check_size(Size::W)?;
+                let src = self.with_size(Size::W, |e| e.ea(src, DATA))?;
+                Some(Insn::new(0x46C0).ea(src))
+            },
+            (&Operand::Special(Reg::Usp, _), dst) => {
+                let n = match dst.areg() {
+                    Some(n) => n,
+                    None    => return self.fail(dst.span(), "expected: address register"),
+                };
+                Some(Insn::new(0x4E68 | n))
+            },
+            (src, &Operand::Special(Reg::Usp, _)) => {
+                let n = match src.areg() {
+                    Some(n) => n,
+                    None    => return self.fail(src.span(), "expected: address register"),
+                };
+                Some(Insn::new(0x4E60 | n))
+            },
+            (src, dst) if dst.areg().is_some() => {
+                self.movea(src, dst)
+            },
+            (src, dst) => {
+                let src = self.ea(src, ALL)?;
+                self.check_byte_areg(&src)?;
+                let dst = self.ea(dst, DATA_ALT)?;
+                self.cf_restrict(&dst, self.cf_move_modes(&src))?;
+                let op  = self.sf() | (dst.field & 7) << 9 | (dst.field >> 3) << 6;
+                Some(Insn::new(op).ea(src).ext(dst))
+            },
+        }
+    }
+
+    fn movea(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let n = match dst.areg() {
+            Some(n) => n,
+            None    => return self.fail(dst.span(), "expected: address register"),
+        };
+
+        if self.size == Size::B {
+            return self.fail(self.span, "operation on address register must be word or long size");
+        }
+
+        let src = self.ea(src, ALL)?;
+        Some(Insn::new(0x0040 | Sf::Move.bits(self.size) | n << 9).ea(src))
+    }
+
+    fn movem(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let op = self.entry.op | self.sf();
+
+        if let Some(mask) = list(dst) {
+            // Memory to registers
+            let src = self.ea(src, CTRL | POSTINC)?;
+            self.cf_restrict(&src, IND | DISP)?;
+            return Some(Insn::new(op | 0x0400).word(mask).ea(src));
+        }
+
+        let mask = match list(src) {
+            Some(mask) => mask,
+            None       => return self.fail(src.span(), "expected: register list"),
+        };
+
+        // Registers to memory
+        let dst  = self.ea(dst, CTRL_ALT | PREDEC)?;
+        self.cf_restrict(&dst, IND | DISP)?;
+        let mask = if dst.mode == PREDEC { mask.reverse_bits() } else { mask };
+        Some(Insn::new(op).word(mask).ea(dst))
+    }
+
+    fn movep(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let long = (self.size == Size::L) as u16;
+
+        let (n, mem, opmode) = match (src.dreg(), dst.dreg()) {
+            (Some(n), _) => (n, dst, 6 | long),
+            (_, Some(n)) => (n, src, 4 | long),
+            _            => return self.fail(self.span, "expected: data register"),
+        };
+
+        let mem = self.ea(mem, IND | DISP)?;
+        let insn = Insn::new(self.entry.op | n << 9 | opmode << 6 | mem.reg());
+
+        match mem.mode {
+            IND => Some(insn.word(0)),
+            _   => Some(insn.ext(mem)),
+        }
+    }
+
+    fn movec(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let (ctrl, reg, op) = match (src, dst) {
+            (&Operand::Special(ctrl, span), reg) => ((ctrl, span), reg, 0x4E7A),
+            (reg, &Operand::Special(ctrl, span)) => ((ctrl, span), reg, 0x4E7B),
+            _ => return self.fail(self.span, "expected: control register"),
+        };
+
+        let code = match ctrl.0 {
+            Reg::Usp     => 0x800,
+            Reg::Ctrl(c) => c,
+            _            => return self.fail(ctrl.1, "expected: control register"),
+        };
+
+        if matches!(code, 0x002 | 0x802..=0x804) && !self.has(M68020 | COLDFIRE) {
+            return self.fail(ctrl.1, "control register requires 68020 or later");
+        }
+
+        let reg = match reg.general() {
+            Some(reg) => reg,
+            None      => return self.fail(reg.span(), "expected: data or address register"),
+        };
+
+        Some(Insn::new(op).word(reg << 12 | code))
+    }
+
+    fn moves(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let op = self.entry.op | self.sf();
+
+        if let Some(reg) = src.general() {
+            let dst = self.ea(dst, MEM_ALT)?;
+            return Some(Insn::new(op).word(reg << 12 | 0x0800).ea(dst));
+        }
+
+        let reg = match dst.general() {
+            Some(reg) => reg,
+            None      => return self.fail(dst.span(), "expected: data or address register"),
+        };
+
+        let src = self.ea(src, MEM_ALT)?;
+        Some(Insn::new(op).word(reg << 12).ea(src))
+    }
+
+    // === Shifts and Bits ===
+
+    fn shift(&mut self, ops: &[Operand]) -> Option<Insn> {
+        let kind = self.entry.op2 >> 1;
+        let dir  = self.entry.op2 & 1;
+
+        match ops {
+            [Operand::Imm(_), Operand::Ea(dst)] if dst.mode == DN => {
+                let n  = self.constant(&ops[0], 1, 8)? as u16;
+                let op = 0xE000 | (n & 7) << 9 | dir << 8 | self.sf() | kind << 3 | dst.reg();
+                Some(Insn::new(op))
+            },
+            [Operand::Ea(src), Operand::Ea(dst)] if src.mode == DN && dst.mode == DN => {
+                let op = 0xE020 | src.reg() << 9 | dir << 8 | self.sf() | kind << 3 | dst.reg();
+                Some(Insn::new(op))
+            },
+            [dst] => {
+                if self.p.coldfire() {
+                    return self.fail(self.span, "memory shift not supported on ColdFire");
+                }
+                if self.suffix.is_some_and(|s| s != Size::W) {
+                    return self.fail(self.span, "memory shift must be word size");
+                }
+                let dst = self.ea(dst, MEM_ALT)?;
+                Some(Insn::new(0xE0C0 | kind << 9 | dir << 8).ea(dst))
+            },
+            _ => self.fail(self.span, "invalid operands"),
+        }
+    }
+
+    fn bit(&mut self, src: &Operand, dst: &Operand) -> Option<Insn> {
+        let kind = self.entry.op2;
+
+        let mask = match (kind, src) {
+            (0, Operand::Imm(_)) => DATA & !IMM,
+            (0, _)               => DATA,
+            _                    => DATA_ALT,
+        };
+
+        let dst = self.ea(dst, mask)?;
+
+        let size = if dst.mode == DN { Size::L } else { Size::B };
+        if self.suffix.is_some_and(|s| s != size) {
+            return match size {
+                Size::L => self.fail(self.span, "bit operation on data register must be long size"),
+                _       => self.fail(self.span, "bit operation on memory must be byte size"),
+            };
+        }
+
+        match *src {
+            Operand::Imm(_) => {
+                self.cf_restrict(&dst, DN | IND | POSTINC | PREDEC | DISP)?;
+                let src = self.imm(src, Size::B)?;
+                Some(Insn::new(0x0800 | kind << 6).ext(src).ea(dst))
+            },
+            _ => {
+                let n = match src.dreg() {
+                    Some(n) => n,
+                    None    => return self.fail(src.span(), "expected: data register or immediate"),
+                };
+                Some(Insn::new(0x0100 | n << 9 | kind << 6).ea(dst))
+            },
+        }
+    }
+
+    fn bitfield(&mut self, mask: u16, ops: &[Operand]) -> Option<Insn> {
+        let (reg, ea, offset, width) = match (self.entry.op2, ops) {
+            (0, [ea, offset, width])      => (0,            ea, offset, width),
+            (1, [ea, offset, width, reg]) => (self.bf_reg(reg)?, ea, offset, width),
+            (2, [reg, ea, offset, width]) => (self.bf_reg(reg)?, ea, offset, width),
+            _ => return self.fail(self.span, "invalid operands"),
+        };
+
+        let offset = match offset.dreg() {
+            Some(n) => 0x0800 | n << 6,
+            None    => (self.constant(offset, 0, 31)? as u16) << 6,
+        };
+
+        let width = match width.dreg() {
+            Some(n) => 0x0020 | n,
+            None    => self.constant(width, 1, 32)? as u16 & 31,
+        };
+
+        let ea = self.ea(ea, mask)?;
+        Some(Insn::new(self.entry.op).word(reg << 12 | offset | width).ea(ea))
+    }
+
+    fn bf_reg(&mut self, reg: &Operand) -> Option<u16> {
+        match reg.dreg() {
+            Some(n) => Some(n),
+            None    => self.fail(reg.span(), "expected: data register"),
+        }
+    }
+
+    fn cas2_word(&mut self, dc: &Operand, du: &Operand, rn: &Operand) -> Option<u16> {
+        let (dc, du) = match (dc.dreg(), du.dreg()) {
+            (Some(dc), Some(du)) => (dc, du),
+            _ => return self.fail(self.span, "expected: data register pair"),
+        };
+
+        let rn = match *rn {
+            Operand::Ea(ref ea) if ea.mode == IND => 8 | ea.reg(),
+            _ => return self.fail(rn.span(), "expected: register indirect pair"),
+        };
+
+        Some(rn << 12 | du << 6 | dc)
+    }
+
+    // === Multiply-Accumulate ===
+
+    fn mac(&mut self, ops: &[Operand]) -> Option<Insn> {
+        let (ry, rx, acc) = match ops {
+            [ry, rx]      => (ry, rx, None),
+            [ry, rx, acc] => (ry, rx, Some(acc)),
+            _             => return self.fail(self.span, "invalid operands"),
+        };
+
+        let (ry, uy) = self.mac_operand(ry)?;
+
+        // Shift of the product, written as a shift of Rx
+        let (rx, sf) = match *rx {
+            Operand::Imm(Expr::Binary(_, op @ (BinOp::Shl | BinOp::Shr), ref rx, ref n)) => {
+                if self.p.eval(n) != Value::Const(1) {
+                    return self.fail(*n.data(), "product shift must be 1");
+                }
+                let sf = if op == BinOp::Shl { 1 } else { 3 };
+                let rx = self.p.parse(&Arg::Expr((**rx).clone()))?;
+                (rx, sf)
+            },
+            _ => (rx.clone(), 0),
+        };
+
+        let (rx, ux) = self.mac_operand(&rx)?;
+
+        let acc = match acc {
+            None                                  => 0,
+            Some(&Operand::Special(Reg::Acc(n), _)) => n as u16,
+            Some(acc) => return self.fail(acc.span(), "expected: accumulator"),
+        };
+
+        if self.size == Size::L && (ux || uy) {
+            return self.fail(self.span, "register halves apply only to word operations");
+        }
+
+        let op  = 0xA000 | (rx & 7) << 9 | (acc & 1) << 7 | (rx >> 3) << 6 | (ry >> 3) << 3 | ry & 7;
+        let ext = ((self.size == Size::L) as u16) << 11
+                | sf << 9
+                | self.entry.op2 << 8
+                | (ux as u16) << 7
+                | (uy as u16) << 6
+                | (acc >> 1) << 4;
+
+        Some(Insn::new(op).word(ext))
+    }
+
+    /// Parses a multiply-accumulate source register with an optional `.u`
+    /// (upper half) or `.l` (lower half) suffix.  Returns the general-purpose
+    /// register number and whether the upper half is selected.
+    fn mac_operand(&mut self, op: &Operand) -> Option<(u16, bool)> {
+        if let Some(reg) = op.general() {
+            return Some((reg, false));
+        }
+
+        if let Operand::Imm(Expr::Ident(_, name)) = *op {
+            let name = &self.p.out.names()[name];
+            let reg  = match name.rsplit_once('.') {
+                Some((reg, "u" | "U")) => Reg::parse(reg).map(|r| (r, true)),
+                Some((reg, "l" | "L")) => Reg::parse(reg).map(|r| (r, false)),
+                _                      => None,
+            };
+            if let Some((reg, upper)) = reg {
+                if let Some(reg) = reg.general() {
+                    return Some((reg, upper));
+                }
+            }
+        }
+
+        self.fail(op.span(), "expected: data or address register")
+    }
+
+    // === Branches ===
+
+    fn branch(&mut self, ops: &[Operand]) {
+        let target = match ops {
+            [Operand::Imm(target)] => target,
+            _ => return self.p.out.error(self.span, "expected: branch target"),
+        };
+
+        let op    = self.entry.op;
+        let value = self.p.eval(target);
+        let disp  = self.pc_rel_value(value, 2);
+
+        let size = match self.suffix {
+            Some(Size::S | Size::B) => Size::S,
+            Some(size)              => size,
+            None                    => {
+                const FORMS: [Size; 3] = [Size::S, Size::W, Size::L];
+                let count = match self.has(M68020 | CF_ISA_B) {
+                    true  => 3,
+                    false => 2,
+                };
+                let fits = |form| match (FORMS[form], disp) {
+                    (Size::S, Value::Const(d)) => matches!(d, -128..=-2 | 1..=127),
+                    (Size::W, Value::Const(d)) => is_signed(d, 16),
+                    (Size::W, Value::Reloc(_)) => true,
+                    (Size::L, _)               => true,
+                    _                          => false,
+                };
+                FORMS[self.p.out.relax(count, &fits)]
+            },
+        };
+
+        let insn = match size {
+            Size::S => {
+                let out = &mut *self.p.out;
+                let field = match disp {
+                    Value::Const(d) => match (BRANCH8.apply)(d, op as u64) {
+                        Ok(field) => field as u16,
+                        Err(msg)  => { out.error(*target.data(), &msg); op },
+                    },
+                    Value::Reloc(_) => {
+                        out.reloc(&pc_rel_expr(target, 2), BRANCH8);
+                        op
+                    },
+                };
+                return out.emit(&field.to_be_bytes());
+            },
+            Size::L => {
+                if self.require(M68020 | CF_ISA_B).is_none() {
+                    return;
+                }
+                Insn::new(op | 0xFF).pc_rel(target, value, RelocKind::INT32)
+            },
+            _ => {
+                Insn::new(op).pc_rel(target, value, DISP16)
+            },
+        };
+
+        self.emit(insn)
+    }
+
+    // === Emission ===
+
+    fn emit(&mut self, insn: Insn) {
+        self.p.out.emit(&insn.op.to_be_bytes());
+
+        let mut pos = 2;
+
+        for ext in insn.ext {
+            pos += match ext {
+                Ext::Word(word) => {
+                    self.p.out.emit(&word.to_be_bytes());
+                    2
+                },
+                Ext::Value { expr, value, kind, high } => {
+                    self.put(&expr, value, kind, high);
+                    kind.size
+                },
+                Ext::PcRel { expr, value, kind, high, adjust } => {
+                    let offset = (pos as i64 + adjust) as u64;
+                    let value  = self.pc_rel_value(value, offset);
+                    self.put(&pc_rel_expr(&expr, offset as i64), value, kind, high);
+                    kind.size
+                },
+            };
+        }
+    }
+
+    /// Emits a field of the given `kind` holding the given value, or records
+    /// a relocation if the linker must compute the value.
+    fn put(&mut self, expr: &Expr<Span>, value: Value, kind: RelocKind, high: u16) {
+        let out   = &mut *self.p.out;
+        let field = match value {
+            Value::Const(v) => match (kind.apply)(v, high as u64) {
+                Ok(field) => field,
+                Err(msg)  => { out.error(*expr.data(), &msg); high as u64 },
+            },
+            Value::Reloc(_) => {
+                out.reloc(expr, kind);
+                high as u64
+            },
+        };
+        out.emit(&field.to_be_bytes()[8 - kind.size..]);
+    }
+
+    /// Returns the displacement from the program counter, which is `offset`
+    /// bytes past the start of the current instruction, to the given target
+    /// value.
+    fn pc_rel_value(&mut self, target: Value, offset: u64) -> Value {
+        pc_rel_value(self.p.out, self.span, target, offset as i64)
+    }
+
+    // === Operands ===
+
+    /// Converts the given operand to an effective address, requiring one of
+    /// the addressing modes in `mask`.
+    fn ea(&mut self, op: &Operand, mask: u16) -> Option<Ea> {
+        let ea = match *op {
+            Operand::Ea(ref ea)              => ea.clone(),
+            Operand::Imm(_) if mask & IMM != 0 => return self.imm(op, self.size),
+            _                                => return self.fail(op.span(), "invalid addressing mode"),
+        };
+
+        if ea.mode & mask == 0 {
+            return self.fail(ea.span, "invalid addressing mode");
+        }
+
+        Some(ea)
+    }
+
+    /// Converts the given operand to an immediate effective address of the
+    /// given size.
+    fn imm(&mut self, op: &Operand, size: Size) -> Option<Ea> {
+        let expr = match *op {
+            Operand::Imm(ref expr) => expr,
+            _ => return self.fail(op.span(), "expected: immediate value"),
+        };
+
+        let kind = match size {
+            Size::B => BYTE,
+            Size::L => RelocKind::INT32,
+            _       => RelocKind::INT16,
+        };
+
+        let value = self.p.eval(expr);
+
+        Some(Ea {
+            mode:  IMM,
+            field: 0x3C,
+            ext:   vec![Ext::Value { expr: expr.clone(), value, kind, high: 0 }],
+            span:  *expr.data(),
+        })
+    }
+
+    /// Evaluates the given operand, which must be a constant immediate value
+    /// in the given range.
+    fn constant(&mut self, op: &Operand, min: i64, max: i64) -> Option<i64> {
+        let expr = match *op {
+            Operand::Imm(ref expr) => expr,
+            _ => return self.fail(op.span(), "expected: immediate value"),
+        };
+
+        match self.p.out.eval(expr)? {
+            Value::Const(v) if (min..=max).contains(&v) => Some(v),
+            Value::Const(v) => self.fail(*expr.data(), &format!(
+                "value {} out of range {}..{}", v, min, max
+            )),
+            Value::Reloc(_) => self.fail(*expr.data(), "expected: constant expression, not relocatable"),
+        }
+    }
+
+    /// Evaluates `f` with the operation size temporarily set to `size`.
+    fn with_size<T>(&mut self, size: Size, f: impl FnOnce(&mut Self) -> T) -> T {
+        let saved = self.size;
+        self.size = size;
+        let result = f(self);
+        self.size = saved;
+        result
+    }
+
+    /// Returns the bits of the size field for the operation size.
+    fn sf(&self) -> u16 {
+        self.entry.sf.bits(self.size)
+    }
+
+    /// Returns whether the target has the given instruction set features.
+    fn has(&self, isa: u32) -> bool {
+        self.p.isa & isa != 0
+    }
+
+    /// Reports an error unless the target has the given instruction set
+    /// features.
+    fn require(&mut self, isa: u32) -> Option<()> {
+        if self.has(isa) {
+            return Some(());
+        }
+        let name = if isa == M68010 { "68010" } else { "68020" };
+        self.fail(self.span, &format!("instruction form requires {} or later", name))
+    }
+
+    /// Reports an error if a size suffix is present and is not `size`.
+    fn check_size(&mut self, size: Size) -> Option<()> {
+        match self.suffix {
+            Some(s) if s != size => self.fail(self.span, "invalid size for this register"),
+            _                    => Some(()),
+        }
+    }
+
+    /// Reports an error if the target is a ColdFire and the given effective
+    /// address uses a mode not in `modes`.
+    fn cf_restrict(&mut self, ea: &Ea, modes: u16) -> Option<()> {
+        match self.p.coldfire() && ea.mode & modes == 0 {
+            true  => self.fail(ea.span, "addressing mode not supported on ColdFire"),
+            false => Some(()),
+        }
+    }
+
+    /// Returns the destination modes that ColdFire permits for `move` from
+    /// the given source.
+    fn cf_move_modes(&self, src: &Ea) -> u16 {
+        const SIMPLE: u16 = DN | AN | IND | POSTINC | PREDEC;
+
+        match src.mode {
+            DISP | PC_DISP => ALL & !(INDEX | ABS_W | ABS_L),
+            IMM if self.has(CF_ISA_B) && self.size != Size::L => SIMPLE | DISP,
+            INDEX | PC_INDEX | ABS_W | ABS_L | IMM => SIMPLE,
+            _ => ALL,
+        }
+    }
+
+    /// Reports an error if the given operand is an address register and the
+    /// operation size is byte.
+    fn check_byte_areg(&mut self, ea: &Ea) -> Option<()> {
+        match ea.mode == AN && self.size == Size::B {
+            true  => self.fail(ea.span, "byte operation on address register"),
+            false => Some(()),
+        }
+    }
+
+    fn fail<T>(&mut self, span: Span, msg: &str) -> Option<T> {
+        self.p.out.error(span, msg);
+        None
+    }
+}
+
+impl Insn {
+    /// Appends a value extension for the given expression.
+    fn value(mut self, expr: &Expr<Span>, value: Value, kind: RelocKind) -> Self {
+        self.ext.push(Ext::Value { expr: expr.clone(), value, kind, high: 0 });
+        self
+    }
+
+    /// Appends a displacement from the extension to the given target.
+    fn pc_rel(mut self, expr: &Expr<Span>, value: Value, kind: RelocKind) -> Self {
+        self.ext.push(Ext::PcRel { expr: expr.clone(), value, kind, high: 0, adjust: 0 });
+        self
+    }
+}
+
+/// Returns the register field of an opcode that moves to or from the given
+/// multiply-accumulate register.
+fn mac_reg(reg: Reg) -> u16 {
+    match reg {
+        Reg::Acc(n) => (n as u16) << 9,
+        Reg::Macsr  => 0x0800,
+        _           => 0x0C00,
+    }
+}
+
+/// Returns the register mask of the given operand, if it is a register list
+/// or a single data or address register.
+fn list(op: &Operand) -> Option<u16> {
+    match *op {
+        Operand::List(mask, _) => Some(mask),
+        _                      => op.general().map(|r| 1 << r),
+    }
+}
@@ -0,0 +1,337 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Motorola 68000 family.
+//!
+//! Instructions use Motorola mnemonics with an optional size suffix (`.b`,
+//! `.w`, `.l`, or `.s` for short branches).  Operands use ras syntax:
+//!
+//! | Mode                          | Motorola            | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | data register direct          | `d0`                | `d0`
+//! | address register direct       | `a0`                | `a0`
+//! | address register indirect     | `(a0)`              | `[a0]`
+//! | postincrement                 | `(a0)+`             | `[a0]!`
+//! | predecrement                  | `-(a0)`             | `[--a0]`
+//! | displacement                  | `8(a0)`             | `[a0 + 8]`
+//! | indexed                       | `8(a0,d1.w*4)`      | `[a0 + d1.w*4 + 8]`
+//! | memory indirect (68020)       | `([8,a0],d1.l,4)`   | `[[a0 + 8] + d1.l + 4]`
+//! | absolute                      | `$1000`             | `[x'1000]`
+//! | program counter relative      | `label(pc)`         | `[pc + label]`
+//! | immediate                     | `#42`               | `42`
+//!
+//! In a program counter relative operand, the expression is the target
+//! address, not the displacement.  An index register without a size suffix
+//! is a word index.  A register list is a sequence of registers and register
+//! ranges separated by `/`, as in `d0-d3/a0`.  A register pair is two
+//! registers joined by `:`, as in `d1:d0`.  A bit field operand is written as
+//! three operands: the effective address, the offset, and the width.
+//!
+//! The ColdFire targets share the instruction table of the 68000 family.  An
+//! entry records the sizes that ColdFire permits, and the encoder rejects the
+//! addressing modes that ColdFire lacks.  Multiply-accumulate instructions
+//! write a shift of the product as a shift of the second register, as in
+//! `mac.w d1.u, d2 << 1, acc1`.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{fits, Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::{Decoded, Emitter, Target};
+
+mod decode;
+mod encode;
+mod operand;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: 68000 instructions.
+pub const M68000: u32 = 1 << 0;
+
+/// Instruction set feature: 68010 additions.
+pub const M68010: u32 = 1 << 1;
+
+/// Instruction set feature: 68020 additions.
+pub const M68020: u32 = 1 << 2;
+
+/// Instruction set feature: ColdFire ISA_A, a subset of the 68000 family
+/// with restricted sizes and addressing modes.
+pub const COLDFIRE: u32 = 1 << 3;
+
+/// Instruction set feature: ColdFire ISA_B additions.
+pub const CF_ISA_B: u32 = 1 << 4;
+
+/// Instruction set feature: ColdFire ISA_C additions.
+pub const CF_ISA_C: u32 = 1 << 5;
+
+/// Instruction set feature: ColdFire multiply-accumulate unit.
+pub const CF_MAC: u32 = 1 << 6;
+
+/// Instruction set feature: ColdFire enhanced multiply-accumulate unit, with
+/// four accumulators.
+pub const CF_EMAC: u32 = 1 << 7;
+
+/// Member of the 68000 family.
+#[derive(Debug)]
+pub struct M68k {
+    name:  &'static str,
+    isa:   u32,
+    table: Vec<Entry>,
+    index: HashMap<Name, usize>,
+}
+
+/// Size suffixes, in order of their index in an instruction number.
+const SUFFIXES: [&str; 5] = ["", ".b", ".w", ".l", ".s"];
+
+impl M68k {
+    /// Creates a new [`M68k`] target with the given `name` and instruction
+    /// set features `isa`, interning its mnemonics in `names`.
+    ///
+    /// The index holds the mnemonics of the whole family, so that one from
+    /// another member reports the features it needs.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        let table = table::build();
+
+        let mut index = HashMap::new();
+
+        for (i, entry) in table.iter().enumerate() {
+            for (s, suffix) in SUFFIXES.iter().enumerate() {
+                if s != 0 && entry.sizes & (1 << (s - 1)) == 0 {
+                    continue;
+                }
+                let lower = format!("{}{}", entry.name, suffix);
+                let upper = lower.to_uppercase();
+                index.insert(names.add(&lower), i * SUFFIXES.len() + s);
+                index.insert(names.add(&upper), i * SUFFIXES.len() + s);
+            }
+        }
+
+        Self { name, isa, table, index }
+    }
+}
+
+impl Target for M68k {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Big
+    }
+
+    fn align(&self) -> u64 {
+        2
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[BYTE, BRANCH8, DISP8, DISP16, ABS16, RelocKind::INT16, RelocKind::INT32]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        operand::Reg::parse(name).map(operand::Reg::key)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        let entry  = &self.table[insn / SUFFIXES.len()];
+        let suffix = match insn % SUFFIXES.len() {
+            1 => Some(Size::B),
+            2 => Some(Size::W),
+            3 => Some(Size::L),
+            4 => Some(Size::S),
+            _ => None,
+        };
+
+        if entry.isa & self.isa == 0 {
+            let msg = format!("instruction {}", requirement(self.isa, entry.isa));
+            return out.error(stmt.data, &msg);
+        }
+
+        encode::encode(self.isa, entry, suffix, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        decode::decode(self.isa, &self.table, bytes, addr, label)
+    }
+}
+
+/// Describes what a target with features `isa` lacks to provide an
+/// instruction available with features `needs`.
+fn requirement(isa: u32, needs: u32) -> &'static str {
+    if isa & COLDFIRE != 0 {
+        if needs & CF_ISA_B != 0 { return "requires ColdFire ISA_B" }
+        if needs & CF_ISA_C != 0 { return "requires ColdFire ISA_C" }
+        if needs & CF_MAC   != 0 { return "requires a ColdFire MAC unit" }
+        if needs & CF_EMAC  != 0 { return "requires a ColdFire EMAC unit" }
+        "not supported on ColdFire"
+    } else {
+        if needs & M68010   != 0 { return "requires 68010 or later" }
+        if needs & M68020   != 0 { return "requires 68020 or later" }
+        "requires ColdFire"
+    }
+}
+
+/// Creates a 68000 target.
+pub fn new_68000(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "m68000", M68000))
+}
+
+/// Creates a 68010 target.
+pub fn new_68010(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "m68010", M68000 | M68010))
+}
+
+/// Creates a 68020 target.
+pub fn new_68020(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "m68020", M68000 | M68010 | M68020))
+}
+
+/// Creates a ColdFire ISA_A target with a MAC unit.
+pub fn new_coldfire_a(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "coldfire-a", COLDFIRE | CF_MAC))
+}
+
+/// Creates a ColdFire ISA_B target with an EMAC unit.
+pub fn new_coldfire_b(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "coldfire-b", COLDFIRE | CF_ISA_B | CF_EMAC))
+}
+
+/// Creates a ColdFire ISA_C target with an EMAC unit.
+pub fn new_coldfire_c(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(M68k::new(names, "coldfire-c", COLDFIRE | CF_ISA_B | CF_ISA_C | CF_EMAC))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Operation sizes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Size {
+    /// Byte.
+    B,
+
+    /// Word.
+    W,
+
+    /// Long word.
+    L,
+
+    /// Short branch.
+    S,
+}
+
+impl Size {
+    /// Returns the size in bytes of an immediate operand of this size.
+    pub fn imm_bytes(self) -> usize {
+        match self {
+            Size::L => 4,
+            _       => 2,
+        }
+    }
+
+    /// Returns the bit of the size in a mask of permitted sizes.
+    pub fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    /// Returns the suffix that selects the size.
+    pub fn suffix(self) -> &'static str {
+        SUFFIXES[self as usize + 1]
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Byte immediate in the low byte of a word.
+pub const BYTE: RelocKind = RelocKind { name: "m68k-byte", size: 2, apply: apply_byte };
+
+/// 8-bit branch displacement in the low byte of the opcode word.
+pub const BRANCH8: RelocKind = RelocKind { name: "m68k-branch8", size: 2, apply: apply_branch8 };
+
+/// 8-bit signed displacement in the low byte of a brief extension word.
+pub const DISP8: RelocKind = RelocKind { name: "m68k-disp8", size: 2, apply: apply_disp8 };
+
+/// 16-bit signed displacement.
+pub const DISP16: RelocKind = RelocKind { name: "m68k-disp16", size: 2, apply: apply_disp16 };
+
+/// 16-bit absolute address, sign-extended to 32 bits by the processor.
+pub const ABS16: RelocKind = RelocKind { name: "m68k-abs16", size: 2, apply: apply_abs16 };
+
+fn apply_byte(value: i64, field: u64) -> Result<u64, String> {
+    match fits(value, 8) {
+        true  => Ok(field & 0xFF00 | value as u64 & 0xFF),
+        false => Err(format!("value {} does not fit in 8 bits", value)),
+    }
+}
+
+fn apply_branch8(value: i64, field: u64) -> Result<u64, String> {
+    match value {
+        -128..=-2 | 1..=127 => Ok(field & 0xFF00 | value as u64 & 0xFF),
+        _ => Err(format!("branch displacement {} out of range for short branch", value)),
+    }
+}
+
+fn apply_disp8(value: i64, field: u64) -> Result<u64, String> {
+    match is_signed(value, 8) {
+        true  => Ok(field & 0xFF00 | value as u64 & 0xFF),
+        false => Err(format!("displacement {} out of range for 8 bits", value)),
+    }
+}
+
+fn apply_disp16(value: i64, _: u64) -> Result<u64, String> {
+    match is_signed(value, 16) {
+        true  => Ok(value as u64),
+        false => Err(format!("displacement {} out of range for 16 bits", value)),
+    }
+}
+
+fn apply_abs16(value: i64, _: u64) -> Result<u64, String> {
+    match is_abs16(value) {
+        true  => Ok(value as u64),
+        false => Err(format!("address {:#X} out of range for absolute short", value)),
+    }
+}
+
+/// Returns whether `value` is representable in `bits` bits as a signed
+/// integer.
+pub fn is_signed(value: i64, bits: u32) -> bool {
+    let min = -1i64 << (bits - 1);
+    (min..=!min).contains(&value)
+}
+
+/// Returns whether `value` is an address reachable by absolute short
+/// addressing.
+pub fn is_abs16(value: i64) -> bool {
+    is_signed(value, 16) || (0xFFFF_8000..=0xFFFF_FFFF).contains(&value)
+}
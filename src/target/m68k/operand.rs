@@ -0,0 +1,819 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Operand parsing.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::name::{Name, NameTable};
+use crate::target::{flatten_sum, here, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+// Addressing-mode categories, as bits of a mask.
+pub const DN:       u16 = 1 <<  0;
+pub const AN:       u16 = 1 <<  1;
+pub const IND:      u16 = 1 <<  2;
+pub const POSTINC:  u16 = 1 <<  3;
+pub const PREDEC:   u16 = 1 <<  4;
+pub const DISP:     u16 = 1 <<  5;
+pub const INDEX:    u16 = 1 <<  6;
+pub const ABS_W:    u16 = 1 <<  7;
+pub const ABS_L:    u16 = 1 <<  8;
+pub const PC_DISP:  u16 = 1 <<  9;
+pub const PC_INDEX: u16 = 1 << 10;
+pub const IMM:      u16 = 1 << 11;
+
+// Addressing-mode classes.
+pub const ALL:      u16 = (1 << 12) - 1;
+pub const DATA:     u16 = ALL  & !AN;
+pub const MEM:      u16 = DATA & !DN;
+pub const ALT:      u16 = ALL  & !(PC_DISP | PC_INDEX | IMM);
+pub const DATA_ALT: u16 = DATA & ALT;
+pub const MEM_ALT:  u16 = MEM  & ALT;
+pub const CTRL:     u16 = IND | DISP | INDEX | ABS_W | ABS_L | PC_DISP | PC_INDEX;
+pub const CTRL_ALT: u16 = CTRL & ALT;
+
+/// Control registers other than `usp`, with their `movec` codes.
+pub const CONTROL: [(&str, u16); 16] = [
+    ("sfc",     0x000), ("dfc",     0x001), ("cacr",    0x002),
+    ("vbr",     0x801), ("caar",    0x802), ("msp",     0x803),
+    ("isp",     0x804), ("acr0",    0x004), ("acr1",    0x005),
+    ("acr2",    0x006), ("acr3",    0x007), ("rombar0", 0xC00),
+    ("rombar1", 0xC01), ("rambar0", 0xC04), ("rambar1", 0xC05),
+    ("mbar",    0xC0F),
+];
+
+// ----------------------------------------------------------------------------
+
+/// Register.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reg {
+    /// Data register.
+    D(u8),
+
+    /// Address register.
+    A(u8),
+
+    /// Program counter.
+    Pc,
+
+    /// Condition code register.
+    Ccr,
+
+    /// Status register.
+    Sr,
+
+    /// User stack pointer.
+    Usp,
+
+    /// Control register, by `movec` code.
+    Ctrl(u16),
+
+    /// Multiply-accumulate accumulator.
+    Acc(u8),
+
+    /// Multiply-accumulate status register.
+    Macsr,
+
+    /// Multiply-accumulate mask register.
+    Mask,
+}
+
+impl Reg {
+    /// Returns the register with the given name, if any.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let reg  = match name.as_bytes() {
+            [b'd', n @ b'0'..=b'7'] => Reg::D(n - b'0'),
+            [b'a', n @ b'0'..=b'7'] => Reg::A(n - b'0'),
+            _         => match &name[..] {
+                "sp"      => Reg::A(7),
+                "pc"      => Reg::Pc,
+                "ccr"     => Reg::Ccr,
+                "sr"      => Reg::Sr,
+                "usp"     => Reg::Usp,
+                "acc"     => Reg::Acc(0),
+                "acc0"    => Reg::Acc(0),
+                "acc1"    => Reg::Acc(1),
+                "acc2"    => Reg::Acc(2),
+                "acc3"    => Reg::Acc(3),
+                "macsr"   => Reg::Macsr,
+                "mask"    => Reg::Mask,
+                _         => match CONTROL.iter().find(|&&(n, _)| n == name) {
+                    Some(&(_, code)) => Reg::Ctrl(code),
+                    None             => return None,
+                },
+            },
+        };
+        Some(reg)
+    }
+
+    /// Returns a number that identifies the register, the same for each name
+    /// of the register.
+    pub fn key(self) -> u32 {
+        match self {
+            Reg::D(n)    => n as u32,
+            Reg::A(n)    => 8 + n as u32,
+            Reg::Pc      => 16,
+            Reg::Ccr     => 17,
+            Reg::Sr      => 18,
+            Reg::Usp     => 19,
+            Reg::Acc(n)  => 20 + n as u32,
+            Reg::Macsr   => 24,
+            Reg::Mask    => 25,
+            Reg::Ctrl(c) => 0x1000 | c as u32,
+        }
+    }
+
+    /// Returns the instruction set features of which the target must have
+    /// one for the register to exist, or `None` if every target has it.
+    pub fn isa(self) -> Option<u32> {
+        match self {
+            Reg::Ctrl(0x000 | 0x001 | 0x802..=0x804) => Some(M68010 | M68020),
+            Reg::Ctrl(0x004..=0x007 | 0xC00..)       => Some(COLDFIRE),
+            Reg::Ctrl(_) | Reg::Usp                  => None,
+            Reg::Acc(0) | Reg::Macsr | Reg::Mask     => Some(CF_MAC | CF_EMAC),
+            Reg::Acc(_)                              => Some(CF_EMAC),
+            _                                        => None,
+        }
+    }
+
+    /// Returns the general-purpose register number (0-15, data registers
+    /// first) of the register, if it is a data or address register.
+    pub fn general(self) -> Option<u16> {
+        match self {
+            Reg::D(n) => Some(n as u16),
+            Reg::A(n) => Some(n as u16 + 8),
+            _         => None,
+        }
+    }
+}
+
+/// Index register of an indexed addressing mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Index {
+    /// General-purpose register number (0-15).
+    pub reg: u16,
+
+    /// Whether the index is a long word rather than a sign-extended word.
+    pub long: bool,
+
+    /// Scale factor, as a power of two.
+    pub scale: u16,
+}
+
+impl Index {
+    /// Returns the index fields of an extension word.
+    pub fn bits(self) -> u16 {
+        self.reg << 12 | (self.long as u16) << 11 | self.scale << 9
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Extension word or long word of an instruction.
+#[derive(Clone, Debug)]
+pub enum Ext {
+    /// Constant word.
+    Word(u16),
+
+    /// Value of an expression, merged into `high` by `kind`.
+    Value {
+        expr:  Expr<Span>,
+        value: Value,
+        kind:  RelocKind,
+        high:  u16,
+    },
+
+    /// Displacement from the program counter to the value of an expression,
+    /// merged into `high` by `kind`.  The program counter is the address of
+    /// the extension plus `adjust`.
+    PcRel {
+        expr:   Expr<Span>,
+        value:  Value,
+        kind:   RelocKind,
+        high:   u16,
+        adjust: i64,
+    },
+}
+
+/// Effective address.
+#[derive(Clone, Debug)]
+pub struct Ea {
+    /// Addressing-mode category.
+    pub mode: u16,
+
+    /// Mode and register fields, as in the low 6 bits of an opcode word.
+    pub field: u16,
+
+    /// Extension words.
+    pub ext: Vec<Ext>,
+
+    /// Location of the operand.
+    pub span: Span,
+}
+
+impl Ea {
+    fn new(mode: u16, field: u16, span: Span) -> Self {
+        Self { mode, field, ext: vec![], span }
+    }
+
+    /// Returns the register field.
+    pub fn reg(&self) -> u16 {
+        self.field & 7
+    }
+}
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+pub enum Operand {
+    /// Effective address, other than an immediate.
+    Ea(Ea),
+
+    /// Immediate value.
+    Imm(Expr<Span>),
+
+    /// Register that is not an effective address.
+    Special(Reg, Span),
+
+    /// Register list, as a mask with bit 0 for `d0` and bit 15 for `a7`.
+    List(u16, Span),
+
+    /// Register pair, as in `d1:d0`.
+    Pair(Box<Operand>, Box<Operand>, Span),
+}
+
+impl Operand {
+    /// Returns the location of the operand.
+    pub fn span(&self) -> Span {
+        match *self {
+            Operand::Ea(ref ea)          => ea.span,
+            Operand::Imm(ref e)          => *e.data(),
+            Operand::Special(_, span)    => span,
+            Operand::List(_, span)       => span,
+            Operand::Pair(_, _, span)    => span,
+        }
+    }
+
+    /// Returns the data register number, if the operand is a data register.
+    pub fn dreg(&self) -> Option<u16> {
+        match *self {
+            Operand::Ea(ref ea) if ea.mode == DN => Some(ea.reg()),
+            _                                    => None,
+        }
+    }
+
+    /// Returns the address register number, if the operand is an address
+    /// register.
+    pub fn areg(&self) -> Option<u16> {
+        match *self {
+            Operand::Ea(ref ea) if ea.mode == AN => Some(ea.reg()),
+            _                                    => None,
+        }
+    }
+
+    /// Returns the general-purpose register number (0-15), if the operand is
+    /// a data or address register.
+    pub fn general(&self) -> Option<u16> {
+        self.dreg().or_else(|| self.areg().map(|r| r + 8))
+    }
+
+    /// Returns the effective address, if the operand is not an immediate.
+    pub fn ea(&self) -> Option<&Ea> {
+        match *self {
+            Operand::Ea(ref ea) => Some(ea),
+            _                   => None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Operand parser.
+pub struct Parser<'a> {
+    pub out: &'a mut dyn Emitter,
+    pub isa: u32,
+}
+
+/// Term of the sum inside a memory operand.
+enum Term<'e> {
+    Base(Reg, Span),
+    Index(Index, Span),
+    Indirect(&'e Expr<Span>),
+    Disp(bool, &'e Expr<Span>),
+}
+
+/// Components of a memory operand.
+#[derive(Default)]
+struct Parts<'e> {
+    base:     Option<Reg>,
+    index:    Option<Index>,
+    indirect: Option<&'e Expr<Span>>,
+    disp:     Vec<(bool, &'e Expr<Span>)>,
+}
+
+impl Parser<'_> {
+    /// Parses the given argument.
+    pub fn parse(&mut self, arg: &Arg<Span>) -> Option<Operand> {
+        match *arg {
+            Arg::Expr(ref e) => self.operand(e),
+            Arg::Unknown(span) => {
+                self.out.error(span, "expected: operand");
+                None
+            },
+        }
+    }
+
+    fn operand(&mut self, expr: &Expr<Span>) -> Option<Operand> {
+        let span = *expr.data();
+
+        match *expr {
+            Expr::Ident(_, name) => match self.reg(name) {
+                Some(Reg::D(n)) => Some(Operand::Ea(Ea::new(DN, n as u16,       span))),
+                Some(Reg::A(n)) => Some(Operand::Ea(Ea::new(AN, 8 | n as u16,   span))),
+                Some(reg)       => Some(Operand::Special(reg, span)),
+                None            => Some(Operand::Imm(expr.clone())),
+            },
+            Expr::Deref(_, ref inner, effect) => {
+                self.memory(span, inner, effect).map(Operand::Ea)
+            },
+            Expr::Binary(_, BinOp::Join, ref lhs, ref rhs) => {
+                let lhs = self.operand(lhs)?;
+                let rhs = self.operand(rhs)?;
+                Some(Operand::Pair(Box::new(lhs), Box::new(rhs), span))
+            },
+            _ => match self.list(expr) {
+                Some(mask) => Some(Operand::List(mask, span)),
+                None       => Some(Operand::Imm(expr.clone())),
+            },
+        }
+    }
+
+    fn reg(&self, name: Name) -> Option<Reg> {
+        Reg::parse(&self.out.names()[name])
+            .filter(|r| r.isa().is_none_or(|isa| self.isa & isa != 0))
+    }
+
+    /// Returns whether the target is a ColdFire.
+    pub fn coldfire(&self) -> bool {
+        self.isa & COLDFIRE != 0
+    }
+
+    // === Register Lists ===
+
+    /// Parses a register list such as `d0-d3/a0`.  Returns `None` if the
+    /// expression is not a register list.
+    fn list(&mut self, expr: &Expr<Span>) -> Option<u16> {
+        let mut items = vec![];
+        flatten_list(self.out.names(), expr, &mut items)?;
+
+        let mut mask  = 0u16;
+        let mut items = items.into_iter().peekable();
+
+        while let Some(item) = items.next() {
+            let first = match item {
+                ListItem::Reg(r) => r,
+                _                => return None,
+            };
+
+            let last = match items.peek() {
+                Some(ListItem::Range) => {
+                    items.next();
+                    match items.next() {
+                        Some(ListItem::Reg(r)) if r >= first => r,
+                        Some(ListItem::Reg(_)) => {
+                            self.out.error(*expr.data(), "register range is reversed");
+                            return Some(0);
+                        },
+                        _ => return None,
+                    }
+                },
+                _ => first,
+            };
+
+            for r in first..=last {
+                mask |= 1 << r;
+            }
+
+            match items.next() {
+                None                  => break,
+                Some(ListItem::Slash) => continue,
+                _                     => return None,
+            }
+        }
+
+        Some(mask)
+    }
+
+    // === Memory Operands ===
+
+    fn memory(&mut self, span: Span, inner: &Expr<Span>, effect: bool) -> Option<Ea> {
+        // Postincrement
+        if effect {
+            return match *inner {
+                Expr::Ident(_, name) => match self.reg(name) {
+                    Some(Reg::A(n)) => Some(Ea::new(POSTINC, 0x18 | n as u16, span)),
+                    _               => self.fail(span, "expected: address register before '!'"),
+                },
+                _ => self.fail(span, "expected: address register before '!'"),
+            };
+        }
+
+        // Predecrement
+        if let Expr::Unary(_, UnOp::PreDec, ref reg) = *inner {
+            return match **reg {
+                Expr::Ident(_, name) => match self.reg(name) {
+                    Some(Reg::A(n)) => Some(Ea::new(PREDEC, 0x20 | n as u16, span)),
+                    _               => self.fail(span, "expected: address register after '--'"),
+                },
+                _ => self.fail(span, "expected: address register after '--'"),
+            };
+        }
+
+        let parts = self.parts(inner)?;
+
+        if let Some(indirect) = parts.indirect {
+            return self.memory_indirect(span, parts, indirect);
+        }
+
+        let disp = sum(&parts.disp);
+
+        match (parts.base, parts.index) {
+            (Some(Reg::A(n)), None) => {
+                let n = n as u16;
+                let disp = match disp {
+                    Some(disp) => disp,
+                    None       => return Some(Ea::new(IND, 0x10 | n, span)),
+                };
+                let value = self.eval(&disp);
+                match value {
+                    Value::Const(v) if !is_signed(v, 16) && self.isa & M68020 != 0 => {
+                        self.full(span, parts.base, None, None, Some(disp), None)
+                    },
+                    _ => {
+                        let mut ea = Ea::new(DISP, 0x28 | n, span);
+                        ea.ext.push(Ext::Value { expr: disp, value, kind: DISP16, high: 0 });
+                        Some(ea)
+                    },
+                }
+            },
+            (Some(Reg::A(n)), Some(index)) => {
+                let n = n as u16;
+                let disp = match disp {
+                    Some(disp) => disp,
+                    None       => {
+                        self.require_scale(span, index);
+                        let mut ea = Ea::new(INDEX, 0x30 | n, span);
+                        ea.ext.push(Ext::Word(index.bits()));
+                        return Some(ea);
+                    },
+                };
+                let value = self.eval(&disp);
+                match value {
+                    Value::Const(v) if !is_signed(v, 8) && self.isa & M68020 != 0 => {
+                        self.full(span, parts.base, parts.index, None, Some(disp), None)
+                    },
+                    _ => {
+                        self.require_scale(span, index);
+                        let mut ea = Ea::new(INDEX, 0x30 | n, span);
+                        ea.ext.push(Ext::Value { expr: disp, value, kind: DISP8, high: index.bits() });
+                        Some(ea)
+                    },
+                }
+            },
+            (Some(_), index) => {
+                // Program counter relative
+                let target = disp.unwrap_or_else(|| here(span));
+                let value  = self.eval(&target);
+                let far    = match value {
+                    Value::Const(v) => {
+                        let dist = v.wrapping_sub(self.out.here() as i64 + 2);
+                        !is_signed(dist, if index.is_some() { 8 } else { 16 })
+                    },
+                    Value::Reloc(_) => false,
+                };
+                if far && self.isa & M68020 != 0 {
+                    return self.full(span, parts.base, index, None, Some(target), None);
+                }
+                let (mode, field, kind, high) = match index {
+                    None        => (PC_DISP,  0x3A, DISP16, 0),
+                    Some(index) => {
+                        self.require_scale(span, index);
+                        (PC_INDEX, 0x3B, DISP8, index.bits())
+                    },
+                };
+                let mut ea = Ea::new(mode, field, span);
+                ea.ext.push(Ext::PcRel { expr: target, value, kind, high, adjust: 0 });
+                Some(ea)
+            },
+            (None, None) => {
+                // Absolute
+                let addr = match disp {
+                    Some(addr) => addr,
+                    None       => return self.fail(span, "expected: address"),
+                };
+                let value = self.eval(&addr);
+                let (mut ea, kind) = match value {
+                    Value::Const(v) if is_abs16(v) => (Ea::new(ABS_W, 0x38, span), ABS16),
+                    _                              => (Ea::new(ABS_L, 0x39, span), RelocKind::INT32),
+                };
+                ea.ext.push(Ext::Value { expr: addr, value, kind, high: 0 });
+                Some(ea)
+            },
+            (None, Some(_)) => {
+                // Index with suppressed base
+                self.full(span, None, parts.index, None, disp, None)
+            },
+        }
+    }
+
+    fn memory_indirect(&mut self, span: Span, outer: Parts, indirect: &Expr<Span>)
+        -> Option<Ea>
+    {
+        let inner = match *indirect {
+            Expr::Deref(_, ref inner, false) => self.parts(inner)?,
+            _ => return self.fail(span, "invalid memory indirect operand"),
+        };
+
+        if outer.base.is_some() || inner.indirect.is_some() {
+            return self.fail(span, "invalid memory indirect operand");
+        }
+
+        let (index, post) = match (inner.index, outer.index) {
+            (Some(_), Some(_)) => return self.fail(span, "too many index registers"),
+            (Some(i), None)    => (Some(i), false),
+            (None,    i)       => (i,       true),
+        };
+
+        let bd = sum(&inner.disp);
+        let od = sum(&outer.disp);
+
+        self.full(span, inner.base, index, Some(post), bd, od)
+    }
+
+    /// Classifies the terms of the sum inside a memory operand.
+    fn parts<'e>(&mut self, expr: &'e Expr<Span>) -> Option<Parts<'e>> {
+        let mut terms = vec![];
+        flatten_sum(expr, false, &mut terms);
+
+        let mut parts = Parts::default();
+
+        for (neg, term) in terms {
+            let span = *term.data();
+            let term = self.term(neg, term)?;
+
+            if neg && !matches!(term, Term::Disp(..)) {
+                return self.fail(span, "cannot subtract a register");
+            }
+
+            match term {
+                Term::Base(reg, span) if parts.base.is_some() => {
+                    match reg {
+                        Reg::A(n) if parts.index.is_none() => {
+                            let long = self.coldfire();
+                            parts.index = Some(Index { reg: n as u16 + 8, long, scale: 0 });
+                        },
+                        _ => return self.fail(span, "too many base registers"),
+                    }
+                },
+                Term::Base(reg, _) => {
+                    parts.base = Some(reg);
+                },
+                Term::Index(_, span) if parts.index.is_some() => {
+                    return self.fail(span, "too many index registers");
+                },
+                Term::Index(index, _) => {
+                    parts.index = Some(index);
+                },
+                Term::Indirect(e) if parts.indirect.is_some() => {
+                    return self.fail(*e.data(), "too many indirections");
+                },
+                Term::Indirect(e) => {
+                    parts.indirect = Some(e);
+                },
+                Term::Disp(neg, e) => {
+                    parts.disp.push((neg, e));
+                },
+            }
+        }
+
+        Some(parts)
+    }
+
+    fn term<'e>(&mut self, neg: bool, expr: &'e Expr<Span>) -> Option<Term<'e>> {
+        let span = *expr.data();
+
+        let term = match *expr {
+            Expr::Ident(_, name) => match self.index_reg(name) {
+                Some((Reg::A(n), None)) => Term::Base(Reg::A(n), span),
+                Some((Reg::Pc,   None)) => Term::Base(Reg::Pc,   span),
+                Some((reg, long)) => match reg.general() {
+                    Some(reg) => {
+                        let long = self.index_size(span, long)?;
+                        Term::Index(Index { reg, long, scale: 0 }, span)
+                    },
+                    None => return self.fail(span, "invalid register in address"),
+                },
+                None => Term::Disp(neg, expr),
+            },
+            Expr::Binary(_, BinOp::Mul, ref lhs, ref rhs) => {
+                let name = match **lhs {
+                    Expr::Ident(_, name) => name,
+                    _                    => return Some(Term::Disp(neg, expr)),
+                };
+                let (reg, long) = match self.index_reg(name) {
+                    Some((reg, long)) => (reg.general(), long),
+                    None              => return Some(Term::Disp(neg, expr)),
+                };
+                let long = self.index_size(span, long)?;
+                let reg = match reg {
+                    Some(reg) => reg,
+                    None      => return self.fail(span, "invalid index register"),
+                };
+                let scale = match self.eval(rhs) {
+                    Value::Const(1) => 0,
+                    Value::Const(2) => 1,
+                    Value::Const(4) => 2,
+                    Value::Const(8) if !self.coldfire() => 3,
+                    _ if self.coldfire() => {
+                        return self.fail(*rhs.data(), "index scale must be 1, 2, or 4 on ColdFire")
+                    },
+                    _ => return self.fail(*rhs.data(), "index scale must be 1, 2, 4, or 8"),
+                };
+                Term::Index(Index { reg, long, scale }, span)
+            },
+            Expr::Deref(..) => Term::Indirect(expr),
+            _               => Term::Disp(neg, expr),
+        };
+
+        Some(term)
+    }
+
+    /// Parses a possibly size-qualified index register name, such as `d1.w`.
+    /// Returns the register and whether the size is long, if given.
+    fn index_reg(&self, name: Name) -> Option<(Reg, Option<bool>)> {
+        let name = &self.out.names()[name];
+        let (reg, long) = match name.rsplit_once('.') {
+            Some((reg, "w" | "W")) => (reg, Some(false)),
+            Some((reg, "l" | "L")) => (reg, Some(true)),
+            _                      => (name, None),
+        };
+        Some((Reg::parse(reg)?, long))
+    }
+
+    /// Returns whether an index register with the given size suffix is a
+    /// long word.  ColdFire supports only long index registers.
+    fn index_size(&mut self, span: Span, long: Option<bool>) -> Option<bool> {
+        match (self.coldfire(), long) {
+            (true,  Some(false)) => self.fail(span, "index register must be long on ColdFire"),
+            (true,  _          ) => Some(true),
+            (false, long       ) => Some(long.unwrap_or(false)),
+        }
+    }
+
+    /// Builds a 68020 full-format effective address.
+    fn full(
+        &mut self,
+        span:  Span,
+        base:  Option<Reg>,
+        index: Option<Index>,
+        post:  Option<bool>,
+        bd:    Option<Expr<Span>>,
+        od:    Option<Expr<Span>>,
+    ) -> Option<Ea> {
+        if self.coldfire() {
+            return self.fail(span, "addressing mode not supported on ColdFire");
+        }
+        if self.isa & M68020 == 0 {
+            return self.fail(span, "addressing mode requires 68020 or later");
+        }
+
+        let pc = base == Some(Reg::Pc);
+
+        let mut ea = match base {
+            Some(Reg::A(n)) => Ea::new(INDEX,    0x30 | n as u16, span),
+            Some(_)         => Ea::new(PC_INDEX, 0x3B,            span),
+            None            => Ea::new(INDEX,    0x30,            span),
+        };
+
+        let mut word = 0x0100;
+
+        match index {
+            Some(index) => word |= index.bits(),
+            None        => word |= 0x0040,
+        }
+
+        if base.is_none() {
+            word |= 0x0080;
+        }
+
+        let mut exts = vec![];
+
+        // Base displacement
+        match bd {
+            None => word |= 0x10,
+            Some(bd) => {
+                let value = self.eval(&bd);
+                let long  = match value {
+                    Value::Const(v) if pc => {
+                        !is_signed(v.wrapping_sub(self.out.here() as i64 + 2), 16)
+                    },
+                    Value::Const(v) => !is_signed(v, 16),
+                    Value::Reloc(_) => true,
+                };
+                let kind = if long { RelocKind::INT32 } else { DISP16 };
+                word |= if long { 0x30 } else { 0x20 };
+                exts.push(match pc {
+                    true  => Ext::PcRel { expr: bd, value, kind, high: 0, adjust: -2 },
+                    false => Ext::Value { expr: bd, value, kind, high: 0 },
+                });
+            },
+        }
+
+        // Outer displacement
+        if let Some(post) = post {
+            let size = match od {
+                None => 1,
+                Some(od) => {
+                    let value = self.eval(&od);
+                    let long  = !matches!(value, Value::Const(v) if is_signed(v, 16));
+                    let kind  = if long { RelocKind::INT32 } else { DISP16 };
+                    exts.push(Ext::Value { expr: od, value, kind, high: 0 });
+                    if long { 3 } else { 2 }
+                },
+            };
+            word |= size | if post && index.is_some() { 4 } else { 0 };
+        }
+
+        ea.ext.push(Ext::Word(word));
+        ea.ext.extend(exts);
+        Some(ea)
+    }
+
+    /// Reports an error if the given index scale requires a later processor.
+    fn require_scale(&mut self, span: Span, index: Index) {
+        if index.scale != 0 && self.isa & (M68020 | COLDFIRE) == 0 {
+            self.out.error(span, "index scale requires 68020 or later");
+        }
+    }
+
+    /// Evaluates the given expression, substituting zero if evaluation fails.
+    pub fn eval(&mut self, expr: &Expr<Span>) -> Value {
+        self.out.eval(expr).unwrap_or(Value::Const(0))
+    }
+
+    fn fail<T>(&mut self, span: Span, msg: &str) -> Option<T> {
+        self.out.error(span, msg);
+        None
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Item of a register list.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ListItem {
+    Reg(u16),
+    Range,
+    Slash,
+}
+
+/// Flattens a register list expression into its items in source order.
+/// Returns `None` if the expression is not a register list.
+fn flatten_list(names: &NameTable, expr: &Expr<Span>, items: &mut Vec<ListItem>) -> Option<()> {
+    match *expr {
+        Expr::Ident(_, name) => {
+            items.push(ListItem::Reg(Reg::parse(&names[name])?.general()?));
+        },
+        Expr::Binary(_, op, ref lhs, ref rhs) => {
+            let sep = match op {
+                BinOp::Sub | BinOp::Range => ListItem::Range,
+                BinOp::Div                => ListItem::Slash,
+                _                         => return None,
+            };
+            flatten_list(names, lhs, items)?;
+            items.push(sep);
+            flatten_list(names, rhs, items)?;
+        },
+        _ => return None,
+    }
+    Some(())
+}
@@ -0,0 +1,561 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction table.
+
+use super::*;
+use super::operand::*;
+
+// ----------------------------------------------------------------------------
+
+// Permitted size suffixes, as bits of a mask.
+pub const SB: u8 = 1 << 0;
+pub const SW: u8 = 1 << 1;
+pub const SL: u8 = 1 << 2;
+pub const SS: u8 = 1 << 3;
+
+/// Entry in the instruction table.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Mnemonic, without size suffix.
+    pub name: String,
+
+    /// Instruction set features that provide the instruction.
+    pub isa: u32,
+
+    /// Permitted size suffixes.
+    pub sizes: u8,
+
+    /// Sizes that ColdFire ISA_A permits.
+    pub cf: u8,
+
+    /// Sizes that ColdFire ISA_B permits in addition to those of ISA_A.
+    pub cf_b: u8,
+
+    /// Size when the mnemonic has no suffix.
+    pub dflt: Option<Size>,
+
+    /// Position of the size field in the opcode word.
+    pub sf: Sf,
+
+    /// Operand syntax and encoding.
+    pub form: Form,
+
+    /// Base opcode word.
+    pub op: u16,
+
+    /// Secondary opcode or form-specific flags.
+    pub op2: u16,
+}
+
+/// Positions of size fields in opcode words.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sf {
+    /// No size field.
+    None,
+
+    /// Bits 7-6: `00` byte, `01` word, `10` long.
+    Std,
+
+    /// Bits 13-12: `01` byte, `11` word, `10` long.
+    Move,
+
+    /// Bit 6: `0` word, `1` long.
+    Bit6,
+
+    /// Bits 8-6: `011` word, `111` long.
+    Addr,
+
+    /// Bits 8-7: `11` word, `10` long.
+    Chk,
+
+    /// Bits 10-9: `01` byte, `10` word, `11` long.
+    Cas,
+
+    /// Bits 10-9: `00` byte, `01` word, `10` long.
+    Chk2,
+}
+
+impl Sf {
+    /// Returns the bits of the size field for the given `size`.
+    pub fn bits(self, size: Size) -> u16 {
+        use Size::*;
+        match (self, size) {
+            (Sf::Std,  W)     => 0x0040,
+            (Sf::Std,  L)     => 0x0080,
+            (Sf::Move, B)     => 0x1000,
+            (Sf::Move, W)     => 0x3000,
+            (Sf::Move, L)     => 0x2000,
+            (Sf::Bit6, L)     => 0x0040,
+            (Sf::Addr, W)     => 0x00C0,
+            (Sf::Addr, L)     => 0x01C0,
+            (Sf::Chk,  W)     => 0x0180,
+            (Sf::Chk,  L)     => 0x0100,
+            (Sf::Cas,  B)     => 0x0200,
+            (Sf::Cas,  W)     => 0x0400,
+            (Sf::Cas,  L)     => 0x0600,
+            (Sf::Chk2, W)     => 0x0200,
+            (Sf::Chk2, L)     => 0x0400,
+            _                 => 0,
+        }
+    }
+}
+
+/// Operand syntaxes and encodings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Form {
+    /// No operands.
+    Inherent,
+
+    /// One effective address with the given permitted modes.
+    Ea(u16),
+
+    /// `tst`: one effective address, more modes on 68020.
+    Tst,
+
+    /// Two-operand arithmetic or logic with the given `ARITH_*` flags.  `op2`
+    /// is the opcode of the immediate form.
+    Arith(u8),
+
+    /// `<ea>, An`.
+    Addr,
+
+    /// `#imm, <ea>` with the given `ARITH_*` flags.
+    Imm(u8),
+
+    /// `#1-8, <ea>`.
+    Quick,
+
+    /// `#imm8, Dn`.
+    Moveq,
+
+    /// `<ea>, <ea>` and special registers.
+    Move,
+
+    /// `<ea>, An` with `move` sizes.
+    Movea,
+
+    /// Shift or rotate.  `op2` holds the type (bits 2-1) and direction (bit
+    /// 0).
+    Shift,
+
+    /// Bit operation.  `op2` holds the type.
+    Bit,
+
+    /// Conditional or unconditional branch.
+    Branch,
+
+    /// `Dn, <label>`.
+    Dbcc,
+
+    /// Conditional trap with optional immediate.
+    Trapcc,
+
+    /// `<ea>, An` with control modes.
+    Lea,
+
+    /// `An, #disp`.
+    Link,
+
+    /// `An`.
+    AReg,
+
+    /// `Dn`.
+    DReg,
+
+    /// `#0-15`.
+    Trap,
+
+    /// `#imm16`.
+    ImmWord,
+
+    /// `#0-7`.
+    Bkpt,
+
+    /// `Rx, Ry`.
+    Exg,
+
+    /// Multiply or divide.  `op2` bit 0 is set for divide; bit 1 is set for
+    /// signed.
+    MulDiv,
+
+    /// `<ea>, Dr:Dq` 32-bit divide with remainder.  `op2` is set for signed.
+    DivL,
+
+    /// `<ea>, Dn`.
+    Chk,
+
+    /// `Dy, Dx` or `[--Ay], [--Ax]`.
+    Extended,
+
+    /// `[Ay]!, [Ax]!`.
+    Cmpm,
+
+    /// Register list to or from memory.
+    Movem,
+
+    /// `Dn, [An + d]` or `[An + d], Dn`.
+    Movep,
+
+    /// `Rc, Rn` or `Rn, Rc`.
+    Movec,
+
+    /// `<ea>, Rn` or `Rn, <ea>`.
+    Moves,
+
+    /// Bit field.  `op2` is 0 for no register, 1 for a destination register,
+    /// or 2 for a source register.  The payload is the permitted modes.
+    Bitfield(u16),
+
+    /// `Dc, Du, <ea>`.
+    Cas,
+
+    /// `Dc1:Dc2, Du1:Du2, [Rn1]:[Rn2]`.
+    Cas2,
+
+    /// `<ea>, Rn`.  `op2` is the extension word template.
+    Chk2,
+
+    /// `Dx, Dy, #adj` or `[--Ax], [--Ay], #adj`.
+    Pack,
+
+    /// `#imm3, <ea>`.
+    Mov3q,
+
+    /// `<ea>, Dn` with sign or zero extension.
+    Mvx,
+
+    /// Multiply-accumulate.  `op2` is set to subtract.
+    Mac,
+
+    /// `ACCn, Rn`.
+    Movclr,
+}
+
+// Flags for arithmetic and logic forms.
+pub const ARITH_ADDR:    u8 = 1 << 0; // <ea>, An form exists
+pub const ARITH_FROM_EA: u8 = 1 << 1; // <ea>, Dn form exists
+pub const ARITH_TO_EA:   u8 = 1 << 2; // Dn, <ea> form exists
+pub const ARITH_CCR:     u8 = 1 << 3; // #imm, ccr/sr forms exist
+pub const ARITH_CMP:     u8 = 1 << 4; // compare: more source modes on 68020
+
+/// Condition codes, with their encodings.
+pub const CONDITIONS: &[(&str, u16)] = &[
+    ("t",  0), ("f",  1), ("hi",  2), ("ls",  3), ("cc",  4), ("hs",  4),
+    ("cs", 5), ("lo", 5), ("ne",  6), ("eq",  7), ("vc",  8), ("vs",  9),
+    ("pl",10), ("mi",11), ("ge", 12), ("lt", 13), ("gt", 14), ("le", 15),
+];
+
+fn e(name: &str, form: Form, op: u16) -> Entry {
+    Entry {
+        name:  name.to_string(),
+        isa:   M68000,
+        sizes: 0,
+        cf:    0,
+        cf_b:  0,
+        dflt:  None,
+        sf:    Sf::None,
+        form,
+        op,
+        op2:   0,
+    }
+}
+
+impl Entry {
+    fn isa(mut self, isa: u32) -> Self {
+        self.isa = isa;
+        self
+    }
+
+    fn sizes(mut self, sizes: u8, dflt: Option<Size>, sf: Sf) -> Self {
+        self.sizes = sizes;
+        self.dflt  = dflt;
+        self.sf    = sf;
+        self
+    }
+
+    fn op2(mut self, op2: u16) -> Self {
+        self.op2 = op2;
+        self
+    }
+
+    fn cf(mut self, isa: u32, cf: u8, cf_b: u8) -> Self {
+        self.isa |= isa;
+        self.cf   = cf;
+        self.cf_b = cf_b;
+        self
+    }
+}
+
+/// Builds the instruction table for the whole 68000 family.
+pub fn build() -> Vec<Entry> {
+    use Form::*;
+    use Size::*;
+
+    const BWL: u8 = SB | SW | SL;
+    const WL:  u8 = SW | SL;
+
+    let mut t = vec![
+        // Inherent
+        e("illegal", Inherent, 0x4AFC),
+        e("nop",     Inherent, 0x4E71),
+        e("reset",   Inherent, 0x4E70),
+        e("rte",     Inherent, 0x4E73),
+        e("rtr",     Inherent, 0x4E77),
+        e("rts",     Inherent, 0x4E75),
+        e("trapv",   Inherent, 0x4E76),
+
+        // Single operand
+        e("clr",  Ea(DATA_ALT), 0x4200).sizes(BWL, Some(W), Sf::Std),
+        e("neg",  Ea(DATA_ALT), 0x4400).sizes(BWL, Some(W), Sf::Std),
+        e("negx", Ea(DATA_ALT), 0x4000).sizes(BWL, Some(W), Sf::Std),
+        e("not",  Ea(DATA_ALT), 0x4600).sizes(BWL, Some(W), Sf::Std),
+        e("tst",  Tst,          0x4A00).sizes(BWL, Some(W), Sf::Std),
+        e("nbcd", Ea(DATA_ALT), 0x4800).sizes(SB,  Some(B), Sf::None),
+        e("tas",  Ea(DATA_ALT), 0x4AC0).sizes(SB,  Some(B), Sf::None),
+        e("jmp",  Ea(CTRL),     0x4EC0),
+        e("jsr",  Ea(CTRL),     0x4E80),
+        e("pea",  Ea(CTRL),     0x4840).sizes(SL,  Some(L), Sf::None),
+
+        // Arithmetic and logic
+        e("add",  Arith(ARITH_ADDR | ARITH_FROM_EA | ARITH_TO_EA), 0xD000).op2(0x0600),
+        e("sub",  Arith(ARITH_ADDR | ARITH_FROM_EA | ARITH_TO_EA), 0x9000).op2(0x0400),
+        e("cmp",  Arith(ARITH_ADDR | ARITH_FROM_EA | ARITH_CMP),   0xB000).op2(0x0C00),
+        e("and",  Arith(ARITH_FROM_EA | ARITH_TO_EA | ARITH_CCR),  0xC000).op2(0x0200),
+        e("or",   Arith(ARITH_FROM_EA | ARITH_TO_EA | ARITH_CCR),  0x8000).op2(0x0000),
+        e("eor",  Arith(ARITH_TO_EA | ARITH_CCR),                  0xB100).op2(0x0A00),
+        e("adda", Addr, 0xD000).sizes(WL, Some(W), Sf::Addr),
+        e("suba", Addr, 0x9000).sizes(WL, Some(W), Sf::Addr),
+        e("cmpa", Addr, 0xB000).sizes(WL, Some(W), Sf::Addr),
+        e("addi", Imm(0),         0x0600),
+        e("subi", Imm(0),         0x0400),
+        e("cmpi", Imm(ARITH_CMP), 0x0C00),
+        e("andi", Imm(ARITH_CCR), 0x0200),
+        e("ori",  Imm(ARITH_CCR), 0x0000),
+        e("eori", Imm(ARITH_CCR), 0x0A00),
+        e("addq", Quick, 0x5000),
+        e("subq", Quick, 0x5100),
+        e("addx", Extended, 0xD100),
+        e("subx", Extended, 0x9100),
+        e("abcd", Extended, 0xC100).sizes(SB, Some(B), Sf::Std),
+        e("sbcd", Extended, 0x8100).sizes(SB, Some(B), Sf::Std),
+        e("cmpm", Cmpm,     0xB108),
+        e("chk",  Chk,      0x4000).sizes(WL, Some(W), Sf::Chk),
+        e("mulu", MulDiv,   0xC0C0).sizes(WL, Some(W), Sf::None).op2(0),
+        e("muls", MulDiv,   0xC1C0).sizes(WL, Some(W), Sf::None).op2(2),
+        e("divu", MulDiv,   0x80C0).sizes(WL, Some(W), Sf::None).op2(1),
+        e("divs", MulDiv,   0x81C0).sizes(WL, Some(W), Sf::None).op2(3),
+
+        // Data movement
+        e("move",  Move,  0x0000).sizes(BWL, Some(W), Sf::Move),
+        e("movea", Movea, 0x0040).sizes(WL,  Some(W), Sf::Move),
+        e("moveq", Moveq, 0x7000).sizes(SL,  Some(L), Sf::None),
+        e("movem", Movem, 0x4880).sizes(WL,  Some(W), Sf::Bit6),
+        e("movep", Movep, 0x0008).sizes(WL,  Some(W), Sf::None),
+        e("lea",   Lea,   0x41C0).sizes(SL,  Some(L), Sf::None),
+        e("exg",   Exg,   0xC100).sizes(SL,  Some(L), Sf::None),
+        e("swap",  DReg,  0x4840).sizes(SW,  Some(W), Sf::None),
+        e("ext",   DReg,  0x4880).sizes(WL,  Some(W), Sf::Bit6),
+        e("link",  Link,  0x4E50).sizes(WL,  Some(W), Sf::None),
+        e("unlk",  AReg,  0x4E58),
+
+        // Shifts and rotates
+        e("asr",  Shift, 0xE000).op2(0b000),
+        e("asl",  Shift, 0xE000).op2(0b001),
+        e("lsr",  Shift, 0xE000).op2(0b010),
+        e("lsl",  Shift, 0xE000).op2(0b011),
+        e("roxr", Shift, 0xE000).op2(0b100),
+        e("roxl", Shift, 0xE000).op2(0b101),
+        e("ror",  Shift, 0xE000).op2(0b110),
+        e("rol",  Shift, 0xE000).op2(0b111),
+
+        // Bit operations
+        e("btst", Bit, 0x0000).sizes(SB | SL, None, Sf::None).op2(0),
+        e("bchg", Bit, 0x0000).sizes(SB | SL, None, Sf::None).op2(1),
+        e("bclr", Bit, 0x0000).sizes(SB | SL, None, Sf::None).op2(2),
+        e("bset", Bit, 0x0000).sizes(SB | SL, None, Sf::None).op2(3),
+
+        // Program control
+        e("bra",  Branch, 0x6000).sizes(SB | SW | SL | SS, None, Sf::None),
+        e("bsr",  Branch, 0x6100).sizes(SB | SW | SL | SS, None, Sf::None),
+        e("dbra", Dbcc,   0x51C8).sizes(SW, Some(W), Sf::None),
+        e("trap", Trap,   0x4E40),
+        e("stop", ImmWord, 0x4E72),
+
+        // 68010
+        e("rtd",   ImmWord, 0x4E74).isa(M68010),
+        e("bkpt",  Bkpt,    0x4848).isa(M68010),
+        e("movec", Movec,   0x4E7A).isa(M68010).sizes(SL,  Some(L), Sf::None),
+        e("moves", Moves,   0x0E00).isa(M68010).sizes(BWL, Some(W), Sf::Std),
+
+        // 68020
+        e("extb",   DReg,  0x49C0).isa(M68020).sizes(SL, Some(L), Sf::None),
+        e("divul",  DivL,  0x4C40).isa(M68020).sizes(SL, Some(L), Sf::None).op2(0),
+        e("divsl",  DivL,  0x4C40).isa(M68020).sizes(SL, Some(L), Sf::None).op2(1),
+        e("cas",    Cas,   0x08C0).isa(M68020).sizes(BWL, Some(W), Sf::Cas),
+        e("cas2",   Cas2,  0x08FC).isa(M68020).sizes(WL,  Some(W), Sf::Cas),
+        e("chk2",   Chk2,  0x00C0).isa(M68020).sizes(BWL, Some(W), Sf::Chk2).op2(0x0800),
+        e("cmp2",   Chk2,  0x00C0).isa(M68020).sizes(BWL, Some(W), Sf::Chk2).op2(0x0000),
+        e("pack",   Pack,  0x8140).isa(M68020),
+        e("unpk",   Pack,  0x8180).isa(M68020),
+        e("bftst",  Bitfield(DN | CTRL),     0xE8C0).isa(M68020).op2(0),
+        e("bfextu", Bitfield(DN | CTRL),     0xE9C0).isa(M68020).op2(1),
+        e("bfchg",  Bitfield(DN | CTRL_ALT), 0xEAC0).isa(M68020).op2(0),
+        e("bfexts", Bitfield(DN | CTRL),     0xEBC0).isa(M68020).op2(1),
+        e("bfclr",  Bitfield(DN | CTRL_ALT), 0xECC0).isa(M68020).op2(0),
+        e("bfffo",  Bitfield(DN | CTRL),     0xEDC0).isa(M68020).op2(1),
+        e("bfset",  Bitfield(DN | CTRL_ALT), 0xEEC0).isa(M68020).op2(0),
+        e("bfins",  Bitfield(DN | CTRL_ALT), 0xEFC0).isa(M68020).op2(2),
+    ];
+
+    // ColdFire
+    t.extend([
+        e("halt",    Inherent, 0x4AC8).isa(COLDFIRE),
+        e("pulse",   Inherent, 0x4ACC).isa(COLDFIRE),
+        e("tpf",     Trapcc,   0x51F8).isa(COLDFIRE).sizes(WL, None, Sf::None),
+        e("remu",    DivL,     0x4C40).isa(COLDFIRE).sizes(SL, Some(L), Sf::None).op2(0),
+        e("rems",    DivL,     0x4C40).isa(COLDFIRE).sizes(SL, Some(L), Sf::None).op2(1),
+        e("mov3q",   Mov3q,    0xA140).isa(CF_ISA_B).sizes(SL, Some(L), Sf::None),
+        e("mvs",     Mvx,      0x7100).isa(CF_ISA_B).sizes(SB | SW, None, Sf::None),
+        e("mvz",     Mvx,      0x7180).isa(CF_ISA_B).sizes(SB | SW, None, Sf::None),
+        e("sats",    DReg,     0x4C80).isa(CF_ISA_B).sizes(SL, Some(L), Sf::None),
+        e("bitrev",  DReg,     0x00C0).isa(CF_ISA_C).sizes(SL, Some(L), Sf::None),
+        e("byterev", DReg,     0x02C0).isa(CF_ISA_C).sizes(SL, Some(L), Sf::None),
+        e("ff1",     DReg,     0x04C0).isa(CF_ISA_C).sizes(SL, Some(L), Sf::None),
+        e("mac",     Mac,      0xA000).isa(CF_MAC | CF_EMAC).sizes(WL, Some(W), Sf::None).op2(0),
+        e("msac",    Mac,      0xA000).isa(CF_MAC | CF_EMAC).sizes(WL, Some(W), Sf::None).op2(1),
+        e("movclr",  Movclr,   0xA1C0).isa(CF_EMAC).sizes(SL, Some(L), Sf::None),
+    ]);
+
+    for entry in &mut t {
+        if entry.isa & (M68000 | M68010 | M68020) == 0 {
+            entry.cf = entry.sizes;
+        }
+    }
+
+    // Sized arithmetic, logic, and shifts
+    for entry in &mut t {
+        let sized = matches!(
+            entry.form,
+            Arith(_) | Imm(_) | Quick | Extended | Cmpm | Shift
+        );
+        if sized && entry.sizes == 0 {
+            entry.sizes = BWL;
+            entry.dflt  = Some(W);
+            entry.sf    = Sf::Std;
+        }
+    }
+
+    // Conditional instructions
+    for &(cc, code) in CONDITIONS {
+        let code = code << 8;
+
+        if code >= 0x200 {
+            t.push(e(&format!("b{}", cc), Branch, 0x6000 | code)
+                .sizes(SB | SW | SL | SS, None, Sf::None)
+                .cf(COLDFIRE, SB | SW | SS, SL));
+        }
+
+        t.push(e(&format!("db{}", cc), Dbcc, 0x50C8 | code)
+            .sizes(SW, Some(W), Sf::None));
+
+        t.push(e(&format!("s{}", cc), Ea(DATA_ALT), 0x50C0 | code)
+            .sizes(SB, Some(B), Sf::None)
+            .cf(COLDFIRE, SB, 0));
+
+        t.push(e(&format!("trap{}", cc), Trapcc, 0x50F8 | code)
+            .isa(M68020)
+            .sizes(WL, None, Sf::None));
+    }
+
+    // Instructions that ColdFire shares with the 68000 family
+    for &(name, isa, cf, cf_b) in COLDFIRE_SIZES {
+        for entry in t.iter_mut().filter(|e| e.name == name) {
+            entry.isa |= isa;
+            entry.cf   = cf;
+            entry.cf_b = cf_b;
+        }
+    }
+
+    t
+}
+
+/// Instructions that ColdFire shares with the 68000 family: the name, the
+/// earliest ColdFire instruction set that provides the instruction, the sizes
+/// that ISA_A permits, and the sizes that ISA_B adds.
+const COLDFIRE_SIZES: &[(&str, u32, u8, u8)] = &[
+    ("illegal", COLDFIRE, 0,        0),
+    ("nop",     COLDFIRE, 0,        0),
+    ("rte",     COLDFIRE, 0,        0),
+    ("rts",     COLDFIRE, 0,        0),
+    ("clr",     COLDFIRE, SB|SW|SL, 0),
+    ("neg",     COLDFIRE, SL,       0),
+    ("negx",    COLDFIRE, SL,       0),
+    ("not",     COLDFIRE, SL,       0),
+    ("tst",     COLDFIRE, SB|SW|SL, 0),
+    ("tas",     CF_ISA_B, 0,        SB),
+    ("jmp",     COLDFIRE, 0,        0),
+    ("jsr",     COLDFIRE, 0,        0),
+    ("pea",     COLDFIRE, SL,       0),
+    ("add",     COLDFIRE, SL,       0),
+    ("sub",     COLDFIRE, SL,       0),
+    ("cmp",     COLDFIRE, SL,       SB|SW),
+    ("and",     COLDFIRE, SL,       0),
+    ("or",      COLDFIRE, SL,       0),
+    ("eor",     COLDFIRE, SL,       0),
+    ("adda",    COLDFIRE, SL,       0),
+    ("suba",    COLDFIRE, SL,       0),
+    ("cmpa",    COLDFIRE, SL,       SW),
+    ("addi",    COLDFIRE, SL,       0),
+    ("subi",    COLDFIRE, SL,       0),
+    ("cmpi",    COLDFIRE, SL,       SB|SW),
+    ("andi",    COLDFIRE, SL,       0),
+    ("ori",     COLDFIRE, SL,       0),
+    ("eori",    COLDFIRE, SL,       0),
+    ("addq",    COLDFIRE, SL,       0),
+    ("subq",    COLDFIRE, SL,       0),
+    ("addx",    COLDFIRE, SL,       0),
+    ("subx",    COLDFIRE, SL,       0),
+    ("mulu",    COLDFIRE, SW|SL,    0),
+    ("muls",    COLDFIRE, SW|SL,    0),
+    ("divu",    COLDFIRE, SW|SL,    0),
+    ("divs",    COLDFIRE, SW|SL,    0),
+    ("move",    COLDFIRE, SB|SW|SL, 0),
+    ("movea",   COLDFIRE, SW|SL,    0),
+    ("moveq",   COLDFIRE, SL,       0),
+    ("movem",   COLDFIRE, SL,       0),
+    ("lea",     COLDFIRE, SL,       0),
+    ("swap",    COLDFIRE, SW,       0),
+    ("ext",     COLDFIRE, SW|SL,    0),
+    ("extb",    COLDFIRE, SL,       0),
+    ("link",    COLDFIRE, SW,       0),
+    ("unlk",    COLDFIRE, 0,        0),
+    ("asl",     COLDFIRE, SL,       0),
+    ("asr",     COLDFIRE, SL,       0),
+    ("lsl",     COLDFIRE, SL,       0),
+    ("lsr",     COLDFIRE, SL,       0),
+    ("btst",    COLDFIRE, SB|SL,    0),
+    ("bchg",    COLDFIRE, SB|SL,    0),
+    ("bclr",    COLDFIRE, SB|SL,    0),
+    ("bset",    COLDFIRE, SB|SL,    0),
+    ("bra",     COLDFIRE, SB|SW|SS, SL),
+    ("bsr",     COLDFIRE, SB|SW|SS, SL),
+    ("trap",    COLDFIRE, 0,        0),
+    ("stop",    COLDFIRE, 0,        0),
+    ("movec",   COLDFIRE, SL,       0),
+];
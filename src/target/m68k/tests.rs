@@ -0,0 +1,477 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, be16};
+
+/// Assembles each line at address zero.
+const BENCH: Bench<u16> = Bench { origin: 0, split: be16 };
+
+#[test]
+fn m68000() {
+    BENCH.check("m68000", &[
+        ("nop",                                 &[0x4E71]),
+        ("RTS",                                 &[0x4E75]),
+        ("moveq 1, d0",                        &[0x7001]),
+        ("moveq -1, d7",                       &[0x7EFF]),
+        ("move.l d0, d1",                       &[0x2200]),
+        ("move.b [a0]!, [a1]!",                 &[0x12D8]),
+        ("move.w [--a0], d2",                   &[0x3420]),
+        ("move.l [a0 + 4], d0",                 &[0x2028, 0x0004]),
+        ("move.l x'12345678, d0",               &[0x203C, 0x1234, 0x5678]),
+        ("move.l [a0 + d1.w + 4], d0",          &[0x2030, 0x1004]),
+        ("move.w [x'1000], [x'12345678]",       &[0x33F8, 0x1000, 0x1234, 0x5678]),
+        ("move.w sr, d0",                       &[0x40C0]),
+        ("move d0, ccr",                        &[0x44C0]),
+        ("move usp, a0",                        &[0x4E68]),
+        ("movea.l a0, a1",                      &[0x2248]),
+        ("move.w d0, a1",                       &[0x3240]),
+        ("lea [a0 + 8], a1",                    &[0x43E8, 0x0008]),
+        ("lea [pc + 10], a0",                   &[0x41FA, 0x0008]),
+        ("pea [a0]",                            &[0x4850]),
+        ("clr.l d0",                            &[0x4280]),
+        ("tst.b [a0]",                          &[0x4A10]),
+        ("addq.l 1, d0",                       &[0x5280]),
+        ("subq.w 8, d1",                       &[0x5141]),
+        ("add.l d1, d0",                        &[0xD081]),
+        ("add.w d0, [a0]",                      &[0xD150]),
+        ("add.l a0, a1",                        &[0xD3C8]),
+        ("adda.l a0, a1",                       &[0xD3C8]),
+        ("addi.w x'10, d0",                     &[0x0640, 0x0010]),
+        ("add.w x'10, d0",                      &[0x0640, 0x0010]),
+        ("cmpi.b 5, d0",                        &[0x0C00, 0x0005]),
+        ("cmp.l [a0], d0",                      &[0xB090]),
+        ("eor.l d0, d1",                        &[0xB181]),
+        ("and.w 1, d0",                         &[0x0240, 0x0001]),
+        ("andi x'700, sr",                      &[0x027C, 0x0700]),
+        ("ori.b 1, ccr",                        &[0x003C, 0x0001]),
+        ("lsl.l 2, d0",                        &[0xE588]),
+        ("lsr.w 1, d1",                        &[0xE249]),
+        ("asr.l d1, d0",                        &[0xE2A0]),
+        ("rol.b 1, d0",                        &[0xE318]),
+        ("asl.w [a0]",                          &[0xE1D0]),
+        ("btst 3, d0",                          &[0x0800, 0x0003]),
+        ("bset d1, [a0]",                       &[0x03D0]),
+        ("jmp [a0]",                            &[0x4ED0]),
+        ("jsr [x'1000]",                        &[0x4EB8, 0x1000]),
+        ("link a6, -8",                         &[0x4E56, 0xFFF8]),
+        ("unlk a6",                             &[0x4E5E]),
+        ("swap d3",                             &[0x4843]),
+        ("ext.l d0",                            &[0x48C0]),
+        ("exg d0, d1",                          &[0xC141]),
+        ("exg a0, a1",                          &[0xC149]),
+        ("exg d0, a0",                          &[0xC188]),
+        ("exg a0, d0",                          &[0xC188]),
+        ("mulu.w d1, d0",                       &[0xC0C1]),
+        ("divs [a0], d2",                       &[0x85D0]),
+        ("chk [a0], d1",                        &[0x4390]),
+        ("movem.l d0-d7/a0-a6, [--sp]",         &[0x48E7, 0xFFFE]),
+        ("movem.l [sp]!, d0-d7/a0-a6",          &[0x4CDF, 0x7FFF]),
+        ("movem.w d0, [a0]",                    &[0x4890, 0x0001]),
+        ("dbf d0, 0",                           &[0x51C8, 0xFFFE]),
+        ("dbra d1, 0",                          &[0x51C9, 0xFFFE]),
+        ("seq d0",                              &[0x57C0]),
+        ("trap 15",                            &[0x4E4F]),
+        ("stop x'2700",                         &[0x4E72, 0x2700]),
+        ("abcd [--a1], [--a0]",                 &[0xC109]),
+        ("addx.l d1, d0",                       &[0xD181]),
+        ("cmpm.b [a0]!, [a1]!",                 &[0xB308]),
+        ("movep.l d0, [a0 + 4]",                &[0x01C8, 0x0004]),
+        ("movep.w [a0], d1",                    &[0x0308, 0x0000]),
+    ]);
+}
+
+#[test]
+fn m68010() {
+    BENCH.check("m68010", &[
+        ("movec vbr, d0",                       &[0x4E7A, 0x0801]),
+        ("movec a0, usp",                       &[0x4E7B, 0x8800]),
+        ("moves.l d0, [a0]",                    &[0x0E90, 0x0800]),
+        ("rtd 4",                               &[0x4E74, 0x0004]),
+        ("bkpt 3",                             &[0x484B]),
+        ("move ccr, d0",                        &[0x42C0]),
+    ]);
+}
+
+#[test]
+fn m68020() {
+    BENCH.check("m68020", &[
+        ("move.l [[a0 + 4] + d1.l*2 + 8], d0",  &[0x2030, 0x1B26, 0x0004, 0x0008]),
+        ("move.l [[a0 + d1.l*2 + 4] + 8], d0",  &[0x2030, 0x1B22, 0x0004, 0x0008]),
+        ("move.l [a0 + d1.w*4], d0",            &[0x2030, 0x1400]),
+        ("move.l [a0 + x'12345], d0",           &[0x2030, 0x0170, 0x0001, 0x2345]),
+        ("bfextu [a0], 4, 8, d0",               &[0xE9D0, 0x0108]),
+        ("bfins d1, [a0], d2, 32",              &[0xEFD0, 0x1880]),
+        ("bftst d0, 0, 1",                      &[0xE8C0, 0x0001]),
+        ("cas.l d0, d1, [a0]",                  &[0x0ED0, 0x0040]),
+        ("cas2.l d0:d1, d2:d3, [a0]:[a1]",      &[0x0EFC, 0x8080, 0x90C1]),
+        ("mulu.l d1, d0",                       &[0x4C01, 0x0000]),
+        ("mulu.l d1, d2:d0",                    &[0x4C01, 0x0402]),
+        ("divs.l [a0], d1",                     &[0x4C50, 0x1801]),
+        ("divul.l d1, d2:d0",                   &[0x4C41, 0x0002]),
+        ("extb.l d0",                           &[0x49C0]),
+        ("chk2.w [a0], d1",                     &[0x02D0, 0x1800]),
+        ("pack [--a0], [--a1], 0",              &[0x8348, 0x0000]),
+        ("unpk d0, d1, x'3030",                 &[0x8380, 0x3030]),
+        ("link.l a6, -8",                       &[0x480E, 0xFFFF, 0xFFF8]),
+        ("trapne",                              &[0x56FC]),
+        ("trapeq.w 1",                          &[0x57FA, 0x0001]),
+        ("tst.l a0",                            &[0x4A88]),
+        ("bra.l 0",                             &[0x60FF, 0xFFFF, 0xFFFE]),
+    ]);
+}
+
+#[test]
+fn coldfire() {
+    BENCH.check("coldfire-a", &[
+        ("add d1, d0",                          &[0xD081]),
+        ("addi.l 1, d0",                        &[0x0680, 0x0000, 0x0001]),
+        ("move.b [a0 + 4], [a1]!",              &[0x12E8, 0x0004]),
+        ("move.l [a0 + d1*4 + 2], d0",          &[0x2030, 0x1C02]),
+        ("movem.l d0-d7, [sp]",                 &[0x48D7, 0x00FF]),
+        ("mulu.l [a0], d1",                     &[0x4C10, 0x1000]),
+        ("remu.l d1, d2:d0",                    &[0x4C41, 0x0002]),
+        ("rems.l [a0], d3:d4",                  &[0x4C50, 0x4803]),
+        ("lsl d1, d0",                          &[0xE3A8]),
+        ("tpf",                                 &[0x51FC]),
+        ("halt",                                &[0x4AC8]),
+        ("movec d0, cacr",                      &[0x4E7B, 0x0002]),
+        ("movec a0, rambar0",                   &[0x4E7B, 0x8C04]),
+        ("mac.w d1, d2",                        &[0xA401, 0x0000]),
+        ("mac.l a0, d1 << 1",                   &[0xA208, 0x0A00]),
+        ("msac.w d1.u, d2.u",                   &[0xA401, 0x01C0]),
+        ("move.l d0, acc",                      &[0xA100]),
+        ("move.l acc, d1",                      &[0xA181]),
+        ("move.l 1, macsr",                     &[0xA93C, 0x0000, 0x0001]),
+        ("move.l d0, mask",                     &[0xAD00]),
+        ("move.l macsr, ccr",                   &[0xA9C0]),
+    ]);
+
+    BENCH.check("coldfire-b", &[
+        ("cmp.b d0, d1",                        &[0xB200]),
+        ("mov3q.l -1, d0",                      &[0xA140]),
+        ("mov3q 7, [a0]",                       &[0xAF50]),
+        ("mvs.b d0, d1",                        &[0x7300]),
+        ("mvz.w [a0], d2",                      &[0x75D0]),
+        ("sats.l d3",                           &[0x4C83]),
+        ("move.w 5, [a0 + 2]",                  &[0x317C, 0x0005, 0x0002]),
+        ("mac.w d1, d2, acc1",                  &[0xA481, 0x0000]),
+        ("msac.w d1, d2, acc2",                 &[0xA401, 0x0110]),
+        ("movclr.l acc3, d0",                   &[0xA7C0]),
+        ("bra.l 0",                             &[0x60FF, 0xFFFF, 0xFFFE]),
+    ]);
+
+    BENCH.check("coldfire-c", &[
+        ("bitrev d0",                           &[0x00C0]),
+        ("byterev d1",                          &[0x02C1]),
+        ("ff1 d2",                              &[0x04C2]),
+    ]);
+}
+
+#[test]
+fn coldfire_errors() {
+    let cf = "coldfire-a";
+    assert_eq!(BENCH.error(cf, "add.w d0, d1"),                   "size .w not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "cmp.b d0, d1"),                   "size .b not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "addi.l 1, [a0]"),                 "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "neg.l [a0]"),                     "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "move.l [a0 + d0 + 4], [x'1000]"), "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "move.w 5, [a0 + 2]"),             "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "movem.l d0-d7, [--sp]"),          "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "move.l [a0 + d0.w], d1"),         "index register must be long on ColdFire");
+    assert_eq!(BENCH.error(cf, "move.l [a0 + d0*8], d1"),         "index scale must be 1, 2, or 4 on ColdFire");
+    assert_eq!(BENCH.error(cf, "move.l [[a0]], d0"),              "addressing mode not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "lsl [a0]"),                       "memory shift not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "mulu.l d0, d2:d1"),               "64-bit multiply and divide not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "bra.l 0"),                        "size .l not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "rol.l 1, d0"),                    "instruction not supported on ColdFire");
+    assert_eq!(BENCH.error(cf, "mov3q 1, d0"),                    "instruction requires ColdFire ISA_B");
+    assert_eq!(BENCH.error(cf, "mac.w d1, d2, acc1"),             "expected: accumulator");
+    assert_eq!(BENCH.error("m68000", "move.l d0, acc"),           "invalid addressing mode");
+}
+
+#[test]
+fn branches() {
+    BENCH.check("m68000", &[
+        ("a: bra a",                            &[0x60FE]),
+        ("bra 2",                               &[0x6000, 0x0000]),
+        ("bne.w 0",                             &[0x6600, 0xFFFE]),
+        ("bsr.s 10",                            &[0x6108]),
+        ("a: nop\nbeq a",                       &[0x4E71, 0x67FC]),
+    ]);
+
+    let (bytes, _) = BENCH.assemble("m68000", "bra b\n.skip 200\nb: nop");
+    assert_eq!(BENCH.words(&bytes[..4]), [0x6000, 0x00CA]);
+
+    // A size suffix overrides relaxation, and the branches after it still
+    // relax
+    let (bytes, session) = BENCH.assemble("m68000", "a: bra.w a\nbra.b a\nbra b\n.skip 200\nb: nop");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(BENCH.words(&bytes[..10]), [0x6000, 0xFFFE, 0x60FA, 0x6000, 0x00CA]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("m68000").unwrap());
+
+    let unit = session.assemble("test.s", "
+        a:  bra a
+            jsr [ext]
+            lea [pc + a], a0
+            move.w ext, d0
+    ");
+
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(BENCH.words(&unit.sections[0].data), [
+        0x60FE, 0x4EB9, 0x0000, 0x0000, 0x41FA, 0xFFF6, 0x303C, 0x0000,
+    ]);
+    assert_eq!(unit.relocs.len(), 2);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("m68000", "moveq 300, d0"),       "value 300 out of range -128..255");
+    assert_eq!(BENCH.error("m68000", "addq 9, d0"),          "value 9 out of range 1..8");
+    assert_eq!(BENCH.error("m68000", "clr.l a0"),             "invalid addressing mode");
+    assert_eq!(BENCH.error("m68000", "move.b a0, d0"),        "byte operation on address register");
+    assert_eq!(BENCH.error("m68000", "lea d0, a0"),           "invalid addressing mode");
+    assert_eq!(BENCH.error("m68000", "nop d0"),               "invalid operands");
+    assert_eq!(BENCH.error("m68000", "btst.b d0, d1"),        "bit operation on data register must be long size");
+    assert_eq!(BENCH.error("m68000", "bra.s 1000"),           "branch displacement 998 out of range for short branch");
+    assert_eq!(BENCH.error("m68000", "move.l [[a0] + 4], d0"), "addressing mode requires 68020 or later");
+    assert_eq!(BENCH.error("m68000", "move.l [a0 + d0*4], d0"), "index scale requires 68020 or later");
+    assert_eq!(BENCH.error("m68000", "mulu.l d0, d1"),        "instruction form requires 68020 or later");
+    assert_eq!(BENCH.error("m68010", "movec cacr, d0"),       "control register requires 68020 or later");
+    assert_eq!(BENCH.error("m68000", "move.w [a0 + 40000], d0"), "displacement 40000 out of range for 16 bits");
+    assert_eq!(BENCH.error("m68000", "bfextu [a0], 4, 8, d0"), "instruction requires 68020 or later");
+    assert_eq!(BENCH.error("m68000", "extb.l d0"),             "instruction requires 68020 or later");
+    assert_eq!(BENCH.error("m68000", "movec cacr, d0"),        "instruction requires 68010 or later");
+    assert_eq!(BENCH.error("m68020", "halt"),                  "instruction requires ColdFire");
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x80).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("m68000", &[
+            "nop",
+            "rts",
+            "illegal",
+            "moveq -1, d7",
+            "moveq x'7F, d0",
+            "move.l d0, d1",
+            "move.b [a0]!, [a1]!",
+            "move.w [--a0], d2",
+            "move.l [a0 + 4], d0",
+            "move.l [a0 - 4], d0",
+            "move.l [a0 + 0], d0",
+            "move.l x'12345678, d0",
+            "move.l [a0 + d1.w + 4], d0",
+            "move.l [a0 + d1.l - 2], d0",
+            "move.w [x'1000], [x'12345678]",
+            "move.w [x'FFFF8000], d0",
+            "move.b [pc + start], d0",
+            "move.b [pc + sp.w + start], d0",
+            "move sr, d0",
+            "move d0, ccr",
+            "move 1, sr",
+            "move usp, a0",
+            "move a1, usp",
+            "movea.l a0, a1",
+            "movea.w d0, sp",
+            "lea [a0 + 8], a1",
+            "lea [pc + start], a0",
+            "pea [a0]",
+            "clr.l d0",
+            "neg.b [a0]",
+            "not.w d1",
+            "negx.l d2",
+            "tst.b [a0]",
+            "tas d0",
+            "nbcd [a1]",
+            "addq.l 1, d0",
+            "subq.w 8, a1",
+            "add.l d1, d0",
+            "add.w d0, [a0]",
+            "sub.b [a0], d1",
+            "adda.l a0, a1",
+            "suba.w d0, a2",
+            "cmpa.l [a0], a1",
+            "addi.w x'10, d0",
+            "subi.l x'12345, [a0]",
+            "cmpi.b x'FF, d0",
+            "cmp.l [a0], d0",
+            "eor.l d0, d1",
+            "and.w [a0], d0",
+            "or.b d1, [a0]",
+            "andi x'700, sr",
+            "ori 1, ccr",
+            "eori x'FF, ccr",
+            "addx.l d1, d0",
+            "subx.b [--a1], [--a2]",
+            "abcd [--a1], [--a0]",
+            "sbcd d0, d1",
+            "cmpm.b [a0]!, [a1]!",
+            "mulu.w d1, d0",
+            "muls.w 3, d0",
+            "divs.w [a0], d2",
+            "divu.w d1, d0",
+            "chk.w [a0], d1",
+            "exg d0, d1",
+            "exg a0, a1",
+            "exg d0, a0",
+            "lsl.l 2, d0",
+            "lsr.w 8, d1",
+            "asr.l d1, d0",
+            "rol.b 1, d0",
+            "roxr.w d2, d3",
+            "asl.w [a0]",
+            "btst 3, d0",
+            "bset d1, [a0]",
+            "bclr 7, [a0 + 2]",
+            "bchg d0, d1",
+            "jmp [a0]",
+            "jsr [start]",
+            "jmp [pc + start]",
+            "jsr [x'12345678]",
+            "link a6, -8",
+            "unlk a6",
+            "swap d3",
+            "ext.w d0",
+            "ext.l d1",
+            "movem.l d0-d7/a0-a6, [--sp]",
+            "movem.l [sp]!, d0-d7/a0-a6",
+            "movem.w d0, [a0]",
+            "movem.l d0/d2/a0-a1, [a0 + 8]",
+            "movep.l d0, [a0 + 4]",
+            "movep.w [a0], d1",
+            "dbra d0, start",
+            "dbne d1, start",
+            "seq d0",
+            "st [a0]",
+            "trap x'F",
+            "stop x'2700",
+            "bra start",
+            "beq start",
+            "bsr start",
+            "bne.w start",
+            "bcc x'1000",
+        ][..]),
+        ("m68010", &[
+            "movec vbr, d0",
+            "movec a0, usp",
+            "moves.l d0, [a0]",
+            "moves.b [a1], a2",
+            "rtd 4",
+            "bkpt 3",
+            "move ccr, d0",
+        ][..]),
+        ("m68020", &[
+            "move.l [a0 + d1.w*4], d0",
+            "bfextu [a0], 4, 8, d0",
+            "bfins d1, [a0], d2, 32",
+            "bftst d0, 0, 1",
+            "cas.l d0, d1, [a0]",
+            "cas2.l d0:d1, d2:d3, [a0]:[a1]",
+            "mulu.l d1, d0",
+            "mulu.l d1, d2:d0",
+            "divs.l [a0], d1",
+            "divul d1, d2:d0",
+            "extb d0",
+            "chk2.w [a0], d1",
+            "cmp2.b [a0], a1",
+            "pack [--a0], [--a1], 0",
+            "unpk d0, d1, x'3030",
+            "link.l a6, -8",
+            "trapne",
+            "trapeq.w 1",
+            "tst.l a0",
+            "chk.l d0, d1",
+            "bra.l start",
+            "bra x'12345",
+        ][..]),
+        ("coldfire-a", &[
+            "add.l d1, d0",
+            "addi.l 1, d0",
+            "move.b [a0 + 4], [a1]!",
+            "move.l [a0 + d1.l*4 + 2], d0",
+            "movem.l d0-d7, [sp]",
+            "mulu.l [a0], d1",
+            "remu d1, d2:d0",
+            "rems [a0], d3:d4",
+            "lsl.l d1, d0",
+            "tpf",
+            "tpf.w 1",
+            "halt",
+            "movec d0, cacr",
+            "movec a0, rambar0",
+            "mac.w d1, d2",
+            "mac.l a0, d1 << 1",
+            "msac.w d1.u, d2.u",
+            "move.l d0, acc0",
+            "move.l acc0, d1",
+            "move.l 1, macsr",
+            "move.l d0, mask",
+            "move.l mask, a0",
+            "move.l macsr, ccr",
+        ][..]),
+        ("coldfire-b", &[
+            "cmp.b d0, d1",
+            "mov3q -1, d0",
+            "mov3q 7, [a0]",
+            "mvs.b d0, d1",
+            "mvz.w [a0], d2",
+            "sats d3",
+            "move.w 5, [a0 + 2]",
+            "mac.w d1, d2, acc1",
+            "msac.w d1, d2 >> 1, acc2",
+            "movclr acc3, d0",
+            "bra.l start",
+        ][..]),
+        ("coldfire-c", &[
+            "bitrev d0",
+            "byterev d1",
+            "ff1 d2",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'80"));
+            let decoded    = decoder.decode(&bytes, 0, &label).expect(line);
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let m68000 = (find("m68000").unwrap().new)(&mut NameTable::new());
+    assert_eq!(m68000.decode(&[0xA1, 0x40], 0, &label), None);
+    assert_eq!(m68000.decode(&[0x60, 0xFF, 0x00, 0x00, 0x00, 0x00], 0, &label), None);
+    assert_eq!(m68000.decode(&[0xF2, 0x00], 0, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(m68000.decode(&[0x20, 0x3C, 0x12, 0x34], 0, &label), None);
+}
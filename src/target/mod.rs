@@ -0,0 +1,637 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Target architectures.
+//!
+//! A target teaches the assembler the instructions of an instruction set
+//! architecture.  When the assembler encounters a statement whose name is not
+//! a directive, it asks the target to look up the name as an instruction and,
+//! if found, to encode the instruction.  The parser knows nothing of targets;
+//! a statement is a [`Dir`] whether it names a directive or an instruction.
+
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{BinOp, Dir, Expr, Span, UnOp};
+use crate::name::{Name, NameTable};
+use crate::sim::Cpu;
+
+pub use crate::asm::Value;
+
+pub mod avr;
+pub mod m68k;
+pub mod mos6502;
+pub mod msp430;
+pub mod riscv;
+pub mod spec;
+pub mod thumb;
+pub mod x86;
+pub mod z80;
+
+#[cfg(test)]
+pub mod testing;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set architecture.
+pub trait Target: Debug {
+    /// Returns the name by which `--target` selects the target.
+    fn name(&self) -> &'static str;
+
+    /// Returns the byte order of instructions and data.
+    fn endian(&self) -> Endian;
+
+    /// Returns the required alignment of instructions, in bytes.
+    fn align(&self) -> u64;
+
+    /// Returns the kinds of relocation that instructions of the target use.
+    fn reloc_kinds(&self) -> &'static [RelocKind];
+
+    /// Looks up the instruction with the given `name`.  Returns an index into
+    /// the instruction table of the target, or `None` if the target has no
+    /// such instruction.
+    fn instruction(&self, name: Name) -> Option<usize>;
+
+    /// Looks up the register with the given `name`.  Returns a number that
+    /// identifies the register, the same for each of its names, such as `sp`
+    /// and `a7` on the 68000.  Returns `None` if the target has no such
+    /// register.
+    fn register(&self, _name: &str) -> Option<u32> {
+        None
+    }
+
+    /// Looks up the builtin function with the given `name`, such as `lo` on
+    /// the 6502.  Returns `None` if the target has no such function.
+    fn builtin(&self, _name: Name) -> Option<fn(i64) -> i64> {
+        None
+    }
+
+    /// Parses the operands of the given instruction statement and emits its
+    /// encoding.  `insn` is an index returned by [`Target::instruction`].
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter);
+
+    /// Prepares for a layout pass.  A target that keeps state across the
+    /// statements of a pass resets it here.
+    fn begin_pass(&self) {}
+
+    /// Emits any content that the target deferred to the end of the current
+    /// section.  The assembler calls this method at the end of each pass,
+    /// once for each section, with that section current.
+    fn end_section(&self, _out: &mut dyn Emitter) {}
+
+    /// Decodes the instruction at the beginning of `bytes`, which lies at
+    /// address `addr`.  `label` returns the name by which to refer to an
+    /// address, if any.  Returns `None` if the bytes do not begin with an
+    /// instruction that the target can decode.
+    fn decode(
+        &self,
+        _bytes: &[u8],
+        _addr:  u64,
+        _label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        None
+    }
+
+    /// Returns a new simulated processor for the target, with its registers
+    /// in their initial state, or `None` if the target does not support
+    /// simulation.
+    fn simulator(&self) -> Option<Box<dyn Cpu>> {
+        None
+    }
+}
+
+/// Instruction decoded by [`Target::decode`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Decoded {
+    /// Text of the instruction in ras syntax.
+    pub text: String,
+
+    /// Size of the instruction in bytes.
+    pub size: usize,
+
+    /// Addresses of the targets of the instruction, if it is a jump, call,
+    /// or branch.
+    pub targets: Vec<u64>,
+}
+
+/// Returns the text of a number in ras syntax, as a decoder prints it:
+/// decimal if small, and hexadecimal otherwise.
+pub fn number(value: i64) -> String {
+    match value {
+        -9..=9          => value.to_string(),
+        _ if value < 0  => format!("-x'{:X}", value.unsigned_abs()),
+        _               => format!("x'{:X}", value),
+    }
+}
+
+/// Services that the assembler provides to a target during encoding.
+pub trait Emitter {
+    /// Returns the table of interned names.
+    fn names(&self) -> &NameTable;
+
+    /// Returns the address of the current instruction.
+    fn here(&self) -> u64;
+
+    /// Returns the index of the current section.
+    fn section(&self) -> usize;
+
+    /// Returns whether the current pass is the final pass.  Targets that
+    /// choose among encodings of different sizes must choose the same
+    /// encodings in the final pass as in the pass before it, which
+    /// [`Emitter::relax`] does.
+    fn is_final(&self) -> bool;
+
+    /// Evaluates the given expression.  Returns `None` if evaluation fails,
+    /// in which case the assembler has reported an error.
+    fn eval(&mut self, expr: &Expr<Span>) -> Option<Value>;
+
+    /// Appends the given `bytes` to the current section.
+    fn emit(&mut self, bytes: &[u8]);
+
+    /// Appends zero bytes to the current section until its end is a multiple
+    /// of `align` bytes, and raises the required alignment of the section to
+    /// at least `align`.
+    fn align(&mut self, align: u64);
+
+    /// Requests another layout pass.  A target calls this method when an
+    /// address that it computed differs from the pass before.
+    fn relayout(&mut self);
+
+    /// Chooses among `count` forms of a span-dependent instruction, ordered
+    /// from shortest to longest, and returns the index of the chosen form.
+    /// `fits` returns whether a form can encode the operands of the
+    /// instruction in the current layout.  In the first pass, when forward
+    /// references are not yet known, the assembler chooses the shortest form
+    /// whether or not it fits, and requests another pass if it does not.  In
+    /// later passes, it chooses the shortest form that fits but never a
+    /// shorter form than before, so that layout converges.  If no form fits,
+    /// it chooses the longest, for the target to report the error.
+    ///
+    /// A target calls this method exactly once for each span-dependent
+    /// instruction that lacks an explicit size, in every pass.  An
+    /// instruction with an explicit size uses that size instead.
+    fn relax(&mut self, count: usize, fits: &dyn Fn(usize) -> bool) -> usize;
+
+    /// Records a relocation of the given `kind` for the value of the given
+    /// expression, to be stored at the current end of the current section.
+    /// Call this method before emitting the placeholder bytes.
+    fn reloc(&mut self, expr: &Expr<Span>, kind: RelocKind);
+
+    /// Reports an error at the given `span`.
+    fn error(&mut self, span: Span, msg: &str);
+
+    /// Reports a warning at the given `span`.
+    fn warning(&mut self, span: Span, msg: &str);
+}
+
+// ----------------------------------------------------------------------------
+
+/// Flattens a sum into its terms, each with a flag indicating subtraction.
+pub fn flatten_sum<'e>(expr: &'e Expr<Span>, neg: bool, terms: &mut Vec<(bool, &'e Expr<Span>)>) {
+    match *expr {
+        Expr::Binary(_, BinOp::Add, ref lhs, ref rhs) => {
+            flatten_sum(lhs, neg, terms);
+            flatten_sum(rhs, neg, terms);
+        },
+        Expr::Binary(_, BinOp::Sub, ref lhs, ref rhs) => {
+            flatten_sum(lhs,  neg, terms);
+            flatten_sum(rhs, !neg, terms);
+        },
+        _ => terms.push((neg, expr)),
+    }
+}
+
+/// Rebuilds a sum from the given terms.  Returns `None` if there are none.
+pub fn sum(terms: &[(bool, &Expr<Span>)]) -> Option<Expr<Span>> {
+    let mut iter = terms.iter();
+
+    let mut acc = match *iter.next()? {
+        (false, e) => e.clone(),
+        (true,  e) => Expr::Unary(*e.data(), UnOp::Neg, Box::new(e.clone())),
+    };
+
+    for &(neg, e) in iter {
+        let op = if neg { BinOp::Sub } else { BinOp::Add };
+        acc = Expr::Binary(*e.data(), op, Box::new(acc), Box::new(e.clone()));
+    }
+
+    Some(acc)
+}
+
+/// Returns an expression for the address of the current statement.
+pub fn here(span: Span) -> Expr<Span> {
+    Expr::Ident(span, Name::DOT)
+}
+
+/// Returns an expression for the offset from the instruction `offset` bytes
+/// into the current statement to `target`.
+pub fn pc_rel_expr(target: &Expr<Span>, offset: i64) -> Expr<Span> {
+    let span = *target.data();
+    let op   = if offset < 0 { BinOp::Sub } else { BinOp::Add };
+    let pc   = Expr::Binary(
+        span, op, Box::new(here(span)), Box::new(Expr::Int(span, offset.unsigned_abs()))
+    );
+    Expr::Binary(span, BinOp::Sub, Box::new(target.clone()), Box::new(pc))
+}
+
+/// Returns the offset from the instruction `offset` bytes into the current
+/// statement, which begins at `span`, to the given target value.
+///
+/// The offset is constant if the target is in the same section as the
+/// statement, even if that section is relocatable.
+pub fn pc_rel_value(out: &mut dyn Emitter, span: Span, target: Value, offset: i64) -> Value {
+    let here = out.eval(&here(span)).unwrap_or(Value::Const(0));
+    let pc   = here.add(Value::Const(offset)).unwrap_or(here);
+
+    target.sub(pc).unwrap_or_else(|| {
+        out.error(span, "expression cannot be expressed as a relocation");
+        Value::opaque(target.provisional().wrapping_sub(pc.provisional()))
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+/// Entry in the target registry.
+#[derive(Clone, Copy)]
+pub struct TargetInfo {
+    /// Name by which `--target` selects the target.
+    pub name: &'static str,
+
+    /// Short description for help output.
+    pub description: &'static str,
+
+    /// Creates the target, interning its names in the given table.
+    pub new: fn(&mut NameTable) -> Rc<dyn Target>,
+}
+
+/// Registered targets.
+pub static TARGETS: &[TargetInfo] = &[
+    TargetInfo {
+        name:        "m68000",
+        description: "Motorola 68000",
+        new:         m68k::new_68000,
+    },
+    TargetInfo {
+        name:        "m68010",
+        description: "Motorola 68010",
+        new:         m68k::new_68010,
+    },
+    TargetInfo {
+        name:        "m68020",
+        description: "Motorola 68020",
+        new:         m68k::new_68020,
+    },
+    TargetInfo {
+        name:        "coldfire-a",
+        description: "ColdFire ISA_A with MAC",
+        new:         m68k::new_coldfire_a,
+    },
+    TargetInfo {
+        name:        "coldfire-b",
+        description: "ColdFire ISA_B with EMAC",
+        new:         m68k::new_coldfire_b,
+    },
+    TargetInfo {
+        name:        "coldfire-c",
+        description: "ColdFire ISA_C with EMAC",
+        new:         m68k::new_coldfire_c,
+    },
+    TargetInfo {
+        name:        "rv32i",
+        description: "RISC-V RV32I",
+        new:         riscv::new_rv32i,
+    },
+    TargetInfo {
+        name:        "rv32im",
+        description: "RISC-V RV32IM",
+        new:         riscv::new_rv32im,
+    },
+    TargetInfo {
+        name:        "rv32imac",
+        description: "RISC-V RV32IMAC",
+        new:         riscv::new_rv32imac,
+    },
+    TargetInfo {
+        name:        "rv64i",
+        description: "RISC-V RV64I",
+        new:         riscv::new_rv64i,
+    },
+    TargetInfo {
+        name:        "rv64im",
+        description: "RISC-V RV64IM",
+        new:         riscv::new_rv64im,
+    },
+    TargetInfo {
+        name:        "rv64imac",
+        description: "RISC-V RV64IMAC",
+        new:         riscv::new_rv64imac,
+    },
+    TargetInfo {
+        name:        "6502",
+        description: "MOS 6502",
+        new:         mos6502::new_6502,
+    },
+    TargetInfo {
+        name:        "65c02",
+        description: "WDC 65C02",
+        new:         mos6502::new_65c02,
+    },
+    TargetInfo {
+        name:        "z80",
+        description: "Zilog Z80",
+        new:         z80::new_z80,
+    },
+    TargetInfo {
+        name:        "z80-undoc",
+        description: "Zilog Z80 with undocumented instructions",
+        new:         z80::new_z80_undoc,
+    },
+    TargetInfo {
+        name:        "armv6m",
+        description: "ARMv6-M Thumb (Cortex-M0)",
+        new:         thumb::new_armv6m,
+    },
+    TargetInfo {
+        name:        "armv7m",
+        description: "ARMv7-M Thumb (Cortex-M3)",
+        new:         thumb::new_armv7m,
+    },
+    TargetInfo {
+        name:        "avr2",
+        description: "Atmel AVR (avr2: classic core, up to 8 KiB)",
+        new:         avr::new_avr2,
+    },
+    TargetInfo {
+        name:        "avr25",
+        description: "Atmel AVR (avr25: enhanced core, up to 8 KiB)",
+        new:         avr::new_avr25,
+    },
+    TargetInfo {
+        name:        "avr35",
+        description: "Atmel AVR (avr35: avr25 with jmp/call)",
+        new:         avr::new_avr35,
+    },
+    TargetInfo {
+        name:        "avr4",
+        description: "Atmel AVR (avr4: enhanced core with multiplier, up to 8 KiB)",
+        new:         avr::new_avr4,
+    },
+    TargetInfo {
+        name:        "avr5",
+        description: "Atmel AVR (avr5: enhanced core, up to 64 KiB)",
+        new:         avr::new_avr5,
+    },
+    TargetInfo {
+        name:        "avr51",
+        description: "Atmel AVR (avr51: enhanced core, 128 KiB)",
+        new:         avr::new_avr51,
+    },
+    TargetInfo {
+        name:        "avr6",
+        description: "Atmel AVR (avr6: enhanced core, over 128 KiB)",
+        new:         avr::new_avr6,
+    },
+    TargetInfo {
+        name:        "avrxmega",
+        description: "Atmel AVR XMEGA",
+        new:         avr::new_avrxmega,
+    },
+    TargetInfo {
+        name:        "attiny85",
+        description: "Atmel ATtiny85",
+        new:         avr::new_attiny85,
+    },
+    TargetInfo {
+        name:        "atmega8",
+        description: "Atmel ATmega8",
+        new:         avr::new_atmega8,
+    },
+    TargetInfo {
+        name:        "atmega328p",
+        description: "Atmel ATmega328P",
+        new:         avr::new_atmega328p,
+    },
+    TargetInfo {
+        name:        "atmega2560",
+        description: "Atmel ATmega2560",
+        new:         avr::new_atmega2560,
+    },
+    TargetInfo {
+        name:        "msp430",
+        description: "TI MSP430",
+        new:         msp430::new_msp430,
+    },
+    TargetInfo {
+        name:        "msp430x",
+        description: "TI MSP430X",
+        new:         msp430::new_msp430x,
+    },
+    TargetInfo {
+        name:        "8086",
+        description: "Intel 8086 (16-bit real mode)",
+        new:         x86::new_8086,
+    },
+    TargetInfo {
+        name:        "80186",
+        description: "Intel 80186 (16-bit real mode)",
+        new:         x86::new_80186,
+    },
+    TargetInfo {
+        name:        "80386",
+        description: "Intel 80386 (16-bit real mode)",
+        new:         x86::new_80386,
+    },
+];
+
+/// Returns the registered target with the given `name`, if any.
+pub fn find(name: &str) -> Option<&'static TargetInfo> {
+    TARGETS.iter().find(|t| t.name == name)
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Unit;
+    use crate::lang::ast::Arg;
+    use crate::link::{link, Layout};
+    use crate::session::Session;
+
+    /// Toy target with a 16-bit `nop`, a 32-bit `jmp <addr16>`, and a `br
+    /// <addr>` that relaxes from a 16-bit form with an 8-bit displacement to
+    /// the encoding of `jmp`.
+    #[derive(Debug)]
+    struct Toy {
+        nop: Name,
+        jmp: Name,
+        br:  Name,
+    }
+
+    impl Target for Toy {
+        fn name  (&self) -> &'static str { "toy" }
+        fn endian(&self) -> Endian       { Endian::Little }
+        fn align (&self) -> u64          { 2 }
+
+        fn reloc_kinds(&self) -> &'static [RelocKind] {
+            &[RelocKind::INT16]
+        }
+
+        fn instruction(&self, name: Name) -> Option<usize> {
+            [self.nop, self.jmp, self.br].iter().position(|&n| n == name)
+        }
+
+        fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+            match (insn, &stmt.args[..]) {
+                (0, []) => out.emit(&[0x00, 0x00]),
+                (1, [Arg::Expr(e)]) => {
+                    out.emit(&[0x01, 0x00]);
+                    match out.eval(e) {
+                        Some(Value::Const(v)) => out.emit(&(v as u16).to_le_bytes()),
+                        Some(Value::Reloc(_)) => {
+                            out.reloc(e, RelocKind::INT16);
+                            out.emit(&[0, 0]);
+                        },
+                        None => out.emit(&[0, 0]),
+                    }
+                },
+                (2, [Arg::Expr(e)]) => {
+                    let disp = match out.eval(e) {
+                        Some(Value::Const(v)) => v - out.here() as i64 - 2,
+                        _                     => i64::MAX,
+                    };
+                    match out.relax(2, &|form| form == 1 || (-128..=127).contains(&disp)) {
+                        0 => out.emit(&[0x02, disp as u8]),
+                        _ => self.encode(1, stmt, out),
+                    }
+                },
+                _ => out.error(stmt.data, "invalid operands"),
+            }
+        }
+    }
+
+    const TOY: TargetInfo = TargetInfo {
+        name:        "toy",
+        description: "Toy target for tests",
+        new:         |names| Rc::new(Toy {
+            nop: names.add("nop"),
+            jmp: names.add("jmp"),
+            br:  names.add("br"),
+        }),
+    };
+
+    fn assemble(session: &mut Session, content: &str) -> Unit {
+        session.set_quiet(true);
+        session.set_target(&TOY);
+        session.assemble("test.s", content)
+    }
+
+    #[test]
+    fn encode() {
+        let mut session = Session::new();
+
+        let mut unit = assemble(&mut session, "
+            .org x'100
+        a:  nop
+            jmp a
+            jmp b
+            .section .text2
+        b:  nop
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.endian, Endian::Little);
+        assert_eq!(unit.relocs.len(), 1);
+
+        let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(program.sections[0].data, [0, 0, 1, 0, 0x00, 0x01, 1, 0, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn relax_growth() {
+        let mut session = Session::new();
+
+        // The second branch grows in the second pass, which pushes the
+        // target of the first out of range in the third.
+        let unit = assemble(&mut session, "
+            .org x'100
+            br c
+            br d
+            .skip 124
+        c:  .skip 200
+        d:  br c
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data[..8], [1, 0, 0x84, 0x01, 1, 0, 0x4C, 0x02]);
+        assert_eq!(unit.sections[0].data[332..], [1, 0, 0x84, 0x01]);
+    }
+
+    #[test]
+    fn relax_fixpoint() {
+        let mut session = Session::new();
+
+        // The short form does not reach the target, but the long form moves
+        // the target into reach of the short form.  Forms never shrink, so
+        // layout converges on the long form.
+        let unit = assemble(&mut session, "
+            .org x'100
+        s:  br s + 2 + 300 - (e - s) * 50
+        e:  br e
+        ");
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].data, [1, 0, 0x66, 0x01, 2, 0xFE]);
+    }
+
+    #[test]
+    fn relax_chain() {
+        let mut session = Session::new();
+
+        // Each branch grows only after the next one does, so layout takes a
+        // pass per branch, more passes than layout allows without growth.
+        let mut source = String::from(".org x'100\n");
+        for i in 0..40 {
+            source += &format!("br l{}\n", i);
+            if i > 0 {
+                source += &format!("l{}:\n", i - 1);
+            }
+            source += ".skip 124\n";
+        }
+        source += ".skip 4\nl39:\n";
+
+        let unit = assemble(&mut session, &source);
+
+        assert_eq!(session.error_count(), 0);
+        assert_eq!(unit.sections[0].size, 40 * (4 + 124) + 4);
+    }
+
+    #[test]
+    fn misaligned() {
+        let mut session = Session::new();
+
+        assemble(&mut session, ".int8 0\nnop\nfoo");
+
+        assert_eq!(session.error_count(), 2);
+        assert_eq!(session.diagnostics()[0].msg, "instruction is not aligned to 2 bytes");
+        assert_eq!(session.diagnostics()[1].msg, "unknown instruction 'foo'");
+    }
+}
@@ -0,0 +1,303 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+enum Operand {
+    /// Accumulator: `a`.
+    Acc,
+
+    /// Immediate value or target address.
+    Imm(Expr<Span>),
+
+    /// Memory at an address, with an optional index: `[addr + x]`.
+    Direct(Expr<Span>, Index),
+
+    /// Memory at an address read from the zero page, with an optional index:
+    /// `[[zp + x]]` or `[[zp] + y]`.
+    Indirect(Expr<Span>, Index),
+}
+
+/// Index register of an operand.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Index {
+    None,
+    X,
+    Y,
+}
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.  If `wide` is true, a memory operand takes the absolute form.
+pub fn encode(entry: &Entry, wide: bool, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let span = stmt.data;
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| operand(arg, out))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    let mode = match ops[..] {
+        [] if entry.opcode(Mode::Imp).is_some() => Mode::Imp,
+        [] | [Operand::Acc]                     => Mode::Acc,
+
+        [Operand::Imm(_)] => {
+            [Mode::Imm, Mode::Rel, Mode::Jump]
+                .into_iter()
+                .find(|&m| entry.opcode(m).is_some())
+                .unwrap_or(Mode::Imm)
+        },
+
+        [Operand::Direct(ref addr, index)] => {
+            let (jump, zp, abs) = match index {
+                Index::None => (Some(Mode::JumpInd),  Mode::Zp,  Mode::Abs),
+                Index::X    => (Some(Mode::JumpIndX), Mode::ZpX, Mode::AbsX),
+                Index::Y    => (None,                 Mode::ZpY, Mode::AbsY),
+            };
+            match jump.filter(|&m| entry.opcode(m).is_some()) {
+                Some(jump) => jump,
+                None       => choose(entry, wide, zp, abs, addr, out),
+            }
+        },
+
+        [Operand::Indirect(_, Index::X)]    => Mode::IndX,
+        [Operand::Indirect(_, Index::Y)]    => Mode::IndY,
+        [Operand::Indirect(_, Index::None)] => Mode::ZpInd,
+
+        [Operand::Direct(_, Index::None), Operand::Imm(_)] => Mode::ZpRel,
+
+        _ => return out.error(span, "invalid operands"),
+    };
+
+    if wide && !matches!(mode, Mode::Abs | Mode::AbsX | Mode::AbsY) {
+        return out.error(span, "suffix .w requires an absolute address operand");
+    }
+
+    let opcode = match entry.opcode(mode) {
+        Some(opcode) => opcode,
+        None         => return out.error(span, "invalid addressing mode"),
+    };
+
+    out.emit(&[opcode]);
+
+    match (mode, &ops[..]) {
+        (Mode::Imm, [Operand::Imm(e)]) => {
+            put(e, RelocKind::INT8, out);
+        },
+        (Mode::Jump, [Operand::Imm(e)]) | (Mode::Abs | Mode::AbsX | Mode::AbsY
+            | Mode::JumpInd | Mode::JumpIndX, [Operand::Direct(e, _)]) => {
+            put(e, RelocKind::INT16, out);
+        },
+        (Mode::Zp | Mode::ZpX | Mode::ZpY, [Operand::Direct(e, _)])
+            | (Mode::IndX | Mode::IndY | Mode::ZpInd, [Operand::Indirect(e, _)]) => {
+            put(e, ZP, out);
+        },
+        (Mode::Rel, [Operand::Imm(target)]) => {
+            branch(span, target, 2, out);
+        },
+        (Mode::ZpRel, [Operand::Direct(zp, _), Operand::Imm(target)]) => {
+            put(zp, ZP, out);
+            branch(span, target, 3, out);
+        },
+        _ => (),
+    }
+}
+
+/// Chooses between the zero page mode `zp` and the absolute mode `abs` for
+/// the address `addr`.  If the instruction has both and `wide` is false, the
+/// choice relaxes from `zp` to `abs` when the address is not known to be a
+/// zero page address.  Otherwise, returns whichever mode the instruction has,
+/// or `abs` if `wide` is true.
+fn choose(
+    entry: &Entry,
+    wide:  bool,
+    zp:    Mode,
+    abs:   Mode,
+    addr:  &Expr<Span>,
+    out:   &mut dyn Emitter,
+) -> Mode {
+    match (entry.opcode(zp), entry.opcode(abs)) {
+        (Some(_), Some(_)) if !wide => {
+            let fits = matches!(out.eval(addr), Some(Value::Const(0..=0xFF)));
+            match out.relax(2, &|form| form == 1 || fits) {
+                0 => zp,
+                _ => abs,
+            }
+        },
+        (Some(_), None) if !wide => zp,
+        _                        => abs,
+    }
+}
+
+/// Emits a field of the given `kind` holding the value of `expr`, or records
+/// a relocation if the linker must compute the value.
+fn put(expr: &Expr<Span>, kind: RelocKind, out: &mut dyn Emitter) {
+    let field = match out.eval(expr) {
+        Some(Value::Const(v)) => match (kind.apply)(v, 0) {
+            Ok(field) => field,
+            Err(msg)  => { out.error(*expr.data(), &msg); 0 },
+        },
+        Some(Value::Reloc(_)) => {
+            out.reloc(expr, kind);
+            0
+        },
+        None => 0,
+    };
+    out.emit(&field.to_le_bytes()[..kind.size]);
+}
+
+/// Emits the offset of a branch to `target` from the end of the instruction,
+/// which is `size` bytes long.
+fn branch(span: Span, target: &Expr<Span>, size: i64, out: &mut dyn Emitter) {
+    let value = match out.eval(target) {
+        Some(value) => pc_rel_value(out, span, value, size),
+        None        => return out.emit(&[0]),
+    };
+
+    let field = match value {
+        Value::Const(v) => match apply_rel8(v) {
+            Ok(field) => field,
+            Err(msg)  => { out.error(*target.data(), &msg); 0 },
+        },
+        Value::Reloc(_) => {
+            out.reloc(&pc_rel_expr(target, size), REL8);
+            0
+        },
+    };
+    out.emit(&[field]);
+}
+
+fn apply_rel8(value: i64) -> Result<u8, String> {
+    (REL8.apply)(value, 0).map(|field| field as u8)
+}
+
+// ----------------------------------------------------------------------------
+
+fn operand(arg: &Arg<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let expr = match *arg {
+        Arg::Expr(ref e) => e,
+        Arg::Unknown(span) => {
+            out.error(span, "expected: operand");
+            return None;
+        },
+    };
+
+    match *expr {
+        Expr::Ident(_, name) if register(&out.names()[name]) == Some(Reg::A) => Some(Operand::Acc),
+        Expr::Deref(_, ref inner, false) => memory(*expr.data(), inner, out),
+        _ => Some(Operand::Imm(expr.clone())),
+    }
+}
+
+/// Parses the contents of a memory operand `[...]`.
+fn memory(span: Span, inner: &Expr<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let (terms, index) = split_index(inner, out)?;
+
+    // Indirect: a single term that is itself a memory operand
+    if let [(false, &Expr::Deref(_, ref ptr, false))] = terms[..] {
+        return match index {
+            Index::None => match split_index(ptr, out)? {
+                (terms, Index::X) => Some(Operand::Indirect(sum(&terms)?, Index::X)),
+                (_,     Index::None) => Some(Operand::Indirect((**ptr).clone(), Index::None)),
+                (_,     Index::Y) => fail(span, "invalid addressing mode", out),
+            },
+            Index::Y => Some(Operand::Indirect((**ptr).clone(), Index::Y)),
+            Index::X => fail(span, "invalid addressing mode", out),
+        };
+    }
+
+    match sum(&terms) {
+        Some(addr) => Some(Operand::Direct(addr, index)),
+        None       => fail(span, "expected: address", out),
+    }
+}
+
+/// Terms of a sum, each with a flag indicating subtraction.
+type Terms<'e> = Vec<(bool, &'e Expr<Span>)>;
+
+/// Splits the given sum into its terms other than an index register, and
+/// the index register.
+fn split_index<'e>(expr: &'e Expr<Span>, out: &mut dyn Emitter) -> Option<(Terms<'e>, Index)> {
+    let mut terms = vec![];
+    flatten_sum(expr, false, &mut terms);
+
+    let mut index = Index::None;
+    let mut rest  = vec![];
+
+    for (neg, term) in terms {
+        let reg = match *term {
+            Expr::Ident(_, name) => register(&out.names()[name]),
+            _                    => None,
+        };
+        let found = match reg {
+            Some(Reg::X) => Index::X,
+            Some(Reg::Y) => Index::Y,
+            Some(Reg::A) => return fail(*term.data(), "invalid index register", out),
+            None         => { rest.push((neg, term)); continue; },
+        };
+        if neg || index != Index::None {
+            return fail(*term.data(), "invalid index register", out);
+        }
+        index = found;
+    }
+
+    Some((rest, index))
+}
+
+/// Registers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Reg {
+    A,
+    X,
+    Y,
+}
+
+/// Returns the register with the given name, if any.
+fn register(name: &str) -> Option<Reg> {
+    match name {
+        "a" | "A" => Some(Reg::A),
+        "x" | "X" => Some(Reg::X),
+        "y" | "Y" => Some(Reg::Y),
+        _         => None,
+    }
+}
+
+/// Returns a number that identifies the register with the given name, the
+/// same for each name of the register, if any.
+pub(super) fn register_key(name: &str) -> Option<u32> {
+    register(name).map(|r| r as u32)
+}
+
+fn fail<T>(span: Span, msg: &str, out: &mut dyn Emitter) -> Option<T> {
+    out.error(span, msg);
+    None
+}
@@ -0,0 +1,195 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! MOS 6502 family.
+//!
+//! Instructions use the standard mnemonics.  Operands use ras syntax:
+//!
+//! | Mode                          | Standard            | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | accumulator                   | `asl a`             | `asl a` or `asl`
+//! | immediate                     | `lda #42`           | `lda 42`
+//! | zero page or absolute         | `lda $10`           | `lda [x'10]`
+//! | indexed                       | `lda $10,x`         | `lda [x'10 + x]`
+//! | indexed indirect              | `lda ($10,x)`       | `lda [[x'10 + x]]`
+//! | indirect indexed              | `lda ($10),y`       | `lda [[x'10] + y]`
+//! | zero page indirect (65C02)    | `lda ($10)`         | `lda [[x'10]]`
+//! | jump                          | `jmp $1000`         | `jmp x'1000`
+//! | jump indirect                 | `jmp ($1000)`       | `jmp [x'1000]`
+//! | jump indexed indirect (65C02) | `jmp ($1000,x)`     | `jmp [x'1000 + x]`
+//!
+//! A memory operand takes the zero page form if the instruction has one and
+//! the address is known to be below `x'100`; otherwise it takes the absolute
+//! form.  The choice relaxes across layout passes, and the suffix `.w`, as in
+//! `lda.w [x'10]`, forces the absolute form.  The builtin functions `lo` and
+//! `hi` extract the low and high bytes of a value, as `<` and `>` do in other
+//! assemblers.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::spec::Table;
+use super::{Decoded, Emitter, Target};
+
+mod encode;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: NMOS 6502 instructions.
+pub const M6502: u32 = 1 << 0;
+
+/// Instruction set feature: 65C02 additions.
+pub const M65C02: u32 = 1 << 1;
+
+/// Member of the 6502 family.
+#[derive(Debug)]
+pub struct Mos6502 {
+    name:  &'static str,
+    isa:   u32,
+    table: Vec<Entry>,
+    index: HashMap<Name, usize>,
+    spec:  Table,
+}
+
+impl Mos6502 {
+    /// Creates a new [`Mos6502`] target with the given `name` and instruction
+    /// set features `isa`, interning its mnemonics in `names`.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        let mut table: Vec<Entry> = vec![];
+        let mut index = HashMap::new();
+        let mut wide  = vec![];
+
+        // Merge the opcodes of each mnemonic that the target has
+        for (mnemonic, row_isa, mode, opcode) in table::build() {
+            if row_isa & isa == 0 {
+                continue;
+            }
+
+            let insn = *index.entry(names.add(mnemonic)).or_insert_with(|| {
+                table.push(Entry::new());
+                (table.len() - 1) << 1
+            });
+            index.insert(names.add(&mnemonic.to_uppercase()), insn);
+            table[insn >> 1].opcodes[mode as usize] = Some(opcode);
+
+            if matches!(mode, Mode::Abs | Mode::AbsX | Mode::AbsY) {
+                wide.push((mnemonic, insn | 1));
+            }
+        }
+
+        // The .w suffix forces an absolute address
+        for (mnemonic, insn) in wide {
+            let lower = format!("{}.w", mnemonic);
+            index.insert(names.add(&lower),                insn);
+            index.insert(names.add(&lower.to_uppercase()), insn);
+        }
+
+        let spec = Table::new(&SPEC, names);
+
+        Self { name, isa, table, index, spec }
+    }
+}
+
+impl Target for Mos6502 {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        1
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[ZP, REL8, RelocKind::INT8, RelocKind::INT16]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        encode::register_key(name)
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        match name {
+            Name::LO => Some(|v| v      & 0xFF),
+            Name::HI => Some(|v| v >> 8 & 0xFF),
+            _        => None,
+        }
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        encode::encode(&self.table[insn >> 1], insn & 1 != 0, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        self.spec.decode(self.isa, bytes, addr, label)
+    }
+}
+
+/// Creates an NMOS 6502 target.
+pub fn new_6502(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Mos6502::new(names, "6502", M6502))
+}
+
+/// Creates a 65C02 target.
+pub fn new_65c02(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Mos6502::new(names, "65c02", M6502 | M65C02))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Zero page address.
+pub const ZP: RelocKind = RelocKind { name: "6502-zp", size: 1, apply: apply_zp };
+
+/// 8-bit signed branch offset.
+pub const REL8: RelocKind = RelocKind { name: "6502-rel8", size: 1, apply: apply_rel8 };
+
+fn apply_zp(value: i64, _: u64) -> Result<u64, String> {
+    match value {
+        0..=0xFF => Ok(value as u64),
+        _        => Err(format!("address {:#X} out of range for zero page", value)),
+    }
+}
+
+fn apply_rel8(value: i64, _: u64) -> Result<u64, String> {
+    match value {
+        -128..=127 => Ok(value as u64 & 0xFF),
+        _          => Err(format!("branch offset {} out of range", value)),
+    }
+}
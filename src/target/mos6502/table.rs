@@ -0,0 +1,478 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction set specification.
+//!
+//! The specification gives each opcode as a form of its mnemonic.  The
+//! decoder derives from it, and so does the table of opcodes by mnemonic and
+//! addressing mode from which the encoder chooses.  The encoder remains
+//! hand-written, as it chooses between the zero page and absolute forms as
+//! layout converges.
+//!
+//! An absolute form of an instruction that also has a zero page form takes
+//! an address of at least `x'100`; a second form with the suffix `.w` takes
+//! the addresses below, which only `.w` encodes in the absolute form.
+
+use crate::asm::Endian;
+use crate::target::spec::{row, Field, Kind, Row, Spec};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Addressing modes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// No operand.
+    Imp,
+
+    /// `a`
+    Acc,
+
+    /// `42`
+    Imm,
+
+    /// `[zp]`
+    Zp,
+
+    /// `[zp + x]`
+    ZpX,
+
+    /// `[zp + y]`
+    ZpY,
+
+    /// `[abs]`
+    Abs,
+
+    /// `[abs + x]`
+    AbsX,
+
+    /// `[abs + y]`
+    AbsY,
+
+    /// `[[zp + x]]`
+    IndX,
+
+    /// `[[zp] + y]`
+    IndY,
+
+    /// `[[zp]]` (65C02)
+    ZpInd,
+
+    /// `target`, for `jmp` and `jsr`.
+    Jump,
+
+    /// `[abs]`, for `jmp`.
+    JumpInd,
+
+    /// `[abs + x]`, for `jmp` (65C02).
+    JumpIndX,
+
+    /// `target`, for a branch.
+    Rel,
+
+    /// `[zp], target`, for `bbr` and `bbs` (65C02).
+    ZpRel,
+}
+
+/// Count of addressing modes.
+pub const MODES: usize = Mode::ZpRel as usize + 1;
+
+/// Entry in the instruction table: the opcodes of a mnemonic.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Opcode for each addressing mode, if the instruction has the mode.
+    pub opcodes: [Option<u8>; MODES],
+}
+
+impl Entry {
+    pub fn new() -> Self {
+        Self { opcodes: [None; MODES] }
+    }
+
+    /// Returns the opcode for the given mode, if the instruction has it.
+    pub fn opcode(&self, mode: Mode) -> Option<u8> {
+        self.opcodes[mode as usize]
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the opcodes of all features, as rows of mnemonic, feature, mode,
+/// and opcode.
+pub fn build() -> impl Iterator<Item = (&'static str, u32, Mode, u8)> {
+    SPEC.rows.iter().filter_map(|row| {
+        let mode   = mode(row.operands)?;
+        let opcode = u8::from_str_radix(&row.encoding[..8], 2).expect("opcode bits");
+        Some((row.names, row.isa, mode, opcode))
+    })
+}
+
+/// Returns the addressing mode of the given operand template, or `None` for
+/// a form that only the `.w` suffix selects.
+fn mode(operands: &str) -> Option<Mode> {
+    use Mode::*;
+
+    Some(match operands {
+        ""          => Imp,
+        "a"         => Acc,
+        "N"         => Imm,
+        "[Z]"       => Zp,
+        "[Z + x]"   => ZpX,
+        "[Z + y]"   => ZpY,
+        "[A]"       => Abs,
+        "[A + x]"   => AbsX,
+        "[A + y]"   => AbsY,
+        "[M + y]"   => AbsY,
+        "[[Z + x]]" => IndX,
+        "[[Z] + y]" => IndY,
+        "[[Z]]"     => ZpInd,
+        "T"         => Jump,
+        "[P]"       => JumpInd,
+        "[P + x]"   => JumpIndX,
+        "E"         => Rel,
+        "[Z], F"    => ZpRel,
+        _           => return None,
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set specification.
+pub static SPEC: Spec = Spec {
+    word:       1,
+    endian:     Endian::Little,
+    word_order: Endian::Big,
+    registers:  &[],
+    fields:     FIELDS,
+    rows:       ROWS,
+};
+
+/// Returns an integer field whose bits lie in the given order.
+const fn int(
+    name:    &'static str,
+    letters: &'static str,
+    min:     i64,
+    max:     i64,
+    order:   &'static str,
+) -> Field {
+    let kind = Kind::Int { min, max, invert: 0 };
+    Field { name, letters, kind, order, what: "value", reloc: None }
+}
+
+/// Returns a branch offset field for a branch whose offset is from `origin`
+/// bytes after the start of the instruction.
+const fn rel(name: &'static str, letters: &'static str, origin: i64) -> Field {
+    let kind = Kind::Rel { origin, scale: 1 };
+    Field { name, letters, kind, order: "", what: "offset", reloc: None }
+}
+
+/// Bits of a 16-bit value, which the low byte holds first.
+const WORD: &str = "7:0|15:8";
+
+static FIELDS: &[Field] = &[
+    int("N", "n", 0,     0xFF,   ""),
+    int("Z", "z", 0,     0xFF,   ""),
+    int("A", "a", 0x100, 0xFFFF, WORD),
+    int("W", "w", 0,     0xFF,   WORD),
+    int("M", "m", 0,     0xFFFF, WORD),
+    int("P", "p", 0,     0xFFFF, WORD),
+    Field {
+        name:    "T",
+        letters: "t",
+        kind:    Kind::Abs { scale: 1 },
+        order:   WORD,
+        what:    "address",
+        reloc:   None,
+    },
+    rel("E", "e", 2),
+    rel("F", "f", 3),
+];
+
+static ROWS: &[Row] = &[
+    // Arithmetic and logic
+    row("ora",    "N",         "00001001 nnnnnnnn",          M6502),
+    row("ora",    "[Z]",       "00000101 zzzzzzzz",          M6502),
+    row("ora",    "[Z + x]",   "00010101 zzzzzzzz",          M6502),
+    row("ora",    "[A]",       "00001101 aaaaaaaa aaaaaaaa", M6502),
+    row("ora.w",  "[W]",       "00001101 wwwwwwww wwwwwwww", M6502),
+    row("ora",    "[A + x]",   "00011101 aaaaaaaa aaaaaaaa", M6502),
+    row("ora.w",  "[W + x]",   "00011101 wwwwwwww wwwwwwww", M6502),
+    row("ora",    "[M + y]",   "00011001 mmmmmmmm mmmmmmmm", M6502),
+    row("ora",    "[[Z + x]]", "00000001 zzzzzzzz",          M6502),
+    row("ora",    "[[Z] + y]", "00010001 zzzzzzzz",          M6502),
+    row("ora",    "[[Z]]",     "00010010 zzzzzzzz",          M65C02),
+    row("and",    "N",         "00101001 nnnnnnnn",          M6502),
+    row("and",    "[Z]",       "00100101 zzzzzzzz",          M6502),
+    row("and",    "[Z + x]",   "00110101 zzzzzzzz",          M6502),
+    row("and",    "[A]",       "00101101 aaaaaaaa aaaaaaaa", M6502),
+    row("and.w",  "[W]",       "00101101 wwwwwwww wwwwwwww", M6502),
+    row("and",    "[A + x]",   "00111101 aaaaaaaa aaaaaaaa", M6502),
+    row("and.w",  "[W + x]",   "00111101 wwwwwwww wwwwwwww", M6502),
+    row("and",    "[M + y]",   "00111001 mmmmmmmm mmmmmmmm", M6502),
+    row("and",    "[[Z + x]]", "00100001 zzzzzzzz",          M6502),
+    row("and",    "[[Z] + y]", "00110001 zzzzzzzz",          M6502),
+    row("and",    "[[Z]]",     "00110010 zzzzzzzz",          M65C02),
+    row("eor",    "N",         "01001001 nnnnnnnn",          M6502),
+    row("eor",    "[Z]",       "01000101 zzzzzzzz",          M6502),
+    row("eor",    "[Z + x]",   "01010101 zzzzzzzz",          M6502),
+    row("eor",    "[A]",       "01001101 aaaaaaaa aaaaaaaa", M6502),
+    row("eor.w",  "[W]",       "01001101 wwwwwwww wwwwwwww", M6502),
+    row("eor",    "[A + x]",   "01011101 aaaaaaaa aaaaaaaa", M6502),
+    row("eor.w",  "[W + x]",   "01011101 wwwwwwww wwwwwwww", M6502),
+    row("eor",    "[M + y]",   "01011001 mmmmmmmm mmmmmmmm", M6502),
+    row("eor",    "[[Z + x]]", "01000001 zzzzzzzz",          M6502),
+    row("eor",    "[[Z] + y]", "01010001 zzzzzzzz",          M6502),
+    row("eor",    "[[Z]]",     "01010010 zzzzzzzz",          M65C02),
+    row("adc",    "N",         "01101001 nnnnnnnn",          M6502),
+    row("adc",    "[Z]",       "01100101 zzzzzzzz",          M6502),
+    row("adc",    "[Z + x]",   "01110101 zzzzzzzz",          M6502),
+    row("adc",    "[A]",       "01101101 aaaaaaaa aaaaaaaa", M6502),
+    row("adc.w",  "[W]",       "01101101 wwwwwwww wwwwwwww", M6502),
+    row("adc",    "[A + x]",   "01111101 aaaaaaaa aaaaaaaa", M6502),
+    row("adc.w",  "[W + x]",   "01111101 wwwwwwww wwwwwwww", M6502),
+    row("adc",    "[M + y]",   "01111001 mmmmmmmm mmmmmmmm", M6502),
+    row("adc",    "[[Z + x]]", "01100001 zzzzzzzz",          M6502),
+    row("adc",    "[[Z] + y]", "01110001 zzzzzzzz",          M6502),
+    row("adc",    "[[Z]]",     "01110010 zzzzzzzz",          M65C02),
+    row("sta",    "[Z]",       "10000101 zzzzzzzz",          M6502),
+    row("sta",    "[Z + x]",   "10010101 zzzzzzzz",          M6502),
+    row("sta",    "[A]",       "10001101 aaaaaaaa aaaaaaaa", M6502),
+    row("sta.w",  "[W]",       "10001101 wwwwwwww wwwwwwww", M6502),
+    row("sta",    "[A + x]",   "10011101 aaaaaaaa aaaaaaaa", M6502),
+    row("sta.w",  "[W + x]",   "10011101 wwwwwwww wwwwwwww", M6502),
+    row("sta",    "[M + y]",   "10011001 mmmmmmmm mmmmmmmm", M6502),
+    row("sta",    "[[Z + x]]", "10000001 zzzzzzzz",          M6502),
+    row("sta",    "[[Z] + y]", "10010001 zzzzzzzz",          M6502),
+    row("sta",    "[[Z]]",     "10010010 zzzzzzzz",          M65C02),
+    row("lda",    "N",         "10101001 nnnnnnnn",          M6502),
+    row("lda",    "[Z]",       "10100101 zzzzzzzz",          M6502),
+    row("lda",    "[Z + x]",   "10110101 zzzzzzzz",          M6502),
+    row("lda",    "[A]",       "10101101 aaaaaaaa aaaaaaaa", M6502),
+    row("lda.w",  "[W]",       "10101101 wwwwwwww wwwwwwww", M6502),
+    row("lda",    "[A + x]",   "10111101 aaaaaaaa aaaaaaaa", M6502),
+    row("lda.w",  "[W + x]",   "10111101 wwwwwwww wwwwwwww", M6502),
+    row("lda",    "[M + y]",   "10111001 mmmmmmmm mmmmmmmm", M6502),
+    row("lda",    "[[Z + x]]", "10100001 zzzzzzzz",          M6502),
+    row("lda",    "[[Z] + y]", "10110001 zzzzzzzz",          M6502),
+    row("lda",    "[[Z]]",     "10110010 zzzzzzzz",          M65C02),
+    row("cmp",    "N",         "11001001 nnnnnnnn",          M6502),
+    row("cmp",    "[Z]",       "11000101 zzzzzzzz",          M6502),
+    row("cmp",    "[Z + x]",   "11010101 zzzzzzzz",          M6502),
+    row("cmp",    "[A]",       "11001101 aaaaaaaa aaaaaaaa", M6502),
+    row("cmp.w",  "[W]",       "11001101 wwwwwwww wwwwwwww", M6502),
+    row("cmp",    "[A + x]",   "11011101 aaaaaaaa aaaaaaaa", M6502),
+    row("cmp.w",  "[W + x]",   "11011101 wwwwwwww wwwwwwww", M6502),
+    row("cmp",    "[M + y]",   "11011001 mmmmmmmm mmmmmmmm", M6502),
+    row("cmp",    "[[Z + x]]", "11000001 zzzzzzzz",          M6502),
+    row("cmp",    "[[Z] + y]", "11010001 zzzzzzzz",          M6502),
+    row("cmp",    "[[Z]]",     "11010010 zzzzzzzz",          M65C02),
+    row("sbc",    "N",         "11101001 nnnnnnnn",          M6502),
+    row("sbc",    "[Z]",       "11100101 zzzzzzzz",          M6502),
+    row("sbc",    "[Z + x]",   "11110101 zzzzzzzz",          M6502),
+    row("sbc",    "[A]",       "11101101 aaaaaaaa aaaaaaaa", M6502),
+    row("sbc.w",  "[W]",       "11101101 wwwwwwww wwwwwwww", M6502),
+    row("sbc",    "[A + x]",   "11111101 aaaaaaaa aaaaaaaa", M6502),
+    row("sbc.w",  "[W + x]",   "11111101 wwwwwwww wwwwwwww", M6502),
+    row("sbc",    "[M + y]",   "11111001 mmmmmmmm mmmmmmmm", M6502),
+    row("sbc",    "[[Z + x]]", "11100001 zzzzzzzz",          M6502),
+    row("sbc",    "[[Z] + y]", "11110001 zzzzzzzz",          M6502),
+    row("sbc",    "[[Z]]",     "11110010 zzzzzzzz",          M65C02),
+
+    // Shifts and rotates
+    row("asl",    "a",         "00001010",                   M6502),
+    row("asl",    "[Z]",       "00000110 zzzzzzzz",          M6502),
+    row("asl",    "[Z + x]",   "00010110 zzzzzzzz",          M6502),
+    row("asl",    "[A]",       "00001110 aaaaaaaa aaaaaaaa", M6502),
+    row("asl.w",  "[W]",       "00001110 wwwwwwww wwwwwwww", M6502),
+    row("asl",    "[A + x]",   "00011110 aaaaaaaa aaaaaaaa", M6502),
+    row("asl.w",  "[W + x]",   "00011110 wwwwwwww wwwwwwww", M6502),
+    row("rol",    "a",         "00101010",                   M6502),
+    row("rol",    "[Z]",       "00100110 zzzzzzzz",          M6502),
+    row("rol",    "[Z + x]",   "00110110 zzzzzzzz",          M6502),
+    row("rol",    "[A]",       "00101110 aaaaaaaa aaaaaaaa", M6502),
+    row("rol.w",  "[W]",       "00101110 wwwwwwww wwwwwwww", M6502),
+    row("rol",    "[A + x]",   "00111110 aaaaaaaa aaaaaaaa", M6502),
+    row("rol.w",  "[W + x]",   "00111110 wwwwwwww wwwwwwww", M6502),
+    row("lsr",    "a",         "01001010",                   M6502),
+    row("lsr",    "[Z]",       "01000110 zzzzzzzz",          M6502),
+    row("lsr",    "[Z + x]",   "01010110 zzzzzzzz",          M6502),
+    row("lsr",    "[A]",       "01001110 aaaaaaaa aaaaaaaa", M6502),
+    row("lsr.w",  "[W]",       "01001110 wwwwwwww wwwwwwww", M6502),
+    row("lsr",    "[A + x]",   "01011110 aaaaaaaa aaaaaaaa", M6502),
+    row("lsr.w",  "[W + x]",   "01011110 wwwwwwww wwwwwwww", M6502),
+    row("ror",    "a",         "01101010",                   M6502),
+    row("ror",    "[Z]",       "01100110 zzzzzzzz",          M6502),
+    row("ror",    "[Z + x]",   "01110110 zzzzzzzz",          M6502),
+    row("ror",    "[A]",       "01101110 aaaaaaaa aaaaaaaa", M6502),
+    row("ror.w",  "[W]",       "01101110 wwwwwwww wwwwwwww", M6502),
+    row("ror",    "[A + x]",   "01111110 aaaaaaaa aaaaaaaa", M6502),
+    row("ror.w",  "[W + x]",   "01111110 wwwwwwww wwwwwwww", M6502),
+
+    // Increments and decrements
+    row("dec",    "[Z]",       "11000110 zzzzzzzz",          M6502),
+    row("dec",    "[Z + x]",   "11010110 zzzzzzzz",          M6502),
+    row("dec",    "[A]",       "11001110 aaaaaaaa aaaaaaaa", M6502),
+    row("dec.w",  "[W]",       "11001110 wwwwwwww wwwwwwww", M6502),
+    row("dec",    "[A + x]",   "11011110 aaaaaaaa aaaaaaaa", M6502),
+    row("dec.w",  "[W + x]",   "11011110 wwwwwwww wwwwwwww", M6502),
+    row("dec",    "a",         "00111010",                   M65C02),
+    row("inc",    "[Z]",       "11100110 zzzzzzzz",          M6502),
+    row("inc",    "[Z + x]",   "11110110 zzzzzzzz",          M6502),
+    row("inc",    "[A]",       "11101110 aaaaaaaa aaaaaaaa", M6502),
+    row("inc.w",  "[W]",       "11101110 wwwwwwww wwwwwwww", M6502),
+    row("inc",    "[A + x]",   "11111110 aaaaaaaa aaaaaaaa", M6502),
+    row("inc.w",  "[W + x]",   "11111110 wwwwwwww wwwwwwww", M6502),
+    row("inc",    "a",         "00011010",                   M65C02),
+
+    // Bit tests
+    row("bit",    "[Z]",       "00100100 zzzzzzzz",          M6502),
+    row("bit",    "[A]",       "00101100 aaaaaaaa aaaaaaaa", M6502),
+    row("bit.w",  "[W]",       "00101100 wwwwwwww wwwwwwww", M6502),
+    row("bit",    "N",         "10001001 nnnnnnnn",          M65C02),
+    row("bit",    "[Z + x]",   "00110100 zzzzzzzz",          M65C02),
+    row("bit",    "[A + x]",   "00111100 aaaaaaaa aaaaaaaa", M65C02),
+    row("bit.w",  "[W + x]",   "00111100 wwwwwwww wwwwwwww", M65C02),
+    row("trb",    "[Z]",       "00010100 zzzzzzzz",          M65C02),
+    row("trb",    "[A]",       "00011100 aaaaaaaa aaaaaaaa", M65C02),
+    row("trb.w",  "[W]",       "00011100 wwwwwwww wwwwwwww", M65C02),
+    row("tsb",    "[Z]",       "00000100 zzzzzzzz",          M65C02),
+    row("tsb",    "[A]",       "00001100 aaaaaaaa aaaaaaaa", M65C02),
+    row("tsb.w",  "[W]",       "00001100 wwwwwwww wwwwwwww", M65C02),
+
+    // Index register loads, stores, and comparisons
+    row("cpx",    "N",         "11100000 nnnnnnnn",          M6502),
+    row("cpx",    "[Z]",       "11100100 zzzzzzzz",          M6502),
+    row("cpx",    "[A]",       "11101100 aaaaaaaa aaaaaaaa", M6502),
+    row("cpx.w",  "[W]",       "11101100 wwwwwwww wwwwwwww", M6502),
+    row("cpy",    "N",         "11000000 nnnnnnnn",          M6502),
+    row("cpy",    "[Z]",       "11000100 zzzzzzzz",          M6502),
+    row("cpy",    "[A]",       "11001100 aaaaaaaa aaaaaaaa", M6502),
+    row("cpy.w",  "[W]",       "11001100 wwwwwwww wwwwwwww", M6502),
+    row("ldx",    "N",         "10100010 nnnnnnnn",          M6502),
+    row("ldx",    "[Z]",       "10100110 zzzzzzzz",          M6502),
+    row("ldx",    "[Z + y]",   "10110110 zzzzzzzz",          M6502),
+    row("ldx",    "[A]",       "10101110 aaaaaaaa aaaaaaaa", M6502),
+    row("ldx.w",  "[W]",       "10101110 wwwwwwww wwwwwwww", M6502),
+    row("ldx",    "[A + y]",   "10111110 aaaaaaaa aaaaaaaa", M6502),
+    row("ldx.w",  "[W + y]",   "10111110 wwwwwwww wwwwwwww", M6502),
+    row("ldy",    "N",         "10100000 nnnnnnnn",          M6502),
+    row("ldy",    "[Z]",       "10100100 zzzzzzzz",          M6502),
+    row("ldy",    "[Z + x]",   "10110100 zzzzzzzz",          M6502),
+    row("ldy",    "[A]",       "10101100 aaaaaaaa aaaaaaaa", M6502),
+    row("ldy.w",  "[W]",       "10101100 wwwwwwww wwwwwwww", M6502),
+    row("ldy",    "[A + x]",   "10111100 aaaaaaaa aaaaaaaa", M6502),
+    row("ldy.w",  "[W + x]",   "10111100 wwwwwwww wwwwwwww", M6502),
+    row("stx",    "[Z]",       "10000110 zzzzzzzz",          M6502),
+    row("stx",    "[Z + y]",   "10010110 zzzzzzzz",          M6502),
+    row("stx",    "[A]",       "10001110 aaaaaaaa aaaaaaaa", M6502),
+    row("stx.w",  "[W]",       "10001110 wwwwwwww wwwwwwww", M6502),
+    row("sty",    "[Z]",       "10000100 zzzzzzzz",          M6502),
+    row("sty",    "[Z + x]",   "10010100 zzzzzzzz",          M6502),
+    row("sty",    "[A]",       "10001100 aaaaaaaa aaaaaaaa", M6502),
+    row("sty.w",  "[W]",       "10001100 wwwwwwww wwwwwwww", M6502),
+    row("stz",    "[Z]",       "01100100 zzzzzzzz",          M65C02),
+    row("stz",    "[Z + x]",   "01110100 zzzzzzzz",          M65C02),
+    row("stz",    "[A]",       "10011100 aaaaaaaa aaaaaaaa", M65C02),
+    row("stz.w",  "[W]",       "10011100 wwwwwwww wwwwwwww", M65C02),
+    row("stz",    "[A + x]",   "10011110 aaaaaaaa aaaaaaaa", M65C02),
+    row("stz.w",  "[W + x]",   "10011110 wwwwwwww wwwwwwww", M65C02),
+
+    // Jumps and branches
+    row("jmp",    "T",         "01001100 tttttttt tttttttt", M6502),
+    row("jmp",    "[P]",       "01101100 pppppppp pppppppp", M6502),
+    row("jmp",    "[P + x]",   "01111100 pppppppp pppppppp", M65C02),
+    row("jsr",    "T",         "00100000 tttttttt tttttttt", M6502),
+    row("bpl",    "E",         "00010000 eeeeeeee",          M6502),
+    row("bmi",    "E",         "00110000 eeeeeeee",          M6502),
+    row("bvc",    "E",         "01010000 eeeeeeee",          M6502),
+    row("bvs",    "E",         "01110000 eeeeeeee",          M6502),
+    row("bcc",    "E",         "10010000 eeeeeeee",          M6502),
+    row("bcs",    "E",         "10110000 eeeeeeee",          M6502),
+    row("bne",    "E",         "11010000 eeeeeeee",          M6502),
+    row("beq",    "E",         "11110000 eeeeeeee",          M6502),
+    row("bra",    "E",         "10000000 eeeeeeee",          M65C02),
+
+    // Implied operands
+    row("brk",    "",          "00000000",                   M6502),
+    row("php",    "",          "00001000",                   M6502),
+    row("clc",    "",          "00011000",                   M6502),
+    row("plp",    "",          "00101000",                   M6502),
+    row("sec",    "",          "00111000",                   M6502),
+    row("rti",    "",          "01000000",                   M6502),
+    row("pha",    "",          "01001000",                   M6502),
+    row("cli",    "",          "01011000",                   M6502),
+    row("rts",    "",          "01100000",                   M6502),
+    row("pla",    "",          "01101000",                   M6502),
+    row("sei",    "",          "01111000",                   M6502),
+    row("dey",    "",          "10001000",                   M6502),
+    row("txa",    "",          "10001010",                   M6502),
+    row("tya",    "",          "10011000",                   M6502),
+    row("txs",    "",          "10011010",                   M6502),
+    row("tay",    "",          "10101000",                   M6502),
+    row("tax",    "",          "10101010",                   M6502),
+    row("clv",    "",          "10111000",                   M6502),
+    row("tsx",    "",          "10111010",                   M6502),
+    row("iny",    "",          "11001000",                   M6502),
+    row("dex",    "",          "11001010",                   M6502),
+    row("cld",    "",          "11011000",                   M6502),
+    row("inx",    "",          "11101000",                   M6502),
+    row("nop",    "",          "11101010",                   M6502),
+    row("sed",    "",          "11111000",                   M6502),
+    row("phy",    "",          "01011010",                   M65C02),
+    row("ply",    "",          "01111010",                   M65C02),
+    row("phx",    "",          "11011010",                   M65C02),
+    row("plx",    "",          "11111010",                   M65C02),
+    row("wai",    "",          "11001011",                   M65C02),
+    row("stp",    "",          "11011011",                   M65C02),
+
+    // Bit manipulation, numbered by bit
+    row("rmb0",   "[Z]",       "00000111 zzzzzzzz",          M65C02),
+    row("rmb1",   "[Z]",       "00010111 zzzzzzzz",          M65C02),
+    row("rmb2",   "[Z]",       "00100111 zzzzzzzz",          M65C02),
+    row("rmb3",   "[Z]",       "00110111 zzzzzzzz",          M65C02),
+    row("rmb4",   "[Z]",       "01000111 zzzzzzzz",          M65C02),
+    row("rmb5",   "[Z]",       "01010111 zzzzzzzz",          M65C02),
+    row("rmb6",   "[Z]",       "01100111 zzzzzzzz",          M65C02),
+    row("rmb7",   "[Z]",       "01110111 zzzzzzzz",          M65C02),
+    row("smb0",   "[Z]",       "10000111 zzzzzzzz",          M65C02),
+    row("smb1",   "[Z]",       "10010111 zzzzzzzz",          M65C02),
+    row("smb2",   "[Z]",       "10100111 zzzzzzzz",          M65C02),
+    row("smb3",   "[Z]",       "10110111 zzzzzzzz",          M65C02),
+    row("smb4",   "[Z]",       "11000111 zzzzzzzz",          M65C02),
+    row("smb5",   "[Z]",       "11010111 zzzzzzzz",          M65C02),
+    row("smb6",   "[Z]",       "11100111 zzzzzzzz",          M65C02),
+    row("smb7",   "[Z]",       "11110111 zzzzzzzz",          M65C02),
+    row("bbr0",   "[Z], F",    "00001111 zzzzzzzz ffffffff", M65C02),
+    row("bbr1",   "[Z], F",    "00011111 zzzzzzzz ffffffff", M65C02),
+    row("bbr2",   "[Z], F",    "00101111 zzzzzzzz ffffffff", M65C02),
+    row("bbr3",   "[Z], F",    "00111111 zzzzzzzz ffffffff", M65C02),
+    row("bbr4",   "[Z], F",    "01001111 zzzzzzzz ffffffff", M65C02),
+    row("bbr5",   "[Z], F",    "01011111 zzzzzzzz ffffffff", M65C02),
+    row("bbr6",   "[Z], F",    "01101111 zzzzzzzz ffffffff", M65C02),
+    row("bbr7",   "[Z], F",    "01111111 zzzzzzzz ffffffff", M65C02),
+    row("bbs0",   "[Z], F",    "10001111 zzzzzzzz ffffffff", M65C02),
+    row("bbs1",   "[Z], F",    "10011111 zzzzzzzz ffffffff", M65C02),
+    row("bbs2",   "[Z], F",    "10101111 zzzzzzzz ffffffff", M65C02),
+    row("bbs3",   "[Z], F",    "10111111 zzzzzzzz ffffffff", M65C02),
+    row("bbs4",   "[Z], F",    "11001111 zzzzzzzz ffffffff", M65C02),
+    row("bbs5",   "[Z], F",    "11011111 zzzzzzzz ffffffff", M65C02),
+    row("bbs6",   "[Z], F",    "11101111 zzzzzzzz ffffffff", M65C02),
+    row("bbs7",   "[Z], F",    "11111111 zzzzzzzz ffffffff", M65C02),
+
+];
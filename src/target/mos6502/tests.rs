@@ -0,0 +1,195 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, bytes};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u8> = Bench { origin: 0x200, split: bytes };
+
+#[test]
+fn m6502() {
+    BENCH.check("6502", &[
+        ("nop",                                 &[0xEA]),
+        ("RTS",                                 &[0x60]),
+        ("asl",                                 &[0x0A]),
+        ("asl a",                               &[0x0A]),
+        ("lda 42",                              &[0xA9, 0x2A]),
+        ("lda -1",                              &[0xA9, 0xFF]),
+        ("lda [x'10]",                          &[0xA5, 0x10]),
+        ("lda [x'1234]",                        &[0xAD, 0x34, 0x12]),
+        ("lda [x'10 + x]",                      &[0xB5, 0x10]),
+        ("lda [x'1234 + x]",                    &[0xBD, 0x34, 0x12]),
+        ("lda [x'10 + y]",                      &[0xB9, 0x10, 0x00]),
+        ("lda [[x'10 + x]]",                    &[0xA1, 0x10]),
+        ("lda [[x'10] + y]",                    &[0xB1, 0x10]),
+        ("ldx [x'10 + y]",                      &[0xB6, 0x10]),
+        ("stx [x'10 + y]",                      &[0x96, 0x10]),
+        ("sta [x'0300 + y]",                    &[0x99, 0x00, 0x03]),
+        ("inc [x'10]",                          &[0xE6, 0x10]),
+        ("cpx 0",                               &[0xE0, 0x00]),
+        ("bit [x'2000]",                        &[0x2C, 0x00, 0x20]),
+        ("jmp x'1234",                          &[0x4C, 0x34, 0x12]),
+        ("jmp [x'fffc]",                        &[0x6C, 0xFC, 0xFF]),
+        ("jsr x'ffd2",                          &[0x20, 0xD2, 0xFF]),
+        ("bne x'200",                           &[0xD0, 0xFE]),
+        ("beq x'281",                           &[0xF0, 0x7F]),
+        ("lda lo(x'1234)",                      &[0xA9, 0x34]),
+        ("lda hi(x'1234)",                      &[0xA9, 0x12]),
+    ]);
+}
+
+#[test]
+fn m65c02() {
+    BENCH.check("65c02", &[
+        ("lda [[x'10]]",                        &[0xB2, 0x10]),
+        ("sta [[x'10]]",                        &[0x92, 0x10]),
+        ("inc",                                 &[0x1A]),
+        ("bit 1",                               &[0x89, 0x01]),
+        ("stz [x'10 + x]",                      &[0x74, 0x10]),
+        ("stz [x'1000]",                        &[0x9C, 0x00, 0x10]),
+        ("jmp [x'1000 + x]",                    &[0x7C, 0x00, 0x10]),
+        ("bra x'200",                           &[0x80, 0xFE]),
+        ("phx",                                 &[0xDA]),
+        ("smb3 [x'10]",                         &[0xB7, 0x10]),
+        ("bbr7 [x'10], x'200",                  &[0x7F, 0x10, 0xFD]),
+    ]);
+}
+
+#[test]
+fn zero_page_selection() {
+    // A forward reference to a symbol in the zero page takes the short form
+    // once the symbol is known.
+    BENCH.check("6502", &[
+        ("lda [ptr]\n.bss\n.org x'80\nptr:",    &[0xA5, 0x80]),
+        ("lda [buf]\n.bss\n.org x'400\nbuf:",   &[0xAD, 0x00, 0x04]),
+        ("lda [end]\nend:",                     &[0xAD, 0x03, 0x02]),
+        ("jmp end\nnop\nend:",                  &[0x4C, 0x04, 0x02, 0xEA]),
+        ("lda.w [x'10]",                        &[0xAD, 0x10, 0x00]),
+        ("STA.W [x'10 + x]",                    &[0x9D, 0x10, 0x00]),
+    ]);
+
+    // An operand that leaves the zero page when the instruction grows keeps
+    // the absolute form, so that layout converges.
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("6502").unwrap());
+
+    let unit = session.assemble("test.s", ".org x'FD\nlda [x'1FF - end]\nend:");
+
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(unit.sections[0].data, [0xAD, 0xFF, 0x00]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("6502").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  lda lo(msg)
+                ldx hi(msg)
+                jsr print
+                beq start
+        print:  rts
+        msg:    .int8 0
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(program.sections[0].data, [
+        0xA9, 0x0A, 0xA2, 0x00, 0x20, 0x09, 0x00, 0xF0, 0xF7, 0x60, 0x00,
+    ]);
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("6502", &[
+            "nop",
+            "asl a",
+            "lda x'2A",
+            "lda [x'10]",
+            "lda.w [x'10]",
+            "lda [x'1234 + x]",
+            "lda [x'10 + y]",
+            "lda [[x'10 + x]]",
+            "lda [[x'10] + y]",
+            "ldx [x'10 + y]",
+            "stx [x'10 + y]",
+            "inc [x'10 + x]",
+            "jmp start",
+            "jmp [x'FFFC]",
+            "jsr x'FFD2",
+            "bne start",
+        ][..]),
+        ("65c02", &[
+            "lda [[x'10]]",
+            "inc a",
+            "bit 1",
+            "stz [x'1000]",
+            "jmp [x'1000 + x]",
+            "bra start",
+            "phx",
+            "smb3 [x'10]",
+            "bbr7 [x'10], start",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'200"));
+            let decoded    = decoder.decode(&bytes, 0x200, &label).unwrap();
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let m6502 = (find("6502").unwrap().new)(&mut NameTable::new());
+    assert_eq!(m6502.decode(&[0xDA], 0x200, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(m6502.decode(&[0xAD, 0x34], 0x200, &label), None);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("6502", "lda [[x'10]]"),           "invalid addressing mode");
+    assert_eq!(BENCH.error("6502", "stx [x'1000 + y]"),       "address 0x1000 out of range for zero page");
+    assert_eq!(BENCH.error("6502", "lda [[x'100] + y]"),      "address 0x100 out of range for zero page");
+    assert_eq!(BENCH.error("6502", "bne x'300"),              "branch offset 254 out of range");
+    assert_eq!(BENCH.error("6502", "lda [x'10 + a]"),         "invalid index register");
+    assert_eq!(BENCH.error("6502", "inc"),                    "invalid addressing mode");
+    assert_eq!(BENCH.error("6502", "phx"),                    "unknown instruction 'phx'");
+    assert_eq!(BENCH.error("6502", "lda 1, 2"),               "invalid operands");
+    assert_eq!(BENCH.error("6502", "lda.w 1"),                "suffix .w requires an absolute address operand");
+    assert_eq!(BENCH.error("6502", "lda.w [[x'10] + y]"),     "suffix .w requires an absolute address operand");
+    assert_eq!(BENCH.error("6502", "nop.w"),                  "unknown instruction 'nop.w'");
+}
@@ -0,0 +1,386 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Instruction decoding.
+
+use crate::target::{number, Decoded};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Register names, by number.
+const REGS: [&str; 16] = [
+    "pc", "sp", "sr", "r3",  "r4",  "r5",  "r6",  "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// Program counter.
+const PC: u16 = 0;
+
+/// Status register, which is also constant generator 1.
+const SR: u16 = 2;
+
+/// Constant generator 2.
+const CG: u16 = 3;
+
+/// Decodes the instruction at the beginning of `bytes`, which lies at
+/// address `addr`.  `label` returns the name by which to refer to an
+/// address, if any.  If `x` is true, decodes MSP430X instructions.
+pub fn decode(
+    x:     bool,
+    bytes: &[u8],
+    addr:  u64,
+    label: &dyn Fn(u64) -> Option<String>,
+) -> Option<Decoded> {
+    let mut d = Decoder { bytes, addr, at: 0, label, targets: vec![] };
+    let text  = d.insn(x)?;
+    Some(Decoded { text, size: d.at, targets: d.targets })
+}
+
+/// Decoder state for one instruction.
+struct Decoder<'a> {
+    /// Bytes of the instruction and those after it.
+    bytes: &'a [u8],
+
+    /// Address of the instruction.
+    addr: u64,
+
+    /// Offset of the next word to read.
+    at: usize,
+
+    /// Returns the name by which to refer to an address, if any.
+    label: &'a dyn Fn(u64) -> Option<String>,
+
+    /// Addresses to which the instruction jumps or calls.
+    targets: Vec<u64>,
+}
+
+impl Decoder<'_> {
+    fn insn(&mut self, x: bool) -> Option<String> {
+        let word = self.word()?;
+
+        let fixed = ENTRIES
+            .iter()
+            .find(|e| e.op == Op::Fixed(word) && (x || e.flags & X == 0));
+        if let Some(entry) = fixed {
+            return Some(entry.name.to_string());
+        }
+
+        match word >> 12 {
+            0x0         if x                   => self.address(word),
+            0x1         if word >= 0x1800      => match x {
+                true  => self.extended(word),
+                false => None,
+            },
+            0x1         if word <  0x1340      => self.one(word, None),
+            0x1         if x                   => self.address(word),
+            0x2 | 0x3                          => self.jump(word),
+            0x4..=0xF                          => self.two(word, None),
+            _                                  => None,
+        }
+    }
+
+    /// Decodes an MSP430X instruction with an extension word.
+    fn extended(&mut self, ext: u16) -> Option<String> {
+        // The encoder sets no repeat count or carry bits
+        if ext & 0x0030 != 0 {
+            return None;
+        }
+
+        match self.word()? {
+            word @ 0x1000..=0x12FF => self.one(word, Some(ext)),
+            word @ 0x4000..        => self.two(word, Some(ext)),
+            _                      => None,
+        }
+    }
+
+    /// Decodes a double-operand (format I) instruction.
+    fn two(&mut self, word: u16, ext: Option<u16>) -> Option<String> {
+        let entry  = entry(Op::Two(word & 0xF000), ext.is_some())?;
+        let suffix = suffix(entry, ext, word)?;
+
+        let mut src_hi = ext.map(|e| e >> 7 & 0xF);
+        let mut dst_hi = ext.map(|e| e & 0xF);
+
+        let (reg, mode) = (word >> 8 & 0xF, word >> 4 & 3);
+        let (dst, ad)   = (word & 0xF, word >> 7 & 1);
+
+        // `mov src, pc` is a branch, whose immediate operand is its target
+        if word & 0xF0CF == 0x4000 && ext.is_none() {
+            let src = self.source(reg, mode, &mut src_hi, suffix, true)?;
+            return Some(format!("br {}", src));
+        }
+
+        let src = self.source(reg, mode, &mut src_hi, suffix, false)?;
+        let dst = match (ad, dst) {
+            (0, _)  => REGS[dst as usize].to_string(),
+            (_, CG) => return None,
+            (_, _)  => self.indexed(dst, &mut dst_hi)?,
+        };
+
+        // An extension word holds no bits of an operand without a word
+        if src_hi.unwrap_or(0) != 0 || dst_hi.unwrap_or(0) != 0 {
+            return None;
+        }
+
+        Some(format!("{}{} {}, {}", entry.name, suffix, src, dst))
+    }
+
+    /// Decodes a single-operand (format II) instruction.
+    fn one(&mut self, word: u16, ext: Option<u16>) -> Option<String> {
+        let op     = word & 0xFF80;
+        let entry  = entry(Op::One(op), ext.is_some())?;
+        let suffix = suffix(entry, ext, word)?;
+
+        let (reg, mode) = (word & 0xF, word >> 4 & 3);
+
+        // Only push and call take an immediate operand
+        let imm = reg == CG || reg == SR && mode >= 2 || reg == PC && mode == 3;
+        if imm && !matches!(op, 0x1200 | 0x1280) {
+            return None;
+        }
+
+        if ext.is_some_and(|e| e >> 7 & 0xF != 0) {
+            return None;
+        }
+
+        let mut hi = ext.map(|e| e & 0xF);
+        let src    = self.source(reg, mode, &mut hi, suffix, op == 0x1280)?;
+
+        if hi.unwrap_or(0) != 0 {
+            return None;
+        }
+
+        Some(format!("{}{} {}", entry.name, suffix, src))
+    }
+
+    /// Decodes a conditional or unconditional jump.
+    fn jump(&mut self, word: u16) -> Option<String> {
+        let entry  = entry(Op::Jump(word & 0xFC00), false)?;
+        let offset = sign_extend(word as u64 & 0x3FF, 10) * 2;
+        let target = self.addr.wrapping_add(2).wrapping_add(offset as u64);
+        Some(format!("{} {}", entry.name, self.target(target)))
+    }
+
+    /// Decodes an MSP430X address instruction.
+    fn address(&mut self, word: u16) -> Option<String> {
+        let (s, d) = (word >> 8 & 0xF, word & 0xF);
+
+        // A move into pc is a branch
+        let mova = |src: String, d: u16| match d {
+            PC => format!("bra {}", src),
+            _  => format!("mova {}, {}", src, REGS[d as usize]),
+        };
+
+        let text = match word >> 4 {
+            0x000..=0x0FF => match word >> 4 & 0xF {
+                0x0 if s != SR && s != CG => mova(format!("[{}]", REGS[s as usize]), d),
+                0x1 if s != SR && s != CG => mova(format!("[{}]!", REGS[s as usize]), d),
+                0x2 => {
+                    let (_, value) = self.operand_word(&mut Some(s))?;
+                    mova(format!("[{}]", number(value as i64)), d)
+                },
+                0x3 if s != SR && s != CG => {
+                    let src = self.indexed(s, &mut None)?;
+                    mova(src, d)
+                },
+                0x4 | 0x5 => {
+                    let entry  = entry(Op::Rotm(word & 0x0300 | 0x0040), false)?;
+                    let suffix = if word & 0x0010 == 0 { ".a" } else { "" };
+                    let count  = (word >> 10 & 3) + 1;
+                    format!("{}{} {}, {}", entry.name, suffix, count, REGS[d as usize])
+                },
+                0x6 => {
+                    let (_, value) = self.operand_word(&mut Some(d))?;
+                    format!("mova {}, [{}]", REGS[s as usize], number(value as i64))
+                },
+                0x7 if d != SR && d != CG => {
+                    let dst = self.indexed(d, &mut None)?;
+                    format!("mova {}, {}", REGS[s as usize], dst)
+                },
+                0x8 => {
+                    let (_, value) = self.operand_word(&mut Some(s))?;
+                    let src = match d {
+                        PC => self.target(value),
+                        _  => number(value as i64),
+                    };
+                    mova(src, d)
+                },
+                0xC => mova(REGS[s as usize].to_string(), d),
+                op @ 0x9..=0xB => {
+                    let entry = ENTRIES.iter().find(|e| matches!(e.op, Op::Addr(imm, _) if imm == op << 4))?;
+                    let (_, value) = self.operand_word(&mut Some(s))?;
+                    format!("{} {}, {}", entry.name, number(value as i64), REGS[d as usize])
+                },
+                op @ 0xD..=0xF => {
+                    let entry = ENTRIES.iter().find(|e| matches!(e.op, Op::Addr(_, reg) if reg == op << 4))?;
+                    format!("{} {}, {}", entry.name, REGS[s as usize], REGS[d as usize])
+                },
+                _ => return None,
+            },
+            0x134..=0x13F => match word >> 4 & 0xF {
+                0x4 => format!("calla {}", REGS[d as usize]),
+                0x5 if d != PC && d != SR && d != CG => {
+                    let dst = self.indexed(d, &mut None)?;
+                    format!("calla {}", dst)
+                },
+                0x6 if d != SR && d != CG => format!("calla [{}]", REGS[d as usize]),
+                0x7 if d != SR && d != CG => format!("calla [{}]!", REGS[d as usize]),
+                0x8 => {
+                    let (_, value) = self.operand_word(&mut Some(d))?;
+                    format!("calla [{}]", number(value as i64))
+                },
+                // The encoder takes the offset of a symbolic operand as is
+                0x9 => {
+                    let (_, value) = self.operand_word(&mut Some(d))?;
+                    format!("calla [pc + {}]", number(value as i64))
+                },
+                0xB => {
+                    let (_, value) = self.operand_word(&mut Some(d))?;
+                    format!("calla {}", self.target(value))
+                },
+                _ => return None,
+            },
+            0x140..=0x17F => {
+                let suffix = if word & 0x0100 == 0 { ".a" } else { "" };
+                let count  = (word >> 4 & 0xF) + 1;
+                match word & 0x0200 {
+                    0 => format!("pushm{} {}, {}", suffix, count, REGS[d as usize]),
+                    _ => match d + count - 1 {
+                        last @ 0..=15 => format!("popm{} {}, {}", suffix, count, REGS[last as usize]),
+                        _             => return None,
+                    },
+                }
+            },
+            _ => return None,
+        };
+
+        Some(text)
+    }
+
+    /// Returns the text of a source operand with the given register and
+    /// addressing mode, reading its operand word, if any.  `hi` holds bits
+    /// 19-16 of the operand word of an extended instruction.  If `jump` is
+    /// true, an immediate operand is the address of a jump or call.
+    fn source(
+        &mut self,
+        reg:    u16,
+        mode:   u16,
+        hi:     &mut Option<u16>,
+        suffix: &str,
+        jump:   bool,
+    ) -> Option<String> {
+        let text = match (mode, reg) {
+            (0, CG) => "0".to_string(),
+            (1, CG) => "1".to_string(),
+            (2, CG) => "2".to_string(),
+            (3, CG) => "-1".to_string(),
+            (2, SR) => "4".to_string(),
+            (3, SR) => "8".to_string(),
+            (0, _)  => REGS[reg as usize].to_string(),
+            (1, _)  => self.indexed(reg, hi)?,
+            (2, _)  => format!("[{}]", REGS[reg as usize]),
+            (3, PC) => {
+                let (_, value) = self.operand_word(hi)?;
+
+                // The encoder takes these from the constant generator
+                let ones = match suffix {
+                    ".b" => 0xFF,
+                    ".a" => 0xF_FFFF,
+                    _    => 0xFFFF,
+                };
+                if [0, 1, 2, 4, 8, ones].contains(&value) {
+                    return None;
+                }
+
+                match jump {
+                    true  => self.target(value),
+                    false => number(value as i64),
+                }
+            },
+            (_, _) => format!("[{}]!", REGS[reg as usize]),
+        };
+
+        Some(text)
+    }
+
+    /// Returns the text of an indexed, symbolic, or absolute memory operand
+    /// with the given register, reading its operand word.
+    fn indexed(&mut self, reg: u16, hi: &mut Option<u16>) -> Option<String> {
+        let bits = if hi.is_some() { 20 } else { 16 };
+        let (at, value) = self.operand_word(hi)?;
+        let offset      = sign_extend(value, bits);
+
+        Some(match reg {
+            PC => {
+                let target = self.addr.wrapping_add(at as u64).wrapping_add(offset as u64);
+                format!("[pc + {}]", number(target as i64))
+            },
+            SR => format!("[{}]", number(value as i64)),
+            _ if offset < 0 => format!("[{} - {}]", REGS[reg as usize], number(-offset)),
+            _               => format!("[{} + {}]", REGS[reg as usize], number(offset)),
+        })
+    }
+
+    /// Reads an operand word and returns its offset in the instruction and
+    /// its value, extended by the bits 19-16 in `hi`, if any, which it takes.
+    fn operand_word(&mut self, hi: &mut Option<u16>) -> Option<(usize, u64)> {
+        let at = self.at;
+        let lo = self.word()? as u64;
+        Some((at, hi.take().map_or(0, |hi| (hi as u64) << 16) | lo))
+    }
+
+    /// Returns the text by which to refer to the target of a jump or call.
+    fn target(&mut self, addr: u64) -> String {
+        self.targets.push(addr);
+        (self.label)(addr).unwrap_or_else(|| number(addr as i64))
+    }
+
+    fn word(&mut self) -> Option<u16> {
+        let bytes = self.bytes.get(self.at..self.at + 2)?;
+        self.at += 2;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// Returns the table entry with the given kind, choosing among the MSP430X
+/// extended instructions if `ext` is true.
+fn entry(op: Op, ext: bool) -> Option<&'static Entry> {
+    ENTRIES.iter().find(|e| e.op == op && (e.flags & E != 0) == ext)
+}
+
+/// Returns the size suffix of the given instruction word, with the given
+/// extension word, if any, if the entry has a form with that suffix.
+fn suffix(entry: &Entry, ext: Option<u16>, word: u16) -> Option<&'static str> {
+    let bw = word >> 6 & 1;
+    let (suffix, flag) = match (ext.map(|e| e >> 6 & 1), bw) {
+        (None,    0) => return Some(""),
+        (None,    _) => (".b", B),
+        (Some(1), 0) => return Some(""),
+        (Some(1), _) => (".b", B),
+        (Some(_), 1) => (".a", A),
+        (Some(_), _) => return None,
+    };
+    (entry.flags & flag != 0).then_some(suffix)
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
@@ -0,0 +1,533 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+enum Operand {
+    /// Register: `r5`.
+    Reg(u16),
+
+    /// Memory at the address in a register: `[r5]`.
+    Ind(u16),
+
+    /// Memory at the address in a register, which then advances: `[r5]!`.
+    PostInc(u16),
+
+    /// Memory at the address in a register plus an offset: `[r5 + 4]`.  If
+    /// the register is `pc`, the operand is symbolic, and the expression is
+    /// the address: `[pc + label]`.
+    Idx(u16, Expr<Span>),
+
+    /// Memory at an address: `[x'200]`.
+    Abs(Expr<Span>),
+
+    /// Immediate value or target address.
+    Imm(Expr<Span>),
+}
+
+/// Program counter.
+const PC: u16 = 0;
+
+/// Stack pointer.
+const SP: u16 = 1;
+
+/// Status register, which is also constant generator 1.
+const SR: u16 = 2;
+
+/// Constant generator 2.
+const CG: u16 = 3;
+
+/// Operand of a format I or II instruction: a register, an addressing mode,
+/// and the value of the operand word, if any, with a flag indicating a
+/// symbolic (PC-relative) operand.
+struct Ea {
+    reg:  u16,
+    mode: u16,
+    word: Option<(Expr<Span>, bool)>,
+}
+
+impl Ea {
+    fn new(reg: u16, mode: u16) -> Self {
+        Self { reg, mode, word: None }
+    }
+}
+
+/// Value that completes a field of the instruction.
+struct Field {
+    /// Index of the word at which the field begins.
+    at: usize,
+
+    /// Value, or the target address if `pc` is present.
+    expr: Expr<Span>,
+
+    /// Kind of the field.
+    kind: RelocKind,
+
+    /// For a PC-relative field, offset of the word that holds the offset.
+    pc: Option<i64>,
+}
+
+type Result<T = ()> = std::result::Result<T, String>;
+
+const INVALID: &str = "invalid operands";
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.  `size` is the size code of the mnemonic suffix.
+pub fn encode(entry: &Entry, size: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let span = stmt.data;
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| operand(arg, out))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    let mut encoder = Encoder { out, span, size, words: vec![], fields: vec![] };
+
+    match encoder.encode(entry, &ops) {
+        Ok(())   => encoder.finish(),
+        Err(msg) => encoder.out.error(span, &msg),
+    }
+}
+
+/// Encoder for one instruction statement.
+struct Encoder<'a> {
+    out:    &'a mut dyn Emitter,
+    span:   Span,
+    size:   usize,
+    words:  Vec<u16>,
+    fields: Vec<Field>,
+}
+
+impl Encoder<'_> {
+    fn encode(&mut self, entry: &Entry, ops: &[Operand]) -> Result {
+        use Operand::{Abs, Idx, Imm, Ind, PostInc, Reg as R};
+
+        let ext = entry.flags & E != 0;
+
+        match (entry.op, ops) {
+            (Op::Two(base), [src, dst]) => {
+                let src = self.source(src)?;
+                let dst = self.dest(dst)?;
+                self.two(base, ext, src, dst)
+            },
+            (Op::One(base), [src]) => {
+                if matches!(src, Imm(_)) && !matches!(base, 0x1200 | 0x1280) {
+                    return Err(INVALID.into());
+                }
+                let src = self.source(src)?;
+                self.one(base, ext, src)
+            },
+            (Op::Jump(base), [Imm(target)]) => {
+                self.words.push(base);
+                self.field(0, target.clone(), JUMP10, Some(2));
+                Ok(())
+            },
+            (Op::Fixed(word), []) => {
+                self.words.push(word);
+                Ok(())
+            },
+            (Op::EmuImm(base, value), [dst]) => {
+                let src = self.constant(value).unwrap();
+                let dst = self.dest(dst)?;
+                self.two(base, ext, src, dst)
+            },
+            (Op::EmuDouble(base), [dst]) => {
+                let src = self.source(dst)?;
+                let dst = self.dest(dst)?;
+                self.two(base, ext, src, dst)
+            },
+            (Op::Pop, [dst]) => {
+                let dst = self.dest(dst)?;
+                self.two(0x4000, ext, Ea::new(SP, 3), dst)
+            },
+            (Op::Br, [src]) => {
+                let src = self.source(src)?;
+                self.two(0x4000, ext, src, Ea::new(PC, 0))
+            },
+
+            (Op::Mova, [src, dst]) => self.mova(src, dst),
+            (Op::Bra,  [src])      => self.mova(src, &R(PC)),
+
+            (Op::Addr(imm, _), [Imm(e), R(d)]) => self.addr(imm | d, e, ADDR_SRC),
+            (Op::Addr(_, reg), [R(s), R(d)])   => self.push(reg | s << 8 | d),
+
+            (Op::Calla, [R(d)])         => self.push(0x1340 | d),
+            (Op::Calla, [Idx(PC, e)])   => self.addr(0x1390, e, ADDR_DST),
+            (Op::Calla, [Idx(d, e)])    => self.index(0x1350 | d, e, false),
+            (Op::Calla, [Ind(d)])       => self.push(0x1360 | d),
+            (Op::Calla, [PostInc(d)])   => self.push(0x1370 | d),
+            (Op::Calla, [Abs(e)])       => self.addr(0x1380, e, ADDR_DST),
+            (Op::Calla, [Imm(e)])       => self.addr(0x13B0, e, ADDR_DST),
+
+            (Op::Pushm, [Imm(n), R(d)]) => {
+                let n = self.range(n, 1, 16, "register count")?;
+                self.push(self.sized(0x1400, 0x0100) | (n - 1) << 4 | d)
+            },
+            (Op::Popm, [Imm(n), R(d)]) => {
+                let n = self.range(n, 1, d + 1, "register count")?;
+                self.push(self.sized(0x1600, 0x0100) | (n - 1) << 4 | (d + 1 - n))
+            },
+            (Op::Rotm(base), [Imm(n), R(d)]) => {
+                let n = self.range(n, 1, 4, "rotate count")?;
+                self.push(self.sized(base, 0x0010) | (n - 1) << 10 | d)
+            },
+
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a double-operand (format I) instruction.
+    fn two(&mut self, base: u16, ext: bool, src: Ea, dst: Ea) -> Result {
+        let (al, bw) = self.size_bits(ext);
+
+        if ext {
+            self.words.push(0x1800 | al << 6);
+        }
+        self.words.push(base | src.reg << 8 | dst.mode << 7 | bw << 6 | src.mode << 4 | dst.reg);
+
+        let has_src = src.word.is_some();
+        if let Some(word) = src.word {
+            self.word(word, if ext { EXT_SRC } else { RelocKind::INT16 });
+        }
+        if let Some(word) = dst.word {
+            let kind = match (ext, has_src) {
+                (false, _)    => RelocKind::INT16,
+                (true, false) => EXT_DST,
+                (true, true)  => EXT_DST6,
+            };
+            self.word(word, kind);
+        }
+        Ok(())
+    }
+
+    /// Emits a single-operand (format II) instruction.
+    fn one(&mut self, base: u16, ext: bool, src: Ea) -> Result {
+        let (al, bw) = self.size_bits(ext);
+
+        if ext {
+            self.words.push(0x1800 | al << 6);
+        }
+        self.words.push(base | bw << 6 | src.mode << 4 | src.reg);
+
+        if let Some(word) = src.word {
+            self.word(word, if ext { EXT_DST } else { RelocKind::INT16 });
+        }
+        Ok(())
+    }
+
+    /// Emits a `mova` instruction.
+    fn mova(&mut self, src: &Operand, dst: &Operand) -> Result {
+        use Operand::{Abs, Idx, Imm, Ind, PostInc, Reg as R};
+
+        match (src, dst) {
+            (R(s),       R(d))      => self.push(0x00C0 | s << 8 | d),
+            (Ind(s),     R(d))      => self.push(          s << 8 | d),
+            (PostInc(s), R(d))      => self.push(0x0010 | s << 8 | d),
+            (Abs(e),     R(d))      => self.addr(0x0020 | d, e, ADDR_SRC),
+            (Idx(s, e),  R(d))      => self.index(0x0030 | s << 8 | d, e, *s == PC),
+            (Imm(e),     R(d))      => self.addr(0x0080 | d, e, ADDR_SRC),
+            (R(s),       Abs(e))    => self.addr(0x0060 | s << 8, e, ADDR_DST),
+            (R(s),       Idx(d, e)) => self.index(0x0070 | s << 8 | d, e, *d == PC),
+            (R(s),       Ind(d))    => {
+                let zero = Expr::Int(self.span, 0);
+                self.index(0x0070 | s << 8 | d, &zero, false)
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits an address instruction whose 20-bit value `expr` is split
+    /// between the opcode word and the word after it.
+    fn addr(&mut self, opcode: u16, expr: &Expr<Span>, kind: RelocKind) -> Result {
+        self.words.push(opcode);
+        self.words.push(0);
+        self.field(0, expr.clone(), kind, None);
+        Ok(())
+    }
+
+    /// Emits an address instruction followed by a 16-bit index.
+    fn index(&mut self, opcode: u16, expr: &Expr<Span>, pc_rel: bool) -> Result {
+        self.words.push(opcode);
+        self.word((expr.clone(), pc_rel), RelocKind::INT16);
+        Ok(())
+    }
+
+    /// Appends an instruction word.
+    fn push(&mut self, word: u16) -> Result {
+        self.words.push(word);
+        Ok(())
+    }
+
+    /// Appends an operand word with the given value.  An extended value
+    /// begins at the extension word; a 16-bit value begins at the operand
+    /// word itself.
+    fn word(&mut self, (expr, pc_rel): (Expr<Span>, bool), kind: RelocKind) {
+        let index = self.words.len();
+        let at    = if kind.size == 2 { index } else { 0 };
+        let pc    = pc_rel.then_some(index as i64 * 2);
+        self.words.push(0);
+        self.field(at, expr, kind, pc);
+    }
+
+    fn field(&mut self, at: usize, expr: Expr<Span>, kind: RelocKind, pc: Option<i64>) {
+        self.fields.push(Field { at, expr, kind, pc });
+    }
+
+    /// Returns the A/L and B/W bits of the instruction size.
+    fn size_bits(&self, ext: bool) -> (u16, u16) {
+        match (ext, self.size) {
+            (false, BYTE) => (0, 1),
+            (false, _)    => (0, 0),
+            (true,  BYTE) => (1, 1),
+            (true,  ADDR) => (0, 1),
+            (true,  _)    => (1, 0),
+        }
+    }
+
+    /// Returns `base` for the `.a` form, or `base | word` for the `.w` form.
+    fn sized(&self, base: u16, word: u16) -> u16 {
+        match self.size {
+            ADDR => base,
+            _    => base | word,
+        }
+    }
+
+    /// Returns the encoding of a source operand.
+    fn source(&mut self, op: &Operand) -> Result<Ea> {
+        let ea = match *op {
+            Operand::Reg(r)        => Ea::new(r, 0),
+            Operand::Idx(r, ref e) => Ea { reg: r,  mode: 1, word: Some((e.clone(), r == PC)) },
+            Operand::Abs(ref e)    => Ea { reg: SR, mode: 1, word: Some((e.clone(), false)) },
+            Operand::Ind(r)        => Ea::new(r, 2),
+            Operand::PostInc(r)    => Ea::new(r, 3),
+            Operand::Imm(ref e)    => {
+                let cg = match self.out.eval(e) {
+                    Some(Value::Const(v)) => self.constant(v),
+                    _                     => None,
+                };
+                // Relax from the constant generator to an extension word;
+                // the first pass may take the short form before it fits.
+                match self.out.relax(2, &|form| form == 1 || cg.is_some()) {
+                    0 => cg.unwrap_or(Ea::new(CG, 0)),
+                    _ => Ea { reg: PC, mode: 3, word: Some((e.clone(), false)) },
+                }
+            },
+        };
+        Ok(ea)
+    }
+
+    /// Returns the encoding of a destination operand.
+    fn dest(&mut self, op: &Operand) -> Result<Ea> {
+        let ea = match *op {
+            Operand::Reg(r)        => Ea::new(r, 0),
+            Operand::Idx(r, ref e) => Ea { reg: r,  mode: 1, word: Some((e.clone(), r == PC)) },
+            Operand::Abs(ref e)    => Ea { reg: SR, mode: 1, word: Some((e.clone(), false)) },
+            Operand::Ind(r)        => {
+                let zero = Expr::Int(self.span, 0);
+                Ea { reg: r, mode: 1, word: Some((zero, false)) }
+            },
+            _ => return Err("invalid destination operand".into()),
+        };
+        Ok(ea)
+    }
+
+    /// Returns the constant generator encoding of the given immediate value,
+    /// if any.
+    fn constant(&self, value: i64) -> Option<Ea> {
+        let ones = match self.size {
+            BYTE => 0xFF,
+            ADDR => 0xF_FFFF,
+            _    => 0xFFFF,
+        };
+        let (reg, mode) = match value {
+            0              => (CG, 0),
+            1              => (CG, 1),
+            2              => (CG, 2),
+            4              => (SR, 2),
+            8              => (SR, 3),
+            -1             => (CG, 3),
+            v if v == ones => (CG, 3),
+            _              => return None,
+        };
+        Some(Ea::new(reg, mode))
+    }
+
+    /// Evaluates the given constant expression, which must lie within `min`
+    /// and `max`.  `what` names the value in the error message.
+    fn range(&mut self, expr: &Expr<Span>, min: u16, max: u16, what: &str) -> Result<u16> {
+        let value = match self.out.eval(expr) {
+            Some(Value::Const(v)) => v,
+            Some(Value::Reloc(v)) => match self.out.is_final() {
+                true  => return Err("expected: constant expression".into()),
+                false => v.addend,
+            },
+            None => min as i64,
+        };
+        match (min as i64..=max as i64).contains(&value) {
+            true  => Ok(value as u16),
+            false => match self.out.is_final() {
+                true  => Err(format!("{} {} out of range", what, value)),
+                false => Ok(min),
+            },
+        }
+    }
+
+    /// Completes the fields of the instruction and emits it, recording a
+    /// relocation for each field that the linker must compute.
+    fn finish(mut self) {
+        let mut relocs = vec![];
+
+        for field in std::mem::take(&mut self.fields) {
+            let value = match self.out.eval(&field.expr) {
+                Some(value) => value,
+                None        => continue,
+            };
+            let value = match field.pc {
+                Some(offset) => pc_rel_value(self.out, self.span, value, offset),
+                None         => value,
+            };
+            match value {
+                Value::Const(v) => self.apply(&field, v),
+                Value::Reloc(_) => relocs.push(field),
+            }
+        }
+
+        for (i, &word) in self.words.iter().enumerate() {
+            for field in relocs.iter().filter(|f| f.at == i) {
+                match field.pc {
+                    Some(offset) => self.out.reloc(&pc_rel_expr(&field.expr, offset), field.kind),
+                    None         => self.out.reloc(&field.expr, field.kind),
+                }
+            }
+            self.out.emit(&word.to_le_bytes());
+        }
+    }
+
+    /// Merges the value of the given field into the instruction words.
+    fn apply(&mut self, field: &Field, value: i64) {
+        let words = &mut self.words[field.at..field.at + field.kind.size / 2];
+
+        let bits = words
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &w| acc << 16 | w as u64);
+
+        match (field.kind.apply)(value, bits) {
+            Ok(bits) => {
+                for (i, w) in words.iter_mut().enumerate() {
+                    *w = (bits >> (16 * i)) as u16;
+                }
+            },
+            Err(msg) => self.out.error(*field.expr.data(), &msg),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn operand(arg: &Arg<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let expr = match *arg {
+        Arg::Expr(ref e) => e,
+        Arg::Unknown(span) => {
+            out.error(span, "expected: operand");
+            return None;
+        },
+    };
+
+    match *expr {
+        Expr::Ident(_, name) => match register(&out.names()[name]) {
+            Some(r) => Some(Operand::Reg(r)),
+            None    => Some(Operand::Imm(expr.clone())),
+        },
+        Expr::Deref(span, ref inner, post) => memory(span, inner, post, out),
+        _ => Some(Operand::Imm(expr.clone())),
+    }
+}
+
+/// Parses the contents of a memory operand `[...]`, which is followed by `!`
+/// if `post` is true.
+fn memory(span: Span, inner: &Expr<Span>, post: bool, out: &mut dyn Emitter) -> Option<Operand> {
+    let mut terms = vec![];
+    flatten_sum(inner, false, &mut terms);
+
+    let mut base = None;
+    let mut rest = vec![];
+
+    for (neg, term) in terms {
+        let reg = match *term {
+            Expr::Ident(_, name) => register(&out.names()[name]),
+            _                    => None,
+        };
+        match reg {
+            Some(r) if !neg && base.is_none() && r != SR && r != CG => base = Some(r),
+            Some(_) => {
+                out.error(*term.data(), "invalid memory operand");
+                return None;
+            },
+            None => rest.push((neg, term)),
+        }
+    }
+
+    match (base, sum(&rest), post) {
+        (None,    Some(addr), false) => Some(Operand::Abs(addr)),
+        (Some(r), None,       false) => Some(Operand::Ind(r)),
+        (Some(r), None,       true)  => Some(Operand::PostInc(r)),
+        (Some(r), Some(x),    false) => Some(Operand::Idx(r, x)),
+        _ => {
+            out.error(span, "invalid memory operand");
+            None
+        },
+    }
+}
+
+/// Returns the number of the register with the given name, if any.
+fn register(name: &str) -> Option<u16> {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        "pc" => return Some(PC),
+        "sp" => return Some(SP),
+        "sr" => return Some(SR),
+        "cg" => return Some(CG),
+        _    => (),
+    }
+    match name.strip_prefix('r')?.parse() {
+        Ok(n @ 0..=15) if !name.starts_with("r0") || name == "r0" => Some(n),
+        _                                                         => None,
+    }
+}
+
+/// Returns a number that identifies the register with the given name, the
+/// same for each name of the register, if any.
+pub(super) fn register_key(name: &str) -> Option<u32> {
+    register(name).map(u32::from)
+}
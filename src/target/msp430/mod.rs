@@ -0,0 +1,220 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Texas Instruments MSP430 and MSP430X.
+//!
+//! Instructions use TI mnemonics with an optional size suffix `.b`, `.w`, or
+//! (on MSP430X) `.a`.  Operands use ras syntax:
+//!
+//! | Mode                          | TI                  | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | register                      | `r5`                | `r5`
+//! | indexed                       | `4(r5)`             | `[r5 + 4]`
+//! | symbolic                      | `label`             | `[pc + label]`
+//! | absolute                      | `&0x200`            | `[x'200]`
+//! | indirect                      | `@r5`               | `[r5]`
+//! | indirect auto-increment       | `@r5+`              | `[r5]!`
+//! | immediate                     | `#42`               | `42`
+//!
+//! An immediate source of -1, 0, 1, 2, 4, or 8 known at assembly time comes
+//! from the constant generator and takes no extension word.  Once an
+//! immediate takes an extension word, it keeps it in later layout passes.  A jump takes
+//! its target address.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{fits, Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::{Decoded, Emitter, Target};
+
+mod decode;
+mod encode;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Member of the MSP430 family.
+#[derive(Debug)]
+pub struct Msp430 {
+    name:  &'static str,
+    x:     bool,
+    index: HashMap<Name, usize>,
+}
+
+/// Size suffixes, in order of their size code.
+const SIZES: [(&str, u8); 4] = [("", W), (".b", B), (".w", W), (".a", A)];
+
+/// Size code of a mnemonic without a suffix.
+pub const NO_SIZE: usize = 0;
+
+/// Size code of the `.b` suffix.
+pub const BYTE: usize = 1;
+
+/// Size code of the `.a` suffix.
+pub const ADDR: usize = 3;
+
+impl Msp430 {
+    /// Creates a new [`Msp430`] target with the given `name`, interning its
+    /// mnemonics in `names`.  If `x` is true, the target has the MSP430X
+    /// instructions.
+    pub fn new(names: &mut NameTable, name: &'static str, x: bool) -> Self {
+        let mut index = HashMap::new();
+
+        for (i, entry) in ENTRIES.iter().enumerate() {
+            if entry.flags & X != 0 && !x {
+                continue;
+            }
+            for (size, &(suffix, flag)) in SIZES.iter().enumerate() {
+                if size != NO_SIZE && entry.flags & flag == 0 {
+                    continue;
+                }
+                let insn  = i << 2 | size;
+                let lower = format!("{}{}", entry.name, suffix);
+                index.insert(names.add(&lower),                insn);
+                index.insert(names.add(&lower.to_uppercase()), insn);
+            }
+        }
+
+        Self { name, x, index }
+    }
+}
+
+impl Target for Msp430 {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        2
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[
+            JUMP10, EXT_SRC, EXT_DST, EXT_DST6, ADDR_SRC, ADDR_DST,
+            RelocKind::INT8, RelocKind::INT16, RelocKind::INT32,
+        ]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        encode::register_key(name)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        encode::encode(&ENTRIES[insn >> 2], insn & 3, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        decode::decode(self.x, bytes, addr, label)
+    }
+}
+
+/// Creates an MSP430 target.
+pub fn new_msp430(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Msp430::new(names, "msp430", false))
+}
+
+/// Creates an MSP430X target.
+pub fn new_msp430x(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Msp430::new(names, "msp430x", true))
+}
+
+// ----------------------------------------------------------------------------
+
+/// 10-bit jump offset.
+pub const JUMP10: RelocKind = RelocKind { name: "msp430-jump10", size: 2, apply: apply_jump10 };
+
+/// 20-bit source value split between an extension word and the source word
+/// 4 bytes after it.
+pub const EXT_SRC: RelocKind = RelocKind { name: "msp430x-ext-src", size: 6, apply: apply_ext_src };
+
+/// 20-bit destination value split between an extension word and the
+/// destination word 4 bytes after it.
+pub const EXT_DST: RelocKind = RelocKind { name: "msp430x-ext-dst", size: 6, apply: apply_ext_dst };
+
+/// 20-bit destination value split between an extension word and the
+/// destination word 6 bytes after it.
+pub const EXT_DST6: RelocKind = RelocKind { name: "msp430x-ext-dst6", size: 8, apply: apply_ext_dst6 };
+
+/// 20-bit source value split between bits 11-8 of an address instruction
+/// and the word after it.
+pub const ADDR_SRC: RelocKind = RelocKind { name: "msp430x-addr-src", size: 4, apply: apply_addr_src };
+
+/// 20-bit destination value split between bits 3-0 of an address
+/// instruction and the word after it.
+pub const ADDR_DST: RelocKind = RelocKind { name: "msp430x-addr-dst", size: 4, apply: apply_addr_dst };
+
+fn apply_jump10(value: i64, field: u64) -> Result<u64, String> {
+    match value & 1 == 0 && (-1024..=1022).contains(&value) {
+        true  => Ok(field & 0xFC00 | (value >> 1) as u64 & 0x3FF),
+        false => Err(format!("jump offset {} out of range", value)),
+    }
+}
+
+/// Splits a 20-bit value into its high 4 bits and low 16 bits.
+fn split20(value: i64) -> Result<(u64, u64), String> {
+    match fits(value, 20) {
+        true  => Ok((value as u64 >> 16 & 0xF, value as u64 & 0xFFFF)),
+        false => Err(format!("value {} does not fit in 20 bits", value)),
+    }
+}
+
+fn apply_ext_src(value: i64, field: u64) -> Result<u64, String> {
+    let (hi, lo) = split20(value)?;
+    Ok(field & 0x0000_FFFF_F87F | hi << 7 | lo << 32)
+}
+
+fn apply_ext_dst(value: i64, field: u64) -> Result<u64, String> {
+    let (hi, lo) = split20(value)?;
+    Ok(field & 0x0000_FFFF_FFF0 | hi | lo << 32)
+}
+
+fn apply_ext_dst6(value: i64, field: u64) -> Result<u64, String> {
+    let (hi, lo) = split20(value)?;
+    Ok(field & 0x0000_FFFF_FFFF_FFF0 | hi | lo << 48)
+}
+
+fn apply_addr_src(value: i64, field: u64) -> Result<u64, String> {
+    let (hi, lo) = split20(value)?;
+    Ok(field & 0xF0FF | hi << 8 | lo << 16)
+}
+
+fn apply_addr_dst(value: i64, field: u64) -> Result<u64, String> {
+    let (hi, lo) = split20(value)?;
+    Ok(field & 0xFFF0 | hi | lo << 16)
+}
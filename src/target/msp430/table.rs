@@ -0,0 +1,221 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction table.
+
+// ----------------------------------------------------------------------------
+
+/// Instruction kinds, each with the opcode bits that its operands complete.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    /// Double-operand instruction (format I): `add src, dst`.
+    Two(u16),
+
+    /// Single-operand instruction (format II): `push src`.
+    One(u16),
+
+    /// Conditional or unconditional jump: `jne target`.
+    Jump(u16),
+
+    /// No operands.
+    Fixed(u16),
+
+    /// Emulated double-operand instruction with an immediate source:
+    /// `inc dst` is `add 1, dst`.
+    EmuImm(u16, i64),
+
+    /// Emulated double-operand instruction with the destination as both
+    /// operands: `rla dst` is `add dst, dst`.
+    EmuDouble(u16),
+
+    /// `pop dst`, which is `mov [sp]!, dst`.
+    Pop,
+
+    /// `br src`, which is `mov src, pc`.
+    Br,
+
+    /// `mova src, dst`.
+    Mova,
+
+    /// `bra src`, which is `mova src, pc`.
+    Bra,
+
+    /// Address-word arithmetic, with the opcodes of the immediate form and
+    /// the register form: `adda src, rd`.
+    Addr(u16, u16),
+
+    /// `calla dst`.
+    Calla,
+
+    /// `pushm n, rd`.
+    Pushm,
+
+    /// `popm n, rd`.
+    Popm,
+
+    /// Multiple-bit rotate: `rrcm n, rd`.
+    Rotm(u16),
+}
+
+/// Flag: `.b` form.
+pub const B: u8 = 1 << 0;
+
+/// Flag: `.w` form, which the mnemonic without a suffix also selects.
+pub const W: u8 = 1 << 1;
+
+/// Flag: `.a` form.
+pub const A: u8 = 1 << 2;
+
+/// Flag: instruction takes an extension word.
+pub const E: u8 = 1 << 3;
+
+/// Flag: MSP430X instruction.
+pub const X: u8 = 1 << 4;
+
+/// Entry in the instruction table.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    /// Mnemonic.
+    pub name: &'static str,
+
+    /// Instruction kind.
+    pub op: Op,
+
+    /// Flags: forms and features.
+    pub flags: u8,
+}
+
+const fn e(name: &'static str, op: Op, flags: u8) -> Entry {
+    Entry { name, op, flags }
+}
+
+/// Flags of an MSP430X instruction with an extension word.
+const EXT: u8 = B | W | A | E | X;
+
+/// Instruction table.
+pub static ENTRIES: &[Entry] = &[
+    // Double-operand instructions
+    e("mov",    Op::Two(0x4000),                    B | W),
+    e("add",    Op::Two(0x5000),                    B | W),
+    e("addc",   Op::Two(0x6000),                    B | W),
+    e("subc",   Op::Two(0x7000),                    B | W),
+    e("sub",    Op::Two(0x8000),                    B | W),
+    e("cmp",    Op::Two(0x9000),                    B | W),
+    e("dadd",   Op::Two(0xA000),                    B | W),
+    e("bit",    Op::Two(0xB000),                    B | W),
+    e("bic",    Op::Two(0xC000),                    B | W),
+    e("bis",    Op::Two(0xD000),                    B | W),
+    e("xor",    Op::Two(0xE000),                    B | W),
+    e("and",    Op::Two(0xF000),                    B | W),
+
+    // Single-operand instructions
+    e("rrc",    Op::One(0x1000),                    B | W),
+    e("swpb",   Op::One(0x1080),                    W),
+    e("rra",    Op::One(0x1100),                    B | W),
+    e("sxt",    Op::One(0x1180),                    W),
+    e("push",   Op::One(0x1200),                    B | W),
+    e("call",   Op::One(0x1280),                    0),
+    e("reti",   Op::Fixed(0x1300),                  0),
+
+    // Jumps
+    e("jne",    Op::Jump(0x2000),                   0),
+    e("jnz",    Op::Jump(0x2000),                   0),
+    e("jeq",    Op::Jump(0x2400),                   0),
+    e("jz",     Op::Jump(0x2400),                   0),
+    e("jnc",    Op::Jump(0x2800),                   0),
+    e("jlo",    Op::Jump(0x2800),                   0),
+    e("jc",     Op::Jump(0x2C00),                   0),
+    e("jhs",    Op::Jump(0x2C00),                   0),
+    e("jn",     Op::Jump(0x3000),                   0),
+    e("jge",    Op::Jump(0x3400),                   0),
+    e("jl",     Op::Jump(0x3800),                   0),
+    e("jmp",    Op::Jump(0x3C00),                   0),
+
+    // Emulated instructions
+    e("nop",    Op::Fixed(0x4303),                  0),
+    e("ret",    Op::Fixed(0x4130),                  0),
+    e("clrc",   Op::Fixed(0xC312),                  0),
+    e("setc",   Op::Fixed(0xD312),                  0),
+    e("clrz",   Op::Fixed(0xC322),                  0),
+    e("setz",   Op::Fixed(0xD322),                  0),
+    e("clrn",   Op::Fixed(0xC222),                  0),
+    e("setn",   Op::Fixed(0xD222),                  0),
+    e("dint",   Op::Fixed(0xC232),                  0),
+    e("eint",   Op::Fixed(0xD232),                  0),
+    e("clr",    Op::EmuImm(0x4000,  0),             B | W),
+    e("inc",    Op::EmuImm(0x5000,  1),             B | W),
+    e("incd",   Op::EmuImm(0x5000,  2),             B | W),
+    e("dec",    Op::EmuImm(0x8000,  1),             B | W),
+    e("decd",   Op::EmuImm(0x8000,  2),             B | W),
+    e("inv",    Op::EmuImm(0xE000, -1),             B | W),
+    e("tst",    Op::EmuImm(0x9000,  0),             B | W),
+    e("adc",    Op::EmuImm(0x6000,  0),             B | W),
+    e("sbc",    Op::EmuImm(0x7000,  0),             B | W),
+    e("dadc",   Op::EmuImm(0xA000,  0),             B | W),
+    e("rla",    Op::EmuDouble(0x5000),              B | W),
+    e("rlc",    Op::EmuDouble(0x6000),              B | W),
+    e("pop",    Op::Pop,                            B | W),
+    e("br",     Op::Br,                             0),
+
+    // MSP430X extended instructions
+    e("movx",   Op::Two(0x4000),                    EXT),
+    e("addx",   Op::Two(0x5000),                    EXT),
+    e("addcx",  Op::Two(0x6000),                    EXT),
+    e("subcx",  Op::Two(0x7000),                    EXT),
+    e("subx",   Op::Two(0x8000),                    EXT),
+    e("cmpx",   Op::Two(0x9000),                    EXT),
+    e("daddx",  Op::Two(0xA000),                    EXT),
+    e("bitx",   Op::Two(0xB000),                    EXT),
+    e("bicx",   Op::Two(0xC000),                    EXT),
+    e("bisx",   Op::Two(0xD000),                    EXT),
+    e("xorx",   Op::Two(0xE000),                    EXT),
+    e("andx",   Op::Two(0xF000),                    EXT),
+    e("rrcx",   Op::One(0x1000),                    EXT),
+    e("swpbx",  Op::One(0x1080),                    W | A | E | X),
+    e("rrax",   Op::One(0x1100),                    EXT),
+    e("sxtx",   Op::One(0x1180),                    W | A | E | X),
+    e("pushx",  Op::One(0x1200),                    EXT),
+    e("clrx",   Op::EmuImm(0x4000,  0),             EXT),
+    e("incx",   Op::EmuImm(0x5000,  1),             EXT),
+    e("incdx",  Op::EmuImm(0x5000,  2),             EXT),
+    e("decx",   Op::EmuImm(0x8000,  1),             EXT),
+    e("decdx",  Op::EmuImm(0x8000,  2),             EXT),
+    e("invx",   Op::EmuImm(0xE000, -1),             EXT),
+    e("tstx",   Op::EmuImm(0x9000,  0),             EXT),
+    e("adcx",   Op::EmuImm(0x6000,  0),             EXT),
+    e("sbcx",   Op::EmuImm(0x7000,  0),             EXT),
+    e("dadcx",  Op::EmuImm(0xA000,  0),             EXT),
+    e("rlax",   Op::EmuDouble(0x5000),              EXT),
+    e("rlcx",   Op::EmuDouble(0x6000),              EXT),
+    e("popx",   Op::Pop,                            EXT),
+
+    // MSP430X address instructions
+    e("mova",   Op::Mova,                           X),
+    e("bra",    Op::Bra,                            X),
+    e("cmpa",   Op::Addr(0x0090, 0x00D0),           X),
+    e("adda",   Op::Addr(0x00A0, 0x00E0),           X),
+    e("suba",   Op::Addr(0x00B0, 0x00F0),           X),
+    e("calla",  Op::Calla,                          X),
+    e("reta",   Op::Fixed(0x0110),                  X),
+    e("pushm",  Op::Pushm,                          W | A | X),
+    e("popm",   Op::Popm,                           W | A | X),
+    e("rrcm",   Op::Rotm(0x0040),                   W | A | X),
+    e("rram",   Op::Rotm(0x0140),                   W | A | X),
+    e("rlam",   Op::Rotm(0x0240),                   W | A | X),
+    e("rrum",   Op::Rotm(0x0340),                   W | A | X),
+];
@@ -0,0 +1,264 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, le16};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u16> = Bench { origin: 0x200, split: le16 };
+
+#[test]
+fn addressing_modes() {
+    BENCH.check("msp430", &[
+        ("mov r4, r5",                          &[0x4405]),
+        ("MOV.B r4, r5",                        &[0x4445]),
+        ("mov.w sp, pc",                        &[0x4100]),
+        ("mov 42, r5",                          &[0x4035, 0x002A]),
+        ("mov 255, r5",                         &[0x4035, 0x00FF]),
+        ("add [r4], r5",                        &[0x5425]),
+        ("add [r4]!, r5",                       &[0x5435]),
+        ("add [r4 + 2], r5",                    &[0x5415, 0x0002]),
+        ("mov r5, [r4 + 2]",                    &[0x4584, 0x0002]),
+        ("mov r5, [r4]",                        &[0x4584, 0x0000]),
+        ("mov [x'200], [x'202]",                &[0x4292, 0x0200, 0x0202]),
+        ("mov [pc + x'210], r5",                &[0x4015, 0x000E]),
+        ("mov r5, [pc + x'200]",                &[0x4580, 0xFFFE]),
+        ("mov [pc + x'210], [pc + x'210]",      &[0x4090, 0x000E, 0x000C]),
+    ]);
+}
+
+#[test]
+fn constant_generator() {
+    BENCH.check("msp430", &[
+        ("mov 0, r5",                           &[0x4305]),
+        ("mov 1, r5",                           &[0x4315]),
+        ("mov 2, r5",                           &[0x4325]),
+        ("mov 4, r5",                           &[0x4225]),
+        ("mov 8, r5",                           &[0x4235]),
+        ("mov -1, r5",                          &[0x4335]),
+        ("mov x'ffff, r5",                      &[0x4335]),
+        ("mov.b 255, r5",                       &[0x4375]),
+        ("push 8",                              &[0x1232]),
+        ("mov later, r5\nlater:",               &[0x4035, 0x0204]),
+        ("a: mov b - a + 4, r5\nb:",            &[0x4035, 0x0008]),
+    ]);
+}
+
+#[test]
+fn instructions() {
+    BENCH.check("msp430", &[
+        ("push r5",                             &[0x1205]),
+        ("push.b r5",                           &[0x1245]),
+        ("push 42",                             &[0x1230, 0x002A]),
+        ("call x'1234",                         &[0x12B0, 0x1234]),
+        ("swpb r5",                             &[0x1085]),
+        ("sxt r5",                              &[0x1185]),
+        ("rra.b [r5]",                          &[0x1165]),
+        ("reti",                                &[0x1300]),
+        ("jmp x'200",                           &[0x3FFF]),
+        ("jne x'210",                           &[0x2007]),
+        ("jz x'200",                            &[0x27FF]),
+        ("nop",                                 &[0x4303]),
+        ("ret",                                 &[0x4130]),
+        ("clrc",                                &[0xC312]),
+        ("eint",                                &[0xD232]),
+        ("clr r5",                              &[0x4305]),
+        ("inc r5",                              &[0x5315]),
+        ("incd r5",                             &[0x5325]),
+        ("dec r5",                              &[0x8315]),
+        ("inv.b r5",                            &[0xE375]),
+        ("tst r5",                              &[0x9305]),
+        ("rla r5",                              &[0x5505]),
+        ("rlc.b [r4 + 1]",                      &[0x64D4, 0x0001, 0x0001]),
+        ("pop r5",                              &[0x4135]),
+        ("br r5",                               &[0x4500]),
+        ("br x'1234",                           &[0x4030, 0x1234]),
+    ]);
+}
+
+#[test]
+fn extended() {
+    BENCH.check("msp430x", &[
+        ("movx.a r4, r5",                       &[0x1800, 0x4445]),
+        ("movx r4, r5",                         &[0x1840, 0x4405]),
+        ("movx.b r4, r5",                       &[0x1840, 0x4445]),
+        ("movx.a x'12345, r5",                  &[0x1880, 0x4075, 0x2345]),
+        ("movx.a r5, [x'12345]",                &[0x1801, 0x45C2, 0x2345]),
+        ("movx.a [x'10000], [x'20000]",         &[0x1882, 0x42D2, 0x0000, 0x0000]),
+        ("movx.a [r4 - 2], r5",                 &[0x1F80, 0x4455, 0xFFFE]),
+        ("pushx.a r5",                          &[0x1800, 0x1245]),
+        ("clrx.a r5",                           &[0x1800, 0x4345]),
+        ("popx.a r5",                           &[0x1800, 0x4175]),
+        ("mova r4, r5",                         &[0x04C5]),
+        ("mova [r4], r5",                       &[0x0405]),
+        ("mova [r4]!, r5",                      &[0x0415]),
+        ("mova [x'12345], r5",                  &[0x0125, 0x2345]),
+        ("mova [r4 + 4], r5",                   &[0x0435, 0x0004]),
+        ("mova x'12345, r5",                    &[0x0185, 0x2345]),
+        ("mova r5, [x'12345]",                  &[0x0561, 0x2345]),
+        ("mova r5, [r4 + 2]",                   &[0x0574, 0x0002]),
+        ("adda 4, r5",                          &[0x00A5, 0x0004]),
+        ("suba r4, r5",                         &[0x04F5]),
+        ("cmpa r4, r5",                         &[0x04D5]),
+        ("calla r5",                            &[0x1345]),
+        ("calla x'12345",                       &[0x13B1, 0x2345]),
+        ("calla [x'12345]",                     &[0x1381, 0x2345]),
+        ("calla [r5 + 4]",                      &[0x1355, 0x0004]),
+        ("reta",                                &[0x0110]),
+        ("bra r5",                              &[0x05C0]),
+        ("pushm.a 4, r10",                      &[0x143A]),
+        ("pushm 4, r10",                        &[0x153A]),
+        ("popm.a 4, r10",                       &[0x1637]),
+        ("popm.w 4, r10",                       &[0x1737]),
+        ("rrcm.a 2, r5",                        &[0x0445]),
+        ("rlam.w 4, r5",                        &[0x0E55]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("msp430x").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  mov label, r5
+                movx.a label + x'10000, r6
+                calla label + x'10000
+                jmp start
+        label:  ret
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(BENCH.words(&program.sections[0].data), [
+        0x4035, 0x0010, 0x1880, 0x4076, 0x0010, 0x13B1, 0x0010, 0x3FF8, 0x4130,
+    ]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("msp430",  "movx r4, r5"),         "unknown instruction 'movx'");
+    assert_eq!(BENCH.error("msp430x", "mov.a r4, r5"),        "unknown instruction 'mov.a'");
+    assert_eq!(BENCH.error("msp430",  "mov r5, 42"),          "invalid destination operand");
+    assert_eq!(BENCH.error("msp430",  "mov r5, [r4]!"),       "invalid destination operand");
+    assert_eq!(BENCH.error("msp430",  "rra 5"),               "invalid operands");
+    assert_eq!(BENCH.error("msp430",  "mov [sr], r5"),        "invalid memory operand");
+    assert_eq!(BENCH.error("msp430",  "jmp x'800"),           "jump offset 1534 out of range");
+    assert_eq!(BENCH.error("msp430",  "mov x'12345, r5"),     "value 74565 does not fit in 16 bits");
+    assert_eq!(BENCH.error("msp430x", "movx.a x'123456, r5"), "value 1193046 does not fit in 20 bits");
+    assert_eq!(BENCH.error("msp430x", "popm.a 5, r3"),        "register count 5 out of range");
+    assert_eq!(BENCH.error("msp430x", "rrcm.a 5, r5"),        "rotate count 5 out of range");
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("msp430", &[
+            "mov r4, r5",
+            "mov.b r4, r5",
+            "mov sp, r5",
+            "mov x'2A, r5",
+            "mov 0, r5",
+            "mov -1, r5",
+            "mov.b -1, r5",
+            "add [r4], r5",
+            "add [r4]!, r5",
+            "add [r4 + 2], r5",
+            "mov r5, [r4 - 2]",
+            "mov [x'200], [x'202]",
+            "mov [pc + x'210], r5",
+            "push 8",
+            "push.b r5",
+            "swpb r5",
+            "rra.b [r5]",
+            "call start",
+            "reti",
+            "jmp start",
+            "jne x'210",
+            "nop",
+            "ret",
+            "clrc",
+            "eint",
+            "br r5",
+            "br start",
+        ][..]),
+        ("msp430x", &[
+            "movx.a r4, r5",
+            "movx r4, r5",
+            "movx.b r4, r5",
+            "movx.a x'12345, r5",
+            "movx.a r5, [x'12345]",
+            "movx.a [x'10000], [x'20000]",
+            "movx.a [r4 - 2], r5",
+            "pushx.a r5",
+            "mova r4, r5",
+            "mova [r4], r5",
+            "mova [r4]!, r5",
+            "mova [x'12345], r5",
+            "mova [r4 + 4], r5",
+            "mova x'12345, r5",
+            "mova r5, [x'12345]",
+            "mova r5, [r4 + 2]",
+            "adda 4, r5",
+            "suba r4, r5",
+            "calla r5",
+            "calla start",
+            "calla [x'12345]",
+            "calla [r5 + 4]",
+            "reta",
+            "bra r5",
+            "bra start",
+            "pushm.a 4, r10",
+            "pushm 4, r10",
+            "popm.a 4, r10",
+            "popm 4, r10",
+            "rrcm.a 2, r5",
+            "rlam 4, r5",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'200"));
+            let decoded    = decoder.decode(&bytes, 0x200, &label).unwrap();
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let msp430 = (find("msp430").unwrap().new)(&mut NameTable::new());
+    assert_eq!(msp430.decode(&[0xC5, 0x04], 0x200, &label), None);
+
+    // An immediate that the constant generator supplies does not decode
+    assert_eq!(msp430.decode(&[0x35, 0x40, 0x01, 0x00], 0x200, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(msp430.decode(&[0x35, 0x40, 0x2A], 0x200, &label), None);
+}
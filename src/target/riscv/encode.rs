@@ -0,0 +1,964 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.
+pub fn encode(target: &RiscV, entry: &Entry, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let mut e = Encoder { target, out, span: stmt.data, offset: 0 };
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| e.operand(arg))
+        .collect::<Option<Vec<_>>>();
+
+    if let Some(ops) = ops {
+        e.encode(entry, &ops, Mode::Auto);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+enum Operand {
+    /// Integer register.
+    Reg(u32, Span),
+
+    /// Memory operand: a base register and an optional offset.
+    Mem(u32, Option<Expr<Span>>, Span),
+
+    /// Immediate value or target address.
+    Imm(Expr<Span>),
+}
+
+impl Operand {
+    fn span(&self) -> Span {
+        match *self {
+            Operand::Reg(_, span)    => span,
+            Operand::Mem(_, _, span) => span,
+            Operand::Imm(ref expr)   => *expr.data(),
+        }
+    }
+}
+
+/// Whether to use the 16-bit encoding of an instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mode {
+    /// Use the 16-bit encoding if the target has the C extension and the
+    /// operands permit.
+    Auto,
+
+    /// Require the 16-bit encoding.
+    Require,
+
+    /// Use the 32-bit encoding.
+    Full,
+}
+
+/// Immediate field of an instruction.
+#[derive(Clone, Debug)]
+struct Imm {
+    /// Expression whose value the field holds.
+    expr: Expr<Span>,
+
+    /// Value of the expression.
+    value: Value,
+
+    /// How the value is merged into the instruction.
+    kind: RelocKind,
+
+    /// Value of the field as the processor sees it, if known.
+    field: Option<i64>,
+}
+
+/// Instruction with resolved operands.
+struct Insn<'t> {
+    entry: &'t Entry,
+    rd:    u32,
+    rs1:   u32,
+    rs2:   u32,
+    imm:   Option<Imm>,
+}
+
+impl Insn<'_> {
+    /// Returns the instruction word, excluding the immediate field.
+    fn word(&self) -> u32 {
+        self.entry.bits | self.rd << 7 | self.rs1 << 15 | self.rs2 << 20
+    }
+}
+
+/// Immediate field formats.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Field {
+    I,
+    S,
+    U,
+}
+
+struct Encoder<'a> {
+    target: &'a RiscV,
+    out:    &'a mut dyn Emitter,
+    span:   Span,
+    offset: i64,
+}
+
+impl<'a> Encoder<'a> {
+    fn encode(&mut self, entry: &'a Entry, ops: &[Operand], mode: Mode) -> Option<()> {
+        use Operand::*;
+
+        let mut insn = Insn { entry, rd: 0, rs1: 0, rs2: 0, imm: None };
+
+        match (entry.form, ops) {
+            (Form::R, &[Reg(rd, _), Reg(rs1, _), Reg(rs2, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.rs2 = rs2;
+            },
+            (Form::I, &[Reg(rd, _), Reg(rs1, _), Imm(ref imm)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.imm(imm, Field::I)?);
+            },
+            (Form::Shift | Form::ShiftW, &[Reg(rd, _), Reg(rs1, _), Imm(ref imm)]) => {
+                let max = match entry.form {
+                    Form::Shift => self.target.xlen() as i64 - 1,
+                    _           => 31,
+                };
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.rs2 = self.constant(imm, 0, max)? as u32;
+            },
+            (Form::Load, &[Reg(rd, _), Mem(rs1, ref off, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.offset(off, Field::I)?);
+            },
+            (Form::Store, &[Reg(rs2, _), Mem(rs1, ref off, _)]) => {
+                insn.rs1 = rs1;
+                insn.rs2 = rs2;
+                insn.imm = Some(self.offset(off, Field::S)?);
+            },
+            (Form::Branch, &[Reg(rs1, _), Reg(rs2, _), Imm(ref target)]) => {
+                insn.rs1 = rs1;
+                insn.rs2 = rs2;
+                insn.imm = Some(self.target(target, BRANCH)?);
+            },
+            (Form::U, &[Reg(rd, _), Imm(ref imm)]) => {
+                insn.rd  = rd;
+                insn.imm = Some(self.imm(imm, Field::U)?);
+            },
+            (Form::Jal, &[Imm(ref target)]) => {
+                insn.rd  = 1;
+                insn.imm = Some(self.target(target, JUMP)?);
+            },
+            (Form::Jal, &[Reg(rd, _), Imm(ref target)]) => {
+                insn.rd  = rd;
+                insn.imm = Some(self.target(target, JUMP)?);
+            },
+            (Form::Jalr, &[Reg(rs1, _)]) => {
+                insn.rd  = 1;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.offset(&None, Field::I)?);
+            },
+            (Form::Jalr, &[Reg(rd, _), Reg(rs1, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.offset(&None, Field::I)?);
+            },
+            (Form::Jalr, &[Reg(rd, _), Mem(rs1, ref off, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.offset(off, Field::I)?);
+            },
+            (Form::Jalr, &[Reg(rd, _), Reg(rs1, _), Imm(ref imm)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.imm = Some(self.imm(imm, Field::I)?);
+            },
+            (Form::Amo, &[Reg(rd, _), Reg(rs2, _), Mem(rs1, None, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.rs2 = rs2;
+            },
+            (Form::Lr, &[Reg(rd, _), Mem(rs1, None, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+            },
+            (Form::Fence, &[]) => {
+                insn.rs2 = 0xFF;
+            },
+            (Form::Fence, [pred, succ]) => {
+                insn.rs2 = self.fence_set(pred)? << 4 | self.fence_set(succ)?;
+            },
+            (Form::Fixed, &[]) => (),
+            (Form::Csr, &[Reg(rd, _), Imm(ref csr), Reg(rs1, _)]) => {
+                insn.rd  = rd;
+                insn.rs1 = rs1;
+                insn.rs2 = self.csr(csr)?;
+            },
+            (Form::CsrI, &[Reg(rd, _), Imm(ref csr), Imm(ref imm)]) => {
+                insn.rd  = rd;
+                insn.rs2 = self.csr(csr)?;
+                insn.rs1 = self.constant(imm, 0, 31)? as u32;
+            },
+            (Form::Pseudo(p), _) => {
+                return self.pseudo(p, ops, mode);
+            },
+            (Form::C(short, base), _) => {
+                let ops = self.expand(short, ops)?;
+                return self.encode(self.target.entry(base), &ops, Mode::Require);
+            },
+            _ => return self.fail(self.span, "invalid operands"),
+        }
+
+        self.emit(insn, mode)
+    }
+
+    // === Pseudo-instructions ===
+
+    fn pseudo(&mut self, p: Pseudo, ops: &[Operand], mode: Mode) -> Option<()> {
+        use Operand::*;
+
+        let span = self.span;
+        let zero = Reg(0, span);
+
+        let (base, ops) = match (p, ops) {
+            (Pseudo::Nop, &[]) => {
+                ("addi", vec![zero.clone(), zero, Imm(int(span, 0))])
+            },
+            (Pseudo::Li, &[Reg(rd, _), Imm(ref imm)]) => {
+                return self.li(rd, imm, mode);
+            },
+            (Pseudo::La, &[Reg(rd, _), Imm(ref sym)]) => {
+                return self.far(rd, "addi", rd, sym);
+            },
+            (Pseudo::Call, &[Imm(ref sym)]) => {
+                return self.far(1, "jalr", 1, sym);
+            },
+            (Pseudo::Tail, &[Imm(ref sym)]) => {
+                return self.far(6, "jalr", 0, sym);
+            },
+            (Pseudo::RegImm(base, k), &[ref rd @ Reg(..), ref rs @ Reg(..)]) => {
+                (base, vec![rd.clone(), rs.clone(), Imm(int(span, k))])
+            },
+            (Pseudo::ZeroReg(base), &[ref rd @ Reg(..), ref rs @ Reg(..)]) => {
+                (base, vec![rd.clone(), zero, rs.clone()])
+            },
+            (Pseudo::RegZero(base), &[ref rd @ Reg(..), ref rs @ Reg(..)]) => {
+                (base, vec![rd.clone(), rs.clone(), zero])
+            },
+            (Pseudo::BranchZ(base), &[ref rs @ Reg(..), ref target @ Imm(_)]) => {
+                (base, vec![rs.clone(), zero, target.clone()])
+            },
+            (Pseudo::ZBranch(base), &[ref rs @ Reg(..), ref target @ Imm(_)]) => {
+                (base, vec![zero, rs.clone(), target.clone()])
+            },
+            (Pseudo::BranchSwap(base), &[ref rs1 @ Reg(..), ref rs2 @ Reg(..), ref target]) => {
+                (base, vec![rs2.clone(), rs1.clone(), target.clone()])
+            },
+            (Pseudo::J, &[ref target @ Imm(_)]) => {
+                ("jal", vec![zero, target.clone()])
+            },
+            (Pseudo::Jr, &[ref rs @ Reg(..)]) => {
+                ("jalr", vec![zero, rs.clone(), Imm(int(span, 0))])
+            },
+            (Pseudo::Ret, &[]) => {
+                ("jalr", vec![zero, Reg(1, span), Imm(int(span, 0))])
+            },
+            (Pseudo::Csrr, &[ref rd @ Reg(..), ref csr @ Imm(_)]) => {
+                ("csrrs", vec![rd.clone(), csr.clone(), zero])
+            },
+            (Pseudo::CsrWrite(base), &[ref csr @ Imm(_), ref rs]) => {
+                (base, vec![zero, csr.clone(), rs.clone()])
+            },
+            _ => return self.fail(span, "invalid operands"),
+        };
+
+        self.encode(self.target.entry(base), &ops, mode)
+    }
+
+    /// Rearranges the operands of a compressed instruction into those of the
+    /// full instruction.
+    fn expand(&mut self, short: Short, ops: &[Operand]) -> Option<Vec<Operand>> {
+        use Operand::*;
+
+        let span = self.span;
+
+        let ops = match (short, ops) {
+            (Short::Same, _) => ops.to_vec(),
+            (Short::Rd2, &[ref rd @ Reg(..), ref x]) => {
+                vec![rd.clone(), rd.clone(), x.clone()]
+            },
+            (Short::Zero, &[ref rd @ Reg(..), ref x]) => {
+                vec![rd.clone(), Reg(0, span), x.clone()]
+            },
+            (Short::Jump(link), &[ref target @ Imm(_)]) => {
+                vec![Reg(link, span), target.clone()]
+            },
+            (Short::JumpReg(link), &[ref rs @ Reg(..)]) => {
+                vec![Reg(link, span), rs.clone(), Imm(int(span, 0))]
+            },
+            (Short::BranchZ, &[ref rs @ Reg(..), ref target @ Imm(_)]) => {
+                vec![rs.clone(), Reg(0, span), target.clone()]
+            },
+            _ => return self.fail(span, "invalid operands"),
+        };
+
+        Some(ops)
+    }
+
+    /// Emits the shortest sequence that loads the value of `expr` into `rd`.
+    fn li(&mut self, rd: u32, expr: &Expr<Span>, mode: Mode) -> Option<()> {
+        let value = self.out.eval(expr)?;
+
+        if !is_literal(expr) {
+            return self.li_symbol(rd, expr, value);
+        }
+
+        let value = match value {
+            Value::Const(v) => v,
+            Value::Reloc(_) => unreachable!("literal is constant"),
+        };
+
+        if self.target.xlen() == 32 {
+            if !fits(value, 32) {
+                let msg = format!("value {} does not fit in 32 bits", value);
+                return self.fail(*expr.data(), &msg);
+            }
+            return self.li_const(rd, sext(value, 32), mode);
+        }
+
+        self.li_const(rd, value, mode)
+    }
+
+    fn li_const(&mut self, rd: u32, value: i64, mode: Mode) -> Option<()> {
+        let lo = sext(value, 12);
+
+        if is_signed(value, 12) {
+            let imm = self.constant_imm(value, I12);
+            return self.op("addi", rd, 0, 0, Some(imm), mode);
+        }
+
+        if is_signed(value, 32) {
+            let hi = self.constant_imm(hi20(value), U20);
+            self.op("lui", rd, 0, 0, Some(hi), mode)?;
+
+            if lo == 0 {
+                return Some(());
+            }
+
+            let add = if self.target.xlen() == 64 { "addiw" } else { "addi" };
+            let imm = self.constant_imm(lo, I12);
+            return self.op(add, rd, rd, 0, Some(imm), mode);
+        }
+
+        // Load the upper bits without their trailing zeros, then shift them
+        // into place and add the low 12 bits.
+        let hi    = value.wrapping_sub(lo) >> 12;
+        let shift = 12 + hi.trailing_zeros();
+        let hi    = value.wrapping_sub(lo) >> shift;
+
+        self.li_const(rd, hi, mode)?;
+        self.op("slli", rd, rd, shift, None, mode)?;
+
+        if lo == 0 {
+            return Some(());
+        }
+
+        let imm = self.constant_imm(lo, I12);
+        self.op("addi", rd, rd, 0, Some(imm), mode)
+    }
+
+    /// Emits a sequence that loads the value of `expr`, which depends on a
+    /// symbol, into `rd`.  The sequence is span-dependent: it grows from
+    /// `c.li` to `addi` to `lui` and `addi`, then on RV64 to the general
+    /// sequence padded to eight instructions.
+    fn li_symbol(&mut self, rd: u32, expr: &Expr<Span>, value: Value) -> Option<()> {
+        let rv64  = self.target.xlen() == 64;
+        let short = self.target.isa & EXT_C != 0 && rd != 0;
+
+        let known = match value {
+            Value::Const(v) if rv64 || fits(v, 32) => Some(sext(v, self.target.xlen())),
+            Value::Const(v) => {
+                let msg = format!("value {} does not fit in 32 bits", v);
+                return self.fail(*expr.data(), &msg);
+            },
+            Value::Reloc(_) => None,
+        };
+
+        let fits = |form| match (form, known) {
+            (0, Some(v)) => short && is_signed(v, 6),
+            (1, Some(v)) => is_signed(v, 12),
+            (2, Some(v)) => is_signed(v, 32),
+            (2, None)    => true,
+            (3, _)       => true,
+            _            => false,
+        };
+
+        let v = known.unwrap_or(0);
+
+        match self.out.relax(if rv64 { 4 } else { 3 }, &fits) {
+            0 => {
+                let imm = self.constant_imm(v, I12);
+                self.op("addi", rd, 0, 0, Some(imm), Mode::Auto)
+            },
+            1 => {
+                let imm = self.constant_imm(v, I12);
+                self.op("addi", rd, 0, 0, Some(imm), Mode::Full)
+            },
+            2 => {
+                // Absolute address that the linker will supply, or a value
+                // that may still grow
+                let value = known.map_or(value, Value::Const);
+                let add   = if rv64 && known.is_some() { "addiw" } else { "addi" };
+                let hi    = self.value(expr, value, HI20);
+                let lo    = self.value(expr, value, LO12_I);
+                self.op("lui", rd, 0,  0, Some(hi), Mode::Full)?;
+                self.op(add,   rd, rd, 0, Some(lo), Mode::Full)
+            },
+            _ => {
+                let start = self.offset;
+                self.li_const(rd, v, Mode::Full)?;
+                while self.offset < start + 32 {
+                    let imm = self.constant_imm(0, I12);
+                    self.op("addi", 0, 0, 0, Some(imm), Mode::Full)?;
+                }
+                Some(())
+            },
+        }
+    }
+
+    /// Emits an `auipc` of the high part of the offset to `sym` into `tmp`,
+    /// followed by the instruction `name` that adds the low part to `tmp` and
+    /// writes `rd`.
+    fn far(&mut self, tmp: u32, name: &str, rd: u32, sym: &Expr<Span>) -> Option<()> {
+        let target = self.out.eval(sym)?;
+        let hi     = self.pc_rel(sym, target, 0, HI20);
+        let lo     = self.pc_rel(sym, target, 0, LO12_I);
+
+        self.op("auipc", tmp, 0,   0, Some(hi), Mode::Auto)?;
+        self.op(name,    rd,  tmp, 0, Some(lo), Mode::Auto)
+    }
+
+    /// Emits the instruction with the given mnemonic and operand fields.
+    fn op(
+        &mut self,
+        name: &str,
+        rd:   u32,
+        rs1:  u32,
+        rs2:  u32,
+        imm:  Option<Imm>,
+        mode: Mode,
+    ) -> Option<()> {
+        let entry = self.target.entry(name);
+        self.emit(Insn { entry, rd, rs1, rs2, imm }, mode)
+    }
+
+    // === Emission ===
+
+    /// Emits the given instruction, compressed if the mode and operands
+    /// permit.
+    fn emit(&mut self, insn: Insn, mode: Mode) -> Option<()> {
+        if self.target.isa & EXT_C != 0 && mode != Mode::Full {
+            let half = compress(&insn, self.target.xlen());
+
+            // A field that depends on a symbol can change from pass to pass;
+            // relax from the 16-bit encoding so that layout converges.
+            let half = match insn.imm {
+                Some(ref imm) if mode == Mode::Auto && !is_literal(&imm.expr) => {
+                    match self.out.relax(2, &|form| form == 1 || half.is_some()) {
+                        0 => half,
+                        _ => None,
+                    }
+                },
+                _ => half,
+            };
+
+            if let Some(half) = half {
+                return self.emit_half(half, &insn);
+            }
+            if mode == Mode::Require {
+                if let Some(half) = compress_reloc(&insn, self.target.xlen()) {
+                    return self.emit_half(half, &insn);
+                }
+            }
+        }
+
+        if mode == Mode::Require {
+            return self.fail(self.span, "operands not valid for compressed instruction");
+        }
+
+        let word = insn.word() as u64;
+        let word = match insn.imm {
+            None => word,
+            Some(Imm { ref expr, value: Value::Const(v), kind, .. }) => {
+                match (kind.apply)(v, word) {
+                    Ok(word) => word,
+                    Err(msg) => { self.out.error(*expr.data(), &msg); word },
+                }
+            },
+            Some(Imm { ref expr, value: Value::Reloc(_), kind, .. }) => {
+                self.out.reloc(expr, kind);
+                word
+            },
+        };
+
+        self.out.emit(&(word as u32).to_le_bytes());
+        self.offset += 4;
+        Some(())
+    }
+
+    /// Emits a 16-bit instruction, recording a relocation for its offset
+    /// field if the linker must compute it.
+    fn emit_half(&mut self, half: u16, insn: &Insn) -> Option<()> {
+        if let Some(Imm { ref expr, value: Value::Reloc(_), .. }) = insn.imm {
+            let kind = match insn.entry.name.as_str() {
+                "jal" => C_JUMP,
+                _     => C_BRANCH,
+            };
+            self.out.reloc(expr, kind);
+        }
+
+        self.out.emit(&half.to_le_bytes());
+        self.offset += 2;
+        Some(())
+    }
+
+    // === Operands ===
+
+    fn operand(&mut self, arg: &Arg<Span>) -> Option<Operand> {
+        let expr = match *arg {
+            Arg::Expr(ref e) => e,
+            Arg::Unknown(span) => return self.fail(span, "expected: operand"),
+        };
+
+        let span = *expr.data();
+
+        match *expr {
+            Expr::Ident(_, name) => match reg(self.out.names().get(name)) {
+                Some(n) => Some(Operand::Reg(n, span)),
+                None    => Some(Operand::Imm(expr.clone())),
+            },
+            Expr::Deref(_, ref inner, false) => {
+                self.memory(span, inner)
+            },
+            _ => Some(Operand::Imm(expr.clone())),
+        }
+    }
+
+    /// Parses a memory operand `[rs1]` or `[rs1 + offset]`.
+    fn memory(&mut self, span: Span, inner: &Expr<Span>) -> Option<Operand> {
+        let mut terms = vec![];
+        flatten_sum(inner, false, &mut terms);
+
+        let mut base = None;
+        let mut rest = vec![];
+
+        for (neg, term) in terms {
+            let n = match *term {
+                Expr::Ident(_, name) => reg(self.out.names().get(name)),
+                _                    => None,
+            };
+            match n {
+                Some(n) if !neg && base.is_none() => base = Some(n),
+                Some(_) => return self.fail(*term.data(), "invalid base register"),
+                None    => rest.push((neg, term)),
+            }
+        }
+
+        match base {
+            Some(n) => Some(Operand::Mem(n, sum(&rest), span)),
+            None    => self.fail(span, "expected: base register"),
+        }
+    }
+
+    /// Converts the given optional memory offset to an immediate field.
+    fn offset(&mut self, off: &Option<Expr<Span>>, field: Field) -> Option<Imm> {
+        match *off {
+            Some(ref expr) => self.imm(expr, field),
+            None => {
+                let kind = if field == Field::S { S12 } else { I12 };
+                Some(self.constant_imm(0, kind))
+            },
+        }
+    }
+
+    /// Converts the given expression, which may apply a relocation operator
+    /// such as `%hi`, to an immediate field of the given format.
+    fn imm(&mut self, expr: &Expr<Span>, field: Field) -> Option<Imm> {
+        let span = *expr.data();
+
+        let (op, arg) = match reloc_op(expr) {
+            Some((name, arg)) => (self.out.names().get(name).to_string(), arg),
+            None => {
+                let value = self.out.eval(expr)?;
+                let kind  = match field { Field::I => I12, Field::S => S12, Field::U => U20 };
+                return Some(self.value(expr, value, kind));
+            },
+        };
+
+        let lo = if field == Field::S { LO12_S } else { LO12_I };
+
+        match (&op[..], field) {
+            ("hi", Field::U) => {
+                let value = self.out.eval(arg)?;
+                Some(self.value(arg, value, HI20))
+            },
+            ("lo", Field::I | Field::S) => {
+                let value = self.out.eval(arg)?;
+                Some(self.value(arg, value, lo))
+            },
+            ("pcrel_hi", Field::U) => {
+                let value = self.out.eval(arg)?;
+                Some(self.pc_rel(arg, value, self.offset, HI20))
+            },
+            ("pcrel_lo", Field::I | Field::S) => {
+                let value = self.out.eval(arg)?;
+                Some(self.pc_rel(arg, value, self.offset - 4, lo))
+            },
+            ("hi" | "lo" | "pcrel_hi" | "pcrel_lo", _) => {
+                self.fail(span, &format!("%{} not valid for this operand", op))
+            },
+            _ => self.fail(span, &format!("unknown relocation operator '%{}'", op)),
+        }
+    }
+
+    /// Converts the given target address to the offset field of a branch or
+    /// jump.
+    fn target(&mut self, expr: &Expr<Span>, kind: RelocKind) -> Option<Imm> {
+        let value = self.out.eval(expr)?;
+        Some(self.pc_rel(expr, value, self.offset, kind))
+    }
+
+    /// Returns an immediate field of the given `kind` that holds the given
+    /// value of `expr`.
+    fn value(&self, expr: &Expr<Span>, value: Value, kind: RelocKind) -> Imm {
+        let field = match value {
+            Value::Const(v) if kind.name == HI20.name   => Some(hi20(v)),
+            Value::Const(v) if kind.name == LO12_I.name => Some(sext(v, 12)),
+            Value::Const(v) if kind.name == LO12_S.name => Some(sext(v, 12)),
+            Value::Const(v) if kind.name == U20.name    => Some(sext(v, 20)),
+            Value::Const(v)                             => Some(v),
+            Value::Reloc(_)                             => None,
+        };
+
+        Imm { expr: expr.clone(), value, kind, field }
+    }
+
+    /// Returns an immediate field of the given `kind` that holds the given
+    /// constant.
+    fn constant_imm(&self, value: i64, kind: RelocKind) -> Imm {
+        self.value(&int(self.span, value), Value::Const(value), kind)
+    }
+
+    /// Returns an immediate field of the given `kind` that holds the offset
+    /// from the instruction `offset` bytes into the current statement to the
+    /// given target.
+    fn pc_rel(&mut self, target: &Expr<Span>, value: Value, offset: i64, kind: RelocKind) -> Imm {
+        let value = pc_rel_value(self.out, self.span, value, offset);
+        self.value(&pc_rel_expr(target, offset), value, kind)
+    }
+
+    /// Evaluates the given expression, which must be a constant in the given
+    /// range.
+    fn constant(&mut self, expr: &Expr<Span>, min: i64, max: i64) -> Option<i64> {
+        match self.out.eval(expr)? {
+            Value::Const(v) if (min..=max).contains(&v) => Some(v),
+            Value::Const(v) => self.fail(*expr.data(), &format!(
+                "value {} out of range {}..{}", v, min, max
+            )),
+            Value::Reloc(_) => self.fail(*expr.data(), "expected: constant expression, not relocatable"),
+        }
+    }
+
+    /// Returns the number of the control and status register named or
+    /// numbered by the given expression.
+    fn csr(&mut self, expr: &Expr<Span>) -> Option<u32> {
+        if let Expr::Ident(_, name) = *expr {
+            if let Some(n) = csr(self.out.names().get(name)) {
+                return Some(n);
+            }
+        }
+        self.constant(expr, 0, 0xFFF).map(|n| n as u32)
+    }
+
+    /// Returns the bits of a `fence` operand such as `rw`.
+    fn fence_set(&mut self, op: &Operand) -> Option<u32> {
+        if let Operand::Imm(Expr::Ident(_, name)) = *op {
+            let set = self.out.names().get(name).chars().try_fold(0, |set, c| {
+                match c.to_ascii_lowercase() {
+                    'i' => Some(set | 8),
+                    'o' => Some(set | 4),
+                    'r' => Some(set | 2),
+                    'w' => Some(set | 1),
+                    _   => None,
+                }
+            });
+            if let Some(set) = set {
+                return Some(set);
+            }
+        }
+        self.fail(op.span(), "expected: fence set such as 'rw'")
+    }
+
+    fn fail<T>(&mut self, span: Span, msg: &str) -> Option<T> {
+        self.out.error(span, msg);
+        None
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the 16-bit encoding of the given instruction, if the C extension
+/// has one for its operands.
+fn compress(insn: &Insn, xlen: u32) -> Option<u16> {
+    let (rd, rs1, rs2) = (insn.rd as u16, insn.rs1 as u16, insn.rs2 as u16);
+
+    // Compressed branches and jumps accept an offset that the linker must
+    // compute only when the mnemonic requires them; see `emit_half`.
+    let imm = match insn.imm {
+        Some(Imm { field: Some(v), .. }) => v,
+        Some(_)                          => return None,
+        None                             => 0,
+    };
+
+    let rv64 = xlen == 64;
+    let u    = imm as u16;
+
+    let half = match insn.entry.name.as_str() {
+        "addi" if rd == 0 && rs1 == 0 && imm == 0 => 0x0001,
+        "addi" if rd != 0 && rs1 == rd && imm != 0 && is_signed(imm, 6) => 0x0001 | ci(rd, u),
+        "addi" if rd != 0 && rs1 == 0 && is_signed(imm, 6) => 0x4001 | ci(rd, u),
+        "addi" if rd != 0 && rs1 != 0 && imm == 0 => 0x8002 | rd << 7 | rs1 << 2,
+        "addi" if rd == 2 && rs1 == 2 && imm != 0 && imm % 16 == 0 && is_signed(imm, 10) => {
+            0x6101 | (u >> 9 & 1) << 12 | (u >> 4 & 1) << 6 | (u >> 6 & 1) << 5
+                | (u >> 7 & 3) << 3 | (u >> 5 & 1) << 2
+        },
+        "addi" if rs1 == 2 && imm > 0 && imm < 1024 && imm % 4 == 0 => {
+            (u >> 4 & 3) << 11 | (u >> 6 & 0xF) << 7 | (u >> 2 & 1) << 6 | (u >> 3 & 1) << 5
+                | prime(rd)? << 2
+        },
+        "addiw" if rd != 0 && rs1 == rd && is_signed(imm, 6) => 0x2001 | ci(rd, u),
+        "lui" if rd != 0 && rd != 2 && imm != 0 && is_signed(imm, 6) => 0x6001 | ci(rd, u),
+        "andi" if rs1 == rd && is_signed(imm, 6) => 0x8801 | ci(prime(rd)?, u),
+        "slli" if rd != 0 && rs1 == rd && rs2 != 0 => 0x0002 | ci(rd, rs2),
+        "srli" if rs1 == rd && rs2 != 0 => 0x8001 | ci(prime(rd)?, rs2),
+        "srai" if rs1 == rd && rs2 != 0 => 0x8401 | ci(prime(rd)?, rs2),
+        "add"  if rd != 0 && rs1 == 0  && rs2 != 0 => 0x8002 | rd << 7 | rs2 << 2,
+        "add"  if rd != 0 && rs1 == rd && rs2 != 0 => 0x9002 | rd << 7 | rs2 << 2,
+        "sub"  if rs1 == rd => 0x8C01 | ca(prime(rd)?, 0, prime(rs2)?),
+        "xor"  if rs1 == rd => 0x8C01 | ca(prime(rd)?, 1, prime(rs2)?),
+        "or"   if rs1 == rd => 0x8C01 | ca(prime(rd)?, 2, prime(rs2)?),
+        "and"  if rs1 == rd => 0x8C01 | ca(prime(rd)?, 3, prime(rs2)?),
+        "subw" if rs1 == rd => 0x9C01 | ca(prime(rd)?, 0, prime(rs2)?),
+        "addw" if rs1 == rd => 0x9C01 | ca(prime(rd)?, 1, prime(rs2)?),
+        "lw" if rs1 == 2 && rd != 0 && (0..256).contains(&imm) && imm % 4 == 0 => {
+            0x4002 | (u >> 5 & 1) << 12 | rd << 7 | (u >> 2 & 7) << 4 | (u >> 6 & 3) << 2
+        },
+        "lw" if (0..128).contains(&imm) && imm % 4 == 0 => {
+            0x4000 | cl_w(prime(rs1)?, u, prime(rd)?)
+        },
+        "sw" if rs1 == 2 && (0..256).contains(&imm) && imm % 4 == 0 => {
+            0xC002 | (u >> 2 & 0xF) << 9 | (u >> 6 & 3) << 7 | rs2 << 2
+        },
+        "sw" if (0..128).contains(&imm) && imm % 4 == 0 => {
+            0xC000 | cl_w(prime(rs1)?, u, prime(rs2)?)
+        },
+        "ld" if rs1 == 2 && rd != 0 && (0..512).contains(&imm) && imm % 8 == 0 => {
+            0x6002 | (u >> 5 & 1) << 12 | rd << 7 | (u >> 3 & 3) << 5 | (u >> 6 & 7) << 2
+        },
+        "ld" if (0..256).contains(&imm) && imm % 8 == 0 => {
+            0x6000 | cl_d(prime(rs1)?, u, prime(rd)?)
+        },
+        "sd" if rs1 == 2 && (0..512).contains(&imm) && imm % 8 == 0 => {
+            0xE002 | (u >> 3 & 7) << 10 | (u >> 6 & 7) << 7 | rs2 << 2
+        },
+        "sd" if (0..256).contains(&imm) && imm % 8 == 0 => {
+            0xE000 | cl_d(prime(rs1)?, u, prime(rs2)?)
+        },
+        "jal" if !is_offset(insn, 12) => return None,
+        "jal" if rd == 0 => 0xA001 | cj_field(imm),
+        "jal" if rd == 1 && !rv64 => 0x2001 | cj_field(imm),
+        "jalr" if rs1 != 0 && imm == 0 && rd == 0 => 0x8002 | rs1 << 7,
+        "jalr" if rs1 != 0 && imm == 0 && rd == 1 => 0x9002 | rs1 << 7,
+        "beq" if rs2 == 0 && is_offset(insn, 9) => 0xC001 | prime(rs1)? << 7 | cb_field(imm),
+        "bne" if rs2 == 0 && is_offset(insn, 9) => 0xE001 | prime(rs1)? << 7 | cb_field(imm),
+        "ebreak"    => 0x9002,
+        _           => return None,
+    };
+
+    Some(half)
+}
+
+/// Returns the 16-bit encoding of the given branch or jump, if the C
+/// extension has one for its registers, leaving the offset for the linker.
+fn compress_reloc(insn: &Insn, xlen: u32) -> Option<u16> {
+    let (rd, rs1, rs2) = (insn.rd, insn.rs1 as u16, insn.rs2);
+
+    match insn.entry.name.as_str() {
+        "jal" if rd == 0               => Some(0xA001),
+        "jal" if rd == 1 && xlen == 32 => Some(0x2001),
+        "beq" if rs2 == 0              => Some(0xC001 | prime(rs1)? << 7),
+        "bne" if rs2 == 0              => Some(0xE001 | prime(rs1)? << 7),
+        _                              => None,
+    }
+}
+
+/// Returns whether the offset of the given branch or jump is known and fits
+/// the compressed form with `bits` bits of offset.
+fn is_offset(insn: &Insn, bits: u32) -> bool {
+    match insn.imm {
+        Some(Imm { field: Some(v), .. }) => is_signed(v, bits) && v & 1 == 0,
+        _                                => false,
+    }
+}
+
+/// Returns the register and immediate fields of a CI-format instruction.
+fn ci(rd: u16, imm: u16) -> u16 {
+    (imm >> 5 & 1) << 12 | rd << 7 | (imm & 0x1F) << 2
+}
+
+/// Returns the register and function fields of a CA-format instruction.
+fn ca(rd: u16, funct: u16, rs2: u16) -> u16 {
+    rd << 7 | funct << 5 | rs2 << 2
+}
+
+/// Returns the fields of a CL- or CS-format word access.
+fn cl_w(rs1: u16, imm: u16, r: u16) -> u16 {
+    (imm >> 3 & 7) << 10 | rs1 << 7 | (imm >> 2 & 1) << 6 | (imm >> 6 & 1) << 5 | r << 2
+}
+
+/// Returns the fields of a CL- or CS-format doubleword access.
+fn cl_d(rs1: u16, imm: u16, r: u16) -> u16 {
+    (imm >> 3 & 7) << 10 | rs1 << 7 | (imm >> 6 & 3) << 5 | r << 2
+}
+
+/// Returns the 3-bit field for a register that compressed instructions can
+/// address in such a field (`x8`-`x15`).
+fn prime(reg: u16) -> Option<u16> {
+    match reg {
+        8..=15 => Some(reg - 8),
+        _      => None,
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the number of the integer register with the given name, if any.
+pub(super) fn reg(name: &str) -> Option<u32> {
+    const ABI: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+        "s0",   "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+        "a6",   "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+        "s8",   "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+    ];
+
+    let name = name.to_ascii_lowercase();
+
+    if let Some(digits) = name.strip_prefix('x') {
+        match digits.parse::<u32>() {
+            Ok(n @ 0..=31) if n.to_string() == digits => return Some(n),
+            _                                         => (),
+        }
+    }
+
+    match &name[..] {
+        "fp" => Some(8),
+        name => ABI.iter().position(|&r| r == name).map(|n| n as u32),
+    }
+}
+
+/// Returns the number of the control and status register with the given
+/// name, if any.
+fn csr(name: &str) -> Option<u32> {
+    let n = match &name.to_ascii_lowercase()[..] {
+        "sstatus"   => 0x100,
+        "sie"       => 0x104,
+        "stvec"     => 0x105,
+        "sscratch"  => 0x140,
+        "sepc"      => 0x141,
+        "scause"    => 0x142,
+        "stval"     => 0x143,
+        "sip"       => 0x144,
+        "satp"      => 0x180,
+        "mstatus"   => 0x300,
+        "misa"      => 0x301,
+        "medeleg"   => 0x302,
+        "mideleg"   => 0x303,
+        "mie"       => 0x304,
+        "mtvec"     => 0x305,
+        "mscratch"  => 0x340,
+        "mepc"      => 0x341,
+        "mcause"    => 0x342,
+        "mtval"     => 0x343,
+        "mip"       => 0x344,
+        "cycle"     => 0xC00,
+        "time"      => 0xC01,
+        "instret"   => 0xC02,
+        "mvendorid" => 0xF11,
+        "marchid"   => 0xF12,
+        "mimpid"    => 0xF13,
+        "mhartid"   => 0xF14,
+        _           => return None,
+    };
+    Some(n)
+}
+
+/// If the given expression applies a relocation operator such as `%hi(x)`,
+/// returns the name of the operator and its argument.
+fn reloc_op(expr: &Expr<Span>) -> Option<(Name, &Expr<Span>)> {
+    match *expr {
+        Expr::Unary(_, UnOp::UnsignedH, ref inner) => match **inner {
+            Expr::Call(_, ref func, ref args) => match (&**func, &args[..]) {
+                (&Expr::Ident(_, name), [arg]) => Some((name, arg)),
+                _                              => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns whether the given expression consists of literals alone, so that
+/// its value is the same in every pass.
+fn is_literal(expr: &Expr<Span>) -> bool {
+    match *expr {
+        Expr::Int   (..) | Expr::Float(..) | Expr::Str(..) | Expr::Char(..) => true,
+        Expr::Unary (_, _, ref e)          => is_literal(e),
+        Expr::Binary(_, _, ref l, ref r)   => is_literal(l) && is_literal(r),
+        _                                  => false,
+    }
+}
+
+/// Returns an expression for the given integer.
+fn int(span: Span, value: i64) -> Expr<Span> {
+    match value {
+        0.. => Expr::Int(span, value as u64),
+        _   => Expr::Unary(span, UnOp::Neg, Box::new(Expr::Int(span, value.unsigned_abs()))),
+    }
+}
@@ -0,0 +1,368 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RISC-V.
+//!
+//! Instructions use the standard mnemonics.  Registers may be written by
+//! number (`x0`-`x31`) or by ABI name (`zero`, `ra`, `sp`, `a0`, and so on).
+//! Memory operands use ras syntax:
+//!
+//! | Operand                       | Standard            | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | base and offset               | `8(sp)`             | `[sp + 8]`
+//! | base                          | `(a0)`              | `[a0]`
+//! | high 20 bits of an address    | `%hi(sym)`          | `%hi(sym)`
+//! | low 12 bits of an address     | `%lo(sym)`          | `[a0 + %lo(sym)]`
+//!
+//! In a branch or jump, the operand is the target address, not the offset.
+//! The relocation operator `%pcrel_hi(sym)` yields the high part of the
+//! offset from the current instruction to `sym`; `%pcrel_lo(sym)` yields the
+//! low part of the offset from the instruction immediately before, which must
+//! be the paired `auipc`.
+//!
+//! Targets with the C extension compress an instruction to its 16-bit form
+//! whenever the operands permit.  A `c.` mnemonic requires the compressed
+//! form.
+//!
+//! Every RISC-V target can simulate the code that it assembles.  Decoding
+//! derives from the declarative [`Spec`](super::spec::Spec) in the `spec`
+//! module; the encoder is hand-written.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{fits, Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+use crate::sim::Cpu;
+
+use super::spec::Table;
+use super::{Decoded, Emitter, Target};
+
+mod encode;
+mod sim;
+mod spec;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: 32-bit base integer instructions.
+pub const RV32: u32 = 1 << 0;
+
+/// Instruction set feature: 64-bit base integer instructions.
+pub const RV64: u32 = 1 << 1;
+
+/// Instruction set feature: M extension, integer multiply and divide.
+pub const EXT_M: u32 = 1 << 2;
+
+/// Instruction set feature: A extension, atomic memory operations.
+pub const EXT_A: u32 = 1 << 3;
+
+/// Instruction set feature: C extension, compressed instructions.
+pub const EXT_C: u32 = 1 << 4;
+
+/// Member of the RISC-V family.
+#[derive(Debug)]
+pub struct RiscV {
+    name:  &'static str,
+    isa:   u32,
+    table: Vec<Entry>,
+    index: HashMap<Name, usize>,
+    bases: HashMap<String, usize>,
+    spec:  Rc<Table>,
+}
+
+impl RiscV {
+    /// Creates a new [`RiscV`] target with the given `name` and instruction
+    /// set features `isa`, interning its mnemonics in `names`.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        let table = table::build()
+            .into_iter()
+            .filter(|e| e.xlen & isa != 0 && e.ext & isa == e.ext)
+            .collect::<Vec<_>>();
+
+        let mut index = HashMap::new();
+        let mut bases = HashMap::new();
+
+        for (i, entry) in table.iter().enumerate() {
+            index.insert(names.add(&entry.name),                i);
+            index.insert(names.add(&entry.name.to_uppercase()), i);
+            bases.insert(entry.name.clone(), i);
+        }
+
+        let spec = Rc::new(Table::new(&spec::SPEC, names));
+
+        Self { name, isa, table, index, bases, spec }
+    }
+
+    /// Returns the entry for the instruction with the given mnemonic, which
+    /// the target must have.
+    fn entry(&self, name: &str) -> &Entry {
+        &self.table[self.bases[name]]
+    }
+
+    /// Returns the width in bits of the integer registers.
+    fn xlen(&self) -> u32 {
+        if self.isa & RV64 != 0 { 64 } else { 32 }
+    }
+}
+
+impl Target for RiscV {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        if self.isa & EXT_C != 0 { 2 } else { 4 }
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[
+            I12, S12, LO12_I, LO12_S, U20, HI20, BRANCH, JUMP, C_BRANCH, C_JUMP,
+            RelocKind::INT32, RelocKind::INT64,
+        ]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        encode::reg(name)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        encode::encode(self, &self.table[insn], stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        self.spec.decode(self.isa, bytes, addr, label)
+    }
+
+    fn simulator(&self) -> Option<Box<dyn Cpu>> {
+        Some(Box::new(sim::Hart::new(self)))
+    }
+}
+
+/// Creates an RV32I target.
+pub fn new_rv32i(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv32i", RV32))
+}
+
+/// Creates an RV32IM target.
+pub fn new_rv32im(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv32im", RV32 | EXT_M))
+}
+
+/// Creates an RV32IMAC target.
+pub fn new_rv32imac(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv32imac", RV32 | EXT_M | EXT_A | EXT_C))
+}
+
+/// Creates an RV64I target.
+pub fn new_rv64i(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv64i", RV64))
+}
+
+/// Creates an RV64IM target.
+pub fn new_rv64im(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv64im", RV64 | EXT_M))
+}
+
+/// Creates an RV64IMAC target.
+pub fn new_rv64imac(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(RiscV::new(names, "rv64imac", RV64 | EXT_M | EXT_A | EXT_C))
+}
+
+// ----------------------------------------------------------------------------
+
+/// 12-bit signed immediate of an I-type instruction.
+pub const I12: RelocKind = RelocKind { name: "riscv-i12", size: 4, apply: apply_i12 };
+
+/// 12-bit signed immediate of an S-type instruction.
+pub const S12: RelocKind = RelocKind { name: "riscv-s12", size: 4, apply: apply_s12 };
+
+/// Low 12 bits of a value, in the immediate of an I-type instruction.
+pub const LO12_I: RelocKind = RelocKind { name: "riscv-lo12-i", size: 4, apply: apply_lo12_i };
+
+/// Low 12 bits of a value, in the immediate of an S-type instruction.
+pub const LO12_S: RelocKind = RelocKind { name: "riscv-lo12-s", size: 4, apply: apply_lo12_s };
+
+/// 20-bit immediate of a U-type instruction.
+pub const U20: RelocKind = RelocKind { name: "riscv-u20", size: 4, apply: apply_u20 };
+
+/// High 20 bits of a value, rounded to complement the sign-extended low 12
+/// bits, in the immediate of a U-type instruction.
+pub const HI20: RelocKind = RelocKind { name: "riscv-hi20", size: 4, apply: apply_hi20 };
+
+/// 13-bit offset of a conditional branch.
+pub const BRANCH: RelocKind = RelocKind { name: "riscv-branch", size: 4, apply: apply_branch };
+
+/// 21-bit offset of a `jal` instruction.
+pub const JUMP: RelocKind = RelocKind { name: "riscv-jump", size: 4, apply: apply_jump };
+
+/// 9-bit offset of a compressed conditional branch.
+pub const C_BRANCH: RelocKind = RelocKind { name: "riscv-c-branch", size: 2, apply: apply_c_branch };
+
+/// 12-bit offset of a compressed jump.
+pub const C_JUMP: RelocKind = RelocKind { name: "riscv-c-jump", size: 2, apply: apply_c_jump };
+
+fn apply_i12(value: i64, field: u64) -> Result<u64, String> {
+    match is_signed(value, 12) {
+        true  => apply_lo12_i(value, field),
+        false => Err(format!("value {} out of range for 12-bit signed immediate", value)),
+    }
+}
+
+fn apply_s12(value: i64, field: u64) -> Result<u64, String> {
+    match is_signed(value, 12) {
+        true  => apply_lo12_s(value, field),
+        false => Err(format!("value {} out of range for 12-bit signed immediate", value)),
+    }
+}
+
+fn apply_lo12_i(value: i64, field: u64) -> Result<u64, String> {
+    Ok(field & 0x000F_FFFF | i_field(value) as u64)
+}
+
+fn apply_lo12_s(value: i64, field: u64) -> Result<u64, String> {
+    Ok(field & 0x01FF_F07F | s_field(value) as u64)
+}
+
+fn apply_u20(value: i64, field: u64) -> Result<u64, String> {
+    match fits(value, 20) {
+        true  => Ok(field & 0xFFF | u_field(value) as u64),
+        false => Err(format!("value {} does not fit in 20 bits", value)),
+    }
+}
+
+fn apply_hi20(value: i64, field: u64) -> Result<u64, String> {
+    match fits(value, 32) {
+        true  => Ok(field & 0xFFF | u_field(hi20(value)) as u64),
+        false => Err(format!("value {} does not fit in 32 bits", value)),
+    }
+}
+
+fn apply_branch(value: i64, field: u64) -> Result<u64, String> {
+    check_offset(value, 13)?;
+    Ok(field & 0x01FF_F07F | b_field(value) as u64)
+}
+
+fn apply_jump(value: i64, field: u64) -> Result<u64, String> {
+    check_offset(value, 21)?;
+    Ok(field & 0xFFF | j_field(value) as u64)
+}
+
+fn apply_c_branch(value: i64, field: u64) -> Result<u64, String> {
+    check_offset(value, 9)?;
+    Ok(field & 0xE383 | cb_field(value) as u64)
+}
+
+fn apply_c_jump(value: i64, field: u64) -> Result<u64, String> {
+    check_offset(value, 12)?;
+    Ok(field & 0xE003 | cj_field(value) as u64)
+}
+
+/// Reports an error unless `value` is an even offset representable in `bits`
+/// bits as a signed integer.
+fn check_offset(value: i64, bits: u32) -> Result<(), String> {
+    if !is_signed(value, bits) {
+        return Err(format!("branch offset {} out of range", value));
+    }
+    if value & 1 != 0 {
+        return Err(format!("branch offset {} is odd", value));
+    }
+    Ok(())
+}
+
+/// Returns whether `value` is representable in `bits` bits as a signed
+/// integer.
+pub fn is_signed(value: i64, bits: u32) -> bool {
+    let min = -1i64 << (bits - 1);
+    (min..=!min).contains(&value)
+}
+
+/// Returns `value` with the low `bits` bits sign-extended.
+pub fn sext(value: i64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    value << shift >> shift
+}
+
+/// Returns the value of the high 20 bits of `value`, rounded so that adding
+/// the sign-extended low 12 bits yields `value`.
+pub fn hi20(value: i64) -> i64 {
+    sext(value.wrapping_add(0x800) >> 12, 20)
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the immediate field of an I-type instruction.
+pub fn i_field(imm: i64) -> u32 {
+    (imm as u32 & 0xFFF) << 20
+}
+
+/// Returns the immediate fields of an S-type instruction.
+pub fn s_field(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 5 & 0x7F) << 25 | (imm & 0x1F) << 7
+}
+
+/// Returns the immediate fields of a B-type instruction.
+pub fn b_field(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 12 & 1) << 31 | (imm >> 5 & 0x3F) << 25 | (imm >> 1 & 0xF) << 8 | (imm >> 11 & 1) << 7
+}
+
+/// Returns the immediate field of a U-type instruction.
+pub fn u_field(imm: i64) -> u32 {
+    (imm as u32 & 0xF_FFFF) << 12
+}
+
+/// Returns the immediate fields of a J-type instruction.
+pub fn j_field(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 20 & 1) << 31 | (imm >> 1 & 0x3FF) << 21 | (imm >> 11 & 1) << 20 | (imm >> 12 & 0xFF) << 12
+}
+
+/// Returns the offset fields of a CB-format compressed branch.
+pub fn cb_field(imm: i64) -> u16 {
+    let imm = imm as u16;
+    (imm >> 8 & 1) << 12 | (imm >> 3 & 3) << 10 | (imm >> 6 & 3) << 5 | (imm >> 1 & 3) << 3
+        | (imm >> 5 & 1) << 2
+}
+
+/// Returns the offset fields of a CJ-format compressed jump.
+pub fn cj_field(imm: i64) -> u16 {
+    let imm = imm as u16;
+    (imm >> 11 & 1) << 12 | (imm >> 4 & 1) << 11 | (imm >> 8 & 3) << 9 | (imm >> 10 & 1) << 8
+        | (imm >> 6 & 1) << 7 | (imm >> 7 & 1) << 6 | (imm >> 1 & 7) << 3 | (imm >> 5 & 1) << 2
+}
@@ -0,0 +1,658 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction-level simulation.
+//!
+//! The simulator implements the base integer instructions and the M, A, and
+//! C extensions that the target has.  It has no control and status
+//! registers, privilege modes, or floating point.  `ecall` and `ebreak`
+//! stop execution, and misaligned loads and stores succeed.
+//!
+//! Instructions decode through the same [`Table`] as the disassembler.  A
+//! compressed instruction executes as the instruction that it stands for.
+
+use std::rc::Rc;
+
+use crate::sim::{Cpu, Fault, Memory, Stop};
+use crate::target::spec::{Fields, Table};
+
+use super::encode::reg;
+use super::table::{Form, Short};
+use super::{RiscV, EXT_C, RV64};
+
+// ----------------------------------------------------------------------------
+
+/// Simulated RISC-V hart.
+#[derive(Clone, Debug)]
+pub struct Hart {
+    isa:   u32,
+    spec:  Rc<Table>,
+    insns: Rc<[Insn]>,
+    x:     [u64; 32],
+    pc:    u64,
+    lock:  Option<u64>,
+}
+
+/// Operation and operands of an instruction form.
+#[derive(Clone, Debug)]
+struct Insn {
+    /// Operation.
+    op: Op,
+
+    /// Operand that each decoded field value supplies.
+    roles: Vec<Role>,
+
+    /// Operands that the form implies.
+    args: Args,
+
+    /// Operands that must not be zero, as the encoding with zero is
+    /// reserved.
+    nonzero: &'static [Role],
+}
+
+/// Operands of an instruction.
+#[derive(Clone, Copy, Default, Debug)]
+struct Args {
+    rd:  u32,
+    rs1: u32,
+    rs2: u32,
+    imm: i64,
+}
+
+/// Operand that a field value supplies.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Role {
+    /// Destination register.
+    Rd,
+
+    /// First source register.
+    Rs1,
+
+    /// Second source register.
+    Rs2,
+
+    /// Both destination and first source register.
+    Both,
+
+    /// Immediate, offset, or target address.
+    Imm,
+}
+
+/// Operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Op {
+    Lui,
+    Auipc,
+    Jal,
+    Jalr,
+
+    /// Branch if the comparison holds.
+    Branch(Cmp),
+
+    /// Load of the given size, sign-extended if the flag is true.
+    Load(usize, bool),
+
+    /// Store of the given size.
+    Store(usize),
+
+    /// Operation on full registers, with an immediate if the flag is true.
+    Alu(Alu, bool),
+
+    /// Operation on 32-bit words, with an immediate if the flag is true.
+    AluW(Alu, bool),
+
+    /// M extension operation on full registers, by its `funct3` field.
+    MulDiv(u32),
+
+    /// M extension operation on 32-bit words, by its `funct3` field.
+    MulDivW(u32),
+
+    /// Load-reserved of the given size.
+    Lr(usize),
+
+    /// Store-conditional of the given size.
+    Sc(usize),
+
+    /// Atomic memory operation of the given size.
+    Amo(Amo, usize),
+
+    Fence,
+    Ecall,
+    Ebreak,
+
+    /// Instruction that the simulator does not implement.
+    Illegal,
+}
+
+/// Branch comparisons.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Cmp { Eq, Ne, Lt, Ge, Ltu, Geu }
+
+/// Arithmetic and logic operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Alu { Add, Sub, Sll, Slt, Sltu, Xor, Srl, Sra, Or, And }
+
+/// Atomic memory operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Amo { Swap, Add, Xor, And, Or, Min, Max, Minu, Maxu }
+
+/// M extension mnemonics, in order of their `funct3` fields.
+const MULDIV: [&str; 8] = ["mul", "mulh", "mulhsu", "mulhu", "div", "divu", "rem", "remu"];
+
+impl Hart {
+    /// Creates a new [`Hart`] for the given target, with all registers
+    /// zero.
+    pub fn new(target: &RiscV) -> Self {
+        let spec  = Rc::clone(&target.spec);
+        let insns = (0..spec.form_count()).map(|i| Insn::new(target, i)).collect();
+
+        Self { isa: target.isa, spec, insns, x: [0; 32], pc: 0, lock: None }
+    }
+
+    fn rv64(&self) -> bool {
+        self.isa & RV64 != 0
+    }
+
+    fn has(&self, ext: u32) -> bool {
+        self.isa & ext != 0
+    }
+
+    /// Returns the given value truncated to the register width and
+    /// sign-extended to 64 bits, which is how registers hold values.
+    fn norm(&self, value: u64) -> u64 {
+        match self.rv64() {
+            true  => value,
+            false => sext32(value),
+        }
+    }
+
+    /// Returns the given address truncated to the register width.
+    fn addr(&self, value: u64) -> u64 {
+        match self.rv64() {
+            true  => value,
+            false => value & 0xFFFF_FFFF,
+        }
+    }
+
+    fn get(&self, r: u32) -> u64 {
+        self.x[r as usize]
+    }
+
+    fn set(&mut self, r: u32, value: u64) {
+        if r != 0 {
+            self.x[r as usize] = self.norm(value);
+        }
+    }
+
+    /// Executes the decoded instruction `fields`, whose bits are `raw`.
+    fn exec(&mut self, fields: &Fields, raw: u64, mem: &mut Memory) -> Result<(), Stop> {
+        let illegal = || Stop::Fault(Fault::Illegal(raw));
+
+        let insns    = Rc::clone(&self.insns);
+        let insn     = &insns[fields.form];
+        let mut args = insn.args;
+
+        for (&role, &value) in insn.roles.iter().zip(&fields.values) {
+            if value == 0 && insn.nonzero.contains(&role) {
+                return Err(illegal());
+            }
+            match role {
+                Role::Rd   => args.rd  = value as u32,
+                Role::Rs1  => args.rs1 = value as u32,
+                Role::Rs2  => args.rs2 = value as u32,
+                Role::Both => { args.rd = value as u32; args.rs1 = value as u32 },
+                Role::Imm  => args.imm = value,
+            }
+        }
+
+        let Args { rd, rs1, rs2, imm } = args;
+
+        let a = self.get(rs1);
+        let b = self.get(rs2);
+
+        let next = self.pc.wrapping_add(fields.size as u64);
+        let mut pc = next;
+
+        match insn.op {
+            Op::Lui   => self.set(rd, sext32((imm as u64) << 12)),
+            Op::Auipc => self.set(rd, self.pc.wrapping_add(sext32((imm as u64) << 12))),
+
+            Op::Jal => {
+                pc = imm as u64;
+                self.set(rd, next);
+            },
+
+            Op::Jalr => {
+                pc = a.wrapping_add(imm as u64) & !1;
+                self.set(rd, next);
+            },
+
+            Op::Branch(cmp) => {
+                let taken = match cmp {
+                    Cmp::Eq  => a == b,
+                    Cmp::Ne  => a != b,
+                    Cmp::Lt  => (a as i64) <  (b as i64),
+                    Cmp::Ge  => (a as i64) >= (b as i64),
+                    Cmp::Ltu => a <  b,
+                    Cmp::Geu => a >= b,
+                };
+                if taken {
+                    pc = imm as u64;
+                }
+            },
+
+            Op::Load(size, signed) => {
+                let addr  = self.addr(a.wrapping_add(imm as u64));
+                let value = mem.read(addr, size).map_err(Stop::Fault)?;
+                let value = match (signed, size) {
+                    (true, 1) => value as i8  as u64,
+                    (true, 2) => value as i16 as u64,
+                    (true, 4) => sext32(value),
+                    _         => value,
+                };
+                self.set(rd, value);
+            },
+
+            Op::Store(size) => {
+                let addr = self.addr(a.wrapping_add(imm as u64));
+                mem.write(addr, size, b).map_err(Stop::Fault)?;
+            },
+
+            Op::Alu(op, is_imm) => {
+                let b = match is_imm {
+                    true  => imm as u64,
+                    false => b,
+                };
+                let shamt = b as u32 & self.mask();
+                let value = match op {
+                    Alu::Add  => a.wrapping_add(b),
+                    Alu::Sub  => a.wrapping_sub(b),
+                    Alu::Slt  => ((a as i64) < (b as i64)) as u64,
+                    Alu::Sltu => (a < b) as u64,
+                    Alu::Xor  => a ^ b,
+                    Alu::Or   => a | b,
+                    Alu::And  => a & b,
+                    Alu::Sll  => self.shift(0, a, shamt),
+                    Alu::Srl  => self.shift(1, a, shamt),
+                    Alu::Sra  => self.shift(2, a, shamt),
+                };
+                self.set(rd, value);
+            },
+
+            Op::AluW(op, is_imm) => {
+                let b = match is_imm {
+                    true  => imm as u32,
+                    false => b as u32,
+                };
+                let (a, shamt) = (a as u32, b & 31);
+                let value = match op {
+                    Alu::Add => a.wrapping_add(b),
+                    Alu::Sub => a.wrapping_sub(b),
+                    Alu::Sll => a << shamt,
+                    Alu::Srl => a >> shamt,
+                    Alu::Sra => ((a as i32) >> shamt) as u32,
+                    _        => return Err(illegal()),
+                };
+                self.set(rd, sext32(value as u64));
+            },
+
+            Op::MulDiv(f3)  => self.set(rd, self.muldiv(f3, a, b)),
+            Op::MulDivW(f3) => self.set(rd, sext32(muldiv32(f3, a as u32, b as u32) as u64)),
+
+            Op::Lr(size) => {
+                let addr = self.addr(a);
+                self.lock = Some(addr);
+                self.set(rd, load(mem, addr, size)?);
+            },
+
+            Op::Sc(size) => {
+                let addr  = self.addr(a);
+                let value = match self.lock.take() == Some(addr) {
+                    true  => { mem.write(addr, size, b).map_err(Stop::Fault)?; 0 },
+                    false => 1,
+                };
+                self.set(rd, value);
+            },
+
+            Op::Amo(op, size) => {
+                let addr = self.addr(a);
+                let old  = load(mem, addr, size)?;
+                let (x, y) = match size {
+                    4 => (sext32(old), sext32(b)),
+                    _ => (old, b),
+                };
+                let new = match op {
+                    Amo::Add  => x.wrapping_add(y),
+                    Amo::Swap => y,
+                    Amo::Xor  => x ^ y,
+                    Amo::Or   => x | y,
+                    Amo::And  => x & y,
+                    Amo::Min  => (x as i64).min(y as i64) as u64,
+                    Amo::Max  => (x as i64).max(y as i64) as u64,
+                    Amo::Minu => cmp_unsigned(size, x, y, false),
+                    Amo::Maxu => cmp_unsigned(size, x, y, true),
+                };
+                mem.write(addr, size, new).map_err(Stop::Fault)?;
+                self.set(rd, old);
+            },
+
+            Op::Fence   => (),
+            Op::Ecall   => { self.pc = next; return Err(Stop::Call)  },
+            Op::Ebreak  => { self.pc = next; return Err(Stop::Break) },
+            Op::Illegal => return Err(illegal()),
+        }
+
+        if pc & self.align() != 0 {
+            return Err(Stop::Fault(Fault::Misaligned(pc)));
+        }
+
+        self.pc = self.addr(pc);
+        Ok(())
+    }
+
+    /// Returns the mask of valid shift amounts.
+    fn mask(&self) -> u32 {
+        if self.rv64() { 63 } else { 31 }
+    }
+
+    /// Returns the mask of the address bits that must be zero in an
+    /// instruction address.
+    fn align(&self) -> u64 {
+        if self.has(EXT_C) { 1 } else { 3 }
+    }
+
+    /// Shifts `a` left (0), right logically (1), or right arithmetically
+    /// (2) by `shamt` bits, which must be below the register width.
+    fn shift(&self, kind: u32, a: u64, shamt: u32) -> u64 {
+        match (self.rv64(), kind) {
+            (_,     0) => a << shamt,
+            (true,  1) => a >> shamt,
+            (true,  _) => ((a as i64) >> shamt) as u64,
+            (false, 1) => ((a as u32) >> shamt) as u64,
+            (false, _) => ((a as i32) >> shamt) as u64,
+        }
+    }
+
+    /// Executes an M extension operation on full registers.
+    fn muldiv(&self, f3: u32, a: u64, b: u64) -> u64 {
+        if !self.rv64() {
+            return muldiv32(f3, a as u32, b as u32) as u64;
+        }
+
+        let (sa, sb) = (a as i64, b as i64);
+        match f3 {
+            0 => a.wrapping_mul(b),
+            1 => ((sa as i128 * sb as i128) >> 64) as u64,
+            2 => ((sa as i128 * b as i128) >> 64) as u64,
+            3 => ((a as u128 * b as u128) >> 64) as u64,
+            4 if b == 0 => u64::MAX,
+            4 => sa.wrapping_div(sb) as u64,
+            5 => a.checked_div(b).unwrap_or(u64::MAX),
+            6 if b == 0 => a,
+            6 => sa.wrapping_rem(sb) as u64,
+            _ => a.checked_rem(b).unwrap_or(a),
+        }
+    }
+}
+
+impl Insn {
+    /// Returns the operation and operands of the form with the given index
+    /// in the decoding table of `target`.
+    fn new(target: &RiscV, form: usize) -> Self {
+        let spec = &target.spec;
+        let name = spec.mnemonic(form);
+
+        // A compressed instruction stands for its base instruction
+        let (base, short) = match target.bases.get(name).map(|&i| target.table[i].form) {
+            Some(Form::C(short, base)) => (base, short),
+            _                          => (name, Short::Same),
+        };
+
+        let mut roles = spec.fields(form)
+            .into_iter()
+            .map(|field| match field {
+                "rd"  | "rdp"  => Role::Rd,
+                "rs1" | "rs1p" => Role::Rs1,
+                "rs2" | "rs2p" => Role::Rs2,
+                _              => Role::Imm,
+            })
+            .collect::<Vec<_>>();
+
+        let mut args = Args::default();
+
+        if spec.words(form).contains(&"sp") {
+            args.rs1 = 2;
+        }
+
+        match short {
+            Short::Rd2 if !roles.iter().any(|&r| r == Role::Rd || r == Role::Rs1) => {
+                args.rd = args.rs1;
+            },
+            Short::Rd2 => {
+                for role in &mut roles {
+                    if *role == Role::Rd || *role == Role::Rs1 {
+                        *role = Role::Both;
+                    }
+                }
+            },
+            Short::Jump(link) | Short::JumpReg(link) => args.rd = link,
+            Short::Same | Short::Zero | Short::BranchZ => (),
+        }
+
+        let nonzero: &[Role] = match name {
+            "c.lui"                           => &[Role::Rd, Role::Imm],
+            "c.addi16sp"                      => &[Role::Imm],
+            "c.lwsp" | "c.ldsp" | "c.addiw"   => &[Role::Rd],
+            "c.jr"                            => &[Role::Rs1],
+            _                                 => &[],
+        };
+
+        Self { op: op(base), roles, args, nonzero }
+    }
+}
+
+impl Cpu for Hart {
+    fn width(&self) -> usize {
+        match self.rv64() {
+            true  => 8,
+            false => 4,
+        }
+    }
+
+    fn pc(&self) -> u64 {
+        self.pc
+    }
+
+    fn set_pc(&mut self, pc: u64) {
+        self.pc = self.addr(pc);
+    }
+
+    fn register(&self, name: &str) -> Option<u64> {
+        let value = match name.eq_ignore_ascii_case("pc") {
+            true  => self.pc,
+            false => self.x[reg(name)? as usize],
+        };
+        Some(self.addr(value))
+    }
+
+    fn set_register(&mut self, name: &str, value: u64) -> bool {
+        if name.eq_ignore_ascii_case("pc") {
+            self.set_pc(value);
+            return true;
+        }
+        match reg(name) {
+            Some(r) => { self.set(r, value); true },
+            None    => false,
+        }
+    }
+
+    fn step(&mut self, mem: &mut Memory) -> Result<(), Stop> {
+        if self.pc & self.align() != 0 {
+            return Err(Stop::Fault(Fault::Misaligned(self.pc)));
+        }
+
+        // The low bits of the first half tell the size of the instruction
+        let low = mem.read(self.pc, 2).map_err(Stop::Fault)?;
+        let (raw, size) = match low & 3 {
+            3 => (low | mem.read(self.pc.wrapping_add(2), 2).map_err(Stop::Fault)? << 16, 4),
+            _ => (low, 2),
+        };
+
+        let bytes = raw.to_le_bytes();
+        match self.spec.decode_fields(self.isa, &bytes[..size], self.pc) {
+            Some(fields) => self.exec(&fields, raw, mem),
+            None         => Err(Stop::Fault(Fault::Illegal(raw))),
+        }
+    }
+}
+
+/// Returns the operation of the instruction with the given mnemonic.
+fn op(name: &str) -> Op {
+    match name {
+        "lui"              => Op::Lui,
+        "auipc"            => Op::Auipc,
+        "jal"              => Op::Jal,
+        "jalr"             => Op::Jalr,
+        "beq"              => Op::Branch(Cmp::Eq),
+        "bne"              => Op::Branch(Cmp::Ne),
+        "blt"              => Op::Branch(Cmp::Lt),
+        "bge"              => Op::Branch(Cmp::Ge),
+        "bltu"             => Op::Branch(Cmp::Ltu),
+        "bgeu"             => Op::Branch(Cmp::Geu),
+        "lb"               => Op::Load(1, true),
+        "lh"               => Op::Load(2, true),
+        "lw"               => Op::Load(4, true),
+        "ld"               => Op::Load(8, true),
+        "lbu"              => Op::Load(1, false),
+        "lhu"              => Op::Load(2, false),
+        "lwu"              => Op::Load(4, false),
+        "sb"               => Op::Store(1),
+        "sh"               => Op::Store(2),
+        "sw"               => Op::Store(4),
+        "sd"               => Op::Store(8),
+        "addi" | "nop"     => Op::Alu(Alu::Add,  true),
+        "slti"             => Op::Alu(Alu::Slt,  true),
+        "sltiu"            => Op::Alu(Alu::Sltu, true),
+        "xori"             => Op::Alu(Alu::Xor,  true),
+        "ori"              => Op::Alu(Alu::Or,   true),
+        "andi"             => Op::Alu(Alu::And,  true),
+        "slli"             => Op::Alu(Alu::Sll,  true),
+        "srli"             => Op::Alu(Alu::Srl,  true),
+        "srai"             => Op::Alu(Alu::Sra,  true),
+        "add"              => Op::Alu(Alu::Add,  false),
+        "sub"              => Op::Alu(Alu::Sub,  false),
+        "sll"              => Op::Alu(Alu::Sll,  false),
+        "slt"              => Op::Alu(Alu::Slt,  false),
+        "sltu"             => Op::Alu(Alu::Sltu, false),
+        "xor"              => Op::Alu(Alu::Xor,  false),
+        "srl"              => Op::Alu(Alu::Srl,  false),
+        "sra"              => Op::Alu(Alu::Sra,  false),
+        "or"               => Op::Alu(Alu::Or,   false),
+        "and"              => Op::Alu(Alu::And,  false),
+        "addiw"            => Op::AluW(Alu::Add, true),
+        "slliw"            => Op::AluW(Alu::Sll, true),
+        "srliw"            => Op::AluW(Alu::Srl, true),
+        "sraiw"            => Op::AluW(Alu::Sra, true),
+        "addw"             => Op::AluW(Alu::Add, false),
+        "subw"             => Op::AluW(Alu::Sub, false),
+        "sllw"             => Op::AluW(Alu::Sll, false),
+        "srlw"             => Op::AluW(Alu::Srl, false),
+        "sraw"             => Op::AluW(Alu::Sra, false),
+        "fence" | "fence.i" => Op::Fence,
+        "ecall"            => Op::Ecall,
+        "ebreak"           => Op::Ebreak,
+        _                  => op_ext(name),
+    }
+}
+
+/// Returns the operation of the M or A extension instruction with the given
+/// mnemonic.
+fn op_ext(name: &str) -> Op {
+    let f3 = |name: &str| MULDIV.iter().position(|&m| m == name).map(|i| i as u32);
+
+    if let Some(f3) = f3(name) {
+        return Op::MulDiv(f3);
+    }
+    if let Some(f3) = name.strip_suffix('w').and_then(f3) {
+        return Op::MulDivW(f3);
+    }
+
+    // Atomics: base, width, and ordering, as in `amoadd.w.aq`
+    let mut parts = name.split('.');
+    let (base, size) = match (parts.next(), parts.next()) {
+        (Some(base), Some("w")) => (base, 4),
+        (Some(base), Some("d")) => (base, 8),
+        _                       => return Op::Illegal,
+    };
+
+    match base {
+        "lr"      => Op::Lr(size),
+        "sc"      => Op::Sc(size),
+        "amoswap" => Op::Amo(Amo::Swap, size),
+        "amoadd"  => Op::Amo(Amo::Add,  size),
+        "amoxor"  => Op::Amo(Amo::Xor,  size),
+        "amoand"  => Op::Amo(Amo::And,  size),
+        "amoor"   => Op::Amo(Amo::Or,   size),
+        "amomin"  => Op::Amo(Amo::Min,  size),
+        "amomax"  => Op::Amo(Amo::Max,  size),
+        "amominu" => Op::Amo(Amo::Minu, size),
+        "amomaxu" => Op::Amo(Amo::Maxu, size),
+        _         => Op::Illegal,
+    }
+}
+
+/// Loads a value of `size` bytes for an atomic memory operation,
+/// sign-extended as registers hold it.
+fn load(mem: &mut Memory, addr: u64, size: usize) -> Result<u64, Stop> {
+    let value = mem.read(addr, size).map_err(Stop::Fault)?;
+    Ok(match size {
+        4 => sext32(value),
+        _ => value,
+    })
+}
+
+/// Executes an M extension operation on 32-bit values.
+fn muldiv32(f3: u32, a: u32, b: u32) -> u32 {
+    let (sa, sb) = (a as i32, b as i32);
+    match f3 {
+        0 => a.wrapping_mul(b),
+        1 => ((sa as i64 * sb as i64) >> 32) as u32,
+        2 => ((sa as i64 * b as i64) >> 32) as u32,
+        3 => ((a as u64 * b as u64) >> 32) as u32,
+        4 if b == 0 => u32::MAX,
+        4 => sa.wrapping_div(sb) as u32,
+        5 => a.checked_div(b).unwrap_or(u32::MAX),
+        6 if b == 0 => a,
+        6 => sa.wrapping_rem(sb) as u32,
+        _ => a.checked_rem(b).unwrap_or(a),
+    }
+}
+
+/// Returns the unsigned minimum (`max` false) or maximum (`max` true) of
+/// two values of `size` bytes, sign-extended as registers hold them.
+fn cmp_unsigned(size: usize, x: u64, y: u64, max: bool) -> u64 {
+    let (kx, ky) = match size {
+        4 => (x as u32 as u64, y as u32 as u64),
+        _ => (x, y),
+    };
+    if (kx < ky) != max { x } else { y }
+}
+
+fn sext32(value: u64) -> u64 {
+    value as u32 as i32 as i64 as u64
+}
@@ -0,0 +1,327 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction set specification.
+//!
+//! The specification describes the encoding of each instruction of the base
+//! integer instruction sets and the M, A, and C extensions.  The target
+//! decodes instructions with it, both to disassemble them and to simulate
+//! them.  The encoder remains hand-written, as it also expands
+//! pseudo-instructions, compresses instructions whose operands permit, and
+//! applies relocation operators such as `%hi`.
+
+use crate::asm::Endian;
+use crate::target::spec::{row, Field, Kind, Row, Spec};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set specification.
+pub static SPEC: Spec = Spec {
+    word:       2,
+    endian:     Endian::Little,
+    word_order: Endian::Little,
+    registers:  REGISTERS,
+    fields:     FIELDS,
+    rows:       ROWS,
+};
+
+static REGISTERS: &[&str] = &[
+    "zero", "ra", "sp",  "gp",  "tp", "t0", "t1", "t2",
+    "s0",   "s1", "a0",  "a1",  "a2", "a3", "a4", "a5",
+    "a6",   "a7", "s2",  "s3",  "s4", "s5", "s6", "s7",
+    "s8",   "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// Returns a register field.
+const fn reg(name: &'static str, letters: &'static str, min: u16, max: u16) -> Field {
+    Field { name, letters, kind: Kind::Reg { min, max, step: 1 }, order: "", what: "", reloc: None }
+}
+
+/// Returns an integer field whose bits lie in the given order.
+const fn int(name: &'static str, letters: &'static str, min: i64, max: i64, order: &'static str) -> Field {
+    Field { name, letters, kind: Kind::Int { min, max, invert: 0 }, order, what: "immediate", reloc: None }
+}
+
+/// Returns a branch or jump offset field whose bits lie in the given order.
+const fn rel(name: &'static str, order: &'static str) -> Field {
+    Field { name, letters: "i", kind: Kind::Rel { origin: 0, scale: 1 }, order, what: "offset", reloc: None }
+}
+
+static FIELDS: &[Field] = &[
+    reg("rd",     "d", 0, 31),
+    reg("rs1",    "s", 0, 31),
+    reg("rs2",    "t", 0, 31),
+    reg("rdp",    "d", 8, 15),
+    reg("rs1p",   "s", 8, 15),
+    reg("rs2p",   "t", 8, 15),
+    int("imm12",  "i", -2048, 2047,  ""),
+    int("imm20",  "i", 0, 0xF_FFFF,  ""),
+    int("shamt5", "h", 0, 31,        ""),
+    int("shamt6", "h", 0, 63,        ""),
+    int("csr",    "c", 0, 0xFFF,     ""),
+    int("uimm5",  "u", 0, 31,        ""),
+    int("fm",     "f", 0, 15,        ""),
+    int("pred",   "p", 0, 15,        ""),
+    int("succ",   "q", 0, 15,        ""),
+    int("cimm6",  "i", -32, 31,      ""),
+    int("nzuimm", "i", 4, 1020,      "5:4|9:6|2|3"),
+    int("nzimm",  "i", -512, 496,    "9|4|6|8:7|5"),
+    int("woff",   "i", 0, 124,       "5:3|2|6"),
+    int("doff",   "i", 0, 248,       "5:3|7:6"),
+    int("lwsp",   "i", 0, 252,       "5|4:2|7:6"),
+    int("ldsp",   "i", 0, 504,       "5|4:3|8:6"),
+    int("swsp",   "i", 0, 252,       "5:2|7:6"),
+    int("sdsp",   "i", 0, 504,       "5:3|8:6"),
+    rel("boff",   "12|10:5|4:1|11"),
+    rel("joff",   "20|10:1|11|19:12"),
+    rel("cboff",  "8|4:3|7:6|2:1|5"),
+    rel("cjoff",  "11|4|9:8|10|6|7|3:1|5"),
+];
+
+/// Features that every target has.
+const BASE: u32 = 0;
+
+static ROWS: &[Row] = &[
+    // Upper immediates and jumps
+    row("lui",            "rd, imm20",            "iiiiiiiiiiiiiiiiiiii ddddd 0110111",    BASE),
+    row("auipc",          "rd, imm20",            "iiiiiiiiiiiiiiiiiiii ddddd 0010111",    BASE),
+    row("jal",            "rd, joff",             "iiiiiiiiiiiiiiiiiiii ddddd 1101111",    BASE),
+    row("jalr",           "rd, rs1, imm12",       "iiiiiiiiiiii sssss 000 ddddd 1100111",  BASE),
+
+    // Branches
+    row("beq",            "rs1, rs2, boff",       "iiiiiii ttttt sssss 000 iiiii 1100011", BASE),
+    row("bne",            "rs1, rs2, boff",       "iiiiiii ttttt sssss 001 iiiii 1100011", BASE),
+    row("blt",            "rs1, rs2, boff",       "iiiiiii ttttt sssss 100 iiiii 1100011", BASE),
+    row("bge",            "rs1, rs2, boff",       "iiiiiii ttttt sssss 101 iiiii 1100011", BASE),
+    row("bltu",           "rs1, rs2, boff",       "iiiiiii ttttt sssss 110 iiiii 1100011", BASE),
+    row("bgeu",           "rs1, rs2, boff",       "iiiiiii ttttt sssss 111 iiiii 1100011", BASE),
+
+    // Loads and stores
+    row("lb",             "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 000 ddddd 0000011",  BASE),
+    row("lh",             "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 001 ddddd 0000011",  BASE),
+    row("lw",             "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 010 ddddd 0000011",  BASE),
+    row("ld",             "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 011 ddddd 0000011",  RV64),
+    row("lbu",            "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 100 ddddd 0000011",  BASE),
+    row("lhu",            "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 101 ddddd 0000011",  BASE),
+    row("lwu",            "rd, [rs1 + imm12]",    "iiiiiiiiiiii sssss 110 ddddd 0000011",  RV64),
+    row("sb",             "rs2, [rs1 + imm12]",   "iiiiiii ttttt sssss 000 iiiii 0100011", BASE),
+    row("sh",             "rs2, [rs1 + imm12]",   "iiiiiii ttttt sssss 001 iiiii 0100011", BASE),
+    row("sw",             "rs2, [rs1 + imm12]",   "iiiiiii ttttt sssss 010 iiiii 0100011", BASE),
+    row("sd",             "rs2, [rs1 + imm12]",   "iiiiiii ttttt sssss 011 iiiii 0100011", RV64),
+
+    // Register-immediate operations
+    row("addi",           "rd, rs1, imm12",       "iiiiiiiiiiii sssss 000 ddddd 0010011",  BASE),
+    row("slti",           "rd, rs1, imm12",       "iiiiiiiiiiii sssss 010 ddddd 0010011",  BASE),
+    row("sltiu",          "rd, rs1, imm12",       "iiiiiiiiiiii sssss 011 ddddd 0010011",  BASE),
+    row("xori",           "rd, rs1, imm12",       "iiiiiiiiiiii sssss 100 ddddd 0010011",  BASE),
+    row("ori",            "rd, rs1, imm12",       "iiiiiiiiiiii sssss 110 ddddd 0010011",  BASE),
+    row("andi",           "rd, rs1, imm12",       "iiiiiiiiiiii sssss 111 ddddd 0010011",  BASE),
+    row("slli",           "rd, rs1, shamt5",      "0000000 hhhhh sssss 001 ddddd 0010011", RV32),
+    row("slli",           "rd, rs1, shamt6",      "000000 hhhhhh sssss 001 ddddd 0010011", RV64),
+    row("srli",           "rd, rs1, shamt5",      "0000000 hhhhh sssss 101 ddddd 0010011", RV32),
+    row("srli",           "rd, rs1, shamt6",      "000000 hhhhhh sssss 101 ddddd 0010011", RV64),
+    row("srai",           "rd, rs1, shamt5",      "0100000 hhhhh sssss 101 ddddd 0010011", RV32),
+    row("srai",           "rd, rs1, shamt6",      "010000 hhhhhh sssss 101 ddddd 0010011", RV64),
+    row("addiw",          "rd, rs1, imm12",       "iiiiiiiiiiii sssss 000 ddddd 0011011",  RV64),
+    row("slliw",          "rd, rs1, shamt5",      "0000000 hhhhh sssss 001 ddddd 0011011", RV64),
+    row("srliw",          "rd, rs1, shamt5",      "0000000 hhhhh sssss 101 ddddd 0011011", RV64),
+    row("sraiw",          "rd, rs1, shamt5",      "0100000 hhhhh sssss 101 ddddd 0011011", RV64),
+
+    // Register-register operations
+    row("add",            "rd, rs1, rs2",         "0000000 ttttt sssss 000 ddddd 0110011", BASE),
+    row("sub",            "rd, rs1, rs2",         "0100000 ttttt sssss 000 ddddd 0110011", BASE),
+    row("sll",            "rd, rs1, rs2",         "0000000 ttttt sssss 001 ddddd 0110011", BASE),
+    row("slt",            "rd, rs1, rs2",         "0000000 ttttt sssss 010 ddddd 0110011", BASE),
+    row("sltu",           "rd, rs1, rs2",         "0000000 ttttt sssss 011 ddddd 0110011", BASE),
+    row("xor",            "rd, rs1, rs2",         "0000000 ttttt sssss 100 ddddd 0110011", BASE),
+    row("srl",            "rd, rs1, rs2",         "0000000 ttttt sssss 101 ddddd 0110011", BASE),
+    row("sra",            "rd, rs1, rs2",         "0100000 ttttt sssss 101 ddddd 0110011", BASE),
+    row("or",             "rd, rs1, rs2",         "0000000 ttttt sssss 110 ddddd 0110011", BASE),
+    row("and",            "rd, rs1, rs2",         "0000000 ttttt sssss 111 ddddd 0110011", BASE),
+    row("addw",           "rd, rs1, rs2",         "0000000 ttttt sssss 000 ddddd 0111011", RV64),
+    row("subw",           "rd, rs1, rs2",         "0100000 ttttt sssss 000 ddddd 0111011", RV64),
+    row("sllw",           "rd, rs1, rs2",         "0000000 ttttt sssss 001 ddddd 0111011", RV64),
+    row("srlw",           "rd, rs1, rs2",         "0000000 ttttt sssss 101 ddddd 0111011", RV64),
+    row("sraw",           "rd, rs1, rs2",         "0100000 ttttt sssss 101 ddddd 0111011", RV64),
+
+    // System
+    row("fence",          "",                     "0000 1111 1111 00000 000 00000 0001111", BASE),
+    row("fence",          "fm, pred, succ",       "ffff pppp qqqq 00000 000 00000 0001111", BASE),
+    row("fence.i",        "",                     "0000000 00000 00000 001 00000 0001111", BASE),
+    row("ecall",          "",                     "0000000 00000 00000 000 00000 1110011", BASE),
+    row("ebreak",         "",                     "0000000 00001 00000 000 00000 1110011", BASE),
+    row("sret",           "",                     "0001000 00010 00000 000 00000 1110011", BASE),
+    row("mret",           "",                     "0011000 00010 00000 000 00000 1110011", BASE),
+    row("wfi",            "",                     "0001000 00101 00000 000 00000 1110011", BASE),
+    row("csrrw",          "rd, csr, rs1",         "cccccccccccc sssss 001 ddddd 1110011",  BASE),
+    row("csrrs",          "rd, csr, rs1",         "cccccccccccc sssss 010 ddddd 1110011",  BASE),
+    row("csrrc",          "rd, csr, rs1",         "cccccccccccc sssss 011 ddddd 1110011",  BASE),
+    row("csrrwi",         "rd, csr, uimm5",       "cccccccccccc uuuuu 101 ddddd 1110011",  BASE),
+    row("csrrsi",         "rd, csr, uimm5",       "cccccccccccc uuuuu 110 ddddd 1110011",  BASE),
+    row("csrrci",         "rd, csr, uimm5",       "cccccccccccc uuuuu 111 ddddd 1110011",  BASE),
+
+    // M extension
+    row("mul",            "rd, rs1, rs2",         "0000001 ttttt sssss 000 ddddd 0110011", EXT_M),
+    row("mulh",           "rd, rs1, rs2",         "0000001 ttttt sssss 001 ddddd 0110011", EXT_M),
+    row("mulhsu",         "rd, rs1, rs2",         "0000001 ttttt sssss 010 ddddd 0110011", EXT_M),
+    row("mulhu",          "rd, rs1, rs2",         "0000001 ttttt sssss 011 ddddd 0110011", EXT_M),
+    row("div",            "rd, rs1, rs2",         "0000001 ttttt sssss 100 ddddd 0110011", EXT_M),
+    row("divu",           "rd, rs1, rs2",         "0000001 ttttt sssss 101 ddddd 0110011", EXT_M),
+    row("rem",            "rd, rs1, rs2",         "0000001 ttttt sssss 110 ddddd 0110011", EXT_M),
+    row("remu",           "rd, rs1, rs2",         "0000001 ttttt sssss 111 ddddd 0110011", EXT_M),
+    row("mulw",           "rd, rs1, rs2",         "0000001 ttttt sssss 000 ddddd 0111011", RV64 | EXT_M),
+    row("divw",           "rd, rs1, rs2",         "0000001 ttttt sssss 100 ddddd 0111011", RV64 | EXT_M),
+    row("divuw",          "rd, rs1, rs2",         "0000001 ttttt sssss 101 ddddd 0111011", RV64 | EXT_M),
+    row("remw",           "rd, rs1, rs2",         "0000001 ttttt sssss 110 ddddd 0111011", RV64 | EXT_M),
+    row("remuw",          "rd, rs1, rs2",         "0000001 ttttt sssss 111 ddddd 0111011", RV64 | EXT_M),
+
+    // A extension
+    row("lr.w",           "rd, [rs1]",            "0001000 00000 sssss 010 ddddd 0101111", EXT_A),
+    row("lr.w.aq",        "rd, [rs1]",            "0001010 00000 sssss 010 ddddd 0101111", EXT_A),
+    row("lr.w.rl",        "rd, [rs1]",            "0001001 00000 sssss 010 ddddd 0101111", EXT_A),
+    row("lr.w.aqrl",      "rd, [rs1]",            "0001011 00000 sssss 010 ddddd 0101111", EXT_A),
+    row("sc.w",           "rd, rs2, [rs1]",       "0001100 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("sc.w.aq",        "rd, rs2, [rs1]",       "0001110 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("sc.w.rl",        "rd, rs2, [rs1]",       "0001101 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("sc.w.aqrl",      "rd, rs2, [rs1]",       "0001111 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoswap.w",      "rd, rs2, [rs1]",       "0000100 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoswap.w.aq",   "rd, rs2, [rs1]",       "0000110 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoswap.w.rl",   "rd, rs2, [rs1]",       "0000101 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoswap.w.aqrl", "rd, rs2, [rs1]",       "0000111 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoadd.w",       "rd, rs2, [rs1]",       "0000000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoadd.w.aq",    "rd, rs2, [rs1]",       "0000010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoadd.w.rl",    "rd, rs2, [rs1]",       "0000001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoadd.w.aqrl",  "rd, rs2, [rs1]",       "0000011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoxor.w",       "rd, rs2, [rs1]",       "0010000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoxor.w.aq",    "rd, rs2, [rs1]",       "0010010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoxor.w.rl",    "rd, rs2, [rs1]",       "0010001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoxor.w.aqrl",  "rd, rs2, [rs1]",       "0010011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoand.w",       "rd, rs2, [rs1]",       "0110000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoand.w.aq",    "rd, rs2, [rs1]",       "0110010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoand.w.rl",    "rd, rs2, [rs1]",       "0110001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoand.w.aqrl",  "rd, rs2, [rs1]",       "0110011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoor.w",        "rd, rs2, [rs1]",       "0100000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoor.w.aq",     "rd, rs2, [rs1]",       "0100010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoor.w.rl",     "rd, rs2, [rs1]",       "0100001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amoor.w.aqrl",   "rd, rs2, [rs1]",       "0100011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomin.w",       "rd, rs2, [rs1]",       "1000000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomin.w.aq",    "rd, rs2, [rs1]",       "1000010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomin.w.rl",    "rd, rs2, [rs1]",       "1000001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomin.w.aqrl",  "rd, rs2, [rs1]",       "1000011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomax.w",       "rd, rs2, [rs1]",       "1010000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomax.w.aq",    "rd, rs2, [rs1]",       "1010010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomax.w.rl",    "rd, rs2, [rs1]",       "1010001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomax.w.aqrl",  "rd, rs2, [rs1]",       "1010011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amominu.w",      "rd, rs2, [rs1]",       "1100000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amominu.w.aq",   "rd, rs2, [rs1]",       "1100010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amominu.w.rl",   "rd, rs2, [rs1]",       "1100001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amominu.w.aqrl", "rd, rs2, [rs1]",       "1100011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomaxu.w",      "rd, rs2, [rs1]",       "1110000 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomaxu.w.aq",   "rd, rs2, [rs1]",       "1110010 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomaxu.w.rl",   "rd, rs2, [rs1]",       "1110001 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("amomaxu.w.aqrl", "rd, rs2, [rs1]",       "1110011 ttttt sssss 010 ddddd 0101111", EXT_A),
+    row("lr.d",           "rd, [rs1]",            "0001000 00000 sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("lr.d.aq",        "rd, [rs1]",            "0001010 00000 sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("lr.d.rl",        "rd, [rs1]",            "0001001 00000 sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("lr.d.aqrl",      "rd, [rs1]",            "0001011 00000 sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("sc.d",           "rd, rs2, [rs1]",       "0001100 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("sc.d.aq",        "rd, rs2, [rs1]",       "0001110 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("sc.d.rl",        "rd, rs2, [rs1]",       "0001101 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("sc.d.aqrl",      "rd, rs2, [rs1]",       "0001111 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoswap.d",      "rd, rs2, [rs1]",       "0000100 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoswap.d.aq",   "rd, rs2, [rs1]",       "0000110 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoswap.d.rl",   "rd, rs2, [rs1]",       "0000101 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoswap.d.aqrl", "rd, rs2, [rs1]",       "0000111 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoadd.d",       "rd, rs2, [rs1]",       "0000000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoadd.d.aq",    "rd, rs2, [rs1]",       "0000010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoadd.d.rl",    "rd, rs2, [rs1]",       "0000001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoadd.d.aqrl",  "rd, rs2, [rs1]",       "0000011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoxor.d",       "rd, rs2, [rs1]",       "0010000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoxor.d.aq",    "rd, rs2, [rs1]",       "0010010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoxor.d.rl",    "rd, rs2, [rs1]",       "0010001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoxor.d.aqrl",  "rd, rs2, [rs1]",       "0010011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoand.d",       "rd, rs2, [rs1]",       "0110000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoand.d.aq",    "rd, rs2, [rs1]",       "0110010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoand.d.rl",    "rd, rs2, [rs1]",       "0110001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoand.d.aqrl",  "rd, rs2, [rs1]",       "0110011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoor.d",        "rd, rs2, [rs1]",       "0100000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoor.d.aq",     "rd, rs2, [rs1]",       "0100010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoor.d.rl",     "rd, rs2, [rs1]",       "0100001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amoor.d.aqrl",   "rd, rs2, [rs1]",       "0100011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomin.d",       "rd, rs2, [rs1]",       "1000000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomin.d.aq",    "rd, rs2, [rs1]",       "1000010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomin.d.rl",    "rd, rs2, [rs1]",       "1000001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomin.d.aqrl",  "rd, rs2, [rs1]",       "1000011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomax.d",       "rd, rs2, [rs1]",       "1010000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomax.d.aq",    "rd, rs2, [rs1]",       "1010010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomax.d.rl",    "rd, rs2, [rs1]",       "1010001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomax.d.aqrl",  "rd, rs2, [rs1]",       "1010011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amominu.d",      "rd, rs2, [rs1]",       "1100000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amominu.d.aq",   "rd, rs2, [rs1]",       "1100010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amominu.d.rl",   "rd, rs2, [rs1]",       "1100001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amominu.d.aqrl", "rd, rs2, [rs1]",       "1100011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomaxu.d",      "rd, rs2, [rs1]",       "1110000 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomaxu.d.aq",   "rd, rs2, [rs1]",       "1110010 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomaxu.d.rl",   "rd, rs2, [rs1]",       "1110001 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+    row("amomaxu.d.aqrl", "rd, rs2, [rs1]",       "1110011 ttttt sssss 011 ddddd 0101111", RV64 | EXT_A),
+
+    // C extension, quadrant 0
+    row("c.addi4spn",     "rdp, sp, nzuimm",      "000 iiiiiiii ddd 00",                   EXT_C),
+    row("c.lw",           "rdp, [rs1p + woff]",   "010 iii sss ii ddd 00",                 EXT_C),
+    row("c.ld",           "rdp, [rs1p + doff]",   "011 iii sss ii ddd 00",                 RV64 | EXT_C),
+    row("c.sw",           "rs2p, [rs1p + woff]",  "110 iii sss ii ttt 00",                 EXT_C),
+    row("c.sd",           "rs2p, [rs1p + doff]",  "111 iii sss ii ttt 00",                 RV64 | EXT_C),
+
+    // C extension, quadrant 1
+    row("c.nop",          "",                     "000 0 00000 00000 01",                  EXT_C),
+    row("c.addi",         "rd, cimm6",            "000 i ddddd iiiii 01",                  EXT_C),
+    row("c.jal",          "cjoff",                "001 iiiiiiiiiii 01",                    RV32 | EXT_C),
+    row("c.addiw",        "rd, cimm6",            "001 i ddddd iiiii 01",                  RV64 | EXT_C),
+    row("c.li",           "rd, cimm6",            "010 i ddddd iiiii 01",                  EXT_C),
+    row("c.addi16sp",     "sp, nzimm",            "011 i 00010 iiiii 01",                  EXT_C),
+    row("c.lui",          "rd, cimm6",            "011 i ddddd iiiii 01",                  EXT_C),
+    row("c.srli",         "rs1p, shamt5",         "100 0 00 sss hhhhh 01",                 RV32 | EXT_C),
+    row("c.srli",         "rs1p, shamt6",         "100 h 00 sss hhhhh 01",                 RV64 | EXT_C),
+    row("c.srai",         "rs1p, shamt5",         "100 0 01 sss hhhhh 01",                 RV32 | EXT_C),
+    row("c.srai",         "rs1p, shamt6",         "100 h 01 sss hhhhh 01",                 RV64 | EXT_C),
+    row("c.andi",         "rs1p, cimm6",          "100 i 10 sss iiiii 01",                 EXT_C),
+    row("c.sub",          "rs1p, rs2p",           "100 0 11 sss 00 ttt 01",                EXT_C),
+    row("c.xor",          "rs1p, rs2p",           "100 0 11 sss 01 ttt 01",                EXT_C),
+    row("c.or",           "rs1p, rs2p",           "100 0 11 sss 10 ttt 01",                EXT_C),
+    row("c.and",          "rs1p, rs2p",           "100 0 11 sss 11 ttt 01",                EXT_C),
+    row("c.subw",         "rs1p, rs2p",           "100 1 11 sss 00 ttt 01",                RV64 | EXT_C),
+    row("c.addw",         "rs1p, rs2p",           "100 1 11 sss 01 ttt 01",                RV64 | EXT_C),
+    row("c.j",            "cjoff",                "101 iiiiiiiiiii 01",                    EXT_C),
+    row("c.beqz",         "rs1p, cboff",          "110 iii sss iiiii 01",                  EXT_C),
+    row("c.bnez",         "rs1p, cboff",          "111 iii sss iiiii 01",                  EXT_C),
+
+    // C extension, quadrant 2
+    row("c.slli",         "rd, shamt5",           "000 0 ddddd hhhhh 10",                  RV32 | EXT_C),
+    row("c.slli",         "rd, shamt6",           "000 h ddddd hhhhh 10",                  RV64 | EXT_C),
+    row("c.lwsp",         "rd, [sp + lwsp]",      "010 i ddddd iiiii 10",                  EXT_C),
+    row("c.ldsp",         "rd, [sp + ldsp]",      "011 i ddddd iiiii 10",                  RV64 | EXT_C),
+    row("c.jr",           "rs1",                  "100 0 sssss 00000 10",                  EXT_C),
+    row("c.mv",           "rd, rs2",              "100 0 ddddd ttttt 10",                  EXT_C),
+    row("c.ebreak",       "",                     "100 1 00000 00000 10",                  EXT_C),
+    row("c.jalr",         "rs1",                  "100 1 sssss 00000 10",                  EXT_C),
+    row("c.add",          "rd, rs2",              "100 1 ddddd ttttt 10",                  EXT_C),
+    row("c.swsp",         "rs2, [sp + swsp]",     "110 iiiiii ttttt 10",                   EXT_C),
+    row("c.sdsp",         "rs2, [sp + sdsp]",     "111 iiiiii ttttt 10",                   RV64 | EXT_C),
+];
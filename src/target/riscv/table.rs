@@ -0,0 +1,389 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction table.
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Both register widths.
+const XLEN: u32 = RV32 | RV64;
+
+/// Entry in the instruction table.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Mnemonic.
+    pub name: String,
+
+    /// Register widths of which the target must have one.
+    pub xlen: u32,
+
+    /// Extensions that the target must have.
+    pub ext: u32,
+
+    /// Operand syntax and encoding.
+    pub form: Form,
+
+    /// Instruction word with all operand fields zero.
+    pub bits: u32,
+}
+
+/// Operand syntax and encoding of an instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Form {
+    /// `rd, rs1, rs2`
+    R,
+
+    /// `rd, rs1, imm`
+    I,
+
+    /// `rd, rs1, shamt`, with a shift amount below the register width.
+    Shift,
+
+    /// `rd, rs1, shamt`, with a shift amount below 32.
+    ShiftW,
+
+    /// `rd, [rs1 + imm]`
+    Load,
+
+    /// `rs2, [rs1 + imm]`
+    Store,
+
+    /// `rs1, rs2, target`
+    Branch,
+
+    /// `rd, imm`
+    U,
+
+    /// `[rd,] target`, with `ra` if `rd` is omitted.
+    Jal,
+
+    /// `rs1`, `rd, rs1`, `rd, rs1, imm`, or `rd, [rs1 + imm]`.
+    Jalr,
+
+    /// `rd, rs2, [rs1]`
+    Amo,
+
+    /// `rd, [rs1]`
+    Lr,
+
+    /// `[pred, succ]`, with `iorw, iorw` if omitted.
+    Fence,
+
+    /// No operands.
+    Fixed,
+
+    /// `rd, csr, rs1`
+    Csr,
+
+    /// `rd, csr, uimm`
+    CsrI,
+
+    /// Pseudo-instruction that expands to other instructions.
+    Pseudo(Pseudo),
+
+    /// Compressed instruction that requires the 16-bit encoding of the
+    /// named instruction, with operands rearranged as given.
+    C(Short, &'static str),
+}
+
+/// Pseudo-instructions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pseudo {
+    /// `nop` → `addi x0, x0, 0`
+    Nop,
+
+    /// `li rd, imm` → shortest sequence that loads `imm`
+    Li,
+
+    /// `la rd, sym` → `auipc` and `addi`
+    La,
+
+    /// `call sym` → `auipc ra` and `jalr ra`
+    Call,
+
+    /// `tail sym` → `auipc t1` and `jalr x0, t1`
+    Tail,
+
+    /// `op rd, rs` → `base rd, rs, imm`
+    RegImm(&'static str, i64),
+
+    /// `op rd, rs` → `base rd, x0, rs`
+    ZeroReg(&'static str),
+
+    /// `op rd, rs` → `base rd, rs, x0`
+    RegZero(&'static str),
+
+    /// `op rs, target` → `base rs, x0, target`
+    BranchZ(&'static str),
+
+    /// `op rs, target` → `base x0, rs, target`
+    ZBranch(&'static str),
+
+    /// `op rs1, rs2, target` → `base rs2, rs1, target`
+    BranchSwap(&'static str),
+
+    /// `j target` → `jal x0, target`
+    J,
+
+    /// `jr rs` → `jalr x0, rs, 0`
+    Jr,
+
+    /// `ret` → `jalr x0, ra, 0`
+    Ret,
+
+    /// `csrr rd, csr` → `csrrs rd, csr, x0`
+    Csrr,
+
+    /// `op csr, rs` → `base x0, csr, rs`
+    CsrWrite(&'static str),
+}
+
+/// Operand arrangement of a compressed instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Short {
+    /// Operands as for the full instruction.
+    Same,
+
+    /// `rd, x` → `rd, rd, x`
+    Rd2,
+
+    /// `rd, x` → `rd, x0, x`
+    Zero,
+
+    /// `target` → `link, target`
+    Jump(u32),
+
+    /// `rs` → `link, rs, 0`
+    JumpReg(u32),
+
+    /// `rs, target` → `rs, x0, target`
+    BranchZ,
+}
+
+impl Entry {
+    fn new(name: &str, xlen: u32, ext: u32, form: Form, bits: u32) -> Self {
+        Self { name: name.into(), xlen, ext, form, bits }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns an instruction word with the given major opcode and function
+/// fields.
+const fn op(opcode: u32, funct3: u32, funct7: u32) -> u32 {
+    opcode | funct3 << 12 | funct7 << 25
+}
+
+const OP_IMM:    u32 = 0x13;
+const OP_IMM_32: u32 = 0x1B;
+const OP:        u32 = 0x33;
+const OP_32:     u32 = 0x3B;
+const LOAD:      u32 = 0x03;
+const STORE:     u32 = 0x23;
+const BRANCH_OP: u32 = 0x63;
+const AMO:       u32 = 0x2F;
+const SYSTEM:    u32 = 0x73;
+
+/// Builds the instruction table for all features.  The target keeps the
+/// entries that it has the features for.
+pub fn build() -> Vec<Entry> {
+    use self::Form::*;
+    use self::Pseudo::*;
+
+    let mut t = vec![];
+
+    let mut add = |name: &str, xlen: u32, ext: u32, form: Form, bits: u32| {
+        t.push(Entry::new(name, xlen, ext, form, bits));
+    };
+
+    // === Base integer instructions ===
+
+    add("lui",    XLEN, 0, U,      0x37);
+    add("auipc",  XLEN, 0, U,      0x17);
+    add("jal",    XLEN, 0, Jal,    0x6F);
+    add("jalr",   XLEN, 0, Jalr,   0x67);
+
+    for (name, f3) in [("beq", 0), ("bne", 1), ("blt", 4), ("bge", 5), ("bltu", 6), ("bgeu", 7)] {
+        add(name, XLEN, 0, Branch, op(BRANCH_OP, f3, 0));
+    }
+
+    for (name, xlen, f3) in [
+        ("lb",  XLEN, 0), ("lh",  XLEN, 1), ("lw",  XLEN, 2), ("ld", RV64, 3),
+        ("lbu", XLEN, 4), ("lhu", XLEN, 5), ("lwu", RV64, 6),
+    ] {
+        add(name, xlen, 0, Load, op(LOAD, f3, 0));
+    }
+
+    for (name, xlen, f3) in [("sb", XLEN, 0), ("sh", XLEN, 1), ("sw", XLEN, 2), ("sd", RV64, 3)] {
+        add(name, xlen, 0, Store, op(STORE, f3, 0));
+    }
+
+    for (name, f3) in [
+        ("addi", 0), ("slti", 2), ("sltiu", 3), ("xori", 4), ("ori", 6), ("andi", 7),
+    ] {
+        add(name, XLEN, 0, I, op(OP_IMM, f3, 0));
+    }
+
+    add("slli",   XLEN, 0, Shift,  op(OP_IMM,    1, 0x00));
+    add("srli",   XLEN, 0, Shift,  op(OP_IMM,    5, 0x00));
+    add("srai",   XLEN, 0, Shift,  op(OP_IMM,    5, 0x20));
+
+    for (name, f3, f7) in [
+        ("add", 0, 0x00), ("sub", 0, 0x20), ("sll", 1, 0x00), ("slt", 2, 0x00),
+        ("sltu", 3, 0x00), ("xor", 4, 0x00), ("srl", 5, 0x00), ("sra", 5, 0x20),
+        ("or",  6, 0x00), ("and", 7, 0x00),
+    ] {
+        add(name, XLEN, 0, R, op(OP, f3, f7));
+    }
+
+    add("addiw",  RV64, 0, I,      op(OP_IMM_32, 0, 0x00));
+    add("slliw",  RV64, 0, ShiftW, op(OP_IMM_32, 1, 0x00));
+    add("srliw",  RV64, 0, ShiftW, op(OP_IMM_32, 5, 0x00));
+    add("sraiw",  RV64, 0, ShiftW, op(OP_IMM_32, 5, 0x20));
+    add("addw",   RV64, 0, R,      op(OP_32,     0, 0x00));
+    add("subw",   RV64, 0, R,      op(OP_32,     0, 0x20));
+    add("sllw",   RV64, 0, R,      op(OP_32,     1, 0x00));
+    add("srlw",   RV64, 0, R,      op(OP_32,     5, 0x00));
+    add("sraw",   RV64, 0, R,      op(OP_32,     5, 0x20));
+
+    add("fence",   XLEN, 0, Fence, 0x0000_000F);
+    add("fence.i", XLEN, 0, Fixed, 0x0000_100F);
+    add("ecall",   XLEN, 0, Fixed, 0x0000_0073);
+    add("ebreak",  XLEN, 0, Fixed, 0x0010_0073);
+    add("sret",    XLEN, 0, Fixed, 0x1020_0073);
+    add("mret",    XLEN, 0, Fixed, 0x3020_0073);
+    add("wfi",     XLEN, 0, Fixed, 0x1050_0073);
+
+    add("csrrw",  XLEN, 0, Csr,    op(SYSTEM, 1, 0));
+    add("csrrs",  XLEN, 0, Csr,    op(SYSTEM, 2, 0));
+    add("csrrc",  XLEN, 0, Csr,    op(SYSTEM, 3, 0));
+    add("csrrwi", XLEN, 0, CsrI,   op(SYSTEM, 5, 0));
+    add("csrrsi", XLEN, 0, CsrI,   op(SYSTEM, 6, 0));
+    add("csrrci", XLEN, 0, CsrI,   op(SYSTEM, 7, 0));
+
+    // === M extension ===
+
+    for (name, f3) in [
+        ("mul", 0), ("mulh", 1), ("mulhsu", 2), ("mulhu", 3),
+        ("div", 4), ("divu", 5), ("rem",    6), ("remu",  7),
+    ] {
+        add(name, XLEN, EXT_M, R, op(OP, f3, 0x01));
+    }
+
+    for (name, f3) in [("mulw", 0), ("divw", 4), ("divuw", 5), ("remw", 6), ("remuw", 7)] {
+        add(name, RV64, EXT_M, R, op(OP_32, f3, 0x01));
+    }
+
+    // === A extension ===
+
+    for (width, xlen, f3) in [(".w", XLEN, 2), (".d", RV64, 3)] {
+        for (name, form, f5) in [
+            ("lr",      Lr,  0x02), ("sc",      Amo, 0x03), ("amoswap", Amo, 0x01),
+            ("amoadd",  Amo, 0x00), ("amoxor",  Amo, 0x04), ("amoand",  Amo, 0x0C),
+            ("amoor",   Amo, 0x08), ("amomin",  Amo, 0x10), ("amomax",  Amo, 0x14),
+            ("amominu", Amo, 0x18), ("amomaxu", Amo, 0x1C),
+        ] {
+            for (order, bits) in [("", 0), (".aq", 2), (".rl", 1), (".aqrl", 3)] {
+                let name = format!("{}{}{}", name, width, order);
+                add(&name, xlen, EXT_A, form, op(AMO, f3, f5 << 2 | bits));
+            }
+        }
+    }
+
+    // === Pseudo-instructions ===
+
+    add("nop",    XLEN, 0, Pseudo(Nop),                 0);
+    add("li",     XLEN, 0, Pseudo(Li),                  0);
+    add("la",     XLEN, 0, Pseudo(La),                  0);
+    add("call",   XLEN, 0, Pseudo(Call),                0);
+    add("tail",   XLEN, 0, Pseudo(Tail),                0);
+    add("mv",     XLEN, 0, Pseudo(RegImm("addi",  0)),  0);
+    add("not",    XLEN, 0, Pseudo(RegImm("xori", -1)),  0);
+    add("seqz",   XLEN, 0, Pseudo(RegImm("sltiu", 1)),  0);
+    add("sext.w", RV64, 0, Pseudo(RegImm("addiw", 0)),  0);
+    add("neg",    XLEN, 0, Pseudo(ZeroReg("sub")),      0);
+    add("negw",   RV64, 0, Pseudo(ZeroReg("subw")),     0);
+    add("snez",   XLEN, 0, Pseudo(ZeroReg("sltu")),     0);
+    add("sgtz",   XLEN, 0, Pseudo(ZeroReg("slt")),      0);
+    add("sltz",   XLEN, 0, Pseudo(RegZero("slt")),      0);
+    add("beqz",   XLEN, 0, Pseudo(BranchZ("beq")),      0);
+    add("bnez",   XLEN, 0, Pseudo(BranchZ("bne")),      0);
+    add("bltz",   XLEN, 0, Pseudo(BranchZ("blt")),      0);
+    add("bgez",   XLEN, 0, Pseudo(BranchZ("bge")),      0);
+    add("blez",   XLEN, 0, Pseudo(ZBranch("bge")),      0);
+    add("bgtz",   XLEN, 0, Pseudo(ZBranch("blt")),      0);
+    add("bgt",    XLEN, 0, Pseudo(BranchSwap("blt")),   0);
+    add("ble",    XLEN, 0, Pseudo(BranchSwap("bge")),   0);
+    add("bgtu",   XLEN, 0, Pseudo(BranchSwap("bltu")),  0);
+    add("bleu",   XLEN, 0, Pseudo(BranchSwap("bgeu")),  0);
+    add("j",      XLEN, 0, Pseudo(J),                   0);
+    add("jr",     XLEN, 0, Pseudo(Jr),                  0);
+    add("ret",    XLEN, 0, Pseudo(Ret),                 0);
+    add("csrr",   XLEN, 0, Pseudo(Csrr),                0);
+    add("csrw",   XLEN, 0, Pseudo(CsrWrite("csrrw")),   0);
+    add("csrs",   XLEN, 0, Pseudo(CsrWrite("csrrs")),   0);
+    add("csrc",   XLEN, 0, Pseudo(CsrWrite("csrrc")),   0);
+    add("csrwi",  XLEN, 0, Pseudo(CsrWrite("csrrwi")),  0);
+    add("csrsi",  XLEN, 0, Pseudo(CsrWrite("csrrsi")),  0);
+    add("csrci",  XLEN, 0, Pseudo(CsrWrite("csrrci")),  0);
+
+    // === C extension ===
+
+    for (name, xlen, short, base) in [
+        ("c.nop",      XLEN, Short::Same,       "nop"),
+        ("c.ebreak",   XLEN, Short::Same,       "ebreak"),
+        ("c.addi",     XLEN, Short::Rd2,        "addi"),
+        ("c.addiw",    RV64, Short::Rd2,        "addiw"),
+        ("c.addi16sp", XLEN, Short::Rd2,        "addi"),
+        ("c.addi4spn", XLEN, Short::Same,       "addi"),
+        ("c.li",       XLEN, Short::Zero,       "addi"),
+        ("c.lui",      XLEN, Short::Same,       "lui"),
+        ("c.andi",     XLEN, Short::Rd2,        "andi"),
+        ("c.slli",     XLEN, Short::Rd2,        "slli"),
+        ("c.srli",     XLEN, Short::Rd2,        "srli"),
+        ("c.srai",     XLEN, Short::Rd2,        "srai"),
+        ("c.mv",       XLEN, Short::Zero,       "add"),
+        ("c.add",      XLEN, Short::Rd2,        "add"),
+        ("c.sub",      XLEN, Short::Rd2,        "sub"),
+        ("c.xor",      XLEN, Short::Rd2,        "xor"),
+        ("c.or",       XLEN, Short::Rd2,        "or"),
+        ("c.and",      XLEN, Short::Rd2,        "and"),
+        ("c.addw",     RV64, Short::Rd2,        "addw"),
+        ("c.subw",     RV64, Short::Rd2,        "subw"),
+        ("c.lw",       XLEN, Short::Same,       "lw"),
+        ("c.sw",       XLEN, Short::Same,       "sw"),
+        ("c.lwsp",     XLEN, Short::Same,       "lw"),
+        ("c.swsp",     XLEN, Short::Same,       "sw"),
+        ("c.ld",       RV64, Short::Same,       "ld"),
+        ("c.sd",       RV64, Short::Same,       "sd"),
+        ("c.ldsp",     RV64, Short::Same,       "ld"),
+        ("c.sdsp",     RV64, Short::Same,       "sd"),
+        ("c.j",        XLEN, Short::Jump(0),    "jal"),
+        ("c.jal",      RV32, Short::Jump(1),    "jal"),
+        ("c.jr",       XLEN, Short::JumpReg(0), "jalr"),
+        ("c.jalr",     XLEN, Short::JumpReg(1), "jalr"),
+        ("c.beqz",     XLEN, Short::BranchZ,    "beq"),
+        ("c.bnez",     XLEN, Short::BranchZ,    "bne"),
+    ] {
+        add(name, xlen, EXT_C, C(short, base), 0);
+    }
+
+    t
+}
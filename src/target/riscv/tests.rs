@@ -0,0 +1,307 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::Bench;
+
+/// Assembles each line at address zero.
+const BENCH: Bench<u32> = Bench { origin: 0, split: insns };
+
+/// Splits the given bytes into 16-bit and 32-bit instructions.
+fn insns(bytes: &[u8]) -> Vec<u32> {
+    let mut insns = vec![];
+    let mut rest  = bytes;
+
+    while let [a, b, tail @ ..] = rest {
+        let half = u16::from_le_bytes([*a, *b]) as u32;
+        match (half & 3, tail) {
+            (3, [c, d, tail @ ..]) => {
+                insns.push(half | (u16::from_le_bytes([*c, *d]) as u32) << 16);
+                rest = tail;
+            },
+            _ => {
+                insns.push(half);
+                rest = tail;
+            },
+        }
+    }
+
+    insns
+}
+
+#[test]
+fn rv32i() {
+    BENCH.check("rv32i", &[
+        ("addi a0, a1, 5",                      &[0x00558513]),
+        ("addi x10, x11, -1",                   &[0xFFF58513]),
+        ("add a0, a1, a2",                      &[0x00C58533]),
+        ("sub a0, a1, a2",                      &[0x40C58533]),
+        ("slli a0, a0, 3",                      &[0x00351513]),
+        ("srai a0, a0, 3",                      &[0x40355513]),
+        ("lw a0, [sp + 8]",                     &[0x00812503]),
+        ("lbu t0, [a0]",                        &[0x00054283]),
+        ("sw a0, [sp + 8]",                     &[0x00A12423]),
+        ("sb zero, [fp - 1]",                   &[0xFE040FA3]),
+        ("lui a0, x'12345",                     &[0x12345537]),
+        ("auipc a0, 0",                         &[0x00000517]),
+        ("jal 0",                               &[0x000000EF]),
+        ("jalr a0",                             &[0x000500E7]),
+        ("jalr ra, [a0 + 4]",                   &[0x004500E7]),
+        ("beq a0, a1, 8",                       &[0x00B50463]),
+        ("bne a0, a1, -4",                      &[0xFEB51EE3]),
+        ("fence",                               &[0x0FF0000F]),
+        ("fence rw, w",                         &[0x0310000F]),
+        ("ecall",                               &[0x00000073]),
+        ("ebreak",                              &[0x00100073]),
+        ("mret",                                &[0x30200073]),
+        ("csrrw a0, mstatus, a1",               &[0x30059573]),
+        ("csrrsi zero, x'300, 8",               &[0x30046073]),
+    ]);
+}
+
+#[test]
+fn extensions() {
+    BENCH.check("rv32imac", &[
+        ("mul a0, a1, a2",                      &[0x02C58533]),
+        ("divu a0, a1, a2",                     &[0x02C5D533]),
+        ("remu a0, a1, a2",                     &[0x02C5F533]),
+        ("amoadd.w a0, a1, [a2]",               &[0x00B6252F]),
+        ("amoswap.w.aqrl a0, a1, [a2]",         &[0x0EB6252F]),
+        ("lr.w a0, [a1]",                       &[0x1005A52F]),
+        ("sc.w a0, a2, [a1]",                   &[0x18C5A52F]),
+    ]);
+    BENCH.check("rv64imac", &[
+        ("mulw a0, a1, a2",                     &[0x02C5853B]),
+        ("amoadd.d a0, a1, [a2]",               &[0x00B6352F]),
+        ("ld a0, [a1 + 8]",                     &[0x6588]),
+        ("addiw a0, a1, 1",                     &[0x0015851B]),
+    ]);
+}
+
+#[test]
+fn pseudo() {
+    BENCH.check("rv32i", &[
+        ("nop",                                 &[0x00000013]),
+        ("mv a0, a1",                           &[0x00058513]),
+        ("not a0, a1",                          &[0xFFF5C513]),
+        ("neg a0, a1",                          &[0x40B00533]),
+        ("seqz a0, a1",                         &[0x0015B513]),
+        ("snez a0, a1",                         &[0x00B03533]),
+        ("li a0, -1",                           &[0xFFF00513]),
+        ("li a0, x'12345678",                   &[0x12345537, 0x67850513]),
+        ("li a0, x'12345FFF",                   &[0x12346537, 0xFFF50513]),
+        ("li a0, x'80000000",                   &[0x80000537]),
+        ("li a0, x'FFFFFFFF",                   &[0xFFF00513]),
+        ("la a0, x'100",                        &[0x00000517, 0x10050513]),
+        ("call x'1800",                         &[0x00002097, 0x800080E7]),
+        ("tail 0",                              &[0x00000317, 0x00030067]),
+        ("j 8",                                 &[0x0080006F]),
+        ("jr a0",                               &[0x00050067]),
+        ("ret",                                 &[0x00008067]),
+        ("beqz a0, 8",                          &[0x00050463]),
+        ("bgt a0, a1, 8",                       &[0x00A5C463]),
+        ("blez a0, 8",                          &[0x00A05463]),
+        ("csrr a0, mstatus",                    &[0x30002573]),
+        ("csrw mtvec, a0",                      &[0x30551073]),
+    ]);
+    BENCH.check("rv64i", &[
+        ("li a0, x'12345678",                   &[0x12345537, 0x6785051B]),
+        ("li a0, x'123456789",                  &[0x00092537, 0xA2B5051B, 0x00D51513, 0x78950513]),
+        ("li a0, x'7FFFFFFF",                   &[0x80000537, 0xFFF5051B]),
+        ("sext.w a0, a1",                       &[0x0005851B]),
+    ]);
+}
+
+#[test]
+fn relocation_operators() {
+    BENCH.check("rv32i", &[
+        ("lui a0, %hi(x'12345FFF)",             &[0x12346537]),
+        ("addi a0, a0, %lo(x'12345FFF)",        &[0xFFF50513]),
+        ("sw a1, [a0 + %lo(x'12345FFF)]",       &[0xFEB52FA3]),
+        ("auipc a0, %pcrel_hi(x'1800)",         &[0x00002517]),
+        ("nop\nauipc a0, %pcrel_hi(x'1804)\naddi a0, a0, %pcrel_lo(x'1804)",
+            &[0x00000013, 0x00002517, 0x80050513]),
+    ]);
+}
+
+#[test]
+fn compressed() {
+    BENCH.check("rv32imac", &[
+        ("nop",                                 &[0x0001]),
+        ("ebreak",                              &[0x9002]),
+        ("addi a0, a0, 1",                      &[0x0505]),
+        ("addi sp, sp, -16",                    &[0x1141]),
+        ("addi sp, sp, 64",                     &[0x6121]),
+        ("addi a0, sp, 16",                     &[0x0808]),
+        ("li a0, 1",                            &[0x4505]),
+        ("lui a0, 1",                           &[0x6505]),
+        ("mv a0, a1",                           &[0x852E]),
+        ("add a0, a0, a1",                      &[0x952E]),
+        ("sub a0, a0, a1",                      &[0x8D0D]),
+        ("andi a0, a0, 15",                     &[0x893D]),
+        ("slli a0, a0, 3",                      &[0x050E]),
+        ("srli a0, a0, 3",                      &[0x810D]),
+        ("lw a0, [sp + 12]",                    &[0x4532]),
+        ("sw ra, [sp + 12]",                    &[0xC606]),
+        ("lw a0, [a1 + 4]",                     &[0x41C8]),
+        ("ret",                                 &[0x8082]),
+        ("jalr a0",                             &[0x9502]),
+        ("j 0",                                 &[0xA001]),
+        ("jal 0",                               &[0x2001]),
+        ("beqz a0, 8",                          &[0xC501]),
+        ("li a0, x'12345678",                   &[0x12345537, 0x67850513]),
+        ("addi a0, a1, 100",                    &[0x06458513]),
+        ("lw a0, [a1 + 2]",                     &[0x0025A503]),
+        ("c.addi a0, 1",                        &[0x0505]),
+        ("c.li a0, -1",                         &[0x557D]),
+        ("c.mv a0, a1",                         &[0x852E]),
+        ("c.j 0",                               &[0xA001]),
+        ("c.jr ra",                             &[0x8082]),
+        ("c.beqz a0, 8",                        &[0xC501]),
+        ("c.lwsp a0, [sp + 12]",                &[0x4532]),
+        ("auipc a0, %pcrel_hi(table)\nlw a1, [a0 + %pcrel_lo(table)]\nmv a3, a4\nnop\n\
+          table: .int32 1",
+            &[0x00000517, 0x00C52583, 0x86BA, 0x0001, 0x0001, 0x0000]),
+        ("li a0, end\nend:",                     &[0x4509]),
+        ("s: li a0, e - s + 30\ne:",              &[0x02200513]),
+        ("s: li a0, e - s + 2044\ne:",            &[0x00001537, 0x80450513]),
+    ]);
+    BENCH.check("rv64imac", &[
+        ("ld a0, [sp + 8]",                     &[0x6522]),
+        ("sd ra, [sp + 8]",                     &[0xE406]),
+        ("addiw a0, a0, 1",                     &[0x2505]),
+        ("jal 0",                               &[0x000000EF]),
+        ("slli a0, a0, 40",                     &[0x1522]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("rv32imac").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  call func
+                la   a0, data
+                j    start
+                .section .text2
+        func:   ret
+        data:   .int32 0
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    let text = &program.sections[0];
+    assert_eq!(insns(&text.data), [0x00000097, 0x012080E7, 0x00000517, 0x00C50513, 0xBFC5]);
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x80).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("rv64im", &[
+            "lui a0, x'12345",
+            "auipc t0, x'FFFFF",
+            "jal ra, start",
+            "jalr zero, a0, 4",
+            "bgeu s0, s1, start",
+            "ld a0, [sp + 8]",
+            "sb zero, [s0 - 1]",
+            "addi a0, a1, -x'800",
+            "srai a0, a0, x'3F",
+            "sraiw a0, a0, x'1F",
+            "subw a0, a1, a2",
+            "fence",
+            "ecall",
+            "csrrw a0, x'300, a1",
+            "csrrsi zero, x'300, 8",
+            "mulhsu t0, t1, t2",
+            "remuw a0, a1, a2",
+        ][..]),
+        ("rv64imac", &[
+            "lr.w.aq a0, [a1]",
+            "amoadd.d.aqrl a0, a1, [a2]",
+            "c.addi4spn s0, sp, x'10",
+            "c.ld a0, [a1 + 8]",
+            "c.sw a5, [s1 + x'7C]",
+            "c.nop",
+            "c.addi a0, -1",
+            "c.addiw a0, 1",
+            "c.li a0, 5",
+            "c.addi16sp sp, -x'40",
+            "c.lui a0, -1",
+            "c.srai a0, x'28",
+            "c.andi s0, x'F",
+            "c.subw a0, a1",
+            "c.j start",
+            "c.bnez a0, start",
+            "c.slli a0, 3",
+            "c.ldsp ra, [sp + x'1F8]",
+            "c.jr ra",
+            "c.mv a0, a1",
+            "c.ebreak",
+            "c.jalr a0",
+            "c.add a0, a1",
+            "c.sdsp ra, [sp + 8]",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'80"));
+            let decoded    = decoder.decode(&bytes, 0, &label).unwrap();
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let rv32i = (find("rv32i").unwrap().new)(&mut NameTable::new());
+    assert_eq!(rv32i.decode(&[0x05, 0x05], 0, &label), None);
+    assert_eq!(rv32i.decode(&[0x33, 0x05, 0xB5, 0x02], 0, &label), None);
+
+    // Reserved encodings do not decode
+    assert_eq!(rv32i.decode(&[0x00, 0x00, 0x00, 0x00], 0, &label), None);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("rv32i", "addi a0, a1, 2048"),   "value 2048 out of range for 12-bit signed immediate");
+    assert_eq!(BENCH.error("rv32i", "slli a0, a0, 32"),     "value 32 out of range 0..31");
+    assert_eq!(BENCH.error("rv32i", "beq a0, a1, 5"),       "branch offset 5 is odd");
+    assert_eq!(BENCH.error("rv32i", "beq a0, a1, 4096"),    "branch offset 4096 out of range");
+    assert_eq!(BENCH.error("rv32i", "lui a0, %lo(1)"),      "%lo not valid for this operand");
+    assert_eq!(BENCH.error("rv32i", "addi a0, a0, %foo(1)"), "unknown relocation operator '%foo'");
+    assert_eq!(BENCH.error("rv32i", "add a0, a1"),          "invalid operands");
+    assert_eq!(BENCH.error("rv32i", "lw a0, [8]"),          "expected: base register");
+    assert_eq!(BENCH.error("rv32i", "li a0, x'100000000"),  "value 4294967296 does not fit in 32 bits");
+    assert_eq!(BENCH.error("rv32i", "fence rx, w"),         "expected: fence set such as 'rw'");
+    assert_eq!(BENCH.error("rv32i", "mul a0, a1, a2"),      "unknown instruction 'mul'");
+    assert_eq!(BENCH.error("rv32i", "ld a0, [a1]"),         "unknown instruction 'ld'");
+    assert_eq!(BENCH.error("rv32imac", "c.addi a0, 100"),   "operands not valid for compressed instruction");
+}
@@ -0,0 +1,224 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Table-driven instruction decoding.
+
+use crate::target::{number, Decoded};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction that [`Table::decode_fields`] decodes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Fields {
+    /// Index of the form of the instruction, as for [`Table::mnemonic`].
+    pub form: usize,
+
+    /// Size of the instruction in bytes.
+    pub size: usize,
+
+    /// Values of the fields of the form, in order of appearance: a register
+    /// number, an integer, or a target address.
+    pub values: Vec<i64>,
+}
+
+impl Table {
+    /// Decodes the instruction at the beginning of `bytes`, which lies at
+    /// address `addr`, choosing among the forms that the instruction set
+    /// features `isa` allow.  `label` returns the name by which to refer to
+    /// an address, if any.
+    pub fn decode(
+        &self,
+        isa:   u32,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        let insn = self.decode_fields(isa, bytes, addr)?;
+        let form = &self.forms[insn.form];
+
+        let mut targets = vec![];
+
+        let values = form.slots
+            .iter()
+            .zip(&insn.values)
+            .map(|(slot, &value)| match self.spec.fields[slot.field].kind {
+                Kind::Reg { .. } => self.spec.registers[value as usize].to_string(),
+                Kind::Int { .. } => number(value),
+                Kind::Rel { .. } | Kind::Abs { .. } => {
+                    targets.push(value as u64);
+                    label(value as u64).unwrap_or_else(|| number(value))
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let mut values = values.into_iter();
+        let operands   = form.operands
+            .iter()
+            .map(|pat| render(pat, &mut values))
+            .collect::<Vec<_>>();
+
+        let text = match operands.is_empty() {
+            true  => form.name.to_string(),
+            false => format!("{} {}", form.name, operands.join(", ")),
+        };
+
+        Some(Decoded { text, size: form.size, targets })
+    }
+
+    /// Decodes the instruction at the beginning of `bytes`, which lies at
+    /// address `addr`, as for [`Table::decode`], and returns its form and the
+    /// values of its fields.
+    pub fn decode_fields(&self, isa: u32, bytes: &[u8], addr: u64) -> Option<Fields> {
+        self.forms
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| !f.alias && has(isa, f.isa) && f.size <= bytes.len())
+            .find_map(|(i, f)| {
+                let bits = self.spec.load_bits(&bytes[..f.size]);
+                match bits & f.mask == f.bits {
+                    true  => self.decode_form(f, bits, addr).map(|values| {
+                        Fields { form: i, size: f.size, values }
+                    }),
+                    false => None,
+                }
+            })
+    }
+
+    /// Returns the mnemonic of the form with the given index.  The decoder
+    /// names a form by its first mnemonic.
+    pub fn mnemonic(&self, form: usize) -> &'static str {
+        self.forms[form].name
+    }
+
+    /// Returns the number of forms.
+    pub fn form_count(&self) -> usize {
+        self.forms.len()
+    }
+
+    /// Returns the names of the fields of the form with the given index, in
+    /// the order of the values that [`Table::decode_fields`] returns.
+    pub fn fields(&self, form: usize) -> Vec<&'static str> {
+        self.forms[form].slots.iter().map(|s| self.spec.fields[s.field].name).collect()
+    }
+
+    /// Returns the identifiers that the operand template of the form with the
+    /// given index requires as written, including those in memory operands.
+    pub fn words(&self, form: usize) -> Vec<&'static str> {
+        let mut words = vec![];
+        for pat in &self.forms[form].operands {
+            match *pat {
+                Pat::Word(w) => words.push(w),
+                Pat::Mem(ref terms, _) => term_words(terms, &mut words),
+                Pat::Field(_)          => (),
+            }
+        }
+        words
+    }
+
+    fn decode_form(&self, form: &Form, bits: u64, addr: u64) -> Option<Vec<i64>> {
+        let mut values = vec![];
+
+        for slot in &form.slots {
+            let field = &self.spec.fields[slot.field];
+            let width = slot.positions[0].len();
+
+            // Every letter of the field must hold the same value
+            let raw = gather(bits, &slot.positions[0]);
+            if slot.positions[1..].iter().any(|p| gather(bits, p) != raw) {
+                return None;
+            }
+
+            let value = match field.kind {
+                Kind::Reg { min, max, step } => {
+                    let n = raw as u16 * step + min;
+                    let name = self.spec.registers.get(n as usize).copied().unwrap_or("");
+                    if n > max || name.is_empty() {
+                        return None;
+                    }
+                    n as i64
+                },
+                Kind::Int { min, max, invert } => {
+                    let raw   = (raw ^ invert) & (u64::MAX >> (64 - width));
+                    let value = match min < 0 && max < 1 << (width - 1) {
+                        true  => sign_extend(raw, width),
+                        false => raw as i64,
+                    };
+                    if !(min..=max).contains(&value) {
+                        return None;
+                    }
+                    value
+                },
+                Kind::Rel { origin, scale } => {
+                    let offset = sign_extend(raw, width) * scale + origin;
+                    addr.wrapping_add(offset as u64) as i64
+                },
+                Kind::Abs { scale } => {
+                    (raw * scale as u64) as i64
+                },
+            };
+
+            values.push(value);
+        }
+
+        Some(values)
+    }
+}
+
+/// Returns the text of an operand pattern, taking the text of its fields
+/// from `values`.
+fn render(pat: &Pat, values: &mut impl Iterator<Item = String>) -> String {
+    match *pat {
+        Pat::Field(_)             => values.next().unwrap_or_default(),
+        Pat::Word(w)              => w.to_string(),
+        Pat::Mem(ref terms, post) => render_mem(terms, post, values),
+    }
+}
+
+/// Returns the text of a memory operand with the given terms, followed by
+/// `!` if `post` is true, taking the text of its fields from `values`.
+fn render_mem(terms: &[Term], post: bool, values: &mut impl Iterator<Item = String>) -> String {
+    let mut text = String::from("[");
+    for (i, term) in terms.iter().enumerate() {
+        let term = match *term {
+            Term::Field(_)     => values.next().unwrap_or_default(),
+            Term::Word(w)      => w.to_string(),
+            Term::PreDec(w)    => format!("--{}", w),
+            Term::Mem(ref sub) => render_mem(sub, false, values),
+        };
+        match term.strip_prefix('-') {
+            Some(t) if i > 0 && !t.starts_with('-') => { text += " - "; text += t },
+            _ if i > 0                              => { text += " + "; text += &term },
+            _                                       => text += &term,
+        }
+    }
+    text += if post { "]!" } else { "]" };
+    text
+}
+
+/// Appends the identifiers in the given memory operand terms to `words`.
+fn term_words(terms: &[Term], words: &mut Vec<&'static str>) {
+    for term in terms {
+        match *term {
+            Term::Word(w)                    => words.push(w),
+            Term::Mem(ref sub)               => term_words(sub, words),
+            Term::Field(_) | Term::PreDec(_) => (),
+        }
+    }
+}
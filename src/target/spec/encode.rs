@@ -0,0 +1,331 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Table-driven instruction encoding.
+
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Value that matching an operand binds to a field.
+#[derive(Clone, Debug)]
+enum Bound {
+    /// Register number.
+    Reg(u16),
+
+    /// Expression.
+    Expr(Expr<Span>),
+}
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.  `insn` is an index returned by [`Table::instruction`], and
+/// `isa` and `target` are the instruction set features and name of the
+/// target.
+pub fn encode(
+    table:  &Table,
+    isa:    u32,
+    target: &str,
+    insn:   usize,
+    stmt:   &Dir<Span>,
+    out:    &mut dyn Emitter,
+) {
+    let span     = stmt.data;
+    let mnemonic = &table.mnemonics[insn];
+
+    if !mnemonic.forms.iter().any(|&i| has(isa, table.forms[i].isa)) {
+        let msg = format!("instruction '{}' is not available on {}", mnemonic.name, target);
+        return out.error(span, &msg);
+    }
+
+    let args = stmt.args
+        .iter()
+        .map(|arg| match *arg {
+            Arg::Expr(ref e) => Some(e),
+            Arg::Unknown(span) => {
+                out.error(span, "expected: operand");
+                None
+            },
+        })
+        .collect::<Option<Vec<_>>>();
+
+    let args = match args {
+        Some(args) => args,
+        None       => return,
+    };
+
+    let mut matcher = Matcher { spec: table.spec, out };
+    let mut error   = None;
+
+    for &i in &mnemonic.forms {
+        let form  = &table.forms[i];
+        let binds = match matcher.form(form, &args) {
+            Some(binds) => binds,
+            None        => continue,
+        };
+        let result = match has(isa, form.isa) {
+            true  => matcher.check(form, &binds),
+            false => Err(format!("instruction form is not available on {}", target)),
+        };
+        match result {
+            Ok(())   => return matcher.emit(form, &binds, span),
+            Err(msg) => { error.get_or_insert(msg); },
+        }
+    }
+
+    let msg = error.unwrap_or_else(|| {
+        // Blame a memory operand where the forms expect one
+        let mem = args.iter().enumerate().any(|(j, arg)| {
+            matches!(arg, Expr::Deref(..)) && mnemonic.forms.iter().any(|&i| {
+                let ops = &table.forms[i].operands;
+                ops.len() == args.len() && matches!(ops[j], Pat::Mem(..))
+            })
+        });
+        match mem {
+            true  => "invalid memory operand".into(),
+            false => "invalid operands".into(),
+        }
+    });
+
+    matcher.out.error(span, &msg);
+}
+
+/// Matcher of operands to instruction forms.
+struct Matcher<'a> {
+    spec: &'static Spec,
+    out:  &'a mut dyn Emitter,
+}
+
+impl Matcher<'_> {
+    /// Matches the given operands to the given form.  Returns the values of
+    /// the fields of the form, in order of appearance, if the operands have
+    /// the shape of the form.
+    fn form(&mut self, form: &Form, args: &[&Expr<Span>]) -> Option<Vec<Bound>> {
+        if args.len() != form.operands.len() {
+            return None;
+        }
+
+        let mut binds = vec![];
+
+        for (pat, &arg) in form.operands.iter().zip(args) {
+            match *pat {
+                Pat::Field(f) => binds.push(self.bind(f, arg)?),
+                Pat::Word(w)  => if !self.is_word(arg, w) { return None },
+                Pat::Mem(ref terms, post) => match *arg {
+                    Expr::Deref(_, ref inner, p) if p == post => self.mem(terms, inner, &mut binds)?,
+                    _ => return None,
+                },
+            }
+        }
+
+        Some(binds)
+    }
+
+    /// Matches the contents of a memory operand to the given terms.
+    fn mem(&mut self, terms: &[Term], inner: &Expr<Span>, binds: &mut Vec<Bound>) -> Option<()> {
+        if let [Term::PreDec(w)] = *terms {
+            return match *inner {
+                Expr::Unary(_, UnOp::PreDec, ref e) if self.is_word(e, w) => Some(()),
+                _                                                         => None,
+            };
+        }
+
+        let mut rest = vec![];
+        flatten_sum(inner, false, &mut rest);
+
+        let mut bound = vec![vec![]; terms.len()];
+        let mut value = None;
+
+        for (i, term) in terms.iter().enumerate() {
+            let found = match *term {
+                Term::Word(w) => rest.iter().position(|&(neg, e)| !neg && self.is_word(e, w)),
+                Term::Field(f) => match self.spec.fields[f].kind {
+                    Kind::Reg { .. } => rest.iter().position(|&(neg, e)| !neg && self.register(e).is_some()),
+                    _ => {
+                        value = Some(i);
+                        continue;
+                    },
+                },
+                Term::PreDec(_) => None,
+                Term::Mem(ref sub) => rest.iter().position(|&(neg, e)| match *e {
+                    Expr::Deref(_, ref inner, false) if !neg => {
+                        self.mem(sub, inner, &mut bound[i]).is_some()
+                    },
+                    _ => false,
+                }),
+            };
+            let (_, e) = rest.remove(found?);
+            if let Some(r) = self.register(e) {
+                bound[i].push(Bound::Reg(r));
+            }
+        }
+
+        if rest.iter().any(|&(_, e)| self.register(e).is_some() || matches!(e, Expr::Deref(..))) {
+            return None;
+        }
+
+        match value {
+            Some(i) => bound[i].push(Bound::Expr(sum(&rest)?)),
+            None if rest.is_empty() => (),
+            None => return None,
+        }
+
+        binds.extend(bound.into_iter().flatten());
+        Some(())
+    }
+
+    /// Matches an operand to the field with index `f`.
+    fn bind(&mut self, f: usize, arg: &Expr<Span>) -> Option<Bound> {
+        match (self.spec.fields[f].kind, self.register(arg)) {
+            (Kind::Reg { .. }, Some(r)) => Some(Bound::Reg(r)),
+            (Kind::Reg { .. }, None)    => None,
+            (_,                Some(_)) => None,
+            (_,                None)    => match *arg {
+                Expr::Deref(..) => None,
+                _               => Some(Bound::Expr(arg.clone())),
+            },
+        }
+    }
+
+    /// Returns whether the given expression is the identifier `word`.
+    fn is_word(&self, expr: &Expr<Span>, word: &str) -> bool {
+        match *expr {
+            Expr::Ident(_, name) => self.out.names()[name].eq_ignore_ascii_case(word),
+            _                    => false,
+        }
+    }
+
+    /// Returns the number of the register that the given expression names,
+    /// if any.
+    fn register(&self, expr: &Expr<Span>) -> Option<u16> {
+        let name = match *expr {
+            Expr::Ident(_, name) => &self.out.names()[name],
+            _                    => return None,
+        };
+        self.spec.registers
+            .iter()
+            .position(|r| r.eq_ignore_ascii_case(name))
+            .map(|n| n as u16)
+    }
+
+    /// Checks that the registers bound to the fields of the given form are
+    /// among those that the fields allow.
+    fn check(&self, form: &Form, binds: &[Bound]) -> Result<(), String> {
+        for (slot, bound) in form.slots.iter().zip(binds) {
+            let field = &self.spec.fields[slot.field];
+            if let (Kind::Reg { min, max, step }, &Bound::Reg(r)) = (field.kind, bound) {
+                if r < min || r > max || (r - min) % step != 0 {
+                    return Err(field.what.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits the given form with the values bound to its fields.
+    fn emit(&mut self, form: &Form, binds: &[Bound], span: Span) {
+        let mut bits   = form.bits;
+        let mut relocs = vec![];
+
+        for (slot, bound) in form.slots.iter().zip(binds) {
+            let field = &self.spec.fields[slot.field];
+
+            let expr = match (field.kind, bound) {
+                (Kind::Reg { min, step, .. }, &Bound::Reg(r)) => {
+                    self.store(&mut bits, slot, ((r - min) / step) as u64);
+                    continue;
+                },
+                (_, Bound::Expr(e)) => e,
+                _                   => continue,
+            };
+
+            let value = match (field.kind, self.out.eval(expr)) {
+                (_, None) => continue,
+                (Kind::Rel { origin, .. }, Some(v)) => pc_rel_value(self.out, span, v, origin),
+                (_, Some(v)) => v,
+            };
+
+            let v = match value {
+                Value::Const(v) => v,
+                Value::Reloc(_) => {
+                    match field.reloc {
+                        Some(kind) => relocs.push((slot.offset, reloc_expr(field.kind, expr), kind)),
+                        None       => self.out.error(*expr.data(), "expected: constant expression"),
+                    }
+                    continue;
+                },
+            };
+
+            let width = slot.positions[0].len();
+            let limit = 1i64 << (width - 1);
+
+            let encoded = match field.kind {
+                Kind::Int { min, max, invert } => {
+                    (min..=max).contains(&v).then_some(v as u64 ^ invert)
+                },
+                Kind::Rel { scale, .. } => {
+                    (v % scale == 0 && (-limit..limit).contains(&(v / scale))).then_some((v / scale) as u64)
+                },
+                Kind::Abs { scale } => {
+                    (v % scale == 0 && (0..limit * 2).contains(&(v / scale))).then_some((v / scale) as u64)
+                },
+                Kind::Reg { .. } => None,
+            };
+
+            if !encoded.is_some_and(|e| self.store(&mut bits, slot, e)) {
+                self.out.error(*expr.data(), &format!("{} {} out of range", field.what, v));
+            }
+        }
+
+        let bytes = self.spec.store_bits(bits, form.size);
+
+        let mut at = 0;
+        relocs.sort_by_key(|&(offset, _, _)| offset);
+
+        for (offset, expr, kind) in relocs {
+            self.out.emit(&bytes[at..offset]);
+            self.out.reloc(&expr, kind);
+            at = offset;
+        }
+
+        self.out.emit(&bytes[at..]);
+    }
+
+    /// Stores the encoded value of a field into the instruction bits.
+    /// Returns `false` if the value has a bit set that the field omits.
+    fn store(&self, bits: &mut u64, slot: &Slot, value: u64) -> bool {
+        let width = slot.positions[0].len();
+        let value = value & (u64::MAX >> (64 - width));
+        slot.positions.iter().fold(true, |ok, positions| scatter(bits, positions, value) & ok)
+    }
+}
+
+/// Returns the expression for the linker to compute for a field of the given
+/// kind that holds the value of `expr`.
+fn reloc_expr(kind: Kind, expr: &Expr<Span>) -> Expr<Span> {
+    let span = *expr.data();
+    match kind {
+        Kind::Rel { origin, .. } => pc_rel_expr(expr, origin),
+        Kind::Int { invert, .. } if invert != 0 => Expr::Binary(
+            span, BinOp::BitXor, Box::new(Expr::Int(span, invert)), Box::new(expr.clone())
+        ),
+        _ => expr.clone(),
+    }
+}
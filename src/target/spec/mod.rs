@@ -0,0 +1,563 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Declarative instruction set descriptions.
+//!
+//! A target whose instructions are words of bit fields can describe its
+//! instruction set with a [`Spec`] instead of a hand-written encoder.  From
+//! the spec, a [`Table`] derives both the encoder and a decoder.  A target
+//! whose encoder must do more than the spec can say, such as expanding
+//! pseudo-instructions, may keep its own encoder and use only the decoder.
+//!
+//! The AVR targets derive both from a spec.  The RISC-V, 6502, and Z80
+//! targets decode with one and keep their own encoders.  The MSP430, 68000,
+//! Thumb, and x86 targets decode by hand: their operands are addressing modes
+//! whose extension words, prefixes, and sizes depend on one another, which a
+//! spec could only list as a form for each combination.
+//!
+//! Each [`Row`] of a spec gives one form of one or more mnemonics: an operand
+//! template, an encoding pattern, and the instruction set features that the
+//! form requires.
+//!
+//! An operand template lists the operands of the form, separated by commas.
+//! An identifier that names a [`Field`] of the spec is a placeholder for the
+//! value of that field; any other identifier must appear as written, without
+//! regard to case.  Memory operands `[a + b]`, `[a]!`, and `[--a]` have their
+//! usual ras meanings, and a memory operand may nest within another, as in
+//! `[[a] + b]`.  For example, `Rd, [y + q]` matches `r16, [y + 5]`.
+//!
+//! An encoding pattern gives the bits of the instruction, most significant
+//! first.  `0` and `1` are fixed bits, a letter is a bit of the fields with
+//! that letter, and `_` and spaces are ignored.  A field fills the positions
+//! of its letter from its most significant bit to its least, unless the field
+//! gives another order.  For example, `1110_KKKK_dddd_KKKK` places the high 4
+//! bits of the `K` field in bits 11-8 and the low 4 bits in bits 3-0.  An
+//! instruction of several words stores them in the word order of the spec.
+
+use std::collections::HashMap;
+
+use crate::asm::{Endian, RelocKind};
+use crate::name::{Name, NameTable};
+
+mod decode;
+mod encode;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::decode::Fields;
+pub use self::encode::encode;
+
+// ----------------------------------------------------------------------------
+
+/// Declarative description of an instruction set.
+#[derive(Clone, Copy, Debug)]
+pub struct Spec {
+    /// Size of an instruction word in bytes.
+    pub word: usize,
+
+    /// Byte order of instruction words.
+    pub endian: Endian,
+
+    /// Order in memory of the words of an instruction of several words:
+    /// [`Endian::Big`] for the most significant word first.
+    pub word_order: Endian,
+
+    /// Register names, in order of their numbers.  An empty name is a number
+    /// that names no register, which a register field does not decode.
+    pub registers: &'static [&'static str],
+
+    /// Operand fields.
+    pub fields: &'static [Field],
+
+    /// Instruction forms.
+    pub rows: &'static [Row],
+}
+
+/// Operand field of an instruction.
+#[derive(Clone, Copy, Debug)]
+pub struct Field {
+    /// Name of the placeholder for the field in operand templates.
+    pub name: &'static str,
+
+    /// Letters of the field in encoding patterns.  If there are several,
+    /// each receives the full value of the field.
+    pub letters: &'static str,
+
+    /// Kind of value that the field holds.
+    pub kind: Kind,
+
+    /// Bits of the encoded value that the positions of each letter hold,
+    /// from the first position to the last, as in `12|10:5`: bit 12, then
+    /// bits 10 through 5.  The encoded value must be zero in the bits that the
+    /// order omits.  If empty, the positions hold the bits from the most
+    /// significant to the least.
+    pub order: &'static str,
+
+    /// For a register field, the error message for a register outside the
+    /// allowed ones.  For any other field, the name of the value in the
+    /// error message for a value out of range.
+    pub what: &'static str,
+
+    /// Kind of relocation by which the linker completes the field, if the
+    /// field can hold a value that only the linker knows.
+    pub reloc: Option<RelocKind>,
+}
+
+/// Kinds of values that fields hold.
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    /// Register from `min` to `max`, every `step` registers, encoded as
+    /// `(n - min) / step`.
+    Reg { min: u16, max: u16, step: u16 },
+
+    /// Integer from `min` to `max`, encoded after an exclusive OR with
+    /// `invert`.
+    Int { min: i64, max: i64, invert: u64 },
+
+    /// Target address, encoded as the offset from `origin` bytes after the
+    /// start of the instruction, divided by `scale`.
+    Rel { origin: i64, scale: i64 },
+
+    /// Target address, encoded divided by `scale`.
+    Abs { scale: i64 },
+}
+
+/// Form of one or more instructions.
+#[derive(Clone, Copy, Debug)]
+pub struct Row {
+    /// Mnemonics, separated by spaces.  The decoder uses the first.
+    pub names: &'static str,
+
+    /// Operand template.
+    pub operands: &'static str,
+
+    /// Encoding pattern.
+    pub encoding: &'static str,
+
+    /// Instruction set features that the form requires.
+    pub isa: u32,
+
+    /// Whether the decoder should prefer another form with the same
+    /// encoding.
+    pub alias: bool,
+}
+
+/// Returns a [`Row`] with the given mnemonics, operand template, encoding
+/// pattern, and required instruction set features.
+pub const fn row(names: &'static str, operands: &'static str, encoding: &'static str, isa: u32) -> Row {
+    Row { names, operands, encoding, isa, alias: false }
+}
+
+/// Returns a [`Row`] as for [`row`], but which the decoder does not use.
+pub const fn alias(names: &'static str, operands: &'static str, encoding: &'static str, isa: u32) -> Row {
+    Row { names, operands, encoding, isa, alias: true }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Encoder and decoder derived from a [`Spec`].
+#[derive(Debug)]
+pub struct Table {
+    spec:      &'static Spec,
+    forms:     Vec<Form>,
+    mnemonics: Vec<Mnemonic>,
+    index:     HashMap<Name, usize>,
+}
+
+/// Mnemonic and its forms.
+#[derive(Debug)]
+struct Mnemonic {
+    name:  &'static str,
+    forms: Vec<usize>,
+}
+
+/// Instruction form compiled from a [`Row`].
+#[derive(Debug)]
+struct Form {
+    name:     &'static str,
+    isa:      u32,
+    alias:    bool,
+    operands: Vec<Pat>,
+    bits:     u64,
+    mask:     u64,
+    size:     usize,
+    slots:    Vec<Slot>,
+}
+
+/// Operand pattern.
+#[derive(Clone, Debug)]
+enum Pat {
+    /// Placeholder for the field with the given index.
+    Field(usize),
+
+    /// Identifier.
+    Word(&'static str),
+
+    /// Memory operand with the given terms, followed by `!` if the flag is
+    /// true.
+    Mem(Vec<Term>, bool),
+}
+
+/// Term of a memory operand pattern.
+#[derive(Clone, Debug)]
+enum Term {
+    /// Placeholder for the field with the given index.
+    Field(usize),
+
+    /// Identifier.
+    Word(&'static str),
+
+    /// Pre-decremented identifier: `--x`.
+    PreDec(&'static str),
+
+    /// Memory operand within a memory operand, as in `[[a] + b]`.
+    Mem(Vec<Term>),
+}
+
+/// Bit positions of a field in an encoding.
+#[derive(Debug)]
+struct Slot {
+    /// Index of the field.
+    field: usize,
+
+    /// For each letter of the field, the position of each bit of the encoded
+    /// value, most significant first, or `None` for a bit that must be zero.
+    positions: Vec<Vec<Option<u8>>>,
+
+    /// Offset in bytes of the first word that holds part of the field.
+    offset: usize,
+}
+
+impl Table {
+    /// Compiles the given `spec`, interning its mnemonics in `names`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spec is malformed.
+    pub fn new(spec: &'static Spec, names: &mut NameTable) -> Self {
+        let mut forms     = vec![];
+        let mut mnemonics = vec![];
+        let mut index     = HashMap::new();
+
+        for row in spec.rows {
+            let i    = forms.len();
+            let form = Form::new(spec, row);
+
+            for name in row.names.split_whitespace() {
+                let m = *index.entry(names.add(name)).or_insert_with(|| {
+                    mnemonics.push(Mnemonic { name, forms: vec![] });
+                    mnemonics.len() - 1
+                });
+                index.insert(names.add(&name.to_uppercase()), m);
+                mnemonics[m].forms.push(i);
+            }
+
+            forms.push(form);
+        }
+
+        Self { spec, forms, mnemonics, index }
+    }
+
+    /// Looks up the mnemonic with the given `name`.  Returns an index for
+    /// [`encode`], or `None` if the spec has no such mnemonic.
+    pub fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    /// Looks up the register with the given `name`.  Returns its number, or
+    /// `None` if the specification has no such register.
+    pub fn register(&self, name: &str) -> Option<usize> {
+        self.spec.registers.iter().position(|r| r.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Form {
+    fn new(spec: &Spec, row: &Row) -> Self {
+        let fail = |msg: &str| -> ! {
+            panic!("{} {}: {}", row.names, row.operands, msg)
+        };
+
+        let operands = parse_operands(spec, row.operands).unwrap_or_else(|| fail("invalid template"));
+
+        // Encoding pattern
+        let chars = row.encoding
+            .chars()
+            .filter(|&c| c != '_' && c != ' ')
+            .collect::<Vec<_>>();
+
+        let len = chars.len();
+        if len == 0 || len > 64 || len % (spec.word * 8) != 0 {
+            fail("invalid encoding length");
+        }
+
+        let mut bits    = 0;
+        let mut mask    = 0;
+        let mut letters = HashMap::<char, Vec<u8>>::new();
+
+        for (i, &c) in chars.iter().enumerate() {
+            let pos = (len - 1 - i) as u8;
+            match c {
+                '0' => mask |= 1 << pos,
+                '1' => { mask |= 1 << pos; bits |= 1 << pos },
+                c if c.is_ascii_alphabetic() => letters.entry(c).or_default().push(pos),
+                _ => fail("invalid encoding character"),
+            }
+        }
+
+        // Field positions
+        let mut slots = vec![];
+
+        for field in fields(&operands) {
+            let order     = spec.fields[field].order;
+            let positions = spec.fields[field].letters
+                .chars()
+                .map(|c| letters.remove(&c).unwrap_or_else(|| fail("field letter not in encoding")))
+                .map(|p| arrange(order, p).unwrap_or_else(|| fail("invalid bit order")))
+                .collect::<Vec<_>>();
+
+            // Offset of the first word in memory with a bit of the field
+            let count  = len / (spec.word * 8);
+            let offset = positions
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|&pos| {
+                    let word = count - 1 - pos as usize / (spec.word * 8);
+                    match spec.word_order {
+                        Endian::Big    => word,
+                        Endian::Little => count - 1 - word,
+                    }
+                })
+                .min()
+                .unwrap_or(0) * spec.word;
+
+            slots.push(Slot { field, positions, offset });
+        }
+
+        if !letters.is_empty() {
+            fail("encoding letter without field");
+        }
+
+        let name = row.names.split_whitespace().next().unwrap_or_else(|| fail("no mnemonic"));
+
+        Self { name, isa: row.isa, alias: row.alias, operands, bits, mask, size: len / 8, slots }
+    }
+}
+
+impl Spec {
+    /// Returns the bytes in memory of an instruction of `size` bytes with the
+    /// given bits.
+    fn store_bits(&self, bits: u64, size: usize) -> Vec<u8> {
+        let count = size / self.word;
+        let mut bytes = Vec::with_capacity(size);
+
+        for i in 0..count {
+            let i    = match self.word_order { Endian::Big => count - 1 - i, Endian::Little => i };
+            let word = bits >> (i * self.word * 8);
+            match self.endian {
+                Endian::Little => bytes.extend_from_slice(&word.to_le_bytes()[..self.word]),
+                Endian::Big    => bytes.extend_from_slice(&word.to_be_bytes()[8 - self.word..]),
+            }
+        }
+
+        bytes
+    }
+
+    /// Returns the bits of the instruction with the given bytes in memory.
+    fn load_bits(&self, bytes: &[u8]) -> u64 {
+        let word = |chunk: &[u8]| match self.endian {
+            Endian::Little => chunk.iter().rev().fold(0, |w, &b| w << 8 | b as u64),
+            Endian::Big    => chunk.iter()      .fold(0, |w, &b| w << 8 | b as u64),
+        };
+        let shift = self.word * 8;
+        match self.word_order {
+            Endian::Big    => bytes.chunks(self.word)      .fold(0, |acc, c| acc << shift | word(c)),
+            Endian::Little => bytes.chunks(self.word).rev().fold(0, |acc, c| acc << shift | word(c)),
+        }
+    }
+}
+
+/// Returns whether the instruction set features `isa` include all of the
+/// features `required`.
+fn has(isa: u32, required: u32) -> bool {
+    isa & required == required
+}
+
+/// Stores `value` into the bits of `bits` at the given positions, most
+/// significant first.  Returns `false` if `value` has a bit set where there
+/// is no position.
+fn scatter(bits: &mut u64, positions: &[Option<u8>], value: u64) -> bool {
+    let n = positions.len();
+    let mut ok = true;
+    for (i, pos) in positions.iter().enumerate() {
+        let bit = value >> (n - 1 - i) & 1;
+        match *pos {
+            Some(pos) => *bits = *bits & !(1 << pos) | bit << pos,
+            None      => ok &= bit == 0,
+        }
+    }
+    ok
+}
+
+/// Returns the value stored in the bits of `bits` at the given positions,
+/// most significant first, with zero where there is no position.
+fn gather(bits: u64, positions: &[Option<u8>]) -> u64 {
+    positions.iter().fold(0, |acc, pos| acc << 1 | pos.map_or(0, |pos| bits >> pos & 1))
+}
+
+/// Arranges the bit `positions` of a letter, in encoding order, by the bits
+/// of the encoded value that a field `order` assigns to them.  Returns the
+/// position of each bit of the value, most significant first, or `None` if
+/// the order is malformed.
+fn arrange(order: &str, positions: Vec<u8>) -> Option<Vec<Option<u8>>> {
+    if order.is_empty() {
+        return Some(positions.into_iter().map(Some).collect());
+    }
+
+    let mut bits = vec![];
+
+    for range in order.split('|') {
+        let (hi, lo) = range.split_once(':').unwrap_or((range, range));
+        let (hi, lo) = (hi.trim().parse::<u8>().ok()?, lo.trim().parse::<u8>().ok()?);
+        if lo > hi || hi >= 64 {
+            return None;
+        }
+        bits.extend((lo..=hi).rev());
+    }
+
+    let width = *bits.iter().max()? as usize + 1;
+    let mut arranged = vec![None; width];
+
+    if bits.len() != positions.len() {
+        return None;
+    }
+
+    for (bit, pos) in bits.into_iter().zip(positions) {
+        let slot = &mut arranged[width - 1 - bit as usize];
+        if slot.replace(pos).is_some() {
+            return None;
+        }
+    }
+
+    Some(arranged)
+}
+
+/// Returns the given `bits`-bit value sign-extended.
+fn sign_extend(value: u64, bits: usize) -> i64 {
+    let shift = 64 - bits as u32;
+    (value << shift) as i64 >> shift
+}
+
+/// Returns the indexes of the fields in the given operand patterns, in
+/// order of appearance.
+fn fields(operands: &[Pat]) -> Vec<usize> {
+    let mut fields = vec![];
+    for pat in operands {
+        match *pat {
+            Pat::Field(f)          => fields.push(f),
+            Pat::Mem(ref terms, _) => term_fields(terms, &mut fields),
+            Pat::Word(_)           => (),
+        }
+    }
+    fields
+}
+
+/// Appends the indexes of the fields in the given memory operand terms to
+/// `fields`, in order of appearance.
+fn term_fields(terms: &[Term], fields: &mut Vec<usize>) {
+    for term in terms {
+        match *term {
+            Term::Field(f)                  => fields.push(f),
+            Term::Mem(ref sub)              => term_fields(sub, fields),
+            Term::Word(_) | Term::PreDec(_) => (),
+        }
+    }
+}
+
+/// Parses an operand template.
+fn parse_operands(spec: &Spec, template: &'static str) -> Option<Vec<Pat>> {
+    let template = template.trim();
+    if template.is_empty() {
+        return Some(vec![]);
+    }
+
+    template.split(',').map(|op| {
+        let op = op.trim();
+
+        if let Some(rest) = op.strip_prefix('[') {
+            let (inner, post) = match rest.strip_suffix("]!") {
+                Some(inner) => (inner, true),
+                None        => (rest.strip_suffix(']')?, false),
+            };
+            return Some(Pat::Mem(parse_terms(spec, inner)?, post));
+        }
+
+        if !ident(op) {
+            return None;
+        }
+        Some(match field(spec, op) {
+            Some(f) => Pat::Field(f),
+            None    => Pat::Word(op),
+        })
+    }).collect()
+}
+
+/// Parses the terms of a memory operand template, between its brackets.
+fn parse_terms(spec: &Spec, inner: &'static str) -> Option<Vec<Term>> {
+    // Split at each + outside of a nested memory operand
+    let mut terms = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in inner.char_indices() {
+        match c {
+            '['               => depth += 1,
+            ']'               => depth -= 1,
+            '+' if depth == 0 => { terms.push(&inner[start..i]); start = i + 1 },
+            _                 => (),
+        }
+    }
+    terms.push(&inner[start..]);
+
+    terms.into_iter().map(|term| {
+        let term = term.trim();
+        if let Some(sub) = term.strip_prefix('[') {
+            return Some(Term::Mem(parse_terms(spec, sub.strip_suffix(']')?)?));
+        }
+        match term.strip_prefix("--") {
+            Some(w) if ident(w) => Some(Term::PreDec(w)),
+            Some(_)             => None,
+            None if !ident(term) => None,
+            None => Some(match field(spec, term) {
+                Some(f) => Term::Field(f),
+                None    => Term::Word(term),
+            }),
+        }
+    }).collect()
+}
+
+/// Returns the index of the field of `spec` with the given name, if any.
+fn field(spec: &Spec, name: &str) -> Option<usize> {
+    spec.fields.iter().position(|f| f.name == name)
+}
+
+/// Returns whether the given text is an identifier.
+fn ident(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,296 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Specification tests.
+
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+use crate::session::Session;
+use crate::target::{Decoded, Emitter, Target, TargetInfo};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Specification of a toy instruction set with big-endian 16-bit words.
+static SPEC: Spec = Spec {
+    word:       2,
+    endian:     Endian::Big,
+    word_order: Endian::Big,
+    registers:  &["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"],
+    fields:     &[
+        Field { name: "Rd", letters: "d", kind: Kind::Reg { min: 0, max: 7, step: 1 }, order: "", what: "", reloc: None },
+        Field { name: "Rs", letters: "s", kind: Kind::Reg { min: 0, max: 7, step: 1 }, order: "", what: "", reloc: None },
+        Field { name: "Re", letters: "d", kind: Kind::Reg { min: 0, max: 6, step: 2 }, order: "", what: "register must be even", reloc: None },
+        Field { name: "I",  letters: "i", kind: Kind::Int { min: -8, max: 7, invert: 0 }, order: "", what: "immediate", reloc: None },
+        Field { name: "J",  letters: "j", kind: Kind::Rel { origin: 2, scale: 2 }, order: "", what: "offset", reloc: None },
+        Field { name: "L",  letters: "l", kind: Kind::Abs { scale: 1 }, order: "", what: "address", reloc: None },
+        Field { name: "K",  letters: "k", kind: Kind::Int { min: 0, max: 30, invert: 0 }, order: "1|4:2", what: "offset", reloc: None },
+    ],
+    rows:       &[
+        row  ("mov",  "Rd, Rs",         "0001_0000_0ddd_0sss",                     BASE),
+        alias("nop",  "",               "0001_0000_0000_0000",                     BASE),
+        row  ("addi", "Rd, I",          "0010_0ddd_0000_iiii",                     BASE),
+        row  ("ld",   "Rd, [Rs + I]",   "0011_0ddd_isss_iiii",                     BASE),
+        row  ("push", "[--sp], Rs",     "0100_0000_0000_0sss",                     BASE),
+        row  ("pop",  "Rd, [sp]!",      "0101_0000_0000_0ddd",                     BASE),
+        row  ("swap", "Re",             "0110_0000_0ddd_0000",                     EXT),
+        row  ("jr",   "J",              "0111_jjjj_jjjj_jjjj",                     BASE),
+        row  ("jmp",  "L",              "1000_0000_0000_0000 llll_llll_llll_llll", EXT),
+        row  ("ldk",  "Rd, [sp + K]",   "1001_0ddd_0000_kkkk",                     BASE),
+        row  ("ldn",  "Rd, [[Rs] + I]", "1010_0ddd_isss_iiii",                     BASE),
+    ],
+};
+
+const BASE: u32 = 1 << 0;
+const EXT:  u32 = 1 << 1;
+
+#[derive(Debug)]
+struct Toy {
+    isa:   u32,
+    table: Table,
+}
+
+impl Target for Toy {
+    fn name(&self) -> &'static str {
+        "toy"
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Big
+    }
+
+    fn align(&self) -> u64 {
+        2
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.table.instruction(name)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        encode(&self.table, self.isa, "toy", insn, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        self.table.decode(self.isa, bytes, addr, label)
+    }
+}
+
+static TOY: TargetInfo = TargetInfo {
+    name:        "toy",
+    description: "toy",
+    new:         |names| Rc::new(Toy { isa: BASE, table: Table::new(&SPEC, names) }),
+};
+
+/// Assembles the given `line` at address `x'100` and returns the resulting
+/// bytes.
+fn assemble(line: &str) -> (Vec<u8>, Session) {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(&TOY);
+
+    let source = format!(".org x'100\n{}\n", line);
+    let unit   = session.assemble("test.s", &source);
+
+    (unit.sections[0].data.clone(), session)
+}
+
+fn check(cases: &[(&str, &[u8])]) {
+    for &(line, expected) in cases {
+        let (bytes, session) = assemble(line);
+        let errors = session.diagnostics().iter().map(|d| &d.msg).collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{}: {:?}", line, errors);
+        assert_eq!(bytes, expected, "{}", line);
+    }
+}
+
+fn error(line: &str) -> String {
+    let (_, session) = assemble(line);
+    assert_eq!(session.error_count(), 1, "{}", line);
+    session.diagnostics()[0].msg.clone()
+}
+
+fn decode(isa: u32, bytes: &[u8]) -> Option<Decoded> {
+    let table = Table::new(&SPEC, &mut NameTable::new());
+    let label = |addr| (addr == 0x100).then(|| "start".to_string());
+    table.decode(isa, bytes, 0x100, &label)
+}
+
+#[test]
+fn encoding() {
+    check(&[
+        ("mov r1, r2",                          &[0x10, 0x12]),
+        ("MOV R7, r0",                          &[0x10, 0x70]),
+        ("nop",                                 &[0x10, 0x00]),
+        ("addi r3, -1",                         &[0x23, 0x0F]),
+        ("addi r3, 7",                          &[0x23, 0x07]),
+        ("ld r1, [r2 + 5]",                     &[0x31, 0x25]),
+        ("ld r1, [-8 + r2]",                    &[0x31, 0xA8]),
+        ("push [--sp], r4",                     &[0x40, 0x04]),
+        ("pop r4, [SP]!",                       &[0x50, 0x04]),
+        ("jr x'100",                            &[0x7F, 0xFF]),
+        ("jr next\nnext:",                      &[0x70, 0x00]),
+        ("ldk r1, [sp + 6]",                    &[0x91, 0x09]),
+        ("ldk r1, [sp + 20]",                   &[0x91, 0x05]),
+        ("ldn r1, [[r2] + 5]",                  &[0xA1, 0x25]),
+        ("ldn r1, [-1 + [r2]]",                 &[0xA1, 0xAF]),
+    ]);
+}
+
+#[test]
+fn decoding() {
+    let cases: &[(&[u8], &str)] = &[
+        (&[0x10, 0x12],                         "mov r1, r2"),
+        (&[0x10, 0x00],                         "mov r0, r0"),
+        (&[0x23, 0x0F],                         "addi r3, -1"),
+        (&[0x31, 0xA8],                         "ld r1, [r2 - 8]"),
+        (&[0x40, 0x04],                         "push [--sp], r4"),
+        (&[0x50, 0x04],                         "pop r4, [sp]!"),
+        (&[0x91, 0x09],                         "ldk r1, [sp + 6]"),
+        (&[0xA1, 0x25],                         "ldn r1, [[r2] + 5]"),
+    ];
+    for &(bytes, expected) in cases {
+        let decoded = decode(BASE, bytes).unwrap();
+        assert_eq!(decoded.text, expected);
+        assert_eq!(decoded.size, 2);
+        assert!(decoded.targets.is_empty());
+    }
+
+    let decoded = decode(BASE, &[0x7F, 0xFF]).unwrap();
+    assert_eq!(decoded.text,    "jr start");
+    assert_eq!(decoded.targets, [0x100]);
+
+    let decoded = decode(BASE, &[0x70, 0x10]).unwrap();
+    assert_eq!(decoded.text,    "jr x'122");
+    assert_eq!(decoded.targets, [0x122]);
+
+    let decoded = decode(EXT, &[0x80, 0x00, 0x12, 0x34]).unwrap();
+    assert_eq!(decoded.text,    "jmp x'1234");
+    assert_eq!(decoded.size,    4);
+
+    // Unavailable forms, truncated input, and unknown bits
+    assert_eq!(decode(BASE, &[0x80, 0x00, 0x12, 0x34]), None);
+    assert_eq!(decode(EXT,  &[0x80, 0x00]),             None);
+    assert_eq!(decode(BASE, &[0xFF, 0xFF]),             None);
+
+    // Register fields outside the allowed registers
+    assert_eq!(decode(EXT, &[0x60, 0x10]).unwrap().text, "swap r2");
+    assert_eq!(decode(EXT, &[0x60, 0x70]),               None);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("jmp x'1234"),         "instruction 'jmp' is not available on toy");
+    assert_eq!(error("addi r3, 8"),         "immediate 8 out of range");
+    assert_eq!(error("addi 3, r3"),         "invalid operands");
+    assert_eq!(error("ld r1, [r2 + r3]"),   "invalid memory operand");
+    assert_eq!(error("pop r4, [sp]"),       "invalid memory operand");
+    assert_eq!(error("jr x'101"),           "offset -1 out of range");
+    assert_eq!(error("jr x'1200"),          "offset 4350 out of range");
+    assert_eq!(error("ldk r1, [sp + 7]"),   "offset 7 out of range");
+    assert_eq!(error("ldk r1, [sp + 32]"),  "offset 32 out of range");
+    assert_eq!(error("ldn r1, [r2 + 5]"),   "invalid memory operand");
+    assert_eq!(error("ldn r1, [[r2 + 5]]"), "invalid memory operand");
+}
+
+#[test]
+fn word_order() {
+    static LITTLE: Spec = Spec {
+        word:       2,
+        endian:     Endian::Little,
+        word_order: Endian::Little,
+        registers:  &[],
+        fields:     &[
+            Field { name: "L", letters: "l", kind: Kind::Abs { scale: 1 }, order: "", what: "address", reloc: None },
+        ],
+        rows:       &[
+            row("far",  "L", "llll_llll_llll_llll 0000_0000_0000_0001", BASE),
+            row("near", "",  "0000_0000_0000_0010",                     BASE),
+        ],
+    };
+
+    let table = Table::new(&LITTLE, &mut NameTable::new());
+
+    let decoded = table.decode(BASE, &[0x01, 0x00, 0x34, 0x12], 0, &|_| None).unwrap();
+    assert_eq!(decoded.text, "far x'1234");
+    assert_eq!(decoded.size, 4);
+
+    let insn = table.decode_fields(BASE, &[0x02, 0x00, 0x34, 0x12], 0).unwrap();
+    assert_eq!(table.mnemonic(insn.form), "near");
+    assert_eq!(insn.size, 2);
+    assert_eq!(LITTLE.store_bits(0x1234_0001, 4), [0x01, 0x00, 0x34, 0x12]);
+}
+
+#[test]
+fn nested() {
+    static NESTED: Spec = Spec {
+        word:       1,
+        endian:     Endian::Little,
+        word_order: Endian::Big,
+        registers:  &["a", "b", "", "d"],
+        fields:     &[
+            Field { name: "R", letters: "r", kind: Kind::Reg { min: 0, max: 3, step: 1 }, order: "", what: "", reloc: None },
+            Field { name: "Z", letters: "z", kind: Kind::Int { min: 0, max: 255, invert: 0 }, order: "", what: "address", reloc: None },
+        ],
+        rows:       &[
+            row("ldx", "R, [[Z + x]]", "0000_00rr zzzz_zzzz", BASE),
+            row("ldy", "R, [[Z] + y]", "0000_01rr zzzz_zzzz", BASE),
+        ],
+    };
+
+    let table = Table::new(&NESTED, &mut NameTable::new());
+
+    let decoded = table.decode(BASE, &[0x01, 0x10], 0, &|_| None).unwrap();
+    assert_eq!(decoded.text, "ldx b, [[x'10 + x]]");
+    let decoded = table.decode(BASE, &[0x07, 0x10], 0, &|_| None).unwrap();
+    assert_eq!(decoded.text, "ldy d, [[x'10] + y]");
+    assert_eq!(table.words(1), ["y"]);
+
+    // A number without a register name does not decode
+    assert_eq!(table.decode(BASE, &[0x02, 0x10], 0, &|_| None), None);
+}
+
+#[test]
+#[should_panic(expected = "field letter not in encoding")]
+fn malformed() {
+    static BAD: Spec = Spec {
+        word:       2,
+        endian:     Endian::Big,
+        word_order: Endian::Big,
+        registers:  &[],
+        fields:     &[
+            Field { name: "I", letters: "i", kind: Kind::Int { min: 0, max: 7, invert: 0 }, order: "", what: "", reloc: None },
+        ],
+        rows:       &[row("bad", "I", "0000_0000_0000_0000", BASE)],
+    };
+
+    Table::new(&BAD, &mut NameTable::new());
+}
@@ -0,0 +1,94 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers for target encoding tests.
+
+use std::fmt::Debug;
+
+use crate::session::Session;
+use crate::target::find;
+
+// ----------------------------------------------------------------------------
+
+/// Test bench that assembles single lines for a target and compares the
+/// resulting code with expected instruction words.
+pub struct Bench<W> {
+    /// Address at which each line is assembled.
+    pub origin: u64,
+
+    /// Splits code bytes into instruction words.
+    pub split: fn(&[u8]) -> Vec<W>,
+}
+
+impl<W: PartialEq + Debug> Bench<W> {
+    /// Assembles the given `line` for the given target at the origin of the
+    /// bench and returns the resulting bytes.
+    pub fn assemble(&self, target: &str, line: &str) -> (Vec<u8>, Session) {
+        let mut session = Session::new();
+        session.set_quiet(true);
+        session.set_target(find(target).unwrap());
+        let source = format!(".org {}\n{}\n", self.origin, line);
+        let unit   = session.assemble("test.s", &source);
+        (unit.sections[0].data.clone(), session)
+    }
+
+    /// Splits the given bytes into instruction words.
+    pub fn words(&self, bytes: &[u8]) -> Vec<W> {
+        (self.split)(bytes)
+    }
+
+    /// Asserts that each line of `cases` assembles without diagnostics to the
+    /// expected words.
+    pub fn check(&self, target: &str, cases: &[(&str, &[W])]) {
+        for &(line, expected) in cases {
+            let (bytes, session) = self.assemble(target, line);
+            let errors = session.diagnostics().iter().map(|d| &d.msg).collect::<Vec<_>>();
+            assert!(errors.is_empty(), "{}: {:?}", line, errors);
+            assert_eq!(self.words(&bytes), expected, "{}", line);
+        }
+    }
+
+    /// Asserts that the given `line` reports exactly one error and returns
+    /// its message.
+    pub fn error(&self, target: &str, line: &str) -> String {
+        let (_, session) = self.assemble(target, line);
+        assert_eq!(session.error_count(), 1, "{}", line);
+        session.diagnostics()[0].msg.clone()
+    }
+}
+
+/// Returns the given bytes unchanged.
+pub fn bytes(bytes: &[u8]) -> Vec<u8> {
+    bytes.to_vec()
+}
+
+/// Splits big-endian bytes into 16-bit words.
+pub fn be16(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+        .collect()
+}
+
+/// Splits little-endian bytes into 16-bit words.
+pub fn le16(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+        .collect()
+}
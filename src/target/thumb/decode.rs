@@ -0,0 +1,881 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Instruction decoding.
+//!
+//! An IT instruction decodes together with the instructions of its block,
+//! which take their condition suffixes from it.  Otherwise, the decoder
+//! assumes that an instruction lies outside any IT block, so a 16-bit
+//! instruction that would set the flags there has the `s` suffix.  A 32-bit
+//! instruction has the `.w` suffix if the encoder would otherwise choose a
+//! 16-bit form for its operands, as it always has in an IT block.
+
+use crate::target::{number, Decoded};
+
+use super::encode::{modimm, SPECIAL};
+use super::operand::{LR, PC, SP};
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Register names, by number.
+const REGS: [&str; 16] = [
+    "r0", "r1", "r2",  "r3",  "r4",  "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "sp", "lr", "pc",
+];
+
+/// Mnemonics of the 16-bit data processing instructions, by opcode.
+const ALU: [&str; 16] = [
+    "ands", "eors", "lsls", "lsrs", "asrs", "adcs", "sbcs", "rors",
+    "tst",  "negs", "cmp",  "cmn",  "orrs", "muls", "bics", "mvns",
+];
+
+/// Mnemonics of the 32-bit data processing instructions, by opcode.
+const DP: [Option<&str>; 16] = [
+    Some("and"), Some("bic"), Some("orr"), Some("orn"),
+    Some("eor"), None,        None,        None,
+    Some("add"), None,        Some("adc"), Some("sbc"),
+    None,        Some("sub"), Some("rsb"), None,
+];
+
+/// Mnemonics of the shift instructions, by shift type.
+const SHIFTS: [&str; 4] = ["lsl", "lsr", "asr", "ror"];
+
+/// Decodes the instruction at the beginning of `bytes`, which lies at
+/// address `addr`.  `label` returns the name by which to refer to an
+/// address, if any.  If `v7` is true, decodes ARMv7-M instructions.
+pub fn decode(
+    v7:    bool,
+    bytes: &[u8],
+    addr:  u64,
+    label: &dyn Fn(u64) -> Option<String>,
+) -> Option<Decoded> {
+    let mut targets = vec![];
+    let mut refer   = |target: u64| {
+        let target = target & 0xFFFF_FFFF;
+        targets.push(target);
+        label(target).unwrap_or_else(|| number(target as i64))
+    };
+
+    let (mut text, mut size) = single(v7, bytes, addr, &mut refer)?;
+
+    if text.starts_with("it") {
+        for cond in it_conds(halfword(bytes, 0)?) {
+            let (insn, n) = single(v7, bytes.get(size..)?, addr + size as u64, &mut refer)?;
+            text.push('\n');
+            text.push_str(&conditional(&insn, n, cond)?);
+            size += n;
+        }
+    }
+
+    Some(Decoded { text, size, targets })
+}
+
+/// Decodes the single instruction at the beginning of `bytes`, returning
+/// its text and size.
+fn single(
+    v7:    bool,
+    bytes: &[u8],
+    addr:  u64,
+    refer: &mut dyn FnMut(u64) -> String,
+) -> Option<(String, usize)> {
+    let hw1 = halfword(bytes, 0)?;
+
+    match hw1 >> 11 {
+        0x1D..=0x1F => Some((wide(v7, hw1, halfword(bytes, 2)?, addr, refer)?, 4)),
+        _           => Some((narrow(v7, hw1, addr, refer)?, 2)),
+    }
+}
+
+/// Returns the text of the instruction with the given text and size in an
+/// IT block with the given condition, or `None` if the instruction is not
+/// permitted there.
+fn conditional(text: &str, size: usize, cond: u8) -> Option<String> {
+    let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+    let name                 = mnemonic.trim_end_matches(".w");
+
+    // Conditional branches and IT instructions have conditions of their own
+    if name.starts_with("it") || name.starts_with('b') && cond_code(&name[1..]).is_some() {
+        return None;
+    }
+
+    // A 16-bit instruction sets the flags only outside an IT block, and the
+    // encoder prefers a 16-bit form inside one
+    let (name, width) = match size {
+        2 => (name.strip_suffix('s').filter(|&n| sets_flags(n)).unwrap_or(name), ""),
+        _ => (name, ".w"),
+    };
+
+    let text = format!("{}{}{} {}", name, cond_name(cond), width, operands);
+    Some(text.trim_end().to_string())
+}
+
+/// Returns whether the instruction with the given mnemonic takes the `s`
+/// suffix.
+fn sets_flags(name: &str) -> bool {
+    ENTRIES.iter().any(|e| e.name == name && e.flags & S != 0)
+}
+
+/// Decodes a 16-bit instruction.
+fn narrow(v7: bool, hw: u16, addr: u64, refer: &mut dyn FnMut(u64) -> String) -> Option<String> {
+    let reg  = |at: u16| REGS[(hw >> at & 7) as usize];
+    let imm8 = hw & 0xFF;
+
+    let text = match hw >> 12 {
+        0x0 | 0x1 => match hw >> 11 & 3 {
+            0 if hw >> 6 & 31 == 0 => format!("movs {}, {}", reg(0), reg(3)),
+            3 => {
+                let op = ["adds", "subs"][(hw >> 9 & 1) as usize];
+                match hw >> 10 & 1 {
+                    0 => format!("{} {}, {}, {}", op, reg(0), reg(3), reg(6)),
+                    _ => format!("{} {}, {}, {}", op, reg(0), reg(3), hw >> 6 & 7),
+                }
+            },
+            shift => {
+                let n = match hw >> 6 & 31 {
+                    0 => 32,
+                    n => n,
+                };
+                format!("{}s {}, {}, {}", SHIFTS[shift as usize], reg(0), reg(3), n)
+            },
+        },
+        0x2 | 0x3 => match hw >> 11 & 3 {
+            0 => format!("movs {}, {}", reg(8), number(imm8 as i64)),
+            1 => format!("cmp {}, {}", reg(8), number(imm8 as i64)),
+            // The encoder takes a 3-bit immediate when it fits
+            _ if imm8 <= 7 => return None,
+            op => {
+                let op = ["adds", "subs"][op as usize & 1];
+                format!("{} {}, {}", op, reg(8), number(imm8 as i64))
+            },
+        },
+        0x4 => match hw >> 10 & 3 {
+            0 => format!("{} {}, {}", ALU[(hw >> 6 & 15) as usize], reg(0), reg(3)),
+            1 => {
+                let rdn = REGS[((hw >> 4 & 8) | hw & 7) as usize];
+                let rm  = REGS[(hw >> 3 & 15) as usize];
+                match hw >> 8 & 3 {
+                    0 => format!("add {}, {}", rdn, rm),
+                    1 if hw & 0x00C0 == 0 => return None,
+                    1 => format!("cmp {}, {}", rdn, rm),
+                    2 => format!("mov {}, {}", rdn, rm),
+                    _ if hw & 7 != 0 => return None,
+                    _ => format!("{} {}", ["bx", "blx"][(hw >> 7 & 1) as usize], rm),
+                }
+            },
+            _ => {
+                let target = ((addr + 4) & !3) + imm8 as u64 * 4;
+                format!("ldr {}, {}", reg(8), number(target as i64))
+            },
+        },
+        0x5 => {
+            let op = ["str", "strh", "strb", "ldrsb", "ldr", "ldrh", "ldrb", "ldrsh"];
+            format!("{} {}, [{} + {}]", op[(hw >> 9 & 7) as usize], reg(0), reg(3), reg(6))
+        },
+        0x6..=0x8 => {
+            let (op, scale) = match hw >> 12 {
+                0x6 => (["str",  "ldr" ], 4),
+                0x7 => (["strb", "ldrb"], 1),
+                _   => (["strh", "ldrh"], 2),
+            };
+            let offset = (hw >> 6 & 31) as i64 * scale;
+            format!("{} {}, {}", op[(hw >> 11 & 1) as usize], reg(0), mem(reg(3), offset))
+        },
+        0x9 => {
+            let op = ["str", "ldr"][(hw >> 11 & 1) as usize];
+            format!("{} {}, {}", op, reg(8), mem("sp", imm8 as i64 * 4))
+        },
+        0xA => match hw >> 11 & 1 {
+            0 => {
+                let target = ((addr + 4) & !3) + imm8 as u64 * 4;
+                format!("adr {}, {}", reg(8), number(target as i64))
+            },
+            _ => format!("add {}, sp, {}", reg(8), number(imm8 as i64 * 4)),
+        },
+        0xB => misc(v7, hw, addr, refer)?,
+        0xC => {
+            let (rn, list) = (hw >> 8 & 7, hw & 0xFF);
+            match (hw >> 11 & 1, list & 1 << rn) {
+                _ if list == 0 => return None,
+                (0, _) => format!("stm [{}]!, {}", reg(8), reg_list(list)),
+                (_, 0) => format!("ldm [{}]!, {}", reg(8), reg_list(list)),
+                (_, _) => format!("ldm [{}], {}", reg(8), reg_list(list)),
+            }
+        },
+        0xD => match hw >> 8 & 15 {
+            14   => format!("udf {}", number(imm8 as i64)),
+            15   => format!("svc {}", number(imm8 as i64)),
+            cond => {
+                let offset = imm8 as i8 as i64 * 2;
+                let target = refer(addr.wrapping_add(4).wrapping_add(offset as u64));
+                format!("b{} {}", cond_name(cond as u8), target)
+            },
+        },
+        0xE if hw & 0x0800 == 0 => {
+            let offset = sign_extend(hw as u32 & 0x7FF, 11) * 2;
+            format!("b {}", refer(addr.wrapping_add(4).wrapping_add(offset as u64)))
+        },
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes a 16-bit miscellaneous instruction.
+fn misc(v7: bool, hw: u16, addr: u64, refer: &mut dyn FnMut(u64) -> String) -> Option<String> {
+    let (rd, rm) = (REGS[(hw & 7) as usize], REGS[(hw >> 3 & 7) as usize]);
+    let imm8     = hw & 0xFF;
+
+    let text = match hw >> 8 & 15 {
+        0x0 => {
+            let op = ["add", "sub"][(hw >> 7 & 1) as usize];
+            format!("{} sp, {}", op, number((hw & 0x7F) as i64 * 4))
+        },
+        0x1 | 0x3 | 0x9 | 0xB if v7 => {
+            let op     = ["cbz", "cbnz"][(hw >> 11 & 1) as usize];
+            let offset = (hw >> 3 & 0x40 | hw >> 2 & 0x3E) as u64;
+            format!("{} {}, {}", op, rd, refer(addr + 4 + offset))
+        },
+        0x2 => {
+            let op = ["sxth", "sxtb", "uxth", "uxtb"][(hw >> 6 & 3) as usize];
+            format!("{} {}, {}", op, rd, rm)
+        },
+        0x4 | 0x5 | 0xC | 0xD => {
+            let pop        = hw >> 11 & 1 != 0;
+            let (op, high) = if pop { ("pop", PC) } else { ("push", LR) };
+            let list       = imm8 | (hw >> 8 & 1) << high;
+            match list {
+                0 => return None,
+                _ => format!("{} {}", op, reg_list(list)),
+            }
+        },
+        0x6 if hw & 0x00EC == 0x0060 && hw & 3 != 0 => {
+            let op = ["cpsie", "cpsid"][(hw >> 4 & 1) as usize];
+            format!("{} {}", op, ["", "f", "i", "if"][(hw & 3) as usize])
+        },
+        0xA if hw >> 6 & 3 != 2 => {
+            let op = ["rev", "rev16", "", "revsh"][(hw >> 6 & 3) as usize];
+            format!("{} {}, {}", op, rd, rm)
+        },
+        0xE => format!("bkpt {}", number(imm8 as i64)),
+        0xF if hw & 0xF == 0 => match hw >> 4 & 15 {
+            hint @ 0..=4 => ["nop", "yield", "wfe", "wfi", "sev"][hint as usize].to_string(),
+            _            => return None,
+        },
+        0xF if v7 => it(hw)?,
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes an IT instruction.
+fn it(hw: u16) -> Option<String> {
+    let conds = it_conds(hw);
+    let first = conds[0];
+
+    let pattern = conds[1..]
+        .iter()
+        .map(|&c| if c == first { 't' } else { 'e' })
+        .collect::<String>();
+
+    if first == 15 || first == 14 && pattern.contains('e') {
+        return None;
+    }
+
+    Some(format!("it{} {}", pattern, cond_name(first)))
+}
+
+/// Returns the conditions of the instructions in the block of the given IT
+/// instruction, in order.
+fn it_conds(hw: u16) -> Vec<u8> {
+    let (first, mask) = ((hw >> 4 & 15) as u8, hw & 15);
+    let count         = 3 - mask.trailing_zeros();
+
+    let rest = (0..count).map(|i| match (mask >> (3 - i) & 1) as u8 == first & 1 {
+        true  => first,
+        false => first ^ 1,
+    });
+
+    [first].into_iter().chain(rest).collect()
+}
+
+/// Decodes a 32-bit instruction.
+fn wide(
+    v7:    bool,
+    hw1:   u16,
+    hw2:   u16,
+    addr:  u64,
+    refer: &mut dyn FnMut(u64) -> String,
+) -> Option<String> {
+    let rn = hw1 & 15;
+    let rd = hw2 >> 8 & 15;
+    let rt = hw2 >> 12;
+
+    // Branches and miscellaneous control, some of which ARMv6-M has
+    if hw1 & 0xF800 == 0xF000 && hw2 & 0x8000 != 0 {
+        return control(v7, hw1, hw2, addr, refer);
+    }
+
+    if !v7 {
+        return None;
+    }
+
+    let text = match hw1 >> 9 {
+        // Load and store multiple, dual, and exclusive, and table branch
+        0x74 => match hw1 & 0xFFF0 {
+            0xE840 => {
+                let offset = (hw2 & 0xFF) as i64 * 4;
+                format!("strex {}, {}, {}", REGS[rd as usize], REGS[rt as usize], mem(REGS[rn as usize], offset))
+            },
+            0xE850 if hw2 & 0x0F00 == 0x0F00 => {
+                let offset = (hw2 & 0xFF) as i64 * 4;
+                format!("ldrex {}, {}", REGS[rt as usize], mem(REGS[rn as usize], offset))
+            },
+            0xE8D0 if hw2 & 0xFFE0 == 0xF000 => match hw2 >> 4 & 1 {
+                0 => format!("tbb [{} + {}]", REGS[rn as usize], REGS[(hw2 & 15) as usize]),
+                _ => format!("tbh [{} + {}*2]", REGS[rn as usize], REGS[(hw2 & 15) as usize]),
+            },
+            _ if hw1 & 0x0140 == 0x0140 => dual(hw1, hw2)?,
+            _ if hw1 & 0x0040 == 0      => multiple(hw1, hw2)?,
+            _                           => return None,
+        },
+
+        // Data processing with a shifted register
+        0x75 => dp_reg(hw1, hw2)?,
+
+        // Data processing with an immediate
+        0x78..=0x7B if hw1 & 0x0200 == 0 => dp_imm(hw1, hw2)?,
+        0x78..=0x7B                      => binary(hw1, hw2)?,
+
+        // Loads and stores
+        0x7C => load_store(hw1, hw2, addr)?,
+
+        // Register shifts, extensions, multiplies, and divides
+        0x7D => match (hw1 >> 4 & 0x1F, hw2 & 0xF0F0) {
+            (0x00..=0x07, 0xF000) => {
+                let op = SHIFTS[(hw1 >> 5 & 3) as usize];
+                let s  = if hw1 & 0x0010 != 0 { "s" } else { "" };
+                let (rd, rm) = (hw2 >> 8 & 15, hw2 & 15);
+                let w  = width(!s.is_empty() && rd == rn && rd < 8 && rm < 8);
+                format!("{}{}{} {}, {}, {}", op, s, w, REGS[rd as usize], REGS[rn as usize], REGS[rm as usize])
+            },
+            _ => unary(hw1, hw2).or_else(|| multiply(hw1, hw2))?,
+        },
+        0x7E..=0x7F => multiply(hw1, hw2)?,
+
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes a branch or miscellaneous control instruction.
+fn control(
+    v7:    bool,
+    hw1:   u16,
+    hw2:   u16,
+    addr:  u64,
+    refer: &mut dyn FnMut(u64) -> String,
+) -> Option<String> {
+    let s     = (hw1 >> 10 & 1) as u32;
+    let (j1, j2) = ((hw2 >> 13 & 1) as u32, (hw2 >> 11 & 1) as u32);
+    let imm11 = (hw2 & 0x7FF) as u32;
+
+    // Offset of an unconditional branch or call
+    let offset24 = || {
+        let (i1, i2) = (!(j1 ^ s) & 1, !(j2 ^ s) & 1);
+        let bits     = s << 24 | i1 << 23 | i2 << 22 | (hw1 as u32 & 0x3FF) << 12 | imm11 << 1;
+        sign_extend(bits, 25)
+    };
+
+    let text = match hw2 & 0xD000 {
+        0xD000 => format!("bl {}", refer(addr.wrapping_add(4).wrapping_add(offset24() as u64))),
+        0x9000 if v7 => {
+            let offset = offset24();
+            let w      = width((-2048..=2046).contains(&offset));
+            format!("b{} {}", w, refer(addr.wrapping_add(4).wrapping_add(offset as u64)))
+        },
+        0x8000 if hw1 >> 6 & 15 < 14 => {
+            if !v7 {
+                return None;
+            }
+            let cond   = (hw1 >> 6 & 15) as u8;
+            let bits   = s << 20 | j2 << 19 | j1 << 18 | (hw1 as u32 & 0x3F) << 12 | imm11 << 1;
+            let offset = sign_extend(bits, 21);
+            let w      = width((-256..=254).contains(&offset));
+            format!("b{}{} {}", cond_name(cond), w, refer(addr.wrapping_add(4).wrapping_add(offset as u64)))
+        },
+        0x8000 => match (hw1, hw2 & 0xFF00) {
+            (0xF3AF, 0x8000) if v7 => match hw2 & 0xFF {
+                hint @ 0..=4 => format!("{}.w", ["nop", "yield", "wfe", "wfi", "sev"][hint as usize]),
+                _            => return None,
+            },
+            (0xF3BF, 0x8F00) => match hw2 & 0xFF {
+                0x2F if v7 => "clrex".to_string(),
+                0x4F       => "dsb".to_string(),
+                0x5F       => "dmb".to_string(),
+                0x6F       => "isb".to_string(),
+                _          => return None,
+            },
+            (0xF3EF, _) if hw2 & 0xF000 == 0x8000 => {
+                format!("mrs {}, {}", REGS[(hw2 >> 8 & 15) as usize], special(hw2 & 0xFF)?)
+            },
+            (0xF380..=0xF38F, 0x8800) => {
+                format!("msr {}, {}", special(hw2 & 0xFF)?, REGS[(hw1 & 15) as usize])
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes a data processing instruction with a modified immediate.
+fn dp_imm(hw1: u16, hw2: u16) -> Option<String> {
+    let op    = hw1 >> 5 & 15;
+    let s     = hw1 >> 4 & 1 != 0;
+    let rn    = hw1 & 15;
+    let rd    = hw2 >> 8 & 15;
+    let imm12 = (hw1 >> 10 & 1) << 11 | (hw2 >> 12 & 7) << 8 | hw2 & 0xFF;
+    let value = expand(imm12);
+
+    // The encoder chooses the canonical encoding of each value
+    if modimm(value as i64) != Some(imm12) || hw2 & 0x8000 != 0 {
+        return None;
+    }
+
+    let v   = number(value as i64);
+    let low = |r: u16| r < 8;
+    let sfx = if s { "s" } else { "" };
+
+    let text = match (op, rn, rd) {
+        (2, PC16, _) => {
+            let w = width(s && low(rd) && value <= 255);
+            format!("mov{}{} {}, {}", sfx, w, REGS[rd as usize], v)
+        },
+        (3, PC16, _) => format!("mvn{} {}, {}", sfx, REGS[rd as usize], v),
+        (0 | 4 | 8 | 13, _, PC16) if s => {
+            let op = ["tst", "", "", "", "teq", "", "", "", "cmn", "", "", "", "", "cmp"][op as usize];
+            let w  = width(op == "cmp" && low(rn) && value <= 255);
+            format!("{}{} {}, {}", op, w, REGS[rn as usize], v)
+        },
+        (8 | 13, _, _) => {
+            let sub = op == 13;
+            let narrow = rd == SP16 && rn == SP16 && !s && value.is_multiple_of(4) && value <= 508
+                || !sub && rn == SP16 && low(rd) && !s && value.is_multiple_of(4) && value <= 1020
+                || low(rd) && low(rn) && value <= 7 && s
+                || rd == rn && low(rd) && value <= 255 && s;
+            let op = DP[op as usize]?;
+            format!("{}{}{} {}, {}, {}", op, sfx, width(narrow), REGS[rd as usize], REGS[rn as usize], v)
+        },
+        (14, _, _) => {
+            let w = width(value == 0 && low(rd) && low(rn) && s);
+            format!("rsb{}{} {}, {}, {}", sfx, w, REGS[rd as usize], REGS[rn as usize], v)
+        },
+        _ => {
+            let op = DP[op as usize]?;
+            format!("{}{} {}, {}, {}", op, sfx, REGS[rd as usize], REGS[rn as usize], v)
+        },
+    };
+
+    Some(text)
+}
+
+/// Decodes a data processing instruction with a plain binary immediate.
+fn binary(hw1: u16, hw2: u16) -> Option<String> {
+    let rn  = hw1 & 15;
+    let rd  = REGS[(hw2 >> 8 & 15) as usize];
+    let imm = (hw1 >> 10 & 1) << 11 | (hw2 >> 12 & 7) << 8 | hw2 & 0xFF;
+
+    if hw2 & 0x8000 != 0 {
+        return None;
+    }
+
+    let text = match hw1 & 0xFBF0 {
+        0xF200 => format!("addw {}, {}, {}", rd, REGS[rn as usize], number(imm as i64)),
+        0xF2A0 => format!("subw {}, {}, {}", rd, REGS[rn as usize], number(imm as i64)),
+        0xF240 | 0xF2C0 => {
+            let op  = if hw1 & 0x0080 == 0 { "movw" } else { "movt" };
+            let imm = rn << 12 | imm;
+            format!("{} {}, {}", op, rd, number(imm as i64))
+        },
+        0xF340 | 0xF3C0 if hw2 & 0x0020 == 0 => {
+            let op    = if hw1 & 0x0080 == 0 { "sbfx" } else { "ubfx" };
+            let lsb   = (hw2 >> 12 & 7) << 2 | hw2 >> 6 & 3;
+            let width = (hw2 & 31) + 1;
+            if lsb + width > 32 {
+                return None;
+            }
+            format!("{} {}, {}, {}, {}", op, rd, REGS[rn as usize], lsb, width)
+        },
+        0xF360 if hw2 & 0x0020 == 0 => {
+            let lsb = (hw2 >> 12 & 7) << 2 | hw2 >> 6 & 3;
+            let msb = hw2 & 31;
+            if msb < lsb {
+                return None;
+            }
+            match rn {
+                PC16 => format!("bfc {}, {}, {}", rd, lsb, msb - lsb + 1),
+                _    => format!("bfi {}, {}, {}, {}", rd, REGS[rn as usize], lsb, msb - lsb + 1),
+            }
+        },
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes a data processing instruction with a shifted register.
+fn dp_reg(hw1: u16, hw2: u16) -> Option<String> {
+    let op    = hw1 >> 5 & 15;
+    let s     = hw1 >> 4 & 1 != 0;
+    let rn    = hw1 & 15;
+    let rd    = hw2 >> 8 & 15;
+    let rm    = hw2 & 15;
+    let ty    = hw2 >> 4 & 3;
+    let n     = (hw2 >> 12 & 7) << 2 | hw2 >> 6 & 3;
+    let plain = ty == 0 && n == 0;
+
+    if hw2 & 0x8000 != 0 {
+        return None;
+    }
+
+    let low = |r: u16| r < 8;
+    let sfx = if s { "s" } else { "" };
+    let src = shifted(rm, ty, n)?;
+
+    let text = match (op, rn, rd) {
+        // A move with a shift is a shift
+        (2, PC16, _) if plain => {
+            let w = width(!s || low(rd) && low(rm));
+            format!("mov{}{} {}, {}", sfx, w, REGS[rd as usize], REGS[rm as usize])
+        },
+        (2, PC16, _) => {
+            let n = if n == 0 { 32 } else { n };
+            let w = width(ty != 3 && s && low(rd) && low(rm));
+            format!("{}{}{} {}, {}, {}", SHIFTS[ty as usize], sfx, w, REGS[rd as usize], REGS[rm as usize], n)
+        },
+        (3, PC16, _) => {
+            let w = width(plain && s && low(rd) && low(rm));
+            format!("mvn{}{} {}, {}", sfx, w, REGS[rd as usize], src)
+        },
+        (0 | 4 | 8 | 13, _, PC16) if s => {
+            let narrow = match op {
+                13 => plain,
+                4  => false,
+                _  => plain && low(rn) && low(rm),
+            };
+            let op = ["tst", "", "", "", "teq", "", "", "", "cmn", "", "", "", "", "cmp"][op as usize];
+            format!("{}{} {}, {}", op, width(narrow), REGS[rn as usize], src)
+        },
+        _ => {
+            let name   = DP[op as usize]?;
+            let narrow = match op {
+                8 | 13 => plain && (
+                    low(rd) && low(rn) && low(rm) && s
+                    || op == 8 && !s && (rd == rn || rd == rm)
+                ),
+                3 | 14 => false,
+                _      => plain && low(rd) && low(rn) && low(rm) && s
+                    && (rd == rn || matches!(op, 0 | 2 | 4 | 10) && rd == rm),
+            };
+            format!("{}{}{} {}, {}, {}", name, sfx, width(narrow), REGS[rd as usize], REGS[rn as usize], src)
+        },
+    };
+
+    Some(text)
+}
+
+/// Decodes a 32-bit extension or byte-reversal instruction.
+fn unary(hw1: u16, hw2: u16) -> Option<String> {
+    let (rd, rm) = (hw2 >> 8 & 15, hw2 & 15);
+
+    ENTRIES.iter().find_map(|e| match e.op {
+        Op::Unary(narrow, op1, op2) => {
+            // Extensions encode `rn` as 15; the others repeat `rm` there
+            let rn = if op1 & 0xF == 0 { rm } else { 0 };
+            (hw1 == op1 | rn && hw2 & 0xF0F0 == op2).then(|| {
+                let w = width(narrow.is_some() && rd < 8 && rm < 8);
+                format!("{}{} {}, {}", e.name, w, REGS[rd as usize], REGS[rm as usize])
+            })
+        },
+        _ => None,
+    })
+}
+
+/// Decodes a multiply or divide instruction.
+fn multiply(hw1: u16, hw2: u16) -> Option<String> {
+    let rn = REGS[(hw1 & 15) as usize];
+    let rd = REGS[(hw2 >> 8 & 15) as usize];
+    let rm = REGS[(hw2 & 15) as usize];
+    let ra = hw2 >> 12;
+
+    let text = match (hw1 & 0xFFF0, hw2 & 0x00F0) {
+        (0xFB00, 0x00) if ra == 15 => format!("mul {}, {}, {}", rd, rn, rm),
+        (0xFB00, 0x00) => format!("mla {}, {}, {}, {}", rd, rn, rm, REGS[ra as usize]),
+        (0xFB00, 0x10) => format!("mls {}, {}, {}, {}", rd, rn, rm, REGS[ra as usize]),
+        (0xFB90, 0xF0) if ra == 15 => format!("sdiv {}, {}, {}", rd, rn, rm),
+        (0xFBB0, 0xF0) if ra == 15 => format!("udiv {}, {}, {}", rd, rn, rm),
+        (op @ (0xFB80 | 0xFBA0 | 0xFBC0 | 0xFBE0), 0x00) => {
+            let name = ["smull", "umull", "smlal", "umlal"][(op >> 5 & 3) as usize];
+            format!("{} {}, {}, {}, {}", name, REGS[ra as usize], rd, rn, rm)
+        },
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decodes a 32-bit load or store of a single register.
+fn load_store(hw1: u16, hw2: u16, addr: u64) -> Option<String> {
+    let load = hw1 & 0x0010 != 0;
+    let rn   = hw1 & 15;
+    let rt   = hw2 >> 12;
+
+    let (name, size) = match (hw1 >> 8 & 1, hw1 >> 5 & 3, load) {
+        (0, 0, false) => ("strb",  Size::B),
+        (0, 1, false) => ("strh",  Size::H),
+        (0, 2, false) => ("str",   Size::W),
+        (0, 0, true)  => ("ldrb",  Size::B),
+        (0, 1, true)  => ("ldrh",  Size::H),
+        (0, 2, true)  => ("ldr",   Size::W),
+        (1, 0, true)  => ("ldrsb", Size::Sb),
+        (1, 1, true)  => ("ldrsh", Size::Sh),
+        _             => return None,
+    };
+
+    // These are preloads
+    if load && rt == 15 && size != Size::W {
+        return None;
+    }
+
+    let up   = hw1 & 0x0080 != 0;
+    let low  = rt < 8 && rn < 8;
+    let rt_s = REGS[rt as usize];
+
+    // A load from pc is a load from an address
+    if load && rn == 15 {
+        let offset = (hw2 & 0xFFF) as i64;
+        if !up && offset == 0 {
+            return None;
+        }
+        let pc     = ((addr + 4) & !3) as i64;
+        let target = if up { pc + offset } else { pc - offset };
+        let w      = width(size == Size::W && rt < 8 && up && offset % 4 == 0 && offset <= 1020);
+        return Some(format!("{}{} {}, {}", name, w, rt_s, number(target)));
+    }
+
+    let base = REGS[rn as usize];
+
+    let text = match (up, hw2 >> 8 & 15) {
+        (true, _) => {
+            let offset = (hw2 & 0xFFF) as i64;
+            let scale  = size.bytes() as i64;
+            let narrow = rn == SP16 && size == Size::W && rt < 8 && offset % 4 == 0 && offset <= 1020
+                || low && matches!(size, Size::W | Size::H | Size::B)
+                    && offset % scale == 0 && offset < 32 * scale;
+            format!("{}{} {}, {}", name, width(narrow), rt_s, mem(base, offset))
+        },
+        (false, 0x0) if hw2 & 0x00C0 == 0 => {
+            let (shift, rm) = (hw2 >> 4 & 3, hw2 & 15);
+            let index = match shift {
+                0 => REGS[rm as usize].to_string(),
+                _ => format!("{}*{}", REGS[rm as usize], 1 << shift),
+            };
+            let w = width(shift == 0 && low && rm < 8);
+            format!("{}{} {}, [{} + {}]", name, w, rt_s, base, index)
+        },
+        (false, 0xC) if hw2 & 0xFF != 0 => {
+            format!("{} {}, {}", name, rt_s, mem(base, -((hw2 & 0xFF) as i64)))
+        },
+        (false, op @ (0x9 | 0xB | 0xD | 0xF)) => {
+            let offset = match (op >> 1 & 1, hw2 & 0xFF) {
+                (1, v) => v as i64,
+                (_, 0) => return None,
+                (_, v) => -(v as i64),
+            };
+            match op >> 2 & 1 {
+                1 => format!("{} {}, {}!", name, rt_s, mem(base, offset)),
+                _ => format!("{} {}, [{}], {}", name, rt_s, base, number(offset)),
+            }
+        },
+        _ => return None,
+    };
+
+    // Pop and push of a single register are loads and stores
+    let text = match (hw1, hw2 & 0x0FFF) {
+        (0xF85D, 0x0B04) => format!("pop{} {}", width(rt < 8 || rt == PC16), rt_s),
+        (0xF84D, 0x0D04) => format!("push{} {}", width(rt < 8 || rt == LR16), rt_s),
+        _                => text,
+    };
+
+    Some(text)
+}
+
+/// Decodes a 32-bit load or store of two registers.
+fn dual(hw1: u16, hw2: u16) -> Option<String> {
+    let name   = if hw1 & 0x0010 != 0 { "ldrd" } else { "strd" };
+    let rn     = REGS[(hw1 & 15) as usize];
+    let offset = (hw2 & 0xFF) as i64 * 4;
+
+    let offset = match hw1 & 0x0080 {
+        0 if offset == 0 => return None,
+        0                => -offset,
+        _                => offset,
+    };
+
+    let wb = if hw1 & 0x0020 != 0 { "!" } else { "" };
+    Some(format!(
+        "{} {}, {}, {}{}", name, REGS[(hw2 >> 12) as usize], REGS[(hw2 >> 8 & 15) as usize],
+        mem(rn, offset), wb,
+    ))
+}
+
+/// Decodes a 32-bit load or store of multiple registers.
+fn multiple(hw1: u16, hw2: u16) -> Option<String> {
+    let load = hw1 & 0x0010 != 0;
+    let wb   = hw1 & 0x0020 != 0;
+    let rn   = hw1 & 15;
+    let list = hw2;
+
+    let db = match hw1 & 0xFF80 {
+        0xE880 => false,
+        0xE900 => true,
+        _      => return None,
+    };
+
+    if list == 0 {
+        return None;
+    }
+
+    let text = match (hw1, list.count_ones()) {
+        (0xE92D, 2..) => {
+            let w = width(list & !(0xFF | 1 << LR) == 0);
+            format!("push{} {}", w, reg_list(list))
+        },
+        (0xE8BD, 2..) => {
+            let w = width(list & !(0xFF | 1 << PC) == 0);
+            format!("pop{} {}", w, reg_list(list))
+        },
+        _ => {
+            let narrow = !db && rn < 8 && list & 0xFF00 == 0
+                && (load && wb == (list & 1 << rn == 0) || !load && wb);
+            let name = match (load, db) {
+                (true,  false) => "ldm",
+                (true,  true)  => "ldmdb",
+                (false, false) => "stm",
+                (false, true)  => "stmdb",
+            };
+            let wb = if wb { "!" } else { "" };
+            format!("{}{} [{}]{}, {}", name, width(narrow), REGS[rn as usize], wb, reg_list(list))
+        },
+    };
+
+    Some(text)
+}
+
+// ----------------------------------------------------------------------------
+
+/// Register numbers as instruction fields hold them.
+const SP16: u16 = SP as u16;
+const LR16: u16 = LR as u16;
+const PC16: u16 = PC as u16;
+
+fn halfword(bytes: &[u8], at: usize) -> Option<u16> {
+    let bytes = bytes.get(at..at + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Returns `.w` if the encoder would choose a 16-bit form without it.
+fn width(narrow: bool) -> &'static str {
+    if narrow { ".w" } else { "" }
+}
+
+/// Returns the text of a memory operand with a base register and offset.
+fn mem(base: &str, offset: i64) -> String {
+    match offset {
+        0          => format!("[{}]", base),
+        _ if offset < 0 => format!("[{} - {}]", base, number(-offset)),
+        _          => format!("[{} + {}]", base, number(offset)),
+    }
+}
+
+/// Returns the text of a register shifted by a constant amount, from the
+/// shift type and 5-bit amount fields, or `None` for a rotation through the
+/// carry flag.
+fn shifted(rm: u16, ty: u16, n: u16) -> Option<String> {
+    let rm = REGS[rm as usize];
+    let n32 = if n == 0 { 32 } else { n };
+    Some(match (ty, n) {
+        (0, 0) => rm.to_string(),
+        (0, _) => format!("{} << {}", rm, n),
+        (1, _) => format!("{} >> {}", rm, n32),
+        (2, _) => format!("asr({}, {})", rm, n32),
+        (_, 0) => return None,
+        (_, _) => format!("ror({}, {})", rm, n),
+    })
+}
+
+/// Returns the text of a register list, with a range for three or more
+/// consecutive registers.
+fn reg_list(list: u16) -> String {
+    let mut items = vec![];
+    let mut r     = 0;
+
+    while r < 16 {
+        if list & 1 << r == 0 {
+            r += 1;
+            continue;
+        }
+        let first = r;
+        while r < 16 && list & 1 << r != 0 {
+            r += 1;
+        }
+        match r - first {
+            1 => items.push(REGS[first].to_string()),
+            2 => items.extend([REGS[first].to_string(), REGS[first + 1].to_string()]),
+            _ => items.push(format!("{}-{}", REGS[first], REGS[r - 1])),
+        }
+    }
+
+    items.join("/")
+}
+
+/// Returns the name of the special register with the given `SYSm` number.
+fn special(sysm: u16) -> Option<&'static str> {
+    SPECIAL.iter().find(|s| s.1 == sysm).map(|s| s.0)
+}
+
+/// Returns the value of a 12-bit modified immediate.
+fn expand(imm12: u16) -> u32 {
+    let b = (imm12 & 0xFF) as u32;
+    match imm12 >> 8 {
+        0 => b,
+        1 => b << 16 | b,
+        2 => b << 24 | b << 8,
+        3 => b * 0x0101_0101,
+        _ => (0x80 | b & 0x7F).rotate_right((imm12 >> 7) as u32),
+    }
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    ((value << (32 - bits)) as i32 >> (32 - bits)) as i64
+}
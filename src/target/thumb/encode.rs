@@ -0,0 +1,1244 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::fits;
+use crate::lang::ast::*;
+use crate::target::{here, pc_rel_expr, pc_rel_value, Emitter, Value};
+
+use super::operand::*;
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+const INVALID: &str = "invalid operands";
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.
+pub fn encode(thumb: &Thumb, insn: Insn, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let span = stmt.data;
+    let op   = insn.entry.op;
+
+    if op == Op::Pool {
+        if !stmt.args.is_empty() {
+            out.error(span, "unexpected operands");
+        }
+        return thumb.place_pool(out);
+    }
+
+    // Consume the next condition of the current IT block, if any
+    let (it, last) = {
+        let mut state = thumb.state.borrow_mut();
+        let it        = state.it.pop();
+        (it, state.it.is_empty())
+    };
+
+    match (it, insn.cond) {
+        (Some(c), Some(d)) if c == d => (),
+        (Some(c), _) => out.error(span, &format!(
+            "instruction in IT block requires condition '{}'", cond_name(c)
+        )),
+        (None, Some(_)) if op != Op::B => {
+            out.error(span, "conditional instruction outside IT block")
+        },
+        _ => (),
+    }
+
+    if it.is_some() && op == Op::B && !last {
+        out.error(span, "branch must be the last instruction in an IT block");
+    }
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| operand(arg, out))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    let mut encoder = Encoder { thumb, out, span, insn, in_it: it.is_some(), mismatch: false };
+
+    if let Err(msg) = encoder.encode(&ops) {
+        encoder.out.error(span, &msg);
+    }
+}
+
+/// Encoder for a single instruction.
+struct Encoder<'a> {
+    thumb: &'a Thumb,
+    out:   &'a mut dyn Emitter,
+    span:  Span,
+    insn:  Insn,
+
+    /// Whether the instruction is in an IT block.
+    in_it: bool,
+
+    /// Whether a 16-bit form was rejected only because it disagrees with
+    /// the `s` suffix about setting the flags.
+    mismatch: bool,
+}
+
+/// Second operand of a data processing instruction.
+#[derive(Clone, Copy, Debug)]
+enum Src {
+    /// Immediate value.
+    Imm(i64),
+
+    /// Register with a constant shift.
+    Reg(u8, Shift, u32),
+}
+
+/// Result of an encoding step.
+type Result<T = ()> = std::result::Result<T, String>;
+
+impl Encoder<'_> {
+    fn encode(&mut self, ops: &[Operand]) -> Result {
+        use Operand::*;
+
+        match (self.insn.entry.op, ops) {
+            (Op::Add, _) => self.add_sub(false, ops),
+            (Op::Sub, _) => self.add_sub(true,  ops),
+
+            (Op::Rsb, _) => {
+                let (rd, rn, src) = self.dp_operands(ops)?;
+                self.rsb(rd, rn, src)
+            },
+            (Op::Neg, &[Reg(rd), Reg(rm)]) => self.rsb(rd, rm, Src::Imm(0)),
+
+            (Op::Logic(op, alu), _) => self.logic(op, alu, ops),
+
+            (Op::Mov, &[Reg(rd), Shifted(rm, shift, n)]) => self.shift_imm(shift, rd, rm, n),
+            (Op::Mov, &[Reg(rd), ref src]) => self.mov(rd, src),
+
+            (Op::Mvn, &[Reg(rd), ref src]) => {
+                let s = self.insn.s;
+                match self.src(src)? {
+                    Src::Imm(v) => {
+                        let wide = modimm(v).map(|imm| dp_imm(3, s, PC, rd, imm));
+                        self.choose(None, wide)
+                    },
+                    Src::Reg(rm, shift, n) => {
+                        let narrow = (n == 0 && low(rd) && low(rm) && self.ok16())
+                            .then(|| 0x43C0 | r(rm) << 3 | r(rd));
+                        let wide   = Some(dp_reg(3, s, PC, rd, rm, shift, n));
+                        self.choose(narrow, wide)
+                    },
+                }
+            },
+
+            (Op::Compare(op), &[Reg(rn), ref src]) => self.compare(op, rn, src),
+
+            (Op::Shift(shift), &[Reg(rd), Reg(rm), Imm(ref n)]) => {
+                let n = self.shift_amount(shift, n)?;
+                self.shift_imm(shift, rd, rm, n)
+            },
+            (Op::Shift(shift), &[Reg(rd), Imm(ref n)]) => {
+                let n = self.shift_amount(shift, n)?;
+                self.shift_imm(shift, rd, rd, n)
+            },
+            (Op::Shift(shift), &[Reg(rd), Reg(rn), Reg(rm)]) => self.shift_reg(shift, rd, rn, rm),
+            (Op::Shift(shift), &[Reg(rd), Reg(rm)])          => self.shift_reg(shift, rd, rd, rm),
+
+            (Op::Mul, &[Reg(rd), Reg(rn), Reg(rm)]) => self.mul(rd, rn, rm),
+            (Op::Mul, &[Reg(rd), Reg(rm)])          => self.mul(rd, rd, rm),
+
+            (Op::Mla(sub), &[Reg(rd), Reg(rn), Reg(rm), Reg(ra)]) => {
+                self.wide(0xFB00 | r(rn), r(ra) << 12 | r(rd) << 8 | (sub as u16) << 4 | r(rm))
+            },
+            (Op::MulLong(hw1), &[Reg(lo), Reg(hi), Reg(rn), Reg(rm)]) => {
+                self.wide(hw1 | r(rn), r(lo) << 12 | r(hi) << 8 | r(rm))
+            },
+            (Op::Div(hw1), &[Reg(rd), Reg(rn), Reg(rm)]) => {
+                self.wide(hw1 | r(rn), 0xF0F0 | r(rd) << 8 | r(rm))
+            },
+            (Op::Div(hw1), &[Reg(rd), Reg(rm)]) => {
+                self.wide(hw1 | r(rd), 0xF0F0 | r(rd) << 8 | r(rm))
+            },
+
+            (Op::AddW(sub), &[Reg(rd), Reg(rn), Imm(ref e)]) => {
+                let v = self.constant(e)?;
+                match addw(sub, rd, rn, v) {
+                    Some((hw1, hw2)) => self.wide(hw1, hw2),
+                    None             => Err(format!("immediate value {} out of range", v)),
+                }
+            },
+            (Op::MovW(top), &[Reg(rd), Imm(ref e)]) => self.movw(top, rd, e),
+
+            (Op::Unary(narrow, hw1, hw2), &[Reg(rd), Reg(rm)]) => {
+                let narrow = narrow.filter(|_| low(rd) && low(rm)).map(|n| n | r(rm) << 3 | r(rd));
+                // Extensions encode `rn` as 15; the others repeat `rm` there
+                let rn     = if hw1 & 0xF == 0 { r(rm) } else { 0 };
+                let wide   = Some((hw1 | rn, hw2 | r(rd) << 8 | r(rm)));
+                self.choose(narrow, wide)
+            },
+
+            (Op::Extract(hw1), &[Reg(rd), Reg(rn), Imm(ref lsb), Imm(ref width)]) => {
+                let (lsb, width) = self.bit_field(lsb, width)?;
+                self.wide(hw1 | r(rn), bit_field(rd, lsb, width - 1))
+            },
+            (Op::Bfi, &[Reg(rd), Reg(rn), Imm(ref lsb), Imm(ref width)]) => {
+                let (lsb, width) = self.bit_field(lsb, width)?;
+                self.wide(0xF360 | r(rn), bit_field(rd, lsb, lsb + width - 1))
+            },
+            (Op::Bfc, &[Reg(rd), Imm(ref lsb), Imm(ref width)]) => {
+                let (lsb, width) = self.bit_field(lsb, width)?;
+                self.wide(0xF36F, bit_field(rd, lsb, lsb + width - 1))
+            },
+
+            (Op::Adr, &[Reg(rd), Imm(ref target)]) => self.pc_rel(None, rd, target),
+
+            (Op::Load(size), &[Reg(rt), Lit(ref e)]) => match size {
+                Size::W => self.literal(rt, e),
+                _       => Err("literal requires 'ldr'".into()),
+            },
+            (Op::Load(size), &[Reg(rt), Imm(ref target)]) => self.pc_rel(Some(size), rt, target),
+            (Op::Load(size), &[Reg(rt), Mem(ref m)])             => self.mem(true,  size, rt, m, None),
+            (Op::Load(size), &[Reg(rt), Mem(ref m), Imm(ref e)]) => self.mem(true,  size, rt, m, Some(e)),
+            (Op::Store(size), &[Reg(rt), Mem(ref m)])             => self.mem(false, size, rt, m, None),
+            (Op::Store(size), &[Reg(rt), Mem(ref m), Imm(ref e)]) => self.mem(false, size, rt, m, Some(e)),
+
+            (Op::Dual(load), &[Reg(rt), Reg(rt2), Mem(ref m)]) if m.index.is_none() => {
+                let v = self.offset(m)?;
+                if v % 4 != 0 || !(-1020..=1020).contains(&v) {
+                    return Err(format!("offset {} out of range", v));
+                }
+                let hw1 = 0xE940 | (load as u16) << 4 | ((v >= 0) as u16) << 7
+                    | (m.writeback as u16) << 5 | r(m.base);
+                self.wide(hw1, r(rt) << 12 | r(rt2) << 8 | (v.unsigned_abs() / 4) as u16)
+            },
+            (Op::Ldrex, &[Reg(rt), Mem(ref m)]) if m.index.is_none() && !m.writeback => {
+                let v = self.word_offset(m)?;
+                self.wide(0xE850 | r(m.base), r(rt) << 12 | 0xF00 | v)
+            },
+            (Op::Strex, &[Reg(rd), Reg(rt), Mem(ref m)]) if m.index.is_none() && !m.writeback => {
+                let v = self.word_offset(m)?;
+                self.wide(0xE840 | r(m.base), r(rt) << 12 | r(rd) << 8 | v)
+            },
+
+            (Op::Multiple(load, db), [Mem(ref m), rest @ ..]) if !rest.is_empty() => {
+                if m.index.is_some() || m.offset.is_some() {
+                    return Err(INVALID.into());
+                }
+                let list = list(rest)?;
+                self.multiple(load, db, m.base, m.writeback, list)
+            },
+            (Op::Push, _) => self.push_pop(false, list(ops)?),
+            (Op::Pop,  _) => self.push_pop(true,  list(ops)?),
+
+            (Op::B, &[Imm(ref target)]) => {
+                let cond = match self.insn.cond {
+                    Some(14) | None   => None,
+                    _ if self.in_it   => None,
+                    cond              => cond,
+                };
+                self.branch(cond, target);
+                Ok(())
+            },
+            (Op::Bl, &[Imm(ref target)]) => {
+                self.call(target);
+                Ok(())
+            },
+            (Op::Bx(link), &[Reg(rm)]) => {
+                self.narrow(0x4700 | (link as u16) << 7 | r(rm) << 3)
+            },
+            (Op::Cbz(nz), &[Reg(rn), Imm(ref target)]) if low(rn) => {
+                if self.in_it {
+                    return Err("instruction not permitted in IT block".into());
+                }
+                self.cbz(0xB100 | (nz as u16) << 11 | r(rn), target);
+                Ok(())
+            },
+            (Op::Tb(h), &[Mem(ref m)]) => match (m.index, &m.offset, m.writeback) {
+                (Some((rm, shift)), None, false) if shift == h as u32 => {
+                    if self.in_it && !self.last_in_it() {
+                        return Err("branch must be the last instruction in an IT block".into());
+                    }
+                    self.wide(0xE8D0 | r(m.base), 0xF000 | (h as u16) << 4 | r(rm))
+                },
+                _ => Err(INVALID.into()),
+            },
+
+            (Op::It(pattern), &[Imm(ref cond)]) => self.it(pattern, cond),
+
+            (Op::Hint(n), []) => {
+                let narrow = 0xBF00 | (n as u16) << 4;
+                self.choose(Some(narrow), Some((0xF3AF, 0x8000 | n as u16)))
+            },
+            (Op::Imm8(base), &[Imm(ref e)]) => {
+                let v = self.constant(e)?;
+                match v {
+                    0..=255 => self.narrow(base | v as u16),
+                    _       => Err(format!("immediate value {} out of range", v)),
+                }
+            },
+            (Op::Cps(disable), &[Imm(Expr::Ident(_, name))]) => {
+                let flags = match self.out.names()[name].to_ascii_lowercase().as_str() {
+                    "i"         => 2,
+                    "f"         => 1,
+                    "if" | "fi" => 3,
+                    _           => return Err("expected: i or f".into()),
+                };
+                self.narrow(0xB660 | (disable as u16) << 4 | flags)
+            },
+            (Op::Barrier(hw2), []) => self.wide(0xF3BF, hw2 | 0xF),
+            (Op::Barrier(hw2), &[Imm(Expr::Ident(_, name))])
+                if self.out.names()[name].eq_ignore_ascii_case("sy") => {
+                self.wide(0xF3BF, hw2 | 0xF)
+            },
+            (Op::Fixed(hw1, hw2), []) => self.wide(hw1, hw2),
+
+            (Op::Mrs, &[Reg(rd), Imm(ref spec)]) => {
+                let sysm = self.special(spec)?;
+                self.wide(0xF3EF, 0x8000 | r(rd) << 8 | sysm)
+            },
+            (Op::Msr, &[Imm(ref spec), Reg(rn)]) => {
+                let sysm = self.special(spec)?;
+                self.wide(0xF380 | r(rn), 0x8800 | sysm)
+            },
+
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    // === Data Processing ===
+
+    /// Splits the operands of a data processing instruction into the
+    /// destination register, the first source register, and the second
+    /// source operand.  Two operands imply that the destination register is
+    /// also the first source register.
+    fn dp_operands(&mut self, ops: &[Operand]) -> Result<(u8, u8, Src)> {
+        match *ops {
+            [Operand::Reg(rd), Operand::Reg(rn), ref src] => Ok((rd, rn, self.src(src)?)),
+            [Operand::Reg(rd), ref src]                   => Ok((rd, rd, self.src(src)?)),
+            _                                             => Err(INVALID.into()),
+        }
+    }
+
+    /// Parses the second source operand of a data processing instruction.
+    fn src(&mut self, op: &Operand) -> Result<Src> {
+        match *op {
+            Operand::Reg(rm)               => Ok(Src::Reg(rm, Shift::Lsl, 0)),
+            Operand::Shifted(rm, shift, n) => Ok(Src::Reg(rm, shift, n)),
+            Operand::Imm(ref e)            => Ok(Src::Imm(self.constant(e)?)),
+            _                              => Err(INVALID.into()),
+        }
+    }
+
+    fn add_sub(&mut self, sub: bool, ops: &[Operand]) -> Result {
+        let (rd, rn, src) = self.dp_operands(ops)?;
+        let s = self.insn.s;
+
+        match src {
+            Src::Imm(v) => {
+                // Adding a negative value is subtracting a positive one
+                let (sub, v) = match v < 0 && v > i32::MIN as i64 {
+                    true  => (!sub, -v),
+                    false => (sub, v),
+                };
+                let bit = sub as u16;
+
+                let narrow = if rd == SP && rn == SP && !s && v % 4 == 0 && v <= 508 {
+                    Some(0xB000 | bit << 7 | (v / 4) as u16)
+                } else if !sub && rn == SP && low(rd) && !s && v % 4 == 0 && v <= 1020 {
+                    Some(0xA800 | r(rd) << 8 | (v / 4) as u16)
+                } else if low(rd) && low(rn) && v <= 7 && self.ok16() {
+                    Some(0x1C00 | bit << 9 | (v as u16) << 6 | r(rn) << 3 | r(rd))
+                } else if rd == rn && low(rd) && v <= 255 && self.ok16() {
+                    Some(0x3000 | bit << 11 | r(rd) << 8 | v as u16)
+                } else {
+                    None
+                };
+
+                let op   = if sub { 13 } else { 8 };
+                let wide = modimm(v)
+                    .map(|imm| dp_imm(op, s, rn, rd, imm))
+                    .or_else(|| addw(sub, rd, rn, v).filter(|_| !s));
+
+                self.choose(narrow, wide)
+            },
+            Src::Reg(rm, shift, n) => {
+                let bit = sub as u16;
+
+                let narrow = if n != 0 {
+                    None
+                } else if low(rd) && low(rn) && low(rm) && self.ok16() {
+                    Some(0x1800 | bit << 9 | r(rm) << 6 | r(rn) << 3 | r(rd))
+                } else if !sub && !s && (rd == rn || rd == rm) {
+                    let rm = if rd == rn { rm } else { rn };
+                    Some(0x4400 | r(rd >> 3) << 7 | r(rm) << 3 | r(rd & 7))
+                } else {
+                    None
+                };
+
+                let op   = if sub { 13 } else { 8 };
+                let wide = Some(dp_reg(op, s, rn, rd, rm, shift, n));
+
+                self.choose(narrow, wide)
+            },
+        }
+    }
+
+    fn rsb(&mut self, rd: u8, rn: u8, src: Src) -> Result {
+        let s = self.insn.s;
+
+        match src {
+            Src::Imm(v) => {
+                let narrow = (v == 0 && low(rd) && low(rn) && self.ok16())
+                    .then(|| 0x4240 | r(rn) << 3 | r(rd));
+                let wide   = modimm(v).map(|imm| dp_imm(14, s, rn, rd, imm));
+                self.choose(narrow, wide)
+            },
+            Src::Reg(rm, shift, n) => {
+                self.choose(None, Some(dp_reg(14, s, rn, rd, rm, shift, n)))
+            },
+        }
+    }
+
+    fn logic(&mut self, op: u8, alu: Option<u8>, ops: &[Operand]) -> Result {
+        let (rd, rn, src) = self.dp_operands(ops)?;
+        let s = self.insn.s;
+
+        match src {
+            Src::Imm(v) => {
+                let wide = modimm(v).map(|imm| dp_imm(op, s, rn, rd, imm));
+                match wide {
+                    Some(_) => self.choose(None, wide),
+                    None    => Err(format!("immediate value {} cannot be encoded", v)),
+                }
+            },
+            Src::Reg(rm, shift, n) => {
+                // AND, EOR, ORR, and ADC are commutative
+                let commutes = matches!(op, 0 | 2 | 4 | 10);
+
+                let narrow = match alu {
+                    Some(alu) if n == 0 && low(rd) && low(rn) && low(rm)
+                        && (rd == rn || commutes && rd == rm) && self.ok16() => {
+                        let rm = if rd == rn { rm } else { rn };
+                        Some(0x4000 | (alu as u16) << 6 | r(rm) << 3 | r(rd))
+                    },
+                    _ => None,
+                };
+                let wide = Some(dp_reg(op, s, rn, rd, rm, shift, n));
+
+                self.choose(narrow, wide)
+            },
+        }
+    }
+
+    fn mov(&mut self, rd: u8, src: &Operand) -> Result {
+        let s = self.insn.s;
+
+        match self.src(src)? {
+            Src::Imm(v) => {
+                let narrow = ((0..=255).contains(&v) && low(rd) && self.ok16())
+                    .then(|| 0x2000 | r(rd) << 8 | v as u16);
+
+                let wide = modimm(v)
+                    .map(|imm| dp_imm(2, s, PC, rd, imm))
+                    .or_else(|| {
+                        (!s && (0..=0xFFFF).contains(&v)).then(|| movw(false, rd, v as u16))
+                    })
+                    .or_else(|| modimm(!v).map(|imm| dp_imm(3, s, PC, rd, imm)));
+
+                match (narrow, wide) {
+                    (None, None) => Err(format!("immediate value {} cannot be encoded", v)),
+                    _            => self.choose(narrow, wide),
+                }
+            },
+            Src::Reg(rm, _, _) => {
+                let narrow = if s {
+                    (low(rd) && low(rm) && self.ok16()).then(|| r(rm) << 3 | r(rd))
+                } else {
+                    Some(0x4600 | r(rd >> 3) << 7 | r(rm) << 3 | r(rd & 7))
+                };
+                let wide = Some(dp_reg(2, s, PC, rd, rm, Shift::Lsl, 0));
+                self.choose(narrow, wide)
+            },
+        }
+    }
+
+    fn compare(&mut self, op: u8, rn: u8, src: &Operand) -> Result {
+        match self.src(src)? {
+            Src::Imm(v) => {
+                // Comparing with a negative value is comparing the negation
+                // with the other comparison
+                let (op, v) = match (op, v < 0 && v > i32::MIN as i64) {
+                    (13, true) => (8,  -v),
+                    (8,  true) => (13, -v),
+                    _          => (op, v),
+                };
+
+                let narrow = (op == 13 && low(rn) && (0..=255).contains(&v))
+                    .then(|| 0x2800 | r(rn) << 8 | v as u16);
+                let wide   = modimm(v).map(|imm| dp_imm(op, true, rn, PC, imm));
+
+                match (narrow, wide) {
+                    (None, None) => Err(format!("immediate value {} cannot be encoded", v)),
+                    _            => self.choose(narrow, wide),
+                }
+            },
+            Src::Reg(rm, shift, n) => {
+                let both_low = low(rn) && low(rm);
+
+                let narrow = match op {
+                    _ if n != 0          => None,
+                    13 if both_low       => Some(0x4280 | r(rm) << 3 | r(rn)),
+                    13                   => Some(0x4500 | r(rn >> 3) << 7 | r(rm) << 3 | r(rn & 7)),
+                    8  if both_low       => Some(0x42C0 | r(rm) << 3 | r(rn)),
+                    0  if both_low       => Some(0x4200 | r(rm) << 3 | r(rn)),
+                    _                    => None,
+                };
+                let wide = Some(dp_reg(op, true, rn, PC, rm, shift, n));
+
+                self.choose(narrow, wide)
+            },
+        }
+    }
+
+    fn shift_imm(&mut self, shift: Shift, rd: u8, rm: u8, n: u32) -> Result {
+        let s = self.insn.s;
+
+        // A zero rotation encodes RRX, and other zero shifts are moves
+        let shift = match (shift, n) {
+            (Shift::Ror, 0) => return Err("shift amount 0 out of range".into()),
+            (_,          0) => Shift::Lsl,
+            _               => shift,
+        };
+
+        let narrow = (shift != Shift::Ror && low(rd) && low(rm) && self.ok16())
+            .then(|| (shift as u16) << 11 | (n as u16 & 31) << 6 | r(rm) << 3 | r(rd));
+        let wide   = Some(dp_reg(2, s, PC, rd, rm, shift, n));
+
+        self.choose(narrow, wide)
+    }
+
+    fn shift_reg(&mut self, shift: Shift, rd: u8, rn: u8, rm: u8) -> Result {
+        let s   = self.insn.s;
+        let alu = match shift {
+            Shift::Lsl => 2,
+            Shift::Lsr => 3,
+            Shift::Asr => 4,
+            Shift::Ror => 7,
+        };
+
+        let narrow = (rd == rn && low(rd) && low(rm) && self.ok16())
+            .then(|| 0x4000 | alu << 6 | r(rm) << 3 | r(rd));
+        let wide   = Some((
+            0xFA00 | (shift as u16) << 5 | (s as u16) << 4 | r(rn),
+            0xF000 | r(rd) << 8 | r(rm),
+        ));
+
+        self.choose(narrow, wide)
+    }
+
+    fn mul(&mut self, rd: u8, rn: u8, rm: u8) -> Result {
+        let narrow = (low(rd) && low(rn) && low(rm) && (rd == rm || rd == rn) && self.ok16())
+            .then(|| {
+                let rn = if rd == rm { rn } else { rm };
+                0x4340 | r(rn) << 3 | r(rd)
+            });
+        let wide = (!self.insn.s).then(|| (0xFB00 | r(rn), 0xF000 | r(rd) << 8 | r(rm)));
+
+        self.choose(narrow, wide)
+    }
+
+    fn movw(&mut self, top: bool, rd: u8, expr: &Expr<Span>) -> Result {
+        let (high, inner) = match half(self.out.names(), expr) {
+            Some((high, inner)) => (Some(high), inner),
+            None                => (None, expr),
+        };
+
+        let imm = match (self.out.eval(inner), high) {
+            (Some(Value::Const(v)), Some(high)) if fits(v, 32) => {
+                (if high { v >> 16 } else { v }) as u16
+            },
+            (Some(Value::Const(v)), None) if (0..=0xFFFF).contains(&v) => v as u16,
+            (Some(Value::Const(v)), _) => {
+                return Err(format!("immediate value {} out of range", v));
+            },
+            (Some(Value::Reloc(_)), Some(high)) => {
+                self.out.reloc(inner, if high { MOVT } else { MOVW });
+                0
+            },
+            (Some(Value::Reloc(_)), None) => {
+                return Err("expected: constant expression; use %lo16 or %hi16".into());
+            },
+            (None, _) => 0,
+        };
+
+        let (hw1, hw2) = movw(top, rd, imm);
+        self.wide(hw1, hw2)
+    }
+
+    // === Loads and Stores ===
+
+    fn mem(&mut self, load: bool, size: Size, rt: u8, m: &Mem, post: Option<&Expr<Span>>)
+        -> Result
+    {
+        let base = mem_opcode(load, size);
+        let rn   = m.base;
+
+        // Post-indexed
+        if let Some(post) = post {
+            if m.index.is_some() || m.offset.is_some() || m.writeback {
+                return Err(INVALID.into());
+            }
+            let v = self.constant(post)?;
+            if !(-255..=255).contains(&v) {
+                return Err(format!("offset {} out of range", v));
+            }
+            let hw2 = r(rt) << 12 | 0x900 | ((v >= 0) as u16) << 9 | v.unsigned_abs() as u16;
+            return self.choose(None, Some((base | r(rn), hw2)));
+        }
+
+        // Register offset
+        if let Some((rm, shift)) = m.index {
+            if m.offset.is_some() || m.writeback || shift > 3 {
+                return Err(INVALID.into());
+            }
+            let narrow = (shift == 0 && low(rt) && low(rn) && low(rm))
+                .then(|| 0x5000 | reg_opcode(load, size) << 9 | r(rm) << 6 | r(rn) << 3 | r(rt));
+            let wide   = Some((base | r(rn), r(rt) << 12 | (shift as u16) << 4 | r(rm)));
+            return self.choose(narrow, wide);
+        }
+
+        let v = self.offset(m)?;
+
+        // Pre-indexed
+        if m.writeback {
+            if !(-255..=255).contains(&v) {
+                return Err(format!("offset {} out of range", v));
+            }
+            let hw2 = r(rt) << 12 | 0xD00 | ((v >= 0) as u16) << 9 | v.unsigned_abs() as u16;
+            return self.choose(None, Some((base | r(rn), hw2)));
+        }
+
+        // Immediate offset
+        let scale = size.bytes() as i64;
+        let word  = size == Size::W;
+
+        let narrow = if rn == SP && word && low(rt) && v % 4 == 0 && (0..=1020).contains(&v) {
+            Some(0x9000 | (load as u16) << 11 | r(rt) << 8 | (v / 4) as u16)
+        } else if low(rt) && low(rn) && v % scale == 0 && (0..32 * scale).contains(&v) {
+            let opcode = match size {
+                Size::W  => Some(0x6000),
+                Size::B  => Some(0x7000),
+                Size::H  => Some(0x8000),
+                _        => None,
+            };
+            opcode.map(|op| op | (load as u16) << 11 | ((v / scale) as u16) << 6 | r(rn) << 3 | r(rt))
+        } else {
+            None
+        };
+
+        let wide = match v {
+            0..=4095  => Some((base | 0x80 | r(rn), r(rt) << 12 | v as u16)),
+            -255..=-1 => Some((base | r(rn), r(rt) << 12 | 0xC00 | v.unsigned_abs() as u16)),
+            _         => None,
+        };
+
+        match (narrow, wide) {
+            (None, None) => Err(format!("offset {} out of range", v)),
+            _            => self.choose(narrow, wide),
+        }
+    }
+
+    /// Encodes `ldr rt, =expr`, which loads `expr` from a literal pool.
+    fn literal(&mut self, rt: u8, expr: &Expr<Span>) -> Result {
+        let value = self.out.eval(expr);
+        let addr  = self.thumb.add_literal(expr, value, self.out);
+        let pc    = self.out.here().wrapping_add(4) & !3;
+        let disp  = addr.map(|addr| addr.wrapping_sub(pc) as i64);
+
+        let narrow_ok = low(rt) && self.insn.width != Width::Wide && match disp {
+            Some(d) => d % 4 == 0 && (0..=1020).contains(&d),
+            None    => true,
+        };
+        let wide_ok = self.thumb.v7() && self.insn.width != Width::Narrow;
+
+        let disp = disp.unwrap_or(0);
+
+        if narrow_ok {
+            self.emit16(0x4800 | r(rt) << 8 | (disp / 4) as u16);
+        } else if wide_ok && (-4095..=4095).contains(&disp) {
+            let up = (disp >= 0) as u16;
+            self.emit32(0xF85F & !0x80 | up << 7, r(rt) << 12 | disp.unsigned_abs() as u16);
+        } else {
+            self.emit16(0x4800 | r(rt) << 8);
+            if self.out.is_final() {
+                return Err("literal pool out of range; place a .pool closer".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes `adr rd, target` (if `size` is `None`) or a load from the
+    /// PC-relative address `target`.
+    fn pc_rel(&mut self, size: Option<Size>, rt: u8, target: &Expr<Span>) -> Result {
+        let narrow = match size {
+            None          => Some(0xA000 | r(rt) << 8),
+            Some(Size::W) => Some(0x4800 | r(rt) << 8),
+            Some(_)       => None,
+        };
+        let narrow  = narrow.filter(|_| low(rt) && self.insn.width != Width::Wide);
+        let wide_ok = self.thumb.v7() && self.insn.width != Width::Narrow;
+
+        let value = match self.out.eval(target) {
+            Some(value) => value,
+            None        => return self.choose(narrow, None).or(Ok(())),
+        };
+
+        match value {
+            Value::Const(v) => {
+                let pc   = self.out.here().wrapping_add(4) & !3;
+                let disp = v.wrapping_sub(pc as i64);
+
+                let narrow = narrow
+                    .filter(|_| disp % 4 == 0 && (0..=1020).contains(&disp))
+                    .map(|hw| hw | (disp / 4) as u16);
+
+                let wide = match size {
+                    _ if !(-4095..=4095).contains(&disp) => None,
+                    Some(size) => {
+                        let (hw1, hw2) = lit_load(size, rt);
+                        Some((hw1 | ((disp >= 0) as u16) << 7, hw2 | disp.unsigned_abs() as u16))
+                    },
+                    None => addw(disp < 0, rt, PC, disp.abs()),
+                };
+
+                match (narrow, wide) {
+                    (None, None) => Err(format!("PC-relative offset {} out of range", disp)),
+                    _            => self.choose(narrow, wide),
+                }
+            },
+            Value::Reloc(_) => {
+                let expr = aligned_pc_rel_expr(target);
+                match (size, narrow) {
+                    (Some(size), _) if wide_ok => {
+                        let (hw1, hw2) = lit_load(size, rt);
+                        self.out.reloc(&expr, PC12);
+                        self.emit32(hw1, hw2);
+                    },
+                    (_, Some(hw)) => {
+                        self.out.reloc(&expr, PC8);
+                        self.emit16(hw);
+                    },
+                    _ => return Err(INVALID.into()),
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn multiple(&mut self, load: bool, db: bool, rn: u8, writeback: bool, list: u16) -> Result {
+        let narrow = if db || !low(rn) || list & 0xFF00 != 0 {
+            None
+        } else if load && writeback == (list & 1 << rn == 0) {
+            Some(0xC800 | r(rn) << 8 | list)
+        } else if !load && writeback {
+            Some(0xC000 | r(rn) << 8 | list)
+        } else {
+            None
+        };
+
+        let base = if db { 0xE900 } else { 0xE880 };
+        let wide = Some((base | (load as u16) << 4 | (writeback as u16) << 5 | r(rn), list));
+
+        self.choose(narrow, wide)
+    }
+
+    fn push_pop(&mut self, pop: bool, list: u16) -> Result {
+        let extra = if pop { PC } else { LR };
+
+        let narrow = (list & !(0xFF | 1 << extra) == 0).then(|| {
+            let base = if pop { 0xBC00 } else { 0xB400 };
+            base | (list >> extra & 1) << 8 | list & 0xFF
+        });
+
+        let wide = match (list.count_ones(), pop) {
+            (1, false) => Some((0xF84D, (list.trailing_zeros() as u16) << 12 | 0xD04)),
+            (1, true)  => Some((0xF85D, (list.trailing_zeros() as u16) << 12 | 0xB04)),
+            (_, false) => Some((0xE92D, list)),
+            (_, true)  => Some((0xE8BD, list)),
+        };
+
+        self.choose(narrow, wide)
+    }
+
+    // === Branches ===
+
+    /// Emits a branch to `target`, conditional if `cond` is present, in the
+    /// shortest form that reaches the target.
+    fn branch(&mut self, cond: Option<u8>, target: &Expr<Span>) {
+        let (narrow_kind, narrow, wide_kind, wide) = match cond {
+            None    => (JUMP11, 0xE000,                  JUMP24, (0xF000, 0x9000)),
+            Some(c) => (JUMP8,  0xD000 | (c as u16) << 8, JUMP20, (0xF000 | (c as u16) << 6, 0x8000)),
+        };
+        let field   = |(hw1, hw2): (u16, u16)| (hw2 as u64) << 16 | hw1 as u64;
+        let wide_ok = self.thumb.v7() && self.insn.width != Width::Narrow;
+
+        let value = self.out.eval(target).map(|v| pc_rel_value(self.out, self.span, v, 4));
+
+        let wide_form = match self.insn.width {
+            Width::Narrow          => false,
+            Width::Wide            => true,
+            Width::Any if !wide_ok => false,
+            Width::Any             => {
+                let narrow_fits = match value {
+                    Some(Value::Const(d)) => (narrow_kind.apply)(d, narrow as u64).is_ok(),
+                    Some(Value::Reloc(_)) => false,
+                    None                  => true,
+                };
+                self.out.relax(2, &|form| form == 1 || narrow_fits) == 1
+            },
+        };
+
+        let value = match (value, wide_form) {
+            (Some(value), _) => value,
+            (None,    false) => return self.emit16(narrow),
+            (None,    true)  => return self.emit32(wide.0, wide.1),
+        };
+
+        match value {
+            Value::Const(d) => {
+                let result = match wide_form {
+                    true if !wide_ok => Err(
+                        "operands require a 32-bit encoding, which the target lacks".into()
+                    ),
+                    true  => (wide_kind.apply)(d, field(wide)),
+                    false => (narrow_kind.apply)(d, narrow as u64),
+                };
+
+                match (result, wide_form) {
+                    (Ok(f),    false) => self.emit16(f as u16),
+                    (Ok(f),    true)  => self.emit32(f as u16, (f >> 16) as u16),
+                    (Err(msg), false) => {
+                        self.out.error(*target.data(), &msg);
+                        self.emit16(narrow);
+                    },
+                    (Err(msg), true) => {
+                        self.out.error(*target.data(), &msg);
+                        self.emit32(wide.0, wide.1);
+                    },
+                }
+            },
+            Value::Reloc(_) => {
+                let expr = pc_rel_expr(target, 4);
+                if wide_form && wide_ok {
+                    self.out.reloc(&expr, wide_kind);
+                    self.emit32(wide.0, wide.1);
+                } else {
+                    self.out.reloc(&expr, narrow_kind);
+                    self.emit16(narrow);
+                }
+            },
+        }
+    }
+
+    /// Emits `bl target`.
+    fn call(&mut self, target: &Expr<Span>) {
+        let value = match self.out.eval(target) {
+            Some(value) => pc_rel_value(self.out, self.span, value, 4),
+            None        => return self.emit32(0xF000, 0xD000),
+        };
+
+        match value {
+            Value::Const(d) => match apply_jump24(d, 0xD000_F000) {
+                Ok(f)    => self.emit32(f as u16, (f >> 16) as u16),
+                Err(msg) => {
+                    self.out.error(*target.data(), &msg);
+                    self.emit32(0xF000, 0xD000);
+                },
+            },
+            Value::Reloc(_) => {
+                self.out.reloc(&pc_rel_expr(target, 4), JUMP24);
+                self.emit32(0xF000, 0xD000);
+            },
+        }
+    }
+
+    /// Emits `cbz` or `cbnz` with the given opcode to `target`.
+    fn cbz(&mut self, opcode: u16, target: &Expr<Span>) {
+        let value = match self.out.eval(target) {
+            Some(value) => pc_rel_value(self.out, self.span, value, 4),
+            None        => return self.emit16(opcode),
+        };
+
+        match value {
+            Value::Const(d) => match apply_cb(d, opcode as u64) {
+                Ok(f)    => self.emit16(f as u16),
+                Err(msg) => {
+                    self.out.error(*target.data(), &msg);
+                    self.emit16(opcode);
+                },
+            },
+            Value::Reloc(_) => {
+                self.out.reloc(&pc_rel_expr(target, 4), CB);
+                self.emit16(opcode);
+            },
+        }
+    }
+
+    fn it(&mut self, pattern: &str, cond: &Expr<Span>) -> Result {
+        if self.in_it {
+            return Err("IT instruction inside IT block".into());
+        }
+
+        let first = match *cond {
+            Expr::Ident(_, name) => cond_code(&self.out.names()[name]),
+            _                    => None,
+        };
+        let first = first.ok_or("expected: condition")?;
+
+        if first == 14 && pattern.contains('e') {
+            return Err("condition 'al' has no inverse".into());
+        }
+
+        let mut conds = vec![first];
+        let mut mask  = 1 << (3 - pattern.len());
+
+        for (i, c) in pattern.chars().enumerate() {
+            let then = c == 't';
+            let bit  = if then { first & 1 } else { !first & 1 };
+            mask |= (bit as u16) << (3 - i);
+            conds.push(if then { first } else { first ^ 1 });
+        }
+
+        conds.reverse();
+        self.thumb.state.borrow_mut().it = conds;
+
+        self.narrow(0xBF00 | (first as u16) << 4 | mask)
+    }
+
+    // === Helpers ===
+
+    /// Returns whether an instruction without the `s` suffix outside an IT
+    /// block or with it inside one may take its 16-bit form, which sets the
+    /// flags only outside an IT block.  Otherwise, records the mismatch.
+    fn ok16(&mut self) -> bool {
+        let ok = self.insn.s != self.in_it;
+        self.mismatch |= !ok;
+        ok
+    }
+
+    /// Returns whether the instruction is the last in its IT block.
+    fn last_in_it(&self) -> bool {
+        self.thumb.state.borrow().it.is_empty()
+    }
+
+    /// Emits the 16-bit encoding `narrow` or the 32-bit encoding `wide`,
+    /// preferring the shorter one unless a width suffix requires otherwise.
+    fn choose(&mut self, narrow: Option<u16>, wide: Option<(u16, u16)>) -> Result {
+        let width = self.insn.width;
+
+        if let (Some(hw), false) = (narrow, width == Width::Wide) {
+            self.emit16(hw);
+            return Ok(());
+        }
+
+        let wide = match (wide, width, self.thumb.v7()) {
+            (Some(wide), Width::Any | Width::Wide, true) => wide,
+            (Some(_),    Width::Any, false) if !self.mismatch => {
+                return Err("operands require a 32-bit encoding, which the target lacks".into());
+            },
+            (None, Width::Wide, _) if narrow.is_some() => {
+                return Err("no 32-bit encoding for operands".into());
+            },
+            _ if self.mismatch && self.in_it => {
+                return Err("16-bit encoding in IT block does not set flags; remove 's' suffix".into());
+            },
+            _ if self.mismatch => {
+                return Err("16-bit encoding outside IT block sets flags; add 's' suffix".into());
+            },
+            (_, Width::Narrow, _) => return Err("no 16-bit encoding for operands".into()),
+            _ => return Err(INVALID.into()),
+        };
+
+        self.emit32(wide.0, wide.1);
+        Ok(())
+    }
+
+    /// Emits the 16-bit encoding `hw`.
+    fn narrow(&mut self, hw: u16) -> Result {
+        match self.insn.width {
+            Width::Wide => Err("no 32-bit encoding for operands".into()),
+            _           => {
+                self.emit16(hw);
+                Ok(())
+            },
+        }
+    }
+
+    /// Emits the 32-bit encoding `hw1`, `hw2`.
+    fn wide(&mut self, hw1: u16, hw2: u16) -> Result {
+        match self.insn.width {
+            Width::Narrow => Err("no 16-bit encoding for operands".into()),
+            _             => {
+                self.emit32(hw1, hw2);
+                Ok(())
+            },
+        }
+    }
+
+    fn emit16(&mut self, hw: u16) {
+        self.out.emit(&hw.to_le_bytes());
+    }
+
+    fn emit32(&mut self, hw1: u16, hw2: u16) {
+        self.out.emit(&hw1.to_le_bytes());
+        self.out.emit(&hw2.to_le_bytes());
+    }
+
+    /// Evaluates an expression whose value must be known at assembly time.
+    fn constant(&mut self, expr: &Expr<Span>) -> Result<i64> {
+        match self.out.eval(expr) {
+            Some(Value::Const(v)) => Ok(v),
+            Some(Value::Reloc(v)) => match self.out.is_final() {
+                true  => Err("expected: constant expression".into()),
+                false => Ok(v.addend),
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// Evaluates the offset of a memory operand, which is zero if absent.
+    fn offset(&mut self, m: &Mem) -> Result<i64> {
+        match m.offset {
+            Some(ref e) => self.constant(e),
+            None        => Ok(0),
+        }
+    }
+
+    /// Evaluates the offset of a memory operand that must be a multiple of 4
+    /// from 0 to 1020, and returns the offset in words.
+    fn word_offset(&mut self, m: &Mem) -> Result<u16> {
+        match self.offset(m)? {
+            v if v % 4 == 0 && (0..=1020).contains(&v) => Ok((v / 4) as u16),
+            v => Err(format!("offset {} out of range", v)),
+        }
+    }
+
+    fn shift_amount(&mut self, shift: Shift, expr: &Expr<Span>) -> Result<u32> {
+        let max = match shift {
+            Shift::Lsl | Shift::Ror => 31,
+            Shift::Lsr | Shift::Asr => 32,
+        };
+        match self.constant(expr)? {
+            n if (0..=max).contains(&n) => Ok(n as u32),
+            n => Err(format!("shift amount {} out of range", n)),
+        }
+    }
+
+    fn bit_field(&mut self, lsb: &Expr<Span>, width: &Expr<Span>) -> Result<(u16, u16)> {
+        let lsb   = self.constant(lsb)?;
+        let width = self.constant(width)?;
+        if !(0..=31).contains(&lsb) || width < 1 || lsb + width > 32 {
+            return Err("bit field out of range".into());
+        }
+        Ok((lsb as u16, width as u16))
+    }
+
+    /// Returns the `SYSm` number of the special register that `expr` names.
+    fn special(&mut self, expr: &Expr<Span>) -> Result<u16> {
+        let name = match *expr {
+            Expr::Ident(_, name) => self.out.names()[name].to_ascii_lowercase(),
+            _                    => return Err("expected: special register".into()),
+        };
+        SPECIAL
+            .iter()
+            .find(|s| s.0 == name)
+            .map(|s| s.1)
+            .ok_or_else(|| "expected: special register".into())
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Special registers of `mrs` and `msr`, and their `SYSm` numbers.
+pub const SPECIAL: [(&str, u16); 14] = [
+    ("apsr",         0), ("iapsr",        1), ("eapsr",        2), ("xpsr",         3),
+    ("ipsr",         5), ("epsr",         6), ("iepsr",        7), ("msp",          8),
+    ("psp",          9), ("primask",     16), ("basepri",     17), ("basepri_max", 18),
+    ("faultmask",   19), ("control",     20),
+];
+
+/// Widens a register number for an encoding.
+fn r(reg: u8) -> u16 {
+    reg as u16
+}
+
+/// Returns whether `reg` is one of `r0`-`r7`.
+fn low(reg: u8) -> bool {
+    reg < 8
+}
+
+/// Returns the union of the registers in the given register list operands.
+fn list(ops: &[Operand]) -> Result<u16> {
+    let mut mask = 0;
+    for op in ops {
+        mask |= match *op {
+            Operand::Reg(r)     => 1 << r,
+            Operand::List(list) => list,
+            _                   => return Err("expected: register list".into()),
+        };
+    }
+    Ok(mask)
+}
+
+/// Returns the 12-bit modified immediate encoding of `value`, if any.
+pub fn modimm(value: i64) -> Option<u16> {
+    if !fits(value, 32) {
+        return None;
+    }
+
+    let v = value as u32;
+    let b = v & 0xFF;
+
+    if v <= 0xFF {
+        return Some(v as u16);
+    }
+    if v == b << 16 | b {
+        return Some(0x100 | b as u16);
+    }
+    let b1 = v >> 8 & 0xFF;
+    if v == b1 << 24 | b1 << 8 {
+        return Some(0x200 | b1 as u16);
+    }
+    if v == b * 0x0101_0101 {
+        return Some(0x300 | b as u16);
+    }
+
+    (8..32).find_map(|rot| {
+        let x = v.rotate_left(rot);
+        (x <= 0xFF && x & 0x80 != 0).then_some((rot as u16) << 7 | (x & 0x7F) as u16)
+    })
+}
+
+/// Returns the 32-bit data processing encoding with a modified immediate.
+fn dp_imm(op: u8, s: bool, rn: u8, rd: u8, imm: u16) -> (u16, u16) {
+    let hw1 = 0xF000 | (imm >> 11 & 1) << 10 | (op as u16) << 5 | (s as u16) << 4 | r(rn);
+    let hw2 = (imm >> 8 & 7) << 12 | r(rd) << 8 | imm & 0xFF;
+    (hw1, hw2)
+}
+
+/// Returns the 32-bit data processing encoding with a shifted register.
+fn dp_reg(op: u8, s: bool, rn: u8, rd: u8, rm: u8, shift: Shift, n: u32) -> (u16, u16) {
+    let n   = (n & 31) as u16;
+    let hw1 = 0xEA00 | (op as u16) << 5 | (s as u16) << 4 | r(rn);
+    let hw2 = (n >> 2) << 12 | r(rd) << 8 | (n & 3) << 6 | (shift as u16) << 4 | r(rm);
+    (hw1, hw2)
+}
+
+/// Returns the encoding of `addw` or `subw` with a 12-bit immediate, if the
+/// value fits.
+fn addw(sub: bool, rd: u8, rn: u8, v: i64) -> Option<(u16, u16)> {
+    if !(0..=4095).contains(&v) {
+        return None;
+    }
+    let v   = v as u16;
+    let hw1 = 0xF200 | (sub as u16) << 7 | (sub as u16) << 5 | (v >> 11) << 10 | r(rn);
+    let hw2 = (v >> 8 & 7) << 12 | r(rd) << 8 | v & 0xFF;
+    Some((hw1, hw2))
+}
+
+/// Returns the encoding of `movw` or `movt` with the given immediate.
+fn movw(top: bool, rd: u8, imm: u16) -> (u16, u16) {
+    let hw1 = 0xF240 | (top as u16) << 7 | (imm >> 11 & 1) << 10 | imm >> 12;
+    let hw2 = (imm >> 8 & 7) << 12 | r(rd) << 8 | imm & 0xFF;
+    (hw1, hw2)
+}
+
+/// Returns the second halfword of a bit field instruction.
+fn bit_field(rd: u8, lsb: u16, msb: u16) -> u16 {
+    (lsb >> 2) << 12 | r(rd) << 8 | (lsb & 3) << 6 | msb
+}
+
+/// Returns the first halfword of a 32-bit load or store with an 8-bit
+/// immediate or register offset.  Setting bit 7 selects a 12-bit immediate.
+fn mem_opcode(load: bool, size: Size) -> u16 {
+    let base = match size {
+        Size::B  => 0xF800,
+        Size::H  => 0xF820,
+        Size::W  => 0xF840,
+        Size::Sb => 0xF900,
+        Size::Sh => 0xF920,
+    };
+    base | (load as u16) << 4
+}
+
+/// Returns the opcode field of a 16-bit load or store with a register
+/// offset.
+fn reg_opcode(load: bool, size: Size) -> u16 {
+    match (load, size) {
+        (false, Size::W)  => 0,
+        (false, Size::H)  => 1,
+        (false, _)        => 2,
+        (true,  Size::Sb) => 3,
+        (true,  Size::W)  => 4,
+        (true,  Size::H)  => 5,
+        (true,  Size::B)  => 6,
+        (true,  Size::Sh) => 7,
+    }
+}
+
+/// Returns the encoding of a 32-bit PC-relative load with a zero offset
+/// subtracted.
+fn lit_load(size: Size, rt: u8) -> (u16, u16) {
+    (mem_opcode(true, size) | 0xF, r(rt) << 12)
+}
+
+/// Returns an expression for the offset from the word-aligned PC of the
+/// current instruction to `target`.
+fn aligned_pc_rel_expr(target: &Expr<Span>) -> Expr<Span> {
+    let span = *target.data();
+    let pc   = Expr::Binary(
+        span, BinOp::Add, Box::new(here(span)), Box::new(Expr::Int(span, 4))
+    );
+    let pc   = Expr::Binary(
+        span, BinOp::BitAnd, Box::new(pc), Box::new(Expr::Int(span, -4i64 as u64))
+    );
+    Expr::Binary(span, BinOp::Sub, Box::new(target.clone()), Box::new(pc))
+}
+
+/// Recognizes `%lo16(x)` and `%hi16(x)`.  Returns whether the operator
+/// selects the high half, and the operand.
+fn half<'e>(names: &NameTable, expr: &'e Expr<Span>) -> Option<(bool, &'e Expr<Span>)> {
+    let call = match *expr {
+        Expr::Unary(_, UnOp::UnsignedH, ref inner) => inner,
+        _                                          => return None,
+    };
+    let (func, args) = match **call {
+        Expr::Call(_, ref func, ref args) => (func, args),
+        _                                 => return None,
+    };
+    let high = match **func {
+        Expr::Ident(_, name) => match &names[name] {
+            "lo16" => false,
+            "hi16" => true,
+            _      => return None,
+        },
+        _ => return None,
+    };
+    match &args[..] {
+        [arg] => Some((high, arg)),
+        _     => None,
+    }
+}
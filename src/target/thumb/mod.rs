@@ -0,0 +1,504 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ARM Thumb instruction set of the Cortex-M profile.
+//!
+//! Instructions use UAL mnemonics with optional `s`, condition, and width
+//! (`.n` or `.w`) suffixes, in that order.  Operands use ras syntax:
+//!
+//! | Operand                       | UAL                 | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | immediate                     | `#42`               | `42`
+//! | shifted register              | `r1, lsl #2`        | `r1 << 2`, `lsl(r1, 2)`
+//! | register list                 | `{r0-r3, lr}`       | `r0-r3/lr`
+//! | base                          | `[r0]`              | `[r0]`
+//! | base and offset               | `[r0, #4]`          | `[r0 + 4]`
+//! | base and index                | `[r0, r1, lsl #2]`  | `[r0 + r1*4]`
+//! | pre-indexed                   | `[r0, #4]!`         | `[r0 + 4]!`
+//! | post-indexed                  | `[r0], #4`          | `[r0], 4`
+//! | writeback base                | `r0!`               | `[r0]!`
+//! | literal                       | `=value`            | `=value`
+//!
+//! An instruction takes its 16-bit form when its operands permit and its
+//! `s` suffix agrees with whether the 16-bit form sets the flags, which it
+//! does outside an IT block and does not inside one.  Otherwise, on ARMv7-M,
+//! it takes its 32-bit form.  A branch without a width suffix relaxes from
+//! its 16-bit form to its 32-bit form.
+//!
+//! `ldr rt, =value` loads `value` from a literal pool.  The target places
+//! pending literals at each `.pool` (or `.ltorg`) directive and at the end of
+//! each section.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{fits, Endian, RelocKind};
+use crate::lang::ast::{Dir, Expr, Span};
+use crate::name::{Name, NameTable};
+
+use super::{Decoded, Emitter, Target, Value};
+
+mod decode;
+mod encode;
+mod operand;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: ARMv6-M instructions.
+pub const V6M: u32 = 1 << 0;
+
+/// Instruction set feature: ARMv7-M additions.
+pub const V7M: u32 = 1 << 1;
+
+/// Member of the Cortex-M family.
+#[derive(Debug)]
+pub struct Thumb {
+    name:  &'static str,
+    isa:   u32,
+    index: HashMap<Name, usize>,
+    state: RefCell<State>,
+}
+
+/// Condition suffixes and their codes.
+const CONDS: [(&str, u8); 17] = [
+    ("eq",  0), ("ne",  1), ("cs",  2), ("hs",  2), ("cc",  3), ("lo",  3),
+    ("mi",  4), ("pl",  5), ("vs",  6), ("vc",  7), ("hi",  8), ("ls",  9),
+    ("ge", 10), ("lt", 11), ("gt", 12), ("le", 13), ("al", 14),
+];
+
+/// Condition code that indicates the absence of a condition suffix.
+const NO_COND: u8 = 15;
+
+/// Width suffixes, in order of their [`Width`] value.
+const WIDTHS: [&str; 3] = ["", ".n", ".w"];
+
+impl Thumb {
+    /// Creates a new [`Thumb`] target with the given `name` and instruction
+    /// set features `isa`, interning its mnemonics in `names`.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        let mut index = HashMap::new();
+
+        // Insert unconditional forms first, so that a mnemonic such as `bls`
+        // never shadows another instruction
+        let conds = [("", NO_COND)].into_iter().chain(CONDS);
+
+        for (with_cond, (cond_name, cond)) in conds.enumerate() {
+            for (i, entry) in ENTRIES.iter().enumerate() {
+                if entry.flags & V7 != 0 && isa & V7M == 0 {
+                    continue;
+                }
+                if with_cond != 0 && matches!(entry.op, Op::It(_) | Op::Pool) {
+                    continue;
+                }
+
+                let s_forms: &[&str] = if entry.flags & S != 0 { &["", "s"] } else { &[""] };
+
+                for (s, s_name) in s_forms.iter().enumerate() {
+                    for (w, w_name) in WIDTHS.iter().enumerate() {
+                        if entry.name.starts_with('.') && w != 0 {
+                            continue;
+                        }
+                        let insn  = i << 7 | s << 6 | w << 4 | cond as usize;
+                        let lower = format!("{}{}{}{}", entry.name, s_name, cond_name, w_name);
+                        let upper = lower.to_uppercase();
+                        index.entry(names.add(&lower)).or_insert(insn);
+                        index.entry(names.add(&upper)).or_insert(insn);
+                    }
+                }
+            }
+        }
+
+        Self { name, isa, index, state: RefCell::default() }
+    }
+
+    /// Returns whether the target has the ARMv7-M instructions.
+    pub fn v7(&self) -> bool {
+        self.isa & V7M != 0
+    }
+}
+
+impl Target for Thumb {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        2
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[
+            JUMP8, JUMP11, JUMP20, JUMP24, CB, PC8, PC12, MOVW, MOVT,
+            RelocKind::INT8, RelocKind::INT16, RelocKind::INT32,
+        ]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        operand::reg(name).map(u32::from)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        let insn = Insn {
+            entry: &ENTRIES[insn >> 7],
+            s:     insn >> 6 & 1 != 0,
+            width: match insn >> 4 & 3 {
+                1 => Width::Narrow,
+                2 => Width::Wide,
+                _ => Width::Any,
+            },
+            cond:  match (insn & 0xF) as u8 {
+                NO_COND => None,
+                cond    => Some(cond),
+            },
+        };
+
+        encode::encode(self, insn, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        decode::decode(self.v7(), bytes, addr, label)
+    }
+
+    fn begin_pass(&self) {
+        let mut state = self.state.borrow_mut();
+        state.it.clear();
+        state.count = 0;
+        state.prev  = std::mem::take(&mut state.addrs);
+        state.pool.clear();
+    }
+
+    fn end_section(&self, out: &mut dyn Emitter) {
+        self.place_pool(out);
+    }
+}
+
+/// Creates an ARMv6-M target.
+pub fn new_armv6m(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Thumb::new(names, "armv6m", V6M))
+}
+
+/// Creates an ARMv7-M target.
+pub fn new_armv7m(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Thumb::new(names, "armv7m", V6M | V7M))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Instruction with its suffixes.
+#[derive(Clone, Copy, Debug)]
+pub struct Insn {
+    /// Instruction table entry.
+    pub entry: &'static Entry,
+
+    /// Whether the mnemonic has the `s` suffix.
+    pub s: bool,
+
+    /// Width suffix.
+    pub width: Width,
+
+    /// Condition suffix, if any.
+    pub cond: Option<u8>,
+}
+
+/// Width suffixes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Width {
+    /// No suffix: choose the shortest encoding.
+    Any,
+
+    /// `.n`: require the 16-bit encoding.
+    Narrow,
+
+    /// `.w`: require the 32-bit encoding.
+    Wide,
+}
+
+/// Returns the suffix that selects the given condition code.
+pub fn cond_name(cond: u8) -> &'static str {
+    CONDS.iter().find(|c| c.1 == cond).map_or("", |c| c.0)
+}
+
+/// Returns the condition code with the given name, if any.
+pub fn cond_code(name: &str) -> Option<u8> {
+    CONDS.iter().find(|c| c.0.eq_ignore_ascii_case(name)).map(|c| c.1)
+}
+
+// ----------------------------------------------------------------------------
+
+/// State that persists across the statements of a pass.
+#[derive(Debug, Default)]
+struct State {
+    /// Conditions of the instructions remaining in the current IT block, in
+    /// reverse order.
+    it: Vec<u8>,
+
+    /// Literals awaiting placement.
+    pool: Vec<Literal>,
+
+    /// Count of literal references so far in the current pass.
+    count: usize,
+
+    /// Addresses of the literals placed in the current pass, by reference
+    /// number.
+    addrs: Vec<Option<u64>>,
+
+    /// Addresses of the literals placed in the previous pass, by reference
+    /// number.
+    prev: Vec<Option<u64>>,
+}
+
+/// Literal awaiting placement in a pool.
+#[derive(Debug)]
+struct Literal {
+    /// Index of the section that refers to the literal.
+    section: usize,
+
+    /// Value of the literal, if known at assembly time.
+    value: Option<i64>,
+
+    /// Expression for the value of the literal.
+    expr: Expr<Span>,
+
+    /// Reference numbers of the instructions that load the literal.
+    refs: Vec<usize>,
+}
+
+impl Thumb {
+    /// Adds a literal with the given `expr` and `value` to the pool of the
+    /// current section.  Returns the address of the literal in the previous
+    /// pass, if known.
+    fn add_literal(&self, expr: &Expr<Span>, value: Option<Value>, out: &dyn Emitter)
+        -> Option<u64>
+    {
+        let mut state = self.state.borrow_mut();
+        let section   = out.section();
+        let number    = state.count;
+        state.count  += 1;
+
+        let value = match value {
+            Some(Value::Const(v)) => Some(v),
+            _                     => None,
+        };
+
+        let existing = state.pool.iter_mut().find(|l| {
+            l.section == section && value.is_some() && l.value == value
+        });
+
+        match existing {
+            Some(literal) => literal.refs.push(number),
+            None => state.pool.push(Literal {
+                section, value, expr: expr.clone(), refs: vec![number]
+            }),
+        }
+
+        state.prev.get(number).copied().flatten()
+    }
+
+    /// Places the pending literals of the current section.
+    fn place_pool(&self, out: &mut dyn Emitter) {
+        let section = out.section();
+
+        let literals = {
+            let mut state = self.state.borrow_mut();
+            let (here, rest) = std::mem::take(&mut state.pool)
+                .into_iter()
+                .partition::<Vec<_>, _>(|l| l.section == section);
+            state.pool = rest;
+            here
+        };
+
+        if literals.is_empty() {
+            return;
+        }
+
+        out.align(4);
+
+        let start = out.here().wrapping_add(3) & !3;
+
+        for (i, literal) in literals.into_iter().enumerate() {
+            let addr = start.wrapping_add(4 * i as u64);
+
+            {
+                let mut state = self.state.borrow_mut();
+                for &number in &literal.refs {
+                    if state.addrs.len() <= number {
+                        state.addrs.resize(number + 1, None);
+                    }
+                    state.addrs[number] = Some(addr);
+                    if state.prev.get(number).copied().flatten() != Some(addr) {
+                        out.relayout();
+                    }
+                }
+            }
+
+            let value = match literal.value {
+                Some(value) => value,
+                None => match out.eval(&literal.expr) {
+                    Some(Value::Reloc(_)) => {
+                        out.reloc(&literal.expr, RelocKind::INT32);
+                        0
+                    },
+                    Some(Value::Const(v)) => v,
+                    None                  => 0,
+                },
+            };
+
+            if !fits(value, 32) {
+                out.error(*literal.expr.data(), &format!(
+                    "value {} does not fit in 32 bits", value
+                ));
+            }
+
+            out.emit(&(value as u32).to_le_bytes());
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// 9-bit conditional branch offset in a 16-bit instruction.
+pub const JUMP8: RelocKind = RelocKind { name: "thumb-jump8", size: 2, apply: apply_jump8 };
+
+/// 12-bit branch offset in a 16-bit instruction.
+pub const JUMP11: RelocKind = RelocKind { name: "thumb-jump11", size: 2, apply: apply_jump11 };
+
+/// 21-bit conditional branch offset in a 32-bit instruction.
+pub const JUMP20: RelocKind = RelocKind { name: "thumb-jump20", size: 4, apply: apply_jump20 };
+
+/// 25-bit branch or call offset in a 32-bit instruction.
+pub const JUMP24: RelocKind = RelocKind { name: "thumb-jump24", size: 4, apply: apply_jump24 };
+
+/// 7-bit forward offset of `cbz` or `cbnz`.
+pub const CB: RelocKind = RelocKind { name: "thumb-cb", size: 2, apply: apply_cb };
+
+/// 10-bit word-aligned offset from the aligned PC in a 16-bit instruction.
+pub const PC8: RelocKind = RelocKind { name: "thumb-pc8", size: 2, apply: apply_pc8 };
+
+/// 12-bit signed offset from the aligned PC in a 32-bit load.
+pub const PC12: RelocKind = RelocKind { name: "thumb-pc12", size: 4, apply: apply_pc12 };
+
+/// Low 16 bits of a value in `movw`.
+pub const MOVW: RelocKind = RelocKind { name: "thumb-movw", size: 4, apply: apply_movw };
+
+/// High 16 bits of a value in `movt`.
+pub const MOVT: RelocKind = RelocKind { name: "thumb-movt", size: 4, apply: apply_movt };
+
+fn branch_range(value: i64, bits: u32) -> Result<(), String> {
+    let min = -1i64 << (bits - 1);
+    match value & 1 == 0 && (min..=!min).contains(&value) {
+        true  => Ok(()),
+        false => Err(format!("branch offset {} out of range", value)),
+    }
+}
+
+fn apply_jump8(value: i64, field: u64) -> Result<u64, String> {
+    branch_range(value, 9)?;
+    Ok(field & 0xFF00 | (value >> 1) as u64 & 0xFF)
+}
+
+fn apply_jump11(value: i64, field: u64) -> Result<u64, String> {
+    branch_range(value, 12)?;
+    Ok(field & 0xF800 | (value >> 1) as u64 & 0x7FF)
+}
+
+fn apply_jump20(value: i64, field: u64) -> Result<u64, String> {
+    branch_range(value, 21)?;
+    let v   = value as u64;
+    let hw1 = field       & 0xFBC0 | (v >> 20 & 1) << 10 | v >> 12 & 0x3F;
+    let hw2 = field >> 16 & 0xD000 | (v >> 18 & 1) << 13 | (v >> 19 & 1) << 11 | v >> 1 & 0x7FF;
+    Ok(hw2 << 16 | hw1)
+}
+
+fn apply_jump24(value: i64, field: u64) -> Result<u64, String> {
+    branch_range(value, 25)?;
+    let v   = value as u64;
+    let s   = v >> 24 & 1;
+    let j1  = !(v >> 23 ^ s) & 1;
+    let j2  = !(v >> 22 ^ s) & 1;
+    let hw1 = field       & 0xF800 | s << 10 | v >> 12 & 0x3FF;
+    let hw2 = field >> 16 & 0xD000 | j1 << 13 | j2 << 11 | v >> 1 & 0x7FF;
+    Ok(hw2 << 16 | hw1)
+}
+
+fn apply_cb(value: i64, field: u64) -> Result<u64, String> {
+    match value & 1 == 0 && (0..=126).contains(&value) {
+        true  => Ok(field & 0xFD07 | (value as u64 >> 6 & 1) << 9 | (value as u64 >> 1 & 0x1F) << 3),
+        false => Err(format!("branch offset {} out of range", value)),
+    }
+}
+
+fn apply_pc8(value: i64, field: u64) -> Result<u64, String> {
+    match value & 3 == 0 && (0..=1020).contains(&value) {
+        true  => Ok(field & 0xFF00 | value as u64 >> 2),
+        false => Err(format!("PC-relative offset {} out of range", value)),
+    }
+}
+
+fn apply_pc12(value: i64, field: u64) -> Result<u64, String> {
+    match (-4095..=4095).contains(&value) {
+        true => {
+            let up  = (value >= 0) as u64;
+            let hw1 = field       & 0xFF7F | up << 7;
+            let hw2 = field >> 16 & 0xF000 | value.unsigned_abs();
+            Ok(hw2 << 16 | hw1)
+        },
+        false => Err(format!("PC-relative offset {} out of range", value)),
+    }
+}
+
+fn apply_movw(value: i64, field: u64) -> Result<u64, String> {
+    match fits(value, 32) {
+        true  => Ok(imm16_field(field, value as u64 & 0xFFFF)),
+        false => Err(format!("value {} does not fit in 32 bits", value)),
+    }
+}
+
+fn apply_movt(value: i64, field: u64) -> Result<u64, String> {
+    match fits(value, 32) {
+        true  => Ok(imm16_field(field, value as u64 >> 16 & 0xFFFF)),
+        false => Err(format!("value {} does not fit in 32 bits", value)),
+    }
+}
+
+/// Merges a 16-bit immediate into the field of a `movw` or `movt`
+/// instruction.
+fn imm16_field(field: u64, imm: u64) -> u64 {
+    let hw1 = field       & 0xFBF0 | (imm >> 11 & 1) << 10 | imm >> 12;
+    let hw2 = field >> 16 & 0x8F00 | (imm >> 8 & 7) << 12 | imm & 0xFF;
+    hw2 << 16 | hw1
+}
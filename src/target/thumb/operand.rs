@@ -0,0 +1,316 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Operand parsing.
+
+use crate::lang::ast::*;
+use crate::name::NameTable;
+use crate::target::{flatten_sum, sum, Emitter, Value};
+
+use super::table::Shift;
+
+// ----------------------------------------------------------------------------
+
+/// Stack pointer.
+pub const SP: u8 = 13;
+
+/// Link register.
+pub const LR: u8 = 14;
+
+/// Program counter.
+pub const PC: u8 = 15;
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+pub enum Operand {
+    /// Register.
+    Reg(u8),
+
+    /// Register shifted by a constant amount: `r1 << 2`.
+    Shifted(u8, Shift, u32),
+
+    /// Register list, as a mask with bit 0 for `r0` and bit 15 for `pc`.
+    List(u16),
+
+    /// Memory operand.
+    Mem(Mem),
+
+    /// Literal to load from a pool: `=value`.
+    Lit(Expr<Span>),
+
+    /// Immediate value or target address.
+    Imm(Expr<Span>),
+}
+
+/// Memory operand: `[base + index << shift + offset]`, with optional
+/// writeback.
+#[derive(Clone, Debug)]
+pub struct Mem {
+    /// Location of the operand.
+    pub span: Span,
+
+    /// Base register.
+    pub base: u8,
+
+    /// Index register and its left shift, if any.
+    pub index: Option<(u8, u32)>,
+
+    /// Offset, if any.
+    pub offset: Option<Expr<Span>>,
+
+    /// Whether the operand writes the address back to the base register.
+    pub writeback: bool,
+}
+
+/// Parses the given instruction argument.
+pub fn operand(arg: &Arg<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let expr = match *arg {
+        Arg::Expr(ref e) => e,
+        Arg::Unknown(span) => {
+            out.error(span, "expected: operand");
+            return None;
+        },
+    };
+
+    if let Some(r) = reg_expr(out.names(), expr) {
+        return Some(Operand::Reg(r));
+    }
+
+    if let Some(op) = shifted(expr, out) {
+        return op;
+    }
+
+    if let Some(list) = list(out.names(), expr) {
+        return Some(Operand::List(list));
+    }
+
+    match *expr {
+        Expr::Deref(span, ref inner, writeback) => memory(span, inner, writeback, out),
+        Expr::Unary(_, UnOp::Literal, ref value) => Some(Operand::Lit((**value).clone())),
+        _ => Some(Operand::Imm(expr.clone())),
+    }
+}
+
+/// Parses a shifted register operand.  Returns `None` if the expression is
+/// not a shifted register.
+fn shifted(expr: &Expr<Span>, out: &mut dyn Emitter) -> Option<Option<Operand>> {
+    let (reg, shift, amount) = match *expr {
+        Expr::Binary(_, BinOp::Shl, ref lhs, ref rhs) => (&**lhs, Shift::Lsl, &**rhs),
+        Expr::Binary(_, BinOp::Shr, ref lhs, ref rhs) => (&**lhs, Shift::Lsr, &**rhs),
+        Expr::Call(_, ref func, ref args) => {
+            let shift = match **func {
+                Expr::Ident(_, name) => match out.names()[name].to_ascii_lowercase().as_str() {
+                    "lsl" => Shift::Lsl,
+                    "lsr" => Shift::Lsr,
+                    "asr" => Shift::Asr,
+                    "ror" => Shift::Ror,
+                    _     => return None,
+                },
+                _ => return None,
+            };
+            match &args[..] {
+                [reg, amount] => (reg, shift, amount),
+                _ => {
+                    out.error(*expr.data(), "expected: register and shift amount");
+                    return Some(None);
+                },
+            }
+        },
+        _ => return None,
+    };
+
+    let reg    = reg_expr(out.names(), reg)?;
+    let amount = shift_amount(shift, amount, out)?;
+    Some(Some(Operand::Shifted(reg, shift, amount)))
+}
+
+/// Evaluates the amount of a shift of the given kind.
+fn shift_amount(shift: Shift, expr: &Expr<Span>, out: &mut dyn Emitter) -> Option<u32> {
+    let max = match shift {
+        Shift::Lsl | Shift::Ror => 31,
+        Shift::Lsr | Shift::Asr => 32,
+    };
+
+    match out.eval(expr)? {
+        Value::Const(n) if (0..=max).contains(&n) => Some(n as u32),
+        Value::Const(n) => {
+            out.error(*expr.data(), &format!("shift amount {} out of range", n));
+            None
+        },
+        Value::Reloc(_) => {
+            out.error(*expr.data(), "expected: constant shift amount");
+            None
+        },
+    }
+}
+
+/// Parses the contents of a memory operand `[...]`.
+fn memory(span: Span, inner: &Expr<Span>, writeback: bool, out: &mut dyn Emitter)
+    -> Option<Operand>
+{
+    let mut terms = vec![];
+    flatten_sum(inner, false, &mut terms);
+
+    let mut base  = None;
+    let mut index = None;
+    let mut rest  = vec![];
+
+    for (neg, term) in terms {
+        let reg = match *term {
+            Expr::Binary(_, BinOp::Mul, ref lhs, ref rhs) => {
+                reg_expr(out.names(), lhs).map(|r| (r, Some(&**rhs)))
+            },
+            _ => reg_expr(out.names(), term).map(|r| (r, None)),
+        };
+
+        match reg {
+            None => rest.push((neg, term)),
+            Some(_) if neg => {
+                out.error(*term.data(), "invalid memory operand");
+                return None;
+            },
+            Some((r, None)) if base.is_none() => base = Some(r),
+            Some((r, amount)) if index.is_none() && base.is_some() => {
+                let shift = match amount {
+                    Some(e) => scale(e, out)?,
+                    None    => 0,
+                };
+                index = Some((r, shift));
+            },
+            Some(_) => {
+                out.error(*term.data(), "invalid memory operand");
+                return None;
+            },
+        }
+    }
+
+    let base = match base {
+        Some(base) => base,
+        None => {
+            out.error(span, "expected: base register");
+            return None;
+        },
+    };
+
+    Some(Operand::Mem(Mem { span, base, index, offset: sum(&rest), writeback }))
+}
+
+/// Evaluates the scale of an index register and returns the corresponding
+/// shift amount.
+fn scale(expr: &Expr<Span>, out: &mut dyn Emitter) -> Option<u32> {
+    match out.eval(expr)? {
+        Value::Const(n @ (1 | 2 | 4 | 8)) => Some(n.trailing_zeros()),
+        _ => {
+            out.error(*expr.data(), "expected: scale 1, 2, 4, or 8");
+            None
+        },
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the register that the given expression names, if any.
+pub fn reg_expr(names: &NameTable, expr: &Expr<Span>) -> Option<u8> {
+    match *expr {
+        Expr::Ident(_, name) => reg(&names[name]),
+        _                    => None,
+    }
+}
+
+/// Returns the register with the given name, if any.
+pub fn reg(name: &str) -> Option<u8> {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        "sp" => return Some(SP),
+        "lr" => return Some(LR),
+        "pc" => return Some(PC),
+        "ip" => return Some(12),
+        _    => (),
+    }
+    let digits = name.strip_prefix('r')?;
+    let n      = digits.parse::<u8>().ok()?;
+    (n < 16 && digits == n.to_string()).then_some(n)
+}
+
+/// Parses a register list such as `r0-r3/lr`.  Returns `None` if the
+/// expression is not a register list.
+fn list(names: &NameTable, expr: &Expr<Span>) -> Option<u16> {
+    let mut items = vec![];
+    flatten_list(names, expr, &mut items)?;
+
+    let mut mask  = 0u16;
+    let mut items = items.into_iter().peekable();
+
+    while let Some(item) = items.next() {
+        let first = match item {
+            ListItem::Reg(r) => r,
+            _                => return None,
+        };
+
+        let last = match items.peek() {
+            Some(ListItem::Range) => {
+                items.next();
+                match items.next() {
+                    Some(ListItem::Reg(r)) if r >= first => r,
+                    _                                    => return None,
+                }
+            },
+            _ => first,
+        };
+
+        for r in first..=last {
+            mask |= 1 << r;
+        }
+
+        match items.next() {
+            None | Some(ListItem::Slash) => (),
+            _                            => return None,
+        }
+    }
+
+    Some(mask)
+}
+
+/// Item of a register list.
+enum ListItem {
+    Reg(u8),
+    Range,
+    Slash,
+}
+
+/// Flattens a register list expression into its items in source order.
+/// Returns `None` if the expression is not a register list.
+fn flatten_list(names: &NameTable, expr: &Expr<Span>, items: &mut Vec<ListItem>) -> Option<()> {
+    match *expr {
+        Expr::Ident(..) => {
+            items.push(ListItem::Reg(reg_expr(names, expr)?));
+        },
+        Expr::Binary(_, op, ref lhs, ref rhs) => {
+            let sep = match op {
+                BinOp::Sub | BinOp::Range => ListItem::Range,
+                BinOp::Div                => ListItem::Slash,
+                _                         => return None,
+            };
+            flatten_list(names, lhs, items)?;
+            items.push(sep);
+            flatten_list(names, rhs, items)?;
+        },
+        _ => return None,
+    }
+    Some(())
+}
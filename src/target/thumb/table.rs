@@ -0,0 +1,352 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction table.
+
+// ----------------------------------------------------------------------------
+
+/// Entry flag: the instruction has a flag-setting form with an `s` suffix.
+pub const S: u8 = 1 << 0;
+
+/// Entry flag: the instruction requires ARMv7-M.
+pub const V7: u8 = 1 << 1;
+
+/// Instruction table entry.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    /// Mnemonic, without suffixes.
+    pub name: &'static str,
+
+    /// Instruction kind.
+    pub op: Op,
+
+    /// Flags: [`S`] and [`V7`].
+    pub flags: u8,
+}
+
+/// Instruction kinds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    /// `add`
+    Add,
+
+    /// `sub`
+    Sub,
+
+    /// `rsb`
+    Rsb,
+
+    /// Two- or three-operand logic or carry arithmetic, with the opcode of the
+    /// 32-bit form and the opcode of the 16-bit form, if any.
+    Logic(u8, Option<u8>),
+
+    /// `mov`
+    Mov,
+
+    /// `mvn`
+    Mvn,
+
+    /// `neg`, an alias for `rsb` with a zero operand.
+    Neg,
+
+    /// Comparison or test, with the opcode of the 32-bit form.
+    Compare(u8),
+
+    /// Shift or rotate.
+    Shift(Shift),
+
+    /// `mul`
+    Mul,
+
+    /// `mla` (false) or `mls` (true).
+    Mla(bool),
+
+    /// Long multiply, with the first halfword of the encoding.
+    MulLong(u16),
+
+    /// `sdiv` or `udiv`, with the first halfword of the encoding.
+    Div(u16),
+
+    /// `addw` (false) or `subw` (true).
+    AddW(bool),
+
+    /// `movw` (false) or `movt` (true).
+    MovW(bool),
+
+    /// Register-to-register operation with 16-bit and 32-bit forms, such as
+    /// `rev`, with the 16-bit opcode, if any, and the 32-bit opcode.
+    Unary(Option<u16>, u16, u16),
+
+    /// `ubfx` or `sbfx`, with the first halfword of the encoding.
+    Extract(u16),
+
+    /// `bfi`
+    Bfi,
+
+    /// `bfc`
+    Bfc,
+
+    /// `adr`
+    Adr,
+
+    /// Load of the given size.
+    Load(Size),
+
+    /// Store of the given size.
+    Store(Size),
+
+    /// `ldrd` (true) or `strd` (false).
+    Dual(bool),
+
+    /// `ldrex`
+    Ldrex,
+
+    /// `strex`
+    Strex,
+
+    /// `ldm` (true) or `stm` (false), incrementing after (false) or
+    /// decrementing before (true).
+    Multiple(bool, bool),
+
+    /// `push`
+    Push,
+
+    /// `pop`
+    Pop,
+
+    /// `b`
+    B,
+
+    /// `bl`
+    Bl,
+
+    /// `bx` (false) or `blx` (true).
+    Bx(bool),
+
+    /// `cbz` (false) or `cbnz` (true).
+    Cbz(bool),
+
+    /// `tbb` (false) or `tbh` (true).
+    Tb(bool),
+
+    /// `it` and its variants, with the `t`/`e` pattern of the instructions
+    /// after the first.
+    It(&'static str),
+
+    /// Hint, with the hint number.
+    Hint(u8),
+
+    /// Instruction with an 8-bit immediate operand, with the opcode.
+    Imm8(u16),
+
+    /// `cpsie` (false) or `cpsid` (true).
+    Cps(bool),
+
+    /// Barrier, with the second halfword of the encoding.
+    Barrier(u16),
+
+    /// Instruction without operands, with the 32-bit encoding.
+    Fixed(u16, u16),
+
+    /// `mrs`
+    Mrs,
+
+    /// `msr`
+    Msr,
+
+    /// `.pool`: place the pending literal pool.
+    Pool,
+}
+
+/// Shift kinds, in order of their encoding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shift {
+    /// Logical shift left.
+    Lsl,
+
+    /// Logical shift right.
+    Lsr,
+
+    /// Arithmetic shift right.
+    Asr,
+
+    /// Rotate right.
+    Ror,
+}
+
+/// Memory access sizes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Size {
+    /// Word.
+    W,
+
+    /// Unsigned halfword.
+    H,
+
+    /// Unsigned byte.
+    B,
+
+    /// Signed halfword.
+    Sh,
+
+    /// Signed byte.
+    Sb,
+}
+
+impl Size {
+    /// Returns the size in bytes.
+    pub fn bytes(self) -> u32 {
+        match self {
+            Size::W           => 4,
+            Size::H | Size::Sh => 2,
+            Size::B | Size::Sb => 1,
+        }
+    }
+}
+
+const fn e(name: &'static str, op: Op, flags: u8) -> Entry {
+    Entry { name, op, flags }
+}
+
+/// Instruction table.
+pub static ENTRIES: &[Entry] = &[
+    // Arithmetic and logic
+    e("add",    Op::Add,                            S),
+    e("sub",    Op::Sub,                            S),
+    e("rsb",    Op::Rsb,                            S),
+    e("adc",    Op::Logic(10, Some(5)),             S),
+    e("sbc",    Op::Logic(11, Some(6)),             S),
+    e("and",    Op::Logic(0,  Some(0)),             S),
+    e("eor",    Op::Logic(4,  Some(1)),             S),
+    e("orr",    Op::Logic(2,  Some(12)),            S),
+    e("bic",    Op::Logic(1,  Some(14)),            S),
+    e("orn",    Op::Logic(3,  None),                S | V7),
+    e("mov",    Op::Mov,                            S),
+    e("mvn",    Op::Mvn,                            S),
+    e("neg",    Op::Neg,                            S),
+    e("cmp",    Op::Compare(13),                    0),
+    e("cmn",    Op::Compare(8),                     0),
+    e("tst",    Op::Compare(0),                     0),
+    e("teq",    Op::Compare(4),                     V7),
+    e("lsl",    Op::Shift(Shift::Lsl),              S),
+    e("lsr",    Op::Shift(Shift::Lsr),              S),
+    e("asr",    Op::Shift(Shift::Asr),              S),
+    e("ror",    Op::Shift(Shift::Ror),              S),
+    e("mul",    Op::Mul,                            S),
+    e("mla",    Op::Mla(false),                     V7),
+    e("mls",    Op::Mla(true),                      V7),
+    e("smull",  Op::MulLong(0xFB80),                V7),
+    e("umull",  Op::MulLong(0xFBA0),                V7),
+    e("smlal",  Op::MulLong(0xFBC0),                V7),
+    e("umlal",  Op::MulLong(0xFBE0),                V7),
+    e("sdiv",   Op::Div(0xFB90),                    V7),
+    e("udiv",   Op::Div(0xFBB0),                    V7),
+    e("addw",   Op::AddW(false),                    V7),
+    e("subw",   Op::AddW(true),                     V7),
+    e("movw",   Op::MovW(false),                    V7),
+    e("movt",   Op::MovW(true),                     V7),
+    e("sxth",   Op::Unary(Some(0xB200), 0xFA0F, 0xF080), 0),
+    e("sxtb",   Op::Unary(Some(0xB240), 0xFA4F, 0xF080), 0),
+    e("uxth",   Op::Unary(Some(0xB280), 0xFA1F, 0xF080), 0),
+    e("uxtb",   Op::Unary(Some(0xB2C0), 0xFA5F, 0xF080), 0),
+    e("rev",    Op::Unary(Some(0xBA00), 0xFA90, 0xF080), 0),
+    e("rev16",  Op::Unary(Some(0xBA40), 0xFA90, 0xF090), 0),
+    e("revsh",  Op::Unary(Some(0xBAC0), 0xFA90, 0xF0B0), 0),
+    e("rbit",   Op::Unary(None,         0xFA90, 0xF0A0), V7),
+    e("clz",    Op::Unary(None,         0xFAB0, 0xF080), V7),
+    e("ubfx",   Op::Extract(0xF3C0),                V7),
+    e("sbfx",   Op::Extract(0xF340),                V7),
+    e("bfi",    Op::Bfi,                            V7),
+    e("bfc",    Op::Bfc,                            V7),
+    e("adr",    Op::Adr,                            0),
+
+    // Loads and stores
+    e("ldr",    Op::Load(Size::W),                  0),
+    e("ldrh",   Op::Load(Size::H),                  0),
+    e("ldrb",   Op::Load(Size::B),                  0),
+    e("ldrsh",  Op::Load(Size::Sh),                 0),
+    e("ldrsb",  Op::Load(Size::Sb),                 0),
+    e("str",    Op::Store(Size::W),                 0),
+    e("strh",   Op::Store(Size::H),                 0),
+    e("strb",   Op::Store(Size::B),                 0),
+    e("ldrd",   Op::Dual(true),                     V7),
+    e("strd",   Op::Dual(false),                    V7),
+    e("ldrex",  Op::Ldrex,                          V7),
+    e("strex",  Op::Strex,                          V7),
+    e("ldm",    Op::Multiple(true,  false),         0),
+    e("ldmia",  Op::Multiple(true,  false),         0),
+    e("ldmfd",  Op::Multiple(true,  false),         0),
+    e("ldmdb",  Op::Multiple(true,  true),          V7),
+    e("ldmea",  Op::Multiple(true,  true),          V7),
+    e("stm",    Op::Multiple(false, false),         0),
+    e("stmia",  Op::Multiple(false, false),         0),
+    e("stmea",  Op::Multiple(false, false),         0),
+    e("stmdb",  Op::Multiple(false, true),          V7),
+    e("stmfd",  Op::Multiple(false, true),          V7),
+    e("push",   Op::Push,                           0),
+    e("pop",    Op::Pop,                            0),
+
+    // Branches
+    e("b",      Op::B,                              0),
+    e("bl",     Op::Bl,                             0),
+    e("bx",     Op::Bx(false),                      0),
+    e("blx",    Op::Bx(true),                       0),
+    e("cbz",    Op::Cbz(false),                     V7),
+    e("cbnz",   Op::Cbz(true),                      V7),
+    e("tbb",    Op::Tb(false),                      V7),
+    e("tbh",    Op::Tb(true),                       V7),
+
+    // If-then
+    e("it",     Op::It(""),                         V7),
+    e("itt",    Op::It("t"),                        V7),
+    e("ite",    Op::It("e"),                        V7),
+    e("ittt",   Op::It("tt"),                       V7),
+    e("itte",   Op::It("te"),                       V7),
+    e("itet",   Op::It("et"),                       V7),
+    e("itee",   Op::It("ee"),                       V7),
+    e("itttt",  Op::It("ttt"),                      V7),
+    e("ittte",  Op::It("tte"),                      V7),
+    e("ittet",  Op::It("tet"),                      V7),
+    e("ittee",  Op::It("tee"),                      V7),
+    e("itett",  Op::It("ett"),                      V7),
+    e("itete",  Op::It("ete"),                      V7),
+    e("iteet",  Op::It("eet"),                      V7),
+    e("iteee",  Op::It("eee"),                      V7),
+
+    // System
+    e("nop",    Op::Hint(0),                        0),
+    e("yield",  Op::Hint(1),                        0),
+    e("wfe",    Op::Hint(2),                        0),
+    e("wfi",    Op::Hint(3),                        0),
+    e("sev",    Op::Hint(4),                        0),
+    e("svc",    Op::Imm8(0xDF00),                   0),
+    e("bkpt",   Op::Imm8(0xBE00),                   0),
+    e("udf",    Op::Imm8(0xDE00),                   0),
+    e("cpsie",  Op::Cps(false),                     0),
+    e("cpsid",  Op::Cps(true),                      0),
+    e("dsb",    Op::Barrier(0x8F40),                0),
+    e("dmb",    Op::Barrier(0x8F50),                0),
+    e("isb",    Op::Barrier(0x8F60),                0),
+    e("clrex",  Op::Fixed(0xF3BF, 0x8F2F),          V7),
+    e("mrs",    Op::Mrs,                            0),
+    e("msr",    Op::Msr,                            0),
+
+    // Directives
+    e(".pool",  Op::Pool,                           0),
+    e(".ltorg", Op::Pool,                           0),
+];
@@ -0,0 +1,374 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, le16};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u16> = Bench { origin: 0x200, split: le16 };
+
+#[test]
+fn armv6m() {
+    BENCH.check("armv6m", &[
+        ("nop",                                 &[0xBF00]),
+        ("WFI",                                 &[0xBF30]),
+        ("movs r0, 42",                         &[0x202A]),
+        ("mov r8, r1",                          &[0x4688]),
+        ("movs r0, r1",                         &[0x0008]),
+        ("adds r0, r1, r2",                     &[0x1888]),
+        ("adds r0, r1, 3",                      &[0x1CC8]),
+        ("adds r0, 200",                        &[0x30C8]),
+        ("subs r0, r1, -3",                     &[0x1CC8]),
+        ("add sp, sp, 16",                      &[0xB004]),
+        ("sub sp, 16",                          &[0xB084]),
+        ("add r0, sp, 8",                       &[0xA802]),
+        ("add r0, r8",                          &[0x4440]),
+        ("ands r0, r1",                         &[0x4008]),
+        ("eors r0, r1, r0",                     &[0x4048]),
+        ("muls r0, r1, r0",                     &[0x4348]),
+        ("lsls r0, r1, 2",                      &[0x0088]),
+        ("lsrs r0, r1, 32",                     &[0x0808]),
+        ("asrs r0, r1",                         &[0x4108]),
+        ("negs r0, r1",                         &[0x4248]),
+        ("mvns r0, r1",                         &[0x43C8]),
+        ("cmp r0, 5",                           &[0x2805]),
+        ("cmp r0, r1",                          &[0x4288]),
+        ("cmp r8, r0",                          &[0x4580]),
+        ("tst r0, r1",                          &[0x4208]),
+        ("sxth r0, r1",                         &[0xB208]),
+        ("rev r0, r1",                          &[0xBA08]),
+        ("ldr r0, [r1 + 4]",                    &[0x6848]),
+        ("str r0, [sp + 8]",                    &[0x9002]),
+        ("strh r0, [r1 + 2]",                   &[0x8048]),
+        ("ldrb r0, [r1 + r2]",                  &[0x5C88]),
+        ("ldrsh r0, [r1 + r2]",                 &[0x5E88]),
+        ("push r4-r7/lr",                       &[0xB5F0]),
+        ("pop r4-r7/pc",                        &[0xBDF0]),
+        ("ldm [r0]!, r1/r2",                    &[0xC806]),
+        ("ldm [r0], r0/r1",                     &[0xC803]),
+        ("stm [r0]!, r1/r2",                    &[0xC006]),
+        ("bx lr",                               &[0x4770]),
+        ("blx r3",                              &[0x4798]),
+        ("svc 1",                               &[0xDF01]),
+        ("cpsid i",                             &[0xB672]),
+        ("cpsie i",                             &[0xB662]),
+        ("dsb",                                 &[0xF3BF, 0x8F4F]),
+        ("isb sy",                              &[0xF3BF, 0x8F6F]),
+        ("mrs r0, primask",                     &[0xF3EF, 0x8010]),
+        ("msr control, r0",                     &[0xF380, 0x8814]),
+        ("adr r0, x'208",                       &[0xA001]),
+        ("b x'200",                             &[0xE7FE]),
+        ("beq x'200",                           &[0xD0FE]),
+        ("bl x'200",                            &[0xF7FF, 0xFFFE]),
+    ]);
+}
+
+#[test]
+fn armv7m() {
+    BENCH.check("armv7m", &[
+        ("add r0, r1, r2",                      &[0xEB01, 0x0002]),
+        ("add.w r0, r1, 1",                     &[0xF101, 0x0001]),
+        ("add r0, r1, 4095",                    &[0xF601, 0x70FF]),
+        ("adds r0, r1, r2 << 3",                &[0xEB11, 0x00C2]),
+        ("orr r0, r1, x'ff00ff00",              &[0xF041, 0x20FF]),
+        ("mov r0, 256",                         &[0xF44F, 0x7080]),
+        ("mov r0, -1",                          &[0xF04F, 0x30FF]),
+        ("mov r0, x'1234",                      &[0xF241, 0x2034]),
+        ("mov r0, -257",                        &[0xF46F, 0x7080]),
+        ("movw r0, %lo16(x'12345678)",          &[0xF245, 0x6078]),
+        ("movt r0, %hi16(x'12345678)",          &[0xF2C1, 0x2034]),
+        ("mov r0, r1 >> 4",                     &[0xEA4F, 0x1011]),
+        ("ror r0, r1, 8",                       &[0xEA4F, 0x2031]),
+        ("lsl r0, r1, r2",                      &[0xFA01, 0xF002]),
+        ("orn r0, r1, r2",                      &[0xEA61, 0x0002]),
+        ("cmp r0, -1",                          &[0xF110, 0x0F01]),
+        ("teq r0, 1",                           &[0xF090, 0x0F01]),
+        ("mul r0, r1, r2",                      &[0xFB01, 0xF002]),
+        ("mla r0, r1, r2, r3",                  &[0xFB01, 0x3002]),
+        ("umull r0, r1, r2, r3",                &[0xFBA2, 0x0103]),
+        ("sdiv r0, r1, r2",                     &[0xFB91, 0xF0F2]),
+        ("ubfx r0, r1, 4, 8",                   &[0xF3C1, 0x1007]),
+        ("bfc r0, 8, 4",                        &[0xF36F, 0x200B]),
+        ("clz r0, r1",                          &[0xFAB1, 0xF081]),
+        ("sxth.w r0, r1",                       &[0xFA0F, 0xF081]),
+        ("ldr r0, [r1 + 4095]",                 &[0xF8D1, 0x0FFF]),
+        ("ldr r0, [r1 - 4]",                    &[0xF851, 0x0C04]),
+        ("ldr r0, [r1 + 4]!",                   &[0xF851, 0x0F04]),
+        ("ldr r0, [r1], 4",                     &[0xF851, 0x0B04]),
+        ("ldr r0, [r1 + r2*4]",                 &[0xF851, 0x0022]),
+        ("ldrsb r8, [r1]",                      &[0xF991, 0x8000]),
+        ("ldrd r0, r1, [r2 + 8]",               &[0xE9D2, 0x0102]),
+        ("ldrex r0, [r1]",                      &[0xE851, 0x0F00]),
+        ("strex r2, r0, [r1]",                  &[0xE841, 0x0200]),
+        ("push r8",                             &[0xF84D, 0x8D04]),
+        ("push r4/r8",                          &[0xE92D, 0x0110]),
+        ("stmdb [sp]!, r4/lr",                  &[0xE92D, 0x4010]),
+        ("tbb [r0 + r1]",                       &[0xE8D0, 0xF001]),
+        ("tbh [r0 + r1*2]",                     &[0xE8D0, 0xF011]),
+        ("cbz r0, end\nnop\nnop\nend:",         &[0xB108, 0xBF00, 0xBF00]),
+        ("nop.w",                               &[0xF3AF, 0x8000]),
+    ]);
+}
+
+#[test]
+fn it_blocks() {
+    BENCH.check("armv7m", &[
+        ("it eq\nmoveq r0, 1",                  &[0xBF08, 0x2001]),
+        ("ite eq\nmoveq r0, 1\nmovne r0, 2",    &[0xBF0C, 0x2001, 0x2002]),
+        ("itte ne\naddne r0, r1\nnopne\nnopeq", &[0xBF1A, 0x1840, 0xBF00, 0xBF00]),
+        ("it ne\naddsne r0, r1, r2",            &[0xBF18, 0xEB11, 0x0002]),
+        ("it eq\nbeq x'200",                    &[0xBF08, 0xE7FD]),
+    ]);
+}
+
+#[test]
+fn branch_relaxation() {
+    BENCH.check("armv7m", &[
+        ("b x'1000",                            &[0xF000, 0xBEFE]),
+        ("beq x'1000",                          &[0xF000, 0x86FE]),
+        ("beq x'300",                           &[0xD07E]),
+        ("b.w x'200",                           &[0xF7FF, 0xBFFE]),
+    ]);
+
+    // A branch over code that grows past the narrow range takes the wide
+    // form, which moves its target
+    let (bytes, session) = BENCH.assemble("armv7m", "beq end\n.skip 258\nend:");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(BENCH.words(&bytes[..4]), [0xF000, 0x8081]);
+
+    // A width suffix overrides relaxation, and the branches after it still
+    // relax
+    let (bytes, session) = BENCH.assemble("armv7m", "a: b.w a\nb.n a\nb end\n.skip 4000\nend:");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(BENCH.words(&bytes[..10]), [0xF7FF, 0xBFFE, 0xE7FC, 0xF000, 0xBFD0]);
+}
+
+#[test]
+fn literal_pool() {
+    BENCH.check("armv6m", &[
+        ("ldr r0, =x'12345678\n.pool",          &[0x4800, 0x0000, 0x5678, 0x1234]),
+        ("ldr r0, =1\nldr r1, =1\nnop",         &[0x4801, 0x4901, 0xBF00, 0x0000, 0x0001, 0x0000]),
+        ("ldr r0, =1\nldr r1, =2\n.ltorg\nnop", &[0x4800, 0x4901, 0x0001, 0x0000, 0x0002, 0x0000, 0xBF00]),
+    ]);
+    BENCH.check("armv7m", &[
+        ("ldr.w r0, =5",                        &[0xF8DF, 0x0000, 0x0005, 0x0000]),
+        ("ldr r8, =5",                          &[0xF8DF, 0x8000, 0x0005, 0x0000]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("armv7m").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  bl func
+                ldr r0, =msg
+        func:   bx lr
+        msg:    .int8 0
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(program.sections[0].data, [
+        0x00, 0xF0, 0x01, 0xF8, 0x01, 0x48, 0x70, 0x47,
+        0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
+    ]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("armv6m", "add r0, r1, r2"),       "16-bit encoding outside IT block sets flags; add 's' suffix");
+    assert_eq!(BENCH.error("armv6m", "movs r0, 256"),         "operands require a 32-bit encoding, which the target lacks");
+    assert_eq!(BENCH.error("armv6m", "b x'1000"),             "branch offset 3580 out of range");
+    assert_eq!(BENCH.error("armv6m", "it eq"),                "unknown instruction 'it'");
+    assert_eq!(BENCH.error("armv6m", "ldr r0, =1\n.skip 1100"), "literal pool out of range; place a .pool closer");
+    assert_eq!(BENCH.error("armv7m", "moveq r0, 1"),          "conditional instruction outside IT block");
+    assert_eq!(BENCH.error("armv7m", "it eq\nmovne r0, 1"),   "instruction in IT block requires condition 'eq'");
+    assert_eq!(BENCH.error("armv7m", "it eq\nmulseq r0, r1, r0"), "16-bit encoding in IT block does not set flags; remove 's' suffix");
+    assert_eq!(BENCH.error("armv7m", "itt eq\nbeq x'200\naddeq r0, r1"), "branch must be the last instruction in an IT block");
+    assert_eq!(BENCH.error("armv7m", "mov.n r0, 256"),        "no 16-bit encoding for operands");
+    assert_eq!(BENCH.error("armv7m", "ldr r0, [r1 + 4096]"),  "offset 4096 out of range");
+    assert_eq!(BENCH.error("armv7m", "lsls r0, r1, 33"),      "shift amount 33 out of range");
+    assert_eq!(BENCH.error("armv7m", "mov r0, x'12345678"),   "immediate value 305419896 cannot be encoded");
+    assert_eq!(BENCH.error("armv7m", "add r0, r1, [r2]"),     "invalid operands");
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("armv6m", &[
+            "nop",
+            "wfi",
+            "movs r0, x'2A",
+            "mov r8, r1",
+            "movs r0, r1",
+            "adds r0, r1, r2",
+            "adds r0, r1, 3",
+            "adds r0, x'C8",
+            "subs r0, r1, 3",
+            "add sp, x'10",
+            "sub sp, x'10",
+            "add r0, sp, 8",
+            "add r0, r8",
+            "ands r0, r1",
+            "muls r0, r1",
+            "lsls r0, r1, 2",
+            "lsrs r0, r1, 32",
+            "asrs r0, r1",
+            "negs r0, r1",
+            "mvns r0, r1",
+            "cmp r0, 5",
+            "cmp r0, r1",
+            "cmp r8, r0",
+            "tst r0, r1",
+            "sxth r0, r1",
+            "rev r0, r1",
+            "ldr r0, [r1 + 4]",
+            "str r0, [sp + 8]",
+            "strh r0, [r1 + 2]",
+            "ldrb r0, [r1]",
+            "ldrb r0, [r1 + r2]",
+            "ldrsh r0, [r1 + r2]",
+            "ldr r0, x'208",
+            "push r4-r7/lr",
+            "pop r4/r5/pc",
+            "ldm [r0]!, r1/r2",
+            "ldm [r0], r0/r1",
+            "stm [r0]!, r1/r2",
+            "bx lr",
+            "blx r3",
+            "svc 1",
+            "bkpt x'AB",
+            "cpsid i",
+            "cpsie if",
+            "dsb",
+            "isb",
+            "mrs r0, primask",
+            "msr control, r0",
+            "adr r0, x'208",
+            "b start",
+            "beq start",
+            "bl start",
+            "bl x'10000",
+        ][..]),
+        ("armv7m", &[
+            "add r0, r1, r2",
+            "add r0, r1, 1",
+            "adds.w r0, r1, 1",
+            "addw r0, r1, x'FFF",
+            "adds r0, r1, r2 << 3",
+            "orr r0, r1, x'FF00FF00",
+            "and r0, r1, x'80000000",
+            "mov r0, x'100",
+            "mov r0, r1",
+            "mov.w r0, r1",
+            "movs.w r0, r1",
+            "mvn r0, 0",
+            "movw r0, x'1234",
+            "movt r0, x'1234",
+            "lsr r0, r1, 4",
+            "ror r0, r1, 8",
+            "lsl r0, r1, r2",
+            "orn r0, r1, r2",
+            "eor r0, r1, asr(r2, 32)",
+            "rsb r0, r1, x'10",
+            "cmp.w r0, 1",
+            "cmp r0, x'100",
+            "cmn r0, 1",
+            "tst r0, x'FF00",
+            "teq r0, 1",
+            "mul r0, r1, r2",
+            "mla r0, r1, r2, r3",
+            "mls r0, r1, r2, r3",
+            "umull r0, r1, r2, r3",
+            "sdiv r0, r1, r2",
+            "ubfx r0, r1, 4, 8",
+            "bfi r0, r1, 4, 8",
+            "bfc r0, 8, 4",
+            "clz r0, r1",
+            "rbit r0, r1",
+            "sxth.w r0, r1",
+            "uxtb r8, r1",
+            "ldr r0, [r1 + x'FFF]",
+            "ldr.w r0, [r1 + 4]",
+            "ldr r0, [r1 - 4]",
+            "ldr r0, [r1 + 4]!",
+            "ldr r0, [r1], 4",
+            "ldr r0, [r1], -4",
+            "ldr r0, [r1 + r2*4]",
+            "ldrsb r8, [r1]",
+            "strh r8, [r1 + r2]",
+            "ldr r8, x'400",
+            "ldrb r0, x'100",
+            "ldrd r0, r1, [r2 + 8]",
+            "strd r0, r1, [sp - 8]!",
+            "ldrex r0, [r1]",
+            "strex r2, r0, [r1 + 4]",
+            "push r8",
+            "push.w r4",
+            "pop r4/r8",
+            "push r4-r11/lr",
+            "push.w r4/lr",
+            "stmdb [r0]!, r4/lr",
+            "ldm [r8]!, r0-r3",
+            "ldmdb [r0], r1/r2",
+            "tbb [r0 + r1]",
+            "tbh [r0 + r1*2]",
+            "cbz r0, x'210",
+            "cbnz r7, x'280",
+            "it eq\nmoveq r0, 1",
+            "itte ne\naddne r0, r0, r1\nsubne.w r0, r1, 1\nnopeq",
+            "itet gt\nmovgt r0, r1\nlslle r0, r1, 2\nbgt start",
+            "nop.w",
+            "clrex",
+            "b x'1000",
+            "b.w start",
+            "bne x'1000",
+            "bge.w start",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'200"));
+            let decoded    = decoder.decode(&bytes, 0x200, &label).expect(line);
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let armv6m = (find("armv6m").unwrap().new)(&mut NameTable::new());
+    assert_eq!(armv6m.decode(&[0x08, 0xBF], 0x200, &label), None);
+    assert_eq!(armv6m.decode(&[0x01, 0xEB, 0x02, 0x00], 0x200, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(armv6m.decode(&[0xFF, 0xF7], 0x200, &label), None);
+}
@@ -0,0 +1,653 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction decoding.
+//!
+//! A prefix other than a segment override, an operand-size prefix, or an
+//! address-size prefix decodes as an instruction of its own, as the
+//! assembler writes it.
+
+use std::cmp::Reverse;
+
+use crate::target::{number, Decoded};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// General-purpose registers, by operand size in bytes and register number.
+const REGS: [[&str; 8]; 3] = [
+    ["al",  "cl",  "dl",  "bl",  "ah",  "ch",  "dh",  "bh" ],
+    ["ax",  "cx",  "dx",  "bx",  "sp",  "bp",  "si",  "di" ],
+    ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"],
+];
+
+/// Segment registers, by number.
+const SEGS: [&str; 6] = ["es", "cs", "ss", "ds", "fs", "gs"];
+
+/// Arithmetic and logic mnemonics, by `/digit`.
+const ALU: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+
+/// Shift and rotate mnemonics, by `/digit`.
+const SHIFTS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "", "sar"];
+
+/// Group 3 mnemonics, by `/digit`.
+const UNARY: [&str; 8] = ["test", "", "not", "neg", "mul", "imul", "div", "idiv"];
+
+/// Registers of 16-bit memory operands, by ModR/M `r/m` field.
+const MEM16: [&str; 8] = ["bx + si", "bx + di", "bp + si", "bp + di", "si", "di", "bp", "bx"];
+
+/// Decodes the instruction at the beginning of `bytes`, which lies at
+/// address `addr`.  `label` returns the name by which to refer to an
+/// address, if any.
+pub fn decode(
+    x86:   &X86,
+    bytes: &[u8],
+    addr:  u64,
+    label: &dyn Fn(u64) -> Option<String>,
+) -> Option<Decoded> {
+    let mut d = Decoder {
+        x86, bytes, addr, label,
+        at: 0, seg: None, size: 2, addr32: false, targets: vec![],
+    };
+    let text = d.insn()?;
+    Some(Decoded { text, size: d.at, targets: d.targets })
+}
+
+/// Decoder state for one instruction.
+struct Decoder<'a> {
+    /// Target.
+    x86: &'a X86,
+
+    /// Bytes of the instruction and those after it.
+    bytes: &'a [u8],
+
+    /// Address of the instruction.
+    addr: u64,
+
+    /// Returns the name by which to refer to an address, if any.
+    label: &'a dyn Fn(u64) -> Option<String>,
+
+    /// Offset of the next byte to read.
+    at: usize,
+
+    /// Segment override, if any, until a memory operand uses it.
+    seg: Option<u8>,
+
+    /// Operand size in bytes of a word instruction: 2, or 4 after an
+    /// operand-size prefix.
+    size: u8,
+
+    /// Whether an address-size prefix selects 32-bit addressing.
+    addr32: bool,
+
+    /// Addresses to which the instruction jumps or calls.
+    targets: Vec<u64>,
+}
+
+/// ModR/M byte and the operand that its `mod` and `r/m` fields select.
+struct ModRm {
+    /// `reg` field.
+    reg: u8,
+
+    /// Text of the operand.
+    rm: String,
+
+    /// Whether the operand is in memory rather than a register.
+    mem: bool,
+}
+
+impl Decoder<'_> {
+    /// Decodes the instruction and its prefixes.
+    fn insn(&mut self) -> Option<String> {
+        if let Some(name) = self.fixed() {
+            return Some(name);
+        }
+
+        let mut opsize = false;
+
+        let op = loop {
+            match self.byte()? {
+                p @ (0x26 | 0x2E | 0x36 | 0x3E) if self.seg.is_none() => {
+                    self.seg = Some((p >> 3) & 3);
+                },
+                p @ (0x64 | 0x65) if self.seg.is_none() && self.x86.has(I386) => {
+                    self.seg = Some(p & 7);
+                },
+                0x66 if !opsize && self.x86.has(I386) => {
+                    opsize    = true;
+                    self.size = 4;
+                },
+                0x67 if !self.addr32 && self.x86.has(I386) => {
+                    self.addr32 = true;
+                },
+                op => break op,
+            }
+        };
+
+        let text = self.op(op)?;
+
+        // Every prefix must apply to an operand
+        match self.seg.is_none() && !self.addr32 {
+            true  => Some(text),
+            false => None,
+        }
+    }
+
+    /// Decodes an instruction without operands, preferring the longest
+    /// encoding and then the first mnemonic.
+    fn fixed(&mut self) -> Option<String> {
+        let (name, len) = self.x86.table
+            .iter()
+            .filter(|e| self.x86.has(e.isa))
+            .filter_map(|e| match e.op {
+                Op::Fixed(b) if self.bytes.starts_with(b) => Some((&e.name, b.len())),
+                _                                          => None,
+            })
+            .min_by_key(|&(_, len)| Reverse(len))?;
+
+        self.at = len;
+        Some(name.clone())
+    }
+
+    /// Decodes the instruction with the given opcode, after its prefixes.
+    fn op(&mut self, op: u8) -> Option<String> {
+        let w    = op & 1;
+        let size = self.width(w);
+        let r    = op & 7;
+
+        let text = match op {
+            // Arithmetic and logic
+            0x00..=0x3F if op & 7 < 4 => {
+                let m    = self.modrm(size)?;
+                let reg  = self.reg(size, m.reg);
+                let name = ALU[(op >> 3) as usize];
+                match op & 2 {
+                    0 => format!("{} {}, {}", name, m.rm, reg),
+                    _ => format!("{} {}, {}", name, reg, m.rm),
+                }
+            },
+            0x00..=0x3F if op & 7 < 6 => {
+                let imm = self.imm(size)?;
+                format!("{} {}, {}", ALU[(op >> 3) as usize], self.reg(size, 0), imm)
+            },
+            0x06 | 0x0E | 0x16 | 0x1E => format!("push {}", SEGS[(op >> 3) as usize]),
+            0x07 | 0x17 | 0x1F        => format!("pop {}",  SEGS[(op >> 3) as usize]),
+            0x0F if self.x86.has(I386) => return self.op2(),
+
+            // Registers
+            0x40..=0x47 => format!("inc {}",  self.reg(self.size, r)),
+            0x48..=0x4F => format!("dec {}",  self.reg(self.size, r)),
+            0x50..=0x57 => format!("push {}", self.reg(self.size, r)),
+            0x58..=0x5F => format!("pop {}",  self.reg(self.size, r)),
+            0x91..=0x97 => format!("xchg {}, {}", self.reg(self.size, 0), self.reg(self.size, r)),
+            0xB0..=0xB7 => format!("mov {}, {}", self.reg(1, r), self.imm(1)?),
+            0xB8..=0xBF => format!("mov {}, {}", self.reg(self.size, r), self.imm(self.size)?),
+
+            // 80186 additions
+            0x68 | 0x6A if self.x86.has(I186) => {
+                let imm = match op {
+                    0x68 => self.imm(self.size)?,
+                    _    => self.small()?,
+                };
+                format!("push{} {}", self.suffix(self.size == 4, 4), imm)
+            },
+            0x69 | 0x6B if self.x86.has(I186) => {
+                let m   = self.modrm(self.size)?;
+                let imm = match op {
+                    0x69 => self.imm(self.size)?,
+                    _    => self.small()?,
+                };
+                format!("imul {}, {}, {}", self.reg(self.size, m.reg), m.rm, imm)
+            },
+            0xC8 if self.x86.has(I186) => {
+                let frame = self.imm(2)?;
+                format!("enter {}, {}", frame, self.imm(1)?)
+            },
+
+            // Jumps
+            0x70..=0x7F if self.near_jcc() => {
+                self.at += 2;
+                let (target, short) = self.rel_near()?;
+                let cond            = condition(op & 15 ^ 1);
+                format!("j{}{} {}", cond, if short { ".w" } else { "" }, target)
+            },
+            0x70..=0x7F => {
+                let target = self.rel(1)?;
+                format!("j{} {}", condition(op & 15), target)
+            },
+            0xE0..=0xE3 => {
+                let name = ["loopne", "loope", "loop", "jcxz"][(r & 3) as usize];
+                format!("{} {}", name, self.rel(1)?)
+            },
+            0xE8 => format!("call {}", self.rel(2)?),
+            0xE9 => {
+                let (target, short) = self.rel_near()?;
+                format!("jmp{} {}", if short { ".w" } else { "" }, target)
+            },
+            0xEB => format!("jmp {}", self.rel(1)?),
+            0x9A | 0xEA => {
+                let off  = self.imm(self.size)?;
+                let seg  = self.imm(2)?;
+                let name = if op == 0x9A { "call" } else { "jmp" };
+                format!("{}{} {}:{}", name, self.suffix(self.size == 4, 4), seg, off)
+            },
+            0xC3 => "ret".to_string(),
+            0xCB => "retf".to_string(),
+            0xC2 | 0xCA => {
+                let name = if op == 0xC2 { "ret" } else { "retf" };
+                format!("{} {}", name, self.imm(2)?)
+            },
+
+            // Group 1
+            0x80 | 0x81 | 0x83 => {
+                let m   = self.modrm(size)?;
+                let imm = match op {
+                    0x83 => self.small()?,
+                    _    => self.imm(size)?,
+                };
+                format!("{}{} {}, {}", ALU[m.reg as usize], self.suffix(m.mem, size), m.rm, imm)
+            },
+
+            // Data movement
+            0x84..=0x87 => {
+                let m    = self.modrm(size)?;
+                let name = if op < 0x86 { "test" } else { "xchg" };
+                format!("{} {}, {}", name, m.rm, self.reg(size, m.reg))
+            },
+            0x88..=0x8B => {
+                let m   = self.modrm(size)?;
+                let reg = self.reg(size, m.reg);
+                match op & 2 {
+                    0 => format!("mov {}, {}", m.rm, reg),
+                    _ => format!("mov {}, {}", reg, m.rm),
+                }
+            },
+            0x8C | 0x8E => {
+                let m   = self.modrm(2)?;
+                let seg = SEGS.get(m.reg as usize)?;
+                if op == 0x8E && m.reg == 1 {
+                    return None;
+                }
+                match op {
+                    0x8C => format!("mov {}, {}", m.rm, seg),
+                    _    => format!("mov {}, {}", seg, m.rm),
+                }
+            },
+            0x8D | 0xC4 | 0xC5 => {
+                let m    = self.modrm(self.size)?;
+                let name = match op { 0x8D => "lea", 0xC4 => "les", _ => "lds" };
+                if !m.mem {
+                    return None;
+                }
+                format!("{} {}, {}", name, self.reg(self.size, m.reg), m.rm)
+            },
+            0x8F => {
+                let m = self.modrm(self.size)?;
+                if m.reg != 0 {
+                    return None;
+                }
+                format!("pop{} {}", self.suffix(m.mem, size), m.rm)
+            },
+            0xA0..=0xA3 => {
+                let mem = self.direct()?;
+                let acc = self.reg(size, 0);
+                match op & 2 {
+                    0 => format!("mov {}, {}", acc, mem),
+                    _ => format!("mov {}, {}", mem, acc),
+                }
+            },
+            0xA8 | 0xA9 => format!("test {}, {}", self.reg(size, 0), self.imm(size)?),
+            0xC6 | 0xC7 => {
+                let m = self.modrm(size)?;
+                if m.reg != 0 {
+                    return None;
+                }
+                format!("mov{} {}, {}", self.suffix(m.mem, size), m.rm, self.imm(size)?)
+            },
+
+            // Shifts and rotates
+            0xC0 | 0xC1 | 0xD0..=0xD3 => {
+                if op < 0xD0 && !self.x86.has(I186) {
+                    return None;
+                }
+                let m    = self.modrm(size)?;
+                let name = SHIFTS[m.reg as usize];
+                if name.is_empty() {
+                    return None;
+                }
+                let count = match op & 0xFE {
+                    0xC0 => self.imm(1)?,
+                    0xD0 => "1".to_string(),
+                    _    => "cl".to_string(),
+                };
+                format!("{}{} {}, {}", name, self.suffix(m.mem, size), m.rm, count)
+            },
+
+            // Input and output
+            0xCD        => format!("int {}", self.imm(1)?),
+            0xE4 | 0xE5 => format!("in {}, {}", self.reg(size, 0), self.imm(1)?),
+            0xE6 | 0xE7 => format!("out {}, {}", self.imm(1)?, self.reg(size, 0)),
+            0xEC | 0xED => format!("in {}, dx", self.reg(size, 0)),
+            0xEE | 0xEF => format!("out dx, {}", self.reg(size, 0)),
+
+            // Group 3
+            0xF6 | 0xF7 => {
+                let m    = self.modrm(size)?;
+                let name = UNARY[m.reg as usize];
+                match m.reg {
+                    0 => format!("test{} {}, {}", self.suffix(m.mem, size), m.rm, self.imm(size)?),
+                    1 => return None,
+                    _ => format!("{}{} {}", name, self.suffix(m.mem, size), m.rm),
+                }
+            },
+
+            // Groups 4 and 5
+            0xFE | 0xFF => {
+                let m = self.modrm(size)?;
+                match (w, m.reg) {
+                    (_, 0) => format!("inc{} {}", self.suffix(m.mem, size), m.rm),
+                    (_, 1) => format!("dec{} {}", self.suffix(m.mem, size), m.rm),
+                    (1, 2) => format!("call{} {}", self.suffix(m.mem && size == 4, size), m.rm),
+                    (1, 4) => format!("jmp{} {}",  self.suffix(m.mem && size == 4, size), m.rm),
+                    (1, 6) => format!("push{} {}", self.suffix(m.mem, size), m.rm),
+                    _      => return None,
+                }
+            },
+
+            _ => return None,
+        };
+
+        Some(text)
+    }
+
+    /// Decodes an 80386 instruction with a two-byte opcode, after its `0x0F`.
+    fn op2(&mut self) -> Option<String> {
+        let op = self.byte()?;
+
+        let text = match op {
+            0x01 => {
+                let m = self.modrm(2)?;
+                match (m.reg, m.mem) {
+                    (2, true) => format!("lgdt {}", m.rm),
+                    (3, true) => format!("lidt {}", m.rm),
+                    _         => return None,
+                }
+            },
+            0x20 | 0x22 => {
+                let m = self.modrm(4)?;
+                if m.mem || !matches!(m.reg, 0 | 2..=4) {
+                    return None;
+                }
+                match op {
+                    0x20 => format!("mov {}, cr{}", m.rm, m.reg),
+                    _    => format!("mov cr{}, {}", m.reg, m.rm),
+                }
+            },
+            0x80..=0x8F => {
+                let (target, short) = self.rel_near()?;
+                format!("j{}{} {}", condition(op & 15), if short { ".w" } else { "" }, target)
+            },
+            0x90..=0x9F => {
+                let m = self.modrm(1)?;
+                format!("set{} {}", condition(op & 15), m.rm)
+            },
+            0xA0 | 0xA8 => format!("push {}", SEGS[(op >> 3 & 7) as usize]),
+            0xA1 | 0xA9 => format!("pop {}",  SEGS[(op >> 3 & 7) as usize]),
+            0xAF => {
+                let m = self.modrm(self.size)?;
+                format!("imul {}, {}", self.reg(self.size, m.reg), m.rm)
+            },
+            0xB2 | 0xB4 | 0xB5 => {
+                let m    = self.modrm(self.size)?;
+                let name = match op { 0xB2 => "lss", 0xB4 => "lfs", _ => "lgs" };
+                if !m.mem {
+                    return None;
+                }
+                format!("{} {}, {}", name, self.reg(self.size, m.reg), m.rm)
+            },
+            0xB6 | 0xB7 | 0xBE | 0xBF => {
+                let src  = self.width(op & 1).min(2);
+                let m    = self.modrm(src)?;
+                let name = if op < 0xBE { "movzx" } else { "movsx" };
+                let dst  = self.reg(self.size, m.reg);
+                format!("{}{} {}, {}", name, self.suffix(m.mem, src), dst, m.rm)
+            },
+            _ => return None,
+        };
+
+        Some(text)
+    }
+
+    // === Operands ===
+
+    /// Reads a ModR/M byte and the SIB byte and displacement that follow it.
+    /// `size` is the size of a register operand.
+    fn modrm(&mut self, size: u8) -> Option<ModRm> {
+        let byte = self.byte()?;
+        let md   = byte >> 6;
+        let reg  = byte >> 3 & 7;
+        let rm   = byte & 7;
+
+        if md == 3 {
+            return Some(ModRm { reg, rm: self.reg(size, rm), mem: false });
+        }
+
+        let (regs, disp) = match self.addr32 {
+            false => match (md, rm) {
+                (0, 6) => (String::new(), Some(self.word()? as i64)),
+                (0, _) => (MEM16[rm as usize].to_string(), None),
+                (1, _) => (MEM16[rm as usize].to_string(), Some(self.byte()? as i8 as i64)),
+                (_, _) => (MEM16[rm as usize].to_string(), Some(self.word()? as i64)),
+            },
+            true => self.mem32(md, rm)?,
+        };
+
+        let seg    = self.seg.take().map_or(String::new(), |s| format!("{}:", SEGS[s as usize]));
+        let addr32 = std::mem::take(&mut self.addr32);
+
+        let text = match (regs.is_empty(), disp) {
+            (true,  Some(addr)) => format!("[{}{}]", seg, self.address(addr as u64)),
+            (false, None)       => format!("[{}{}]", seg, regs),
+            (false, Some(0)) if md == 1 && matches!(&regs[..], "bp" | "ebp") => {
+                format!("[{}{}]", seg, regs)
+            },
+            (false, Some(disp)) => {
+                format!("[{}{}{}]", seg, regs, offset(md, disp, addr32))
+            },
+            (true,  None)       => return None,
+        };
+
+        Some(ModRm { reg, rm: text, mem: true })
+    }
+
+    /// Reads the SIB byte and displacement of a 32-bit memory operand with
+    /// the given `mod` and `r/m` fields.  Returns the registers of the
+    /// address and the displacement, if any.
+    fn mem32(&mut self, md: u8, rm: u8) -> Option<(String, Option<i64>)> {
+        let (base, index) = match rm {
+            4 => {
+                let sib   = self.byte()?;
+                let index = match sib >> 3 & 7 {
+                    4 => None,
+                    r => Some((REGS[2][r as usize], 1 << (sib >> 6))),
+                };
+                (sib & 7, index)
+            },
+            rm => (rm, None),
+        };
+
+        let base = match (md, base) {
+            (0, 5) => None,
+            (_, b) => Some(REGS[2][b as usize]),
+        };
+
+        let mut regs = base.unwrap_or("").to_string();
+
+        if let Some((index, scale)) = index {
+            if !regs.is_empty() {
+                regs += " + ";
+            }
+            regs += index;
+            if scale != 1 {
+                regs += &format!("*{}", scale);
+            }
+        }
+
+        let disp = match (md, base) {
+            (0, None) => Some(self.dword()? as i64).filter(|&d| d != 0),
+            (0, _)    => None,
+            (1, _)    => Some(self.byte()? as i8 as i64),
+            (_, _)    => Some(self.dword()? as i64),
+        };
+
+        // A direct address uses 16-bit addressing
+        if regs.is_empty() {
+            return None;
+        }
+
+        Some((regs, disp))
+    }
+
+    /// Reads the direct address of a move to or from the accumulator.
+    fn direct(&mut self) -> Option<String> {
+        if self.addr32 {
+            return None;
+        }
+        let addr = self.word()? as u64;
+        let seg  = self.seg.take().map_or(String::new(), |s| format!("{}:", SEGS[s as usize]));
+        Some(format!("[{}{}]", seg, self.address(addr)))
+    }
+
+    /// Reads an immediate operand of `size` bytes.
+    fn imm(&mut self, size: u8) -> Option<String> {
+        let value = match size {
+            1 => self.byte()? as u32,
+            2 => self.word()? as u32,
+            _ => self.dword()?,
+        };
+        Some(number(value as i64))
+    }
+
+    /// Reads an 8-bit immediate operand that the processor sign-extends.
+    fn small(&mut self) -> Option<String> {
+        Some(number(self.byte()? as i8 as i64))
+    }
+
+    /// Reads the offset of a jump of `size` bytes and returns the text of
+    /// its target.
+    fn rel(&mut self, size: u8) -> Option<String> {
+        let disp = match size {
+            1 => self.byte()? as i8 as i64,
+            _ => self.word()? as i16 as i64,
+        };
+        Some(self.target(disp))
+    }
+
+    /// Returns whether a short conditional jump begins the near form that
+    /// the assembler emits for processors without a near conditional jump:
+    /// a jump on the opposite condition over a near `jmp`.
+    fn near_jcc(&self) -> bool {
+        !self.x86.has(I386) && self.bytes[self.at..].starts_with(&[3, 0xE9])
+    }
+
+    /// Reads the 16-bit offset of a near jump.  Returns the text of its
+    /// target and whether a short jump would reach it.
+    fn rel_near(&mut self) -> Option<(String, bool)> {
+        let disp  = self.word()? as i16 as i64;
+        let short = disp + self.at as i64 - 2;
+        Some((self.target(disp), (-128..=127).contains(&short)))
+    }
+
+    /// Returns the text by which to refer to the target of a jump or call
+    /// `disp` bytes past the end of the instruction.
+    fn target(&mut self, disp: i64) -> String {
+        let addr = self.addr.wrapping_add(self.at as u64).wrapping_add(disp as u64);
+        self.targets.push(addr);
+        (self.label)(addr).unwrap_or_else(|| number(addr as i64))
+    }
+
+    /// Returns the text by which to refer to a direct address.
+    fn address(&self, addr: u64) -> String {
+        (self.label)(addr).unwrap_or_else(|| number(addr as i64))
+    }
+
+    /// Returns the name of register `num` of `size` bytes.
+    fn reg(&self, size: u8, num: u8) -> String {
+        REGS[(size / 2) as usize][num as usize].to_string()
+    }
+
+    /// Returns the operand size of an instruction with the given `w` bit.
+    fn width(&self, w: u8) -> u8 {
+        match w {
+            0 => 1,
+            _ => self.size,
+        }
+    }
+
+    /// Returns the suffix for operands of `size` bytes if `needed` is true.
+    fn suffix(&self, needed: bool, size: u8) -> &'static str {
+        match needed {
+            true  => SIZES.iter().find(|s| s.1 == size).map_or("", |s| s.0),
+            false => "",
+        }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.at)?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    fn word(&mut self) -> Option<u16> {
+        let lo = self.byte()? as u16;
+        let hi = self.byte()? as u16;
+        Some(hi << 8 | lo)
+    }
+
+    fn dword(&mut self) -> Option<u32> {
+        let lo = self.word()? as u32;
+        let hi = self.word()? as u32;
+        Some(hi << 16 | lo)
+    }
+}
+
+/// Returns the mnemonic suffix of the given condition code.
+fn condition(code: u8) -> &'static str {
+    CONDS.iter().find(|c| c.0 == code).map_or("", |c| c.1[0])
+}
+
+/// Returns the text that adds displacement `disp` to the registers of a
+/// memory operand whose ModR/M `mod` field is `md`, with 32-bit addressing
+/// if `addr32` is true.  The assembler chooses the 8-bit form for a
+/// displacement that fits, so a full displacement that would fit if signed
+/// appears unsigned.
+fn offset(md: u8, disp: i64, addr32: bool) -> String {
+    let signed = match addr32 {
+        true  => disp as i32 as i64,
+        false => disp as i16 as i64,
+    };
+    let disp = match md == 1 || !(-128..=127).contains(&signed) {
+        true  => signed,
+        false => disp,
+    };
+    match disp < 0 {
+        true  => format!(" - {}", number(-disp)),
+        false => format!(" + {}", number(disp)),
+    }
+}
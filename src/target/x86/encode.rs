@@ -0,0 +1,1131 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// General-purpose register.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Reg {
+    /// Register number.
+    num: u8,
+
+    /// Size in bytes.
+    size: u8,
+}
+
+const CL: Reg = Reg { num: 1, size: 1 };
+const DX: Reg = Reg { num: 2, size: 2 };
+
+// Register numbers with special roles in addressing
+const BX: u8 = 3;
+const SP: u8 = 4;
+const BP: u8 = 5;
+const SI: u8 = 6;
+const DI: u8 = 7;
+
+/// Segment register `cs`, which cannot be a destination.
+const CS: u8 = 1;
+
+/// Segment override prefixes, by segment register number.
+const SEG_PREFIX: [u8; 6] = [0x26, 0x2E, 0x36, 0x3E, 0x64, 0x65];
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+enum Operand {
+    /// General-purpose register: `ax`.
+    Reg(Reg),
+
+    /// Segment register: `ds`.
+    Seg(u8),
+
+    /// Control register: `cr0`.
+    Ctl(u8),
+
+    /// Memory: `[bx + si + 4]`.
+    Mem(Mem),
+
+    /// Immediate value or target address.
+    Imm(Expr<Span>),
+
+    /// Far pointer: `x'f000:x'fff0`.
+    Far(Expr<Span>, Expr<Span>),
+}
+
+/// Memory operand.
+#[derive(Clone, Debug)]
+struct Mem {
+    /// Segment override, if any.
+    seg: Option<u8>,
+
+    /// Base register, if any.  In 16-bit addressing, `bx` or `bp`.
+    base: Option<Reg>,
+
+    /// Index register, if any, with its scale.  In 16-bit addressing, `si`
+    /// or `di` with a scale of 1.
+    index: Option<(Reg, u8)>,
+
+    /// Displacement, if any.
+    disp: Option<Expr<Span>>,
+}
+
+impl Mem {
+    /// Returns whether the operand uses 32-bit addressing.
+    fn addr32(&self) -> bool {
+        self.base.or(self.index.map(|(r, _)| r)).is_some_and(|r| r.size == 4)
+    }
+
+    /// Returns whether the operand is a direct address.
+    fn direct(&self) -> bool {
+        self.base.is_none() && self.index.is_none()
+    }
+}
+
+/// Value that completes a field of the instruction.
+struct Field {
+    /// Offset of the field in the instruction.
+    at: usize,
+
+    /// Value, or the target address if `pc_rel` is true.
+    expr: Expr<Span>,
+
+    /// Kind of the field.
+    kind: RelocKind,
+
+    /// Whether the field holds an offset from the end of the instruction.
+    pc_rel: bool,
+}
+
+type Result<T = ()> = std::result::Result<T, String>;
+
+const INVALID: &str = "invalid operands";
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.  `size` is the operand size of the mnemonic suffix in bytes, or
+/// 0 if there is no suffix.
+pub fn encode(x86: &X86, entry: &Entry, size: u8, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let span = stmt.data;
+
+    if !x86.has(entry.isa) {
+        let msg = format!("instruction '{}' is not available on {}", entry.name, x86.name);
+        return out.error(span, &msg);
+    }
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| operand(arg, out))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    let mut encoder = Encoder { x86, out, span, size, bytes: vec![], fields: vec![] };
+
+    match encoder.encode(entry.op, &ops) {
+        Ok(())   => encoder.finish(),
+        Err(msg) => encoder.out.error(span, &msg),
+    }
+}
+
+/// Encoder for one instruction statement.
+struct Encoder<'a> {
+    x86:    &'a X86,
+    out:    &'a mut dyn Emitter,
+    span:   Span,
+    size:   u8,
+    bytes:  Vec<u8>,
+    fields: Vec<Field>,
+}
+
+/// Returns the `w` bit of an opcode for the given operand size.
+fn w(size: u8) -> u8 {
+    (size != 1) as u8
+}
+
+impl Encoder<'_> {
+    fn encode(&mut self, op: Op, ops: &[Operand]) -> Result {
+        use Operand::{Far, Imm, Mem as M, Reg as R};
+
+        match (op, ops) {
+            (Op::Fixed(bytes), []) => {
+                self.bytes.extend_from_slice(bytes);
+                Ok(())
+            },
+
+            (Op::Alu(n),  [dst, src]) => self.alu(n, dst, src),
+            (Op::Mov,     [dst, src]) => self.mov(dst, src),
+            (Op::Test,    [a, b])     => self.test(a, b),
+            (Op::Xchg,    [a, b])     => self.xchg(a, b),
+            (Op::Shift(n), [dst, n2]) => self.shift(n, dst, n2),
+            (Op::Imul,    ops)        => self.imul(ops),
+            (Op::Push,    [src])      => self.push(src),
+            (Op::Pop,     [dst])      => self.pop(dst),
+
+            (Op::IncDec(n), [R(r)]) if r.size > 1 => {
+                let size = self.size(&[&ops[0]])?;
+                self.prefix(size)?;
+                self.bytes.push(0x40 | n << 3 | r.num);
+                Ok(())
+            },
+            (Op::IncDec(n), [dst]) => {
+                let size = self.size(&[dst])?;
+                self.rm(size, &[0xFE | w(size)], n, dst)
+            },
+            (Op::Unary(n), [dst]) => {
+                let size = self.size(&[dst])?;
+                self.rm(size, &[0xF6 | w(size)], n, dst)
+            },
+
+            (Op::Lea, [R(r), m @ M(_)]) if r.size > 1 => {
+                self.rm(r.size, &[0x8D], r.num, m)
+            },
+            (Op::LoadFar(opcode), [R(r), m @ M(_)]) if r.size > 1 => {
+                self.rm(r.size, opcode, r.num, m)
+            },
+
+            (Op::Jmp,  [Imm(target)]) if self.size <= 2 => self.jmp(target),
+            (Op::Call, [Imm(target)]) if self.size == 0 => {
+                self.bytes.push(0xE8);
+                self.rel(target, RelocKind::INT16);
+                Ok(())
+            },
+            (Op::Jmp,  [Far(seg, off)]) => self.far(0xEA, seg, off),
+            (Op::Call, [Far(seg, off)]) => self.far(0x9A, seg, off),
+            (Op::Jmp,  [dst]) => self.indirect(4, dst),
+            (Op::Call, [dst]) => self.indirect(2, dst),
+
+            (Op::Jcc(cc), [Imm(target)]) if self.size <= 2 => self.jcc(cc, target),
+
+            (Op::Short(opcode), [Imm(target)]) => {
+                self.bytes.push(opcode);
+                self.rel(target, REL8);
+                Ok(())
+            },
+
+            (Op::Ret(opcode), []) => {
+                self.bytes.push(opcode);
+                Ok(())
+            },
+            (Op::Ret(opcode), [Imm(n)]) => {
+                self.bytes.push(opcode - 1);
+                self.imm(n, 2);
+                Ok(())
+            },
+
+            (Op::Int, [Imm(n)]) => {
+                self.bytes.push(0xCD);
+                self.imm(n, 1);
+                Ok(())
+            },
+
+            (Op::In, [R(a), Imm(port)]) if a.num == 0 => {
+                self.prefix(a.size)?;
+                self.bytes.push(0xE4 | w(a.size));
+                self.imm(port, 1);
+                Ok(())
+            },
+            (Op::In, [R(a), R(DX)]) if a.num == 0 => {
+                self.prefix(a.size)?;
+                self.bytes.push(0xEC | w(a.size));
+                Ok(())
+            },
+            (Op::Out, [Imm(port), R(a)]) if a.num == 0 => {
+                self.prefix(a.size)?;
+                self.bytes.push(0xE6 | w(a.size));
+                self.imm(port, 1);
+                Ok(())
+            },
+            (Op::Out, [R(DX), R(a)]) if a.num == 0 => {
+                self.prefix(a.size)?;
+                self.bytes.push(0xEE | w(a.size));
+                Ok(())
+            },
+
+            (Op::Enter, [Imm(frame), Imm(level)]) => {
+                self.bytes.push(0xC8);
+                self.imm(frame, 2);
+                self.imm(level, 1);
+                Ok(())
+            },
+
+            (Op::Movx(opcode), [R(d), src]) => self.movx(opcode, *d, src),
+
+            (Op::Setcc(cc), [dst]) => {
+                if self.size(&[dst])? != 1 {
+                    return Err(INVALID.into());
+                }
+                self.rm(1, &[0x0F, 0x90 | cc], 0, dst)
+            },
+
+            (Op::Desc(n), [m @ M(_)]) => self.rm(2, &[0x0F, 0x01], n, m),
+
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    // === Arithmetic and Data Movement ===
+
+    /// Emits an arithmetic or logic instruction with the given `/digit`.
+    fn alu(&mut self, n: u8, dst: &Operand, src: &Operand) -> Result {
+        use Operand::{Imm, Mem as M, Reg as R};
+
+        match (dst, src) {
+            (R(_) | M(_), R(s)) => {
+                let size = self.size(&[dst, src])?;
+                self.rm(size, &[n << 3 | w(size)], s.num, dst)
+            },
+            (R(d), M(_)) => {
+                let size = self.size(&[dst])?;
+                self.rm(size, &[n << 3 | 2 | w(size)], d.num, src)
+            },
+            (R(_) | M(_), Imm(e)) => {
+                let size = self.size(&[dst])?;
+                if let Some(v) = self.small(e, size) {
+                    self.rm(size, &[0x83], n, dst)?;
+                    self.bytes.push(v as u8);
+                } else if let R(Reg { num: 0, .. }) = dst {
+                    self.prefix(size)?;
+                    self.bytes.push(n << 3 | 4 | w(size));
+                    self.imm(e, size);
+                } else {
+                    self.rm(size, &[0x80 | w(size)], n, dst)?;
+                    self.imm(e, size);
+                }
+                Ok(())
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a `mov` instruction.
+    fn mov(&mut self, dst: &Operand, src: &Operand) -> Result {
+        use Operand::{Ctl, Imm, Mem as M, Reg as R, Seg};
+
+        match (dst, src) {
+            (R(_) | M(_), R(s)) => {
+                let size = self.size(&[dst, src])?;
+                match dst {
+                    M(m) if s.num == 0 && m.direct() => self.moffs(0xA2, size, m),
+                    _                                => self.rm(size, &[0x88 | w(size)], s.num, dst),
+                }
+            },
+            (R(d), M(m)) => {
+                let size = self.size(&[dst])?;
+                match d.num == 0 && m.direct() {
+                    true  => self.moffs(0xA0, size, m),
+                    false => self.rm(size, &[0x8A | w(size)], d.num, src),
+                }
+            },
+            (R(d), Imm(e)) => {
+                let size = self.size(&[dst])?;
+                self.prefix(size)?;
+                self.bytes.push(if size == 1 { 0xB0 } else { 0xB8 } | d.num);
+                self.imm(e, size);
+                Ok(())
+            },
+            (M(_), Imm(e)) => {
+                let size = self.size(&[dst])?;
+                self.rm(size, &[0xC6 | w(size)], 0, dst)?;
+                self.imm(e, size);
+                Ok(())
+            },
+            (&Seg(s), R(_) | M(_)) if s != CS => {
+                self.segment(s, src)?;
+                self.rm(2, &[0x8E], s, src)
+            },
+            (R(_) | M(_), &Seg(s)) => {
+                self.segment(s, dst)?;
+                self.rm(2, &[0x8C], s, dst)
+            },
+            (&Ctl(c), &R(r)) if r.size == 4 => {
+                self.require(I386)?;
+                self.bytes.extend_from_slice(&[0x0F, 0x22, 0xC0 | c << 3 | r.num]);
+                Ok(())
+            },
+            (&R(r), &Ctl(c)) if r.size == 4 => {
+                self.require(I386)?;
+                self.bytes.extend_from_slice(&[0x0F, 0x20, 0xC0 | c << 3 | r.num]);
+                Ok(())
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Checks the operand of a move to or from segment register `s`.
+    fn segment(&mut self, s: u8, op: &Operand) -> Result {
+        if s >= 4 {
+            self.require(I386)?;
+        }
+        match self.size_or(&[op], 2)? {
+            2 => Ok(()),
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a move between the accumulator and a direct address.
+    fn moffs(&mut self, opcode: u8, size: u8, m: &Mem) -> Result {
+        if let Some(s) = m.seg {
+            self.seg_prefix(s)?;
+        }
+        self.prefix(size)?;
+        self.bytes.push(opcode | w(size));
+        self.disp(m, 2);
+        Ok(())
+    }
+
+    /// Emits a `test` instruction.
+    fn test(&mut self, a: &Operand, b: &Operand) -> Result {
+        use Operand::{Imm, Mem as M, Reg as R};
+
+        match (a, b) {
+            (R(_) | M(_), R(r)) | (R(r), M(_)) => {
+                let size = self.size(&[a, b])?;
+                let rm   = if matches!(b, R(_)) { a } else { b };
+                self.rm(size, &[0x84 | w(size)], r.num, rm)
+            },
+            (R(Reg { num: 0, .. }), Imm(e)) => {
+                let size = self.size(&[a])?;
+                self.prefix(size)?;
+                self.bytes.push(0xA8 | w(size));
+                self.imm(e, size);
+                Ok(())
+            },
+            (M(_), Imm(e)) | (R(_), Imm(e)) => {
+                let size = self.size(&[a])?;
+                self.rm(size, &[0xF6 | w(size)], 0, a)?;
+                self.imm(e, size);
+                Ok(())
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits an `xchg` instruction.
+    fn xchg(&mut self, a: &Operand, b: &Operand) -> Result {
+        use Operand::{Mem as M, Reg as R};
+
+        match (a, b) {
+            (&R(x), &R(y)) if x.size > 1 && (x.num == 0 || y.num == 0) => {
+                let size = self.size(&[a, b])?;
+                self.prefix(size)?;
+                self.bytes.push(0x90 | x.num | y.num);
+                Ok(())
+            },
+            (R(_) | M(_), R(r)) | (R(r), M(_)) => {
+                let size = self.size(&[a, b])?;
+                let rm   = if matches!(b, R(_)) { a } else { b };
+                self.rm(size, &[0x86 | w(size)], r.num, rm)
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a shift or rotate with the given `/digit`.
+    fn shift(&mut self, n: u8, dst: &Operand, count: &Operand) -> Result {
+        let size = self.size(&[dst])?;
+
+        match count {
+            &Operand::Reg(CL) => self.rm(size, &[0xD2 | w(size)], n, dst),
+            Operand::Imm(e) if self.constant(e) == Some(1) => {
+                self.rm(size, &[0xD0 | w(size)], n, dst)
+            },
+            Operand::Imm(e) => {
+                self.require(I186)?;
+                self.rm(size, &[0xC0 | w(size)], n, dst)?;
+                self.imm(e, 1);
+                Ok(())
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits an `imul` instruction.
+    fn imul(&mut self, ops: &[Operand]) -> Result {
+        use Operand::{Imm, Mem as M, Reg as R};
+
+        match ops {
+            [src @ (R(_) | M(_))] => {
+                let size = self.size(&[src])?;
+                self.rm(size, &[0xF6 | w(size)], 5, src)
+            },
+            [R(d), Imm(e)] => self.imul3(*d, &ops[0], e),
+            [R(d), src @ (R(_) | M(_))] => {
+                self.require(I386)?;
+                let size = self.word_size(&[&ops[0], src])?;
+                self.rm(size, &[0x0F, 0xAF], d.num, src)
+            },
+            [R(d), src @ (R(_) | M(_)), Imm(e)] => self.imul3(*d, src, e),
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits the three-operand form of `imul`.
+    fn imul3(&mut self, d: Reg, src: &Operand, e: &Expr<Span>) -> Result {
+        self.require(I186)?;
+        let size = self.word_size(&[&Operand::Reg(d), src])?;
+        match self.small(e, size) {
+            Some(v) => {
+                self.rm(size, &[0x6B], d.num, src)?;
+                self.bytes.push(v as u8);
+            },
+            None => {
+                self.rm(size, &[0x69], d.num, src)?;
+                self.imm(e, size);
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits a `movzx` or `movsx` instruction.
+    fn movx(&mut self, opcode: u8, d: Reg, src: &Operand) -> Result {
+        let size = match *src {
+            Operand::Reg(r) if self.size == 0 || self.size == r.size => r.size,
+            Operand::Mem(_) if self.size != 0                        => self.size,
+            Operand::Mem(_) => return Err("operand size required; add a size suffix".into()),
+            _               => return Err(INVALID.into()),
+        };
+        if size >= d.size || size > 2 {
+            return Err(INVALID.into());
+        }
+        self.rm(d.size, &[0x0F, opcode | (size == 2) as u8], d.num, src)
+    }
+
+    // === Stack ===
+
+    /// Emits a `push` instruction.
+    fn push(&mut self, src: &Operand) -> Result {
+        match *src {
+            Operand::Reg(r) => {
+                let size = self.word_size(&[src])?;
+                self.prefix(size)?;
+                self.bytes.push(0x50 | r.num);
+                Ok(())
+            },
+            Operand::Seg(s) => self.push_seg(s, &[0x06, 0x0E, 0x16, 0x1E, 0xA0, 0xA8]),
+            Operand::Mem(_) => {
+                let size = self.word_size(&[src])?;
+                self.rm(size, &[0xFF], 6, src)
+            },
+            Operand::Imm(ref e) => {
+                self.require(I186)?;
+                let size = self.word_size_or(&[], 2)?;
+                self.prefix(size)?;
+                match self.small(e, size) {
+                    Some(v) => self.bytes.extend_from_slice(&[0x6A, v as u8]),
+                    None    => {
+                        self.bytes.push(0x68);
+                        self.imm(e, size);
+                    },
+                }
+                Ok(())
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a `pop` instruction.
+    fn pop(&mut self, dst: &Operand) -> Result {
+        match *dst {
+            Operand::Reg(r) => {
+                let size = self.word_size(&[dst])?;
+                self.prefix(size)?;
+                self.bytes.push(0x58 | r.num);
+                Ok(())
+            },
+            Operand::Seg(s) if s != CS => self.push_seg(s, &[0x07, 0, 0x17, 0x1F, 0xA1, 0xA9]),
+            Operand::Mem(_) => {
+                let size = self.word_size(&[dst])?;
+                self.rm(size, &[0x8F], 0, dst)
+            },
+            _ => Err(INVALID.into()),
+        }
+    }
+
+    /// Emits a push or pop of segment register `s`, given the opcodes for
+    /// each segment register.  The opcodes for `fs` and `gs` follow `0x0F`.
+    fn push_seg(&mut self, s: u8, opcodes: &[u8; 6]) -> Result {
+        if s >= 4 {
+            self.require(I386)?;
+            self.bytes.push(0x0F);
+        }
+        self.bytes.push(opcodes[s as usize]);
+        Ok(())
+    }
+
+    // === Jumps ===
+
+    /// Emits `jmp target` in the shortest form that reaches the target.
+    fn jmp(&mut self, target: &Expr<Span>) -> Result {
+        match self.near(target) {
+            false => {
+                self.bytes.push(0xEB);
+                self.rel(target, REL8);
+            },
+            true => {
+                self.bytes.push(0xE9);
+                self.rel(target, RelocKind::INT16);
+            },
+        }
+        Ok(())
+    }
+
+    /// Emits a conditional jump in the shortest form that reaches the target.
+    fn jcc(&mut self, cc: u8, target: &Expr<Span>) -> Result {
+        if !self.near(target) {
+            self.bytes.push(0x70 | cc);
+            self.rel(target, REL8);
+            return Ok(());
+        }
+
+        match self.x86.has(I386) {
+            true  => self.bytes.extend_from_slice(&[0x0F, 0x80 | cc]),
+            false => self.bytes.extend_from_slice(&[0x70 | (cc ^ 1), 3, 0xE9]),
+        }
+        self.rel(target, RelocKind::INT16);
+        Ok(())
+    }
+
+    /// Returns whether a jump to `target` takes the near form: if the `.w`
+    /// suffix requires it, or if no suffix is present and a short jump does
+    /// not reach the target.
+    fn near(&mut self, target: &Expr<Span>) -> bool {
+        match self.size {
+            1 => return false,
+            2 => return true,
+            _ => (),
+        }
+
+        let short = match self.out.eval(target) {
+            Some(value) => matches!(
+                pc_rel_value(self.out, self.span, value, 2),
+                Value::Const(d) if (-128..=127).contains(&d)
+            ),
+            None => true,
+        };
+
+        self.out.relax(2, &|form| form == 1 || short) == 1
+    }
+
+    /// Emits a far jump or call to `seg:off`.
+    fn far(&mut self, opcode: u8, seg: &Expr<Span>, off: &Expr<Span>) -> Result {
+        let size = self.word_size_or(&[], 2)?;
+        self.prefix(size)?;
+        self.bytes.push(opcode);
+        self.imm(off, size);
+        self.imm(seg, 2);
+        Ok(())
+    }
+
+    /// Emits an indirect near jump or call with the given `/digit`.
+    fn indirect(&mut self, n: u8, dst: &Operand) -> Result {
+        let size = self.word_size_or(&[dst], 2)?;
+        self.rm(size, &[0xFF], n, dst)
+    }
+
+    // === Operand Encoding ===
+
+    /// Emits the prefixes and opcode of an instruction with a ModR/M byte,
+    /// followed by the ModR/M byte with the given `reg` field for operand
+    /// `rm`, and any SIB byte and displacement.
+    fn rm(&mut self, size: u8, opcode: &[u8], reg: u8, rm: &Operand) -> Result {
+        match *rm {
+            Operand::Reg(r) => {
+                self.prefix(size)?;
+                self.bytes.extend_from_slice(opcode);
+                self.bytes.push(0xC0 | reg << 3 | r.num);
+            },
+            Operand::Mem(ref m) => {
+                if let Some(s) = m.seg {
+                    self.seg_prefix(s)?;
+                }
+                if m.addr32() {
+                    self.require(I386)?;
+                    self.bytes.push(0x67);
+                }
+                self.prefix(size)?;
+                self.bytes.extend_from_slice(opcode);
+                match m.addr32() {
+                    true  => self.mem32(reg, m),
+                    false => self.mem16(reg, m),
+                }
+            },
+            _ => return Err(INVALID.into()),
+        }
+        Ok(())
+    }
+
+    /// Emits the ModR/M byte and displacement of a 16-bit memory operand.
+    fn mem16(&mut self, reg: u8, m: &Mem) {
+        let rm = match (m.base.map(|r| r.num), m.index.map(|(r, _)| r.num)) {
+            (Some(BX), Some(SI)) => 0,
+            (Some(BX), Some(DI)) => 1,
+            (Some(BP), Some(SI)) => 2,
+            (Some(BP), Some(DI)) => 3,
+            (None,     Some(SI)) => 4,
+            (None,     Some(DI)) => 5,
+            (Some(BP), None)     => 6,
+            (Some(BX), None)     => 7,
+            _                    => {
+                self.bytes.push(reg << 3 | 6);
+                return self.disp(m, 2);
+            },
+        };
+
+        let md = self.disp_mode(m, rm == 6);
+        self.bytes.push(md << 6 | reg << 3 | rm);
+
+        match md {
+            0 => (),
+            1 => self.disp(m, 1),
+            _ => self.disp(m, 2),
+        }
+    }
+
+    /// Emits the ModR/M byte, SIB byte, and displacement of a 32-bit memory
+    /// operand.
+    fn mem32(&mut self, reg: u8, m: &Mem) {
+        let base = m.base.map(|r| r.num);
+
+        let sib = match (m.index, base) {
+            (Some((r, scale)), _) => Some((scale.trailing_zeros() as u8) << 6 | r.num << 3),
+            (None, Some(SP))      => Some(SP << 3),
+            _                     => None,
+        };
+
+        let md = match base {
+            Some(b) => self.disp_mode(m, b == BP),
+            None    => 0,
+        };
+
+        match sib {
+            Some(sib) => {
+                self.bytes.push(md << 6 | reg << 3 | SP);
+                self.bytes.push(sib | base.unwrap_or(BP));
+            },
+            None => {
+                self.bytes.push(md << 6 | reg << 3 | base.unwrap_or(BP));
+            },
+        }
+
+        match (base, md) {
+            (None, _) => self.disp(m, 4),
+            (_,    0) => (),
+            (_,    1) => self.disp(m, 1),
+            _         => self.disp(m, 4),
+        }
+    }
+
+    /// Returns the ModR/M `mod` field for the displacement of a memory
+    /// operand with a base or index register: 0 for none, 1 for 8 bits, or 2
+    /// for the full address size.  If `bp` is true, the base register is
+    /// `bp` or `ebp`, which requires a displacement.
+    fn disp_mode(&mut self, m: &Mem, bp: bool) -> u8 {
+        let disp = match m.disp {
+            Some(ref disp) => disp,
+            None           => return bp as u8,
+        };
+        match self.out.eval(disp) {
+            Some(Value::Const(0)) if !bp                      => 0,
+            Some(Value::Const(v)) if (-128..=127).contains(&v) => 1,
+            _                                                 => 2,
+        }
+    }
+
+    /// Appends the displacement of a memory operand as a field of `size`
+    /// bytes.
+    fn disp(&mut self, m: &Mem, size: u8) {
+        match m.disp {
+            Some(ref disp) => self.imm(disp, size),
+            None           => self.bytes.resize(self.bytes.len() + size as usize, 0),
+        }
+    }
+
+    /// Appends an operand-size prefix if `size` is 4.
+    fn prefix(&mut self, size: u8) -> Result {
+        if size == 4 {
+            self.require(I386)?;
+            self.bytes.push(0x66);
+        }
+        Ok(())
+    }
+
+    /// Appends the override prefix for segment register `s`.
+    fn seg_prefix(&mut self, s: u8) -> Result {
+        if s >= 4 {
+            self.require(I386)?;
+        }
+        self.bytes.push(SEG_PREFIX[s as usize]);
+        Ok(())
+    }
+
+    /// Fails unless the target has the given instruction set features.
+    fn require(&self, isa: u32) -> Result {
+        match self.x86.has(isa) {
+            true  => Ok(()),
+            false => Err(format!("instruction form is not available on {}", self.x86.name)),
+        }
+    }
+
+    // === Sizes and Values ===
+
+    /// Returns the operand size, which is the size of the register operands
+    /// among `ops` and of the size suffix, which must agree.
+    fn size(&self, ops: &[&Operand]) -> Result<u8> {
+        self.size_or(ops, 0)
+    }
+
+    /// Returns the operand size as for [`Self::size`], or `default` if
+    /// neither a register nor a suffix determines it.
+    fn size_or(&self, ops: &[&Operand], default: u8) -> Result<u8> {
+        let mut size = self.size;
+
+        for op in ops {
+            if let Operand::Reg(r) = op {
+                if size == 0 {
+                    size = r.size;
+                } else if size != r.size {
+                    return Err("operand size mismatch".into());
+                }
+            }
+        }
+
+        match (size, default) {
+            (0, 0) => Err("operand size required; add a size suffix".into()),
+            (0, d) => Ok(d),
+            (s, _) => Ok(s),
+        }
+    }
+
+    /// Returns the operand size as for [`Self::size`], which must be 16 or
+    /// 32 bits.
+    fn word_size(&self, ops: &[&Operand]) -> Result<u8> {
+        self.word_size_or(ops, 0)
+    }
+
+    /// Returns the operand size as for [`Self::size_or`], which must be 16
+    /// or 32 bits.
+    fn word_size_or(&self, ops: &[&Operand], default: u8) -> Result<u8> {
+        match self.size_or(ops, default)? {
+            1 => Err(INVALID.into()),
+            s => Ok(s),
+        }
+    }
+
+    /// Returns the value of the given expression if it is a constant.
+    fn constant(&mut self, expr: &Expr<Span>) -> Option<i64> {
+        match self.out.eval(expr) {
+            Some(Value::Const(v)) => Some(v),
+            _                     => None,
+        }
+    }
+
+    /// Returns the value of the given immediate for an operand of `size`
+    /// bytes if it is a constant that an 8-bit immediate can represent by
+    /// sign extension.
+    fn small(&mut self, expr: &Expr<Span>, size: u8) -> Option<i64> {
+        if size == 1 {
+            return None;
+        }
+        let bits = size as u32 * 8;
+        let v    = self.constant(expr)?;
+        let v    = match (0..1 << bits).contains(&v) {
+            true  => v << (64 - bits) >> (64 - bits),
+            false => v,
+        };
+        (-128..=127).contains(&v).then_some(v)
+    }
+
+    /// Appends an immediate field of `size` bytes with the given value.
+    fn imm(&mut self, expr: &Expr<Span>, size: u8) {
+        let kind = match size {
+            1 => RelocKind::INT8,
+            2 => RelocKind::INT16,
+            _ => RelocKind::INT32,
+        };
+        self.field(expr, kind, false);
+    }
+
+    /// Appends a field with the offset from the end of the instruction to
+    /// `target`.
+    fn rel(&mut self, target: &Expr<Span>, kind: RelocKind) {
+        self.field(target, kind, true);
+    }
+
+    fn field(&mut self, expr: &Expr<Span>, kind: RelocKind, pc_rel: bool) {
+        let at = self.bytes.len();
+        self.bytes.resize(at + kind.size, 0);
+        self.fields.push(Field { at, expr: expr.clone(), kind, pc_rel });
+    }
+
+    /// Completes the fields of the instruction and emits it, recording a
+    /// relocation for each field that the linker must compute.
+    fn finish(mut self) {
+        let len = self.bytes.len() as i64;
+
+        let mut relocs = vec![];
+
+        for field in std::mem::take(&mut self.fields) {
+            let value = match self.out.eval(&field.expr) {
+                Some(value) => value,
+                None        => continue,
+            };
+            let value = match field.pc_rel {
+                true  => pc_rel_value(self.out, self.span, value, len),
+                false => value,
+            };
+            match value {
+                Value::Const(v) => self.apply(&field, v),
+                Value::Reloc(_) => relocs.push(field),
+            }
+        }
+
+        let mut at = 0;
+
+        for field in relocs {
+            self.out.emit(&self.bytes[at..field.at]);
+            match field.pc_rel {
+                true  => self.out.reloc(&pc_rel_expr(&field.expr, len), field.kind),
+                false => self.out.reloc(&field.expr, field.kind),
+            }
+            at = field.at;
+        }
+
+        self.out.emit(&self.bytes[at..]);
+    }
+
+    /// Merges the value of the given field into the instruction bytes.
+    fn apply(&mut self, field: &Field, value: i64) {
+        let bytes = &mut self.bytes[field.at..field.at + field.kind.size];
+
+        let bits = bytes
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &b| acc << 8 | b as u64);
+
+        match (field.kind.apply)(value, bits) {
+            Ok(bits) => {
+                for (i, b) in bytes.iter_mut().enumerate() {
+                    *b = (bits >> (8 * i)) as u8;
+                }
+            },
+            Err(msg) => self.out.error(*field.expr.data(), &msg),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn operand(arg: &Arg<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let expr = match *arg {
+        Arg::Expr(ref e) => e,
+        Arg::Unknown(span) => {
+            out.error(span, "expected: operand");
+            return None;
+        },
+    };
+
+    match *expr {
+        Expr::Ident(_, name) => match register(&out.names()[name]) {
+            Some(r) => Some(r),
+            None    => Some(Operand::Imm(expr.clone())),
+        },
+        Expr::Deref(span, ref inner, false) => memory(span, inner, out),
+        Expr::Deref(span, _, true) => {
+            out.error(span, "invalid memory operand");
+            None
+        },
+        Expr::Binary(_, BinOp::Join, ref seg, ref off) => {
+            Some(Operand::Far((**seg).clone(), (**off).clone()))
+        },
+        _ => Some(Operand::Imm(expr.clone())),
+    }
+}
+
+/// Parses the contents of a memory operand `[...]`.
+fn memory(span: Span, inner: &Expr<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let (seg, inner) = match *inner {
+        Expr::Binary(_, BinOp::Join, ref lhs, ref rhs) => {
+            let seg = match **lhs {
+                Expr::Ident(_, name) => register(&out.names()[name]),
+                _                    => None,
+            };
+            match seg {
+                Some(Operand::Seg(s)) => (Some(s), &**rhs),
+                _                     => {
+                    out.error(*lhs.data(), "invalid segment override");
+                    return None;
+                },
+            }
+        },
+        _ => (None, inner),
+    };
+
+    let mut terms = vec![];
+    flatten_sum(inner, false, &mut terms);
+
+    let mut regs = vec![];
+    let mut rest = vec![];
+
+    for (neg, term) in terms {
+        let (name, scale) = match *term {
+            Expr::Ident(_, name) => (name, None),
+            Expr::Binary(_, BinOp::Mul, ref lhs, ref rhs) => match **lhs {
+                Expr::Ident(_, name) => (name, Some(rhs)),
+                _                    => {
+                    rest.push((neg, term));
+                    continue;
+                },
+            },
+            _ => {
+                rest.push((neg, term));
+                continue;
+            },
+        };
+
+        let span = *term.data();
+
+        let reg = match register(&out.names()[name]) {
+            Some(Operand::Reg(r)) if r.size > 1 => r,
+            Some(_) => {
+                out.error(span, "invalid register in address");
+                return None;
+            },
+            None => {
+                rest.push((neg, term));
+                continue;
+            },
+        };
+
+        if neg {
+            out.error(span, "cannot subtract a register");
+            return None;
+        }
+
+        let scale = match scale.map(|s| out.eval(s)) {
+            None                                           => 1,
+            Some(Some(Value::Const(s @ (1 | 2 | 4 | 8)))) => s as u8,
+            Some(_)                                        => {
+                out.error(span, "invalid scale");
+                return None;
+            },
+        };
+
+        regs.push((reg, scale));
+    }
+
+    let disp = sum(&rest);
+
+    match address(&regs) {
+        Some((None, None)) if disp.is_none() => (),
+        Some((base, index)) => return Some(Operand::Mem(Mem { seg, base, index, disp })),
+        None => (),
+    }
+
+    out.error(span, "invalid memory operand");
+    None
+}
+
+/// Returns the base register and the index register with its scale of a
+/// memory operand with the given registers and scales, if they form a valid
+/// address.
+#[allow(clippy::type_complexity)]
+fn address(regs: &[(Reg, u8)]) -> Option<(Option<Reg>, Option<(Reg, u8)>)> {
+    match *regs {
+        [] => Some((None, None)),
+
+        // 16-bit addressing
+        [(r, 1)] if r.size == 2 => match r.num {
+            BX | BP => Some((Some(r), None)),
+            SI | DI => Some((None, Some((r, 1)))),
+            _       => None,
+        },
+        [(a, 1), (b, 1)] if a.size == 2 && b.size == 2 => {
+            let (a, b) = if matches!(a.num, SI | DI) { (b, a) } else { (a, b) };
+            match (a.num, b.num) {
+                (BX | BP, SI | DI) => Some((Some(a), Some((b, 1)))),
+                _                  => None,
+            }
+        },
+
+        // 32-bit addressing
+        [(r, 1)] if r.size == 4 => Some((Some(r), None)),
+        [(r, s)] if r.size == 4 && r.num != SP => Some((None, Some((r, s)))),
+        [(a, sa), (b, sb)] if a.size == 4 && b.size == 4 => {
+            let ((base, sbase), index) = match sa != 1 || b.num == SP {
+                true  => ((b, sb), (a, sa)),
+                false => ((a, sa), (b, sb)),
+            };
+            match sbase == 1 && index.0.num != SP {
+                true  => Some((Some(base), Some(index))),
+                false => None,
+            }
+        },
+
+        _ => None,
+    }
+}
+
+/// Returns the register with the given name, if any.
+fn register(name: &str) -> Option<Operand> {
+    const GENERAL: [([&str; 8], u8); 3] = [
+        (["al",  "cl",  "dl",  "bl",  "ah",  "ch",  "dh",  "bh" ], 1),
+        (["ax",  "cx",  "dx",  "bx",  "sp",  "bp",  "si",  "di" ], 2),
+        (["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"], 4),
+    ];
+    const SEGMENT: [&str; 6] = ["es", "cs", "ss", "ds", "fs", "gs"];
+
+    let name = name.to_ascii_lowercase();
+
+    for (names, size) in GENERAL {
+        if let Some(num) = names.iter().position(|&n| n == name) {
+            return Some(Operand::Reg(Reg { num: num as u8, size }));
+        }
+    }
+
+    if let Some(num) = SEGMENT.iter().position(|&n| n == name) {
+        return Some(Operand::Seg(num as u8));
+    }
+
+    match name.as_str() {
+        "cr0" => Some(Operand::Ctl(0)),
+        "cr2" => Some(Operand::Ctl(2)),
+        "cr3" => Some(Operand::Ctl(3)),
+        "cr4" => Some(Operand::Ctl(4)),
+        _     => None,
+    }
+}
+
+/// Returns a number that identifies the register with the given name, the
+/// same for each name of the register, if any.
+pub(super) fn register_key(name: &str) -> Option<u32> {
+    match register(name)? {
+        Operand::Reg(r) => Some((r.size as u32) << 8 | r.num as u32),
+        Operand::Seg(n) => Some(0x1000 | n as u32),
+        Operand::Ctl(n) => Some(0x2000 | n as u32),
+        _               => None,
+    }
+}
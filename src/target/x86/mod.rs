@@ -0,0 +1,193 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Intel 8086 and successors in 16-bit real mode.
+//!
+//! Instructions use Intel mnemonics, with the destination first.  An
+//! instruction whose operand size does not follow from a register operand
+//! takes a size suffix `.b`, `.w`, or (on the 80386) `.d`: `mov.b [bx], 1`.
+//! Operands use ras syntax:
+//!
+//! | Operand                       | Intel               | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | register                      | `ax`                | `ax`
+//! | immediate                     | `42`                | `42`
+//! | memory                        | `[bx+si+4]`         | `[bx + si + 4]`
+//! | memory, direct                | `[0x7C00]`          | `[x'7c00]`
+//! | memory, segment override      | `es:[di]`           | `[es:di]`
+//! | memory, scaled index (80386)  | `[ebx+ecx*4]`       | `[ebx + ecx*4]`
+//! | far pointer                   | `0xF000:0xFFF0`     | `x'f000:x'fff0`
+//!
+//! A memory operand with 32-bit registers uses the 80386 addressing modes
+//! and an address-size prefix, and a 32-bit operand uses an operand-size
+//! prefix.  A jump or call takes its target address.  `jmp` and conditional
+//! jumps relax from the short form to the near form, unless the suffix `.b`
+//! or `.w` forces one of them.  Before the 80386, a conditional jump has no
+//! near form, so a far-off conditional jump becomes the opposite conditional
+//! jump around a near `jmp`.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::{Decoded, Emitter, Target};
+
+mod decode;
+mod encode;
+mod table;
+
+#[cfg(test)]
+mod tests;
+
+use self::table::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set feature: instructions of the 8086 and 8088.
+pub const I86: u32 = 1 << 0;
+
+/// Instruction set feature: additions of the 80186 and 80188.
+pub const I186: u32 = 1 << 1;
+
+/// Instruction set feature: additions of the 80386, including 32-bit
+/// registers and addressing.
+pub const I386: u32 = 1 << 2;
+
+/// Size suffixes, in order of their size code, with the operand size in
+/// bytes.
+const SIZES: [(&str, u8); 4] = [("", 0), (".b", 1), (".w", 2), (".d", 4)];
+
+/// Instruction table entry.
+#[derive(Debug)]
+pub struct Entry {
+    name: String,
+    isa:  u32,
+    op:   Op,
+}
+
+/// Member of the x86 family.
+#[derive(Debug)]
+pub struct X86 {
+    name:  &'static str,
+    isa:   u32,
+    table: Vec<Entry>,
+    index: HashMap<Name, usize>,
+}
+
+impl X86 {
+    /// Creates a new [`X86`] target with the given `name` and instruction set
+    /// features `isa`, interning its mnemonics in `names`.
+    ///
+    /// The target knows every mnemonic, so that it can reject one that the
+    /// processor lacks with a better message than an unknown instruction.
+    pub fn new(names: &mut NameTable, name: &'static str, isa: u32) -> Self {
+        let mut table = vec![];
+        let mut index = HashMap::new();
+
+        for (mnemonic, isa, op) in table::build() {
+            let i = table.len();
+            for (size, &(suffix, _)) in SIZES.iter().enumerate() {
+                if size != 0 && !op.sized() {
+                    continue;
+                }
+                let insn  = i << 2 | size;
+                let lower = format!("{}{}", mnemonic, suffix);
+                index.insert(names.add(&lower),                insn);
+                index.insert(names.add(&lower.to_uppercase()), insn);
+            }
+            table.push(Entry { name: mnemonic, isa, op });
+        }
+
+        Self { name, isa, table, index }
+    }
+
+    /// Returns whether the target has the given instruction set features.
+    pub fn has(&self, isa: u32) -> bool {
+        self.isa & isa == isa
+    }
+}
+
+impl Target for X86 {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        1
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[REL8, RelocKind::INT8, RelocKind::INT16, RelocKind::INT32]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        encode::register_key(name)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        let size = SIZES[insn & 3].1;
+        encode::encode(self, &self.table[insn >> 2], size, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        decode::decode(self, bytes, addr, label)
+    }
+}
+
+/// Creates an 8086 target.
+pub fn new_8086(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(X86::new(names, "8086", I86))
+}
+
+/// Creates an 80186 target.
+pub fn new_80186(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(X86::new(names, "80186", I86 | I186))
+}
+
+/// Creates an 80386 target in 16-bit mode.
+pub fn new_80386(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(X86::new(names, "80386", I86 | I186 | I386))
+}
+
+// ----------------------------------------------------------------------------
+
+/// 8-bit signed jump offset.
+pub const REL8: RelocKind = RelocKind { name: "x86-rel8", size: 1, apply: apply_rel8 };
+
+fn apply_rel8(value: i64, field: u64) -> Result<u64, String> {
+    match (-128..=127).contains(&value) {
+        true  => Ok(field & !0xFF | value as u64 & 0xFF),
+        false => Err(format!("jump offset {} out of range", value)),
+    }
+}
@@ -0,0 +1,255 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction table.
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Instruction kinds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    /// Instruction without operands, with the given encoding.
+    Fixed(&'static [u8]),
+
+    /// Arithmetic or logic with the given `/digit`: `add dst, src`.
+    Alu(u8),
+
+    /// `mov`
+    Mov,
+
+    /// `test`
+    Test,
+
+    /// `xchg`
+    Xchg,
+
+    /// `inc` (0) or `dec` (1).
+    IncDec(u8),
+
+    /// Group 3 instruction with the given `/digit`: `not`, `neg`, `mul`,
+    /// `div`, or `idiv`.
+    Unary(u8),
+
+    /// `imul`
+    Imul,
+
+    /// Shift or rotate with the given `/digit`.
+    Shift(u8),
+
+    /// `push`
+    Push,
+
+    /// `pop`
+    Pop,
+
+    /// `lea`
+    Lea,
+
+    /// Load of a far pointer, with the given opcode: `les r, [m]`.
+    LoadFar(&'static [u8]),
+
+    /// `jmp`
+    Jmp,
+
+    /// `call`
+    Call,
+
+    /// Conditional jump with the given condition code.
+    Jcc(u8),
+
+    /// Jump with only a short form, with the given opcode: `loop`.
+    Short(u8),
+
+    /// `ret` (`0xC3`) or `retf` (`0xCB`).
+    Ret(u8),
+
+    /// `int`
+    Int,
+
+    /// `in`
+    In,
+
+    /// `out`
+    Out,
+
+    /// `enter`
+    Enter,
+
+    /// `movzx` (`0xB6`) or `movsx` (`0xBE`).
+    Movx(u8),
+
+    /// Set byte on the given condition code.
+    Setcc(u8),
+
+    /// Load of a descriptor table register with the given `/digit`: `lgdt`
+    /// or `lidt`.
+    Desc(u8),
+}
+
+impl Op {
+    /// Returns whether the instruction takes a size suffix.
+    pub fn sized(self) -> bool {
+        !matches!(
+            self,
+            Op::Fixed(_) | Op::Lea | Op::LoadFar(_) | Op::Short(_)
+                | Op::Ret(_) | Op::Int | Op::In | Op::Out | Op::Enter | Op::Setcc(_)
+                | Op::Desc(_)
+        )
+    }
+}
+
+/// Condition codes and their names.
+pub const CONDS: &[(u8, &[&str])] = &[
+    (0x0, &["o"]),
+    (0x1, &["no"]),
+    (0x2, &["b", "c", "nae"]),
+    (0x3, &["nb", "nc", "ae"]),
+    (0x4, &["e", "z"]),
+    (0x5, &["ne", "nz"]),
+    (0x6, &["be", "na"]),
+    (0x7, &["nbe", "a"]),
+    (0x8, &["s"]),
+    (0x9, &["ns"]),
+    (0xA, &["p", "pe"]),
+    (0xB, &["np", "po"]),
+    (0xC, &["l", "nge"]),
+    (0xD, &["nl", "ge"]),
+    (0xE, &["le", "ng"]),
+    (0xF, &["nle", "g"]),
+];
+
+/// Instructions without operands on the 8086.
+const FIXED_86: &[(&str, &[u8])] = &[
+    ("nop",   &[0x90]),          ("hlt",   &[0xF4]),       ("cli",   &[0xFA]),
+    ("sti",   &[0xFB]),          ("cld",   &[0xFC]),       ("std",   &[0xFD]),
+    ("clc",   &[0xF8]),          ("stc",   &[0xF9]),       ("cmc",   &[0xF5]),
+    ("cbw",   &[0x98]),          ("cwd",   &[0x99]),       ("pushf", &[0x9C]),
+    ("popf",  &[0x9D]),          ("sahf",  &[0x9E]),       ("lahf",  &[0x9F]),
+    ("xlat",  &[0xD7]),          ("aaa",   &[0x37]),       ("aas",   &[0x3F]),
+    ("daa",   &[0x27]),          ("das",   &[0x2F]),       ("aam",   &[0xD4, 0x0A]),
+    ("aad",   &[0xD5, 0x0A]),    ("wait",  &[0x9B]),       ("int3",  &[0xCC]),
+    ("into",  &[0xCE]),          ("iret",  &[0xCF]),       ("lock",  &[0xF0]),
+    ("rep",   &[0xF3]),          ("repe",  &[0xF3]),       ("repz",  &[0xF3]),
+    ("repne", &[0xF2]),          ("repnz", &[0xF2]),       ("movsb", &[0xA4]),
+    ("movsw", &[0xA5]),          ("cmpsb", &[0xA6]),       ("cmpsw", &[0xA7]),
+    ("stosb", &[0xAA]),          ("stosw", &[0xAB]),       ("lodsb", &[0xAC]),
+    ("lodsw", &[0xAD]),          ("scasb", &[0xAE]),       ("scasw", &[0xAF]),
+];
+
+/// Instructions without operands added by the 80186.
+const FIXED_186: &[(&str, &[u8])] = &[
+    ("pusha", &[0x60]),          ("popa",  &[0x61]),       ("leave", &[0xC9]),
+    ("insb",  &[0x6C]),          ("insw",  &[0x6D]),       ("outsb", &[0x6E]),
+    ("outsw", &[0x6F]),
+];
+
+/// Instructions without operands added by the 80386, with their
+/// operand-size prefixes.
+const FIXED_386: &[(&str, &[u8])] = &[
+    ("cwde",   &[0x66, 0x98]),          ("cdq",    &[0x66, 0x99]),
+    ("pushfd", &[0x66, 0x9C]),          ("popfd",  &[0x66, 0x9D]),
+    ("pushad", &[0x66, 0x60]),          ("popad",  &[0x66, 0x61]),
+    ("iretd",  &[0x66, 0xCF]),          ("movsd",  &[0x66, 0xA5]),
+    ("cmpsd",  &[0x66, 0xA7]),          ("stosd",  &[0x66, 0xAB]),
+    ("lodsd",  &[0x66, 0xAD]),          ("scasd",  &[0x66, 0xAF]),
+    ("insd",   &[0x66, 0x6D]),          ("outsd",  &[0x66, 0x6F]),
+];
+
+// ----------------------------------------------------------------------------
+
+/// Builds the instruction table, as rows of mnemonic, required instruction
+/// set feature, and instruction kind.
+pub fn build() -> Vec<(String, u32, Op)> {
+    let mut t = vec![];
+
+    let mut add = |name: &str, isa: u32, op: Op| t.push((name.to_string(), isa, op));
+
+    for &(name, bytes) in FIXED_86 {
+        add(name, I86, Op::Fixed(bytes));
+    }
+
+    for &(name, bytes) in FIXED_186 {
+        add(name, I186, Op::Fixed(bytes));
+    }
+
+    for &(name, bytes) in FIXED_386 {
+        add(name, I386, Op::Fixed(bytes));
+    }
+
+    for (name, digit) in [
+        ("add", 0), ("or",  1), ("adc", 2), ("sbb", 3),
+        ("and", 4), ("sub", 5), ("xor", 6), ("cmp", 7),
+    ] {
+        add(name, I86, Op::Alu(digit));
+    }
+
+    for (name, digit) in [("not", 2), ("neg", 3), ("mul", 4), ("div", 6), ("idiv", 7)] {
+        add(name, I86, Op::Unary(digit));
+    }
+
+    for (name, digit) in [
+        ("rol", 0), ("ror", 1), ("rcl", 2), ("rcr", 3),
+        ("shl", 4), ("sal", 4), ("shr", 5), ("sar", 7),
+    ] {
+        add(name, I86, Op::Shift(digit));
+    }
+
+    add("mov",   I86,  Op::Mov);
+    add("test",  I86,  Op::Test);
+    add("xchg",  I86,  Op::Xchg);
+    add("inc",   I86,  Op::IncDec(0));
+    add("dec",   I86,  Op::IncDec(1));
+    add("imul",  I86,  Op::Imul);
+    add("push",  I86,  Op::Push);
+    add("pop",   I86,  Op::Pop);
+    add("lea",   I86,  Op::Lea);
+    add("les",   I86,  Op::LoadFar(&[0xC4]));
+    add("lds",   I86,  Op::LoadFar(&[0xC5]));
+    add("lss",   I386, Op::LoadFar(&[0x0F, 0xB2]));
+    add("lfs",   I386, Op::LoadFar(&[0x0F, 0xB4]));
+    add("lgs",   I386, Op::LoadFar(&[0x0F, 0xB5]));
+    add("jmp",   I86,  Op::Jmp);
+    add("call",  I86,  Op::Call);
+    add("loopne",I86,  Op::Short(0xE0));
+    add("loopnz",I86,  Op::Short(0xE0));
+    add("loope", I86,  Op::Short(0xE1));
+    add("loopz", I86,  Op::Short(0xE1));
+    add("loop",  I86,  Op::Short(0xE2));
+    add("jcxz",  I86,  Op::Short(0xE3));
+    add("ret",   I86,  Op::Ret(0xC3));
+    add("retf",  I86,  Op::Ret(0xCB));
+    add("int",   I86,  Op::Int);
+    add("in",    I86,  Op::In);
+    add("out",   I86,  Op::Out);
+    add("enter", I186, Op::Enter);
+    add("movzx", I386, Op::Movx(0xB6));
+    add("movsx", I386, Op::Movx(0xBE));
+    add("lgdt",  I386, Op::Desc(2));
+    add("lidt",  I386, Op::Desc(3));
+
+    for &(cc, names) in CONDS {
+        for name in names {
+            add(&format!("j{}",   name), I86,  Op::Jcc(cc));
+            add(&format!("set{}", name), I386, Op::Setcc(cc));
+        }
+    }
+
+    t
+}
@@ -0,0 +1,358 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, bytes};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u8> = Bench { origin: 0x200, split: bytes };
+
+#[test]
+fn addressing_modes() {
+    BENCH.check("8086", &[
+        ("mov ax, bx",                          &[0x89, 0xD8]),
+        ("MOV AL, [BX + SI]",                   &[0x8A, 0x00]),
+        ("mov [bx + di + 4], cx",               &[0x89, 0x49, 0x04]),
+        ("mov [di + bx + 4], cx",               &[0x89, 0x49, 0x04]),
+        ("mov dx, [bp]",                        &[0x8B, 0x56, 0x00]),
+        ("mov ax, [bp + si - 2]",               &[0x8B, 0x42, 0xFE]),
+        ("mov ax, [si + x'1234]",               &[0x8B, 0x84, 0x34, 0x12]),
+        ("mov ax, [bx + 0]",                    &[0x8B, 0x07]),
+        ("mov cx, [x'7c00]",                    &[0x8B, 0x0E, 0x00, 0x7C]),
+        ("mov ax, [x'7c00]",                    &[0xA1, 0x00, 0x7C]),
+        ("mov [x'7c00], al",                    &[0xA2, 0x00, 0x7C]),
+        ("mov [es:di], al",                     &[0x26, 0x88, 0x05]),
+        ("mov ax, [cs:x'10]",                   &[0x2E, 0xA1, 0x10, 0x00]),
+        ("mov.b [bx], 1",                       &[0xC6, 0x07, 0x01]),
+        ("mov.w [bx], 1",                       &[0xC7, 0x07, 0x01, 0x00]),
+        ("mov si, x'7c00",                      &[0xBE, 0x00, 0x7C]),
+        ("mov ah, x'0e",                        &[0xB4, 0x0E]),
+        ("mov ds, ax",                          &[0x8E, 0xD8]),
+        ("mov ax, cs",                          &[0x8C, 0xC8]),
+        ("mov [bx], es",                        &[0x8C, 0x07]),
+    ]);
+}
+
+#[test]
+fn instructions() {
+    BENCH.check("8086", &[
+        ("add ax, 1",                           &[0x83, 0xC0, 0x01]),
+        ("add al, 1",                           &[0x04, 0x01]),
+        ("add ax, x'1234",                      &[0x05, 0x34, 0x12]),
+        ("and ax, x'fff0",                      &[0x83, 0xE0, 0xF0]),
+        ("sub bx, x'100",                       &[0x81, 0xEB, 0x00, 0x01]),
+        ("cmp.b [si], 0",                       &[0x80, 0x3C, 0x00]),
+        ("xor ax, ax",                          &[0x31, 0xC0]),
+        ("or dx, [bx]",                         &[0x0B, 0x17]),
+        ("test al, 1",                          &[0xA8, 0x01]),
+        ("test.w [bx], x'8000",                 &[0xF7, 0x07, 0x00, 0x80]),
+        ("xchg ax, bx",                         &[0x93]),
+        ("xchg bx, [si]",                       &[0x87, 0x1C]),
+        ("inc cx",                              &[0x41]),
+        ("inc al",                              &[0xFE, 0xC0]),
+        ("dec.b [bx]",                          &[0xFE, 0x0F]),
+        ("neg ax",                              &[0xF7, 0xD8]),
+        ("mul bl",                              &[0xF6, 0xE3]),
+        ("div.w [bx]",                          &[0xF7, 0x37]),
+        ("imul cx",                             &[0xF7, 0xE9]),
+        ("shl ax, 1",                           &[0xD1, 0xE0]),
+        ("shr al, cl",                          &[0xD2, 0xE8]),
+        ("rol.w [bx], 1",                       &[0xD1, 0x07]),
+        ("push ax",                             &[0x50]),
+        ("push es",                             &[0x06]),
+        ("pop ds",                              &[0x1F]),
+        ("push.w [bx]",                         &[0xFF, 0x37]),
+        ("pop.w [bx]",                          &[0x8F, 0x07]),
+        ("lea si, [bx + 4]",                    &[0x8D, 0x77, 0x04]),
+        ("les di, [bx]",                        &[0xC4, 0x3F]),
+        ("int x'10",                            &[0xCD, 0x10]),
+        ("ret",                                 &[0xC3]),
+        ("ret 4",                               &[0xC2, 0x04, 0x00]),
+        ("retf",                                &[0xCB]),
+        ("in al, x'60",                         &[0xE4, 0x60]),
+        ("in ax, dx",                           &[0xED]),
+        ("out dx, al",                          &[0xEE]),
+        ("out x'80, al",                        &[0xE6, 0x80]),
+        ("jmp x'f000:x'fff0",                   &[0xEA, 0xF0, 0xFF, 0x00, 0xF0]),
+        ("call x'1234:x'5678",                  &[0x9A, 0x78, 0x56, 0x34, 0x12]),
+        ("jmp ax",                              &[0xFF, 0xE0]),
+        ("call [bx]",                           &[0xFF, 0x17]),
+        ("rep\nmovsb",                          &[0xF3, 0xA4]),
+        ("cli\nhlt",                            &[0xFA, 0xF4]),
+        ("aam",                                 &[0xD4, 0x0A]),
+    ]);
+}
+
+#[test]
+fn jumps() {
+    BENCH.check("8086", &[
+        ("jmp x'200",                           &[0xEB, 0xFE]),
+        ("jmp x'281",                           &[0xEB, 0x7F]),
+        ("jmp x'282",                           &[0xE9, 0x7F, 0x00]),
+        ("jmp x'100",                           &[0xE9, 0xFD, 0xFE]),
+        ("call x'300",                          &[0xE8, 0xFD, 0x00]),
+        ("je x'210",                            &[0x74, 0x0E]),
+        ("jne x'300",                           &[0x74, 0x03, 0xE9, 0xFB, 0x00]),
+        ("loop x'200",                          &[0xE2, 0xFE]),
+        ("jcxz x'202",                          &[0xE3, 0x00]),
+    ]);
+    BENCH.check("80386", &[
+        ("jne x'300",                           &[0x0F, 0x85, 0xFC, 0x00]),
+        ("jnz x'210",                           &[0x75, 0x0E]),
+    ]);
+
+    // A size suffix forces the short or near form
+    BENCH.check("8086", &[
+        ("jmp.w x'200",                         &[0xE9, 0xFD, 0xFF]),
+        ("jmp.b x'210",                         &[0xEB, 0x0E]),
+        ("je.w x'210",                          &[0x75, 0x03, 0xE9, 0x0B, 0x00]),
+        ("je.b x'210",                          &[0x74, 0x0E]),
+    ]);
+
+    // A jump over code that grows past the short range takes the near form,
+    // which moves its target
+    let (bytes, session) = BENCH.assemble("8086", "jmp end\njc end\n.skip 126\nend:");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(bytes[..5], [0xE9, 0x80, 0x00, 0x72, 0x7E]);
+
+    let (bytes, session) = BENCH.assemble("8086", "jmp end\njc end\n.skip 125\nend:");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(bytes[..4], [0xEB, 0x7F, 0x72, 0x7D]);
+
+    // Growth of one jump can push another out of the short range
+    let (bytes, session) = BENCH.assemble("8086", "top: jz end\n.skip 125\njmp top\nend:");
+    assert_eq!(session.error_count(), 0);
+    assert_eq!(bytes[..5],    [0x75, 0x03, 0xE9, 0x80, 0x00]);
+    assert_eq!(bytes[130..], [0xE9, 0x7B, 0xFF]);
+}
+
+#[test]
+fn extensions() {
+    BENCH.check("80186", &[
+        ("push 1",                              &[0x6A, 0x01]),
+        ("push x'1234",                         &[0x68, 0x34, 0x12]),
+        ("shl ax, 4",                           &[0xC1, 0xE0, 0x04]),
+        ("imul ax, bx, 10",                     &[0x6B, 0xC3, 0x0A]),
+        ("imul cx, [bx], 1000",                 &[0x69, 0x0F, 0xE8, 0x03]),
+        ("imul ax, 10",                         &[0x6B, 0xC0, 0x0A]),
+        ("pusha",                               &[0x60]),
+        ("enter 8, 0",                          &[0xC8, 0x08, 0x00, 0x00]),
+    ]);
+    BENCH.check("80386", &[
+        ("mov eax, ebx",                        &[0x66, 0x89, 0xD8]),
+        ("mov eax, x'12345678",                 &[0x66, 0xB8, 0x78, 0x56, 0x34, 0x12]),
+        ("add.d [bx], 1",                       &[0x66, 0x83, 0x07, 0x01]),
+        ("mov eax, [ebx + ecx*4 + 8]",          &[0x67, 0x66, 0x8B, 0x44, 0x8B, 0x08]),
+        ("mov ax, [esp]",                       &[0x67, 0x8B, 0x04, 0x24]),
+        ("mov ax, [ebp]",                       &[0x67, 0x8B, 0x45, 0x00]),
+        ("mov ax, [esi + x'1000]",              &[0x67, 0x8B, 0x86, 0x00, 0x10, 0x00, 0x00]),
+        ("mov ax, [ecx*2]",                     &[0x67, 0x8B, 0x04, 0x4D, 0x00, 0x00, 0x00, 0x00]),
+        ("mov [fs:bx], ax",                     &[0x64, 0x89, 0x07]),
+        ("push fs",                             &[0x0F, 0xA0]),
+        ("pop gs",                              &[0x0F, 0xA9]),
+        ("mov eax, cr0",                        &[0x0F, 0x20, 0xC0]),
+        ("mov cr0, eax",                        &[0x0F, 0x22, 0xC0]),
+        ("movzx eax, al",                       &[0x66, 0x0F, 0xB6, 0xC0]),
+        ("movzx.b ax, [bx]",                    &[0x0F, 0xB6, 0x07]),
+        ("movsx ecx, dx",                       &[0x66, 0x0F, 0xBF, 0xCA]),
+        ("imul ax, bx",                         &[0x0F, 0xAF, 0xC3]),
+        ("sete al",                             &[0x0F, 0x94, 0xC0]),
+        ("lgdt [x'7e00]",                       &[0x0F, 0x01, 0x16, 0x00, 0x7E]),
+        ("jmp.d x'8:x'1000",                    &[0x66, 0xEA, 0x00, 0x10, 0x00, 0x00, 0x08, 0x00]),
+        ("cdq",                                 &[0x66, 0x99]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("8086").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  mov si, label
+                call label
+                jmp start
+        label:  ret
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(program.sections[0].data, [
+        0xBE, 0x08, 0x00, 0xE8, 0x02, 0x00, 0xEB, 0xF8, 0xC3,
+    ]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("8086",  "pusha"),               "instruction 'pusha' is not available on 8086");
+    assert_eq!(BENCH.error("8086",  "push 1"),              "instruction form is not available on 8086");
+    assert_eq!(BENCH.error("8086",  "mov eax, 1"),          "instruction form is not available on 8086");
+    assert_eq!(BENCH.error("80186", "mov ax, [ebx]"),       "instruction form is not available on 80186");
+    assert_eq!(BENCH.error("8086",  "mov [bx], 1"),         "operand size required; add a size suffix");
+    assert_eq!(BENCH.error("8086",  "mov ax, bl"),          "operand size mismatch");
+    assert_eq!(BENCH.error("8086",  "mov.b ax, 1"),         "operand size mismatch");
+    assert_eq!(BENCH.error("8086",  "mov ax, [bx + bp]"),   "invalid memory operand");
+    assert_eq!(BENCH.error("8086",  "mov ax, [si - bx]"),   "cannot subtract a register");
+    assert_eq!(BENCH.error("8086",  "mov ax, [al]"),        "invalid register in address");
+    assert_eq!(BENCH.error("8086",  "mov ax, [ax:bx]"),     "invalid segment override");
+    assert_eq!(BENCH.error("80386", "mov ax, [eax*3]"),     "invalid scale");
+    assert_eq!(BENCH.error("8086",  "mov cs, ax"),          "invalid operands");
+    assert_eq!(BENCH.error("8086",  "lea ax, bx"),          "invalid operands");
+    assert_eq!(BENCH.error("8086",  "loop x'300"),          "jump offset 254 out of range");
+    assert_eq!(BENCH.error("8086",  "mov al, x'1234"),      "value 4660 does not fit in 8 bits");
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("8086", &[
+            "nop",
+            "aam",
+            "rep",
+            "movsb",
+            "mov ax, bx",
+            "mov al, [bx + si]",
+            "mov [bx + di + 4], cx",
+            "mov dx, [bp]",
+            "mov ax, [bp + si - 2]",
+            "mov ax, [si + x'1234]",
+            "mov cx, [x'7C00]",
+            "mov ax, [start]",
+            "mov [es:di], al",
+            "mov.b [bx], 1",
+            "mov ds, ax",
+            "mov ax, es",
+            "mov cl, x'12",
+            "mov si, x'1234",
+            "add ax, 1",
+            "add al, 1",
+            "add ax, x'1234",
+            "sub bx, x'100",
+            "and ax, -x'10",
+            "cmp.b [si], 0",
+            "adc [bx], ax",
+            "xor ax, [bx]",
+            "test.w [bx], x'8000",
+            "test ax, x'8000",
+            "test [bx], al",
+            "xchg ax, bx",
+            "xchg [si], bx",
+            "inc cx",
+            "dec.b [bx]",
+            "neg ax",
+            "imul.w [bx]",
+            "shl ax, 1",
+            "shr al, cl",
+            "sar.w [bx], 1",
+            "push ax",
+            "push es",
+            "pop ds",
+            "push.w [bx]",
+            "pop.w [bx]",
+            "lea si, [bx + 4]",
+            "les di, [bx]",
+            "int x'10",
+            "ret",
+            "ret 4",
+            "retf",
+            "in al, x'60",
+            "in ax, dx",
+            "out dx, al",
+            "out x'80, ax",
+            "jmp start",
+            "jmp.w start",
+            "jmp x'1000",
+            "jmp x'F000:x'FFF0",
+            "jmp ax",
+            "call [bx]",
+            "call start",
+            "jne start",
+            "jne.w start",
+            "jb x'1000",
+            "loop start",
+            "jcxz start",
+        ][..]),
+        ("80186", &[
+            "pusha",
+            "push 1",
+            "push x'1234",
+            "imul ax, bx, 9",
+            "imul cx, [bx], x'1234",
+            "shl ax, 4",
+            "enter 8, 0",
+        ][..]),
+        ("80386", &[
+            "cwde",
+            "mov eax, ebx",
+            "mov eax, x'12345678",
+            "mov eax, [ebx + ecx*4 + 8]",
+            "mov ax, [esp]",
+            "mov ax, [ebp]",
+            "mov ax, [ecx*2]",
+            "mov [fs:bx], ax",
+            "mov fs, ax",
+            "mov cr0, eax",
+            "mov eax, cr3",
+            "add.d [bx], 1",
+            "push.d 1",
+            "push fs",
+            "pop gs",
+            "imul eax, ecx",
+            "movzx ax, bl",
+            "movzx.b eax, [bx]",
+            "movsx.w eax, [bx]",
+            "lss sp, [bx]",
+            "lgdt [bx]",
+            "sete al",
+            "jne.w start",
+            "jne x'1000",
+            "jmp.d x'F000:x'12345678",
+            "call [ebx]",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'200"));
+            let decoded    = decoder.decode(&bytes, 0x200, &label).expect(line);
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let i8086 = (find("8086").unwrap().new)(&mut NameTable::new());
+    assert_eq!(i8086.decode(&[0x60], 0x200, &label), None);
+    assert_eq!(i8086.decode(&[0x66, 0x89, 0xD8], 0x200, &label), None);
+
+    // Prefixes that no operand uses do not decode
+    assert_eq!(i8086.decode(&[0x26, 0x89, 0xD8], 0x200, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(i8086.decode(&[0xB8, 0x34], 0x200, &label), None);
+}
@@ -0,0 +1,566 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction encoding.
+
+use crate::asm::RelocKind;
+use crate::lang::ast::*;
+use crate::target::{flatten_sum, pc_rel_expr, pc_rel_value, sum, Emitter, Value};
+
+use super::*;
+
+// ----------------------------------------------------------------------------
+
+/// Operand of an instruction.
+#[derive(Clone, Debug)]
+enum Operand {
+    /// Register.
+    Reg(Reg),
+
+    /// Memory at the address in a register, or the port in `c`: `[hl]`.
+    Ind(Reg),
+
+    /// Memory at the address in an index register plus a displacement:
+    /// `[ix + 5]`.  The operand holds the prefix that selects the index
+    /// register.
+    Idx(u8, Option<Expr<Span>>),
+
+    /// Memory or port at an address: `[x'1234]`.
+    Mem(Expr<Span>),
+
+    /// Immediate value or target address, with the condition code that the
+    /// operand names, if any.
+    Imm(Expr<Span>, Option<u8>),
+}
+
+/// Registers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Reg {
+    B, C, D, E, H, L, A, I, R,
+    Ixh, Ixl, Iyh, Iyl,
+    Bc, De, Hl, Sp, Af, Ix, Iy,
+}
+
+impl Reg {
+    /// Returns the prefix that selects the index register of an index
+    /// register or index register half.
+    fn prefix(self) -> Option<u8> {
+        match self {
+            Reg::Ix | Reg::Ixh | Reg::Ixl => Some(0xDD),
+            Reg::Iy | Reg::Iyh | Reg::Iyl => Some(0xFD),
+            _                             => None,
+        }
+    }
+
+    /// Returns the encoding of an 8-bit register in an `r` field.
+    fn r(self) -> Option<u8> {
+        match self {
+            Reg::B                       => Some(0),
+            Reg::C                       => Some(1),
+            Reg::D                       => Some(2),
+            Reg::E                       => Some(3),
+            Reg::H | Reg::Ixh | Reg::Iyh => Some(4),
+            Reg::L | Reg::Ixl | Reg::Iyl => Some(5),
+            Reg::A                       => Some(7),
+            _                            => None,
+        }
+    }
+
+    /// Returns the encoding of a register pair in an `ss` field, in which
+    /// `hl` has the code 2 and `sp` has the code 3.
+    fn ss(self) -> Option<u8> {
+        match self {
+            Reg::Bc => Some(0),
+            Reg::De => Some(1),
+            Reg::Hl => Some(2),
+            Reg::Sp => Some(3),
+            _       => None,
+        }
+    }
+
+    /// Returns whether the register is an undocumented index register half.
+    fn is_half(self) -> bool {
+        matches!(self, Reg::Ixh | Reg::Ixl | Reg::Iyh | Reg::Iyl)
+    }
+}
+
+/// 8-bit operand that an `r` field can select: a register, `[hl]`, or an
+/// indexed memory operand.
+struct Loc<'a> {
+    /// Prefix that selects an index register.
+    prefix: Option<u8>,
+
+    /// Encoding in an `r` field.
+    code: u8,
+
+    /// Displacement of an indexed memory operand.
+    disp: Option<Option<&'a Expr<Span>>>,
+
+    /// Whether the operand is an index register half.
+    half: bool,
+}
+
+impl<'a> Loc<'a> {
+    fn new(op: &'a Operand) -> Option<Self> {
+        match *op {
+            Operand::Reg(r) => Some(Self {
+                prefix: r.prefix(),
+                code:   r.r()?,
+                disp:   None,
+                half:   r.is_half(),
+            }),
+            Operand::Ind(Reg::Hl) => Some(Self {
+                prefix: None, code: 6, disp: None, half: false,
+            }),
+            Operand::Idx(prefix, ref disp) => Some(Self {
+                prefix: Some(prefix), code: 6, disp: Some(disp.as_ref()), half: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the operand is in memory.
+    fn is_mem(&self) -> bool {
+        self.code == 6
+    }
+}
+
+/// Part of an instruction encoding.
+enum Part<'a> {
+    /// Opcode or prefix byte.
+    Byte(u8),
+
+    /// Index displacement; absent means zero.
+    Disp(Option<&'a Expr<Span>>),
+
+    /// 8-bit immediate value or port.
+    Imm8(&'a Expr<Span>),
+
+    /// 16-bit immediate value or address.
+    Imm16(&'a Expr<Span>),
+
+    /// Relative jump offset, the last byte of the instruction.
+    Rel(&'a Expr<Span>),
+}
+
+use Part::*;
+
+/// Parses the operands of the given instruction statement and emits its
+/// encoding.
+pub fn encode(op: Op, undoc: bool, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+    let span = stmt.data;
+
+    let ops = stmt.args
+        .iter()
+        .map(|arg| operand(arg, undoc, out))
+        .collect::<Option<Vec<_>>>();
+
+    let ops = match ops {
+        Some(ops) => ops,
+        None      => return,
+    };
+
+    let parts = match parts(op, undoc, &ops, out) {
+        Ok(parts) => parts,
+        Err(msg)  => return out.error(span, msg),
+    };
+
+    let size = parts.iter().map(|p| match p { Imm16(_) => 2, _ => 1 }).sum::<i64>();
+
+    for part in parts {
+        match part {
+            Byte(b)       => out.emit(&[b]),
+            Disp(Some(e)) => put(e, DISP8, out),
+            Disp(None)    => out.emit(&[0]),
+            Imm8(e)       => put(e, RelocKind::INT8, out),
+            Imm16(e)      => put(e, RelocKind::INT16, out),
+            Rel(e)        => rel(span, e, size, out),
+        }
+    }
+}
+
+/// Returns the encoding of an instruction of kind `op` with operands `ops`.
+fn parts<'a>(op: Op, undoc: bool, ops: &'a [Operand], out: &mut dyn Emitter)
+    -> Result<Vec<Part<'a>>, &'static str>
+{
+    use Operand::{Idx, Imm, Ind, Mem, Reg as R};
+
+    const INVALID: &str = "invalid operands";
+
+    let parts = match (op, ops) {
+        (Op::Fixed(bytes), []) => bytes.iter().map(|&b| Byte(b)).collect(),
+
+        // 8-bit loads with special registers or addresses
+        (Op::Ld, [R(Reg::A), Ind(Reg::Bc)]) => vec![Byte(0x0A)],
+        (Op::Ld, [R(Reg::A), Ind(Reg::De)]) => vec![Byte(0x1A)],
+        (Op::Ld, [R(Reg::A), Mem(e)])       => vec![Byte(0x3A), Imm16(e)],
+        (Op::Ld, [Ind(Reg::Bc), R(Reg::A)]) => vec![Byte(0x02)],
+        (Op::Ld, [Ind(Reg::De), R(Reg::A)]) => vec![Byte(0x12)],
+        (Op::Ld, [Mem(e), R(Reg::A)])       => vec![Byte(0x32), Imm16(e)],
+        (Op::Ld, [R(Reg::A), R(Reg::I)])    => vec![Byte(0xED), Byte(0x57)],
+        (Op::Ld, [R(Reg::A), R(Reg::R)])    => vec![Byte(0xED), Byte(0x5F)],
+        (Op::Ld, [R(Reg::I), R(Reg::A)])    => vec![Byte(0xED), Byte(0x47)],
+        (Op::Ld, [R(Reg::R), R(Reg::A)])    => vec![Byte(0xED), Byte(0x4F)],
+
+        // 16-bit loads
+        (Op::Ld, [R(Reg::Sp), R(Reg::Hl)]) => vec![Byte(0xF9)],
+        (Op::Ld, [R(Reg::Sp), R(r @ (Reg::Ix | Reg::Iy))]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0xF9)]
+        },
+        (Op::Ld, [R(r @ (Reg::Ix | Reg::Iy)), Imm(e, _)]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0x21), Imm16(e)]
+        },
+        (Op::Ld, [R(r @ (Reg::Ix | Reg::Iy)), Mem(e)]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0x2A), Imm16(e)]
+        },
+        (Op::Ld, [Mem(e), R(r @ (Reg::Ix | Reg::Iy))]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0x22), Imm16(e)]
+        },
+        (Op::Ld, [R(Reg::Hl), Mem(e)]) => vec![Byte(0x2A), Imm16(e)],
+        (Op::Ld, [Mem(e), R(Reg::Hl)]) => vec![Byte(0x22), Imm16(e)],
+        (Op::Ld, [R(r), Imm(e, _)]) if r.ss().is_some() => {
+            vec![Byte(0x01 | r.ss().unwrap() << 4), Imm16(e)]
+        },
+        (Op::Ld, [R(r), Mem(e)]) if r.ss().is_some() => {
+            vec![Byte(0xED), Byte(0x4B | r.ss().unwrap() << 4), Imm16(e)]
+        },
+        (Op::Ld, [Mem(e), R(r)]) if r.ss().is_some() => {
+            vec![Byte(0xED), Byte(0x43 | r.ss().unwrap() << 4), Imm16(e)]
+        },
+
+        // 8-bit loads between registers and memory
+        (Op::Ld, [dst, Imm(e, _)]) => {
+            let dst       = Loc::new(dst).ok_or(INVALID)?;
+            let mut parts = prefixed(dst.prefix, 0x06 | dst.code << 3, &dst);
+            parts.push(Imm8(e));
+            parts
+        },
+        (Op::Ld, [dst, src]) => {
+            let dst    = Loc::new(dst).ok_or(INVALID)?;
+            let src    = Loc::new(src).ok_or(INVALID)?;
+            let prefix = pair(&dst, &src)?;
+            let mem    = if src.is_mem() { &src } else { &dst };
+            prefixed(prefix, 0x40 | dst.code << 3 | src.code, mem)
+        },
+
+        // 16-bit arithmetic
+        (Op::Alu(0x80), [R(Reg::Hl), R(r)]) if r.ss().is_some() => {
+            vec![Byte(0x09 | r.ss().unwrap() << 4)]
+        },
+        (Op::Alu(0x88), [R(Reg::Hl), R(r)]) if r.ss().is_some() => {
+            vec![Byte(0xED), Byte(0x4A | r.ss().unwrap() << 4)]
+        },
+        (Op::Alu(0x98), [R(Reg::Hl), R(r)]) if r.ss().is_some() => {
+            vec![Byte(0xED), Byte(0x42 | r.ss().unwrap() << 4)]
+        },
+        (Op::Alu(0x80), [R(x @ (Reg::Ix | Reg::Iy)), R(r)]) => {
+            let pp = match *r {
+                Reg::Bc      => 0,
+                Reg::De      => 1,
+                r if r == *x => 2,
+                Reg::Sp      => 3,
+                _            => return Err(INVALID),
+            };
+            vec![Byte(x.prefix().unwrap()), Byte(0x09 | pp << 4)]
+        },
+
+        // 8-bit arithmetic and logic
+        (Op::Alu(base), [R(Reg::A), src] | [src]) => match *src {
+            Imm(ref e, _) => vec![Byte(base | 0x46), Imm8(e)],
+            ref src       => {
+                let src = Loc::new(src).ok_or(INVALID)?;
+                prefixed(src.prefix, base | src.code, &src)
+            },
+        },
+
+        (Op::IncDec(dec), [R(r @ (Reg::Ix | Reg::Iy))]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0x23 | dec << 3)]
+        },
+        (Op::IncDec(dec), [R(r)]) if r.ss().is_some() => {
+            vec![Byte(0x03 | dec << 3 | r.ss().unwrap() << 4)]
+        },
+        (Op::IncDec(dec), [dst]) => {
+            let dst = Loc::new(dst).ok_or(INVALID)?;
+            prefixed(dst.prefix, 0x04 | dec | dst.code << 3, &dst)
+        },
+
+        (Op::Stack(base), [R(r)]) => match *r {
+            Reg::Bc           => vec![Byte(base)],
+            Reg::De           => vec![Byte(base | 0x10)],
+            Reg::Hl           => vec![Byte(base | 0x20)],
+            Reg::Af           => vec![Byte(base | 0x30)],
+            Reg::Ix | Reg::Iy => vec![Byte(r.prefix().unwrap()), Byte(base | 0x20)],
+            _                 => return Err(INVALID),
+        },
+
+        (Op::Ex, [R(Reg::De), R(Reg::Hl)])  => vec![Byte(0xEB)],
+        (Op::Ex, [R(Reg::Af), R(Reg::Af)])  => vec![Byte(0x08)],
+        (Op::Ex, [Ind(Reg::Sp), R(Reg::Hl)])  => vec![Byte(0xE3)],
+        (Op::Ex, [Ind(Reg::Sp), R(r @ (Reg::Ix | Reg::Iy))]) => {
+            vec![Byte(r.prefix().unwrap()), Byte(0xE3)]
+        },
+
+        (Op::Im, [Imm(e, _)]) => match out.eval(e) {
+            Some(Value::Const(0)) => vec![Byte(0xED), Byte(0x46)],
+            Some(Value::Const(1)) => vec![Byte(0xED), Byte(0x56)],
+            Some(Value::Const(2)) => vec![Byte(0xED), Byte(0x5E)],
+            None                  => vec![Byte(0xED), Byte(0x46)],
+            _                     => return Err("invalid interrupt mode"),
+        },
+
+        (Op::Cb(base), [dst]) => {
+            let dst = Loc::new(dst).ok_or(INVALID)?;
+            cb(base | dst.code, &dst)?
+        },
+
+        (Op::Bit(base), [Imm(bit, _), dst]) => {
+            let bit = match out.eval(bit) {
+                Some(Value::Const(b @ 0..=7)) => b as u8,
+                None                          => 0,
+                _                             => return Err("invalid bit number"),
+            };
+            let dst = Loc::new(dst).ok_or(INVALID)?;
+            cb(base | bit << 3 | dst.code, &dst)?
+        },
+
+        (Op::Jp, [Imm(e, _)])                   => vec![Byte(0xC3), Imm16(e)],
+        (Op::Jp, [Imm(_, Some(cc)), Imm(e, _)]) => vec![Byte(0xC2 | cc << 3), Imm16(e)],
+        (Op::Jp, [R(Reg::C), Imm(e, _)])        => vec![Byte(0xDA), Imm16(e)],
+        (Op::Jp, [Ind(Reg::Hl)])                => vec![Byte(0xE9)],
+        (Op::Jp, [Idx(prefix, None)])           => vec![Byte(*prefix), Byte(0xE9)],
+
+        (Op::Jr, [Imm(e, _)])                           => vec![Byte(0x18), Rel(e)],
+        (Op::Jr, [Imm(_, Some(cc @ 0..=3)), Imm(e, _)]) => vec![Byte(0x20 | cc << 3), Rel(e)],
+        (Op::Jr, [R(Reg::C), Imm(e, _)])                => vec![Byte(0x38), Rel(e)],
+
+        (Op::Djnz, [Imm(e, _)]) => vec![Byte(0x10), Rel(e)],
+
+        (Op::Call, [Imm(e, _)])                   => vec![Byte(0xCD), Imm16(e)],
+        (Op::Call, [Imm(_, Some(cc)), Imm(e, _)]) => vec![Byte(0xC4 | cc << 3), Imm16(e)],
+        (Op::Call, [R(Reg::C), Imm(e, _)])      => vec![Byte(0xDC), Imm16(e)],
+
+        (Op::Ret, [])                 => vec![Byte(0xC9)],
+        (Op::Ret, [Imm(_, Some(cc))]) => vec![Byte(0xC0 | cc << 3)],
+        (Op::Ret, [R(Reg::C)])        => vec![Byte(0xD8)],
+
+        (Op::Rst, [Imm(e, _)]) => match out.eval(e) {
+            Some(Value::Const(p)) if p & !0x38 == 0 => vec![Byte(0xC7 | p as u8)],
+            None                                    => vec![Byte(0xC7)],
+            _                                       => return Err("invalid restart address"),
+        },
+
+        (Op::In, [R(Reg::A), Mem(e)]) => vec![Byte(0xDB), Imm8(e)],
+        (Op::In, [Ind(Reg::C)]) if undoc => vec![Byte(0xED), Byte(0x70)],
+        (Op::In, [R(r), Ind(Reg::C)]) if plain(*r) => {
+            vec![Byte(0xED), Byte(0x40 | r.r().unwrap() << 3)]
+        },
+
+        (Op::Out, [Mem(e), R(Reg::A)]) => vec![Byte(0xD3), Imm8(e)],
+        (Op::Out, [Ind(Reg::C), R(r)]) if plain(*r) => {
+            vec![Byte(0xED), Byte(0x41 | r.r().unwrap() << 3)]
+        },
+        (Op::Out, [Ind(Reg::C), Imm(e, _)]) if undoc => match out.eval(e) {
+            Some(Value::Const(0)) | None => vec![Byte(0xED), Byte(0x71)],
+            _                            => return Err(INVALID),
+        },
+
+        _ => return Err(INVALID),
+    };
+
+    Ok(parts)
+}
+
+/// Returns whether `r` is an 8-bit register other than an index register
+/// half.
+fn plain(r: Reg) -> bool {
+    r.r().is_some() && !r.is_half()
+}
+
+/// Returns the encoding `[prefix] opcode [disp]`, taking the displacement
+/// from `mem` if it is an indexed memory operand.
+fn prefixed<'a>(prefix: Option<u8>, opcode: u8, mem: &Loc<'a>) -> Vec<Part<'a>> {
+    let mut parts = vec![];
+    parts.extend(prefix.map(Byte));
+    parts.push(Byte(opcode));
+    parts.extend(mem.disp.map(Disp));
+    parts
+}
+
+/// Returns the encoding of a rotate, shift, or bit instruction with the
+/// given second opcode byte.  An indexed form places the displacement before
+/// the opcode byte.
+fn cb<'a>(opcode: u8, dst: &Loc<'a>) -> Result<Vec<Part<'a>>, &'static str> {
+    match (dst.prefix, dst.disp) {
+        _ if dst.half           => Err("invalid operands"),
+        (Some(prefix), Some(d)) => Ok(vec![Byte(prefix), Byte(0xCB), Disp(d), Byte(opcode)]),
+        _                       => Ok(vec![Byte(0xCB), Byte(opcode)]),
+    }
+}
+
+/// Returns the prefix of an instruction that selects the two 8-bit operands
+/// `a` and `b`, if they can appear together.
+fn pair(a: &Loc, b: &Loc) -> Result<Option<u8>, &'static str> {
+    const INVALID: &str = "invalid operands";
+
+    if a.is_mem() && b.is_mem() {
+        return Err(INVALID);
+    }
+
+    // An index prefix changes h and l to index register halves, and changes
+    // [hl] to an indexed memory operand, except in the other operand of an
+    // indexed memory operand
+    let (x, y) = if b.half { (b, a) } else { (a, b) };
+
+    if x.half && (y.is_mem() || y.prefix.is_none() && (4..=5).contains(&y.code)) {
+        return Err(INVALID);
+    }
+    if x.half && y.half && x.prefix != y.prefix {
+        return Err(INVALID);
+    }
+
+    Ok(a.prefix.or(b.prefix))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Emits a field of the given `kind` holding the value of `expr`, or records
+/// a relocation if the linker must compute the value.
+fn put(expr: &Expr<Span>, kind: RelocKind, out: &mut dyn Emitter) {
+    let field = match out.eval(expr) {
+        Some(Value::Const(v)) => match (kind.apply)(v, 0) {
+            Ok(field) => field,
+            Err(msg)  => { out.error(*expr.data(), &msg); 0 },
+        },
+        Some(Value::Reloc(_)) => {
+            out.reloc(expr, kind);
+            0
+        },
+        None => 0,
+    };
+    out.emit(&field.to_le_bytes()[..kind.size]);
+}
+
+/// Emits the offset of a relative jump to `target` from the end of the
+/// instruction, which is `size` bytes long.
+fn rel(span: Span, target: &Expr<Span>, size: i64, out: &mut dyn Emitter) {
+    let value = match out.eval(target) {
+        Some(value) => pc_rel_value(out, span, value, size),
+        None        => return out.emit(&[0]),
+    };
+
+    let field = match value {
+        Value::Const(v) => match (REL8.apply)(v, 0) {
+            Ok(field) => field as u8,
+            Err(msg)  => { out.error(*target.data(), &msg); 0 },
+        },
+        Value::Reloc(_) => {
+            out.reloc(&pc_rel_expr(target, size), REL8);
+            0
+        },
+    };
+    out.emit(&[field]);
+}
+
+// ----------------------------------------------------------------------------
+
+fn operand(arg: &Arg<Span>, undoc: bool, out: &mut dyn Emitter) -> Option<Operand> {
+    let expr = match *arg {
+        Arg::Expr(ref e) => e,
+        Arg::Unknown(span) => {
+            out.error(span, "expected: operand");
+            return None;
+        },
+    };
+
+    match *expr {
+        Expr::Ident(span, name) => match register(&out.names()[name]) {
+            Some(r) if r.is_half() && !undoc => {
+                out.error(span, "undocumented register; use target z80-undoc");
+                None
+            },
+            Some(r) => Some(Operand::Reg(r)),
+            None    => Some(Operand::Imm(expr.clone(), condition(out, name))),
+        },
+        Expr::Deref(span, ref inner, false) => memory(span, inner, out),
+        _ => Some(Operand::Imm(expr.clone(), None)),
+    }
+}
+
+/// Parses the contents of a memory operand `[...]`.
+fn memory(span: Span, inner: &Expr<Span>, out: &mut dyn Emitter) -> Option<Operand> {
+    let mut terms = vec![];
+    flatten_sum(inner, false, &mut terms);
+
+    let mut base = None;
+    let mut rest = vec![];
+
+    for (neg, term) in terms {
+        let reg = match *term {
+            Expr::Ident(_, name) => register(&out.names()[name]),
+            _                    => None,
+        };
+        match reg {
+            Some(r) if !neg && base.is_none() => base = Some(r),
+            Some(_) => {
+                out.error(*term.data(), "invalid memory operand");
+                return None;
+            },
+            None => rest.push((neg, term)),
+        }
+    }
+
+    match (base, sum(&rest)) {
+        (None, Some(addr)) => Some(Operand::Mem(addr)),
+        (Some(r @ (Reg::Ix | Reg::Iy)), disp) => Some(Operand::Idx(r.prefix().unwrap(), disp)),
+        (Some(r @ (Reg::Bc | Reg::De | Reg::Hl | Reg::Sp | Reg::C)), None) => Some(Operand::Ind(r)),
+        _ => {
+            out.error(span, "invalid memory operand");
+            None
+        },
+    }
+}
+
+/// Returns the register with the given name, if any.
+fn register(name: &str) -> Option<Reg> {
+    let reg = match name.to_ascii_lowercase().as_str() {
+        "a"   => Reg::A,   "b"   => Reg::B,   "c"   => Reg::C,   "d"   => Reg::D,
+        "e"   => Reg::E,   "h"   => Reg::H,   "l"   => Reg::L,   "i"   => Reg::I,
+        "r"   => Reg::R,   "ixh" => Reg::Ixh, "ixl" => Reg::Ixl, "iyh" => Reg::Iyh,
+        "iyl" => Reg::Iyl, "bc"  => Reg::Bc,  "de"  => Reg::De,  "hl"  => Reg::Hl,
+        "sp"  => Reg::Sp,  "af"  => Reg::Af,  "ix"  => Reg::Ix,  "iy"  => Reg::Iy,
+        _     => return None,
+    };
+    Some(reg)
+}
+
+/// Returns a number that identifies the register with the given name, the
+/// same for each name of the register, if any.
+pub(super) fn register_key(name: &str) -> Option<u32> {
+    register(name).map(|r| r as u32)
+}
+
+/// Returns the code of the condition with the given name, if any.  The
+/// condition `c` is the register `c`.
+fn condition(out: &dyn Emitter, name: Name) -> Option<u8> {
+    let cc = match out.names()[name].to_ascii_lowercase().as_str() {
+        "nz" => 0, "z"  => 1, "nc" => 2,
+        "po" => 4, "pe" => 5, "p"  => 6, "m" => 7,
+        _    => return None,
+    };
+    Some(cc)
+}
@@ -0,0 +1,286 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Zilog Z80.
+//!
+//! Instructions use Zilog mnemonics.  Operands use ras syntax:
+//!
+//! | Operand                       | Zilog               | ras
+//! |:------------------------------|:--------------------|:------------------------
+//! | immediate                     | `ld a, 42`          | `ld a, 42`
+//! | register indirect             | `ld a, (hl)`        | `ld a, [hl]`
+//! | indexed                       | `ld a, (ix+5)`      | `ld a, [ix + 5]`
+//! | absolute                      | `ld a, ($1234)`     | `ld a, [x'1234]`
+//! | port                          | `in a, ($10)`       | `in a, [x'10]`
+//! | port by `c`                   | `out (c), a`        | `out [c], a`
+//! | jump indirect                 | `jp (hl)`           | `jp [hl]`
+//! | alternate register pair       | `ex af, af'`        | `ex af, af`
+//!
+//! In a jump, call, or relative jump, the operand is the target address.
+//! The `z80-undoc` target also accepts the undocumented instructions: the
+//! index register halves `ixh`, `ixl`, `iyh`, and `iyl`, `sll`, `in [c]`,
+//! and `out [c], 0`.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::asm::{Endian, RelocKind};
+use crate::lang::ast::{Dir, Span};
+use crate::name::{Name, NameTable};
+
+use super::spec::Table;
+use super::{Decoded, Emitter, Target};
+
+mod encode;
+mod spec;
+
+#[cfg(test)]
+mod tests;
+
+// ----------------------------------------------------------------------------
+
+/// Zilog Z80.
+#[derive(Debug)]
+pub struct Z80 {
+    name:  &'static str,
+    undoc: bool,
+    index: HashMap<Name, usize>,
+    spec:  Table,
+}
+
+impl Z80 {
+    /// Creates a new [`Z80`] target with the given `name`, interning its
+    /// mnemonics in `names`.  If `undoc` is true, the target accepts the
+    /// undocumented instructions.
+    pub fn new(names: &mut NameTable, name: &'static str, undoc: bool) -> Self {
+        let mut index = HashMap::new();
+
+        for (i, &(mnemonic, _)) in OPS.iter().enumerate() {
+            if mnemonic == "sll" && !undoc {
+                continue;
+            }
+            index.insert(names.add(mnemonic),                  i);
+            index.insert(names.add(&mnemonic.to_uppercase()), i);
+        }
+
+        let spec = Table::new(&spec::SPEC, names);
+
+        Self { name, undoc, index, spec }
+    }
+}
+
+impl Target for Z80 {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn endian(&self) -> Endian {
+        Endian::Little
+    }
+
+    fn align(&self) -> u64 {
+        1
+    }
+
+    fn reloc_kinds(&self) -> &'static [RelocKind] {
+        &[DISP8, REL8, RelocKind::INT8, RelocKind::INT16]
+    }
+
+    fn instruction(&self, name: Name) -> Option<usize> {
+        self.index.get(&name).copied()
+    }
+
+    fn register(&self, name: &str) -> Option<u32> {
+        encode::register_key(name)
+    }
+
+    fn encode(&self, insn: usize, stmt: &Dir<Span>, out: &mut dyn Emitter) {
+        encode::encode(OPS[insn].1, self.undoc, stmt, out);
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        addr:  u64,
+        label: &dyn Fn(u64) -> Option<String>,
+    ) -> Option<Decoded> {
+        let isa = if self.undoc { spec::UNDOC } else { 0 };
+        self.spec.decode(isa, bytes, addr, label)
+    }
+}
+
+/// Creates a Z80 target.
+pub fn new_z80(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Z80::new(names, "z80", false))
+}
+
+/// Creates a Z80 target that accepts undocumented instructions.
+pub fn new_z80_undoc(names: &mut NameTable) -> Rc<dyn Target> {
+    Rc::new(Z80::new(names, "z80-undoc", true))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Instruction kinds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    /// Instruction without operands, with the given encoding.
+    Fixed(&'static [u8]),
+
+    /// `ld`
+    Ld,
+
+    /// 8-bit arithmetic or logic, with the given base opcode.
+    Alu(u8),
+
+    /// `inc` (0) or `dec` (1).
+    IncDec(u8),
+
+    /// `push` (`0xC5`) or `pop` (`0xC1`).
+    Stack(u8),
+
+    /// `ex`
+    Ex,
+
+    /// `im`
+    Im,
+
+    /// Rotate or shift with a `cb` prefix, with the given base opcode.
+    Cb(u8),
+
+    /// `bit`, `res`, or `set`, with the given base opcode.
+    Bit(u8),
+
+    /// `jp`
+    Jp,
+
+    /// `jr`
+    Jr,
+
+    /// `djnz`
+    Djnz,
+
+    /// `call`
+    Call,
+
+    /// `ret`
+    Ret,
+
+    /// `rst`
+    Rst,
+
+    /// `in`
+    In,
+
+    /// `out`
+    Out,
+}
+
+/// Mnemonics and their instruction kinds.
+static OPS: &[(&str, Op)] = &[
+    ("nop",  Op::Fixed(&[0x00])),
+    ("halt", Op::Fixed(&[0x76])),
+    ("di",   Op::Fixed(&[0xF3])),
+    ("ei",   Op::Fixed(&[0xFB])),
+    ("daa",  Op::Fixed(&[0x27])),
+    ("cpl",  Op::Fixed(&[0x2F])),
+    ("ccf",  Op::Fixed(&[0x3F])),
+    ("scf",  Op::Fixed(&[0x37])),
+    ("rlca", Op::Fixed(&[0x07])),
+    ("rla",  Op::Fixed(&[0x17])),
+    ("rrca", Op::Fixed(&[0x0F])),
+    ("rra",  Op::Fixed(&[0x1F])),
+    ("exx",  Op::Fixed(&[0xD9])),
+    ("neg",  Op::Fixed(&[0xED, 0x44])),
+    ("reti", Op::Fixed(&[0xED, 0x4D])),
+    ("retn", Op::Fixed(&[0xED, 0x45])),
+    ("rld",  Op::Fixed(&[0xED, 0x6F])),
+    ("rrd",  Op::Fixed(&[0xED, 0x67])),
+    ("ldi",  Op::Fixed(&[0xED, 0xA0])),
+    ("ldir", Op::Fixed(&[0xED, 0xB0])),
+    ("ldd",  Op::Fixed(&[0xED, 0xA8])),
+    ("lddr", Op::Fixed(&[0xED, 0xB8])),
+    ("cpi",  Op::Fixed(&[0xED, 0xA1])),
+    ("cpir", Op::Fixed(&[0xED, 0xB1])),
+    ("cpd",  Op::Fixed(&[0xED, 0xA9])),
+    ("cpdr", Op::Fixed(&[0xED, 0xB9])),
+    ("ini",  Op::Fixed(&[0xED, 0xA2])),
+    ("inir", Op::Fixed(&[0xED, 0xB2])),
+    ("ind",  Op::Fixed(&[0xED, 0xAA])),
+    ("indr", Op::Fixed(&[0xED, 0xBA])),
+    ("outi", Op::Fixed(&[0xED, 0xA3])),
+    ("otir", Op::Fixed(&[0xED, 0xB3])),
+    ("outd", Op::Fixed(&[0xED, 0xAB])),
+    ("otdr", Op::Fixed(&[0xED, 0xBB])),
+    ("ld",   Op::Ld),
+    ("add",  Op::Alu(0x80)),
+    ("adc",  Op::Alu(0x88)),
+    ("sub",  Op::Alu(0x90)),
+    ("sbc",  Op::Alu(0x98)),
+    ("and",  Op::Alu(0xA0)),
+    ("xor",  Op::Alu(0xA8)),
+    ("or",   Op::Alu(0xB0)),
+    ("cp",   Op::Alu(0xB8)),
+    ("inc",  Op::IncDec(0)),
+    ("dec",  Op::IncDec(1)),
+    ("push", Op::Stack(0xC5)),
+    ("pop",  Op::Stack(0xC1)),
+    ("ex",   Op::Ex),
+    ("im",   Op::Im),
+    ("rlc",  Op::Cb(0x00)),
+    ("rrc",  Op::Cb(0x08)),
+    ("rl",   Op::Cb(0x10)),
+    ("rr",   Op::Cb(0x18)),
+    ("sla",  Op::Cb(0x20)),
+    ("sra",  Op::Cb(0x28)),
+    ("sll",  Op::Cb(0x30)),
+    ("srl",  Op::Cb(0x38)),
+    ("bit",  Op::Bit(0x40)),
+    ("res",  Op::Bit(0x80)),
+    ("set",  Op::Bit(0xC0)),
+    ("jp",   Op::Jp),
+    ("jr",   Op::Jr),
+    ("djnz", Op::Djnz),
+    ("call", Op::Call),
+    ("ret",  Op::Ret),
+    ("rst",  Op::Rst),
+    ("in",   Op::In),
+    ("out",  Op::Out),
+];
+
+// ----------------------------------------------------------------------------
+
+/// 8-bit signed index displacement.
+pub const DISP8: RelocKind = RelocKind { name: "z80-disp8", size: 1, apply: apply_disp8 };
+
+/// 8-bit signed relative jump offset.
+pub const REL8: RelocKind = RelocKind { name: "z80-rel8", size: 1, apply: apply_rel8 };
+
+fn apply_disp8(value: i64, _: u64) -> Result<u64, String> {
+    match value {
+        -128..=127 => Ok(value as u64 & 0xFF),
+        _          => Err(format!("index displacement {} out of range", value)),
+    }
+}
+
+fn apply_rel8(value: i64, _: u64) -> Result<u64, String> {
+    match value {
+        -128..=127 => Ok(value as u64 & 0xFF),
+        _          => Err(format!("relative jump offset {} out of range", value)),
+    }
+}
@@ -0,0 +1,393 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Instruction set specification.
+//!
+//! The specification describes the encoding of each instruction, with the
+//! undocumented ones as a feature of their own.  The target decodes
+//! instructions with it.  The encoder remains hand-written, as it chooses
+//! among register, immediate, and memory forms by the operands as written.
+//!
+//! A `dd` or `fd` prefix selects `ix` or `iy` by bit 5, which the `XY` and
+//! `XH` fields hold.  An indexed memory operand has a form without a
+//! displacement for a displacement of zero.
+
+use crate::asm::Endian;
+use crate::target::spec::{row, Field, Kind, Row, Spec};
+
+// ----------------------------------------------------------------------------
+
+/// Instruction set specification.
+pub static SPEC: Spec = Spec {
+    word:       1,
+    endian:     Endian::Little,
+    word_order: Endian::Big,
+    registers:  REGISTERS,
+    fields:     FIELDS,
+    rows:       ROWS,
+};
+
+/// Instruction set feature: undocumented instructions.
+pub const UNDOC: u32 = 1 << 0;
+
+/// Features that every target has.
+const BASE: u32 = 0;
+
+/// Registers, with 8-bit registers first by their encoding in an `r` field,
+/// in which 6 is a memory operand.
+static REGISTERS: &[&str] = &[
+    "b",   "c",   "d",   "e",   "h", "l", "", "a",
+    "bc",  "de",  "hl",  "sp",
+    "ix",  "iy",
+    "ixh", "ixl", "iyh", "iyl",
+];
+
+/// Returns a register field.
+const fn reg(name: &'static str, letters: &'static str, min: u16, max: u16) -> Field {
+    Field { name, letters, kind: Kind::Reg { min, max, step: 1 }, order: "", what: "", reloc: None }
+}
+
+/// Returns an integer field whose bits lie in the given order.
+const fn int(
+    name:    &'static str,
+    letters: &'static str,
+    min:     i64,
+    max:     i64,
+    order:   &'static str,
+) -> Field {
+    let kind = Kind::Int { min, max, invert: 0 };
+    Field { name, letters, kind, order, what: "value", reloc: None }
+}
+
+/// Bits of a 16-bit value, which the low byte holds first.
+const WORD: &str = "7:0|15:8";
+
+static FIELDS: &[Field] = &[
+    reg("R",  "r", 0,  7),
+    reg("S",  "s", 0,  7),
+    reg("SS", "q", 8,  11),
+    reg("XY", "x", 12, 13),
+    reg("XH", "h", 14, 17),
+    int("N",  "n", 0,    0xFF,   ""),
+    int("NN", "n", 0,    0xFFFF, WORD),
+    int("D",  "d", -128, 127,    ""),
+    int("B",  "b", 0,    7,      ""),
+    int("P",  "p", 0,    0x38,   "5:3"),
+    Field {
+        name:    "T",
+        letters: "t",
+        kind:    Kind::Abs { scale: 1 },
+        order:   WORD,
+        what:    "address",
+        reloc:   None,
+    },
+    Field {
+        name:    "E",
+        letters: "e",
+        kind:    Kind::Rel { origin: 2, scale: 1 },
+        order:   "",
+        what:    "offset",
+        reloc:   None,
+    },
+];
+
+static ROWS: &[Row] = &[
+    // 8-bit loads
+    row("ld",   "R, S",        "01rrrsss",                            BASE),
+    row("ld",   "R, N",        "00rrr110 nnnnnnnn",                   BASE),
+    row("ld",   "R, [hl]",     "01rrr110",                            BASE),
+    row("ld",   "[hl], S",     "01110sss",                            BASE),
+    row("ld",   "[hl], N",     "00110110 nnnnnnnn",                   BASE),
+    row("ld",   "[bc], a",     "00000010",                            BASE),
+    row("ld",   "[de], a",     "00010010",                            BASE),
+    row("ld",   "[NN], a",     "00110010 nnnnnnnn nnnnnnnn",          BASE),
+    row("ld",   "a, [bc]",     "00001010",                            BASE),
+    row("ld",   "a, [de]",     "00011010",                            BASE),
+    row("ld",   "a, [NN]",     "00111010 nnnnnnnn nnnnnnnn",          BASE),
+    row("ld",   "i, a",        "11101101 01000111",                   BASE),
+    row("ld",   "r, a",        "11101101 01001111",                   BASE),
+    row("ld",   "a, i",        "11101101 01010111",                   BASE),
+    row("ld",   "a, r",        "11101101 01011111",                   BASE),
+    row("ld",   "R, [XY]",     "11x11101 01rrr110 00000000",          BASE),
+    row("ld",   "R, [XY + D]", "11x11101 01rrr110 dddddddd",          BASE),
+    row("ld",   "[XY], S",     "11x11101 01110sss 00000000",          BASE),
+    row("ld",   "[XY + D], S", "11x11101 01110sss dddddddd",          BASE),
+    row("ld",   "[XY], N",     "11x11101 00110110 00000000 nnnnnnnn", BASE),
+    row("ld",   "[XY + D], N", "11x11101 00110110 dddddddd nnnnnnnn", BASE),
+
+    // 16-bit loads
+    row("ld",   "SS, NN",      "00qq0001 nnnnnnnn nnnnnnnn",          BASE),
+    row("ld",   "[NN], hl",    "00100010 nnnnnnnn nnnnnnnn",          BASE),
+    row("ld",   "hl, [NN]",    "00101010 nnnnnnnn nnnnnnnn",          BASE),
+    row("ld",   "[NN], bc",    "11101101 01000011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "[NN], de",    "11101101 01010011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "[NN], sp",    "11101101 01110011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "bc, [NN]",    "11101101 01001011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "de, [NN]",    "11101101 01011011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "sp, [NN]",    "11101101 01111011 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "sp, hl",      "11111001",                            BASE),
+    row("ld",   "XY, NN",      "11x11101 00100001 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "[NN], XY",    "11x11101 00100010 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "XY, [NN]",    "11x11101 00101010 nnnnnnnn nnnnnnnn", BASE),
+    row("ld",   "sp, XY",      "11x11101 11111001",                   BASE),
+    row("push", "bc",          "11000101",                            BASE),
+    row("push", "de",          "11010101",                            BASE),
+    row("push", "hl",          "11100101",                            BASE),
+    row("push", "af",          "11110101",                            BASE),
+    row("push", "XY",          "11x11101 11100101",                   BASE),
+    row("pop",  "bc",          "11000001",                            BASE),
+    row("pop",  "de",          "11010001",                            BASE),
+    row("pop",  "hl",          "11100001",                            BASE),
+    row("pop",  "af",          "11110001",                            BASE),
+    row("pop",  "XY",          "11x11101 11100001",                   BASE),
+
+    // Exchanges and block operations
+    row("ex",   "af, af",      "00001000",                            BASE),
+    row("ex",   "de, hl",      "11101011",                            BASE),
+    row("ex",   "[sp], hl",    "11100011",                            BASE),
+    row("ex",   "[sp], XY",    "11x11101 11100011",                   BASE),
+    row("exx",  "",            "11011001",                            BASE),
+    row("ldi",  "",            "11101101 10100000",                   BASE),
+    row("cpi",  "",            "11101101 10100001",                   BASE),
+    row("ini",  "",            "11101101 10100010",                   BASE),
+    row("outi", "",            "11101101 10100011",                   BASE),
+    row("ldd",  "",            "11101101 10101000",                   BASE),
+    row("cpd",  "",            "11101101 10101001",                   BASE),
+    row("ind",  "",            "11101101 10101010",                   BASE),
+    row("outd", "",            "11101101 10101011",                   BASE),
+    row("ldir", "",            "11101101 10110000",                   BASE),
+    row("cpir", "",            "11101101 10110001",                   BASE),
+    row("inir", "",            "11101101 10110010",                   BASE),
+    row("otir", "",            "11101101 10110011",                   BASE),
+    row("lddr", "",            "11101101 10111000",                   BASE),
+    row("cpdr", "",            "11101101 10111001",                   BASE),
+    row("indr", "",            "11101101 10111010",                   BASE),
+    row("otdr", "",            "11101101 10111011",                   BASE),
+
+    // 8-bit arithmetic and logic
+    row("add",  "a, S",        "10000sss",                            BASE),
+    row("add",  "a, [hl]",     "10000110",                            BASE),
+    row("add",  "a, N",        "11000110 nnnnnnnn",                   BASE),
+    row("add",  "a, [XY]",     "11x11101 10000110 00000000",          BASE),
+    row("add",  "a, [XY + D]", "11x11101 10000110 dddddddd",          BASE),
+    row("adc",  "a, S",        "10001sss",                            BASE),
+    row("adc",  "a, [hl]",     "10001110",                            BASE),
+    row("adc",  "a, N",        "11001110 nnnnnnnn",                   BASE),
+    row("adc",  "a, [XY]",     "11x11101 10001110 00000000",          BASE),
+    row("adc",  "a, [XY + D]", "11x11101 10001110 dddddddd",          BASE),
+    row("sub",  "S",           "10010sss",                            BASE),
+    row("sub",  "[hl]",        "10010110",                            BASE),
+    row("sub",  "N",           "11010110 nnnnnnnn",                   BASE),
+    row("sub",  "[XY]",        "11x11101 10010110 00000000",          BASE),
+    row("sub",  "[XY + D]",    "11x11101 10010110 dddddddd",          BASE),
+    row("sbc",  "a, S",        "10011sss",                            BASE),
+    row("sbc",  "a, [hl]",     "10011110",                            BASE),
+    row("sbc",  "a, N",        "11011110 nnnnnnnn",                   BASE),
+    row("sbc",  "a, [XY]",     "11x11101 10011110 00000000",          BASE),
+    row("sbc",  "a, [XY + D]", "11x11101 10011110 dddddddd",          BASE),
+    row("and",  "S",           "10100sss",                            BASE),
+    row("and",  "[hl]",        "10100110",                            BASE),
+    row("and",  "N",           "11100110 nnnnnnnn",                   BASE),
+    row("and",  "[XY]",        "11x11101 10100110 00000000",          BASE),
+    row("and",  "[XY + D]",    "11x11101 10100110 dddddddd",          BASE),
+    row("xor",  "S",           "10101sss",                            BASE),
+    row("xor",  "[hl]",        "10101110",                            BASE),
+    row("xor",  "N",           "11101110 nnnnnnnn",                   BASE),
+    row("xor",  "[XY]",        "11x11101 10101110 00000000",          BASE),
+    row("xor",  "[XY + D]",    "11x11101 10101110 dddddddd",          BASE),
+    row("or",   "S",           "10110sss",                            BASE),
+    row("or",   "[hl]",        "10110110",                            BASE),
+    row("or",   "N",           "11110110 nnnnnnnn",                   BASE),
+    row("or",   "[XY]",        "11x11101 10110110 00000000",          BASE),
+    row("or",   "[XY + D]",    "11x11101 10110110 dddddddd",          BASE),
+    row("cp",   "S",           "10111sss",                            BASE),
+    row("cp",   "[hl]",        "10111110",                            BASE),
+    row("cp",   "N",           "11111110 nnnnnnnn",                   BASE),
+    row("cp",   "[XY]",        "11x11101 10111110 00000000",          BASE),
+    row("cp",   "[XY + D]",    "11x11101 10111110 dddddddd",          BASE),
+    row("inc",  "R",           "00rrr100",                            BASE),
+    row("inc",  "[hl]",        "00110100",                            BASE),
+    row("inc",  "[XY]",        "11x11101 00110100 00000000",          BASE),
+    row("inc",  "[XY + D]",    "11x11101 00110100 dddddddd",          BASE),
+    row("dec",  "R",           "00rrr101",                            BASE),
+    row("dec",  "[hl]",        "00110101",                            BASE),
+    row("dec",  "[XY]",        "11x11101 00110101 00000000",          BASE),
+    row("dec",  "[XY + D]",    "11x11101 00110101 dddddddd",          BASE),
+
+    // General-purpose arithmetic and control
+    row("daa",  "",            "00100111",                            BASE),
+    row("cpl",  "",            "00101111",                            BASE),
+    row("ccf",  "",            "00111111",                            BASE),
+    row("scf",  "",            "00110111",                            BASE),
+    row("nop",  "",            "00000000",                            BASE),
+    row("halt", "",            "01110110",                            BASE),
+    row("di",   "",            "11110011",                            BASE),
+    row("ei",   "",            "11111011",                            BASE),
+    row("neg",  "",            "11101101 01000100",                   BASE),
+    row("im",   "0",           "11101101 01000110",                   BASE),
+    row("im",   "1",           "11101101 01010110",                   BASE),
+    row("im",   "2",           "11101101 01011110",                   BASE),
+
+    // 16-bit arithmetic
+    row("add",  "hl, SS",      "00qq1001",                            BASE),
+    row("adc",  "hl, SS",      "11101101 01qq1010",                   BASE),
+    row("sbc",  "hl, SS",      "11101101 01qq0010",                   BASE),
+    row("add",  "XY, bc",      "11x11101 00001001",                   BASE),
+    row("add",  "XY, de",      "11x11101 00011001",                   BASE),
+    row("add",  "XY, sp",      "11x11101 00111001",                   BASE),
+    row("add",  "ix, ix",      "11011101 00101001",                   BASE),
+    row("add",  "iy, iy",      "11111101 00101001",                   BASE),
+    row("inc",  "SS",          "00qq0011",                            BASE),
+    row("inc",  "XY",          "11x11101 00100011",                   BASE),
+    row("dec",  "SS",          "00qq1011",                            BASE),
+    row("dec",  "XY",          "11x11101 00101011",                   BASE),
+
+    // Rotates and shifts
+    row("rlca", "",            "00000111",                            BASE),
+    row("rrca", "",            "00001111",                            BASE),
+    row("rla",  "",            "00010111",                            BASE),
+    row("rra",  "",            "00011111",                            BASE),
+    row("rld",  "",            "11101101 01101111",                   BASE),
+    row("rrd",  "",            "11101101 01100111",                   BASE),
+    row("rlc",  "S",           "11001011 00000sss",                   BASE),
+    row("rlc",  "[hl]",        "11001011 00000110",                   BASE),
+    row("rlc",  "[XY]",        "11x11101 11001011 00000000 00000110", BASE),
+    row("rlc",  "[XY + D]",    "11x11101 11001011 dddddddd 00000110", BASE),
+    row("rrc",  "S",           "11001011 00001sss",                   BASE),
+    row("rrc",  "[hl]",        "11001011 00001110",                   BASE),
+    row("rrc",  "[XY]",        "11x11101 11001011 00000000 00001110", BASE),
+    row("rrc",  "[XY + D]",    "11x11101 11001011 dddddddd 00001110", BASE),
+    row("rl",   "S",           "11001011 00010sss",                   BASE),
+    row("rl",   "[hl]",        "11001011 00010110",                   BASE),
+    row("rl",   "[XY]",        "11x11101 11001011 00000000 00010110", BASE),
+    row("rl",   "[XY + D]",    "11x11101 11001011 dddddddd 00010110", BASE),
+    row("rr",   "S",           "11001011 00011sss",                   BASE),
+    row("rr",   "[hl]",        "11001011 00011110",                   BASE),
+    row("rr",   "[XY]",        "11x11101 11001011 00000000 00011110", BASE),
+    row("rr",   "[XY + D]",    "11x11101 11001011 dddddddd 00011110", BASE),
+    row("sla",  "S",           "11001011 00100sss",                   BASE),
+    row("sla",  "[hl]",        "11001011 00100110",                   BASE),
+    row("sla",  "[XY]",        "11x11101 11001011 00000000 00100110", BASE),
+    row("sla",  "[XY + D]",    "11x11101 11001011 dddddddd 00100110", BASE),
+    row("sra",  "S",           "11001011 00101sss",                   BASE),
+    row("sra",  "[hl]",        "11001011 00101110",                   BASE),
+    row("sra",  "[XY]",        "11x11101 11001011 00000000 00101110", BASE),
+    row("sra",  "[XY + D]",    "11x11101 11001011 dddddddd 00101110", BASE),
+    row("sll",  "S",           "11001011 00110sss",                   UNDOC),
+    row("sll",  "[hl]",        "11001011 00110110",                   UNDOC),
+    row("sll",  "[XY]",        "11x11101 11001011 00000000 00110110", UNDOC),
+    row("sll",  "[XY + D]",    "11x11101 11001011 dddddddd 00110110", UNDOC),
+    row("srl",  "S",           "11001011 00111sss",                   BASE),
+    row("srl",  "[hl]",        "11001011 00111110",                   BASE),
+    row("srl",  "[XY]",        "11x11101 11001011 00000000 00111110", BASE),
+    row("srl",  "[XY + D]",    "11x11101 11001011 dddddddd 00111110", BASE),
+
+    // Bit set, reset, and test
+    row("bit",  "B, S",        "11001011 01bbbsss",                   BASE),
+    row("bit",  "B, [hl]",     "11001011 01bbb110",                   BASE),
+    row("bit",  "B, [XY]",     "11x11101 11001011 00000000 01bbb110", BASE),
+    row("bit",  "B, [XY + D]", "11x11101 11001011 dddddddd 01bbb110", BASE),
+    row("res",  "B, S",        "11001011 10bbbsss",                   BASE),
+    row("res",  "B, [hl]",     "11001011 10bbb110",                   BASE),
+    row("res",  "B, [XY]",     "11x11101 11001011 00000000 10bbb110", BASE),
+    row("res",  "B, [XY + D]", "11x11101 11001011 dddddddd 10bbb110", BASE),
+    row("set",  "B, S",        "11001011 11bbbsss",                   BASE),
+    row("set",  "B, [hl]",     "11001011 11bbb110",                   BASE),
+    row("set",  "B, [XY]",     "11x11101 11001011 00000000 11bbb110", BASE),
+    row("set",  "B, [XY + D]", "11x11101 11001011 dddddddd 11bbb110", BASE),
+
+    // Jumps, calls, and returns
+    row("jp",   "T",           "11000011 tttttttt tttttttt",          BASE),
+    row("jp",   "nz, T",       "11000010 tttttttt tttttttt",          BASE),
+    row("jp",   "z, T",        "11001010 tttttttt tttttttt",          BASE),
+    row("jp",   "nc, T",       "11010010 tttttttt tttttttt",          BASE),
+    row("jp",   "c, T",        "11011010 tttttttt tttttttt",          BASE),
+    row("jp",   "po, T",       "11100010 tttttttt tttttttt",          BASE),
+    row("jp",   "pe, T",       "11101010 tttttttt tttttttt",          BASE),
+    row("jp",   "p, T",        "11110010 tttttttt tttttttt",          BASE),
+    row("jp",   "m, T",        "11111010 tttttttt tttttttt",          BASE),
+    row("jp",   "[hl]",        "11101001",                            BASE),
+    row("jp",   "[XY]",        "11x11101 11101001",                   BASE),
+    row("jr",   "E",           "00011000 eeeeeeee",                   BASE),
+    row("jr",   "nz, E",       "00100000 eeeeeeee",                   BASE),
+    row("jr",   "z, E",        "00101000 eeeeeeee",                   BASE),
+    row("jr",   "nc, E",       "00110000 eeeeeeee",                   BASE),
+    row("jr",   "c, E",        "00111000 eeeeeeee",                   BASE),
+    row("djnz", "E",           "00010000 eeeeeeee",                   BASE),
+    row("call", "T",           "11001101 tttttttt tttttttt",          BASE),
+    row("call", "nz, T",       "11000100 tttttttt tttttttt",          BASE),
+    row("call", "z, T",        "11001100 tttttttt tttttttt",          BASE),
+    row("call", "nc, T",       "11010100 tttttttt tttttttt",          BASE),
+    row("call", "c, T",        "11011100 tttttttt tttttttt",          BASE),
+    row("call", "po, T",       "11100100 tttttttt tttttttt",          BASE),
+    row("call", "pe, T",       "11101100 tttttttt tttttttt",          BASE),
+    row("call", "p, T",        "11110100 tttttttt tttttttt",          BASE),
+    row("call", "m, T",        "11111100 tttttttt tttttttt",          BASE),
+    row("ret",  "",            "11001001",                            BASE),
+    row("ret",  "nz",          "11000000",                            BASE),
+    row("ret",  "z",           "11001000",                            BASE),
+    row("ret",  "nc",          "11010000",                            BASE),
+    row("ret",  "c",           "11011000",                            BASE),
+    row("ret",  "po",          "11100000",                            BASE),
+    row("ret",  "pe",          "11101000",                            BASE),
+    row("ret",  "p",           "11110000",                            BASE),
+    row("ret",  "m",           "11111000",                            BASE),
+    row("reti", "",            "11101101 01001101",                   BASE),
+    row("retn", "",            "11101101 01000101",                   BASE),
+    row("rst",  "P",           "11ppp111",                            BASE),
+
+    // Input and output
+    row("in",   "a, [N]",      "11011011 nnnnnnnn",                   BASE),
+    row("in",   "R, [c]",      "11101101 01rrr000",                   BASE),
+    row("in",   "[c]",         "11101101 01110000",                   UNDOC),
+    row("out",  "[N], a",      "11010011 nnnnnnnn",                   BASE),
+    row("out",  "[c], S",      "11101101 01sss001",                   BASE),
+    row("out",  "[c], 0",      "11101101 01110001",                   UNDOC),
+
+    // Undocumented index register halves
+    row("ld",   "b, XH",       "11h11101 0100010h",                   UNDOC),
+    row("ld",   "c, XH",       "11h11101 0100110h",                   UNDOC),
+    row("ld",   "d, XH",       "11h11101 0101010h",                   UNDOC),
+    row("ld",   "e, XH",       "11h11101 0101110h",                   UNDOC),
+    row("ld",   "a, XH",       "11h11101 0111110h",                   UNDOC),
+    row("ld",   "XH, b",       "11h11101 0110h000",                   UNDOC),
+    row("ld",   "XH, c",       "11h11101 0110h001",                   UNDOC),
+    row("ld",   "XH, d",       "11h11101 0110h010",                   UNDOC),
+    row("ld",   "XH, e",       "11h11101 0110h011",                   UNDOC),
+    row("ld",   "XH, a",       "11h11101 0110h111",                   UNDOC),
+    row("ld",   "ixh, ixh",    "11011101 01100100",                   UNDOC),
+    row("ld",   "ixh, ixl",    "11011101 01100101",                   UNDOC),
+    row("ld",   "ixl, ixh",    "11011101 01101100",                   UNDOC),
+    row("ld",   "ixl, ixl",    "11011101 01101101",                   UNDOC),
+    row("ld",   "iyh, iyh",    "11111101 01100100",                   UNDOC),
+    row("ld",   "iyh, iyl",    "11111101 01100101",                   UNDOC),
+    row("ld",   "iyl, iyh",    "11111101 01101100",                   UNDOC),
+    row("ld",   "iyl, iyl",    "11111101 01101101",                   UNDOC),
+    row("ld",   "XH, N",       "11h11101 0010h110 nnnnnnnn",          UNDOC),
+    row("inc",  "XH",          "11h11101 0010h100",                   UNDOC),
+    row("dec",  "XH",          "11h11101 0010h101",                   UNDOC),
+    row("add",  "a, XH",       "11h11101 1000010h",                   UNDOC),
+    row("adc",  "a, XH",       "11h11101 1000110h",                   UNDOC),
+    row("sub",  "XH",          "11h11101 1001010h",                   UNDOC),
+    row("sbc",  "a, XH",       "11h11101 1001110h",                   UNDOC),
+    row("and",  "XH",          "11h11101 1010010h",                   UNDOC),
+    row("xor",  "XH",          "11h11101 1010110h",                   UNDOC),
+    row("or",   "XH",          "11h11101 1011010h",                   UNDOC),
+    row("cp",   "XH",          "11h11101 1011110h",                   UNDOC),
+
+];
@@ -0,0 +1,279 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding tests.
+
+use crate::link::{link, Layout};
+use crate::name::NameTable;
+use crate::session::Session;
+use crate::target::find;
+use crate::target::testing::{Bench, bytes};
+
+/// Assembles each line at address `x'200`.
+const BENCH: Bench<u8> = Bench { origin: 0x200, split: bytes };
+
+#[test]
+fn loads() {
+    BENCH.check("z80", &[
+        ("nop",                                 &[0x00]),
+        ("HALT",                                &[0x76]),
+        ("ld a, b",                             &[0x78]),
+        ("ld b, a",                             &[0x47]),
+        ("ld [hl], a",                          &[0x77]),
+        ("ld a, [hl]",                          &[0x7E]),
+        ("ld a, 42",                            &[0x3E, 0x2A]),
+        ("ld [hl], 5",                          &[0x36, 0x05]),
+        ("ld a, [ix + 5]",                      &[0xDD, 0x7E, 0x05]),
+        ("ld [iy - 2], c",                      &[0xFD, 0x71, 0xFE]),
+        ("ld [ix], 7",                          &[0xDD, 0x36, 0x00, 0x07]),
+        ("ld h, [ix + 1]",                      &[0xDD, 0x66, 0x01]),
+        ("ld a, [bc]",                          &[0x0A]),
+        ("ld [de], a",                          &[0x12]),
+        ("ld a, [x'1234]",                      &[0x3A, 0x34, 0x12]),
+        ("ld [x'1234], a",                      &[0x32, 0x34, 0x12]),
+        ("ld a, i",                             &[0xED, 0x57]),
+        ("ld r, a",                             &[0xED, 0x4F]),
+        ("ld hl, x'1234",                       &[0x21, 0x34, 0x12]),
+        ("ld sp, x'ffff",                       &[0x31, 0xFF, 0xFF]),
+        ("ld ix, x'1234",                       &[0xDD, 0x21, 0x34, 0x12]),
+        ("ld hl, [x'1234]",                     &[0x2A, 0x34, 0x12]),
+        ("ld de, [x'1234]",                     &[0xED, 0x5B, 0x34, 0x12]),
+        ("ld [x'1234], sp",                     &[0xED, 0x73, 0x34, 0x12]),
+        ("ld [x'1234], iy",                     &[0xFD, 0x22, 0x34, 0x12]),
+        ("ld sp, hl",                           &[0xF9]),
+        ("ld sp, ix",                           &[0xDD, 0xF9]),
+        ("push af",                             &[0xF5]),
+        ("pop bc",                              &[0xC1]),
+        ("push iy",                             &[0xFD, 0xE5]),
+        ("ex de, hl",                           &[0xEB]),
+        ("ex af, af",                           &[0x08]),
+        ("exx",                                 &[0xD9]),
+        ("ex [sp], hl",                         &[0xE3]),
+        ("ex [sp], ix",                         &[0xDD, 0xE3]),
+        ("ldir",                                &[0xED, 0xB0]),
+    ]);
+}
+
+#[test]
+fn arithmetic() {
+    BENCH.check("z80", &[
+        ("add a, b",                            &[0x80]),
+        ("add a, 1",                            &[0xC6, 0x01]),
+        ("sub 1",                               &[0xD6, 0x01]),
+        ("and [hl]",                            &[0xA6]),
+        ("xor a",                               &[0xAF]),
+        ("cp [ix + 3]",                         &[0xDD, 0xBE, 0x03]),
+        ("sbc a, c",                            &[0x99]),
+        ("or x'0f",                             &[0xF6, 0x0F]),
+        ("neg",                                 &[0xED, 0x44]),
+        ("add hl, hl",                          &[0x29]),
+        ("adc hl, de",                          &[0xED, 0x5A]),
+        ("sbc hl, sp",                          &[0xED, 0x72]),
+        ("add ix, ix",                          &[0xDD, 0x29]),
+        ("add iy, bc",                          &[0xFD, 0x09]),
+        ("inc a",                               &[0x3C]),
+        ("dec b",                               &[0x05]),
+        ("inc [hl]",                            &[0x34]),
+        ("dec [ix + 2]",                        &[0xDD, 0x35, 0x02]),
+        ("inc bc",                              &[0x03]),
+        ("dec sp",                              &[0x3B]),
+        ("inc ix",                              &[0xDD, 0x23]),
+        ("dec iy",                              &[0xFD, 0x2B]),
+        ("im 1",                                &[0xED, 0x56]),
+        ("im 2",                                &[0xED, 0x5E]),
+        ("rlc b",                               &[0xCB, 0x00]),
+        ("rr [hl]",                             &[0xCB, 0x1E]),
+        ("srl a",                               &[0xCB, 0x3F]),
+        ("sla [ix + 1]",                        &[0xDD, 0xCB, 0x01, 0x26]),
+        ("bit 7, a",                            &[0xCB, 0x7F]),
+        ("set 0, [hl]",                         &[0xCB, 0xC6]),
+        ("res 3, [iy + 4]",                     &[0xFD, 0xCB, 0x04, 0x9E]),
+    ]);
+}
+
+#[test]
+fn control() {
+    BENCH.check("z80", &[
+        ("jp x'1234",                           &[0xC3, 0x34, 0x12]),
+        ("jp nz, x'1234",                       &[0xC2, 0x34, 0x12]),
+        ("jp c, x'1234",                        &[0xDA, 0x34, 0x12]),
+        ("jp m, x'1234",                        &[0xFA, 0x34, 0x12]),
+        ("jp [hl]",                             &[0xE9]),
+        ("jp [ix]",                             &[0xDD, 0xE9]),
+        ("jr x'200",                            &[0x18, 0xFE]),
+        ("jr nz, x'210",                        &[0x20, 0x0E]),
+        ("jr c, x'200",                         &[0x38, 0xFE]),
+        ("jr z, x'281",                         &[0x28, 0x7F]),
+        ("djnz x'182",                          &[0x10, 0x80]),
+        ("call x'1234",                         &[0xCD, 0x34, 0x12]),
+        ("call z, x'1234",                      &[0xCC, 0x34, 0x12]),
+        ("ret",                                 &[0xC9]),
+        ("ret nc",                              &[0xD0]),
+        ("ret c",                               &[0xD8]),
+        ("reti",                                &[0xED, 0x4D]),
+        ("rst x'38",                            &[0xFF]),
+        ("rst 8",                               &[0xCF]),
+        ("in a, [x'10]",                        &[0xDB, 0x10]),
+        ("in b, [c]",                           &[0xED, 0x40]),
+        ("out [x'10], a",                       &[0xD3, 0x10]),
+        ("out [c], e",                          &[0xED, 0x59]),
+        ("jr end\nnop\nend:",                   &[0x18, 0x01, 0x00]),
+    ]);
+}
+
+#[test]
+fn undocumented() {
+    BENCH.check("z80-undoc", &[
+        ("ld a, ixh",                           &[0xDD, 0x7C]),
+        ("ld iyl, 5",                           &[0xFD, 0x2E, 0x05]),
+        ("ld ixh, ixl",                         &[0xDD, 0x65]),
+        ("add a, iyh",                          &[0xFD, 0x84]),
+        ("inc ixl",                             &[0xDD, 0x2C]),
+        ("sll b",                               &[0xCB, 0x30]),
+        ("in [c]",                              &[0xED, 0x70]),
+        ("out [c], 0",                          &[0xED, 0x71]),
+    ]);
+}
+
+#[test]
+fn relocatable() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("z80").unwrap());
+
+    let mut unit = session.assemble("test.s", "
+        start:  ld hl, msg
+                call print
+                jr start
+        print:  ret
+        msg:    .int8 0
+    ");
+
+    assert_eq!(session.error_count(), 0);
+
+    let program = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0);
+
+    assert_eq!(program.sections[0].data, [
+        0x21, 0x09, 0x00, 0xCD, 0x08, 0x00, 0x18, 0xF8, 0xC9, 0x00,
+    ]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(BENCH.error("z80", "ld a, ixh"),               "undocumented register; use target z80-undoc");
+    assert_eq!(BENCH.error("z80", "sll b"),                   "unknown instruction 'sll'");
+    assert_eq!(BENCH.error("z80", "in [c]"),                  "invalid operands");
+    assert_eq!(BENCH.error("z80", "jr x'300"),                "relative jump offset 254 out of range");
+    assert_eq!(BENCH.error("z80", "djnz x'100"),              "relative jump offset -258 out of range");
+    assert_eq!(BENCH.error("z80", "ld a, [ix + 200]"),        "index displacement 200 out of range");
+    assert_eq!(BENCH.error("z80", "ld [hl], [hl]"),           "invalid operands");
+    assert_eq!(BENCH.error("z80", "jr po, x'200"),            "invalid operands");
+    assert_eq!(BENCH.error("z80", "ld a, [hl + 1]"),          "invalid memory operand");
+    assert_eq!(BENCH.error("z80", "im 3"),                    "invalid interrupt mode");
+    assert_eq!(BENCH.error("z80", "rst 3"),                   "invalid restart address");
+    assert_eq!(BENCH.error("z80", "bit 8, a"),                "invalid bit number");
+    assert_eq!(BENCH.error("z80-undoc", "ld h, ixl"),         "invalid operands");
+    assert_eq!(BENCH.error("z80-undoc", "ld ixh, iyl"),       "invalid operands");
+    assert_eq!(BENCH.error("z80-undoc", "ld ixh, [ix + 1]"),  "invalid operands");
+}
+
+#[test]
+fn decoding() {
+    let label = |addr| (addr == 0x200).then(|| "start".to_string());
+
+    for (target, lines) in [
+        ("z80", &[
+            "nop",
+            "ld a, b",
+            "ld [hl], 5",
+            "ld a, [ix + 5]",
+            "ld [iy - 2], c",
+            "ld [ix], 7",
+            "ld h, [ix + 1]",
+            "ld a, [x'1234]",
+            "ld a, i",
+            "ld hl, x'1234",
+            "ld ix, x'1234",
+            "ld hl, [x'1234]",
+            "ld de, [x'1234]",
+            "ld [x'1234], iy",
+            "ld sp, ix",
+            "push af",
+            "pop iy",
+            "ex af, af",
+            "ex [sp], ix",
+            "ldir",
+            "add a, b",
+            "sub 1",
+            "cp [ix + 3]",
+            "sbc a, c",
+            "neg",
+            "adc hl, de",
+            "add iy, bc",
+            "dec [ix + 2]",
+            "inc ix",
+            "im 2",
+            "rr [hl]",
+            "sla [ix + 1]",
+            "bit 7, a",
+            "res 3, [iy + 4]",
+            "jp start",
+            "jp m, x'1234",
+            "jp [ix]",
+            "jr start",
+            "jr nz, x'210",
+            "djnz start",
+            "call z, x'1234",
+            "ret nc",
+            "reti",
+            "rst x'38",
+            "in a, [x'10]",
+            "out [c], e",
+        ][..]),
+        ("z80-undoc", &[
+            "ld a, ixh",
+            "ld iyl, 5",
+            "ld ixh, ixl",
+            "add a, iyh",
+            "sll b",
+            "in [c]",
+            "out [c], 0",
+        ][..]),
+    ] {
+        let decoder = (find(target).unwrap().new)(&mut NameTable::new());
+
+        for line in lines {
+            let (bytes, _) = BENCH.assemble(target, &line.replace("start", "x'200"));
+            let decoded    = decoder.decode(&bytes, 0x200, &label).unwrap();
+            assert_eq!(decoded.text, *line);
+            assert_eq!(decoded.size, bytes.len());
+        }
+    }
+
+    // Instructions that the target lacks do not decode
+    let z80 = (find("z80").unwrap().new)(&mut NameTable::new());
+    assert_eq!(z80.decode(&[0xDD, 0x7C], 0x200, &label), None);
+    assert_eq!(z80.decode(&[0xCB, 0x30], 0x200, &label), None);
+
+    // A prefix that changes nothing does not decode
+    assert_eq!(z80.decode(&[0xDD, 0x00], 0x200, &label), None);
+
+    // Truncated instructions do not decode
+    assert_eq!(z80.decode(&[0xC3, 0x34], 0x200, &label), None);
+}
@@ -0,0 +1,269 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test runner.
+//!
+//! The `test` command assembles its inputs with `.test` blocks included and
+//! links them.  Then, for each block, the runner loads the linked program
+//! into a fresh simulated memory, starts a fresh CPU at the beginning of the
+//! block, and executes instructions until the CPU reaches the end of the
+//! block.  Whenever the CPU reaches an `.expect` directive, the runner checks
+//! its assertion, reporting a failure at the location of the expression.
+//! The memory of a test also holds any RAM and devices that its `.map`
+//! directives request.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::asm::{eval, Context, Expect, MapKind, Unit, Value};
+use crate::lang::ast::*;
+use crate::link::Program;
+use crate::name::Name;
+use crate::session::{Level, Loc, Session};
+use crate::sim::{Cpu, Echo, Memory, Stop};
+
+#[cfg(test)]
+mod tests;
+
+/// Maximum count of instructions that a test can execute.
+const STEP_LIMIT: u64 = 1_000_000;
+
+/// Size in bytes of the stack that the runner provides to each test.
+const STACK_SIZE: u64 = 0x1_0000;
+
+// ----------------------------------------------------------------------------
+
+/// Result of a test.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Outcome {
+    /// Name of the test.
+    pub name: String,
+
+    /// Whether the test reached its end with all assertions true.
+    pub passed: bool,
+}
+
+/// Runs the tests of the given `units`, which the linker has linked into the
+/// given `program`.  Returns the outcome of each test, in order.
+pub fn run(units: &[Unit], program: &Program, session: &mut Session) -> Vec<Outcome> {
+    let target = match session.target() {
+        Some(target) => target.clone(),
+        None         => return vec![],
+    };
+
+    if target.simulator().is_none() {
+        let msg = format!("target '{}' does not support simulation", target.name());
+        session.report(Level::Error, None, msg);
+        return vec![];
+    }
+
+    // Place the stack after the program
+    let stack = program.sections
+        .iter()
+        .map(|s| s.base.max(s.load_base()) + s.size)
+        .max()
+        .unwrap_or(0)
+        .next_multiple_of(16);
+
+    let mut outcomes = vec![];
+
+    for (u, unit) in units.iter().enumerate() {
+        for test in &unit.tests {
+            let mut cpu = target.simulator().unwrap();
+            let mut mem = Memory::new(unit.endian);
+
+            mem.load(&program.sections);
+            mem.add_ram(stack, STACK_SIZE);
+
+            for map in &test.maps {
+                match map.kind {
+                    MapKind::Ram  => mem.add_ram(map.base, map.size),
+                    MapKind::Echo => {
+                        let echo = Rc::new(RefCell::new(Echo::default()));
+                        mem.map(map.base, map.size, echo);
+                    },
+                }
+            }
+
+            let base = unit.sections[test.section].base;
+            let end  = base.wrapping_add(test.end);
+
+            cpu.set_pc(base.wrapping_add(test.start));
+            cpu.set_register("sp", stack + STACK_SIZE);
+
+            let mut passed = true;
+            let mut steps  = 0;
+
+            loop {
+                let offset = cpu.pc().wrapping_sub(base);
+
+                for expect in test.expects.iter().filter(|e| e.offset == offset) {
+                    passed &= check(units, u, expect, &*cpu, &mut mem, session);
+                }
+
+                if cpu.pc() == end {
+                    break;
+                }
+
+                let result = match steps {
+                    STEP_LIMIT => Err(Stop::Limit(STEP_LIMIT)),
+                    _          => cpu.step(&mut mem),
+                };
+
+                if let Err(stop) = result {
+                    session.error(test.loc, format!(
+                        "test '{}' stopped before its end: {}", test.name, stop
+                    ));
+                    passed = false;
+                    break;
+                }
+
+                steps += 1;
+            }
+
+            outcomes.push(Outcome { name: test.name.clone(), passed });
+        }
+    }
+
+    outcomes
+}
+
+/// Checks the given assertion of the unit with index `unit`.  Returns whether
+/// the assertion is true, reporting an error if it is not.
+fn check(
+    units:   &[Unit],
+    unit:    usize,
+    expect:  &Expect,
+    cpu:     &dyn Cpu,
+    mem:     &mut Memory,
+    session: &mut Session,
+) -> bool {
+    let mut cx = CheckContext { units, unit, expect, cpu, mem, session };
+
+    match eval(&mut cx, &expect.expr).map(Value::provisional) {
+        Some(0) => (),
+        Some(_) => return true,
+        None    => return false,
+    }
+
+    // Show both sides of a failed comparison
+    let sides = match expect.expr {
+        Expr::Binary(_, op, ref lhs, ref rhs) if is_comparison(op) => {
+            eval(&mut cx, lhs).zip(eval(&mut cx, rhs))
+                .map(|(l, r)| (l.provisional(), r.provisional()))
+        },
+        _ => None,
+    };
+
+    let msg = match sides {
+        Some((lhs, rhs)) => format!(
+            "expectation failed: left side is {}, right side is {}", lhs, rhs
+        ),
+        None => "expectation failed".to_string(),
+    };
+
+    session.error(expect.loc, msg);
+    false
+}
+
+/// Returns whether the given operator is a comparison operator.
+fn is_comparison(op: BinOp) -> bool {
+    use BinOp::*;
+    matches!(op, Eq | NotEq | Less | More | LessEq | MoreEq)
+}
+
+/// Context for evaluation of assertions.
+///
+/// In an assertion, the name of a register denotes the value of the register,
+/// and `[addr]` denotes the register-sized value in memory at `addr`.  Both
+/// are sign-extended.  Other names denote symbols of the linked program.
+struct CheckContext<'a> {
+    units:   &'a [Unit],
+    unit:    usize,
+    expect:  &'a Expect,
+    cpu:     &'a dyn Cpu,
+    mem:     &'a mut Memory,
+    session: &'a mut Session,
+}
+
+impl CheckContext<'_> {
+    /// Sign-extends a register-sized value.
+    fn extend(&self, value: u64) -> i64 {
+        let shift = 64 - self.cpu.width() as u32 * 8;
+        ((value << shift) as i64) >> shift
+    }
+}
+
+impl Context for CheckContext<'_> {
+    fn symbol(&mut self, span: Span, name: Name) -> Option<Value> {
+        if let Some(value) = self.cpu.register(&self.session.names()[name]) {
+            return Some(Value::Const(self.extend(value)));
+        }
+
+        let unit = &self.units[self.unit];
+
+        // Prefer a symbol of this unit, qualifying a local label
+        let names = self.session.names_mut();
+        let local = if names[name].starts_with('.') {
+            let qualified = format!("{}{}", &names[self.expect.scope], &names[name]);
+            unit.symbols.get(names.add(&qualified)).filter(|s| s.is_defined())
+        } else {
+            None
+        };
+
+        // Otherwise, use a symbol that another unit exports
+        let found = local
+            .or_else(|| unit.symbols.get(name).filter(|s| s.is_defined()))
+            .map(|s| (unit, s))
+            .or_else(|| self.units.iter().find_map(|u| {
+                let s = u.symbols.get(name)?;
+                (s.is_defined() && s.scope >= Scope::Weak).then_some((u, s))
+            }));
+
+        match found {
+            Some((unit, symbol)) => {
+                let base = symbol.section.map_or(0, |s| unit.sections[s].base);
+                Some(Value::Const(base.wrapping_add(symbol.value) as i64))
+            },
+            None => {
+                let msg = format!("undefined symbol '{}'", &self.session.names()[name]);
+                self.error(span, &msg);
+                None
+            },
+        }
+    }
+
+    fn deref(&mut self, span: Span, addr: i64) -> Option<i64> {
+        match self.mem.read(addr as u64, self.cpu.width()) {
+            Ok(value) => Some(self.extend(value)),
+            Err(e)    => {
+                self.error(span, &e.to_string());
+                None
+            },
+        }
+    }
+
+    fn builtin(&self, name: Name) -> Option<fn(i64) -> i64> {
+        self.session.target()?.builtin(name)
+    }
+
+    fn error(&mut self, span: Span, msg: &str) {
+        let loc = Loc { file: self.expect.loc.file, span };
+        self.session.error(loc, msg);
+    }
+}
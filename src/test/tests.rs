@@ -0,0 +1,299 @@
+// This file is part of ras, an assembler.
+// Copyright 2022 Jeffrey Sharp
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+//
+// ras is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published
+// by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// ras is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See
+// the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ras.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test runner tests.
+
+use crate::link::{link, Layout};
+use crate::session::Session;
+use crate::target::find;
+
+use super::*;
+
+/// Assembles and links the given source for the given target with tests
+/// included, then runs the tests.
+fn run_source(target: &str, source: &str) -> (Vec<Outcome>, Session) {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_tests(true);
+    session.set_target(find(target).unwrap());
+
+    let mut unit = session.assemble("test.s", source);
+    let program  = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+
+    let outcomes = run(std::slice::from_mut(&mut unit), &program, &mut session);
+    (outcomes, session)
+}
+
+fn outcome(name: &str, passed: bool) -> Outcome {
+    Outcome { name: name.into(), passed }
+}
+
+/// Returns the line and message of each diagnostic.
+fn errors(session: &Session) -> Vec<(usize, &str)> {
+    session.diagnostics()
+        .iter()
+        .map(|d| (d.loc.map_or(0, |l| l.span.line), d.msg.as_str()))
+        .collect()
+}
+
+/// Routine under test.
+const ROUTINE: &str = "
+    double: add     a0, a0, a0
+            ret
+
+    .data
+    result: .int64  0
+    .code
+";
+
+#[test]
+fn pass() {
+    let source = format!("{}{}", ROUTINE, "
+        .test doubles
+            li      a0, 21
+            call    double
+            .expect a0 == 42
+            la      t0, result
+            sw      a0, [t0]
+            .expect [result] == 42 && [t0] == a0
+        .end
+
+        .test \"negative values\"
+            li      a0, -3
+            .expect a0 == -3
+            call    double
+            .expect a0 == -6 && a0 < 0
+        .end
+    ");
+
+    let (outcomes, session) = run_source("rv32i", &source);
+
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+    assert_eq!(outcomes, [outcome("doubles", true), outcome("negative values", true)]);
+
+    let (outcomes, session) = run_source("rv64i", &source);
+
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+    assert_eq!(outcomes, [outcome("doubles", true), outcome("negative values", true)]);
+}
+
+#[test]
+fn fail() {
+    let source = format!("{}{}", ROUTINE, "
+        .test fails
+            li      a0, 2
+            call    double
+            .expect a0 == 5
+            .expect a0
+            .expect a0 - 4
+        .end
+
+        .test passes
+            li      a0, 0
+            .expect a0 == 0
+        .end
+    ");
+
+    let (outcomes, session) = run_source("rv32i", &source);
+
+    assert_eq!(outcomes, [outcome("fails", false), outcome("passes", true)]);
+    assert_eq!(errors(&session), [
+        (12, "expectation failed: left side is 4, right side is 5"),
+        (14, "expectation failed"),
+    ]);
+
+    // The error spans the expression
+    let span = session.diagnostics()[0].loc.unwrap().span;
+    assert_eq!(&source[span.start..span.end], "a0 == 5");
+}
+
+/// Each test begins with fresh state, and an assertion within a loop holds
+/// each time the CPU reaches it.
+#[test]
+fn state() {
+    let (outcomes, session) = run_source("rv32i", "
+        .data
+        count:  .int32  0
+        .code
+
+        .test first
+                la      t0, count
+                lw      t1, [t0]
+                .expect t1 == 0 && t2 == 0
+                addi    t1, t1, 1
+                sw      t1, [t0]
+                li      t2, 3
+        loop:   .expect t2 > 0 && t2 <= 3
+                addi    t2, t2, -1
+                bnez    t2, loop
+                .expect [count] == 1
+        .end
+
+        .test second
+                .expect [count] == 0 && t2 == 0 && sp != 0
+        .end
+    ");
+
+    assert_eq!(outcomes, [outcome("first", true), outcome("second", true)]);
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+}
+
+#[test]
+fn stops() {
+    let (outcomes, session) = run_source("rv32i", "
+        .test breaks
+            ebreak
+            .expect 1
+        .end
+
+        .test loops
+        spin:
+            j       spin
+        .end
+
+        .test faults
+            li      t0, x'4000_0000
+            lw      t1, [t0]
+        .end
+
+        .test reads
+            .expect [x'4000_0000] == 0
+        .end
+    ");
+
+    assert_eq!(outcomes, [
+        outcome("breaks", false),
+        outcome("loops",  false),
+        outcome("faults", false),
+        outcome("reads",  false),
+    ]);
+    assert_eq!(errors(&session), [
+        ( 2, "test 'breaks' stopped before its end: breakpoint"),
+        ( 7, "test 'loops' stopped before its end: no stop after 1000000 instructions"),
+        (12, "test 'faults' stopped before its end: access to unmapped address x'40000000"),
+        (18, "access to unmapped address x'40000000"),
+    ]);
+}
+
+/// A test can map RAM and loopback devices, which only it sees.
+#[test]
+fn maps() {
+    let (outcomes, session) = run_source("rv32i", "
+        .test ram
+                .map    ram, x'4000_0000, 16
+                li      t0, x'4000_0000
+                li      t1, 5
+                sw      t1, [t0 + 12]
+                .expect [x'4000_000C] == 5
+        .end
+
+        .test echo
+                .map    echo, x'5000_0000, 4
+                .map    ram, x'4000_0000, 16
+                li      t0, x'5000_0000
+                li      t1, x'107
+                sb      t1, [t0]
+                li      t1, 9
+                sw      t1, [t0 + 2]
+                lbu     a0, [t0]
+                lw      a1, [t0]
+                lw      a2, [t0]
+                .expect a0 == 7 && a1 == 9 && a2 == 0
+                .expect [x'4000_000C] == 0
+        .end
+    ");
+
+    assert_eq!(outcomes, [outcome("ram", true), outcome("echo", true)]);
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+}
+
+/// Without tests included, the assembler skips test blocks.
+#[test]
+fn excluded() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_target(find("rv32i").unwrap());
+
+    let unit = session.assemble("test.s", "
+                nop
+        .test skipped
+                nop
+                .bogus
+        .end
+                nop
+    ");
+
+    assert_eq!(session.error_count(), 0, "{:?}", session.diagnostics());
+    assert_eq!(unit.sections.len(), 1);
+    assert_eq!(unit.sections[0].size, 8);
+    assert!(unit.tests.is_empty());
+}
+
+#[test]
+fn errors_in_source() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_tests(true);
+    session.set_target(find("rv32i").unwrap());
+
+    session.assemble("test.s", "
+        .expect a0 == 0
+        .end
+        .test
+        .end
+        .test a
+        .expect
+        .test b
+        .end
+        .map ram, 0, 16
+        .test c
+        .map rom, 0, 16
+        .map ram, 0
+        .map echo, 0, 0
+    ");
+
+    assert_eq!(errors(&session), [
+        ( 2, ".expect outside of .test block"),
+        ( 3, "unexpected .end"),
+        ( 4, "expected: test name"),
+        ( 5, "unexpected .end"),
+        ( 7, ".expect requires one argument"),
+        ( 8, ".test block cannot be nested"),
+        (10, ".map outside of .test block"),
+        (12, "expected: ram or echo"),
+        (13, "expected: ram or echo, address, and size"),
+        (14, "map size must be positive"),
+        (11, ".test block without .end"),
+    ]);
+}
+
+#[test]
+fn unsupported() {
+    let mut session = Session::new();
+    session.set_quiet(true);
+    session.set_tests(true);
+    session.set_target(find("avr5").unwrap());
+
+    let mut unit = session.assemble("test.s", ".test t\nnop\n.end\n");
+    let program  = link(std::slice::from_mut(&mut unit), &Layout::new(), &mut session);
+    let outcomes = run(std::slice::from_mut(&mut unit), &program, &mut session);
+
+    assert!(outcomes.is_empty());
+    assert_eq!(errors(&session), [(0, "target 'avr5' does not support simulation")]);
+}